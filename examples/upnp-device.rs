@@ -0,0 +1,115 @@
+/*!
+An emulated binary light, hosting a single `SwitchPower` service, that may be used to exercise
+control point code against a known-good device on the local machine. The device advertises itself
+over SSDP, answers `M-SEARCH` requests, implements the `GetTarget`, `SetTarget`, and `GetStatus`
+actions, and sends the current `Status` to all subscribers every ten seconds. As the light turns
+on and off instantly the target value is always the current status.
+
+```bash
+$ cargo run --example upnp-device -- 192.168.1.10
+```
+
+The optional argument is the local address to serve the device from, the default is `127.0.0.1`.
+*/
+
+use std::net::IpAddr;
+use std::thread::park;
+use std::time::Duration;
+use upnp_rs::control::UpnpError;
+use upnp_rs::description::scaffold::Scaffold;
+use upnp_rs::description::service::{Action, Argument, Direction, Spcd, StateVariable};
+use upnp_rs::description::well_known::{device_type, service_type};
+use upnp_rs::emulation::{from_device_with, Options};
+use upnp_rs::SpecVersion;
+
+const CONTROL_PATH: &str = "/SwitchPower/control";
+const EVENT_PATH: &str = "/SwitchPower/event";
+
+fn main() {
+    let address: IpAddr = std::env::args()
+        .nth(1)
+        .map(|address| address.parse().expect("invalid IP address"))
+        .unwrap_or_else(|| "127.0.0.1".parse().unwrap());
+
+    let (root, services) = Scaffold::new(device_type("BinaryLight", 1), "Emulated Light")
+        .with_model_name("upnp-device")
+        .with_service(service_type("SwitchPower", 1), switch_power())
+        .build();
+    let device = from_device_with(
+        root,
+        services,
+        Options {
+            spec_version: SpecVersion::V11,
+            address,
+            advertise_interval: Some(Duration::from_secs(30)),
+            event_interval: Some(Duration::from_secs(10)),
+            ..Default::default()
+        },
+    )
+    .expect("could not start device");
+
+    let action = |name: &str| format!("{}#{}", service_type("SwitchPower", 1), name);
+    device.add_action_handler(CONTROL_PATH, &action("GetTarget"), |events, _| {
+        Ok(vec![(
+            "RetTargetValue".to_string(),
+            events.get(EVENT_PATH, "Status").unwrap_or_default(),
+        )])
+    });
+    device.add_action_handler(CONTROL_PATH, &action("GetStatus"), |events, _| {
+        Ok(vec![(
+            "ResultStatus".to_string(),
+            events.get(EVENT_PATH, "Status").unwrap_or_default(),
+        )])
+    });
+    device.add_action_handler(CONTROL_PATH, &action("SetTarget"), |events, arguments| {
+        match arguments.iter().find(|(name, _)| name == "newTargetValue") {
+            Some((_, value)) if value == "0" || value == "1" => {
+                events.set(EVENT_PATH, "Status", value);
+                Ok(Vec::new())
+            }
+            Some(_) => Err(UpnpError::ArgumentValueInvalid),
+            None => Err(UpnpError::InvalidArgs),
+        }
+    });
+
+    println!("device description at {}", device.location());
+    park();
+}
+
+fn switch_power() -> Spcd {
+    let argument = |name: &str, direction: Direction, variable: &str| Argument {
+        name: name.to_string(),
+        direction,
+        return_value: false,
+        related_state_variable: variable.to_string(),
+    };
+    let variable = |name: &str, send_events: bool| StateVariable {
+        send_events,
+        multicast: false,
+        name: name.to_string(),
+        data_type: "boolean".to_string(),
+        default_value: Some("0".to_string()),
+        allowed_values: None,
+        moderation: None,
+    };
+    Spcd {
+        spec_version: SpecVersion::V11,
+        config_id: Some(1),
+        action_list: vec![
+            Action {
+                name: "SetTarget".to_string(),
+                argument_list: vec![argument("newTargetValue", Direction::In, "Target")],
+            },
+            Action {
+                name: "GetTarget".to_string(),
+                argument_list: vec![argument("RetTargetValue", Direction::Out, "Target")],
+            },
+            Action {
+                name: "GetStatus".to_string(),
+                argument_list: vec![argument("ResultStatus", Direction::Out, "Status")],
+            },
+        ],
+        service_state_table: vec![variable("Target", false), variable("Status", true)],
+        extensions: Vec::new(),
+    }
+}
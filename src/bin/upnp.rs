@@ -1,23 +1,54 @@
 use human_panic::setup_panic;
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 use tracing::info;
-use upnp_rs::common::interface::IP;
+use upnp_rs::common::date;
+use upnp_rs::common::http::fetch;
+use upnp_rs::common::interface::{find_interface, list_interfaces, IP};
+use upnp_rs::common::udn::Udn;
+use upnp_rs::common::uri::URL;
+use upnp_rs::control::codegen::generate;
+use upnp_rs::control::{ServiceClient, UpnpError};
+use upnp_rs::control_point::{Presence, Registry};
+use upnp_rs::description::device::{Device, DeviceRoot};
+use upnp_rs::description::service::{Direction, Spcd};
+use upnp_rs::description::{ServiceId, TypeID};
+use upnp_rs::discovery::listen::{Notification, NotifyListener, Options as ListenOptions};
 use upnp_rs::discovery::search::*;
-use upnp_rs::SpecVersion;
+use upnp_rs::error::{ControlError, Error};
+use upnp_rs::eventing::listener::{EventListener, Options as ListenerOptions};
+use upnp_rs::eventing::manager::{
+    Options as ManagerOptions, SubscriptionEvent, SubscriptionManager,
+};
+use upnp_rs::{build_info, SpecVersion};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
 #[derive(StructOpt)]
-#[structopt(name = "upnp", about = "UPnP simple qery/discovery tool.")]
+#[structopt(
+    name = "upnp",
+    about = "UPnP simple qery/discovery tool.",
+    global_settings = &[AppSettings::DisableVersion]
+)]
 struct CommandLine {
     /// The level of logging to perform, from off to trace; the default is off
     #[structopt(long, short = "v", parse(from_occurrences))]
     verbose: i8,
 
-    /// The network interface name to bind to; the default is all
+    /// Prints version information, with --verbose also prints the enabled features
+    #[structopt(long)]
+    version: bool,
+
+    /// The network interface to bind to, by name, index, or network (e.g. 192.168.1.0/24); the
+    /// default is all
     #[structopt(long)]
     interface: Option<String>,
 
@@ -30,7 +61,7 @@ struct CommandLine {
     spec_version: Option<String>,
 
     #[structopt(subcommand)]
-    cmd: Command,
+    cmd: Option<Command>,
 }
 
 #[derive(StructOpt)]
@@ -50,9 +81,92 @@ enum Command {
         /// The maximum wait time, in seconds, for devices to respond to multicast; the default is 2
         #[structopt(long, short = "w")]
         max_wait: Option<u8>,
+
+        /// The format of the results, one of markdown, json, table, or csv; the default is
+        /// markdown
+        #[structopt(long, short)]
+        output: Option<OutputFormat>,
+    },
+    /// Fetch and display a device description
+    Describe {
+        /// The URL of the device description, or the UDN of a device to search for
+        location: String,
+
+        /// Also fetch and display the description of each service
+        #[structopt(long)]
+        services: bool,
+
+        /// Display the description as JSON rather than text
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Invoke an action on a service and display the results
+    Invoke {
+        /// The control URL of the service
+        control_url: String,
+
+        /// The service type, for example urn:schemas-upnp-org:service:WANIPConnection:1
+        service_type: String,
+
+        /// The name of the action to invoke
+        action: String,
+
+        /// The in arguments for the action, each as name=value
+        arguments: Vec<String>,
+    },
+    /// Subscribe to the events published by a service and display each change
+    Subscribe {
+        /// The event subscription URL of the service
+        event_url: String,
+
+        /// The local address, reachable by the publisher, on which to receive events; the default
+        /// is the local address used to reach the publisher, with any free port
+        #[structopt(long, short)]
+        address: Option<SocketAddr>,
+
+        /// The subscription duration, in seconds, to request; 0 requests an infinite
+        /// subscription; the default is 1800
+        #[structopt(long, short)]
+        timeout: Option<u64>,
+
+        /// The fraction of the granted duration after which the subscription is renewed; the
+        /// default is 0.5
+        #[structopt(long, short)]
+        renew_fraction: Option<f64>,
     },
+    /// List the network interfaces that may be selected with --interface
+    Interfaces,
     /// Listen for device notifications
-    Listen,
+    Listen {
+        /// Maintain, and display, a table of the devices currently present rather than each
+        /// notification
+        #[structopt(long, short)]
+        follow: bool,
+
+        /// Display each notification as a single line of JSON
+        #[structopt(long, conflicts_with = "follow")]
+        json_lines: bool,
+    },
+    /// Generate the Rust source of a typed client for a service
+    Codegen {
+        /// The URL, or local file path, of the service description
+        scpd: String,
+
+        /// The service type, for example urn:schemas-upnp-org:service:WANIPConnection:1
+        service_type: String,
+
+        /// The file to write the source to; the default is standard output
+        #[structopt(long, short)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Markdown,
+    Json,
+    Table,
+    Csv,
 }
 
 #[derive(Debug)]
@@ -64,6 +178,26 @@ pub enum CLSearchTarget {
     ServiceType(String),
 }
 
+#[derive(serde::Serialize)]
+struct Description<'a> {
+    location: &'a URL,
+    description: &'a DeviceRoot,
+    services: BTreeMap<&'a ServiceId, Spcd>,
+}
+
+#[derive(serde::Serialize)]
+struct NotificationLine {
+    received: String,
+    nts: String,
+    nt: String,
+    usn: String,
+    location: Option<String>,
+    max_age: Option<u64>,
+    boot_id: Option<u32>,
+    config_id: Option<u32>,
+    source: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum CommandLineError {
     MissingParameter(String),
@@ -85,12 +219,12 @@ impl FromStr for CLSearchTarget {
             Ok(CLSearchTarget::All)
         } else if s == "root" {
             Ok(CLSearchTarget::RootDevice)
-        } else if s.starts_with("device:") {
-            Ok(CLSearchTarget::Device(s[7..].to_string()))
-        } else if s.starts_with("device-type:") {
-            Ok(CLSearchTarget::DeviceType(s[12..].to_string()))
-        } else if s.starts_with("service-type:") {
-            Ok(CLSearchTarget::ServiceType(s[13..].to_string()))
+        } else if let Some(device) = s.strip_prefix("device:") {
+            Ok(CLSearchTarget::Device(device.to_string()))
+        } else if let Some(device_type) = s.strip_prefix("device-type:") {
+            Ok(CLSearchTarget::DeviceType(device_type.to_string()))
+        } else if let Some(service_type) = s.strip_prefix("service-type:") {
+            Ok(CLSearchTarget::ServiceType(service_type.to_string()))
         } else {
             Err(CommandLineError::InvalidParameterValue(
                 "search_target".to_string(),
@@ -100,13 +234,30 @@ impl FromStr for CLSearchTarget {
     }
 }
 
-impl ToString for CommandLineError {
-    fn to_string(&self) -> String {
+impl FromStr for OutputFormat {
+    type Err = CommandLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(CommandLineError::InvalidParameterValue(
+                "output".to_string(),
+                s.to_string(),
+            )),
+        }
+    }
+}
+
+impl Display for CommandLineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         match self {
-            CommandLineError::MissingParameter(p) => format!("Parameter {} required", p),
-            CommandLineError::UnexpectedParameter(p) => format!("Parameter {} unnecessary", p),
+            CommandLineError::MissingParameter(p) => write!(f, "Parameter {} required", p),
+            CommandLineError::UnexpectedParameter(p) => write!(f, "Parameter {} unnecessary", p),
             CommandLineError::InvalidParameterValue(p, v) => {
-                format!("Value '{}' invalid for parameter {}", v, p)
+                write!(f, "Value '{}' invalid for parameter {}", v, p)
             }
         }
     }
@@ -117,22 +268,86 @@ pub fn main() {
 
     let args = CommandLine::from_args();
 
+    if args.version {
+        if args.verbose > 0 {
+            println!("{}", build_info());
+        } else {
+            println!("upnp {}", build_info().version);
+        }
+        return;
+    }
+
     init_tracing(args.verbose);
 
+    if let Some(selector) = &args.interface {
+        if let Err(e) = find_interface(selector) {
+            eprintln!(
+                "{}; use `upnp interfaces` to list the available interfaces",
+                e.source()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| e.to_string())
+            );
+            std::process::exit(1);
+        }
+    }
+
     match args.cmd {
-        Command::Search {
+        None => {
+            CommandLine::clap()
+                .print_help()
+                .expect("Could not write help text");
+            println!();
+        }
+        Some(Command::Search {
             search_target,
             domain,
             max_wait,
-        } => do_search(
+            output,
+        }) => do_search(
             parse_version(args.spec_version),
             args.interface,
             if args.use_ipv6 { IP::V6 } else { IP::V4 },
             search_target,
             domain,
             max_wait,
+            output,
+        ),
+        Some(Command::Describe {
+            location,
+            services,
+            json,
+        }) => do_describe(
+            parse_version(args.spec_version),
+            args.interface,
+            if args.use_ipv6 { IP::V6 } else { IP::V4 },
+            location,
+            services,
+            json,
+        ),
+        Some(Command::Invoke {
+            control_url,
+            service_type,
+            action,
+            arguments,
+        }) => do_invoke(control_url, service_type, action, arguments),
+        Some(Command::Subscribe {
+            event_url,
+            address,
+            timeout,
+            renew_fraction,
+        }) => do_subscribe(event_url, address, timeout, renew_fraction),
+        Some(Command::Interfaces) => do_interfaces(),
+        Some(Command::Listen { follow, json_lines }) => do_listen(
+            args.interface,
+            if args.use_ipv6 { IP::V6 } else { IP::V4 },
+            follow,
+            json_lines,
         ),
-        Command::Listen => do_listen(),
+        Some(Command::Codegen {
+            scpd,
+            service_type,
+            output,
+        }) => do_codegen(scpd, service_type, output),
     }
 }
 
@@ -173,11 +388,11 @@ fn init_tracing(verbosity: i8) {
 
 fn parse_version(version: Option<String>) -> SpecVersion {
     if let Some(s) = version {
-        if s == "1.0".to_string() {
+        if s == "1.0" {
             SpecVersion::V10
-        } else if s == "1.1".to_string() {
+        } else if s == "1.1" {
             SpecVersion::V11
-        } else if s == "2.0".to_string() {
+        } else if s == "2.0" {
             SpecVersion::V20
         } else {
             SpecVersion::default()
@@ -194,6 +409,7 @@ fn do_search(
     search_target: Option<CLSearchTarget>,
     domain: Option<String>,
     max_wait_time: Option<u8>,
+    output: Option<OutputFormat>,
 ) {
     let mut options = Options::default_for(spec_version);
     options.network_interface = bind_to_interface;
@@ -202,7 +418,20 @@ fn do_search(
         options.search_target = match search_target {
             CLSearchTarget::All => SearchTarget::All,
             CLSearchTarget::RootDevice => SearchTarget::RootDevice,
-            CLSearchTarget::Device(d) => SearchTarget::Device(d),
+            CLSearchTarget::Device(d) => {
+                let d = if d.starts_with("uuid:") {
+                    d
+                } else {
+                    format!("uuid:{}", d)
+                };
+                match Udn::from_str(&d) {
+                    Ok(udn) => SearchTarget::Device(udn),
+                    Err(e) => {
+                        eprintln!("search failed with error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
             CLSearchTarget::DeviceType(dt) => {
                 if let Some(domain) = domain {
                     SearchTarget::DomainDeviceType(domain, dt)
@@ -220,10 +449,12 @@ fn do_search(
         }
     }
     if let Some(max_wait_time) = max_wait_time {
-        options.max_wait_time = max_wait_time;
+        options.max_wait_time = Duration::from_secs(max_wait_time.into());
     }
-    println!(
-        r#"
+    let output = output.unwrap_or(OutputFormat::Markdown);
+    if output == OutputFormat::Markdown {
+        println!(
+            r#"
 # UPnP Search Response
     
 Search parameters
@@ -234,16 +465,24 @@ Search parameters
 * Wait time: {} seconds
     
 ## Results "#,
-        &options.spec_version,
-        &options.search_target,
-        match &options.network_interface {
-            None => "all".to_string(),
-            Some(s) => s.to_string(),
-        },
-        &options.max_wait_time
-    );
-    match search_once(options) {
-        Ok(responses) => {
+            &options.spec_version,
+            &options.search_target,
+            match &options.network_interface {
+                None => "all".to_string(),
+                Some(s) => s.to_string(),
+            },
+            &options.max_wait_time.as_secs()
+        );
+    }
+    let responses = match search_once(options) {
+        Ok(responses) => responses,
+        Err(error) => {
+            eprintln!("search failed with error: {:#?}", error);
+            std::process::exit(1);
+        }
+    };
+    match output {
+        OutputFormat::Markdown => {
             for response in responses.iter() {
                 println!("\n**[{}]({})**\n", response.service_name, response.location);
                 println!("* Product Version: {}", response.versions.product_version());
@@ -254,10 +493,540 @@ Search parameters
                 );
             }
         }
+        OutputFormat::Json => match serde_json::to_string_pretty(&responses) {
+            Ok(json) => println!("{}", json),
+            Err(error) => {
+                eprintln!("search failed with error: {}", error);
+                std::process::exit(1);
+            }
+        },
+        OutputFormat::Table | OutputFormat::Csv => {
+            let rows: Vec<[String; 5]> = responses
+                .iter()
+                .map(|response| {
+                    [
+                        response.service_name.to_string(),
+                        response.search_target.to_string(),
+                        response.location.to_string(),
+                        response.versions.to_string(),
+                        response.max_age.as_secs().to_string(),
+                    ]
+                })
+                .collect();
+            if output == OutputFormat::Csv {
+                print_csv(&SEARCH_COLUMNS, &rows);
+            } else {
+                print_table(&SEARCH_COLUMNS, &rows);
+            }
+        }
+    }
+}
+
+const SEARCH_COLUMNS: [&str; 5] = ["usn", "search_target", "location", "server", "max_age"];
+
+fn print_csv<const N: usize>(columns: &[&str; N], rows: &[[String; N]]) {
+    fn quoted(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+    println!("{}", columns.join(","));
+    for row in rows {
+        println!(
+            "{}",
+            row.iter()
+                .map(|value| quoted(value))
+                .collect::<Vec<String>>()
+                .join(",")
+        );
+    }
+}
+
+fn print_table<const N: usize>(columns: &[&str; N], rows: &[[String; N]]) {
+    let mut widths = columns.map(str::len);
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(value.len());
+        }
+    }
+    let print_line = |values: [String; N]| {
+        println!(
+            "{}",
+            values
+                .iter()
+                .zip(widths.iter())
+                .map(|(value, width)| format!("{:<width$}", value, width = width))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+        );
+    };
+    print_line(columns.map(str::to_string));
+    print_line(widths.map(|width| "-".repeat(width)));
+    for row in rows {
+        print_line(row.clone());
+    }
+}
+
+fn do_describe(
+    spec_version: SpecVersion,
+    bind_to_interface: Option<String>,
+    ip_version: IP,
+    location: String,
+    services: bool,
+    json: bool,
+) {
+    let location = if location.starts_with("uuid:") {
+        let udn = match Udn::from_str(&location) {
+            Ok(udn) => udn,
+            Err(e) => {
+                eprintln!("describe failed with error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let mut options = Options::default_for(spec_version);
+        options.network_interface = bind_to_interface;
+        options.network_version = Some(ip_version);
+        options.search_target = SearchTarget::Device(udn);
+        match search_once(options) {
+            Ok(responses) if !responses.is_empty() => responses[0].location.clone(),
+            Ok(_) => {
+                eprintln!("describe failed, no device responded for {}", location);
+                std::process::exit(1);
+            }
+            Err(error) => {
+                eprintln!("describe failed with error: {:#?}", error);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        URL::from_str(&location).unwrap()
+    };
+
+    let root: DeviceRoot = match fetch(location.to_string()) {
+        Ok(root) => root,
         Err(error) => {
-            println!("search failed with error: {:#?}", error);
+            eprintln!("describe failed with error: {:#?}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let mut descriptions: BTreeMap<&ServiceId, Spcd> = Default::default();
+    if services {
+        let base = root.base_url(&location);
+        for service in root
+            .device
+            .all_devices()
+            .into_iter()
+            .flat_map(|device| device.service_list.iter())
+        {
+            match base
+                .resolve(&service.scpd_url)
+                .map_err(Into::into)
+                .and_then(|url| fetch::<Spcd>(url.to_string()))
+            {
+                Ok(spcd) => {
+                    let _ = descriptions.insert(&service.service_id, spcd);
+                }
+                Err(error) => {
+                    eprintln!(
+                        "could not fetch service {}, error: {}",
+                        service.service_id, error
+                    );
+                }
+            }
+        }
+    }
+
+    if json {
+        let description = Description {
+            location: &location,
+            description: &root,
+            services: descriptions,
+        };
+        match serde_json::to_string_pretty(&description) {
+            Ok(json) => println!("{}", json),
+            Err(error) => {
+                eprintln!("describe failed with error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        println!("Location: {}", location);
+        println!("UPnP Version: {}", root.spec_version);
+        if let Some(config_id) = root.config_id {
+            println!("Config ID: {}", config_id);
+        }
+        print_device(&root.device, &descriptions, 0);
+    }
+}
+
+fn print_device(device: &Device, services: &BTreeMap<&ServiceId, Spcd>, depth: usize) {
+    let indent = "  ".repeat(depth);
+    println!("{}Device: {}", indent, device.friendly_name);
+    println!("{}  Type:         {}", indent, device.device_type);
+    println!("{}  UDN:          {}", indent, device.unique_device_name);
+    println!("{}  Manufacturer: {}", indent, device.manufacturer);
+    println!(
+        "{}  Model:        {} {}",
+        indent,
+        device.model_name,
+        device.model_number.as_deref().unwrap_or_default()
+    );
+    for service in &device.service_list {
+        println!("{}  Service: {}", indent, service.service_id);
+        println!("{}    Type:    {}", indent, service.service_type);
+        println!("{}    Control: {}", indent, service.control_url);
+        println!("{}    Events:  {}", indent, service.event_sub_url);
+        if let Some(spcd) = services.get(&service.service_id) {
+            for action in &spcd.action_list {
+                let arguments = |direction: Direction| {
+                    action
+                        .argument_list
+                        .iter()
+                        .filter(|argument| argument.direction == direction)
+                        .map(|argument| argument.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                };
+                println!(
+                    "{}    Action: {}({}) -> ({})",
+                    indent,
+                    action.name,
+                    arguments(Direction::In),
+                    arguments(Direction::Out)
+                );
+            }
+            for variable in &spcd.service_state_table {
+                println!(
+                    "{}    Variable: {}: {}{}",
+                    indent,
+                    variable.name,
+                    variable.data_type,
+                    if variable.send_events {
+                        " (evented)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+    }
+    for embedded in &device.device_list {
+        print_device(embedded, services, depth + 1);
+    }
+}
+
+fn do_invoke(control_url: String, service_type: String, action: String, arguments: Vec<String>) {
+    let service_type = match SearchTarget::from_str(&service_type)
+        .map_err(Error::from)
+        .and_then(TypeID::service_from)
+    {
+        Ok(service_type) => service_type,
+        Err(_) => {
+            eprintln!(
+                "{}",
+                CommandLineError::InvalidParameterValue("service_type".to_string(), service_type)
+            );
+            std::process::exit(1);
+        }
+    };
+    let mut in_arguments: Vec<(&str, &str)> = Default::default();
+    for argument in &arguments {
+        match argument.split_once('=') {
+            Some(pair) => in_arguments.push(pair),
+            None => {
+                eprintln!(
+                    "{}",
+                    CommandLineError::InvalidParameterValue(
+                        "arguments".to_string(),
+                        argument.to_string()
+                    )
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let client = ServiceClient::new(service_type, URL::from_str(&control_url).unwrap());
+    match client.invoke(&action, &in_arguments) {
+        Ok(out_arguments) => {
+            for (name, value) in out_arguments {
+                println!("{} = {}", name, value);
+            }
+        }
+        Err(Error::Control(ControlError::ActionFailed {
+            code, description, ..
+        })) => {
+            eprintln!(
+                "action {} failed with UPnP error {} ({}): {}",
+                action,
+                code,
+                UpnpError::from_code(code).description(),
+                description
+            );
+            std::process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("invoke failed with error: {}", error);
+            std::process::exit(1);
         }
     }
 }
 
-fn do_listen() {}
+fn do_subscribe(
+    event_url: String,
+    address: Option<SocketAddr>,
+    timeout: Option<u64>,
+    renew_fraction: Option<f64>,
+) {
+    const CALLBACK_PATH: &str = "events";
+
+    let event_url = URL::from_str(&event_url).unwrap();
+    let address = match address {
+        Some(address) => address,
+        None => SocketAddr::new(local_address_for(&event_url), 0),
+    };
+    let listener = match EventListener::bind(ListenerOptions {
+        address,
+        ..Default::default()
+    }) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("subscribe failed with error: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let events = listener.events_for(CALLBACK_PATH);
+
+    let mut options = ManagerOptions::default();
+    if let Some(timeout) = timeout {
+        options.timeout = if timeout == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(timeout))
+        };
+    }
+    if let Some(renew_fraction) = renew_fraction {
+        options.renew_fraction = renew_fraction;
+    }
+    let manager = match SubscriptionManager::new(options) {
+        Ok(manager) => manager,
+        Err(error) => {
+            eprintln!("subscribe failed with error: {}", error);
+            std::process::exit(1);
+        }
+    };
+    match manager.subscribe(&event_url, &[listener.callback_url(CALLBACK_PATH)]) {
+        Ok(subscription) => println!(
+            "subscribed {}, timeout {}",
+            subscription.sid(),
+            match subscription.timeout() {
+                None => "infinite".to_string(),
+                Some(timeout) => format!("{} seconds", timeout.as_secs()),
+            }
+        ),
+        Err(error) => {
+            eprintln!("subscribe failed with error: {}", error);
+            std::process::exit(1);
+        }
+    }
+
+    loop {
+        if let Some(event) = events.next_timeout(Duration::from_secs(1)) {
+            println!("{} {} #{}", date::now(), event.sid, event.seq);
+            for (name, value) in event.properties.properties() {
+                println!("    {} = {}", name, value);
+            }
+        }
+        while let Ok(event) = manager.events().try_recv() {
+            match event {
+                SubscriptionEvent::Renewed(subscription) => {
+                    println!("renewed {}", subscription.sid());
+                }
+                SubscriptionEvent::SubscriptionLost {
+                    subscription,
+                    reason,
+                } => {
+                    eprintln!("subscription {} lost: {}", subscription.sid(), reason);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Find the local address used to reach the host in `url`, the event listener must bind to an
+/// address the publisher can reach.
+///
+fn local_address_for(url: &URL) -> IpAddr {
+    url::Url::parse(&url.to_string())
+        .ok()
+        .and_then(|url| url.socket_addrs(|| None).ok())
+        .and_then(|addresses| addresses.first().copied())
+        .and_then(|remote| {
+            let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)).ok()?;
+            socket.connect(remote).ok()?;
+            socket.local_addr().ok()
+        })
+        .map(|local| local.ip())
+        .unwrap_or_else(|| Ipv4Addr::LOCALHOST.into())
+}
+
+fn do_codegen(scpd: String, service_type: String, output: Option<String>) {
+    let service_type = match TypeID::from_str(&service_type) {
+        Ok(service_type) if service_type.is_service() => service_type,
+        _ => {
+            eprintln!(
+                "{}",
+                CommandLineError::InvalidParameterValue("service_type".to_string(), service_type)
+            );
+            std::process::exit(1);
+        }
+    };
+    let description = if scpd.starts_with("http://") || scpd.starts_with("https://") {
+        fetch::<Spcd>(scpd)
+    } else {
+        std::fs::read_to_string(&scpd)
+            .map_err(Error::from)
+            .and_then(|xml| Spcd::from_str(&xml))
+    };
+    let source = match description {
+        Ok(description) => generate(&service_type, &description),
+        Err(error) => {
+            eprintln!("could not read service description, error: {}", error);
+            std::process::exit(1);
+        }
+    };
+    match output {
+        None => print!("{}", source),
+        Some(output) => {
+            if let Err(error) = std::fs::write(&output, source) {
+                eprintln!("could not write {}, error: {}", output, error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn do_interfaces() {
+    for interface in list_interfaces() {
+        let flags = [
+            (interface.flags.up, "up"),
+            (interface.flags.loopback, "loopback"),
+            (interface.flags.multicast, "multicast"),
+            (interface.flags.broadcast, "broadcast"),
+            (interface.flags.point_to_point, "point-to-point"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect::<Vec<&str>>()
+        .join(", ");
+        println!("{}: {} <{}>", interface.index, interface.name, flags);
+        for address in &interface.addresses {
+            println!("    {}", address);
+        }
+    }
+}
+
+fn do_listen(bind_to_interface: Option<String>, ip_version: IP, follow: bool, json_lines: bool) {
+    let listener = match NotifyListener::bind(ListenOptions {
+        network_interface: bind_to_interface,
+        network_version: Some(ip_version),
+        ..Default::default()
+    }) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("listen failed with error: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let mut registry = Registry::default();
+    if follow {
+        print_registry(&registry);
+    }
+    loop {
+        let notification = match listener.receive() {
+            Ok(notification) => notification,
+            Err(error) => {
+                eprintln!("listen failed with error: {}", error);
+                std::process::exit(1);
+            }
+        };
+        let received = date::now();
+        if follow {
+            let mut changes = registry.expire(Instant::now());
+            if let Some(notification) = &notification {
+                changes.extend(registry.notify(notification));
+            }
+            if !changes.is_empty() {
+                let _ = registry.purge();
+                print_registry(&registry);
+            }
+        } else if let Some(notification) = notification {
+            if json_lines {
+                let line = NotificationLine {
+                    received,
+                    nts: notification.subtype.to_string(),
+                    nt: notification.notification_type.to_string(),
+                    usn: notification.service_name.to_string(),
+                    location: notification.location.as_ref().map(ToString::to_string),
+                    max_age: notification.max_age.map(|max_age| max_age.as_secs()),
+                    boot_id: notification.boot_id,
+                    config_id: notification.config_id,
+                    source: notification.source.as_ref().map(ToString::to_string),
+                };
+                match serde_json::to_string(&line) {
+                    Ok(json) => println!("{}", json),
+                    Err(error) => {
+                        eprintln!("listen failed with error: {}", error);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                print_notification(&received, &notification);
+            }
+        }
+    }
+}
+
+fn print_notification(received: &str, notification: &Notification) {
+    println!(
+        "{} {} {} {}",
+        received, notification.subtype, notification.notification_type, notification.service_name
+    );
+    if let Some(location) = &notification.location {
+        println!("    location: {}", location);
+    }
+    if let Some(boot_id) = notification.boot_id {
+        println!("    boot id:  {}", boot_id);
+    }
+    if let Some(source) = &notification.source {
+        println!("    source:   {}", source);
+    }
+}
+
+fn print_registry(registry: &Registry) {
+    // clear the screen and move the cursor to the top-left.
+    print!("\x1b[2J\x1b[H");
+    println!("Devices present at {}\n", date::now());
+    let mut devices: Vec<_> = registry.present().collect();
+    devices.sort_by(|lhs, rhs| lhs.udn.cmp(&rhs.udn));
+    for device in devices {
+        println!(
+            "{:<48} {:<8} {}",
+            device.udn.to_string(),
+            match device.presence {
+                Presence::Alive => "alive",
+                Presence::Expiring => "expiring",
+                Presence::ByeBye => "byebye",
+                Presence::Stale => "stale",
+            },
+            device.location
+        );
+    }
+}
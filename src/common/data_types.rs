@@ -0,0 +1,561 @@
+/*!
+This module provides typed lexical conversions for the UPnP data types whose string forms are not
+handled correctly by the standard library, namely `boolean`, `date`, `dateTime`, `dateTime.tz`,
+`time`, and `time.tz`. These are used wherever argument values and evented state variables are
+converted to and from their on-the-wire representation, e.g. SOAP action arguments and GENA
+property sets.
+
+# Example
+
+```rust
+use upnp_rs::common::data_types::{Boolean, Date};
+use std::str::FromStr;
+
+assert!(bool::from(Boolean::from_str("yes").unwrap()));
+assert_eq!(Boolean::from(true).to_string(), "1");
+
+let date = Date::from_str("2021-02-28").unwrap();
+assert_eq!(date.month(), 2);
+```
+*/
+
+use crate::error::{invalid_value_for_type, MessageFormatError};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The UPnP `boolean` type. When parsing the values `0`, `false`, and `no` are accepted for false
+/// and `1`, `true`, and `yes` are accepted for true (case-insensitive); when formatting the
+/// recommended values `0` and `1` are always used.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Boolean(bool);
+
+///
+/// The UPnP `date` type; a date in a subset of ISO 8601 format without time data, `YYYY-MM-DD`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+///
+/// A time zone offset from UTC, as used by the `dateTime.tz` and `time.tz` types. This is either
+/// the value `Z` or an offset of the form `+hh:mm` or `-hh:mm`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeZone {
+    offset_minutes: i16,
+}
+
+///
+/// The UPnP `time` and `time.tz` types; a time in a subset of ISO 8601 format with no date,
+/// `hh:mm:ss` with an optional time zone.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    hour: u8,
+    minute: u8,
+    second: u8,
+    time_zone: Option<TimeZone>,
+}
+
+///
+/// The UPnP `dateTime` and `dateTime.tz` types; a date in ISO 8601 format with an optional time,
+/// `YYYY-MM-DD` or `YYYY-MM-DDThh:mm:ss`, where the time may include a time zone.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    date: Date,
+    time: Option<Time>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Parse a string as a UPnP `boolean` value.
+///
+pub fn boolean_from_str(s: &str) -> Result<bool, MessageFormatError> {
+    Boolean::from_str(s).map(bool::from)
+}
+
+///
+/// Format a value as a UPnP `boolean` string, this is always `0` or `1`.
+///
+pub fn boolean_to_string(value: bool) -> String {
+    Boolean::from(value).to_string()
+}
+
+///
+/// Check that `value` is a valid lexical form for the named UPnP data type, e.g. `ui2`, `string`,
+/// or `dateTime.tz`. Values for unrecognized, possibly vendor-defined, types are always accepted.
+///
+pub fn validate_value(data_type: &str, value: &str) -> Result<(), MessageFormatError> {
+    let valid = match data_type {
+        "ui1" => value.parse::<u8>().is_ok(),
+        "ui2" => value.parse::<u16>().is_ok(),
+        "ui4" => value.parse::<u32>().is_ok(),
+        "ui8" => value.parse::<u64>().is_ok(),
+        "i1" => value.parse::<i8>().is_ok(),
+        "i2" => value.parse::<i16>().is_ok(),
+        "i4" | "int" => value.parse::<i32>().is_ok(),
+        "i8" => value.parse::<i64>().is_ok(),
+        "r4" | "r8" | "number" | "float" | "fixed.14.4" => value.parse::<f64>().is_ok(),
+        "char" => value.chars().count() == 1,
+        "boolean" => Boolean::from_str(value).is_ok(),
+        "date" => Date::from_str(value).is_ok(),
+        "dateTime" => DateTime::from_str(value)
+            .map(|date_time| !date_time.has_time_zone())
+            .unwrap_or_default(),
+        "dateTime.tz" => DateTime::from_str(value).is_ok(),
+        "time" => Time::from_str(value)
+            .map(|time| time.time_zone().is_none())
+            .unwrap_or_default(),
+        "time.tz" => Time::from_str(value).is_ok(),
+        "bin.hex" => value.len().is_multiple_of(2) && value.chars().all(|c| c.is_ascii_hexdigit()),
+        "bin.base64" => value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '='),
+        "uuid" => uuid::Uuid::parse_str(value).is_ok(),
+        _ => true,
+    };
+    if valid {
+        Ok(())
+    } else {
+        invalid_value_for_type(data_type, value).into()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Boolean {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", if self.0 { "1" } else { "0" })
+    }
+}
+
+impl FromStr for Boolean {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(Boolean(true)),
+            "0" | "false" | "no" => Ok(Boolean(false)),
+            _ => invalid_value_for_type("boolean", s).into(),
+        }
+    }
+}
+
+impl From<bool> for Boolean {
+    fn from(v: bool) -> Self {
+        Boolean(v)
+    }
+}
+
+impl From<Boolean> for bool {
+    fn from(v: Boolean) -> Self {
+        v.0
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() == 3 && parts[0].len() == 4 && parts[1].len() == 2 && parts[2].len() == 2 {
+            if let (Some(year), Some(month), Some(day)) = (
+                parse_digits::<u16>(parts[0]),
+                parse_digits::<u8>(parts[1]),
+                parse_digits::<u8>(parts[2]),
+            ) {
+                if let Some(date) = Date::new(year, month, day) {
+                    return Ok(date);
+                }
+            }
+        }
+        invalid_value_for_type("date", s).into()
+    }
+}
+
+impl Date {
+    ///
+    /// Construct a new date, returning `None` if the month or day values are out of range.
+    ///
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(Self { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if self.offset_minutes == 0 {
+            write!(f, "Z")
+        } else {
+            let abs = self.offset_minutes.abs();
+            write!(
+                f,
+                "{}{:02}:{:02}",
+                if self.offset_minutes < 0 { '-' } else { '+' },
+                abs / 60,
+                abs % 60
+            )
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "Z" {
+            return Ok(TimeZone::utc());
+        }
+        let (sign, rest) = match s.chars().next() {
+            Some('+') => (1, &s[1..]),
+            Some('-') => (-1, &s[1..]),
+            _ => return invalid_value_for_type("time zone", s).into(),
+        };
+        let parts: Vec<&str> = rest.split(':').collect();
+        if parts.len() == 2 && parts[0].len() == 2 && parts[1].len() == 2 {
+            if let (Some(hours), Some(minutes)) =
+                (parse_digits::<i16>(parts[0]), parse_digits::<i16>(parts[1]))
+            {
+                if hours <= 14 && minutes < 60 {
+                    return Ok(TimeZone {
+                        offset_minutes: sign * (hours * 60 + minutes),
+                    });
+                }
+            }
+        }
+        invalid_value_for_type("time zone", s).into()
+    }
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self { offset_minutes: 0 }
+    }
+
+    ///
+    /// Construct a new time zone from a signed offset in minutes, returning `None` if the offset
+    /// is greater than 14 hours either side of UTC.
+    ///
+    pub fn from_offset_minutes(offset_minutes: i16) -> Option<Self> {
+        if offset_minutes.abs() <= 14 * 60 {
+            Some(Self { offset_minutes })
+        } else {
+            None
+        }
+    }
+
+    pub fn offset_minutes(&self) -> i16 {
+        self.offset_minutes
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        if let Some(time_zone) = &self.time_zone {
+            write!(f, "{}", time_zone)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Time {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, time_zone) = match s.find(['Z', '+', '-']) {
+            None => (s, None),
+            Some(index) => (&s[..index], Some(TimeZone::from_str(&s[index..])?)),
+        };
+        let parts: Vec<&str> = time.split(':').collect();
+        if parts.len() == 3 && parts.iter().all(|part| part.len() == 2) {
+            if let (Some(hour), Some(minute), Some(second)) = (
+                parse_digits::<u8>(parts[0]),
+                parse_digits::<u8>(parts[1]),
+                parse_digits::<u8>(parts[2]),
+            ) {
+                if let Some(time) = Time::new(hour, minute, second) {
+                    return Ok(match time_zone {
+                        None => time,
+                        Some(time_zone) => time.with_time_zone(time_zone),
+                    });
+                }
+            }
+        }
+        invalid_value_for_type("time", s).into()
+    }
+}
+
+impl Time {
+    ///
+    /// Construct a new time, with no time zone, returning `None` if any value is out of range.
+    ///
+    pub fn new(hour: u8, minute: u8, second: u8) -> Option<Self> {
+        if hour < 24 && minute < 60 && second < 60 {
+            Some(Self {
+                hour,
+                minute,
+                second,
+                time_zone: None,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn with_time_zone(self, time_zone: TimeZone) -> Self {
+        Self {
+            time_zone: Some(time_zone),
+            ..self
+        }
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub fn time_zone(&self) -> Option<TimeZone> {
+        self.time_zone
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.date)?;
+        if let Some(time) = &self.time {
+            write!(f, "T{}", time)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let result = match s.split_once('T') {
+            None => Date::from_str(s).map(DateTime::from),
+            Some((date, time)) => Ok(DateTime::new(
+                Date::from_str(date)?,
+                Some(Time::from_str(time)?),
+            )),
+        };
+        result.or_else(|_| invalid_value_for_type("dateTime", s).into())
+    }
+}
+
+impl From<Date> for DateTime {
+    fn from(date: Date) -> Self {
+        Self::new(date, None)
+    }
+}
+
+impl DateTime {
+    pub fn new(date: Date, time: Option<Time>) -> Self {
+        Self { date, time }
+    }
+
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    pub fn time(&self) -> Option<Time> {
+        self.time
+    }
+
+    ///
+    /// Returns `true` if this value has a time zone, and so is a valid `dateTime.tz` value.
+    ///
+    pub fn has_time_zone(&self) -> bool {
+        matches!(
+            self.time,
+            Some(Time {
+                time_zone: Some(_),
+                ..
+            })
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parse_digits<T: FromStr>(s: &str) -> Option<T> {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        s.parse::<T>().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_value() {
+        assert!(validate_value("ui1", "255").is_ok());
+        assert!(validate_value("ui1", "256").is_err());
+        assert!(validate_value("i4", "-12").is_ok());
+        assert!(validate_value("r8", "1.5e3").is_ok());
+        assert!(validate_value("boolean", "yes").is_ok());
+        assert!(validate_value("char", "ab").is_err());
+        assert!(validate_value("time", "12:00:00Z").is_err());
+        assert!(validate_value("time.tz", "12:00:00Z").is_ok());
+        assert!(validate_value("bin.hex", "0aF").is_err());
+        assert!(validate_value("string", "anything").is_ok());
+        assert!(validate_value("x-vendor", "anything").is_ok());
+    }
+
+    #[test]
+    fn test_boolean_accepted_forms() {
+        for value in &["1", "true", "yes", "TRUE", "Yes", " 1 "] {
+            assert!(boolean_from_str(value).unwrap(), "{}", value);
+        }
+        for value in &["0", "false", "no", "FALSE", "No"] {
+            assert!(!boolean_from_str(value).unwrap(), "{}", value);
+        }
+        for value in &["", "2", "on", "off", "y", "n"] {
+            assert!(boolean_from_str(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_boolean_to_string() {
+        assert_eq!(boolean_to_string(true), "1");
+        assert_eq!(boolean_to_string(false), "0");
+    }
+
+    #[test]
+    fn test_date() {
+        let date = Date::from_str("2020-02-29").unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2020, 2, 29));
+        assert_eq!(date.to_string(), "2020-02-29");
+
+        for value in &[
+            "2021-02-29",
+            "2021-13-01",
+            "2021-1-01",
+            "20210101",
+            "2021-01-01T",
+        ] {
+            assert!(Date::from_str(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_time() {
+        let time = Time::from_str("23:59:01").unwrap();
+        assert_eq!(time.time_zone(), None);
+        assert_eq!(time.to_string(), "23:59:01");
+
+        let time = Time::from_str("08:30:00+05:30").unwrap();
+        assert_eq!(time.time_zone().unwrap().offset_minutes(), 330);
+        assert_eq!(time.to_string(), "08:30:00+05:30");
+
+        let time = Time::from_str("08:30:00-08:00").unwrap();
+        assert_eq!(time.time_zone().unwrap().offset_minutes(), -480);
+
+        let time = Time::from_str("08:30:00Z").unwrap();
+        assert_eq!(time.time_zone(), Some(TimeZone::utc()));
+        assert_eq!(time.to_string(), "08:30:00Z");
+
+        for value in &[
+            "24:00:00",
+            "12:60:00",
+            "12:00",
+            "12:00:00+5:00",
+            "12:00:00X",
+        ] {
+            assert!(Time::from_str(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_date_time() {
+        let date_time = DateTime::from_str("2021-06-01").unwrap();
+        assert!(date_time.time().is_none());
+        assert_eq!(date_time.to_string(), "2021-06-01");
+
+        let date_time = DateTime::from_str("2021-06-01T12:34:56").unwrap();
+        assert!(!date_time.has_time_zone());
+        assert_eq!(date_time.to_string(), "2021-06-01T12:34:56");
+
+        let date_time = DateTime::from_str("2021-06-01T12:34:56-07:00").unwrap();
+        assert!(date_time.has_time_zone());
+        assert_eq!(date_time.to_string(), "2021-06-01T12:34:56-07:00");
+
+        for value in &["2021-06-01T", "2021-06-01 12:34:56", "2021-06-01T25:00:00"] {
+            assert!(DateTime::from_str(value).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/*!
+Generation and parsing of the `DATE` header values carried by SSDP responses and HTTP messages.
+Values are generated in the RFC 1123 form required by the UPnP Device Architecture, for example
+`Sun, 06 Nov 1994 08:49:37 GMT`; when parsing, the obsolete RFC 850 and `asctime` forms permitted
+by HTTP/1.1 are also accepted.
+
+# Example
+
+```rust
+use upnp_rs::common::date;
+use std::time::{Duration, UNIX_EPOCH};
+
+let time = UNIX_EPOCH + Duration::from_secs(784111777);
+assert_eq!(date::format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+assert_eq!(date::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap(), time);
+```
+*/
+
+use crate::error::{invalid_header_value, MessageFormatError};
+use crate::syntax::HTTP_HEADER_DATE;
+use std::time::SystemTime;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Format `time` as an RFC 1123 date; any fractional seconds are discarded.
+///
+pub fn format(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+///
+/// The current time formatted as an RFC 1123 date, the value to send in a `DATE` header.
+///
+pub fn now() -> String {
+    format(SystemTime::now())
+}
+
+///
+/// Parse a `DATE` header value.
+///
+pub fn parse(value: &str) -> Result<SystemTime, MessageFormatError> {
+    httpdate::parse_http_date(value.trim())
+        .map_err(|_| invalid_header_value(HTTP_HEADER_DATE, value))
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_parse_all_forms() {
+        let expected = UNIX_EPOCH + Duration::from_secs(784111777);
+        for value in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse(value).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("yesterday").is_err());
+    }
+}
@@ -8,10 +8,17 @@ pub fn check_required(
     headers: &HashMap<String, String>,
     required: &[&str],
 ) -> Result<(), MessageFormatError> {
+    check_required_by(|name| headers.contains_key(name), required)
+}
+
+pub fn check_required_by<F>(is_present: F, required: &[&str]) -> Result<(), MessageFormatError>
+where
+    F: Fn(&str) -> bool,
+{
     let missing_headers: Vec<String> = required
         .iter()
         .cloned()
-        .take_while(|h| !headers.contains_key(*h))
+        .filter(|h| !is_present(h))
         .map(String::from)
         .collect();
     if missing_headers.is_empty() {
@@ -68,12 +75,44 @@ pub fn check_empty(header_value: &str, name: &str) -> Result<(), MessageFormatEr
     }
 }
 
-pub fn check_not_empty(header_entry: std::option::Option<&String>, default: &str) -> String {
-    let default_value = default.to_string();
-    let header_value = header_entry.unwrap_or(&default_value);
-    if !header_value.trim().is_empty() {
-        header_value.to_string()
-    } else {
-        default_value
+///
+/// Split a header value of `name=value` parameters, separated by semicolons, into its pairs.
+/// Values may be quoted, quotes are removed; a parameter with no value has an empty value.
+///
+pub fn parse_parameters(header_value: &str) -> Vec<(String, String)> {
+    let mut parameters = Vec::new();
+    let mut rest = header_value.trim();
+    while !rest.is_empty() {
+        let (name, after_name) = match rest.find(['=', ';']) {
+            Some(index) if rest[index..].starts_with('=') => (&rest[..index], &rest[index + 1..]),
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, ""),
+        };
+        let after_name = after_name.trim_start();
+        let (value, after_value) = match after_name.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after_name.find(';') {
+                Some(end) => (after_name[..end].trim_end(), &after_name[end..]),
+                None => (after_name.trim_end(), ""),
+            },
+        };
+        if !name.trim().is_empty() {
+            parameters.push((name.trim().to_string(), value.to_string()));
+        }
+        rest = after_value
+            .trim_start()
+            .trim_start_matches(';')
+            .trim_start();
+    }
+    parameters
+}
+
+pub fn check_not_empty(header_entry: Option<&str>, default: &str) -> String {
+    match header_entry {
+        Some(header_value) if !header_value.trim().is_empty() => header_value.to_string(),
+        _ => default.to_string(),
     }
 }
@@ -0,0 +1,218 @@
+/*!
+The HTTP client abstraction used to retrieve descriptions, invoke actions, and manage and deliver
+events.
+*/
+
+use crate::common::http::add_header;
+use crate::error::Error;
+use crate::syntax::HTTP_HEADER_CONTENT_TYPE;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+#[cfg(feature = "net")]
+use std::sync::Arc;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An HTTP client, as used by the description, control, and eventing modules. With the `net`
+/// feature the crate provides an implementation for
+/// [`MinimalClient`](crate::common::http::MinimalClient) and, with the corresponding features
+/// enabled, for `reqwest::blocking::Client` and `ureq::Agent`; other
+/// implementations allow an application to supply its own, possibly constrained, client.
+///
+pub trait HttpClient: Debug + Send + Sync {
+    ///
+    /// Send `request` and return the complete response. A response with any status, including an
+    /// error status, is returned as `Ok`; only a failure to exchange messages is an error.
+    ///
+    fn send(&self, request: &ClientRequest) -> Result<ClientResponse, Error>;
+}
+
+///
+/// A request to be sent by an [`HttpClient`].
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientRequest {
+    /// The request method, for example `GET` or `SUBSCRIBE`.
+    pub method: String,
+    /// The absolute URL of the resource.
+    pub url: String,
+    /// Any headers in addition to those the client adds, such as `HOST` and `CONTENT-LENGTH`.
+    pub headers: Vec<(String, String)>,
+    /// The request body, may be empty.
+    pub body: Vec<u8>,
+    /// The time allowed for the whole exchange, else the client's own timeout applies.
+    pub timeout: Option<Duration>,
+}
+
+///
+/// A complete response received by an [`HttpClient`].
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientResponse {
+    /// The status code, for example `200`.
+    pub status: u16,
+    /// The reason phrase, for example `OK`; may be empty.
+    pub reason: String,
+    /// The response headers, with names in upper case; repeated headers are joined with `,`.
+    pub headers: HashMap<String, String>,
+    /// The response body, after removing any transfer coding.
+    pub body: Vec<u8>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The client used where none is provided; a `reqwest` client if that feature is enabled, else a
+/// `ureq` agent if that feature is enabled, else a
+/// [`MinimalClient`](crate::common::http::MinimalClient). Without the `net` feature there is no
+/// default, and a client must be provided.
+///
+#[cfg(feature = "net")]
+pub fn default_client() -> Arc<dyn HttpClient> {
+    #[cfg(feature = "reqwest")]
+    {
+        Arc::new(reqwest::blocking::Client::new())
+    }
+    #[cfg(all(feature = "ureq", not(feature = "reqwest")))]
+    {
+        Arc::new(ureq::Agent::new())
+    }
+    #[cfg(not(any(feature = "reqwest", feature = "ureq")))]
+    {
+        Arc::new(crate::common::http::MinimalClient::default())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl ClientRequest {
+    pub fn new(method: &str, url: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: Default::default(),
+            body: Default::default(),
+            timeout: None,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(self, body: Vec<u8>) -> Self {
+        Self { body, ..self }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    ///
+    /// Return the value of the named header, header names are compared case-insensitively.
+    ///
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ClientResponse {
+    pub fn new(status: u16, reason: &str) -> Self {
+        Self {
+            status,
+            reason: reason.to_string(),
+            headers: Default::default(),
+            body: Default::default(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        add_header(&mut self.headers, name, value);
+        self
+    }
+
+    pub fn with_body(self, body: Vec<u8>) -> Self {
+        Self { body, ..self }
+    }
+
+    ///
+    /// Returns `true` for any `2xx` status.
+    ///
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    ///
+    /// The status code and reason phrase, for example `404 Not Found`.
+    ///
+    pub fn status_text(&self) -> String {
+        format!("{} {}", self.status, self.reason)
+            .trim_end()
+            .to_string()
+    }
+
+    ///
+    /// Return the value of the named header, header names are compared case-insensitively.
+    ///
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_uppercase())
+            .map(|value| value.as_str())
+    }
+
+    ///
+    /// The media type of the body, in lower case and without any parameters.
+    ///
+    pub fn content_type(&self) -> Option<String> {
+        self.header(HTTP_HEADER_CONTENT_TYPE)
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_lowercase())
+    }
+
+    ///
+    /// The body as a string; any invalid UTF-8 sequences are replaced.
+    ///
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_values() {
+        let response = ClientResponse::new(200, "OK")
+            .with_header("Content-Type", "Image/PNG; charset=binary")
+            .with_header("Ext", "");
+        assert!(response.is_success());
+        assert_eq!(response.status_text(), "200 OK");
+        assert_eq!(response.content_type(), Some("image/png".to_string()));
+        assert_eq!(response.header("EXT"), Some(""));
+        assert!(!ClientResponse::new(412, "").is_success());
+        assert_eq!(ClientResponse::new(412, "").status_text(), "412");
+    }
+}
@@ -0,0 +1,149 @@
+/*!
+A minimal blocking HTTP client, requiring nothing beyond the standard library.
+*/
+
+use crate::common::http::{
+    is_chunked, read_chunked_body, read_content_length_body, read_headers, read_line,
+    ClientRequest, ClientResponse, HttpClient, MAX_BODY_SIZE,
+};
+use crate::error::{invalid_header_value, invalid_value_for_type, unsupported_operation, Error};
+use crate::syntax::{HTTP_HEADER_CONTENT_LENGTH, HTTP_HEADER_HOST, HTTP_METHOD_HEAD};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use tracing::trace;
+use url::Url;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A client that sends each request on a new connection, closed once the response is read. Only
+/// `http` URLs are supported; chunked, content-length delimited, and connection delimited response
+/// bodies are read, and any interim `1xx` responses are skipped.
+///
+#[derive(Clone, Debug, Default)]
+pub struct MinimalClient {
+    timeout: Option<Duration>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl MinimalClient {
+    ///
+    /// Set the time allowed to connect, and for each read and write, where a request does not
+    /// specify its own timeout. Default: none.
+    ///
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+        }
+    }
+}
+
+impl HttpClient for MinimalClient {
+    fn send(&self, request: &ClientRequest) -> Result<ClientResponse, Error> {
+        let url = match Url::parse(&request.url) {
+            Ok(url) if url.scheme() == "http" && url.host_str().is_some() => url,
+            Ok(_) => {
+                return unsupported_operation(format!("{} {}", request.method, request.url)).into()
+            }
+            Err(_) => return invalid_value_for_type("URL", request.url.as_str()).into(),
+        };
+        let timeout = request.timeout.or(self.timeout);
+        let mut stream = connect(&url, timeout)?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        let mut message = format!(
+            "{} {} HTTP/1.1\r\n{}: {}\r\n",
+            request.method,
+            &url[url::Position::BeforePath..url::Position::AfterQuery],
+            HTTP_HEADER_HOST,
+            &url[url::Position::BeforeHost..url::Position::AfterPort],
+        );
+        for (name, value) in &request.headers {
+            message.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !request.body.is_empty() && request.header(HTTP_HEADER_CONTENT_LENGTH).is_none() {
+            message.push_str(&format!(
+                "{}: {}\r\n",
+                HTTP_HEADER_CONTENT_LENGTH,
+                request.body.len()
+            ));
+        }
+        message.push_str("CONNECTION: close\r\n\r\n");
+        trace!("send - {} {}", request.method, request.url);
+        stream.write_all(message.as_bytes())?;
+        stream.write_all(&request.body)?;
+        stream.flush()?;
+
+        read_response(
+            &mut BufReader::new(stream),
+            request.method.eq_ignore_ascii_case(HTTP_METHOD_HEAD),
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn connect(url: &Url, timeout: Option<Duration>) -> Result<TcpStream, Error> {
+    let mut last_error = std::io::Error::from(ErrorKind::AddrNotAvailable);
+    for address in url.socket_addrs(|| Some(80))? {
+        let result = match timeout {
+            None => TcpStream::connect(address),
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error.into())
+}
+
+fn read_response<R: BufRead>(reader: &mut R, is_head: bool) -> Result<ClientResponse, Error> {
+    loop {
+        let mut status_line = String::new();
+        if read_line(reader, &mut status_line)? == 0 {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        let mut parts = status_line.trim_end().splitn(3, ' ');
+        let status = match (parts.next(), parts.next()) {
+            (Some(protocol), Some(status)) if protocol.starts_with("HTTP/") => status
+                .parse::<u16>()
+                .map_err(|_| invalid_header_value("STATUS", status_line.trim_end()))?,
+            _ => return invalid_header_value("STATUS", status_line.trim_end()).into(),
+        };
+        let mut response = ClientResponse::new(status, parts.next().unwrap_or_default());
+        response.headers = read_headers(reader)?;
+        if (100..200).contains(&status) {
+            continue;
+        }
+        if !(is_head || status == 204 || status == 304) {
+            response.body = if is_chunked(&response.headers) {
+                read_chunked_body(reader)?
+            } else {
+                match read_content_length_body(reader, &response.headers)? {
+                    Some(body) => body,
+                    None => {
+                        let mut body = Vec::new();
+                        let _ = reader
+                            .take(MAX_BODY_SIZE as u64 + 1)
+                            .read_to_end(&mut body)?;
+                        if body.len() > MAX_BODY_SIZE {
+                            return invalid_header_value(HTTP_HEADER_CONTENT_LENGTH, "").into();
+                        }
+                        body
+                    }
+                }
+            };
+        }
+        return Ok(response);
+    }
+}
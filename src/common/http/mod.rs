@@ -0,0 +1,628 @@
+/*!
+This module provides simple HTTP functions used to retrieve device and service descriptions, the
+[`HttpClient`] abstraction through which the crate sends all HTTP requests, and the request
+handling shared by the small HTTP servers in this crate, the event listener and the device
+emulator.
+
+Requests are sent by an `HttpClient`; functions that take no client use [`default_client`]. With
+the `net` feature the crate provides a [`MinimalClient`] that requires nothing beyond the standard
+library and, behind the features of the same name, adapters for `reqwest` (enabled by default) and
+`ureq`; without it there is no default client, and only the functions taking a client remain.
+Any client is expected to handle chunked and content-length delimited bodies, bodies delimited by
+closing the connection, and interim `100 Continue` responses. The servers use [`read_request`] and
+[`write_response`], which accept the same range of requests, including the quirks of embedded
+devices such as bare `LF` line endings and blank lines between requests, and keep a connection
+open where the client asks.
+*/
+
+use crate::error::{invalid_field_value, invalid_header_value, operation_failed, Error};
+use crate::observer::{self, MessageKind};
+use crate::syntax::HTTP_METHOD_GET;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{error, info, info_span, trace};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A request received by one of the crate's HTTP servers.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The request method, for example `GET` or `NOTIFY`.
+    pub method: String,
+    /// The request target, usually an absolute path.
+    pub path: String,
+    /// The protocol version, for example `1.1`.
+    pub version: String,
+    /// The request headers, with names in upper case; repeated headers are joined with `,`.
+    pub headers: HashMap<String, String>,
+    /// The request body, after removing any chunked transfer coding.
+    pub body: Vec<u8>,
+}
+
+///
+/// The time a server waits for a further request on a persistent connection.
+///
+pub const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_millis(500);
+
+///
+/// The largest request body a server will accept.
+///
+pub const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Fetch the resource at `url` and parse the response body into a value of type `T`.
+///
+#[cfg(feature = "net")]
+pub fn fetch<T>(url: String) -> Result<T, Error>
+where
+    T: FromStr<Err = Error>,
+{
+    fetch_with(url, default_client().as_ref())
+}
+
+///
+/// Fetch the resource at `url`, using the provided client, and parse the response body into a
+/// value of type `T`.
+///
+pub fn fetch_with<T>(url: String, client: &dyn HttpClient) -> Result<T, Error>
+where
+    T: FromStr<Err = Error>,
+{
+    let text = fetch_string_with(url, client)?;
+    T::from_str(&text).inspect_err(|_| observer::parse_failed(MessageKind::Description))
+}
+
+///
+/// Fetch the resource at `url`, using the provided client, and return the response body as a
+/// string.
+///
+pub fn fetch_string_with(url: String, client: &dyn HttpClient) -> Result<String, Error> {
+    let _span = info_span!("fetch", url = %url).entered();
+    let response = send(client, &url)?;
+    if response.is_success() {
+        Ok(response.text())
+    } else {
+        error!(
+            "fetch_string_with - request for {} returned status {}",
+            url, response.status
+        );
+        operation_failed(format!("GET {}", url), response.status_text()).into()
+    }
+}
+
+///
+/// Fetch the resource at `url`, using the provided client, and return the response's content type,
+/// without any parameters, and body.
+///
+pub fn fetch_bytes_with(
+    url: String,
+    client: &dyn HttpClient,
+) -> Result<(Option<String>, Vec<u8>), Error> {
+    let _span = info_span!("fetch", url = %url).entered();
+    let response = send(client, &url)?;
+    if response.is_success() {
+        Ok((response.content_type(), response.body))
+    } else {
+        error!(
+            "fetch_bytes_with - request for {} returned status {}",
+            url, response.status
+        );
+        operation_failed(format!("GET {}", url), response.status_text()).into()
+    }
+}
+
+///
+/// Read the next request from a connection. `None` is returned if the connection is closed, or the
+/// read timeout expires, before a request starts; a request that is started but incomplete is an
+/// error. If the client expects `100 Continue` the interim response is written before the body
+/// is read.
+///
+pub fn read_request<S>(reader: &mut BufReader<S>) -> Result<Option<HttpRequest>, Error>
+where
+    S: Read + Write,
+{
+    let request_line = loop {
+        let mut line = String::new();
+        match read_line(reader, &mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) if line.trim().is_empty() => continue,
+            Ok(_) => break line,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    let (method, path, version) = match parts.as_slice() {
+        [method, path, protocol] if protocol.starts_with("HTTP/") => (
+            method.to_string(),
+            path.to_string(),
+            protocol[5..].to_string(),
+        ),
+        _ => return invalid_header_value("REQUEST", request_line.trim_end()).into(),
+    };
+
+    let headers = read_headers(reader)?;
+    let mut request = HttpRequest {
+        method,
+        path,
+        version,
+        headers,
+        body: Vec::new(),
+    };
+    if request.version != "1.0" && has_token(&request.headers, HEADER_EXPECT, "100-continue") {
+        let stream = reader.get_mut();
+        stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        stream.flush()?;
+    }
+    request.body = if is_chunked(&request.headers) {
+        read_chunked_body(reader)?
+    } else {
+        read_content_length_body(reader, &request.headers)?.unwrap_or_default()
+    };
+    trace!(
+        "read_request - {} {} ({} bytes)",
+        request.method,
+        request.path,
+        request.body.len()
+    );
+    Ok(Some(request))
+}
+
+///
+/// Write a complete response, adding the `CONTENT-LENGTH` and `CONNECTION` headers.
+///
+pub fn write_response<W: Write>(
+    writer: &mut W,
+    status: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+    keep_alive: bool,
+) -> Result<(), Error> {
+    write!(writer, "HTTP/1.1 {}\r\n", status)?;
+    for (name, value) in headers {
+        write!(writer, "{}: {}\r\n", name, value)?;
+    }
+    write!(
+        writer,
+        "{}: {}\r\n{}: {}\r\n\r\n",
+        HEADER_CONTENT_LENGTH,
+        body.len(),
+        HEADER_CONNECTION,
+        if keep_alive { "keep-alive" } else { "close" }
+    )?;
+    writer.write_all(body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HttpRequest {
+    ///
+    /// Return the value of the named header, header names are compared case-insensitively.
+    ///
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_uppercase())
+            .map(|value| value.as_str())
+    }
+
+    ///
+    /// Returns `true` if the client has asked for the connection to remain open after the
+    /// response; the default for HTTP/1.1 and not for HTTP/1.0.
+    ///
+    pub fn keep_alive(&self) -> bool {
+        if self.version == "1.0" {
+            has_token(&self.headers, HEADER_CONNECTION, "keep-alive")
+        } else {
+            !has_token(&self.headers, HEADER_CONNECTION, "close")
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const HEADER_CONNECTION: &str = "CONNECTION";
+
+const HEADER_CONTENT_LENGTH: &str = "CONTENT-LENGTH";
+
+const HEADER_EXPECT: &str = "EXPECT";
+
+const HEADER_TRANSFER_ENCODING: &str = "TRANSFER-ENCODING";
+
+/// The longest request, status, header or chunk-size line that will be read.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// The most header, or trailer, lines that will be read for a single message.
+const MAX_HEADER_COUNT: usize = 100;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> std::io::Result<usize> {
+    let read = reader.take(MAX_LINE_LENGTH as u64).read_line(line)?;
+    if read == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        error!(
+            "read_line - line exceeds the maximum length of {} bytes",
+            MAX_LINE_LENGTH
+        );
+        return Err(std::io::Error::from(ErrorKind::InvalidData));
+    }
+    Ok(read)
+}
+
+fn read_header_lines<R: BufRead>(reader: &mut R) -> Result<Vec<String>, Error> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if read_line(reader, &mut line)? == 0 {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(lines);
+        }
+        if lines.len() == MAX_HEADER_COUNT {
+            error!(
+                "read_header_lines - more than the maximum of {} headers",
+                MAX_HEADER_COUNT
+            );
+            return invalid_header_value("HEADER", line).into();
+        }
+        lines.push(line.to_string());
+    }
+}
+
+fn read_headers<R: BufRead>(reader: &mut R) -> Result<HashMap<String, String>, Error> {
+    let mut headers: HashMap<String, String> = Default::default();
+    for line in read_header_lines(reader)? {
+        match line.split_once(':') {
+            Some((name, value)) if !name.trim().contains(char::is_whitespace) => {
+                add_header(&mut headers, name.trim(), value.trim())
+            }
+            _ => return invalid_header_value("HEADER", line).into(),
+        }
+    }
+    Ok(headers)
+}
+
+fn add_header(headers: &mut HashMap<String, String>, name: &str, value: &str) {
+    let _ = headers
+        .entry(name.to_uppercase())
+        .and_modify(|existing| {
+            existing.push_str(", ");
+            existing.push_str(value)
+        })
+        .or_insert_with(|| value.to_string());
+}
+
+fn has_token(headers: &HashMap<String, String>, name: &str, token: &str) -> bool {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .split(',')
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        })
+        .unwrap_or(false)
+}
+
+fn is_chunked(headers: &HashMap<String, String>) -> bool {
+    headers
+        .get(HEADER_TRANSFER_ENCODING)
+        .and_then(|codings| codings.rsplit(',').next())
+        .map(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+}
+
+fn read_content_length_body<R: BufRead>(
+    reader: &mut R,
+    headers: &HashMap<String, String>,
+) -> Result<Option<Vec<u8>>, Error> {
+    match headers.get(HEADER_CONTENT_LENGTH) {
+        None => Ok(None),
+        Some(length) => {
+            let mut body = vec![0u8; content_length(length)?];
+            reader.read_exact(&mut body)?;
+            Ok(Some(body))
+        }
+    }
+}
+
+fn content_length(value: &str) -> Result<usize, Error> {
+    // a repeated header, with the same value each time, is tolerated.
+    let mut values = value.split(',').map(str::trim);
+    let first = values.next().unwrap_or_default();
+    match first.parse::<usize>() {
+        Ok(length) if length <= MAX_BODY_SIZE && values.all(|other| other == first) => Ok(length),
+        _ => invalid_header_value(HEADER_CONTENT_LENGTH, value).into(),
+    }
+}
+
+fn read_chunked_body<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        if read_line(reader, &mut line)? == 0 {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        // any chunk extensions are ignored.
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| invalid_field_value("chunk-size", line.trim_end()))?;
+        if size == 0 {
+            // trailer fields are read and discarded.
+            let _ = read_header_lines(reader)?;
+            return Ok(body);
+        }
+        if size > MAX_BODY_SIZE - body.len() {
+            return invalid_field_value("chunk-size", line.trim_end()).into();
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        let mut end = String::new();
+        let _ = read_line(reader, &mut end)?;
+        if !end.trim_end().is_empty() {
+            return invalid_field_value("chunk-data", end.trim_end()).into();
+        }
+    }
+}
+
+fn send(client: &dyn HttpClient, url: &str) -> Result<ClientResponse, Error> {
+    info!("fetching");
+    observer::sent(MessageKind::Description);
+    let response = client.send(&ClientRequest::new(HTTP_METHOD_GET, url))?;
+    observer::received(MessageKind::Description);
+    info!(status = response.status, "received");
+    Ok(response)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+#[doc(hidden)]
+mod client;
+#[cfg(feature = "net")]
+pub use client::default_client;
+pub use client::{ClientRequest, ClientResponse, HttpClient};
+
+#[cfg(feature = "net")]
+#[doc(hidden)]
+mod minimal;
+#[cfg(feature = "net")]
+pub use minimal::MinimalClient;
+
+#[cfg(feature = "reqwest")]
+mod reqwest_client;
+
+#[cfg(feature = "ureq")]
+mod ureq_client;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    #[cfg(feature = "net")]
+    use std::net::TcpListener;
+    #[cfg(feature = "net")]
+    use std::sync::Arc;
+    #[cfg(feature = "net")]
+    use std::thread::spawn;
+
+    #[derive(Debug)]
+    struct Connection {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Connection {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Connection {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connection(input: &[u8]) -> BufReader<Connection> {
+        BufReader::new(Connection {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+        })
+    }
+
+    #[cfg(feature = "net")]
+    fn serve_once(response: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/description.xml", listener.local_addr().unwrap());
+        let _ = spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let _ = read_request(&mut reader).unwrap();
+            reader.get_mut().write_all(response).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_read_requests() {
+        let mut reader = connection(
+            b"\r\nNOTIFY /events HTTP/1.1\nHOST: 10.0.0.1\nTransfer-Encoding: chunked\nSID: uuid:1\n\n4;name=value\r\n<e:p\r\n3\r\nro/\r\n0\r\nTrailer: 1\r\n\r\nGET /description.xml HTTP/1.0\r\nConnection: keep-alive\r\nContent-Length: 2, 2\r\n\r\nOK",
+        );
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(request.method, "NOTIFY");
+        assert_eq!(request.path, "/events");
+        assert_eq!(request.header("sid"), Some("uuid:1"));
+        assert_eq!(request.body, b"<e:pro/");
+        assert!(request.keep_alive());
+
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(request.version, "1.0");
+        assert_eq!(request.body, b"OK");
+        assert!(request.keep_alive());
+
+        assert!(read_request(&mut reader).unwrap().is_none());
+        assert!(reader.get_ref().output.is_empty());
+    }
+
+    #[test]
+    fn test_read_expect_continue() {
+        let mut reader = connection(
+            b"POST /control HTTP/1.1\r\nExpect: 100-continue\r\nConnection: close\r\nContent-Length: 4\r\n\r\nbody",
+        );
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(request.body, b"body");
+        assert!(!request.keep_alive());
+        assert_eq!(reader.get_ref().output, b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+
+    #[test]
+    fn test_read_malformed_requests() {
+        for malformed in [
+            &b"NOTIFY /events\r\n\r\n"[..],
+            b"NOTIFY /events HTTP/1.1\r\nSID uuid:1\r\n\r\n",
+            b"NOTIFY /events HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+            b"NOTIFY /events HTTP/1.1\r\nContent-Length: 2, 3\r\n\r\nabc",
+            b"NOTIFY /events HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            b"NOTIFY /events HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n",
+            b"NOTIFY /events HTTP/1.1\r\nHOST: 10.0.0.1",
+            b"NOTIFY /events HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\nffffffffffffffff\r\n",
+        ] {
+            assert!(read_request(&mut connection(malformed)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_oversized_requests() {
+        let mut long_line = b"NOTIFY /events HTTP/1.1\r\nSID: ".to_vec();
+        long_line.resize(long_line.len() + MAX_LINE_LENGTH, b'x');
+        assert!(read_request(&mut connection(&long_line)).is_err());
+
+        let mut long_request_line = b"NOTIFY /".to_vec();
+        long_request_line.resize(long_request_line.len() + MAX_LINE_LENGTH, b'x');
+        assert!(read_request(&mut connection(&long_request_line)).is_err());
+
+        let mut many_headers = b"NOTIFY /events HTTP/1.1\r\n".to_vec();
+        for i in 0..=MAX_HEADER_COUNT {
+            many_headers.extend_from_slice(format!("X-{}: {}\r\n", i, i).as_bytes());
+        }
+        many_headers.extend_from_slice(b"\r\n");
+        assert!(read_request(&mut connection(&many_headers)).is_err());
+    }
+
+    #[test]
+    fn test_write_response() {
+        let mut written = Vec::new();
+        write_response(
+            &mut written,
+            "200 OK",
+            &[("SID", "uuid:1".to_string())],
+            b"",
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            written,
+            b"HTTP/1.1 200 OK\r\nSID: uuid:1\r\nCONTENT-LENGTH: 0\r\nCONNECTION: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_fetch_canned_responses() {
+        let clients: [Arc<dyn HttpClient>; 2] =
+            [default_client(), Arc::new(MinimalClient::default())];
+        for client in &clients {
+            let client = client.as_ref();
+            let chunked = serve_once(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\n<root\r\n2\r\n/>\r\n0\r\n\r\n",
+            );
+            assert_eq!(fetch_string_with(chunked, client).unwrap(), "<root/>");
+
+            let interim = serve_once(
+                b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\n<root/>",
+            );
+            assert_eq!(fetch_string_with(interim, client).unwrap(), "<root/>");
+
+            let until_close =
+                serve_once(b"HTTP/1.0 200 OK\r\nContent-Type: text/xml\r\n\r\n<root/>");
+            let (content_type, body) = fetch_bytes_with(until_close, client).unwrap();
+            assert_eq!(content_type.as_deref(), Some("text/xml"));
+            assert_eq!(body, b"<root/>");
+
+            let truncated = serve_once(b"HTTP/1.1 200 OK\r\nContent-Length: 70\r\n\r\n<root/>");
+            assert!(fetch_string_with(truncated, client).is_err());
+
+            let not_found = serve_once(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+            assert!(fetch_string_with(not_found, client).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_minimal_client_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/control?x=1", listener.local_addr().unwrap());
+        let server = spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let request = read_request(&mut reader).unwrap().unwrap();
+            write_response(reader.get_mut(), "200 OK", &[], b"done", false).unwrap();
+            request
+        });
+        let response = MinimalClient::default()
+            .send(
+                &ClientRequest::new("POST", &url)
+                    .with_header("SOAPACTION", "\"urn:x#y\"")
+                    .with_body(b"body".to_vec())
+                    .with_timeout(Duration::from_secs(5)),
+            )
+            .unwrap();
+        assert_eq!(response.status_text(), "200 OK");
+        assert_eq!(response.body, b"done");
+
+        let request = server.join().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/control?x=1");
+        assert_eq!(request.header("soapaction"), Some("\"urn:x#y\""));
+        assert!(request.header("host").unwrap().starts_with("127.0.0.1:"));
+        assert!(!request.keep_alive());
+        assert_eq!(request.body, b"body");
+
+        assert!(MinimalClient::default()
+            .send(&ClientRequest::new("GET", "https://127.0.0.1/"))
+            .is_err());
+    }
+}
@@ -0,0 +1,67 @@
+/*!
+The [`HttpClient`] implementation for the blocking `reqwest` client, enabled by the `reqwest`
+feature.
+*/
+
+use crate::common::http::{add_header, ClientRequest, ClientResponse, HttpClient};
+use crate::error::{invalid_field_value, messaging_error, Error};
+use reqwest::blocking::{Client, Response};
+use reqwest::Method;
+use tracing::info;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HttpClient for Client {
+    fn send(&self, request: &ClientRequest) -> Result<ClientResponse, Error> {
+        let method = Method::from_bytes(request.method.as_bytes())
+            .map_err(|_| invalid_field_value("method", request.method.as_str()))?;
+        let send = || {
+            let mut builder = self.request(method.clone(), request.url.as_str());
+            for (name, value) in &request.headers {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+            if let Some(timeout) = request.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if !request.body.is_empty() {
+                builder = builder.body(request.body.clone());
+            }
+            builder.send()
+        };
+        // A failure before a response is received, other than by timing out, is usually the
+        // result of reusing a persistent connection that the server closed while it was idle,
+        // which embedded devices tend to do after a short time; so the request is sent once more.
+        let response = match send() {
+            Err(e) if e.is_request() && !e.is_timeout() && !e.is_connect() => {
+                info!(error = %e, "request failed, retrying");
+                send()
+            }
+            result => result,
+        }
+        .map_err(messaging_error)?;
+        response_from(response)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn response_from(response: Response) -> Result<ClientResponse, Error> {
+    let status = response.status();
+    let mut result = ClientResponse::new(
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default(),
+    );
+    for (name, value) in response.headers() {
+        add_header(
+            &mut result.headers,
+            name.as_str(),
+            &String::from_utf8_lossy(value.as_bytes()),
+        );
+    }
+    result.body = response.bytes().map_err(messaging_error)?.to_vec();
+    Ok(result)
+}
@@ -0,0 +1,45 @@
+/*!
+The [`HttpClient`] implementation for the `ureq` agent, enabled by the `ureq` feature.
+*/
+
+use crate::common::http::{add_header, ClientRequest, ClientResponse, HttpClient, MAX_BODY_SIZE};
+use crate::error::{invalid_header_value, messaging_error, Error};
+use crate::syntax::HTTP_HEADER_CONTENT_LENGTH;
+use std::io::Read;
+use ureq::Agent;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HttpClient for Agent {
+    fn send(&self, request: &ClientRequest) -> Result<ClientResponse, Error> {
+        let mut builder = self.request(&request.method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.set(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = match builder.send_bytes(&request.body) {
+            // an error status is still a response.
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(messaging_error(e)),
+        };
+
+        let mut result = ClientResponse::new(response.status(), response.status_text());
+        for name in response.headers_names() {
+            for value in response.all(&name) {
+                add_header(&mut result.headers, &name, value);
+            }
+        }
+        let _ = response
+            .into_reader()
+            .take(MAX_BODY_SIZE as u64 + 1)
+            .read_to_end(&mut result.body)?;
+        if result.body.len() > MAX_BODY_SIZE {
+            return invalid_header_value(HTTP_HEADER_CONTENT_LENGTH, "").into();
+        }
+        Ok(result)
+    }
+}
@@ -1,11 +1,27 @@
 use crate::common::httpu::request::Request;
+use crate::discovery::search::SearchTarget;
+use crate::error::{invalid_header_value, Error, MessageFormatError};
+use crate::syntax::{HeaderName, HTTP_EXTENSION};
 use std::collections::HashMap;
 
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Builds a [`Request`]; header names and values are checked by [`build`](Self::build), so that a
+/// value containing a line break cannot inject additional headers into the message.
+///
 #[derive(Debug)]
 pub struct RequestBuilder {
     request: Request,
+    error: Option<MessageFormatError>,
 }
 
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
 impl RequestBuilder {
     pub fn new(message: &str) -> Self {
         RequestBuilder {
@@ -14,6 +30,7 @@ impl RequestBuilder {
                 resource: None,
                 headers: Default::default(),
             },
+            error: None,
         }
     }
 
@@ -37,16 +54,175 @@ impl RequestBuilder {
         self
     }
 
+    ///
+    /// Set the `HOST` header explicitly; by default the header is derived from the address the
+    /// request is sent to.
+    ///
+    pub fn host(&mut self, host: &str) -> &mut Self {
+        self.header(HeaderName::Host, host)
+    }
+
+    ///
+    /// Set the `MX` header, the maximum number of seconds a device may wait before responding
+    /// to a search; this must be between 1 and 120.
+    ///
+    pub fn mx(&mut self, seconds: u8) -> &mut Self {
+        if !(1..=120).contains(&seconds) {
+            self.fail(invalid_header_value(
+                HeaderName::Mx.as_str(),
+                seconds.to_string(),
+            ));
+        }
+        self.header(HeaderName::Mx, &seconds.to_string())
+    }
+
+    ///
+    /// Set the `MAN` header to the quoted `"ssdp:discover"` extension required by a search.
+    ///
+    pub fn man_discover(&mut self) -> &mut Self {
+        self.header(HeaderName::Man, HTTP_EXTENSION)
+    }
+
+    ///
+    /// Set the `ST` header to the search target.
+    ///
+    pub fn st(&mut self, search_target: &SearchTarget) -> &mut Self {
+        self.header(HeaderName::St, &search_target.to_string())
+    }
+
+    ///
+    /// Set the `CACHE-CONTROL` header to `max-age=` the number of seconds an advertisement
+    /// remains valid; this must be greater than zero.
+    ///
+    pub fn cache_control_max_age(&mut self, seconds: u32) -> &mut Self {
+        let value = format!("max-age={}", seconds);
+        if seconds == 0 {
+            self.fail(invalid_header_value(
+                HeaderName::CacheControl.as_str(),
+                &value,
+            ));
+        }
+        self.header(HeaderName::CacheControl, &value)
+    }
+
+    ///
+    /// Add a header known to this crate, see [`HeaderName`].
+    ///
+    pub fn header(&mut self, name: HeaderName, value: &str) -> &mut Self {
+        self.add_header(name.as_str(), value)
+    }
+
     pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
         self.request
             .headers
             .insert(name.to_string(), value.to_string());
         self
     }
+
+    ///
+    /// Return the request, or the first error recorded by a typed header method. An error is
+    /// also returned if the method or resource are not single tokens, a header name is not a
+    /// valid HTTP token, or a header value contains a control character such as CR or LF.
+    ///
+    pub fn build(self) -> Result<Request, Error> {
+        if let Some(error) = self.error {
+            return Err(error.into());
+        }
+        let request = self.request;
+        if !is_token(&request.message) {
+            return invalid_header_value("method", &request.message).into();
+        }
+        if let Some(resource) = &request.resource {
+            if resource.is_empty()
+                || resource
+                    .chars()
+                    .any(|c| c.is_whitespace() || c.is_control())
+            {
+                return invalid_header_value("resource", resource).into();
+            }
+        }
+        for (name, value) in &request.headers {
+            if !is_token(name) || !is_field_value(value) {
+                return invalid_header_value(name, value).into();
+            }
+        }
+        Ok(request)
+    }
+
+    fn fail(&mut self, error: MessageFormatError) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
 }
 
-impl From<RequestBuilder> for Request {
-    fn from(rb: RequestBuilder) -> Self {
-        rb.request
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+fn is_field_value(s: &str) -> bool {
+    !s.chars().any(|c| c.is_control() && c != '\t')
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{
+        HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_MAN, HTTP_HEADER_MX, HTTP_HEADER_ST,
+    };
+
+    #[test]
+    fn test_typed_headers() {
+        let mut builder = RequestBuilder::new("M-SEARCH");
+        builder
+            .man_discover()
+            .mx(3)
+            .st(&SearchTarget::RootDevice)
+            .cache_control_max_age(1800);
+        let request = builder.build().unwrap();
+        assert_eq!(
+            request.headers.get(HTTP_HEADER_MAN).unwrap(),
+            "\"ssdp:discover\""
+        );
+        assert_eq!(request.headers.get(HTTP_HEADER_MX).unwrap(), "3");
+        assert_eq!(
+            request.headers.get(HTTP_HEADER_ST).unwrap(),
+            "upnp:rootdevice"
+        );
+        assert_eq!(
+            request.headers.get(HTTP_HEADER_CACHE_CONTROL).unwrap(),
+            "max-age=1800"
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_rejected() {
+        let mut builder = RequestBuilder::new("M-SEARCH");
+        let _ = builder.mx(0);
+        assert!(builder.build().is_err());
+
+        let mut builder = RequestBuilder::new("NOTIFY");
+        let _ = builder.cache_control_max_age(0);
+        assert!(builder.build().is_err());
+
+        let mut builder = RequestBuilder::new("M-SEARCH");
+        let _ = builder.add_header("ST", "ssdp:all\r\nX-INJECTED: yes");
+        assert!(builder.build().is_err());
+
+        let mut builder = RequestBuilder::new("M-SEARCH");
+        let _ = builder.add_header("BAD NAME", "value");
+        assert!(builder.build().is_err());
+
+        assert!(RequestBuilder::new("M SEARCH").build().is_err());
     }
 }
@@ -0,0 +1,413 @@
+/*!
+This module provides the ability to record the datagrams sent and received over a [`Transport`],
+and to replay a recording later. Recordings are saved in a simple line-oriented text format, one
+datagram per line, which is easy to attach to an issue report and to edit by hand:
+
+```text
+<elapsed micros> <S|R> <peer address> <message>
+```
+
+Where `S` denotes a sent, and `R` a received, datagram and the message has any `\`, any
+non-printable bytes, and any line breaks escaped (`\\`, `\xNN`, `\r`, and `\n` respectively).
+
+# Example
+
+The following records a search, and then replays the recorded responses through the search
+response parser.
+
+```rust,no_run
+use upnp_rs::common::httpu::{Capture, RecordingTransport, create_udp_socket};
+use upnp_rs::discovery::search::{search_once, Options};
+use upnp_rs::SpecVersion;
+use std::sync::Arc;
+
+let socket = create_udp_socket(&"0.0.0.0:0".parse().unwrap(), &Default::default()).unwrap();
+let recorder = Arc::new(RecordingTransport::new(Arc::new(socket)));
+let mut options = Options::default_for(SpecVersion::V10);
+options.transport = Some(recorder.clone());
+let _ = search_once(options.clone()).unwrap();
+recorder.capture().save("search.capture").unwrap();
+
+let capture = Capture::load("search.capture").unwrap();
+options.transport = Some(Arc::new(capture.replay()));
+let responses = search_once(options).unwrap();
+```
+*/
+
+use crate::common::httpu::Transport;
+use crate::error::{invalid_value_for_type, Error};
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind as IOErrorKind, Result as IOResult, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Whether a captured datagram was sent or received.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+///
+/// A single captured datagram.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Datagram {
+    /// The time since recording started.
+    pub elapsed: Duration,
+    pub direction: Direction,
+    /// The address the datagram was sent to, or received from.
+    pub peer: SocketAddr,
+    pub bytes: Vec<u8>,
+}
+
+///
+/// A recorded sequence of datagrams.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capture {
+    datagrams: Vec<Datagram>,
+}
+
+///
+/// A transport that passes all operations to an inner transport, recording each datagram sent or
+/// received.
+///
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    started: Instant,
+    datagrams: Mutex<Vec<Datagram>>,
+}
+
+///
+/// A transport that returns the received datagrams of a capture, in order, and discards anything
+/// sent. Once all datagrams have been returned, receives time out.
+///
+#[derive(Debug)]
+pub struct ReplayTransport {
+    received: Mutex<VecDeque<Datagram>>,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Capture {
+    ///
+    /// All datagrams in the capture, in the order they were recorded.
+    ///
+    pub fn datagrams(&self) -> &[Datagram] {
+        &self.datagrams
+    }
+
+    ///
+    /// Create a transport that replays the received datagrams in this capture.
+    ///
+    pub fn replay(&self) -> ReplayTransport {
+        ReplayTransport {
+            received: Mutex::new(
+                self.datagrams
+                    .iter()
+                    .filter(|datagram| datagram.direction == Direction::Received)
+                    .cloned()
+                    .collect(),
+            ),
+            read_timeout: Mutex::new(None),
+        }
+    }
+
+    ///
+    /// Write the capture, in the format described in the module documentation.
+    ///
+    pub fn write_to(&self, w: &mut impl Write) -> Result<(), Error> {
+        for datagram in &self.datagrams {
+            writeln!(
+                w,
+                "{} {} {} {}",
+                datagram.elapsed.as_micros(),
+                match datagram.direction {
+                    Direction::Sent => DIRECTION_SENT,
+                    Direction::Received => DIRECTION_RECEIVED,
+                },
+                datagram.peer,
+                escape(&datagram.bytes)
+            )?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Read a capture, in the format described in the module documentation.
+    ///
+    pub fn read_from(r: impl BufRead) -> Result<Self, Error> {
+        let mut datagrams = Vec::new();
+        for line in r.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                datagrams.push(parse_line(&line)?);
+            }
+        }
+        Ok(Capture { datagrams })
+    }
+
+    ///
+    /// Save the capture to the file at `path`.
+    ///
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        self.write_to(&mut file)
+    }
+
+    ///
+    /// Load a capture from the file at `path`.
+    ///
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        RecordingTransport {
+            inner,
+            started: Instant::now(),
+            datagrams: Default::default(),
+        }
+    }
+
+    ///
+    /// A copy of everything recorded so far.
+    ///
+    pub fn capture(&self) -> Capture {
+        Capture {
+            datagrams: self.datagrams.lock().unwrap().clone(),
+        }
+    }
+
+    fn record(&self, direction: Direction, peer: SocketAddr, bytes: &[u8]) {
+        // recorded to the same precision as saved, so that a saved capture loads unchanged.
+        let elapsed = Duration::from_micros(self.started.elapsed().as_micros() as u64);
+        self.datagrams.lock().unwrap().push(Datagram {
+            elapsed,
+            direction,
+            peer,
+            bytes: bytes.to_vec(),
+        });
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send_to(&self, buf: &[u8], to: &SocketAddr) -> IOResult<usize> {
+        let sent = self.inner.send_to(buf, to)?;
+        self.record(Direction::Sent, *to, &buf[..sent]);
+        Ok(sent)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        let (received, from) = self.inner.recv_from(buf)?;
+        self.record(Direction::Received, from, &buf[..received]);
+        Ok((received, from))
+    }
+
+    fn peek_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        self.inner.peek_from(buf)
+    }
+
+    fn read_timeout(&self) -> IOResult<Option<Duration>> {
+        self.inner.read_timeout()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IOResult<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ReplayTransport {
+    fn receive(&self, buf: &mut [u8], remove: bool) -> IOResult<(usize, SocketAddr)> {
+        let mut received = self.received.lock().unwrap();
+        let datagram = if remove {
+            received.pop_front()
+        } else {
+            received.front().cloned()
+        };
+        match datagram {
+            Some(datagram) => {
+                let length = datagram.bytes.len().min(buf.len());
+                buf[..length].copy_from_slice(&datagram.bytes[..length]);
+                Ok((length, datagram.peer))
+            }
+            None => {
+                drop(received);
+                if let Some(timeout) = *self.read_timeout.lock().unwrap() {
+                    std::thread::sleep(timeout);
+                }
+                Err(IOErrorKind::WouldBlock.into())
+            }
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_to(&self, buf: &[u8], _: &SocketAddr) -> IOResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        self.receive(buf, true)
+    }
+
+    fn peek_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        self.receive(buf, false)
+    }
+
+    fn read_timeout(&self) -> IOResult<Option<Duration>> {
+        Ok(*self.read_timeout.lock().unwrap())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IOResult<()> {
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const DIRECTION_SENT: &str = "S";
+
+const DIRECTION_RECEIVED: &str = "R";
+
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for byte in bytes {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            b'\r' => escaped.push_str("\\r"),
+            b'\n' => escaped.push_str("\\n"),
+            b' '..=b'~' => escaped.push(*byte as char),
+            _ => {
+                let _ = write!(escaped, "\\x{:02X}", byte);
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'\\' {
+            match chars.next()? {
+                b'\\' => bytes.push(b'\\'),
+                b'r' => bytes.push(b'\r'),
+                b'n' => bytes.push(b'\n'),
+                b'x' => {
+                    let hex = [chars.next()?, chars.next()?];
+                    bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                }
+                _ => return None,
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(bytes)
+}
+
+fn parse_line(line: &str) -> Result<Datagram, Error> {
+    let invalid = || invalid_value_for_type("Capture", line);
+    let mut parts = line.splitn(4, ' ');
+    let elapsed = parts
+        .next()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or_else(invalid)?;
+    let direction = match parts.next() {
+        Some(DIRECTION_SENT) => Direction::Sent,
+        Some(DIRECTION_RECEIVED) => Direction::Received,
+        _ => return Err(invalid().into()),
+    };
+    let peer = parts
+        .next()
+        .and_then(|s| s.parse::<SocketAddr>().ok())
+        .ok_or_else(invalid)?;
+    let bytes = unescape(parts.next().unwrap_or_default()).ok_or_else(invalid)?;
+    Ok(Datagram {
+        elapsed: Duration::from_micros(elapsed),
+        direction,
+        peer,
+        bytes,
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::LoopbackNetwork;
+
+    #[test]
+    fn test_escape_round_trip() {
+        let bytes = b"NOTIFY * HTTP/1.1\r\nX-PATH: C:\\upnp\r\n\x00\xff\r\n\r\n";
+        assert_eq!(unescape(&escape(bytes)).unwrap(), bytes.to_vec());
+    }
+
+    #[test]
+    fn test_record_save_and_replay() {
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+        let recorder = RecordingTransport::new(Arc::new(client));
+
+        let _ = recorder
+            .send_to(b"M-SEARCH", &device.local_address())
+            .unwrap();
+        let _ = device
+            .send_to(
+                b"HTTP/1.1 200 OK\r\n\r\n",
+                &"10.0.0.2:5000".parse().unwrap(),
+            )
+            .unwrap();
+        let mut buf = [0u8; 64];
+        let _ = recorder.recv_from(&mut buf).unwrap();
+
+        let mut saved = Vec::new();
+        recorder.capture().write_to(&mut saved).unwrap();
+        let capture = Capture::read_from(saved.as_slice()).unwrap();
+        assert_eq!(capture, recorder.capture());
+        assert_eq!(capture.datagrams().len(), 2);
+        assert_eq!(capture.datagrams()[0].direction, Direction::Sent);
+
+        let replay = capture.replay();
+        let (received, from) = replay.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..received], b"HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(from, device.local_address());
+        assert!(replay.recv_from(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_malformed_line() {
+        assert!(Capture::read_from("12 X 10.0.0.1:1900 hello".as_bytes()).is_err());
+        assert!(Capture::read_from("12 R 10.0.0.1:1900 \\q".as_bytes()).is_err());
+    }
+}
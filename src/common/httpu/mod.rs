@@ -1,16 +1,33 @@
 /*!
 This module implements the _HTTP Multicast UDP_ (HTTPMU) and _HTTP Unicast UDP_ (HTTPU)  specified
 components.
+
+Message building and parsing, and the functions that send and receive using a [`Transport`], are
+always available; the functions that create operating system sockets require the `net` feature.
 */
 
-use crate::common::interface;
 use crate::common::interface::IP;
-use crate::error::{invalid_socket_value, Error};
+use crate::error::{message_truncated, Error};
+use crate::observer::{self, MessageKind};
+use crate::syntax::{HTTP_METHOD_NOTIFY, HTTP_METHOD_SEARCH};
 use std::convert::TryFrom;
 use std::io::ErrorKind as IOErrorKind;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
-use std::time::Duration;
-use tracing::{debug, error, trace};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, trace, warn};
+
+#[cfg(feature = "net")]
+use crate::common::interface;
+#[cfg(feature = "net")]
+use crate::error::{invalid_socket_value, multicast_join_failed};
+#[cfg(feature = "net")]
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+#[cfg(feature = "net")]
+use std::io::Read;
+#[cfg(feature = "net")]
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6, TcpListener, UdpSocket};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -19,73 +36,117 @@ use tracing::{debug, error, trace};
 //type CallbackFn = fn(&Response) -> bool;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
 pub struct Options {
     pub(crate) network_interface: Option<String>,
     pub(crate) network_version: Option<IP>,
     pub(crate) local_port: u16,
-    pub(crate) recv_timeout: u64,
+    pub(crate) recv_timeout: Duration,
+    pub(crate) deadline: Option<Duration>,
     pub(crate) packet_ttl: u32,
-    pub(crate) local_network_only: bool,
+    pub(crate) multicast_ttl: u32,
     pub(crate) loop_back_also: bool,
+    pub(crate) buffer_size: usize,
+    pub(crate) oversized: Oversized,
+    pub(crate) reuse_address: bool,
+    pub(crate) reuse_port: bool,
+    pub(crate) bind_to_device: bool,
+    pub(crate) send_count: u8,
+    pub(crate) send_interval: Duration,
+    pub(crate) transport: Option<Arc<dyn Transport>>,
     //    pub callback: Option<CallbackFn>,
 }
 
+///
+/// Determines how a received datagram larger than the receive buffer is handled.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oversized {
+    /// Grow the buffer, up to `MAX_BUFFER_SIZE`, until the whole datagram can be read.
+    GrowBuffer,
+    /// Discard the datagram and record a `MessageTruncated` error for it, with its source address,
+    /// in the failures of the receive; other datagrams are still received.
+    Error,
+}
+
+///
+/// The datagrams received in reply to a message. Those that could not be parsed as an HTTP
+/// response are kept separately, with the address they were received from, so that one malformed
+/// device does not hide all the others.
+///
+#[derive(Debug, Default)]
+pub struct Received {
+    /// The datagrams parsed as HTTP responses.
+    pub responses: Vec<Response>,
+    /// The address each unparseable datagram was received from, where known, and the reason it
+    /// was rejected.
+    pub failed: Vec<(Option<SocketAddr>, Error)>,
+}
+
+///
+/// A token used to cancel a receive loop from another thread; all clones of a token share the same
+/// state, so cancelling any clone cancels all.
+///
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
 pub const DEFAULT_BUFFER_SIZE: usize = 1500;
 
-pub const DEFAULT_RECV_TIMEOUT: u64 = 2;
+///
+/// The largest payload that can be carried in a single UDP datagram.
+///
+pub const MAX_BUFFER_SIZE: usize = 65_507;
+
+pub const DEFAULT_RECV_TIMEOUT: Duration = Duration::from_secs(2);
+
+///
+/// The multicast TTL, or IPv6 hop limit, recommended by UDA 1.1 and later; UDA 1.0 recommends `4`.
+///
+pub const DEFAULT_MULTICAST_TTL: u32 = 2;
+
+///
+/// The delay between repeated sends of the same message, where `send_count` is greater than one.
+///
+pub const DEFAULT_SEND_INTERVAL: Duration = Duration::from_millis(100);
 
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+#[cfg(feature = "net")]
 pub fn create_multicast_socket(
     to_address: &SocketAddr,
     options: &Options,
 ) -> Result<UdpSocket, Error> {
     debug!("create_multicast_socket - options: {:?}", options);
-    let local_address = match interface::ip_address_for_interface(
-        &options.network_interface,
-        &options.network_version,
-    ) {
-        None => match &options.network_version {
-            Some(IP::V6) => SocketAddr::V6(SocketAddrV6::new(
-                Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0),
-                options.local_port,
-                0,
-                0,
-            )),
-            _ => SocketAddr::V4(SocketAddrV4::new(
-                Ipv4Addr::new(0, 0, 0, 0),
-                options.local_port,
-            )),
-        },
-        Some(address) => SocketAddr::new(address, options.local_port),
-    };
+    let local_address = local_address_for(options, options.local_port)?;
     trace!(
         "create_multicast_socket - binding to local_address: {:?}",
         local_address
     );
-    let socket = UdpSocket::bind(local_address)?;
+    let socket = create_udp_socket(&local_address, options)?;
 
     trace!("create_multicast_socket - setting socket options");
     socket.set_nonblocking(false)?;
     socket.set_ttl(options.packet_ttl)?;
-    socket.set_read_timeout(Some(Duration::from_secs(options.recv_timeout)))?;
+    socket.set_read_timeout(Some(options.recv_timeout))?;
     match (to_address, local_address) {
         (SocketAddr::V4(to_address), SocketAddr::V4(local_address)) => {
-            socket.join_multicast_v4(to_address.ip(), local_address.ip())?;
-            socket.set_multicast_ttl_v4(if options.local_network_only { 1 } else { 10 })?;
+            socket
+                .join_multicast_v4(to_address.ip(), local_address.ip())
+                .map_err(|e| multicast_join_failed(to_address.ip().to_string(), e))?;
+            socket.set_multicast_ttl_v4(options.multicast_ttl)?;
             socket.set_multicast_loop_v4(options.loop_back_also)?;
         }
         (SocketAddr::V6(_), SocketAddr::V6(_)) => {
+            SockRef::from(&socket).set_multicast_hops_v6(options.multicast_ttl)?;
             socket.set_multicast_loop_v6(options.loop_back_also)?;
         }
         _ => {
-            return invalid_socket_value(
-                "to, local",
-                &format!("{}, {}", to_address, local_address),
-            )
-            .into();
+            return invalid_socket_value("to, local", format!("{}, {}", to_address, local_address))
+                .into();
         }
     }
 
@@ -94,20 +155,51 @@ pub fn create_multicast_socket(
         socket,
         socket.read_timeout()?,
         socket.ttl()?,
-        socket.multicast_ttl_v4()?
+        options.multicast_ttl
     );
 
     Ok(socket)
 }
 
+///
+/// Create a UDP socket bound to `local_address`. Depending on `options` the socket may share its
+/// port with other sockets on the host (`SO_REUSEADDR`, and `SO_REUSEPORT` where supported), which
+/// is necessary to bind the SSDP port alongside other UPnP stacks, and may be bound to the network
+/// interface named in the options (`SO_BINDTODEVICE`, Linux only).
+///
+#[cfg(feature = "net")]
+pub fn create_udp_socket(
+    local_address: &SocketAddr,
+    options: &Options,
+) -> Result<UdpSocket, Error> {
+    let socket = Socket::new(
+        Domain::for_address(*local_address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if options.reuse_address {
+        socket.set_reuse_address(true)?;
+    }
+    if options.reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    if options.bind_to_device {
+        match &options.network_interface {
+            Some(selector) => bind_to_device(&socket, &interface::find_interface(selector)?.name)?,
+            None => return invalid_socket_value("bind_to_device", "no network_interface").into(),
+        }
+    }
+    socket.bind(&(*local_address).into())?;
+    Ok(socket.into())
+}
+
 pub fn multicast(
     message: &Request,
     to_address: &SocketAddr,
     options: &Options,
 ) -> Result<Vec<Response>, Error> {
-    let socket = create_multicast_socket(to_address, options)?;
-
-    multicast_using(message, to_address, &socket)
+    multicast_until(message, to_address, options, &CancellationToken::default())
+        .map(|received| received.responses)
 }
 
 pub fn multicast_once(
@@ -115,53 +207,181 @@ pub fn multicast_once(
     to_address: &SocketAddr,
     options: &Options,
 ) -> Result<(), Error> {
-    let socket = create_multicast_socket(to_address, options)?;
-
-    multicast_once_using(message, to_address, &socket)
+    match &options.transport {
+        Some(transport) => multicast_once_using(message, to_address, transport.as_ref()),
+        None => multicast_once_using(
+            message,
+            to_address,
+            default_transport(to_address, options)?.as_ref(),
+        ),
+    }
 }
 
 pub fn multicast_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
+    socket: &dyn Transport,
+) -> Result<Vec<Response>, Error> {
+    multicast_using_with(message, to_address, socket, &Default::default())
+}
+
+///
+/// Send `message` using `socket` and collect responses until the socket's read timeout expires,
+/// using the buffer size and handling of oversized messages from `options`.
+///
+pub fn multicast_using_with(
+    message: &Request,
+    to_address: &SocketAddr,
+    socket: &dyn Transport,
+    options: &Options,
 ) -> Result<Vec<Response>, Error> {
+    multicast_using_until(
+        message,
+        to_address,
+        socket,
+        options,
+        &CancellationToken::default(),
+    )
+    .map(|received| received.responses)
+}
+
+///
+/// As `multicast`, but the receive loop ends early, returning the responses received so far, when
+/// `cancel` is cancelled. Datagrams that could not be parsed are returned in `failed`, rather than
+/// being discarded.
+///
+pub fn multicast_until(
+    message: &Request,
+    to_address: &SocketAddr,
+    options: &Options,
+    cancel: &CancellationToken,
+) -> Result<Received, Error> {
+    match &options.transport {
+        Some(transport) => {
+            multicast_using_until(message, to_address, transport.as_ref(), options, cancel)
+        }
+        None => multicast_using_until(
+            message,
+            to_address,
+            default_transport(to_address, options)?.as_ref(),
+            options,
+            cancel,
+        ),
+    }
+}
+
+///
+/// Send `message` using `socket` and collect responses until the socket's read timeout has
+/// elapsed, or until `cancel` is cancelled. The socket is polled at short intervals so that
+/// cancellation takes effect promptly; the socket's read timeout is restored before returning.
+///
+/// If `options` requests more than one send, the message is re-sent at the configured interval
+/// while receiving, and the receive window is measured from the last send. If `options` sets a
+/// deadline the loop ends once it has elapsed from the first send, whatever the receive window.
+///
+/// A datagram that cannot be parsed is returned in `failed`, with the address it was received
+/// from; only a failure of the socket itself ends the loop with an error.
+///
+pub fn multicast_using_until(
+    message: &Request,
+    to_address: &SocketAddr,
+    socket: &dyn Transport,
+    options: &Options,
+    cancel: &CancellationToken,
+) -> Result<Received, Error> {
+    let read_timeout = socket.read_timeout()?;
+    let window = read_timeout.unwrap_or(options.recv_timeout);
+
     multicast_send_using(message, to_address, socket)?;
 
-    let mut responses: Vec<Response> = Default::default();
+    let resend = Resend {
+        message,
+        to_address,
+        remaining: options.send_count.saturating_sub(1),
+        interval: options.send_interval,
+        next: Instant::now() + options.send_interval,
+    };
+    let result = receive_until(socket, options, window, resend, cancel);
+    socket.set_read_timeout(read_timeout)?;
+    result
+}
 
+///
+/// Create a TCP listener, bound to the interface selected in `options`, on which devices may
+/// return search responses. This is the 2.0 `TCPPORT.UPNP.ORG` reply mechanism.
+///
+#[cfg(feature = "net")]
+pub fn create_tcp_listener(port: u16, options: &Options) -> Result<TcpListener, Error> {
+    let local_address = local_address_for(options, port)?;
+    trace!(
+        "create_tcp_listener - binding to local_address: {:?}",
+        local_address
+    );
+    let listener = TcpListener::bind(local_address)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+///
+/// Accept all pending connections on `listener` and read any responses sent over them. This does
+/// not wait for new connections, it is expected that this is called after the corresponding UDP
+/// receive window has closed. Each connection is read for at most the receive timeout in
+/// `options`, and up to `MAX_BUFFER_SIZE` bytes. A connection that cannot be read, or is larger
+/// than this, or a message that cannot be parsed, is returned in `failed`; only a failure of the
+/// listener itself is an error.
+///
+#[cfg(feature = "net")]
+pub fn tcp_receive_using(listener: &TcpListener, options: &Options) -> Result<Received, Error> {
+    let mut received: Received = Default::default();
     loop {
-        let mut buf = [0u8; DEFAULT_BUFFER_SIZE];
-        trace!(
-            "multicast_using - blocking on recv_from, buffer size {}",
-            DEFAULT_BUFFER_SIZE
-        );
-        match socket.recv_from(&mut buf) {
-            Ok((received, from)) => {
-                trace!(
-                    "multicast_using - received {} bytes from {:?}",
-                    received,
-                    from,
-                );
-                responses.push(Response::try_from(&buf[..received])?);
+        match listener.accept() {
+            Ok((stream, from)) => {
+                trace!("tcp_receive_using - accepted connection from {:?}", from);
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(options.recv_timeout))?;
+                let mut buf: Vec<u8> = Default::default();
+                if let Err(e) = stream
+                    .take(MAX_BUFFER_SIZE as u64 + 1)
+                    .read_to_end(&mut buf)
+                {
+                    if e.kind() != IOErrorKind::WouldBlock && e.kind() != IOErrorKind::TimedOut {
+                        warn!("tcp_receive_using - stream read returned error: {:?}", e);
+                        received.failed.push((Some(from), e.into()));
+                        continue;
+                    }
+                }
+                if buf.len() > MAX_BUFFER_SIZE {
+                    warn!(
+                        "tcp_receive_using - connection from {:?} exceeds {} bytes, discarded",
+                        from, MAX_BUFFER_SIZE
+                    );
+                    received
+                        .failed
+                        .push((Some(from), message_truncated(MAX_BUFFER_SIZE).into()));
+                    continue;
+                }
+                for message in split_messages(&buf) {
+                    received.push(message, from);
+                }
             }
             Err(e) => {
                 if e.kind() == IOErrorKind::WouldBlock {
-                    trace!("multicast_using - socket timed out, no data");
+                    trace!("tcp_receive_using - no further pending connections");
                     break;
                 } else {
-                    error!("multicast_using - socket read returned error: {:?}", e);
-                    return Err(Error::NetworkTransport(e));
+                    error!("tcp_receive_using - accept returned error: {:?}", e);
+                    return Err(e.into());
                 }
             }
         }
     }
-    Ok(responses)
+    Ok(received)
 }
 
 pub fn multicast_once_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
+    socket: &dyn Transport,
 ) -> Result<(), Error> {
     multicast_send_using(message, to_address, socket)
 }
@@ -177,26 +397,314 @@ impl Default for Options {
             network_version: None,
             local_port: 0,
             recv_timeout: DEFAULT_RECV_TIMEOUT,
+            deadline: None,
             packet_ttl: 2,
-            local_network_only: false,
+            multicast_ttl: DEFAULT_MULTICAST_TTL,
             loop_back_also: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            oversized: Oversized::GrowBuffer,
+            reuse_address: false,
+            reuse_port: false,
+            bind_to_device: false,
+            send_count: 1,
+            send_interval: DEFAULT_SEND_INTERVAL,
+            transport: None,
             //callback: None,
         }
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl Received {
+    ///
+    /// Add the responses, and failures, of `other` to those of this value.
+    ///
+    pub fn append(&mut self, mut other: Received) {
+        self.responses.append(&mut other.responses);
+        self.failed.append(&mut other.failed);
+    }
+
+    fn push(&mut self, message: &[u8], from: SocketAddr) {
+        match Response::try_from(message) {
+            Ok(response) => self.responses.push(response.with_source(from)),
+            Err(e) => {
+                warn!("push - ignoring malformed response from {:?}: {}", from, e);
+                self.failed.push((Some(from), e.into()));
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// Cancel any receive loop using this token, or a clone of it.
+    ///
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+struct Resend<'a> {
+    message: &'a Request,
+    to_address: &'a SocketAddr,
+    remaining: u8,
+    interval: Duration,
+    next: Instant,
+}
+
+#[derive(Debug)]
+enum ReceiveError {
+    Truncated(SocketAddr),
+    Socket(std::io::Error),
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The transport used where `options` does not provide one; a multicast socket if the `net`
+/// feature is enabled, else an error.
+///
+#[cfg(feature = "net")]
+pub(crate) fn default_transport(
+    to_address: &SocketAddr,
+    options: &Options,
+) -> Result<Box<dyn Transport>, Error> {
+    Ok(Box::new(create_multicast_socket(to_address, options)?))
+}
+
+#[cfg(not(feature = "net"))]
+pub(crate) fn default_transport(_: &SocketAddr, _: &Options) -> Result<Box<dyn Transport>, Error> {
+    crate::error::unsupported_operation("multicast without a transport").into()
+}
+
+#[cfg(feature = "net")]
+fn local_address_for(options: &Options, port: u16) -> Result<SocketAddr, Error> {
+    Ok(
+        match interface::ip_address_for_interface(
+            &options.network_interface,
+            &options.network_version,
+        )? {
+            None => match &options.network_version {
+                Some(IP::V6) => SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0),
+                    port,
+                    0,
+                    0,
+                )),
+                _ => SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port)),
+            },
+            Some(address) => SocketAddr::new(address, port),
+        },
+    )
+}
+
+///
+/// Receive a single datagram into `buf`. The datagram is first peeked with one byte more than the
+/// buffer holds, so that a datagram that does not fit can be detected rather than silently
+/// truncated; depending on `oversized` the buffer is then grown or the datagram discarded.
+///
+fn receive_from(
+    socket: &dyn Transport,
+    buf: &mut Vec<u8>,
+    oversized: Oversized,
+) -> Result<(usize, SocketAddr), ReceiveError> {
+    loop {
+        let buffer_size = buf.len();
+        buf.resize(buffer_size + 1, 0);
+        let peeked = socket.peek_from(buf);
+        buf.truncate(buffer_size);
+        let (peeked, _) = peeked.map_err(ReceiveError::Socket)?;
+        if peeked <= buffer_size {
+            return socket.recv_from(buf).map_err(ReceiveError::Socket);
+        }
+        match oversized {
+            Oversized::GrowBuffer if buffer_size < MAX_BUFFER_SIZE => {
+                let grown = (buffer_size * 2).min(MAX_BUFFER_SIZE);
+                debug!(
+                    "receive_from - growing buffer from {} to {}",
+                    buffer_size, grown
+                );
+                buf.resize(grown, 0);
+            }
+            _ => {
+                let (_, from) = socket.recv_from(buf).map_err(ReceiveError::Socket)?;
+                return Err(ReceiveError::Truncated(from));
+            }
+        }
+    }
+}
+
+fn receive_until(
+    socket: &dyn Transport,
+    options: &Options,
+    window: Duration,
+    mut resend: Resend<'_>,
+    cancel: &CancellationToken,
+) -> Result<Received, Error> {
+    let mut collected: Received = Default::default();
+    let mut buf = vec![0u8; options.buffer_size.clamp(1, MAX_BUFFER_SIZE)];
+    let started = Instant::now();
+    let mut deadline = started + window;
+    if let Some(limit) = options.deadline {
+        deadline = deadline.min(started + limit);
+    }
+
+    loop {
+        if cancel.is_cancelled() {
+            debug!("receive_until - cancelled");
+            break;
+        }
+        let now = Instant::now();
+        if resend.remaining > 0 && now >= resend.next {
+            trace!("receive_until - re-sending, {} remaining", resend.remaining);
+            multicast_send_using(resend.message, resend.to_address, socket)?;
+            resend.remaining -= 1;
+            resend.next = now + resend.interval;
+            deadline = now + window;
+            if let Some(limit) = options.deadline {
+                deadline = deadline.min(started + limit);
+            }
+        }
+        let mut remaining = deadline.saturating_duration_since(now);
+        if remaining.is_zero() {
+            trace!("receive_until - receive window closed");
+            break;
+        }
+        if resend.remaining > 0 {
+            remaining = remaining.min(resend.next.saturating_duration_since(now));
+        }
+        socket.set_read_timeout(Some(
+            remaining
+                .min(CANCEL_POLL_INTERVAL)
+                .max(Duration::from_millis(1)),
+        ))?;
+        trace!(
+            "receive_until - blocking on recv_from, buffer size {}",
+            buf.len()
+        );
+        match receive_from(socket, &mut buf, options.oversized) {
+            Ok((received, from)) => {
+                trace!(
+                    "receive_until - received {} bytes from {:?}",
+                    received,
+                    from,
+                );
+                collected.push(&buf[..received], from);
+            }
+            Err(ReceiveError::Truncated(from)) => {
+                warn!(
+                    "receive_until - message from {:?} larger than buffer size {}",
+                    from,
+                    buf.len()
+                );
+                collected
+                    .failed
+                    .push((Some(from), message_truncated(buf.len()).into()));
+            }
+            Err(ReceiveError::Socket(e))
+                if e.kind() == IOErrorKind::WouldBlock || e.kind() == IOErrorKind::TimedOut => {}
+            Err(ReceiveError::Socket(e)) => {
+                error!("receive_until - socket read returned error: {:?}", e);
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(collected)
+}
+
+#[cfg(all(
+    feature = "net",
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos"))
+))]
+fn set_reuse_port(socket: &Socket) -> Result<(), Error> {
+    Ok(socket.set_reuse_port(true)?)
+}
+
+#[cfg(all(
+    feature = "net",
+    not(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))
+))]
+fn set_reuse_port(_: &Socket) -> Result<(), Error> {
+    crate::error::unsupported_operation("SO_REUSEPORT").into()
+}
+
+#[cfg(all(
+    feature = "net",
+    any(target_os = "android", target_os = "fuchsia", target_os = "linux")
+))]
+fn bind_to_device(socket: &Socket, name: &str) -> Result<(), Error> {
+    Ok(socket.bind_device(Some(name.as_bytes()))?)
+}
+
+#[cfg(all(
+    feature = "net",
+    not(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))
+))]
+fn bind_to_device(_: &Socket, _: &str) -> Result<(), Error> {
+    crate::error::unsupported_operation("SO_BINDTODEVICE").into()
+}
+
+#[cfg(feature = "net")]
+fn split_messages(all: &[u8]) -> Vec<&[u8]> {
+    static BLANK_LINE: &[u8] = b"\r\n\r\n";
+    let mut messages = Vec::new();
+    let mut remaining = all;
+    while !remaining.is_empty() {
+        match remaining
+            .windows(BLANK_LINE.len())
+            .position(|window| window == BLANK_LINE)
+        {
+            None => {
+                messages.push(remaining);
+                break;
+            }
+            Some(end) => {
+                let (message, rest) = remaining.split_at(end + BLANK_LINE.len());
+                messages.push(message);
+                remaining = rest;
+            }
+        }
+    }
+    messages
+}
+
 #[inline]
 fn multicast_send_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
+    socket: &dyn Transport,
 ) -> Result<(), Error> {
-    let message: String = message.into();
+    let kind = match message.message.as_str() {
+        HTTP_METHOD_SEARCH => Some(MessageKind::Search),
+        HTTP_METHOD_NOTIFY => Some(MessageKind::Notify),
+        _ => None,
+    };
+    let message = message.to_string_for(to_address);
     socket.send_to(message.as_bytes(), to_address)?;
+    if let Some(kind) = kind {
+        observer::sent(kind);
+    }
     Ok(())
 }
 
@@ -214,4 +722,237 @@ pub use request::Request;
 
 #[doc(hidden)]
 mod response;
-pub use response::Response;
+pub(crate) use response::split_at_body;
+pub use response::{RawResponse, Response};
+
+#[doc(hidden)]
+mod transport;
+pub use transport::{LoopbackNetwork, LoopbackTransport, Transport};
+
+#[doc(hidden)]
+mod capture;
+pub use capture::{Capture, Datagram, Direction, RecordingTransport, ReplayTransport};
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    const RESPONSE: &str =
+        "HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\nUSN: uuid:1::upnp:rootdevice\r\n\r\n";
+
+    #[test]
+    fn test_split_messages() {
+        let both = format!("{}{}", RESPONSE, RESPONSE);
+        assert_eq!(split_messages(both.as_bytes()).len(), 2);
+        assert_eq!(split_messages(b"").len(), 0);
+    }
+
+    fn oversized_response() -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\nX-PADDING: {}\r\n\r\n",
+            "x".repeat(DEFAULT_BUFFER_SIZE * 2)
+        )
+    }
+
+    fn send_to_self(message: &str) -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let _ = socket
+            .send_to(message.as_bytes(), socket.local_addr().unwrap())
+            .unwrap();
+        socket
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_shared_port() {
+        let options = Options {
+            reuse_address: true,
+            reuse_port: true,
+            ..Default::default()
+        };
+        let first = create_udp_socket(&"127.0.0.1:0".parse().unwrap(), &options).unwrap();
+        let address = first.local_addr().unwrap();
+        assert!(create_udp_socket(&address, &options).is_ok());
+        assert!(create_udp_socket(&address, &Default::default()).is_err());
+    }
+
+    #[test]
+    fn test_multicast_ttl() {
+        let options = Options {
+            multicast_ttl: 7,
+            ..Default::default()
+        };
+        let socket =
+            create_multicast_socket(&"239.255.255.250:1900".parse().unwrap(), &options).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_cancel_receive() {
+        let sink = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let _ = socket.send_to(RESPONSE.as_bytes(), socket.local_addr().unwrap());
+
+        let cancel = CancellationToken::new();
+        let canceller = {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                cancel.cancel()
+            })
+        };
+        let started = Instant::now();
+        let responses = multicast_using_until(
+            &RequestBuilder::new("M-SEARCH").build().unwrap(),
+            &sink.local_addr().unwrap(),
+            &socket,
+            &Default::default(),
+            &cancel,
+        )
+        .unwrap()
+        .responses;
+        canceller.join().unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].raw().bytes, RESPONSE.as_bytes());
+        assert_eq!(
+            responses[0].raw().source,
+            Some(socket.local_addr().unwrap())
+        );
+        assert_eq!(
+            socket.read_timeout().unwrap(),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_repeated_send() {
+        let target = UdpSocket::bind("127.0.0.1:0").unwrap();
+        target
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let options = Options {
+            send_count: 3,
+            send_interval: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        let responses = multicast_using_with(
+            &RequestBuilder::new("M-SEARCH").build().unwrap(),
+            &target.local_addr().unwrap(),
+            &socket,
+            &options,
+        )
+        .unwrap();
+        assert!(responses.is_empty());
+
+        let mut buf = [0u8; DEFAULT_BUFFER_SIZE];
+        for _ in 0..3 {
+            assert!(target.recv_from(&mut buf).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_receive_grows_buffer() {
+        let message = oversized_response();
+        let socket = send_to_self(&message);
+        let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE];
+
+        let (received, _) = receive_from(&socket, &mut buf, Oversized::GrowBuffer).unwrap();
+        assert_eq!(received, message.len());
+        assert_eq!(buf.len(), DEFAULT_BUFFER_SIZE * 4);
+        let response = Response::try_from(&buf[..received]).unwrap();
+        assert_eq!(response.header("X-PADDING").unwrap().len(), 3000);
+    }
+
+    #[test]
+    fn test_receive_reports_truncation() {
+        let socket = send_to_self(&oversized_response());
+        let _ = socket
+            .send_to(RESPONSE.as_bytes(), socket.local_addr().unwrap())
+            .unwrap();
+        let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE];
+
+        assert!(matches!(
+            receive_from(&socket, &mut buf, Oversized::Error),
+            Err(ReceiveError::Truncated(_))
+        ));
+        let (received, _) = receive_from(&socket, &mut buf, Oversized::Error).unwrap();
+        assert_eq!(&buf[..received], RESPONSE.as_bytes());
+    }
+
+    #[test]
+    fn test_receive_continues_after_truncation() {
+        let socket = send_to_self(&oversized_response());
+        let _ = socket
+            .send_to(RESPONSE.as_bytes(), socket.local_addr().unwrap())
+            .unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(300)))
+            .unwrap();
+        let sink = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let options = Options {
+            oversized: Oversized::Error,
+            ..Default::default()
+        };
+
+        let received = multicast_using_until(
+            &RequestBuilder::new("M-SEARCH").build().unwrap(),
+            &sink.local_addr().unwrap(),
+            &socket,
+            &options,
+            &CancellationToken::default(),
+        )
+        .unwrap();
+        assert_eq!(received.responses.len(), 1);
+        assert_eq!(received.failed.len(), 1);
+        assert_eq!(received.failed[0].0, Some(socket.local_addr().unwrap()));
+    }
+
+    #[test]
+    fn test_tcp_receive() {
+        let options = Options {
+            network_version: Some(IP::V4),
+            ..Default::default()
+        };
+        let listener = create_tcp_listener(0, &options).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(RESPONSE.as_bytes()).unwrap();
+        drop(stream);
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"HTTP/1.1 404 Not Found\r\n\r\n").unwrap();
+        drop(stream);
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(&[b' '; MAX_BUFFER_SIZE + 1]).unwrap();
+        drop(stream);
+
+        let received = tcp_receive_using(&listener, &options).unwrap();
+        assert_eq!(received.responses.len(), 1);
+        assert_eq!(
+            received.responses[0].header("ST").unwrap(),
+            "upnp:rootdevice"
+        );
+        assert_eq!(received.failed.len(), 2);
+    }
+}
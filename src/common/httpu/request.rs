@@ -1,12 +1,21 @@
 /*!
-What's this all about then?
+This module provides the HTTPU request type, used both to construct outgoing requests (see
+[`RequestBuilder`](../struct.RequestBuilder.html)) and to parse incoming `NOTIFY` and `M-SEARCH`
+requests.
 */
 
+use crate::common::httpu::response::{decode_headers, split_at_body};
+use crate::error::{invalid_header_value, MessageFormatError};
 use crate::syntax::{
-    HTTP_HEADER_LINE_SEP, HTTP_HEADER_SEP, HTTP_MATCH_ANY_RESOURCE, HTTP_PROTOCOL_NAME,
-    HTTP_PROTOCOL_VERSION,
+    Method, HTTP_HEADER_HOST, HTTP_HEADER_LINE_SEP, HTTP_HEADER_SEP, HTTP_MATCH_ANY_RESOURCE,
+    HTTP_PROTOCOL_NAME, HTTP_PROTOCOL_VERSION,
 };
+use regex::Regex;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::str::{from_utf8, FromStr};
+use tracing::{error, trace};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -24,6 +33,66 @@ pub struct Request {
 // ------------------------------------------------------------------------------------------------
 
 impl Request {
+    ///
+    /// The request method, for example `NOTIFY` or `M-SEARCH`.
+    ///
+    pub fn method(&self) -> &str {
+        &self.message
+    }
+
+    ///
+    /// The request method, if it is one of those known to this crate.
+    ///
+    pub fn known_method(&self) -> Option<Method> {
+        Method::from_str(&self.message).ok()
+    }
+
+    ///
+    /// The requested resource, `None` indicates the `*` (any resource) form.
+    ///
+    pub fn resource(&self) -> Option<&str> {
+        self.resource.as_deref()
+    }
+
+    ///
+    /// Return the value of the named header, header names are compared case-insensitively.
+    ///
+    pub fn header<N: AsRef<str>>(&self, name: N) -> Option<&str> {
+        let name = name.as_ref();
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    ///
+    /// All headers in this request; for parsed requests all header names are upper case.
+    ///
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    ///
+    /// Format this request to be sent to `destination`. If the request has no `HOST` header one is
+    /// added, derived from `destination`, with IPv6 addresses in the bracketed form.
+    ///
+    pub fn to_string_for(&self, destination: &SocketAddr) -> String {
+        if self.header(HTTP_HEADER_HOST).is_some() {
+            String::from(self)
+        } else {
+            format!(
+                "{}{}{}{}{}{}{}",
+                self.request_line(),
+                HTTP_HEADER_HOST,
+                HTTP_HEADER_SEP,
+                host_for(destination),
+                HTTP_HEADER_LINE_SEP,
+                self.all_headers(),
+                self.body()
+            )
+        }
+    }
+
     fn request_line(&self) -> String {
         format!(
             "{} {} {}/{}{}",
@@ -51,8 +120,138 @@ impl Request {
     }
 }
 
+impl TryFrom<&[u8]> for Request {
+    type Error = MessageFormatError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (raw_headers, _) = split_at_body(bytes);
+
+        let headers = from_utf8(raw_headers)?;
+        let mut lines = headers.split(HTTP_HEADER_LINE_SEP);
+
+        let (message, resource) = decode_request_line(lines.next().unwrap_or_default())?;
+
+        let headers = decode_headers(lines)?;
+
+        trace!("{:?}", headers);
+
+        Ok(Request {
+            message,
+            resource,
+            headers,
+        })
+    }
+}
+
 impl From<&Request> for String {
     fn from(rq: &Request) -> Self {
         format!("{}{}{}", rq.request_line(), rq.all_headers(), rq.body())
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn host_for(destination: &SocketAddr) -> String {
+    match destination {
+        SocketAddr::V4(address) => address.to_string(),
+        // the zone (scope) identifier is not part of the host, RFC 6874 notwithstanding.
+        SocketAddr::V6(address) => format!("[{}]:{}", address.ip(), address.port()),
+    }
+}
+
+fn decode_request_line(line: &str) -> Result<(String, Option<String>), MessageFormatError> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^([A-Z\-]+) (\S+) HTTP/([\d\.]+)$").unwrap();
+    }
+    match RE.captures(line) {
+        None => {
+            error!(
+                "decode_request_line - could not decode request line '{}'",
+                line
+            );
+            invalid_header_value("REQUEST", line).into()
+        }
+        Some(captured) => {
+            let resource = captured.get(2).unwrap().as_str();
+            Ok((
+                captured.get(1).unwrap().as_str().to_string(),
+                if resource == HTTP_MATCH_ANY_RESOURCE {
+                    None
+                } else {
+                    Some(resource.to_string())
+                },
+            ))
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_from_destination() {
+        let mut request = Request {
+            message: "M-SEARCH".to_string(),
+            resource: None,
+            headers: Default::default(),
+        };
+        let _ = request
+            .headers
+            .insert("ST".to_string(), "ssdp:all".to_string());
+
+        let message = request.to_string_for(&"192.168.1.10:1900".parse().unwrap());
+        assert!(message.starts_with("M-SEARCH * HTTP/1.1\r\nHOST:192.168.1.10:1900\r\n"));
+        let message = request.to_string_for(&"[ff02::c%2]:1900".parse().unwrap());
+        assert!(message.contains("\r\nHOST:[ff02::c]:1900\r\n"));
+
+        let _ = request
+            .headers
+            .insert("Host".to_string(), "239.255.255.250:1900".to_string());
+        let message = request.to_string_for(&"192.168.1.10:1900".parse().unwrap());
+        assert!(message.contains("Host:239.255.255.250:1900"));
+        assert!(!message.contains("192.168.1.10"));
+    }
+
+    #[test]
+    fn test_parse_search_request() {
+        let request = Request::try_from(
+            "M-SEARCH * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\nman: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(request.method(), "M-SEARCH");
+        assert_eq!(request.resource(), None);
+        assert_eq!(request.header("HOST"), Some("239.255.255.250:1900"));
+        assert_eq!(request.header("Man"), Some("\"ssdp:discover\""));
+        assert_eq!(request.header("st"), Some("ssdp:all"));
+    }
+
+    #[test]
+    fn test_parse_notify_with_folding() {
+        let request = Request::try_from(
+            "NOTIFY * HTTP/1.1\r\nNT: upnp:rootdevice\r\nSERVER: Linux/5.0\r\n UPnP/1.0\r\n\tTest/1.0\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(request.method(), "NOTIFY");
+        assert_eq!(
+            request.header("server"),
+            Some("Linux/5.0 UPnP/1.0 Test/1.0")
+        );
+        assert_eq!(request.header("nt"), Some("upnp:rootdevice"));
+    }
+
+    #[test]
+    fn test_parse_invalid_request() {
+        assert!(Request::try_from("HTTP/1.1 200 OK\r\n\r\n".as_bytes()).is_err());
+        assert!(Request::try_from(" folded: first\r\n\r\n".as_bytes()).is_err());
+        assert!(Request::try_from("NOTIFY * HTTP/1.1\r\n continued\r\n\r\n".as_bytes()).is_err());
+    }
+}
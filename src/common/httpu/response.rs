@@ -4,36 +4,53 @@ What's this all about then?
 
 use crate::error::{invalid_header_value, MessageFormatError};
 use crate::syntax::HTTP_HEADER_LINE_SEP;
-use regex::Regex;
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::ops::Range;
 use std::str::from_utf8;
-use std::str::FromStr;
 use tracing::{error, trace};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The decoded status line; the version and reason phrase are ranges within the raw message.
+///
 #[derive(Clone, Debug)]
 pub struct ResponseStatus {
     #[allow(dead_code)]
-    protocol: String,
-    #[allow(dead_code)]
-    version: String,
-    #[allow(dead_code)]
+    version: Range<usize>,
     code: u16,
     #[allow(dead_code)]
-    message: String,
+    message: Range<usize>,
+}
+
+///
+/// The message exactly as received, and the address of the sender where known; useful when
+/// debugging interoperability issues with a particular device.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawResponse {
+    /// The bytes of the message, including the status line, headers, and any body.
+    pub bytes: Vec<u8>,
+    /// The address the message was received from.
+    pub source: Option<SocketAddr>,
 }
 
+///
+/// A parsed HTTPU response. Headers are not copied out of the received message, the response
+/// records the position of each header name and value within the raw bytes and header values are
+/// returned as slices of the message.
+///
 #[derive(Clone, Debug)]
 pub struct Response {
-    #[allow(dead_code)]
     status: ResponseStatus,
-    pub(crate) headers: HashMap<String, String>,
+    headers: Vec<(Range<usize>, HeaderValue)>,
     #[allow(dead_code)]
-    body: Option<Vec<u8>>,
+    body: Option<Range<usize>>,
+    raw: RawResponse,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -46,36 +63,141 @@ impl TryFrom<&[u8]> for Response {
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
         let (raw_headers, body) = split_at_body(bytes);
 
-        let headers = from_utf8(raw_headers)?;
-        let mut lines = headers
-            .split(HTTP_HEADER_LINE_SEP)
-            .map(String::from)
-            .collect::<Vec<String>>();
-
-        let status = decode_status_line(lines.remove(0))?;
+        let text = from_utf8(raw_headers)?;
+        let mut lines = text.split(HTTP_HEADER_LINE_SEP);
 
-        let headers = decode_headers(lines)?;
+        let status = decode_status_line(lines.next().unwrap_or_default())?;
 
-        trace!("{:?}", headers);
+        let mut headers: Vec<(Range<usize>, HeaderValue)> = Vec::with_capacity(EXPECTED_HEADERS);
+        for line in lines {
+            let offset = line.as_ptr() as usize - text.as_ptr() as usize;
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // a folded (continuation) line is appended to the value of the previous header.
+                match headers.last_mut() {
+                    Some((_, value)) => {
+                        let mut folded = match value {
+                            HeaderValue::Span(range) => text[range.clone()].to_string(),
+                            HeaderValue::Folded(folded) => std::mem::take(folded),
+                        };
+                        folded.push(' ');
+                        folded.push_str(line.trim());
+                        *value = HeaderValue::Folded(folded);
+                    }
+                    None => {
+                        error!(
+                            "Response::try_from - continuation line with no header '{}'",
+                            line
+                        );
+                        return invalid_header_value("?", line).into();
+                    }
+                }
+            } else if !line.is_empty() {
+                let (name, value) = header_spans(line)?;
+                headers.push((
+                    offset + name.start..offset + name.end,
+                    HeaderValue::Span(offset + value.start..offset + value.end),
+                ));
+            }
+        }
 
-        Ok(Response {
+        let response = Response {
             status,
             headers,
             body: if body.is_empty() {
                 None
             } else {
-                Some(body.into())
+                Some(bytes.len() - body.len()..bytes.len())
+            },
+            raw: RawResponse {
+                bytes: bytes.to_vec(),
+                source: None,
             },
+        };
+        trace!("{:?}", response.headers().collect::<Vec<_>>());
+        Ok(response)
+    }
+}
+
+impl Response {
+    ///
+    /// The status code from the response's status line.
+    ///
+    pub fn status_code(&self) -> u16 {
+        self.status.code
+    }
+
+    ///
+    /// Return the value of the named header, header names are compared case-insensitively. If a
+    /// header appears more than once the last value is returned.
+    ///
+    pub fn header<N: AsRef<str>>(&self, name: N) -> Option<&str> {
+        let name = name.as_ref();
+        self.headers()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    ///
+    /// All headers in this response, in the order received, with names as they were sent.
+    ///
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(move |(name, value)| {
+            (
+                self.text(name),
+                match value {
+                    HeaderValue::Span(range) => self.text(range),
+                    HeaderValue::Folded(folded) => folded.as_str(),
+                },
+            )
         })
     }
+
+    ///
+    /// The message as received, and the address it was received from.
+    ///
+    pub fn raw(&self) -> &RawResponse {
+        &self.raw
+    }
+
+    pub(crate) fn with_source(mut self, source: SocketAddr) -> Self {
+        self.raw.source = Some(source);
+        self
+    }
+
+    fn text(&self, range: &Range<usize>) -> &str {
+        // the ranges were taken from the validated UTF-8 header block of these same bytes.
+        from_utf8(&self.raw.bytes[range.clone()]).unwrap_or_default()
+    }
 }
 
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Header values are usually a range within the raw message, only folded values, that span more
+/// than one line, need to be copied.
+///
+#[derive(Clone, Debug)]
+enum HeaderValue {
+    Span(Range<usize>),
+    Folded(String),
+}
+
+const HTTP_STATUS_PREFIX: &str = "HTTP/";
+
+///
+/// The initial capacity of the header list, enough for a typical SSDP response without growing.
+///
+const EXPECTED_HEADERS: usize = 16;
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn split_at_body(all: &[u8]) -> (&[u8], &[u8]) {
-    static BLANK_LINE: &[u8] = &[b'\r', b'\n', b'\r', b'\n'];
+pub(crate) fn split_at_body(all: &[u8]) -> (&[u8], &[u8]) {
+    static BLANK_LINE: &[u8] = b"\r\n\r\n";
     match all
         .windows(BLANK_LINE.len())
         .position(|window| window == BLANK_LINE)
@@ -85,11 +207,26 @@ fn split_at_body(all: &[u8]) -> (&[u8], &[u8]) {
     }
 }
 
-fn decode_status_line(line: String) -> Result<ResponseStatus, MessageFormatError> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^HTTP/([\d\.]+) (\d+) (.*)$").unwrap();
-    }
-    match RE.captures(&line) {
+///
+/// Decode a status line of the form `HTTP/{version} {code} {message}`, only the status code `200`
+/// is accepted.
+///
+fn decode_status_line(line: &str) -> Result<ResponseStatus, MessageFormatError> {
+    let decoded = line.strip_prefix(HTTP_STATUS_PREFIX).and_then(|rest| {
+        let (version, rest) = rest.split_once(' ')?;
+        let (code, _) = rest.split_once(' ')?;
+        if version.is_empty() || !version.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return None;
+        }
+        let version_start = HTTP_STATUS_PREFIX.len();
+        let message_start = version_start + version.len() + code.len() + 2;
+        Some(ResponseStatus {
+            version: version_start..version_start + version.len(),
+            code: code.parse::<u16>().ok()?,
+            message: message_start..line.len(),
+        })
+    });
+    match decoded {
         None => {
             error!(
                 "decode_status_line - could not decode status line '{}'",
@@ -97,44 +234,104 @@ fn decode_status_line(line: String) -> Result<ResponseStatus, MessageFormatError
             );
             invalid_header_value("STATUS", line).into()
         }
-        Some(captured) => {
-            let status_code = u16::from_str(captured.get(2).unwrap().as_str()).unwrap();
-            if status_code == 200 {
-                Ok(ResponseStatus {
-                    protocol: String::from("HTTP"),
-                    version: captured.get(1).unwrap().as_str().to_string(),
-                    code: status_code,
-                    message: captured.get(3).unwrap().as_str().to_string(),
-                })
-            } else {
-                error!("server returned error '{}'", status_code);
-                invalid_header_value("STATUS", &status_code.to_string()).into()
-            }
+        Some(status) if status.code == 200 => Ok(status),
+        Some(status) => {
+            error!("server returned error '{}'", status.code);
+            invalid_header_value("STATUS", status.code.to_string()).into()
         }
     }
 }
 
-fn decode_headers(lines: Vec<String>) -> Result<HashMap<String, String>, MessageFormatError> {
+pub(super) fn decode_headers<'a>(
+    lines: impl Iterator<Item = &'a str>,
+) -> Result<HashMap<String, String>, MessageFormatError> {
     let mut headers: HashMap<String, String> = HashMap::new();
+    let mut last_key: Option<String> = None;
     for line in lines {
-        let (key, value) = decode_header(line)?;
-        headers.insert(key, value);
+        if line.starts_with(' ') || line.starts_with('\t') {
+            // a folded (continuation) line is appended to the value of the previous header.
+            match last_key.as_ref().and_then(|key| headers.get_mut(key)) {
+                Some(value) => {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                None => {
+                    error!(
+                        "decode_headers - continuation line with no header '{}'",
+                        line
+                    );
+                    return invalid_header_value("?", line).into();
+                }
+            }
+        } else if !line.is_empty() {
+            let (name, value) = header_spans(line)?;
+            let key = line[name].to_ascii_uppercase();
+            let _ = headers.insert(key.clone(), line[value].to_string());
+            last_key = Some(key);
+        }
     }
     Ok(headers)
 }
 
-fn decode_header(line: String) -> Result<(String, String), MessageFormatError> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^([a-zA-Z0-9\-_]*)[ ]*:[ ]*(.*)$").unwrap();
+///
+/// Split a header line into the ranges of its name and value; the name is a token of
+/// alphanumeric, `-`, `_`, and `.` characters, and the value excludes leading spaces.
+///
+fn header_spans(line: &str) -> Result<(Range<usize>, Range<usize>), MessageFormatError> {
+    let name_end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .unwrap_or(line.len());
+    let separator =
+        name_end + (line[name_end..].len() - line[name_end..].trim_start_matches(' ').len());
+    if !line[separator..].starts_with(':') {
+        error!("decode_header - could not decode header '{}'", line);
+        return invalid_header_value("?", line).into();
     }
-    match RE.captures(&line) {
-        None => {
-            error!("decode_header - could not decode header '{}'", line);
-            invalid_header_value("?", line).into()
-        }
-        Some(captured) => Ok((
-            captured.get(1).unwrap().as_str().to_uppercase(),
-            captured.get(2).unwrap().as_str().to_string(),
-        )),
+    let value_end = line.len();
+    let value_start = value_end - line[separator + 1..].trim_start_matches(' ').len();
+    Ok((0..name_end, value_start..value_end))
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_borrow_from_message() {
+        let response = Response::try_from(
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=1800\r\nST:upnp:rootdevice\r\nX-Folded: one\r\n  two\r\nst: ssdp:all\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(response.header("CACHE-CONTROL"), Some("max-age=1800"));
+        assert_eq!(response.header("x-folded"), Some("one two"));
+        assert_eq!(response.header("ST"), Some("ssdp:all"));
+        assert_eq!(
+            response.headers().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["Cache-Control", "ST", "X-Folded", "st"]
+        );
+        assert!(response.header("EXT").is_none());
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        assert!(Response::try_from("HTTP/1.1 200 OK\r\nNOT A HEADER\r\n\r\n".as_bytes()).is_err());
+        assert!(Response::try_from("HTTP/1.1 200 OK\r\n folded\r\n\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_status_line() {
+        let response = Response::try_from("HTTP/1.1 200 OK\r\n\r\n".as_bytes()).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(&response.status.version), "1.1");
+        assert_eq!(response.text(&response.status.message), "OK");
+        assert!(Response::try_from("HTTP/1.1 404 Not Found\r\n\r\n".as_bytes()).is_err());
+        assert!(Response::try_from("HTTP/1.1 99999 Big\r\n\r\n".as_bytes()).is_err());
+        assert!(Response::try_from("HTTP/x 200 OK\r\n\r\n".as_bytes()).is_err());
+        assert!(Response::try_from("NOTIFY * HTTP/1.1\r\n\r\n".as_bytes()).is_err());
     }
 }
@@ -0,0 +1,319 @@
+/*!
+This module provides the [`Transport`] trait, the datagram operations used by the HTTPU functions,
+so that messages may be sent and received over something other than a UDP socket. An
+implementation is provided for `UdpSocket`, with the `net` feature, and an in-memory implementation,
+[`LoopbackNetwork`], allows search and notification logic to be tested deterministically without a
+network.
+
+# Example
+
+```rust
+use upnp_rs::common::httpu::{LoopbackNetwork, Transport};
+
+let network = LoopbackNetwork::new();
+let device = network.bind("10.0.0.2:1900".parse().unwrap()).unwrap();
+device.join("239.255.255.250:1900".parse().unwrap());
+let client = network.bind("10.0.0.1:5000".parse().unwrap()).unwrap();
+
+client
+    .send_to(b"hello", &"239.255.255.250:1900".parse().unwrap())
+    .unwrap();
+
+let mut buf = [0u8; 16];
+let (received, from) = device.recv_from(&mut buf).unwrap();
+assert_eq!(&buf[..received], b"hello");
+assert_eq!(from, "10.0.0.1:5000".parse().unwrap());
+```
+*/
+
+use crate::error::{invalid_socket_value, Error};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult};
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "net")]
+use std::net::UdpSocket;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The datagram operations required to send HTTPU requests and receive their responses. The
+/// semantics follow those of the same methods on `UdpSocket`; in particular a receive that times
+/// out returns an error of kind `WouldBlock` or `TimedOut`.
+///
+pub trait Transport: Debug + Send + Sync {
+    ///
+    /// Send a single datagram to `to`, returning the number of bytes sent.
+    ///
+    fn send_to(&self, buf: &[u8], to: &SocketAddr) -> IOResult<usize>;
+
+    ///
+    /// Receive a single datagram, returning the number of bytes read and the sender's address.
+    ///
+    fn recv_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)>;
+
+    ///
+    /// As `recv_from`, but the datagram remains available to the next receive.
+    ///
+    fn peek_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)>;
+
+    ///
+    /// The current receive timeout, `None` blocks indefinitely.
+    ///
+    fn read_timeout(&self) -> IOResult<Option<Duration>>;
+
+    ///
+    /// Set the receive timeout, `None` blocks indefinitely.
+    ///
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IOResult<()>;
+}
+
+///
+/// An in-memory network, datagrams sent to the address of a bound transport, or to a multicast
+/// group it has joined, are queued for that transport. All clones share the same network.
+///
+#[derive(Clone, Debug, Default)]
+pub struct LoopbackNetwork {
+    endpoints: Arc<Mutex<HashMap<SocketAddr, Endpoint>>>,
+}
+
+///
+/// A transport bound to an address on a [`LoopbackNetwork`]; the binding is released when the
+/// transport is dropped.
+///
+#[derive(Debug)]
+pub struct LoopbackTransport {
+    address: SocketAddr,
+    network: LoopbackNetwork,
+    mailbox: Arc<Mailbox>,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+impl Transport for UdpSocket {
+    fn send_to(&self, buf: &[u8], to: &SocketAddr) -> IOResult<usize> {
+        UdpSocket::send_to(self, buf, to)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn peek_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        UdpSocket::peek_from(self, buf)
+    }
+
+    fn read_timeout(&self) -> IOResult<Option<Duration>> {
+        UdpSocket::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IOResult<()> {
+        UdpSocket::set_read_timeout(self, timeout)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl LoopbackNetwork {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// Bind a new transport to `address`, which must not already be bound on this network.
+    ///
+    pub fn bind(&self, address: SocketAddr) -> Result<LoopbackTransport, Error> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if endpoints.contains_key(&address) {
+            return invalid_socket_value("address", address.to_string()).into();
+        }
+        let mailbox: Arc<Mailbox> = Default::default();
+        let _ = endpoints.insert(
+            address,
+            Endpoint {
+                mailbox: mailbox.clone(),
+                groups: Vec::new(),
+            },
+        );
+        Ok(LoopbackTransport {
+            address,
+            network: self.clone(),
+            mailbox,
+            read_timeout: Mutex::new(None),
+        })
+    }
+
+    fn deliver(&self, from: SocketAddr, to: &SocketAddr, buf: &[u8]) {
+        let endpoints = self.endpoints.lock().unwrap();
+        endpoints
+            .iter()
+            .filter(|(address, endpoint)| {
+                **address != from && (*address == to || endpoint.groups.contains(to))
+            })
+            .for_each(|(_, endpoint)| endpoint.mailbox.push(buf.to_vec(), from));
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl LoopbackTransport {
+    ///
+    /// The address this transport is bound to.
+    ///
+    pub fn local_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    ///
+    /// Join the multicast `group`; datagrams sent to the group are received by this transport.
+    ///
+    pub fn join(&self, group: SocketAddr) {
+        let mut endpoints = self.network.endpoints.lock().unwrap();
+        if let Some(endpoint) = endpoints.get_mut(&self.address) {
+            if !endpoint.groups.contains(&group) {
+                endpoint.groups.push(group);
+            }
+        }
+    }
+
+    fn receive(&self, buf: &mut [u8], remove: bool) -> IOResult<(usize, SocketAddr)> {
+        let timeout = *self.read_timeout.lock().unwrap();
+        let (bytes, from) = self.mailbox.wait(timeout, remove)?;
+        let received = bytes.len().min(buf.len());
+        buf[..received].copy_from_slice(&bytes[..received]);
+        Ok((received, from))
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send_to(&self, buf: &[u8], to: &SocketAddr) -> IOResult<usize> {
+        self.network.deliver(self.address, to, buf);
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        self.receive(buf, true)
+    }
+
+    fn peek_from(&self, buf: &mut [u8]) -> IOResult<(usize, SocketAddr)> {
+        self.receive(buf, false)
+    }
+
+    fn read_timeout(&self) -> IOResult<Option<Duration>> {
+        Ok(*self.read_timeout.lock().unwrap())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> IOResult<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                "cannot set a 0 duration timeout",
+            ));
+        }
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        if let Ok(mut endpoints) = self.network.endpoints.lock() {
+            let _ = endpoints.remove(&self.address);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct Endpoint {
+    mailbox: Arc<Mailbox>,
+    groups: Vec<SocketAddr>,
+}
+
+#[derive(Debug, Default)]
+struct Mailbox {
+    queue: Mutex<VecDeque<(Vec<u8>, SocketAddr)>>,
+    available: Condvar,
+}
+
+impl Mailbox {
+    fn push(&self, bytes: Vec<u8>, from: SocketAddr) {
+        self.queue.lock().unwrap().push_back((bytes, from));
+        self.available.notify_all();
+    }
+
+    fn wait(&self, timeout: Option<Duration>, remove: bool) -> IOResult<(Vec<u8>, SocketAddr)> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if let Some(front) = queue.front() {
+                return Ok(if remove {
+                    queue.pop_front().unwrap()
+                } else {
+                    front.clone()
+                });
+            }
+            queue = match deadline {
+                None => self.available.wait(queue).unwrap(),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(IOErrorKind::WouldBlock.into());
+                    }
+                    self.available.wait_timeout(queue, remaining).unwrap().0
+                }
+            };
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicast_and_timeout() {
+        let network = LoopbackNetwork::new();
+        let one = network.bind("10.0.0.1:1000".parse().unwrap()).unwrap();
+        let two = network.bind("10.0.0.2:2000".parse().unwrap()).unwrap();
+        two.set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+
+        let _ = one.send_to(b"ping", &two.local_address()).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(two.peek_from(&mut buf).unwrap().0, 2);
+        let mut buf = [0u8; 8];
+        let (received, from) = two.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..received], b"ping");
+        assert_eq!(from, one.local_address());
+
+        let error = two.recv_from(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), IOErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_bind_twice_fails() {
+        let network = LoopbackNetwork::new();
+        let address: SocketAddr = "10.0.0.1:1000".parse().unwrap();
+        let first = network.bind(address).unwrap();
+        assert!(network.bind(address).is_err());
+        drop(first);
+        assert!(network.bind(address).is_ok());
+    }
+}
@@ -1,5 +1,45 @@
+/*!
+This module provides the listing of local network interfaces, and the selection of an interface,
+and of the local address to bind to, from the `network_interface` value in the search, notify, and
+listen options.
+
+An interface may be selected by its name (`eth0`), by its index (`2`), or by a network in CIDR form
+(`192.168.1.0/24`) in which case the interface with an address on that network is selected. A
+selector that matches no interface is an error, rather than a silent fallback to the unspecified
+address.
+
+Only the [`IP`] version selector is available without the `net` feature; listing and selecting
+interfaces requires the operating system's network stack.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::interface::{list_interfaces, select_address, IP};
+
+for interface in list_interfaces() {
+    println!("{}: {} {:?}", interface.index, interface.name, interface.addresses);
+}
+
+let address = select_address("192.168.1.0/24", &Some(IP::V4)).unwrap();
+```
+*/
+
+#[cfg(feature = "net")]
+use crate::error::{no_interface_address, unknown_interface, Error};
+#[cfg(feature = "net")]
+use ipnetwork::IpNetwork;
+#[cfg(feature = "net")]
 use pnet::datalink;
+#[cfg(feature = "net")]
+use std::fmt::{Display, Formatter, Result as FmtResult};
+#[cfg(feature = "net")]
 use std::net::IpAddr;
+#[cfg(feature = "net")]
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
 
 #[derive(Clone, Debug)]
 #[allow(dead_code)]
@@ -8,48 +48,278 @@ pub enum IP {
     V6,
 }
 
+///
+/// A local network interface.
+///
+#[cfg(feature = "net")]
+#[derive(Clone, Debug)]
+pub struct NetworkInterface {
+    /// The operating system name of the interface.
+    pub name: String,
+    /// The operating system index of the interface.
+    pub index: u32,
+    /// The hardware (MAC) address of the interface, if it has one.
+    pub mac: Option<[u8; 6]>,
+    /// The addresses, with their network prefix, assigned to the interface.
+    pub addresses: Vec<IpNetwork>,
+    /// The state and capabilities of the interface.
+    pub flags: InterfaceFlags,
+}
+
+///
+/// The subset of interface flags relevant to selecting an interface for SSDP.
+///
+#[cfg(feature = "net")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceFlags {
+    pub up: bool,
+    pub loopback: bool,
+    pub multicast: bool,
+    pub broadcast: bool,
+    pub point_to_point: bool,
+}
+
+///
+/// Identifies an interface by name, index, or by a network one of its addresses is on. The
+/// string form is parsed as an index if numeric, a network if it is an address with an optional
+/// prefix length, and a name otherwise.
+///
+#[cfg(feature = "net")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterfaceSelector {
+    Name(String),
+    Index(u32),
+    Network(IpNetwork),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return all the local network interfaces.
+///
+#[cfg(feature = "net")]
+pub fn list_interfaces() -> Vec<NetworkInterface> {
+    datalink::interfaces()
+        .into_iter()
+        .map(|ni| NetworkInterface {
+            flags: InterfaceFlags {
+                up: ni.is_up(),
+                loopback: ni.is_loopback(),
+                multicast: ni.is_multicast(),
+                broadcast: ni.is_broadcast(),
+                point_to_point: ni.is_point_to_point(),
+            },
+            name: ni.name,
+            index: ni.index,
+            mac: ni.mac.filter(|mac| !mac.is_zero()).map(|mac| mac.octets()),
+            addresses: ni
+                .ips
+                .iter()
+                .filter_map(|ip| IpNetwork::new(ip.ip(), ip.prefix()).ok())
+                .collect(),
+        })
+        .collect()
+}
+
+///
+/// Return the interface identified by `selector`, see [`InterfaceSelector`] for its forms; an
+/// error is returned if no interface matches.
+///
+#[cfg(feature = "net")]
+pub fn find_interface(selector: &str) -> Result<NetworkInterface, Error> {
+    let selector: InterfaceSelector = selector.parse()?;
+    list_interfaces()
+        .into_iter()
+        .find(|interface| selector.matches(interface))
+        .ok_or_else(|| unknown_interface(selector.to_string()))
+}
+
+///
+/// Return the address, of the requested IP version, on the interface identified by `selector`.
+/// For a network selector this is the interface's address on that network. An error is returned
+/// if no interface matches, or the interface has no suitable address.
+///
+#[cfg(feature = "net")]
+pub fn select_address(selector: &str, version: &Option<IP>) -> Result<IpAddr, Error> {
+    let selector: InterfaceSelector = selector.parse()?;
+    let interface = list_interfaces()
+        .into_iter()
+        .find(|interface| selector.matches(interface))
+        .ok_or_else(|| unknown_interface(selector.to_string()))?;
+    let address = interface
+        .addresses_for(version)
+        .find(|address| match &selector {
+            InterfaceSelector::Network(network) => network.contains(*address),
+            _ => true,
+        });
+    address.ok_or_else(|| no_interface_address(&interface.name))
+}
+
+///
+/// Return the address to bind to for the optional interface selector in a set of options; `None`
+/// if no interface was specified, an error if the selector does not identify an interface with a
+/// suitable address.
+///
+#[cfg(feature = "net")]
 pub fn ip_address_for_interface(
     network_interface: &Option<String>,
     network_version: &Option<IP>,
-) -> Option<IpAddr> {
-    match network_interface {
-        None => None,
-        Some(name) => {
-            let addresses = ip_addresses_for_interface(name.clone(), network_version.clone());
-            if addresses.is_empty() {
-                None
-            } else {
-                let address = addresses.first().unwrap();
-                Some(*address)
-            }
-        }
-    }
+) -> Result<Option<IpAddr>, Error> {
+    network_interface
+        .as_ref()
+        .map(|selector| select_address(selector, network_version))
+        .transpose()
+}
+
+///
+/// Return the hardware (MAC) address of the first non-loopback interface that has one, this is
+/// used as the node identifier for time-based UUIDs.
+///
+#[cfg(feature = "net")]
+pub fn hardware_address() -> Option<[u8; 6]> {
+    datalink::interfaces()
+        .into_iter()
+        .filter(|ni| !ni.is_loopback())
+        .filter_map(|ni| ni.mac)
+        .find(|mac| !mac.is_zero())
+        .map(|mac| mac.octets())
 }
 
-pub fn ip_addresses_for_interface(interface: String, version: Option<IP>) -> Vec<IpAddr> {
-    let interfaces = datalink::interfaces();
-    match &interfaces.into_iter().find(|ni| ni.name == interface) {
-        None => Vec::new(),
-        Some(ni) => ni
-            .ips
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+impl NetworkInterface {
+    ///
+    /// The interface's addresses, restricted to one IP version if `version` is specified.
+    ///
+    pub fn addresses_for<'a>(
+        &'a self,
+        version: &'a Option<IP>,
+    ) -> impl Iterator<Item = IpAddr> + 'a {
+        self.addresses
             .iter()
-            .filter_map(|ip| match version {
-                None => Some(ip.ip()),
-                Some(IP::V4) => {
-                    if ip.is_ipv4() {
-                        Some(ip.ip())
-                    } else {
-                        None
-                    }
-                }
-                Some(IP::V6) => {
-                    if ip.is_ipv6() {
-                        Some(ip.ip())
-                    } else {
-                        None
-                    }
-                }
+            .map(|network| network.ip())
+            .filter(move |address| match version {
+                None => true,
+                Some(IP::V4) => address.is_ipv4(),
+                Some(IP::V6) => address.is_ipv6(),
             })
-            .collect(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+impl Display for InterfaceSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            InterfaceSelector::Name(name) => write!(f, "{}", name),
+            InterfaceSelector::Index(index) => write!(f, "{}", index),
+            InterfaceSelector::Network(network) => write!(f, "{}", network),
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl FromStr for InterfaceSelector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(unknown_interface(s))
+        } else if let Ok(index) = s.parse::<u32>() {
+            Ok(InterfaceSelector::Index(index))
+        } else if let Ok(network) = s.parse::<IpNetwork>() {
+            Ok(InterfaceSelector::Network(network))
+        } else {
+            Ok(InterfaceSelector::Name(s.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl InterfaceSelector {
+    ///
+    /// Returns `true` if `interface` is identified by this selector.
+    ///
+    pub fn matches(&self, interface: &NetworkInterface) -> bool {
+        match self {
+            InterfaceSelector::Name(name) => &interface.name == name,
+            InterfaceSelector::Index(index) => interface.index == *index,
+            InterfaceSelector::Network(network) => interface
+                .addresses
+                .iter()
+                .any(|address| network.contains(address.ip())),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+
+    fn loopback() -> NetworkInterface {
+        NetworkInterface {
+            name: "lo".to_string(),
+            index: 1,
+            mac: None,
+            addresses: vec!["127.0.0.1/8".parse().unwrap(), "::1/128".parse().unwrap()],
+            flags: InterfaceFlags {
+                up: true,
+                loopback: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!(
+            "eth0".parse::<InterfaceSelector>().unwrap(),
+            InterfaceSelector::Name("eth0".to_string())
+        );
+        assert_eq!(
+            "2".parse::<InterfaceSelector>().unwrap(),
+            InterfaceSelector::Index(2)
+        );
+        assert_eq!(
+            "192.168.1.0/24".parse::<InterfaceSelector>().unwrap(),
+            InterfaceSelector::Network("192.168.1.0/24".parse().unwrap())
+        );
+        assert!("".parse::<InterfaceSelector>().is_err());
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let interface = loopback();
+        assert!(InterfaceSelector::Name("lo".to_string()).matches(&interface));
+        assert!(InterfaceSelector::Index(1).matches(&interface));
+        assert!("127.0.0.0/8"
+            .parse::<InterfaceSelector>()
+            .unwrap()
+            .matches(&interface));
+        assert!(!"192.168.1.0/24"
+            .parse::<InterfaceSelector>()
+            .unwrap()
+            .matches(&interface));
+        assert_eq!(
+            interface.addresses_for(&Some(IP::V6)).collect::<Vec<_>>(),
+            vec!["::1".parse::<IpAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_unknown_interface() {
+        assert!(find_interface("no-such-interface0").is_err());
+        assert!(ip_address_for_interface(&Some("no-such-interface0".to_string()), &None).is_err());
+        assert!(ip_address_for_interface(&None, &None).unwrap().is_none());
     }
 }
@@ -1,3 +1,7 @@
+pub mod data_types;
+
+pub mod date;
+
 pub mod headers;
 
 pub mod http;
@@ -8,6 +12,8 @@ pub mod interface;
 
 pub mod soap;
 
+pub mod udn;
+
 pub mod uri;
 
 pub mod user_agent;
@@ -1,6 +1,7 @@
-use crate::description::TypeID;
-use crate::syntax::{SOAP_NS_ENCODING, SOAP_NS_ENVELOPE};
-/**
+/*!
+This module provides the SOAP envelope used to invoke actions on, and return results from, a
+service's control URL. The request takes the following form, responses replace the action element
+with an `actionNameResponse` element containing any out arguments, or with a `s:Fault` element.
 
 ```http
 POST path of control URL HTTP/1.1
@@ -22,13 +23,40 @@ SOAPACTION: "urn:schemas-upnp-org:service:serviceType:v#actionName"
 </s:Envelope>
 ```
 */
-use std::collections::HashMap;
-use std::fmt::{Display, Error, Formatter};
+
+use crate::common::xml::read::{read_xml, Element as ReadElement};
+use crate::common::xml::write::{
+    end_element, start, start_element, start_element_with, text_element, RootWritable, Writable,
+};
+use crate::description::TypeID;
+use crate::discovery::search::SearchTarget;
+use crate::error::{invalid_field_value, missing_required_field, xml_error, Error};
+use crate::syntax::{
+    SOAP_ACTION_RESPONSE_SUFFIX, SOAP_ATTR_ENCODING_STYLE, SOAP_ELEM_BODY, SOAP_ELEM_DETAIL,
+    SOAP_ELEM_ENVELOPE, SOAP_ELEM_ERROR_CODE, SOAP_ELEM_ERROR_DESCRIPTION, SOAP_ELEM_FAULT,
+    SOAP_ELEM_FAULT_CODE, SOAP_ELEM_FAULT_STRING, SOAP_ELEM_UPNP_ERROR, SOAP_FAULT_CODE_CLIENT,
+    SOAP_FAULT_STRING_UPNP, SOAP_NS_CONTROL, SOAP_NS_ENCODING, SOAP_NS_ENVELOPE,
+    SOAP_PREFIX_ACTION, SOAP_PREFIX_ENVELOPE, XML_ATTR_NAMESPACE,
+};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::Write;
+use std::str::FromStr;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Action arguments, as name/value pairs. SOAP requires that arguments are sent in the order
+/// they are declared in the service description, so this is a list rather than a map.
+///
+pub type Arguments = Vec<(String, String)>;
+
+///
+/// An action, identified by the service type and the action name.
+///
 #[derive(Clone, Debug)]
 pub struct Action {
     service: TypeID,
@@ -39,11 +67,11 @@ pub struct Action {
 pub enum Body {
     Action {
         action: Action,
-        argumments: HashMap<String, String>,
+        arguments: Arguments,
     },
     Response {
         action: Action,
-        argumments: HashMap<String, String>,
+        arguments: Arguments,
     },
     Fault {
         code: String,
@@ -59,10 +87,20 @@ pub struct Envelope {
     schema: String,
     #[allow(dead_code)]
     encoding_style: String,
-    #[allow(dead_code)]
     body: Body,
 }
 
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Write the envelope, as a complete XML document, to `writer`.
+///
+pub fn to_writer<T: Write>(envelope: &Envelope, writer: T) -> Result<T, Error> {
+    envelope.write_root(writer)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -78,10 +116,18 @@ impl Action {
             action,
         }
     }
+
+    pub fn service(&self) -> &TypeID {
+        &self.service
+    }
+
+    pub fn name(&self) -> &str {
+        &self.action
+    }
 }
 
 impl Display for Action {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         write!(f, "{}#{}", self.service, self.action)
     }
 }
@@ -93,20 +139,174 @@ impl Envelope {
         Self::new_with(action, Default::default())
     }
 
-    pub fn new_with(action: Action, argumments: HashMap<String, String>) -> Self {
+    pub fn new_with(action: Action, arguments: Arguments) -> Self {
+        Self::with_body(Body::Action { action, arguments })
+    }
+
+    pub fn new_response(action: Action, arguments: Arguments) -> Self {
+        Self::with_body(Body::Response { action, arguments })
+    }
+
+    ///
+    /// Construct a fault response carrying the UPnP error code and description.
+    ///
+    pub fn new_fault(upnp_code: u16, upnp_description: &str) -> Self {
+        Self::with_body(Body::Fault {
+            code: SOAP_FAULT_CODE_CLIENT.to_string(),
+            string: SOAP_FAULT_STRING_UPNP.to_string(),
+            upnp_code: upnp_code.to_string(),
+            upnp_description: upnp_description.to_string(),
+        })
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Body {
+        self.body
+    }
+
+    fn with_body(body: Body) -> Self {
         Envelope {
             schema: SOAP_NS_ENVELOPE.to_string(),
             encoding_style: SOAP_NS_ENCODING.to_string(),
-            body: Body::Action { action, argumments },
+            body,
         }
     }
+}
 
-    pub fn new_response(action: Action, argumments: HashMap<String, String>) -> Self {
-        Envelope {
-            schema: SOAP_NS_ENVELOPE.to_string(),
-            encoding_style: SOAP_NS_ENCODING.to_string(),
-            body: Body::Response { action, argumments },
+impl FromStr for Envelope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let envelope = read_xml(s)?;
+        if envelope.local_name() != local_name(SOAP_ELEM_ENVELOPE) {
+            return invalid_field_value(SOAP_ELEM_ENVELOPE, envelope.name.clone()).into();
+        }
+        if let Some(namespace) = &envelope.namespace {
+            if namespace != SOAP_NS_ENVELOPE {
+                return invalid_field_value(SOAP_ELEM_ENVELOPE, namespace.clone()).into();
+            }
+        }
+        let body = required_child(&envelope, SOAP_ELEM_BODY)?;
+        let content = match body.children.first() {
+            None => return missing_required_field(SOAP_ELEM_BODY).into(),
+            Some(content) => content,
+        };
+        if content.local_name() == local_name(SOAP_ELEM_FAULT) {
+            let error = required_child(content, SOAP_ELEM_DETAIL)
+                .and_then(|detail| required_child(detail, SOAP_ELEM_UPNP_ERROR))?;
+            Ok(Self::with_body(Body::Fault {
+                code: content
+                    .child_text(SOAP_ELEM_FAULT_CODE)
+                    .unwrap_or_default()
+                    .to_string(),
+                string: content
+                    .child_text(SOAP_ELEM_FAULT_STRING)
+                    .unwrap_or_default()
+                    .to_string(),
+                upnp_code: error
+                    .required_child_text(SOAP_ELEM_ERROR_CODE)?
+                    .trim()
+                    .to_string(),
+                upnp_description: error
+                    .child_text(SOAP_ELEM_ERROR_DESCRIPTION)
+                    .unwrap_or_default()
+                    .to_string(),
+            }))
+        } else {
+            let service = service_type_of(content)?;
+            let arguments = content
+                .children
+                .iter()
+                .map(|argument| (argument.local_name().to_string(), argument.text.clone()))
+                .collect();
+            Ok(Self::with_body(
+                match content
+                    .local_name()
+                    .strip_suffix(SOAP_ACTION_RESPONSE_SUFFIX)
+                {
+                    Some(action) => Body::Response {
+                        action: Action::new(service, action.to_string()),
+                        arguments,
+                    },
+                    None => Body::Action {
+                        action: Action::new(service, content.local_name().to_string()),
+                        arguments,
+                    },
+                },
+            ))
+        }
+    }
+}
+
+impl<T: Write> RootWritable<T> for Envelope {}
+
+impl<T: Write> Writable<T> for Envelope {
+    fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
+        let xmlns = format!("{}:{}", XML_ATTR_NAMESPACE, SOAP_PREFIX_ENVELOPE);
+        let envelope = start_element_with(
+            writer,
+            SOAP_ELEM_ENVELOPE,
+            vec![
+                (xmlns.as_str(), SOAP_NS_ENVELOPE),
+                (SOAP_ATTR_ENCODING_STYLE, SOAP_NS_ENCODING),
+            ],
+        )
+        .map_err(xml_error)?;
+        let body = start_element(writer, SOAP_ELEM_BODY).map_err(xml_error)?;
+
+        match &self.body {
+            Body::Action { action, arguments } => {
+                write_action(writer, action, action.name().to_string(), arguments)?
+            }
+            Body::Response { action, arguments } => write_action(
+                writer,
+                action,
+                format!("{}{}", action.name(), SOAP_ACTION_RESPONSE_SUFFIX),
+                arguments,
+            )?,
+            Body::Fault {
+                code,
+                string,
+                upnp_code,
+                upnp_description,
+            } => {
+                let fault = start_element(writer, SOAP_ELEM_FAULT).map_err(xml_error)?;
+                text_element(writer, SOAP_ELEM_FAULT_CODE, code).map_err(xml_error)?;
+                text_element(writer, SOAP_ELEM_FAULT_STRING, string).map_err(xml_error)?;
+                let detail = start_element(writer, SOAP_ELEM_DETAIL).map_err(xml_error)?;
+                let error = start_element_with(
+                    writer,
+                    SOAP_ELEM_UPNP_ERROR,
+                    vec![(XML_ATTR_NAMESPACE, SOAP_NS_CONTROL)],
+                )
+                .map_err(xml_error)?;
+                text_element(writer, SOAP_ELEM_ERROR_CODE, upnp_code).map_err(xml_error)?;
+                text_element(writer, SOAP_ELEM_ERROR_DESCRIPTION, upnp_description)
+                    .map_err(xml_error)?;
+                error.end(writer).map_err(xml_error)?;
+                detail.end(writer).map_err(xml_error)?;
+                fault.end(writer).map_err(xml_error)?;
+            }
         }
+
+        body.end(writer).map_err(xml_error)?;
+        envelope.end(writer).map_err(xml_error)
+    }
+}
+
+impl Display for Envelope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        let mut writer = Writer::new(Vec::new());
+        start(&mut writer).map_err(|_| FmtError)?;
+        self.write(&mut writer).map_err(|_| FmtError)?;
+        write!(
+            f,
+            "{}",
+            String::from_utf8(writer.into_inner()).map_err(|_| FmtError)?
+        )
     }
 }
 
@@ -114,6 +314,154 @@ impl Envelope {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn local_name(name: &str) -> &str {
+    name.split_once(':').map(|(_, local)| local).unwrap_or(name)
+}
+
+fn required_child<'a>(element: &'a ReadElement, name: &str) -> Result<&'a ReadElement, Error> {
+    match element.child(local_name(name)) {
+        Some(child) => Ok(child),
+        None => missing_required_field(name).into(),
+    }
+}
+
+fn service_type_of(element: &ReadElement) -> Result<TypeID, Error> {
+    match &element.namespace {
+        None => missing_required_field(XML_ATTR_NAMESPACE).into(),
+        Some(namespace) => SearchTarget::from_str(namespace)
+            .map_err(|_| invalid_field_value(XML_ATTR_NAMESPACE, namespace).into())
+            .and_then(TypeID::service_from),
+    }
+}
+
+fn write_action<T: Write>(
+    writer: &mut Writer<T>,
+    action: &Action,
+    element_name: String,
+    arguments: &Arguments,
+) -> Result<(), Error> {
+    let name = format!("{}:{}", SOAP_PREFIX_ACTION, element_name);
+    let xmlns = format!("{}:{}", XML_ATTR_NAMESPACE, SOAP_PREFIX_ACTION);
+    let service = action.service().to_string();
+    let mut start = BytesStart::new(name.as_str());
+    start.push_attribute((xmlns.as_str(), service.as_str()));
+    writer.write_event(Event::Start(start)).map_err(xml_error)?;
+    for (argument, value) in arguments {
+        writer
+            .write_event(Event::Start(BytesStart::new(argument.as_str())))
+            .map_err(xml_error)?;
+        writer
+            .write_event(Event::Text(BytesText::new(value)))
+            .map_err(xml_error)?;
+        writer
+            .write_event(Event::End(BytesEnd::new(argument.as_str())))
+            .map_err(xml_error)?;
+    }
+    end_element(writer, &name).map_err(xml_error)
+}
+
 // ------------------------------------------------------------------------------------------------
-// Modules
+// Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action() -> Action {
+        Action::new(
+            TypeID::new_service("WANIPConnection".to_string(), 1),
+            "GetExternalIPAddress".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_action_round_trip() {
+        let envelope = Envelope::new_with(
+            action().copy_to("DeletePortMapping".to_string()),
+            vec![
+                ("NewRemoteHost".to_string(), String::new()),
+                ("NewExternalPort".to_string(), "8080".to_string()),
+                ("NewProtocol".to_string(), "TCP".to_string()),
+            ],
+        );
+        let xml = envelope.to_string();
+        assert!(xml.starts_with("<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\""));
+        assert!(xml.contains("<u:DeletePortMapping xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\"><NewRemoteHost></NewRemoteHost><NewExternalPort>8080</NewExternalPort>"));
+
+        match Envelope::from_str(&xml).unwrap().into_body() {
+            Body::Action { action, arguments } => {
+                assert_eq!(
+                    action.to_string(),
+                    "urn:schemas-upnp-org:service:WANIPConnection:1#DeletePortMapping"
+                );
+                assert_eq!(arguments.len(), 3);
+                assert_eq!(arguments[1].1, "8080");
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let envelope = Envelope::new_response(
+            action(),
+            vec![(
+                "NewExternalIPAddress".to_string(),
+                "203.0.113.1".to_string(),
+            )],
+        );
+        match Envelope::from_str(&envelope.to_string())
+            .unwrap()
+            .into_body()
+        {
+            Body::Response { action, arguments } => {
+                assert_eq!(action.name(), "GetExternalIPAddress");
+                assert_eq!(
+                    arguments,
+                    vec![(
+                        "NewExternalIPAddress".to_string(),
+                        "203.0.113.1".to_string()
+                    )]
+                );
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn test_fault_round_trip() {
+        let envelope = Envelope::new_fault(714, "NoSuchEntryInArray");
+        match Envelope::from_str(&envelope.to_string())
+            .unwrap()
+            .into_body()
+        {
+            Body::Fault {
+                code,
+                upnp_code,
+                upnp_description,
+                ..
+            } => {
+                assert_eq!(code, "s:Client");
+                assert_eq!(upnp_code, "714");
+                assert_eq!(upnp_description, "NoSuchEntryInArray");
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    #[test]
+    fn test_parse_inherited_namespace() {
+        const RESPONSE: &str = "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\"><s:Body><u:GetExternalIPAddressResponse><NewExternalIPAddress>203.0.113.1</NewExternalIPAddress></u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        match Envelope::from_str(RESPONSE).unwrap().into_body() {
+            Body::Response {
+                action: response, ..
+            } => {
+                assert_eq!(response.to_string(), action().to_string());
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+
+        assert!(Envelope::from_str(&RESPONSE.replace("xmlsoap.org", "example.org")).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+/*!
+This module provides the `Udn` type for _Unique Device Names_. A UDN is the `uuid:` prefixed
+identifier of a device that appears in the device description's `UDN` element, in `USN` headers,
+and in search targets.
+
+The UDA requires the UUID portion to be an RFC 4122 UUID; however, many devices in the wild use
+other unique values. Parsing a `Udn` therefore only requires the `uuid:` prefix and a non-empty
+value, use [`Udn::is_rfc4122`](struct.Udn.html#method.is_rfc4122) or
+[`Udn::from_str_strict`](struct.Udn.html#method.from_str_strict) where strict validation is
+required. Newly generated values are always valid RFC 4122 UUIDs.
+
+# Example
+
+```rust
+use upnp_rs::common::udn::Udn;
+use std::str::FromStr;
+
+let udn = Udn::new_v4();
+assert!(udn.is_rfc4122());
+assert!(udn.to_string().starts_with("uuid:"));
+
+let legacy = Udn::from_str("uuid:Upnp-BasicDevice-1_0-00408CA45086").unwrap();
+assert!(!legacy.is_rfc4122());
+```
+*/
+
+#[cfg(feature = "net")]
+use crate::common::interface::hardware_address;
+use crate::error::{invalid_value_for_type, MessageFormatError};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use uuid::Uuid;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A Unique Device Name, the `uuid:` prefixed device identifier.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Udn(String);
+
+///
+/// An alternate name for `Udn`, reflecting its use as the device UUID in headers.
+///
+pub type DeviceUuid = Udn;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+const UDN_PREFIX: &str = "uuid:";
+
+impl Display for Udn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}{}", UDN_PREFIX, self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Udn {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for Udn {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UDN_PREFIX) {
+            Some(uuid) if is_valid_value(uuid) => Ok(Udn(uuid.to_string())),
+            _ => invalid_value_for_type("Udn", s).into(),
+        }
+    }
+}
+
+impl From<Uuid> for Udn {
+    fn from(uuid: Uuid) -> Self {
+        Udn(uuid.hyphenated().to_string())
+    }
+}
+
+impl Udn {
+    ///
+    /// Generate a new, random (version 4), UDN.
+    ///
+    pub fn new_v4() -> Self {
+        Uuid::new_v4().into()
+    }
+
+    ///
+    /// Generate a new time-based (version 1) UDN as described in the UDA annex on UUIDs. The node
+    /// identifier is the hardware address of the first non-loopback interface, if there is no
+    /// such interface, or the `net` feature is not enabled, a random node identifier is used with
+    /// the multicast bit set as required by RFC 4122.
+    ///
+    pub fn new_time_based() -> Self {
+        #[cfg(feature = "net")]
+        let node_id = hardware_address().unwrap_or_else(random_node_id);
+        #[cfg(not(feature = "net"))]
+        let node_id = random_node_id();
+        Uuid::now_v1(&node_id).into()
+    }
+
+    ///
+    /// Parse a UDN, requiring the value to be a valid RFC 4122 UUID.
+    ///
+    pub fn from_str_strict(s: &str) -> Result<Self, MessageFormatError> {
+        let udn = Udn::from_str(s)?;
+        if udn.is_rfc4122() {
+            Ok(udn)
+        } else {
+            invalid_value_for_type("Udn", s).into()
+        }
+    }
+
+    ///
+    /// Returns `true` if the value is a valid RFC 4122 UUID.
+    ///
+    pub fn is_rfc4122(&self) -> bool {
+        self.as_uuid().is_some()
+    }
+
+    ///
+    /// Return the value as a UUID, if it is a valid RFC 4122 UUID.
+    ///
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match Uuid::try_parse(&self.0) {
+            Ok(uuid) if self.0.len() == 36 && uuid.get_variant() == uuid::Variant::RFC4122 => {
+                Some(uuid)
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Return the UUID portion of the UDN, without the `uuid:` prefix.
+    ///
+    pub fn uuid_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn random_node_id() -> [u8; 6] {
+    let random = Uuid::new_v4();
+    let mut node_id = [0u8; 6];
+    node_id.copy_from_slice(&random.as_bytes()[10..]);
+    node_id[0] |= 0x01;
+    node_id
+}
+
+fn is_valid_value(s: &str) -> bool {
+    !s.is_empty() && !s.contains("::") && !s.chars().any(char::is_whitespace)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let udn = Udn::from_str("uuid:2fac1234-31f8-11b4-a222-08002b34c003").unwrap();
+        assert_eq!(udn.uuid_str(), "2fac1234-31f8-11b4-a222-08002b34c003");
+        assert_eq!(udn.to_string(), "uuid:2fac1234-31f8-11b4-a222-08002b34c003");
+        assert!(udn.is_rfc4122());
+
+        assert!(Udn::from_str("uuid:device-1").is_ok());
+        assert!(Udn::from_str_strict("uuid:device-1").is_err());
+        assert!(Udn::from_str("2fac1234-31f8-11b4-a222-08002b34c003").is_err());
+        assert!(Udn::from_str("uuid:").is_err());
+        assert!(Udn::from_str("uuid:device-1::upnp:rootdevice").is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let v4 = Udn::new_v4();
+        assert_eq!(v4.as_uuid().unwrap().get_version_num(), 4);
+        let v1 = Udn::new_time_based();
+        assert_eq!(v1.as_uuid().unwrap().get_version_num(), 1);
+        assert_ne!(v1, Udn::new_time_based());
+        assert_eq!(Udn::from_str(&v1.to_string()).unwrap(), v1);
+    }
+}
@@ -2,8 +2,10 @@
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+use crate::error::{invalid_value_for_type, MessageFormatError};
 use std::fmt::{Display, Error, Formatter};
 use std::str::FromStr;
+use url::Url;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct URI(String);
@@ -24,6 +26,16 @@ impl Display for URI {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for URI {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for URI {
     type Err = ();
 
@@ -32,7 +44,19 @@ impl FromStr for URI {
     }
 }
 
-impl URI {}
+impl URI {
+    ///
+    /// Resolve `reference`, which may be relative or absolute, against this URI as the base. This
+    /// is used to resolve URLs found in a device description against either the `URLBase` value or
+    /// the `LOCATION` the description was retrieved from.
+    ///
+    pub fn resolve(&self, reference: &str) -> Result<URI, MessageFormatError> {
+        match Url::parse(&self.0).and_then(|base| base.join(reference)) {
+            Ok(url) => Ok(URI(url.to_string())),
+            Err(_) => invalid_value_for_type("URL", reference).into(),
+        }
+    }
+}
 
 // ------------------------------------------------------------------------------------------------
 // Private Types
@@ -45,3 +69,28 @@ impl URI {}
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let base = URI::from_str("http://10.0.0.1:49152/desc/root.xml").unwrap();
+        assert_eq!(
+            base.resolve("/scpd.xml").unwrap().to_string(),
+            "http://10.0.0.1:49152/scpd.xml"
+        );
+        assert_eq!(
+            base.resolve("scpd.xml").unwrap().to_string(),
+            "http://10.0.0.1:49152/desc/scpd.xml"
+        );
+        assert_eq!(
+            base.resolve("http://10.0.0.2/scpd.xml")
+                .unwrap()
+                .to_string(),
+            "http://10.0.0.2/scpd.xml"
+        );
+        assert!(URI::from_str("not a url").unwrap().resolve("x").is_err());
+    }
+}
@@ -1,18 +1,542 @@
+/*!
+This module provides the product tokens carried in the `SERVER` and `USER-AGENT` headers. The
+specification requires these to list, in order, the operating system, the UPnP version, and the
+product, each as `name/version`; for example `Linux/5.15 UPnP/1.1 MyProduct/1.0`.
+
+Real-world values often differ, UPnP 1.0 devices commonly separate the tokens with commas, some
+omit the operating system or product, and others add further products or parenthesized comments.
+The parser follows the product and comment grammar of RFC 7231 and only requires a `UPnP` token;
+the product tokens either side of it are taken as the operating system and product, and any others
+are retained.
+
+The operating system token of sent messages is the name of the platform from `std::env::consts::OS`
+with, where it can be detected, the platform version. Detection happens once per process, and a
+failure only omits the version. For reproducible headers the token may be replaced, for all messages
+with [`set_platform`], or for a single set of versions with [`ProductVersions::with_platform`].
+
+Some devices only answer control points they recognize, so the whole value sent may also be fixed
+with [`set_product_versions`]. This applies to every message sent; search, notify, and search
+responses, SOAP control requests, and GENA subscription requests. A product given in the options
+of an individual discovery message still replaces the product token.
+
+# Example
+
+```rust
+use upnp_rs::common::user_agent::ProductVersions;
+use std::str::FromStr;
+
+let versions = ProductVersions::from_str("Linux/5.15, UPnP/1.0, MyProduct/1.0").unwrap();
+assert_eq!(versions.platform_version().name(), "Linux");
+assert_eq!(versions.upnp_version().version(), "1.0");
+assert_eq!(versions.to_string(), "Linux/5.15 UPnP/1.0 MyProduct/1.0");
+
+let versions = ProductVersions::from_str("Linux/3.x UPnP/1.0 MiniUPnPd/2.0 (something)").unwrap();
+assert_eq!(versions.product_version().name(), "MiniUPnPd");
+assert_eq!(versions.others(), &["(something)".to_string()]);
+```
+*/
+
+use crate::error::{invalid_value_for_type, MessageFormatError};
+use crate::{SpecVersion, UPNP_STRING};
+use os_version::{detect, OsVersion};
+use std::env::consts::OS;
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+use std::sync::RwLock;
 use tracing::trace;
 
-use crate::discovery::{ProductVersion, ProductVersions};
-use crate::SpecVersion;
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
 
+///
+/// A product name and version, used in constructing `SERVER` and `USER-AGENT` headers. These
+/// are specified by the UPnP vendor.
+///
+/// Field value MUST begin with the following "product tokens" (defined by HTTP/1.1).
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProductVersion {
+    name: String,
+    version: String,
+}
+
+///
+/// The set of three products, and associated version identifiers, present in both `SERVER` and
+/// `USER-AGENT` headers; in the order operating system, UPnP, and product.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProductVersions {
+    platform: ProductVersion,
+    upnp: ProductVersion,
+    product: ProductVersion,
+    others: Vec<String>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Replace the detected operating system token used in all `SERVER` and `USER-AGENT` headers;
+/// `None` restores the detected value.
+///
+pub fn set_platform(platform: Option<ProductVersion>) {
+    *PLATFORM_OVERRIDE.write().unwrap() = platform;
+}
+
+///
+/// Replace the value of all `SERVER` and `USER-AGENT` headers sent, including the UPnP token, which
+/// is otherwise taken from the specification version of each message; `None` restores the
+/// constructed value.
+///
+pub fn set_product_versions(versions: Option<ProductVersions>) {
+    *VERSIONS_OVERRIDE.write().unwrap() = versions;
+}
+
+///
+/// The value of a `SERVER` or `USER-AGENT` header for a message of `spec_version`; `product`, if
+/// specified, replaces the product token of the default or configured value.
+///
 pub fn user_agent_string(spec_version: SpecVersion, product: Option<ProductVersion>) -> String {
-    let versions = ProductVersions::new(
-        ProductVersion::for_upnp_version(spec_version),
-        if let Some(product) = product {
-            product
-        } else {
-            ProductVersion::for_default_product()
-        },
-        ProductVersion::for_platform(),
+    let versions = product_versions_for(
+        VERSIONS_OVERRIDE.read().unwrap().clone(),
+        spec_version,
+        product,
     );
     trace!("User-Agent: {:?}", versions);
     versions.to_string()
 }
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_PRODUCT_NAME: &str = env!("CARGO_PKG_NAME");
+const DEFAULT_PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+lazy_static! {
+    static ref PLATFORM: ProductVersion = detect_platform();
+    static ref PLATFORM_OVERRIDE: RwLock<Option<ProductVersion>> = RwLock::new(None);
+    static ref VERSIONS_OVERRIDE: RwLock<Option<ProductVersions>> = RwLock::new(None);
+}
+
+impl Display for ProductVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        if self.version.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}/{}", self.name, self.version)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ProductVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for ProductVersion {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((name, version)) if !name.is_empty() && !version.is_empty() => {
+                Ok(Self::for_product(name, version))
+            }
+            _ => invalid_value_for_type("ProductVersion", s).into(),
+        }
+    }
+}
+
+impl ProductVersion {
+    fn empty() -> Self {
+        Self::for_product("", "")
+    }
+
+    pub fn for_default_product() -> Self {
+        Self {
+            name: DEFAULT_PRODUCT_NAME.to_string(),
+            version: DEFAULT_PRODUCT_VERSION.to_string(),
+        }
+    }
+    pub fn for_product(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    pub fn for_default_upnp() -> Self {
+        Self {
+            name: UPNP_STRING.to_string(),
+            version: SpecVersion::default().to_string(),
+        }
+    }
+
+    pub fn for_upnp_version(version: SpecVersion) -> Self {
+        Self {
+            name: UPNP_STRING.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    ///
+    /// The operating system token, either the value set with [`set_platform`] or that detected.
+    ///
+    pub fn for_platform() -> Self {
+        PLATFORM_OVERRIDE
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| PLATFORM.clone())
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+
+    fn is_empty(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    fn is_upnp(&self) -> bool {
+        self.name.eq_ignore_ascii_case(UPNP_STRING)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Default for ProductVersions {
+    fn default() -> Self {
+        Self {
+            platform: ProductVersion::for_platform(),
+            upnp: ProductVersion::for_default_upnp(),
+            product: ProductVersion::for_default_product(),
+            others: Default::default(),
+        }
+    }
+}
+
+impl Display for ProductVersions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let tokens: Vec<String> = [&self.platform, &self.upnp, &self.product]
+            .iter()
+            .filter(|token| !token.is_empty())
+            .map(|token| token.to_string())
+            .chain(self.others.iter().cloned())
+            .collect();
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl FromStr for ProductVersions {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let versions = Self::parse_lenient(s);
+        if versions.upnp.is_empty() {
+            invalid_value_for_type("ProductVersions", s).into()
+        } else {
+            Ok(versions)
+        }
+    }
+}
+
+impl ProductVersions {
+    pub fn new(platform: ProductVersion, upnp: ProductVersion, product: ProductVersion) -> Self {
+        Self {
+            platform,
+            upnp,
+            product,
+            others: Default::default(),
+        }
+    }
+
+    ///
+    /// Parse the value of a `SERVER` or `USER-AGENT` header without requiring a `UPnP` token; if
+    /// there is none the UPnP version is empty, the first product is taken as the operating
+    /// system, and the remaining tokens and comments are retained in `others`.
+    ///
+    pub fn parse_lenient(s: &str) -> Self {
+        let mut tokens: Vec<Option<Token<'_>>> = product_tokens(s).into_iter().map(Some).collect();
+        let is_product = |token: &Option<Token<'_>>| matches!(token, Some(Token::Product(_)));
+        let upnp_index = tokens.iter().position(|token| match token {
+            Some(Token::Product(product)) => product.is_upnp(),
+            _ => false,
+        });
+        let (platform_index, product_index) = match upnp_index {
+            Some(upnp_index) => (
+                tokens[..upnp_index].iter().position(is_product),
+                tokens[upnp_index + 1..]
+                    .iter()
+                    .position(is_product)
+                    .map(|index| upnp_index + 1 + index),
+            ),
+            None => (tokens.iter().position(is_product), None),
+        };
+        let mut take = |index: Option<usize>| match index.and_then(|index| tokens[index].take()) {
+            Some(Token::Product(product)) => product,
+            _ => ProductVersion::empty(),
+        };
+        let platform = take(platform_index);
+        let upnp = take(upnp_index);
+        let product = take(product_index);
+        let others = tokens
+            .into_iter()
+            .flatten()
+            .map(|token| match token {
+                Token::Product(product) => product.to_string(),
+                Token::Comment(comment) => comment.to_string(),
+            })
+            .collect();
+        Self {
+            platform,
+            upnp,
+            product,
+            others,
+        }
+    }
+
+    ///
+    /// Replace the operating system token.
+    ///
+    pub fn with_platform(self, platform: ProductVersion) -> Self {
+        Self { platform, ..self }
+    }
+
+    pub fn platform_version(&self) -> &ProductVersion {
+        &self.platform
+    }
+
+    pub fn upnp_version(&self) -> &ProductVersion {
+        &self.upnp
+    }
+
+    pub fn product_version(&self) -> &ProductVersion {
+        &self.product
+    }
+
+    ///
+    /// Any product tokens and comments beyond the operating system, UPnP, and product tokens, in
+    /// the order they appeared.
+    ///
+    pub fn others(&self) -> &[String] {
+        &self.others
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+enum Token<'a> {
+    Product(ProductVersion),
+    Comment(&'a str),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Split a header value into product tokens and comments, RFC 7231 §5.5.3; commas between tokens
+/// are treated as whitespace. Comments may be nested and contain escaped characters, an
+/// unterminated comment extends to the end of the value.
+///
+fn product_tokens(s: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() || c == ',' {
+            continue;
+        }
+        if c == '(' {
+            let mut depth = 1;
+            let mut end = s.len();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        let _ = chars.next();
+                    }
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = index + 1;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            tokens.push(Token::Comment(&s[start..end]));
+        } else {
+            let mut end = s.len();
+            while let Some((index, c)) = chars.peek() {
+                if c.is_whitespace() || *c == ',' || *c == '(' {
+                    end = *index;
+                    break;
+                }
+                let _ = chars.next();
+            }
+            let token = &s[start..end];
+            let (name, version) = token.split_once('/').unwrap_or((token, ""));
+            tokens.push(Token::Product(ProductVersion::for_product(name, version)));
+        }
+    }
+    tokens
+}
+
+fn product_versions_for(
+    configured: Option<ProductVersions>,
+    spec_version: SpecVersion,
+    product: Option<ProductVersion>,
+) -> ProductVersions {
+    match (configured, product) {
+        (Some(configured), None) => configured,
+        (Some(configured), Some(product)) => ProductVersions {
+            product,
+            ..configured
+        },
+        (None, product) => ProductVersions::new(
+            ProductVersion::for_platform(),
+            ProductVersion::for_upnp_version(spec_version),
+            product.unwrap_or_else(ProductVersion::for_default_product),
+        ),
+    }
+}
+
+///
+/// The platform name is always known, the version is only included if it can be detected.
+///
+fn detect_platform() -> ProductVersion {
+    let version = match detect() {
+        Ok(OsVersion::Linux(v)) => v.version,
+        Ok(OsVersion::MacOS(v)) => Some(v.version),
+        Ok(OsVersion::Windows(v)) => Some(v.version),
+        Ok(OsVersion::OpenBSD(v)) => Some(v.version),
+        Ok(_) => None,
+        Err(e) => {
+            trace!("detect_platform - could not detect version: {}", e);
+            None
+        }
+    };
+    let version = version
+        .map(|version| version.trim().replace(char::is_whitespace, "_"))
+        .unwrap_or_default();
+    ProductVersion::for_product(OS, &version)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_product_versions() {
+        let versions = ProductVersions::from_str("Linux/5.15 UPnP/1.1 MyProduct/1.0").unwrap();
+        assert_eq!(
+            versions.platform_version(),
+            &ProductVersion::for_product("Linux", "5.15")
+        );
+        assert_eq!(
+            versions.upnp_version(),
+            &ProductVersion::for_upnp_version(SpecVersion::V11)
+        );
+        assert_eq!(
+            versions.product_version(),
+            &ProductVersion::for_product("MyProduct", "1.0")
+        );
+        assert_eq!(versions.to_string(), "Linux/5.15 UPnP/1.1 MyProduct/1.0");
+        assert!(ProductVersions::from_str("Some Router").is_err());
+    }
+
+    #[test]
+    fn test_parse_real_world_values() {
+        let versions =
+            ProductVersions::from_str("Linux/3.x UPnP/1.0 MiniUPnPd/2.0 (something)").unwrap();
+        assert_eq!(versions.platform_version().version(), "3.x");
+        assert_eq!(versions.product_version().name(), "MiniUPnPd");
+        assert_eq!(versions.others(), &["(something)".to_string()]);
+
+        let versions = ProductVersions::from_str("Linux/4.9 UPnP/1.0").unwrap();
+        assert_eq!(versions.upnp_version().version(), "1.0");
+        assert!(versions.product_version().name().is_empty());
+        assert_eq!(versions.to_string(), "Linux/4.9 UPnP/1.0");
+
+        let versions = ProductVersions::from_str(
+            "Microsoft-Windows/10.0 (nested (comment\\)) x) upnp/1.1, DLNADOC/1.50 Platinum/1.0",
+        )
+        .unwrap();
+        assert_eq!(versions.platform_version().name(), "Microsoft-Windows");
+        assert_eq!(versions.upnp_version().version(), "1.1");
+        assert_eq!(versions.product_version().name(), "DLNADOC");
+        assert_eq!(
+            versions.others(),
+            &[
+                "(nested (comment\\)) x)".to_string(),
+                "Platinum/1.0".to_string()
+            ]
+        );
+
+        let versions = ProductVersions::parse_lenient("Some Router");
+        assert_eq!(versions.platform_version().name(), "Some");
+        assert!(versions.upnp_version().name().is_empty());
+        assert_eq!(versions.others(), &["Router".to_string()]);
+    }
+
+    #[test]
+    fn test_platform() {
+        let detected = ProductVersion::for_platform();
+        assert_eq!(detected.name(), OS);
+        let versions =
+            ProductVersions::default().with_platform(ProductVersion::for_product("TestOS", "1.0"));
+        assert!(versions.to_string().starts_with("TestOS/1.0 UPnP/"));
+    }
+
+    #[test]
+    fn test_user_agent_order() {
+        let user_agent = user_agent_string(
+            SpecVersion::V11,
+            Some(ProductVersion::for_product("Test", "2.0")),
+        );
+        assert!(user_agent.ends_with(" UPnP/1.1 Test/2.0"));
+    }
+
+    #[test]
+    fn test_configured_versions() {
+        let configured =
+            ProductVersions::from_str("Windows/10.0 UPnP/1.0 DLNADOC/1.50 Player/12.0").unwrap();
+        assert_eq!(
+            product_versions_for(Some(configured.clone()), SpecVersion::V20, None).to_string(),
+            "Windows/10.0 UPnP/1.0 DLNADOC/1.50 Player/12.0"
+        );
+        assert_eq!(
+            product_versions_for(
+                Some(configured),
+                SpecVersion::V20,
+                Some(ProductVersion::for_product("Test", "2.0"))
+            )
+            .to_string(),
+            "Windows/10.0 UPnP/1.0 Test/2.0 Player/12.0"
+        );
+        assert!(product_versions_for(None, SpecVersion::V20, None)
+            .to_string()
+            .contains(" UPnP/2.0 "));
+    }
+}
@@ -1,228 +1,745 @@
 /*!
-One-line description.
+This module provides a simple element tree constructed from an XML document. This is sufficient
+for the structured documents used in UPnP (device descriptions, service descriptions, and SOAP
+envelopes) where mixed content is not used. Elements may be queried, and modified in place, before
+being used to construct a typed value.
 
-More detailed description, with
+Each element owns its attributes and children, there are no shared or parent references, and so a
+parsed tree is `Send` and `Sync`; it may be moved to, or shared between, threads once parsing is
+complete, for example from the thread fetching a description to the one interpreting it.
 
 # Example
 
+```rust
+use upnp_rs::common::xml::read::read_xml;
+
+let root = read_xml("<root><name>Test</name></root>").unwrap();
+assert_eq!(root.local_name(), "root");
+assert_eq!(root.child_text("name"), Some("Test"));
+```
 */
 
-use crate::common::xml::udom::{Document, Error as DOMError, Name, Node, NodeType};
-use crate::{Error, MessageErrorKind};
-use quick_xml::events::{BytesStart, BytesText, Event};
-use quick_xml::Reader;
-use std::borrow::BorrowMut;
-use std::convert::TryFrom;
-use std::rc::Rc;
+use crate::common::xml::write::{RootWritable, Writable, WriteOptions};
+use crate::error::{
+    invalid_field_value, missing_required_field, xml_error, Error, MessageFormatError,
+};
+use crate::syntax::{XML_ATTR_ID, XML_ATTR_NAMESPACE, XML_ATTR_UNQUALIFIED_ID};
+use crate::UPNP_DOMAIN;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::{Error as XMLError, NsReader, Writer};
+use std::collections::HashMap;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::Write;
 use std::str::from_utf8;
+use tracing::{error, warn};
 
 // ------------------------------------------------------------------------------------------------
-// Public Functions
+// Public Types
 // ------------------------------------------------------------------------------------------------
 
-pub fn read_xml(xml: &str) -> Result<Document, Error> {
-    let mut reader = Reader::from_str(xml);
-    reader.trim_text(true);
+///
+/// A single element, the element name is the qualified name as it appears in the document and the
+/// namespace is the URI bound to its prefix, or the default namespace, where the element appears.
+/// Character data for an element is concatenated into the single `text` value.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+}
 
-    let event_buffer: Vec<u8> = Vec::new();
+///
+/// An element, with its attributes and descendants, that is not understood by the parser of a
+/// typed value, such as a vendor extension in a description. It is retained so that it can be
+/// written out again when the value is.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlFragment(Element);
 
-    read_loop_outer(reader, event_buffer)
-}
+// ------------------------------------------------------------------------------------------------
+// Public Values
+// ------------------------------------------------------------------------------------------------
 
-fn read_loop_outer(
-    mut reader: Reader<&[u8]>,
-    mut event_buffer: Vec<u8>,
-) -> Result<Document, Error> {
-    let mut document = Document::default();
+///
+/// The deepest nesting of elements accepted by [`read_xml`].
+///
+pub const MAX_ELEMENT_DEPTH: usize = 256;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Read the XML document in `xml` and return the document (root) element. Comments and processing
+/// instructions are not retained. Some devices omit namespace declarations, so an element using an
+/// undeclared prefix is accepted but has no namespace. Documents nested more than
+/// `MAX_ELEMENT_DEPTH` elements deep are rejected.
+///
+pub fn read_xml(xml: &str) -> Result<Element, Error> {
+    let mut reader = NsReader::from_str(xml);
+    reader.trim_text(true);
 
+    let mut stack: Vec<Element> = Vec::new();
     loop {
-        match reader.read_event(&mut event_buffer) {
-            Ok(Event::Start(ev)) => {
-                trace!("read_loop_outer - Event::Start ({:?})", ev);
-                document.document_element = Some(try_element_from(ev)?);
-                let document = read_loop_inner(reader, event_buffer, document)?;
-                return Ok(document);
+        match reader.read_resolved_event().map_err(xml_error)? {
+            (namespace, Event::Start(ev)) => {
+                if stack.len() == MAX_ELEMENT_DEPTH {
+                    error!(
+                        "read_xml - document exceeds the maximum depth of {}",
+                        MAX_ELEMENT_DEPTH
+                    );
+                    return Err(xml_error(XMLError::UnexpectedToken(
+                        String::from_utf8_lossy(ev.name().as_ref()).to_string(),
+                    )));
+                }
+                stack.push(element_from(namespace, &ev)?);
             }
-            Ok(Event::Empty(ev)) => {
-                document.document_element = Some(try_element_from(ev)?);
-                let document = read_loop_inner(reader, event_buffer, document)?;
-                return Ok(document);
+            (namespace, Event::Empty(ev)) => {
+                let element = element_from(namespace, &ev)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
             }
-            Ok(Event::Decl(ev)) => {
-                trace!("read_loop_outer - Event::Decl ({:?})", ev);
-                let pi_node = Document::create_xml_declaration("", Some(""), Some(true))?;
-                document.children.push(Rc::new(pi_node));
+            (_, Event::End(_)) => {
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
             }
-            Ok(Event::PI(ev)) => {
-                trace!("read_loop_outer - Event::PI");
-                ev.
-                let pi_node = Document::create_processing_instruction("", "")?;
-                document.children.push(Rc::new(pi_node));
+            (_, Event::Text(ev)) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&ev.unescape().map_err(xml_error)?);
+                }
             }
-            Ok(Event::DocType(ev)) => {
-                trace!("read_loop_outer - Event::DocType ({:?})", ev);
+            (_, Event::CData(ev)) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(from_utf8(&ev.into_inner()).map_err(MessageFormatError::from)?);
+                }
             }
-            Ok(Event::Eof) => break,
-            Ok(ev) => {
-                error!("read_loop_outer, unexpected event {:?}", ev);
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
-            }
-            Err(err) => {
-                error!(
-                    "read_loop_outer error at position {}: {:?}",
-                    reader.buffer_position(),
-                    err
-                );
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
+            (_, Event::Eof) => {
+                error!("read_xml - unexpected end of document");
+                return missing_required_field("document element").into();
             }
+            _ => {}
         }
     }
-    Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError))
 }
 
-fn read_loop_inner(
-    mut reader: Reader<&[u8]>,
-    mut event_buffer: Vec<u8>,
-    document: Document,
-) -> Result<Document, Error> {
-    let mut current_element = document.document_element.unwrap();
-    loop {
-        let child_node = match reader.read_event(&mut event_buffer) {
-            Ok(Event::Start(ev)) => {
-                trace!("read_loop_inner - Event::Start ({:?})", ev);
-                let mut new_element = try_element_from(ev)?;
-                let c = &mut current_element;
-
-                c.borrow_mut().append_child(c, &mut new_element);
-                current_element = new_element;
-                None
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Element {
+    ///
+    /// Return the name of this element with any namespace prefix removed.
+    ///
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    ///
+    /// Return the value of the named attribute, if present.
+    ///
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    ///
+    /// Return the first child element with the given local name.
+    ///
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.local_name() == name)
+    }
+
+    ///
+    /// Return all child elements with the given local name.
+    ///
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.local_name() == name)
+    }
+
+    ///
+    /// Return the first child element, if any.
+    ///
+    pub fn first_child(&self) -> Option<&Element> {
+        self.children.first()
+    }
+
+    ///
+    /// Return the last child element, if any.
+    ///
+    pub fn last_child(&self) -> Option<&Element> {
+        self.children.last()
+    }
+
+    ///
+    /// Return the index of `child` in the children of this element; the comparison is by
+    /// identity, not value, so `child` must be a reference into this element's children.
+    ///
+    pub fn index_of(&self, child: &Element) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|existing| std::ptr::eq(existing, child))
+    }
+
+    ///
+    /// Return the child before the child at `index`, `None` if `index` is the first child or is
+    /// out of range. As elements do not refer to their parent, siblings are found from the parent.
+    ///
+    /// ```rust
+    /// use upnp_rs::common::xml::read::read_xml;
+    ///
+    /// let root = read_xml("<root><a/><b/><c/></root>").unwrap();
+    /// let b = root.child("b").unwrap();
+    /// let index = root.index_of(b).unwrap();
+    /// assert_eq!(root.previous_sibling(index).unwrap().name, "a");
+    /// assert_eq!(root.next_sibling(index).unwrap().name, "c");
+    /// ```
+    ///
+    pub fn previous_sibling(&self, index: usize) -> Option<&Element> {
+        if index < self.children.len() {
+            index
+                .checked_sub(1)
+                .and_then(|index| self.children.get(index))
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Return the child after the child at `index`, `None` if `index` is the last child or is out
+    /// of range, see [`previous_sibling`](Self::previous_sibling).
+    ///
+    pub fn next_sibling(&self, index: usize) -> Option<&Element> {
+        self.children.get(index.checked_add(1)?)
+    }
+
+    ///
+    /// Return all descendant elements, in document order, with the given local name; the name
+    /// `"*"` matches all elements. This element is not included.
+    ///
+    pub fn elements_by_tag_name(&self, name: &str) -> Vec<&Element> {
+        let mut found = Vec::new();
+        for child in &self.children {
+            if name == "*" || child.local_name() == name {
+                found.push(child);
             }
-            Ok(Event::End(ev)) => {
-                trace!("read_loop_inner - Event::End ({:?})", ev);
-                let name = Name::try_from(from_utf8(ev.name())?)?;
-                if name != current_element.name {
-                    return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
-                } else {
-                    match current_element.parent_node.clone() {
-                        Some(parent) => current_element = parent,
-                        None => return Ok(document),
-                    }
+            found.extend(child.elements_by_tag_name(name));
+        }
+        found
+    }
+
+    ///
+    /// Return the identifier of this element, the value of its `xml:id` attribute or, if that is
+    /// not present, of an unqualified `id` attribute as used by DIDL-Lite. As no DTD is read, no
+    /// other attribute is treated as an identifier.
+    ///
+    pub fn id(&self) -> Option<&str> {
+        self.attribute(XML_ATTR_ID)
+            .or_else(|| self.attribute(XML_ATTR_UNQUALIFIED_ID))
+    }
+
+    ///
+    /// Return the first element, in document order, whose identifier is `id`; this element is
+    /// included. The identifier is taken from the attributes at the time of the call, so the
+    /// result reflects any children added or removed since the document was read.
+    ///
+    /// ```rust
+    /// use upnp_rs::common::xml::read::read_xml;
+    ///
+    /// let root = read_xml(
+    ///     "<DIDL-Lite><container id=\"1\"><item id=\"1.1\"><title>One</title></item>\
+    ///      </container></DIDL-Lite>",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(root.element_by_id("1.1").unwrap().child_text("title"), Some("One"));
+    /// assert!(root.element_by_id("2").is_none());
+    /// ```
+    ///
+    pub fn element_by_id(&self, id: &str) -> Option<&Element> {
+        if self.id() == Some(id) {
+            Some(self)
+        } else {
+            self.children
+                .iter()
+                .find_map(|child| child.element_by_id(id))
+        }
+    }
+
+    ///
+    /// Return the first element, in document order, whose identifier is `id` for modification,
+    /// see [`element_by_id`](Self::element_by_id).
+    ///
+    pub fn element_by_id_mut(&mut self, id: &str) -> Option<&mut Element> {
+        if self.id() == Some(id) {
+            Some(self)
+        } else {
+            self.children
+                .iter_mut()
+                .find_map(|child| child.element_by_id_mut(id))
+        }
+    }
+
+    ///
+    /// Return a map from identifier to element for this element and all its descendants, useful
+    /// when many lookups are made on a document that is not being modified. Where an identifier
+    /// is repeated the first element, in document order, is used as for
+    /// [`element_by_id`](Self::element_by_id).
+    ///
+    pub fn ids(&self) -> HashMap<&str, &Element> {
+        let mut ids = HashMap::new();
+        self.collect_ids(&mut ids);
+        ids
+    }
+
+    ///
+    /// Return all elements matching `path`, in document order. The path is a list of steps
+    /// separated by `/`, the first step matches this element and each later step matches its
+    /// children. A step is a local name, or `"*"` for any element, optionally followed by a
+    /// zero-based index such as `service[1]` to select a single match from each parent. A name
+    /// matches elements in any namespace, a name of the form `{urn:schemas-upnp-org:device-1-0}device`
+    /// only matches elements in that namespace. A step that cannot be parsed matches nothing.
+    ///
+    /// ```rust
+    /// use upnp_rs::common::xml::read::read_xml;
+    ///
+    /// let root = read_xml(
+    ///     "<root><device><serviceList><service><serviceId>a</serviceId></service>\
+    ///      <service><serviceId>b</serviceId></service></serviceList></device></root>",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(root.select("root/device/serviceList/service").len(), 2);
+    /// assert_eq!(
+    ///     root.select_text("root/device/serviceList/service[1]/serviceId"),
+    ///     Some("b")
+    /// );
+    /// ```
+    ///
+    pub fn select(&self, path: &str) -> Vec<&Element> {
+        let mut steps = path.split('/').filter(|step| !step.is_empty());
+        let mut selected: Vec<&Element> = match steps.next().map(Step::parse) {
+            Some(Some(step)) => step.select(std::iter::once(self)),
+            _ => return Vec::new(),
+        };
+        for step in steps {
+            match Step::parse(step) {
+                Some(step) => {
+                    selected = selected
+                        .into_iter()
+                        .flat_map(|parent| step.select(parent.children.iter()))
+                        .collect()
                 }
-                None
-            }
-            Ok(Event::Empty(ev)) => {
-                trace!("read_loop_inner - Event::Empty ({:?})", ev);
-                Some(try_element_from(ev)?)
-            }
-            Ok(Event::Text(ev)) => {
-                trace!("read_loop_inner - Event::Text ({:?})", ev);
-                Some(Rc::new(Document::create_text_node(&string_from_bytes(
-                    ev, &reader,
-                )?)))
-            }
-            Ok(Event::Comment(ev)) => {
-                trace!("read_loop_inner - Event::Comment ({:?})", ev);
-                Some(Rc::new(Document::create_comment(&string_from_bytes(
-                    ev, &reader,
-                )?)))
-            }
-            Ok(Event::CData(ev)) => {
-                trace!("read_loop_inner - Event::CData ({:?})", ev);
-                Some(Rc::new(Document::create_cdata_section(
-                    &string_from_bytes(ev, &reader)?,
-                )?))
-            }
-            Ok(Event::Decl(ev)) => {
-                trace!("read_loop_inner - Event::Decl ({:?})", ev);
-                None
-            }
-            Ok(Event::PI(ev)) => {
-                trace!("read_loop_inner - Event::PI ({:?})", ev);
-                None
+                None => return Vec::new(),
             }
-            Ok(Event::DocType(ev)) => {
-                trace!("read_loop_inner - Event::DocType ({:?})", ev);
-                None
-            }
-            Err(err) => {
+        }
+        selected
+    }
+
+    ///
+    /// Return the first element matching `path`, see [`select`](Self::select).
+    ///
+    pub fn select_first(&self, path: &str) -> Option<&Element> {
+        self.select(path).into_iter().next()
+    }
+
+    ///
+    /// Return the text of the first element matching `path`, see [`select`](Self::select).
+    ///
+    pub fn select_text(&self, path: &str) -> Option<&str> {
+        self.select_first(path).map(|element| element.text.as_str())
+    }
+
+    ///
+    /// Returns `true` if this element has the named attribute.
+    ///
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
+
+    ///
+    /// Set the value of the named attribute, replacing any existing value.
+    ///
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.attributes.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    ///
+    /// Remove the named attribute, returning its value if it was present.
+    ///
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let index = self.attributes.iter().position(|(key, _)| key == name)?;
+        Some(self.attributes.remove(index).1)
+    }
+
+    ///
+    /// Add `child` as the last child of this element.
+    ///
+    pub fn append_child(&mut self, child: Element) -> &mut Element {
+        self.children.push(child);
+        self.children.last_mut().unwrap()
+    }
+
+    ///
+    /// Insert `child` before the child currently at `index`; if `index` is equal to the number
+    /// of children this is the same as `append_child`.
+    ///
+    pub fn insert_before(&mut self, index: usize, child: Element) -> Result<(), Error> {
+        if index > self.children.len() {
+            return invalid_child_index(&self.name, index);
+        }
+        self.children.insert(index, child);
+        Ok(())
+    }
+
+    ///
+    /// Replace the child at `index` with `child`, returning the replaced element.
+    ///
+    pub fn replace_child(&mut self, index: usize, child: Element) -> Result<Element, Error> {
+        match self.children.get_mut(index) {
+            Some(existing) => Ok(std::mem::replace(existing, child)),
+            None => invalid_child_index(&self.name, index),
+        }
+    }
+
+    ///
+    /// Remove the child at `index`, returning the removed element.
+    ///
+    pub fn remove_child(&mut self, index: usize) -> Result<Element, Error> {
+        if index < self.children.len() {
+            Ok(self.children.remove(index))
+        } else {
+            invalid_child_index(&self.name, index)
+        }
+    }
+
+    ///
+    /// Return a copy of this element; if `deep` is `false` the copy has no children.
+    ///
+    pub fn clone_node(&self, deep: bool) -> Element {
+        Element {
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            attributes: self.attributes.clone(),
+            text: self.text.clone(),
+            children: if deep {
+                self.children.clone()
+            } else {
+                Default::default()
+            },
+        }
+    }
+
+    ///
+    /// Write this element, and its descendants, as a complete XML document. Text and attribute
+    /// values are escaped, and a namespace declaration is added to any element whose namespace is
+    /// not already in scope.
+    ///
+    pub fn write_to<W: Write>(&self, writer: W, options: &WriteOptions) -> Result<W, Error> {
+        self.write_root_with(writer, options)
+    }
+
+    fn collect_ids<'a>(&'a self, ids: &mut HashMap<&'a str, &'a Element>) {
+        if let Some(id) = self.id() {
+            let _ = ids.entry(id).or_insert(self);
+        }
+        for child in &self.children {
+            child.collect_ids(ids);
+        }
+    }
+
+    ///
+    /// Return the text content of the first child element with the given local name.
+    ///
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.as_str())
+    }
+
+    ///
+    /// Return the text content of the first child element with the given local name, or an error
+    /// if no such child exists.
+    ///
+    pub fn required_child_text(&self, name: &str) -> Result<&str, MessageFormatError> {
+        match self.child_text(name) {
+            Some(text) => Ok(text),
+            None => {
                 error!(
-                    "read_loop_inner error at position {}: {:?}",
-                    reader.buffer_position(),
-                    err
+                    "required_child_text - element '{}' missing child '{}'",
+                    self.name, name
                 );
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
+                missing_required_field(name).into()
             }
-            Ok(Event::Eof) => break,
-        };
-        if let Some(mut child_node) = child_node {
-            Rc::make_mut(&mut current_element).append_child(&mut current_element, &mut child_node);
         }
     }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Element {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        let options = WriteOptions {
+            xml_decl: false,
+            ..Default::default()
+        };
+        let written = self.write_to(Vec::new(), &options).map_err(|_| FmtError)?;
+        write!(f, "{}", String::from_utf8(written).map_err(|_| FmtError)?)
+    }
+}
+
+impl<T: Write> RootWritable<T> for Element {}
+
+impl<T: Write> Writable<T> for Element {
+    fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
+        write_element(writer, self, &mut Vec::new()).map_err(xml_error)
+    }
+}
+
+impl From<Element> for XmlFragment {
+    fn from(element: Element) -> Self {
+        Self(element)
+    }
+}
 
-    Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError))
+impl Display for XmlFragment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for XmlFragment {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl XmlFragment {
+    pub fn element(&self) -> &Element {
+        &self.0
+    }
+
+    pub fn into_element(self) -> Element {
+        self.0
+    }
+
+    ///
+    /// Return copies of the children of `element` that are not in the `known` set of UPnP
+    /// elements; a child with a known local name, but in a namespace from another domain, is also
+    /// returned.
+    ///
+    pub fn unknown_children(element: &Element, known: &[&str]) -> Vec<XmlFragment> {
+        element
+            .children
+            .iter()
+            .filter(|child| {
+                !known.contains(&child.local_name())
+                    || !child
+                        .namespace
+                        .as_deref()
+                        .map(|namespace| namespace.contains(UPNP_DOMAIN))
+                        .unwrap_or(true)
+            })
+            .cloned()
+            .map(XmlFragment)
+            .collect()
+    }
+
+    ///
+    /// Write this fragment where `namespace` is the default namespace, so that a fragment in the
+    /// same namespace as its parent does not repeat the declaration.
+    ///
+    pub fn write_in<T: Write>(&self, writer: &mut Writer<T>, namespace: &str) -> Result<(), Error> {
+        let mut scope = vec![(String::new(), namespace.to_string())];
+        write_element(writer, &self.0, &mut scope).map_err(xml_error)
+    }
 }
 
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// One step of a path used by [`Element::select`].
+///
+#[derive(Debug)]
+struct Step<'a> {
+    namespace: Option<&'a str>,
+    name: &'a str,
+    index: Option<usize>,
+}
+
+impl<'a> Step<'a> {
+    fn parse(step: &'a str) -> Option<Self> {
+        let (step, index) = match step.strip_suffix(']') {
+            Some(step) => {
+                let (step, index) = step.split_once('[')?;
+                (step, Some(index.trim().parse::<usize>().ok()?))
+            }
+            None => (step, None),
+        };
+        let (namespace, name) = match step.strip_prefix('{') {
+            Some(step) => {
+                let (namespace, name) = step.split_once('}')?;
+                (Some(namespace), name)
+            }
+            None => (None, step),
+        };
+        if name.is_empty() || name.contains(['[', ']', '{', '}']) {
+            None
+        } else {
+            Some(Self {
+                namespace,
+                name,
+                index,
+            })
+        }
+    }
+
+    fn matches(&self, element: &Element) -> bool {
+        (self.name == "*" || element.local_name() == self.name)
+            && match self.namespace {
+                None => true,
+                Some(namespace) => element.namespace.as_deref() == Some(namespace),
+            }
+    }
+
+    fn select<'e, I>(&self, candidates: I) -> Vec<&'e Element>
+    where
+        I: Iterator<Item = &'e Element>,
+    {
+        let mut matched = candidates.filter(|element| self.matches(element));
+        match self.index {
+            None => matched.collect(),
+            Some(index) => matched.nth(index).into_iter().collect(),
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-#[inline]
-fn string_from_bytes(bytes: BytesText<'_>, reader: &Reader<&[u8]>) -> Result<String, Error> {
-    bytes
-        .unescape_and_decode(reader)
-        .map_err(|_| Error::MessageFormat(MessageErrorKind::InvalidEncoding))
+fn local_name(name: &str) -> &str {
+    match name.find(':') {
+        None => name,
+        Some(index) => &name[index + 1..],
+    }
 }
 
-fn string_from_utf8(value: &[u8]) -> Result<&str, DOMError> {
-    match from_utf8(value) {
-        Ok(value) => Ok(value),
-        Err(e) => {
-            error!("Could not convert from UTF-8, error {:?}", e);
-            Err(DOMError::InvalidCharacter)
+fn write_element<T: Write>(
+    writer: &mut Writer<T>,
+    element: &Element,
+    scope: &mut Vec<(String, String)>,
+) -> Result<(), quick_xml::Error> {
+    let scope_len = scope.len();
+    for (name, value) in &element.attributes {
+        if name == XML_ATTR_NAMESPACE {
+            scope.push((String::new(), value.clone()));
+        } else if let Some(prefix) = name
+            .strip_prefix(XML_ATTR_NAMESPACE)
+            .and_then(|name| name.strip_prefix(':'))
+        {
+            scope.push((prefix.to_string(), value.clone()));
         }
     }
-}
 
-fn try_element_from(ev: BytesStart<'_>) -> Result<Node, DOMError> {
-    trace!("try_element_from");
-    let name = string_from_utf8(ev.name())?;
-    let element = Rc::new(Document::create_element(name)?);
-
-    for maybe in ev.attributes() {
-        let attribute = maybe.unwrap();
-        let mut attribute_node = Document::create_attribute(string_from_utf8(attribute.key)?)?;
-        match &attribute_node.rc_node.node {
-            NodeType::Attribute {
-                mut value,
-                mut owner_element,
-                ..
-            } => {
-                value = string_from_utf8(attribute.value.as_ref())?.to_string();
-                owner_element = Some(element);
-            }
-            _ => return Err(DOMError::Syntax),
+    let mut start = BytesStart::new(element.name.as_str());
+    if let Some(namespace) = &element.namespace {
+        let prefix = match element.name.split_once(':') {
+            Some((prefix, _)) => prefix,
+            None => "",
+        };
+        let in_scope = scope
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == prefix)
+            .map(|(_, bound)| bound == namespace)
+            .unwrap_or_default();
+        if !in_scope {
+            let xmlns = if prefix.is_empty() {
+                XML_ATTR_NAMESPACE.to_string()
+            } else {
+                format!("{}:{}", XML_ATTR_NAMESPACE, prefix)
+            };
+            start.push_attribute((xmlns.as_str(), namespace.as_str()));
+            scope.push((prefix.to_string(), namespace.clone()));
         }
-        element
-            .attributes
-            .insert(attribute_node.name, Rc::new(attribute_node));
     }
-    Ok(element)
-}
+    for (name, value) in &element.attributes {
+        start.push_attribute((name.as_str(), value.as_str()));
+    }
+    writer.write_event(Event::Start(start))?;
 
-impl From<DOMError> for Error {
-    fn from(e: DOMError) -> Self {
-        error!("XML DOM error: {:?}", e);
-        Error::MessageFormat(MessageErrorKind::XmlFormattingError)
+    // text is always written for a leaf element to keep the end tag on the same line when
+    // indenting.
+    if !element.text.is_empty() || element.children.is_empty() {
+        writer.write_event(Event::Text(BytesText::new(&element.text)))?;
+    }
+    for child in &element.children {
+        write_element(writer, child, scope)?;
     }
+
+    writer.write_event(Event::End(BytesEnd::new(element.name.as_str())))?;
+    scope.truncate(scope_len);
+    Ok(())
 }
 
-// ------------------------------------------------------------------------------------------------
-// Modules
-// ------------------------------------------------------------------------------------------------
+fn invalid_child_index<T>(name: &str, index: usize) -> Result<T, Error> {
+    error!(
+        "invalid_child_index - element '{}' has no child at index {}",
+        name, index
+    );
+    invalid_field_value(name, index.to_string()).into()
+}
+
+fn element_from(namespace: ResolveResult<'_>, ev: &BytesStart<'_>) -> Result<Element, Error> {
+    let name = from_utf8(ev.name().as_ref())
+        .map_err(MessageFormatError::from)?
+        .to_string();
+    let namespace = match namespace {
+        ResolveResult::Bound(namespace) => Some(
+            from_utf8(namespace.as_ref())
+                .map_err(MessageFormatError::from)?
+                .to_string(),
+        ),
+        ResolveResult::Unbound => None,
+        ResolveResult::Unknown(_) => {
+            warn!("element_from - element '{}' has an undeclared prefix", name);
+            None
+        }
+    };
+    let mut element = Element {
+        name,
+        namespace,
+        ..Default::default()
+    };
+    for attribute in ev.attributes() {
+        let attribute = attribute.map_err(|e| xml_error(e.into()))?;
+        element.attributes.push((
+            from_utf8(attribute.key.as_ref())
+                .map_err(MessageFormatError::from)?
+                .to_string(),
+            attribute.unescape_value().map_err(xml_error)?.to_string(),
+        ));
+    }
+    Ok(element)
+}
 
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
@@ -231,61 +748,253 @@ impl From<DOMError> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use log::LevelFilter;
 
-    #[allow(unused_must_use)]
-    fn setup_logging() {
-        env_logger::Builder::from_default_env()
-            .filter_module("upnp_rs::common::xml::read", LevelFilter::Trace)
-            .try_init();
+    #[test]
+    fn test_xml_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Element>();
+        assert_send_sync::<XmlFragment>();
+
+        let root = read_xml("<root><a>one</a></root>").unwrap();
+        let handle = std::thread::spawn(move || root.child_text("a").map(str::to_string));
+        assert_eq!(handle.join().unwrap(), Some("one".to_string()));
     }
 
     #[test]
     fn test_xml_read_minimal() {
-        const TEST_DOC: &str = "<root></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        println!("{:#?}", doc);
+        let root = read_xml("<root></root>").unwrap();
+        assert_eq!(root.name, "root");
+        assert!(root.children.is_empty());
     }
 
     #[test]
-    fn test_xml_read_minimal_with_namespace() {
-        const TEST_DOC: &str = "<root xmlns=\"urn:schemas-upnp-org:device-1-0\"></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        assert_eq!(doc.document_element.unwrap().attributes.len(), 1);
-        let ns = doc.document_element.unwrap().attributes.get(0).unwrap();
-        assert_eq!(ns.name.to_string(), "xmlns");
-        assert_eq!(ns.value, "urn:schemas-upnp-org:device-1-0");
-        println!("{:#?}", doc);
+    fn test_xml_read_with_decl_and_namespace() {
+        const TEST_DOC: &str = "<?xml version=\"1.0\"?><s:root xmlns:s=\"urn:schemas-upnp-org:device-1-0\"><s:a>one &amp; two</s:a><b/><a><![CDATA[<three>]]></a></s:root>";
+        let root = read_xml(TEST_DOC).unwrap();
+        assert_eq!(root.name, "s:root");
+        assert_eq!(root.local_name(), "root");
+        assert_eq!(
+            root.namespace.as_deref(),
+            Some("urn:schemas-upnp-org:device-1-0")
+        );
+        assert_eq!(
+            root.children[0].namespace.as_deref(),
+            Some("urn:schemas-upnp-org:device-1-0")
+        );
+        assert_eq!(root.children[1].namespace, None);
+        assert_eq!(
+            root.attribute("xmlns:s"),
+            Some("urn:schemas-upnp-org:device-1-0")
+        );
+        assert_eq!(root.children.len(), 3);
+        assert_eq!(root.child_text("a"), Some("one & two"));
+        assert_eq!(
+            root.children_named("a")
+                .map(|e| e.text.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["one & two", "<three>"]
+        );
+        assert!(root.required_child_text("c").is_err());
     }
 
     #[test]
-    fn test_xml_read_minimal_with_decl() {
+    fn test_xml_read_unterminated() {
+        assert!(read_xml("<root><a></a>").is_err());
+    }
+
+    #[test]
+    fn test_xml_read_too_deep() {
+        let nested = |depth: usize| format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+        assert!(read_xml(&nested(MAX_ELEMENT_DEPTH)).is_ok());
+        assert!(read_xml(&nested(MAX_ELEMENT_DEPTH + 1)).is_err());
+        assert!(read_xml(&"<a>".repeat(300_000)).is_err());
+    }
+
+    #[test]
+    fn test_xml_queries() {
+        let root = read_xml(
+            "<root><a id=\"1\"><b>one</b></a><s:b xmlns:s=\"urn:s\">two</s:b><c><b>three</b></c></root>",
+        )
+        .unwrap();
+        assert_eq!(
+            root.elements_by_tag_name("b")
+                .iter()
+                .map(|e| e.text.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["one", "two", "three"]
+        );
+        assert_eq!(root.elements_by_tag_name("*").len(), 5);
+        assert!(root.children[0].has_attribute("id"));
+        assert!(!root.children[0].has_attribute("name"));
+    }
+
+    #[test]
+    fn test_xml_siblings() {
+        let root = read_xml("<root><a/><b/><c/></root>").unwrap();
+        assert_eq!(root.first_child().unwrap().name, "a");
+        assert_eq!(root.last_child().unwrap().name, "c");
+        assert!(root.children[0].first_child().is_none());
+        assert!(root.children[0].last_child().is_none());
+
+        assert_eq!(root.index_of(&root.children[2]), Some(2));
+        assert_eq!(root.index_of(&root.children[2].clone()), None);
+
+        assert!(root.previous_sibling(0).is_none());
+        assert_eq!(root.previous_sibling(2).unwrap().name, "b");
+        assert!(root.previous_sibling(3).is_none());
+        assert_eq!(root.next_sibling(0).unwrap().name, "b");
+        assert!(root.next_sibling(2).is_none());
+        assert!(root.next_sibling(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_xml_ids() {
+        let mut root = read_xml(
+            "<root xml:id=\"r\"><a id=\"1\"><b xml:id=\"2\" id=\"3\"/></a><c id=\"1\"/></root>",
+        )
+        .unwrap();
+        assert_eq!(root.element_by_id("r").unwrap().name, "root");
+        assert_eq!(root.element_by_id("1").unwrap().name, "a");
+        assert_eq!(root.element_by_id("2").unwrap().name, "b");
+        assert!(root.element_by_id("3").is_none());
+
+        let ids = root.ids();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids["1"].name, "a");
+
+        root.remove_child(0).unwrap();
+        assert_eq!(root.element_by_id("1").unwrap().name, "c");
+        assert!(root.element_by_id("2").is_none());
+        root.append_child(Element {
+            name: "d".to_string(),
+            ..Default::default()
+        })
+        .set_attribute("xml:id", "4");
+        root.element_by_id_mut("4")
+            .unwrap()
+            .set_attribute("name", "new");
+        assert_eq!(root.children[1].attribute("name"), Some("new"));
+    }
+
+    #[test]
+    fn test_xml_modify() {
+        let mut root = read_xml("<root><a/><c/></root>").unwrap();
+        let b = Element {
+            name: "b".to_string(),
+            ..Default::default()
+        };
+        root.insert_before(1, b.clone()).unwrap();
+        assert!(root.insert_before(4, b.clone()).is_err());
+        root.append_child(b.clone()).set_attribute("id", "4");
+
+        let replaced = root.replace_child(2, b).unwrap();
+        assert_eq!(replaced.name, "c");
+        assert_eq!(root.remove_child(3).unwrap().attribute("id"), Some("4"));
+        assert!(root.remove_child(3).is_err());
+        assert_eq!(
+            root.children
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["a", "b", "b"]
+        );
+
+        root.set_attribute("id", "1");
+        root.set_attribute("id", "2");
+        assert_eq!(root.attributes.len(), 1);
+        assert_eq!(root.remove_attribute("id"), Some("2".to_string()));
+
+        assert_eq!(root.clone_node(true), root);
+        assert!(root.clone_node(false).children.is_empty());
+    }
+
+    #[test]
+    fn test_xml_read_namespaces() {
+        let root = read_xml(
+            "<root xmlns=\"urn:a\"><!-- skipped --><?pi skipped?><b xmlns=\"urn:b\"><c/></b><d/></root>",
+        )
+        .unwrap();
+        assert_eq!(root.namespace.as_deref(), Some("urn:a"));
+        assert_eq!(root.children[0].namespace.as_deref(), Some("urn:b"));
+        assert_eq!(
+            root.children[0].children[0].namespace.as_deref(),
+            Some("urn:b")
+        );
+        assert_eq!(root.children[1].namespace.as_deref(), Some("urn:a"));
+
+        let root = read_xml("<x:root></x:root>").unwrap();
+        assert_eq!(root.local_name(), "root");
+        assert_eq!(root.namespace, None);
+    }
+
+    #[test]
+    fn test_xml_write_escaped() {
         const TEST_DOC: &str =
-            "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        assert_eq!(doc.document_element.unwrap().attributes.len(), 1);
-        let ns = doc.document_element.unwrap().attributes.get(0).unwrap();
-        assert_eq!(ns.name.to_string(), "xmlns");
-        assert_eq!(ns.value, "urn:schemas-upnp-org:device-1-0");
-        assert_eq!(doc.processing_instructions.len(), 1);
-        let pi = doc.processing_instructions.get(0).unwrap();
-        assert_eq!(pi.target, "xml");
-        // TODO: fix this assert_eq!(pi.attributes.len(), 3);
-        println!("{:#?}", doc);
+            "<root a=\"&quot;1&quot; &lt; 2\"><b>one &amp; &lt;two&gt;</b><c></c></root>";
+        let root = read_xml(TEST_DOC).unwrap();
+        assert_eq!(root.children[0].text, "one & <two>");
+        assert_eq!(root.to_string(), TEST_DOC);
+
+        let written = root.write_to(Vec::new(), &WriteOptions::pretty()).unwrap();
+        assert_eq!(
+            from_utf8(&written).unwrap(),
+            "<?xml version=\"1.0\"?>\n<root a=\"&quot;1&quot; &lt; 2\">\n  <b>one &amp; &lt;two&gt;</b>\n  <c></c>\n</root>"
+        );
+    }
+
+    #[test]
+    fn test_xml_write_namespaces() {
+        let mut root = read_xml("<s:root xmlns:s=\"urn:s\"><s:a/></s:root>").unwrap();
+        root.append_child(Element {
+            name: "s:b".to_string(),
+            namespace: Some("urn:s".to_string()),
+            ..Default::default()
+        });
+        root.append_child(Element {
+            name: "u:c".to_string(),
+            namespace: Some("urn:u".to_string()),
+            ..Default::default()
+        });
+        root.append_child(Element {
+            name: "d".to_string(),
+            namespace: Some("urn:d".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            root.to_string(),
+            "<s:root xmlns:s=\"urn:s\"><s:a></s:a><s:b></s:b><u:c xmlns:u=\"urn:u\"></u:c><d xmlns=\"urn:d\"></d></s:root>"
+        );
+        let namespaces = |element: &Element| {
+            element
+                .children
+                .iter()
+                .map(|child| child.namespace.clone())
+                .collect::<Vec<Option<String>>>()
+        };
+        assert_eq!(
+            namespaces(&read_xml(&root.to_string()).unwrap()),
+            namespaces(&root)
+        );
+    }
+
+    #[test]
+    fn test_xml_select() {
+        let root = read_xml(
+            "<root xmlns=\"urn:d\" xmlns:v=\"urn:v\"><device><v:X_info>one</v:X_info><serviceList><service><id>a</id></service><service><id>b</id></service></serviceList></device></root>",
+        )
+        .unwrap();
+        assert_eq!(root.select("root/device/serviceList/service").len(), 2);
+        assert_eq!(
+            root.select_text("root/device/serviceList/service[1]/id"),
+            Some("b")
+        );
+        assert!(root
+            .select_first("root/device/serviceList/service[2]")
+            .is_none());
+        assert_eq!(root.select("root/*/*").len(), 2);
+        assert_eq!(root.select_text("root/device/{urn:v}X_info"), Some("one"));
+        assert!(root.select_first("root/device/{urn:d}X_info").is_none());
+        assert!(root.select("device").is_empty());
+        assert!(root.select("root/device[x]").is_empty());
     }
 }
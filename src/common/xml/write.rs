@@ -9,12 +9,16 @@ More detailed description, with
 
 use crate::error::{xml_error, Error};
 use crate::syntax::{
-    XML_ATTR_NAMESPACE, XML_DECL_VERSION, XML_ELEM_MAJOR, XML_ELEM_MINOR, XML_ELEM_SPEC_VERSION,
+    XML_ATTR_CONFIG_ID, XML_ATTR_NAMESPACE, XML_DECL_VERSION, XML_ELEM_MAJOR, XML_ELEM_MINOR,
+    XML_ELEM_SPEC_VERSION,
 };
 use crate::SpecVersion;
 use quick_xml::events::{attributes::Attribute, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
+use std::borrow::Cow;
+use std::fmt::{Display, Error as FmtError, Result as FmtResult, Write as FmtWrite};
 use std::io::Write;
+use std::str::from_utf8;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -22,7 +26,22 @@ use std::io::Write;
 
 #[derive(Debug)]
 pub struct Element {
-    name: &'static [u8],
+    name: &'static str,
+}
+
+///
+/// Options controlling the layout of a written XML document. The default writes a single line,
+/// with an XML declaration and no `standalone` attribute.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// The number of spaces to indent each nested element by; if `None` no line breaks or
+    /// indentation are written.
+    pub indent: Option<usize>,
+    /// Whether to start the document with an XML declaration.
+    pub xml_decl: bool,
+    /// The value of the `standalone` attribute in the XML declaration, if any.
+    pub standalone: Option<bool>,
 }
 
 pub trait Writable<T: Write> {
@@ -31,9 +50,11 @@ pub trait Writable<T: Write> {
 
 pub trait RootWritable<T: Write>: Writable<T> {
     fn write_root(&self, writer: T) -> Result<T, Error> {
-        let mut xml = Writer::new(writer);
+        self.write_root_with(writer, &Default::default())
+    }
 
-        start(&mut xml).map_err(xml_error)?;
+    fn write_root_with(&self, writer: T, options: &WriteOptions) -> Result<T, Error> {
+        let mut xml = document_writer(writer, options)?;
 
         self.write(&mut xml)?;
 
@@ -45,53 +66,101 @@ pub trait RootWritable<T: Write>: Writable<T> {
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Create an XML writer laid out according to `options`, writing the XML declaration if required.
+///
+pub fn document_writer<T: Write>(writer: T, options: &WriteOptions) -> Result<Writer<T>, Error> {
+    let mut xml = match options.indent {
+        None => Writer::new(writer),
+        Some(indent) => Writer::new_with_indent(writer, b' ', indent),
+    };
+
+    if options.xml_decl {
+        start_with(&mut xml, options.standalone).map_err(xml_error)?;
+    }
+    Ok(xml)
+}
+
 pub fn start<T: Write>(writer: &mut Writer<T>) -> Result<(), quick_xml::Error> {
+    start_with(writer, None)
+}
+
+pub fn start_with<T: Write>(
+    writer: &mut Writer<T>,
+    standalone: Option<bool>,
+) -> Result<(), quick_xml::Error> {
     writer
-        .write_event(Event::Decl(BytesDecl::new(XML_DECL_VERSION, None, None)))
+        .write_event(Event::Decl(BytesDecl::new(
+            XML_DECL_VERSION,
+            None,
+            standalone.map(|standalone| if standalone { "yes" } else { "no" }),
+        )))
         .map(|_| ())
 }
 
 pub fn element<T: Write>(
     writer: &mut Writer<T>,
-    name: &'static [u8],
+    name: &'static str,
 ) -> Result<(), quick_xml::Error> {
-    writer.write_event(Event::Start(BytesStart::borrowed_name(name)))?;
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
+    // an empty text event keeps the end tag on the same line when indenting.
+    writer.write_event(Event::Text(BytesText::new("")))?;
     writer
-        .write_event(Event::End(BytesEnd::borrowed(name)))
+        .write_event(Event::End(BytesEnd::new(name)))
         .map(|_| ())
 }
 
 pub fn start_element<T: Write>(
     writer: &mut Writer<T>,
-    name: &'static [u8],
+    name: &'static str,
 ) -> Result<Element, quick_xml::Error> {
-    writer.write_event(Event::Start(BytesStart::borrowed_name(name)))?;
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
     Ok(Element { name })
 }
 
 pub fn start_ns_element<T: Write>(
     writer: &mut Writer<T>,
-    name: &'static [u8],
+    name: &'static str,
     namespace: &'static str,
     prefix: Option<&str>,
 ) -> Result<Element, quick_xml::Error> {
-    let xmlns = [
-        XML_ATTR_NAMESPACE,
-        if prefix.is_some() { ":" } else { "" },
-        if let Some(p) = prefix { p } else { "" },
-    ]
-    .concat();
-
-    start_element_with(writer, name, vec![(xmlns.as_str(), namespace)])?;
+    let xmlns = match prefix {
+        None => Cow::Borrowed(XML_ATTR_NAMESPACE),
+        Some(prefix) => Cow::Owned([XML_ATTR_NAMESPACE, ":", prefix].concat()),
+    };
+
+    start_element_with(writer, name, [(xmlns.as_ref(), namespace)])?;
     Ok(Element { name })
 }
 
-pub fn start_element_with<T: Write>(
+///
+/// Start the root element of a device or service description. The namespace is the same for all
+/// versions of the specification, the `configId` attribute is only written for version 1.1 and
+/// later.
+///
+pub fn start_description_element<T: Write>(
+    writer: &mut Writer<T>,
+    name: &'static str,
+    namespace: &'static str,
+    spec_version: SpecVersion,
+    config_id: Option<u32>,
+) -> Result<Element, quick_xml::Error> {
+    let config_id = config_id
+        .filter(|_| spec_version >= SpecVersion::V11)
+        .map(|config_id| config_id.to_string());
+    let mut attrs = vec![(XML_ATTR_NAMESPACE, namespace)];
+    if let Some(config_id) = &config_id {
+        attrs.push((XML_ATTR_CONFIG_ID, config_id.as_str()));
+    }
+    start_element_with(writer, name, attrs)
+}
+
+pub fn start_element_with<'a, T: Write>(
     writer: &mut Writer<T>,
-    name: &'static [u8],
-    attrs: Vec<(&str, &str)>,
+    name: &'static str,
+    attrs: impl IntoIterator<Item = (&'a str, &'a str)>,
 ) -> Result<Element, quick_xml::Error> {
-    let mut element = BytesStart::borrowed_name(name);
+    let mut element = BytesStart::new(name);
     for (name, value) in attrs {
         element.push_attribute(Attribute::from((name, value)));
     }
@@ -99,22 +168,38 @@ pub fn start_element_with<T: Write>(
     Ok(Element { name })
 }
 
-pub fn end_element<T: Write>(writer: &mut Writer<T>, name: &[u8]) -> Result<(), quick_xml::Error> {
+pub fn end_element<T: Write>(writer: &mut Writer<T>, name: &str) -> Result<(), quick_xml::Error> {
     writer
-        .write_event(Event::End(BytesEnd::borrowed(name)))
+        .write_event(Event::End(BytesEnd::new(name)))
         .map(|_| ())
 }
 
 pub fn text_element<T: Write>(
     writer: &mut Writer<T>,
-    name: &'static [u8],
-    content: &[u8],
+    name: &'static str,
+    content: &str,
 ) -> Result<(), quick_xml::Error> {
     let element = start_element(writer, name)?;
-    writer.write_event(Event::Text(BytesText::from_plain(content)))?;
+    writer.write_event(Event::Text(BytesText::new(content)))?;
     element.end(writer)
 }
 
+///
+/// Write an element whose text content is the `Display` form of `content`. Short values, such as
+/// numbers and type identifiers, are formatted on the stack rather than into a new `String`.
+///
+pub fn display_element<T: Write, D: Display>(
+    writer: &mut Writer<T>,
+    name: &'static str,
+    content: D,
+) -> Result<(), quick_xml::Error> {
+    let mut buffer = TextBuffer::default();
+    match write!(buffer, "{}", content) {
+        Ok(()) => text_element(writer, name, buffer.as_str()),
+        Err(_) => text_element(writer, name, &content.to_string()),
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -127,6 +212,28 @@ impl Element {
 
 // ------------------------------------------------------------------------------------------------
 
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            xml_decl: true,
+            standalone: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    ///
+    /// Options for human-readable output, indenting nested elements by two spaces.
+    ///
+    pub fn pretty() -> Self {
+        Self {
+            indent: Some(2),
+            ..Default::default()
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl<T: Write> Writable<T> for SpecVersion {
@@ -139,8 +246,7 @@ impl<T: Write> Writable<T> for SpecVersion {
                 SpecVersion::V10 => "1",
                 SpecVersion::V11 => "1",
                 SpecVersion::V20 => "2",
-            }
-            .as_bytes(),
+            },
         )
         .map_err(xml_error)?;
         text_element(
@@ -150,18 +256,57 @@ impl<T: Write> Writable<T> for SpecVersion {
                 SpecVersion::V10 => "0",
                 SpecVersion::V11 => "1",
                 SpecVersion::V20 => "0",
-            }
-            .as_bytes(),
+            },
         )
         .map_err(xml_error)?;
         spec_version.end(writer).map_err(xml_error)
     }
 }
 
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self {
+            bytes: [0; TEXT_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+}
+
+impl FmtWrite for TextBuffer {
+    fn write_str(&mut self, s: &str) -> FmtResult {
+        let end = self.len + s.len();
+        if end > self.bytes.len() {
+            return Err(FmtError);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl TextBuffer {
+    fn as_str(&self) -> &str {
+        // only whole `str` values are ever copied into the buffer.
+        from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// A fixed-size buffer for formatting short text values; writing more than it can hold is an
+/// error, and the caller falls back to allocating.
+///
+#[derive(Debug)]
+struct TextBuffer {
+    bytes: [u8; TEXT_BUFFER_SIZE],
+    len: usize,
+}
+
+const TEXT_BUFFER_SIZE: usize = 128;
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
@@ -0,0 +1,469 @@
+/*!
+This module generates the Rust source for a typed client of a service from its service
+description (SCPD). The generated client wraps a [`ServiceClient`](../struct.ServiceClient.html)
+and has one method for each action, with in arguments as parameters and out arguments as the
+result; the Rust type of each argument is determined by the data type of its related state
+variable, see [`rust_type_for`]. Actions with more than one out argument return a generated
+structure with a field for each.
+
+The same generator is available from the command-line tool as `upnp codegen`.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::http::fetch;
+use upnp_rs::control::codegen::generate;
+use upnp_rs::description::service::Spcd;
+use upnp_rs::description::well_known::service_type;
+
+let scpd: Spcd = fetch("http://10.0.0.1:49152/SwitchPower/scpd.xml".to_string()).unwrap();
+let source = generate(&service_type("SwitchPower", 1), &scpd);
+std::fs::write("src/switch_power.rs", source).unwrap();
+```
+
+For the `SetTarget` and `GetStatus` actions of the `SwitchPower` service this produces methods
+with the following signatures.
+
+```rust,ignore
+pub fn set_target(&self, new_target_value: bool) -> Result<(), Error>;
+pub fn get_status(&self) -> Result<bool, Error>;
+```
+*/
+
+use crate::description::service::{Action, Direction, Spcd};
+use crate::description::TypeID;
+use std::fmt::Write;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Generate the source of a client for the service `service_type`, described by `scpd`. The
+/// client structure is named for the service, e.g. `SwitchPowerClient`.
+///
+pub fn generate(service_type: &TypeID, scpd: &Spcd) -> String {
+    let client_name = format!("{}Client", type_name(service_type.name()));
+    let actions: Vec<GeneratedAction> = scpd
+        .action_list
+        .iter()
+        .map(|action| GeneratedAction::new(action, scpd))
+        .collect();
+
+    let mut source = String::new();
+    let _ = writeln!(
+        source,
+        "//! A client for the service `{}`, generated by {} {}.",
+        service_type,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    source.push_str(
+        "
+#![allow(dead_code)]
+
+use std::str::FromStr;
+use upnp_rs::common::data_types::Boolean;
+use upnp_rs::common::uri::URL;
+use upnp_rs::control::{parsed_argument, ServiceClient};
+use upnp_rs::description::TypeID;
+use upnp_rs::error::Error;
+",
+    );
+
+    for action in actions.iter().filter(|action| action.outs.len() > 1) {
+        let _ = write!(
+            source,
+            "
+///
+/// The out arguments of the `{}` action.
+///
+#[derive(Clone, Debug)]
+pub struct {} {{
+",
+            action.name, action.response_name
+        );
+        for argument in &action.outs {
+            let _ = writeln!(
+                source,
+                "    pub {}: {},",
+                argument.field, argument.rust_type
+            );
+        }
+        source.push_str("}\n");
+    }
+
+    let _ = write!(
+        source,
+        "
+///
+/// A client for the service `{service_type}`.
+///
+#[derive(Clone, Debug)]
+pub struct {client_name} {{
+    client: ServiceClient,
+}}
+
+impl {client_name} {{
+    ///
+    /// A client for the service at `control_url`.
+    ///
+    pub fn new(control_url: URL) -> Self {{
+        Self::from_client(ServiceClient::new(
+            TypeID::from_str(\"{service_type}\").unwrap(),
+            control_url,
+        ))
+    }}
+
+    pub fn from_client(client: ServiceClient) -> Self {{
+        Self {{ client }}
+    }}
+
+    pub fn client(&self) -> &ServiceClient {{
+        &self.client
+    }}
+"
+    );
+    for action in &actions {
+        action.write_method(&mut source);
+    }
+    source.push_str("}\n");
+    source
+}
+
+///
+/// The Rust type used for arguments of the UPnP `data_type`; types without a more specific
+/// representation, including `string`, `uri`, `uuid`, and the binary types, are `String`.
+///
+pub fn rust_type_for(data_type: &str) -> &'static str {
+    match data_type {
+        "ui1" => "u8",
+        "ui2" => "u16",
+        "ui4" => "u32",
+        "ui8" => "u64",
+        "i1" => "i8",
+        "i2" => "i16",
+        "i4" | "int" => "i32",
+        "i8" => "i64",
+        "r4" => "f32",
+        "r8" | "number" | "float" | "fixed.14.4" => "f64",
+        "char" => "char",
+        "boolean" => "bool",
+        "date" => "upnp_rs::common::data_types::Date",
+        "dateTime" | "dateTime.tz" => "upnp_rs::common::data_types::DateTime",
+        "time" | "time.tz" => "upnp_rs::common::data_types::Time",
+        _ => "String",
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl GeneratedAction {
+    fn new(action: &Action, scpd: &Spcd) -> Self {
+        let arguments = |direction: Direction| {
+            action
+                .argument_list
+                .iter()
+                .filter(|argument| argument.direction == direction)
+                .map(|argument| GeneratedArgument {
+                    name: argument.name.clone(),
+                    field: field_name(&argument.name),
+                    rust_type: rust_type_for(
+                        scpd.service_state_table
+                            .iter()
+                            .find(|variable| variable.name == argument.related_state_variable)
+                            .map(|variable| variable.data_type.as_str())
+                            .unwrap_or_default(),
+                    ),
+                })
+                .collect::<Vec<GeneratedArgument>>()
+        };
+        Self {
+            name: action.name.clone(),
+            method: field_name(&action.name),
+            response_name: format!("{}Response", type_name(&action.name)),
+            ins: arguments(Direction::In),
+            outs: arguments(Direction::Out),
+        }
+    }
+
+    fn write_method(&self, source: &mut String) {
+        let parameters: String = self
+            .ins
+            .iter()
+            .map(|argument| {
+                format!(
+                    ", {}: {}",
+                    argument.field,
+                    if argument.rust_type == "String" {
+                        "&str"
+                    } else {
+                        argument.rust_type
+                    }
+                )
+            })
+            .collect();
+        let result = match self.outs.as_slice() {
+            [] => "()",
+            [argument] => argument.rust_type,
+            _ => self.response_name.as_str(),
+        };
+        let _ = write!(
+            source,
+            "
+    ///
+    /// Invoke the `{}` action.
+    ///
+    pub fn {}(&self{}) -> Result<{}, Error> {{
+",
+            self.name, self.method, parameters, result
+        );
+        for argument in &self.ins {
+            let _ = writeln!(
+                source,
+                "        let {field} = {};",
+                if argument.rust_type == "bool" {
+                    format!("Boolean::from({}).to_string()", argument.field)
+                } else {
+                    format!("{}.to_string()", argument.field)
+                },
+                field = argument.field
+            );
+        }
+        let in_arguments: Vec<String> = self
+            .ins
+            .iter()
+            .map(|argument| format!("(\"{}\", {}.as_str())", argument.name, argument.field))
+            .collect();
+        let _ = writeln!(
+            source,
+            "        {}self.client.invoke(\"{}\", &[{}])?;",
+            if self.outs.is_empty() {
+                ""
+            } else {
+                "let results = "
+            },
+            self.name,
+            in_arguments.join(", ")
+        );
+        match self.outs.as_slice() {
+            [] => source.push_str("        Ok(())\n"),
+            [argument] => {
+                let _ = writeln!(source, "        {}", parse_expression(argument));
+            }
+            outs => {
+                let _ = writeln!(source, "        Ok({} {{", self.response_name);
+                for argument in outs {
+                    let _ = writeln!(
+                        source,
+                        "            {}: {}?,",
+                        argument.field,
+                        parse_expression(argument)
+                    );
+                }
+                source.push_str("        })\n");
+            }
+        }
+        source.push_str("    }\n");
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct GeneratedAction {
+    name: String,
+    method: String,
+    response_name: String,
+    ins: Vec<GeneratedArgument>,
+    outs: Vec<GeneratedArgument>,
+}
+
+#[derive(Debug)]
+struct GeneratedArgument {
+    name: String,
+    field: String,
+    rust_type: &'static str,
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "try",
+    "type", "unsafe", "use", "where", "while", "yield",
+];
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parse_expression(argument: &GeneratedArgument) -> String {
+    if argument.rust_type == "bool" {
+        format!(
+            "parsed_argument::<Boolean>(&results, \"{}\").map(bool::from)",
+            argument.name
+        )
+    } else {
+        format!(
+            "parsed_argument::<{}>(&results, \"{}\")",
+            argument.rust_type, argument.name
+        )
+    }
+}
+
+///
+/// Convert a UPnP name, e.g. `GetExternalIPAddress`, to a Rust identifier in snake case, e.g.
+/// `get_external_ip_address`.
+///
+fn field_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut field = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let after_lower =
+                i > 0 && (chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit());
+            let before_lower = i > 0
+                && chars[i - 1].is_ascii_uppercase()
+                && chars.get(i + 1).is_some_and(|c| c.is_ascii_lowercase());
+            if (after_lower || before_lower) && !field.ends_with('_') {
+                field.push('_');
+            }
+            field.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            field.push(*c);
+        } else if !field.is_empty() && !field.ends_with('_') {
+            field.push('_');
+        }
+    }
+    if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+        field.insert(0, '_');
+    }
+    if RUST_KEYWORDS.contains(&field.as_str()) {
+        field.push('_');
+    }
+    field
+}
+
+///
+/// Convert a UPnP name to a Rust type name, removing any characters not valid in an identifier.
+///
+fn type_name(name: &str) -> String {
+    let mut type_name = String::new();
+    let mut upper = true;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            type_name.push(if upper { c.to_ascii_uppercase() } else { c });
+            upper = false;
+        } else {
+            upper = true;
+        }
+    }
+    type_name
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::description::service::{Argument, StateVariable};
+    use crate::description::well_known::wan_ip_connection;
+    use crate::SpecVersion;
+
+    fn scpd() -> Spcd {
+        let argument = |name: &str, direction: Direction, variable: &str| Argument {
+            name: name.to_string(),
+            direction,
+            return_value: false,
+            related_state_variable: variable.to_string(),
+        };
+        let variable = |name: &str, data_type: &str| StateVariable {
+            send_events: false,
+            multicast: false,
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            default_value: None,
+            allowed_values: None,
+            moderation: None,
+        };
+        Spcd {
+            spec_version: SpecVersion::V10,
+            config_id: None,
+            action_list: vec![
+                Action {
+                    name: "GetExternalIPAddress".to_string(),
+                    argument_list: vec![argument(
+                        "NewExternalIPAddress",
+                        Direction::Out,
+                        "ExternalIPAddress",
+                    )],
+                },
+                Action {
+                    name: "SetConnectionType".to_string(),
+                    argument_list: vec![argument(
+                        "NewConnectionType",
+                        Direction::In,
+                        "ConnectionType",
+                    )],
+                },
+                Action {
+                    name: "GetStatusInfo".to_string(),
+                    argument_list: vec![
+                        argument("NewConnectionStatus", Direction::Out, "ConnectionStatus"),
+                        argument("NewUptime", Direction::Out, "Uptime"),
+                    ],
+                },
+                Action {
+                    name: "SetEnabled".to_string(),
+                    argument_list: vec![argument("type", Direction::In, "Enabled")],
+                },
+            ],
+            service_state_table: vec![
+                variable("ExternalIPAddress", "string"),
+                variable("ConnectionType", "string"),
+                variable("ConnectionStatus", "string"),
+                variable("Uptime", "ui4"),
+                variable("Enabled", "boolean"),
+            ],
+            extensions: vec![],
+        }
+    }
+
+    #[test]
+    fn test_field_names() {
+        assert_eq!(
+            field_name("GetExternalIPAddress"),
+            "get_external_ip_address"
+        );
+        assert_eq!(field_name("NewUptime"), "new_uptime");
+        assert_eq!(field_name("X_GetDNS2"), "x_get_dns2");
+        assert_eq!(field_name("type"), "type_");
+        assert_eq!(type_name("WANIPConnection"), "WANIPConnection");
+        assert_eq!(type_name("x-vendor"), "XVendor");
+    }
+
+    #[test]
+    fn test_generate() {
+        let source = generate(&wan_ip_connection(1), &scpd());
+        assert!(source.contains("pub struct WANIPConnectionClient {"));
+        assert!(source.contains("pub fn get_external_ip_address(&self) -> Result<String, Error> {"));
+        assert!(source.contains(
+            "pub fn set_connection_type(&self, new_connection_type: &str) -> Result<(), Error> {"
+        ));
+        assert!(source.contains("pub struct GetStatusInfoResponse {"));
+        assert!(source.contains("    pub new_uptime: u32,"));
+        assert!(source
+            .contains("pub fn get_status_info(&self) -> Result<GetStatusInfoResponse, Error> {"));
+        assert!(source.contains("pub fn set_enabled(&self, type_: bool) -> Result<(), Error> {"));
+        assert!(source.contains("let type_ = Boolean::from(type_).to_string();"));
+        assert!(
+            source.contains("self.client.invoke(\"SetEnabled\", &[(\"type\", type_.as_str())])?;")
+        );
+    }
+}
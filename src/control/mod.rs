@@ -1,27 +1,439 @@
 /*!
 This module implements the UPnP device and service control capabilities.
+
+Actions are invoked by sending a SOAP request to the service's control URL, see the
+[`soap`](../common/soap/index.html) module for the envelope format. A `ServiceClient` is
+usually created from a device description, and retains the service type and resolved control URL
+so that actions can be invoked by name.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::http::fetch;
+use upnp_rs::common::uri::URL;
+use upnp_rs::control::{argument, ServiceClient};
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::description::well_known::wan_ip_connection;
+use std::str::FromStr;
+
+let location = URL::from_str("http://10.0.0.1:49152/description.xml").unwrap();
+let root: DeviceRoot = fetch(location.to_string()).unwrap();
+let client = ServiceClient::for_service(&root, &location, &wan_ip_connection(1)).unwrap();
+let results = client.invoke("GetExternalIPAddress", &[]).unwrap();
+println!("{}", argument(&results, "NewExternalIPAddress").unwrap());
+```
 */
 
+use crate::common::http::{ClientRequest, HttpClient};
+use crate::common::soap::{Action, Arguments, Body, Envelope};
+use crate::common::uri::URL;
+use crate::common::user_agent::user_agent_string;
+use crate::description::device::DeviceRoot;
+use crate::description::TypeID;
+use crate::error::{
+    action_failed, invalid_field_value, missing_required_field, operation_failed,
+    unsupported_operation, Error,
+};
+use crate::observer::{self, MessageKind};
+use crate::syntax::{
+    HTTP_HEADER_CONTENT_TYPE, HTTP_HEADER_USER_AGENT, HTTP_METHOD_POST, SOAP_ELEM_ERROR_CODE,
+    SOAP_HTTP_CONTENT_TYPE, SOAP_HTTP_HEADER_ACTION,
+};
+use crate::SpecVersion;
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{error, info, info_span};
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
+
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The standard error codes returned in the `UPnPError` element of a SOAP fault, as defined by the
+/// UDA. Codes without a standard meaning are retained by range.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UpnpError {
+    /// 401: No action by that name at this service.
+    InvalidAction,
+    /// 402: Not enough in args, args in the wrong order, one or more in args are of the wrong
+    /// data type.
+    InvalidArgs,
+    /// 501: Current state of service prevents invoking that action.
+    ActionFailed,
+    /// 600: The argument value is invalid.
+    ArgumentValueInvalid,
+    /// 601: An argument value is less than the minimum or more than the maximum value of the
+    /// allowed value range, or is not in the allowed value list.
+    ArgumentValueOutOfRange,
+    /// 602: The requested action is optional and is not implemented by the device.
+    OptionalActionNotImplemented,
+    /// 603: The device does not have sufficient memory available to complete the action.
+    OutOfMemory,
+    /// 604: The device has encountered an error condition which it cannot resolve itself and
+    /// required human intervention such as a reset or power cycle.
+    HumanInterventionRequired,
+    /// 605: A string argument is too long for the device to handle properly.
+    StringArgumentTooLong,
+    /// Any other code in the range 606-699, reserved for common action errors.
+    Common(u16),
+    /// A code in the range 700-799, defined by the standard service specification.
+    ServiceSpecific(u16),
+    /// A code in the range 800-899, defined by the device vendor.
+    VendorSpecific(u16),
+    /// Any code outside the ranges defined by the UDA.
+    Other(u16),
+}
+
+///
+/// A client for a single service, identified by its type and control URL.
+///
+#[derive(Clone, Debug)]
+pub struct ServiceClient {
+    service_type: TypeID,
+    control_url: URL,
+    client: Arc<dyn HttpClient>,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Invoke `action` with the provided in `arguments` at `control_url`, returning the out arguments.
+///
+#[cfg(feature = "net")]
+pub fn invoke(
+    control_url: &URL,
+    action: &Action,
+    arguments: Arguments,
+) -> Result<Arguments, Error> {
+    invoke_with(control_url, action, arguments, default_client().as_ref())
+}
+
+///
+/// Invoke `action` with the provided in `arguments` at `control_url`, using the provided HTTP
+/// client, returning the out arguments. A SOAP fault is returned as an `Error::Control` value
+/// carrying the UPnP error code and description.
+///
+pub fn invoke_with(
+    control_url: &URL,
+    action: &Action,
+    arguments: Arguments,
+    client: &dyn HttpClient,
+) -> Result<Arguments, Error> {
+    let _span = info_span!("invoke", action = %action, url = %control_url).entered();
+    info!("invoking");
+    let request = Envelope::new_with(action.clone(), arguments);
+    observer::sent(MessageKind::Control);
+    let response = client.send(
+        &ClientRequest::new(HTTP_METHOD_POST, &control_url.to_string())
+            .with_header(HTTP_HEADER_CONTENT_TYPE, SOAP_HTTP_CONTENT_TYPE)
+            .with_header(SOAP_HTTP_HEADER_ACTION, &format!("\"{}\"", action))
+            .with_header(
+                HTTP_HEADER_USER_AGENT,
+                &user_agent_string(SpecVersion::default(), None),
+            )
+            .with_body(request.to_string().into_bytes()),
+    )?;
+    let status = response.status_text();
+    let text = response.text();
+    observer::received(MessageKind::Control);
+    info!(status = response.status, "received");
+    match Envelope::from_str(&text) {
+        Ok(envelope) => match envelope.into_body() {
+            Body::Response { arguments, .. } if response.is_success() => Ok(arguments),
+            Body::Fault {
+                upnp_code,
+                upnp_description,
+                ..
+            } => {
+                let error = upnp_error_from(&upnp_code)?;
+                error!(
+                    "invoke_with - action {} failed with {} ({})",
+                    action, error, upnp_description
+                );
+                action_failed(action.to_string(), error.code(), upnp_description).into()
+            }
+            _ => operation_failed(action.to_string(), status).into(),
+        },
+        Err(e) => {
+            observer::parse_failed(MessageKind::Control);
+            error!(
+                "invoke_with - action {} returned status {}, could not parse body: {:?}",
+                action, status, e
+            );
+            if response.is_success() {
+                Err(e)
+            } else {
+                operation_failed(action.to_string(), status).into()
+            }
+        }
+    }
+}
+
+///
+/// Return the value of the named argument from a set of action results.
+///
+pub fn argument<'a>(arguments: &'a [(String, String)], name: &str) -> Result<&'a str, Error> {
+    match arguments.iter().find(|(key, _)| key == name) {
+        Some((_, value)) => Ok(value.as_str()),
+        None => missing_required_field(name).into(),
+    }
+}
+
+///
+/// Return the value of the named argument from a set of action results, parsed as type `T`.
+///
+pub fn parsed_argument<T: FromStr>(arguments: &[(String, String)], name: &str) -> Result<T, Error> {
+    let value = argument(arguments, name)?;
+    T::from_str(value.trim()).map_err(|_| invalid_field_value(name, value).into())
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl Display for UpnpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{} {}", self.code(), self.description())
+    }
+}
+
+impl From<u16> for UpnpError {
+    fn from(code: u16) -> Self {
+        Self::from_code(code)
+    }
+}
+
+impl From<UpnpError> for u16 {
+    fn from(error: UpnpError) -> Self {
+        error.code()
+    }
+}
+
+impl TryFrom<&Body> for UpnpError {
+    type Error = Error;
+
+    fn try_from(body: &Body) -> Result<Self, Self::Error> {
+        match body {
+            Body::Fault { upnp_code, .. } => upnp_error_from(upnp_code),
+            _ => unsupported_operation("UpnpError::try_from(&Body)").into(),
+        }
+    }
+}
+
+impl UpnpError {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            401 => Self::InvalidAction,
+            402 => Self::InvalidArgs,
+            501 => Self::ActionFailed,
+            600 => Self::ArgumentValueInvalid,
+            601 => Self::ArgumentValueOutOfRange,
+            602 => Self::OptionalActionNotImplemented,
+            603 => Self::OutOfMemory,
+            604 => Self::HumanInterventionRequired,
+            605 => Self::StringArgumentTooLong,
+            606..=699 => Self::Common(code),
+            700..=799 => Self::ServiceSpecific(code),
+            800..=899 => Self::VendorSpecific(code),
+            _ => Self::Other(code),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::InvalidAction => 401,
+            Self::InvalidArgs => 402,
+            Self::ActionFailed => 501,
+            Self::ArgumentValueInvalid => 600,
+            Self::ArgumentValueOutOfRange => 601,
+            Self::OptionalActionNotImplemented => 602,
+            Self::OutOfMemory => 603,
+            Self::HumanInterventionRequired => 604,
+            Self::StringArgumentTooLong => 605,
+            Self::Common(code)
+            | Self::ServiceSpecific(code)
+            | Self::VendorSpecific(code)
+            | Self::Other(code) => *code,
+        }
+    }
+
+    ///
+    /// The short description for standard codes, as used in the `errorDescription` element.
+    ///
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::InvalidAction => "Invalid Action",
+            Self::InvalidArgs => "Invalid Args",
+            Self::ActionFailed => "Action Failed",
+            Self::ArgumentValueInvalid => "Argument Value Invalid",
+            Self::ArgumentValueOutOfRange => "Argument Value Out of Range",
+            Self::OptionalActionNotImplemented => "Optional Action Not Implemented",
+            Self::OutOfMemory => "Out of Memory",
+            Self::HumanInterventionRequired => "Human Intervention Required",
+            Self::StringArgumentTooLong => "String Argument Too Long",
+            Self::Common(_) => "Common Action Error",
+            Self::ServiceSpecific(_) => "Service Specific Error",
+            Self::VendorSpecific(_) => "Vendor Specific Error",
+            Self::Other(_) => "Unknown Error",
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
-// Private Types
-// ------------------------------------------------------------------------------------------------
+
+impl ServiceClient {
+    #[cfg(feature = "net")]
+    pub fn new(service_type: TypeID, control_url: URL) -> Self {
+        Self::with_client(service_type, control_url, default_client())
+    }
+
+    pub fn with_client(
+        service_type: TypeID,
+        control_url: URL,
+        client: Arc<dyn HttpClient>,
+    ) -> Self {
+        Self {
+            service_type,
+            control_url,
+            client,
+        }
+    }
+
+    ///
+    /// Find the first service, in the root or any embedded device, that satisfies `service_type`
+    /// and return a client for it. The control URL is resolved against the description's base
+    /// URL, `location` is the URL the description was retrieved from.
+    ///
+    #[cfg(feature = "net")]
+    pub fn for_service(
+        root: &DeviceRoot,
+        location: &URL,
+        service_type: &TypeID,
+    ) -> Result<Self, Error> {
+        Self::for_service_with(root, location, service_type, default_client())
+    }
+
+    ///
+    /// As [`ServiceClient::for_service`], returning a client that sends requests using `client`.
+    ///
+    pub fn for_service_with(
+        root: &DeviceRoot,
+        location: &URL,
+        service_type: &TypeID,
+        client: Arc<dyn HttpClient>,
+    ) -> Result<Self, Error> {
+        let base = root.base_url(location);
+        match root
+            .device
+            .all_devices()
+            .iter()
+            .flat_map(|device| device.service_list.iter())
+            .find(|service| service.service_type.is_compatible_with(service_type))
+        {
+            Some(service) => Ok(Self::with_client(
+                service.service_type.clone(),
+                base.resolve(&service.control_url)?,
+                client,
+            )),
+            None => {
+                error!("for_service - no service of type {} found", service_type);
+                missing_required_field(service_type.to_string()).into()
+            }
+        }
+    }
+
+    pub fn service_type(&self) -> &TypeID {
+        &self.service_type
+    }
+
+    pub fn control_url(&self) -> &URL {
+        &self.control_url
+    }
+
+    ///
+    /// Invoke the named action with the provided in arguments, returning the out arguments.
+    ///
+    pub fn invoke(&self, action: &str, arguments: &[(&str, &str)]) -> Result<Arguments, Error> {
+        invoke_with(
+            &self.control_url,
+            &Action::new(self.service_type.clone(), action.to_string()),
+            arguments
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            self.client.as_ref(),
+        )
+    }
+}
 
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn upnp_error_from(upnp_code: &str) -> Result<UpnpError, Error> {
+    u16::from_str(upnp_code.trim())
+        .map(UpnpError::from_code)
+        .map_err(|_| invalid_field_value(SOAP_ELEM_ERROR_CODE, upnp_code).into())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+pub mod codegen;
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argument() {
+        let arguments = vec![("NewEnabled".to_string(), "1".to_string())];
+        assert_eq!(argument(&arguments, "NewEnabled").unwrap(), "1");
+        assert!(argument(&arguments, "NewDisabled").is_err());
+    }
+
+    #[test]
+    fn test_upnp_error_codes() {
+        assert_eq!(UpnpError::from_code(401), UpnpError::InvalidAction);
+        assert_eq!(UpnpError::from_code(606), UpnpError::Common(606));
+        assert_eq!(UpnpError::from_code(713), UpnpError::ServiceSpecific(713));
+        assert_eq!(UpnpError::from_code(899), UpnpError::VendorSpecific(899));
+        assert_eq!(UpnpError::from_code(200), UpnpError::Other(200));
+        for code in [
+            401, 402, 501, 600, 601, 602, 603, 604, 605, 650, 718, 801, 999,
+        ] {
+            assert_eq!(UpnpError::from_code(code).code(), code);
+        }
+        assert_eq!(UpnpError::InvalidArgs.to_string(), "402 Invalid Args");
+    }
+
+    #[test]
+    fn test_upnp_error_from_fault() {
+        let fault = Envelope::new_fault(602, "Optional Action Not Implemented");
+        assert_eq!(
+            UpnpError::try_from(fault.body()).unwrap(),
+            UpnpError::OptionalActionNotImplemented
+        );
+        let body = Body::Response {
+            action: Action::new(
+                TypeID::new_service("Test".to_string(), 1),
+                "Test".to_string(),
+            ),
+            arguments: Default::default(),
+        };
+        assert!(UpnpError::try_from(&body).is_err());
+    }
+}
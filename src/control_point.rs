@@ -0,0 +1,550 @@
+/*!
+This module provides the device registry at the heart of a control point. The registry combines
+the responses to searches with the notifications received while listening into a single table of
+devices, keyed by UDN, and tracks the presence of each device over time.
+
+Each device in the registry is in one of the following states:
+
+* `Alive` — an advertisement or search response was received and has not yet expired.
+* `Expiring` — most of the advertisement's `max-age` has passed without a refresh, a control
+  point may wish to search for the device again.
+* `ByeBye` — the device announced it is leaving the network.
+* `Stale` — the advertisement's `max-age` passed without a refresh.
+
+Changes are reported as a list of [`RegistryEvent`]s from each operation. A device that reports a
+new `BOOTID.UPNP.ORG` value is reported as `Rebooted`; any subscriptions or cached state for the
+device should be discarded. A device that announces the change beforehand, with an `ssdp:update`
+message, is instead reported as `Updated` and its stored boot identifier replaced by the
+`NEXTBOOTID.UPNP.ORG` value, so that its following advertisements are not seen as a reboot.
+
+# Example
+
+```rust,no_run
+use upnp_rs::control_point::{Registry, RegistryEvent};
+use upnp_rs::discovery::listen::{NotifyListener, Options as ListenOptions};
+use upnp_rs::discovery::search::Options;
+use upnp_rs::SpecVersion;
+use std::time::Instant;
+
+let mut registry = Registry::default();
+let listener = NotifyListener::bind(ListenOptions::default()).unwrap();
+
+let mut events = registry.search(Options::default_for(SpecVersion::V11)).unwrap();
+loop {
+    for event in events.drain(..) {
+        match event {
+            RegistryEvent::Added(udn) => println!("added {}", udn),
+            RegistryEvent::Removed(udn) => println!("removed {}", udn),
+            _ => {}
+        }
+    }
+    if let Some(notification) = listener.receive().unwrap() {
+        events.extend(registry.notify(&notification));
+    }
+    events.extend(registry.expire(Instant::now()));
+}
+```
+*/
+
+use crate::common::udn::Udn;
+use crate::common::uri::URL;
+use crate::discovery::listen::Notification;
+use crate::discovery::search::{search_once, Options as SearchOptions, Response, SearchTarget};
+use crate::discovery::NotificationSubtype;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{info, trace};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The presence of a device, as last observed by the registry.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Presence {
+    /// The device has advertised, or responded, and the advertisement has not yet expired.
+    Alive,
+    /// Most of the advertisement's `max-age` has passed without a refresh.
+    Expiring,
+    /// The device has announced that it is leaving the network.
+    ByeBye,
+    /// The advertisement's `max-age` has passed without a refresh.
+    Stale,
+}
+
+///
+/// A change to the registry, keyed by the UDN of the device concerned.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryEvent {
+    /// A device not currently present was discovered.
+    Added(Udn),
+    /// The location, configuration, or announced boot identifier of a present device changed.
+    Updated(Udn),
+    /// A present device reported a new boot identifier.
+    Rebooted(Udn),
+    /// A device left the network, or its advertisement expired.
+    Removed(Udn),
+}
+
+///
+/// A single device in the registry.
+///
+#[derive(Clone, Debug)]
+pub struct DeviceEntry {
+    /// The unique device name.
+    pub udn: Udn,
+    /// The location of the device description, as last advertised.
+    pub location: URL,
+    /// The current presence of the device.
+    pub presence: Presence,
+    /// The last boot identifier advertised by the device, if any.
+    pub boot_id: Option<u32>,
+    /// The last configuration identifier advertised by the device, if any.
+    pub config_id: Option<u32>,
+    /// The distinct set of targets advertised by the device, in the order received.
+    pub targets: Vec<SearchTarget>,
+    /// The last time an advertisement or response was received for the device.
+    pub last_seen: Instant,
+    /// The `max-age` of the most recent advertisement.
+    pub max_age: Duration,
+}
+
+///
+/// The device table, see the module documentation for details.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    devices: HashMap<Udn, DeviceEntry>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceEntry {
+    ///
+    /// Returns `true` if the device is `Alive` or `Expiring`.
+    ///
+    pub fn is_present(&self) -> bool {
+        matches!(self.presence, Presence::Alive | Presence::Expiring)
+    }
+
+    fn expiring_at(&self) -> Instant {
+        self.last_seen + self.max_age.mul_f64(EXPIRING_FRACTION)
+    }
+
+    fn expires_at(&self) -> Instant {
+        self.last_seen + self.max_age
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Registry {
+    ///
+    /// Issue a single search and add all responses to the registry.
+    ///
+    pub fn search(&mut self, options: SearchOptions) -> Result<Vec<RegistryEvent>, Error> {
+        let responses = search_once(options)?;
+        Ok(responses
+            .iter()
+            .flat_map(|response| self.respond(response))
+            .collect())
+    }
+
+    ///
+    /// Record a single search response.
+    ///
+    pub fn respond(&mut self, response: &Response) -> Vec<RegistryEvent> {
+        // the search response parser uses `0` for a missing boot identifier.
+        let boot_id = if response.boot_id == 0 {
+            None
+        } else {
+            Some(response.boot_id)
+        };
+        self.observe(Observation {
+            udn: response.service_name.device().clone(),
+            target: response.search_target.clone(),
+            location: response.location.clone(),
+            boot_id,
+            config_id: response.config_id,
+            max_age: response.max_age,
+        })
+    }
+
+    ///
+    /// Record a single notification received from a device. An _update_ for a device that is not
+    /// present is ignored, the device will be added by its following advertisement.
+    ///
+    pub fn notify(&mut self, notification: &Notification) -> Vec<RegistryEvent> {
+        let udn = notification.service_name.device().clone();
+        match notification.subtype {
+            NotificationSubtype::Alive => match (&notification.location, notification.max_age) {
+                (Some(location), Some(max_age)) => self.observe(Observation {
+                    udn,
+                    target: notification.notification_type.clone().into(),
+                    location: location.clone(),
+                    boot_id: notification.boot_id,
+                    config_id: notification.config_id,
+                    max_age,
+                }),
+                _ => Vec::new(),
+            },
+            NotificationSubtype::Update => match self.devices.get_mut(&udn) {
+                Some(entry) if entry.is_present() => {
+                    let mut events = Vec::new();
+                    if entry.boot_id.is_some() && entry.boot_id != notification.boot_id {
+                        // the device restarted without our seeing an advertisement for it.
+                        info!("notify - device {} rebooted", udn);
+                        events.push(RegistryEvent::Rebooted(udn.clone()));
+                    }
+                    if let Some(next_boot_id) = notification.next_boot_id {
+                        entry.boot_id = Some(next_boot_id);
+                    }
+                    if let Some(location) = &notification.location {
+                        entry.location = location.clone();
+                    }
+                    entry.config_id = notification.config_id.or(entry.config_id);
+                    let target = SearchTarget::from(notification.notification_type.clone());
+                    if !entry.targets.contains(&target) {
+                        entry.targets.push(target);
+                    }
+                    info!("notify - device {} updated", udn);
+                    events.push(RegistryEvent::Updated(udn));
+                    events
+                }
+                _ => Vec::new(),
+            },
+            NotificationSubtype::ByeBye => match self.devices.get_mut(&udn) {
+                Some(entry) if entry.is_present() => {
+                    info!("notify - device {} leaving", udn);
+                    entry.presence = Presence::ByeBye;
+                    vec![RegistryEvent::Removed(udn)]
+                }
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    ///
+    /// Update the presence of all devices according to the `max-age` of their most recent
+    /// advertisement, devices whose advertisement has expired are reported as removed.
+    ///
+    pub fn expire(&mut self, now: Instant) -> Vec<RegistryEvent> {
+        let mut events = Vec::new();
+        for entry in self.devices.values_mut().filter(|entry| entry.is_present()) {
+            if now >= entry.expires_at() {
+                trace!("expire - device {} is stale", entry.udn);
+                entry.presence = Presence::Stale;
+                events.push(RegistryEvent::Removed(entry.udn.clone()));
+            } else if now >= entry.expiring_at() {
+                entry.presence = Presence::Expiring;
+            }
+        }
+        events
+    }
+
+    ///
+    /// Remove all devices that are no longer present, returning their entries.
+    ///
+    pub fn purge(&mut self) -> Vec<DeviceEntry> {
+        let (absent, present) = self
+            .devices
+            .drain()
+            .partition(|(_, entry)| !entry.is_present());
+        self.devices = present;
+        absent.into_values().collect()
+    }
+
+    ///
+    /// Return the entry for the identified device, if known.
+    ///
+    pub fn get(&self, udn: &Udn) -> Option<&DeviceEntry> {
+        self.devices.get(udn)
+    }
+
+    ///
+    /// Return all devices in the registry, including those no longer present.
+    ///
+    pub fn devices(&self) -> impl Iterator<Item = &DeviceEntry> {
+        self.devices.values()
+    }
+
+    ///
+    /// Return only the devices currently `Alive` or `Expiring`.
+    ///
+    pub fn present(&self) -> impl Iterator<Item = &DeviceEntry> {
+        self.devices.values().filter(|entry| entry.is_present())
+    }
+
+    fn observe(&mut self, observed: Observation) -> Vec<RegistryEvent> {
+        let now = Instant::now();
+        let udn = observed.udn.clone();
+        match self.devices.get_mut(&udn) {
+            Some(entry) if entry.is_present() => {
+                let mut events = Vec::new();
+                if observed.boot_id.is_some() && entry.boot_id != observed.boot_id {
+                    if entry.boot_id.is_some() {
+                        info!("observe - device {} rebooted", udn);
+                        events.push(RegistryEvent::Rebooted(udn.clone()));
+                    }
+                    entry.boot_id = observed.boot_id;
+                }
+                if entry.location != observed.location
+                    || (observed.config_id.is_some() && entry.config_id != observed.config_id)
+                {
+                    entry.location = observed.location;
+                    entry.config_id = observed.config_id.or(entry.config_id);
+                    events.push(RegistryEvent::Updated(udn));
+                }
+                if !entry.targets.contains(&observed.target) {
+                    entry.targets.push(observed.target);
+                }
+                entry.presence = Presence::Alive;
+                entry.last_seen = now;
+                entry.max_age = observed.max_age;
+                events
+            }
+            _ => {
+                info!("observe - device {} added", udn);
+                let _ = self.devices.insert(
+                    udn.clone(),
+                    DeviceEntry {
+                        udn: udn.clone(),
+                        location: observed.location,
+                        presence: Presence::Alive,
+                        boot_id: observed.boot_id,
+                        config_id: observed.config_id,
+                        targets: vec![observed.target],
+                        last_seen: now,
+                        max_age: observed.max_age,
+                    },
+                );
+                vec![RegistryEvent::Added(udn)]
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The portion of a response or notification that affects the registry.
+///
+struct Observation {
+    udn: Udn,
+    target: SearchTarget,
+    location: URL,
+    boot_id: Option<u32>,
+    config_id: Option<u32>,
+    max_age: Duration,
+}
+
+///
+/// The fraction of `max-age` after which a device is considered to be `Expiring`.
+///
+const EXPIRING_FRACTION: f64 = 0.8;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::uri::URI;
+    use crate::discovery::{NotificationType, UniqueServiceName};
+    use std::str::FromStr;
+
+    fn notification(
+        subtype: NotificationSubtype,
+        device: &str,
+        location: &str,
+        boot_id: Option<u32>,
+    ) -> Notification {
+        let alive = subtype == NotificationSubtype::Alive;
+        Notification {
+            subtype,
+            notification_type: NotificationType::RootDevice,
+            service_name: UniqueServiceName::from_str(&format!("{}::upnp:rootdevice", device))
+                .unwrap(),
+            location: if alive {
+                Some(URI::from_str(location).unwrap())
+            } else {
+                None
+            },
+            max_age: if alive {
+                Some(Duration::from_secs(100))
+            } else {
+                None
+            },
+            boot_id,
+            config_id: None,
+            next_boot_id: None,
+            search_port: None,
+            secure_location: None,
+            source: None,
+        }
+    }
+
+    fn udn(device: &str) -> Udn {
+        Udn::from_str(device).unwrap()
+    }
+
+    #[test]
+    fn test_added_then_refreshed() {
+        let mut registry = Registry::default();
+        let alive = notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            None,
+        );
+        assert_eq!(
+            registry.notify(&alive),
+            vec![RegistryEvent::Added(udn("uuid:device-1"))]
+        );
+        assert!(registry.notify(&alive).is_empty());
+        assert_eq!(registry.present().count(), 1);
+    }
+
+    #[test]
+    fn test_location_change_is_update() {
+        let mut registry = Registry::default();
+        let _ = registry.notify(&notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            None,
+        ));
+        assert_eq!(
+            registry.notify(&notification(
+                NotificationSubtype::Alive,
+                "uuid:device-1",
+                "http://10.0.0.2/d.xml",
+                None,
+            )),
+            vec![RegistryEvent::Updated(udn("uuid:device-1"))]
+        );
+    }
+
+    #[test]
+    fn test_boot_id_change_is_reboot() {
+        let mut registry = Registry::default();
+        let _ = registry.notify(&notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            Some(1),
+        ));
+        assert_eq!(
+            registry.notify(&notification(
+                NotificationSubtype::Alive,
+                "uuid:device-1",
+                "http://10.0.0.1/d.xml",
+                Some(2),
+            )),
+            vec![RegistryEvent::Rebooted(udn("uuid:device-1"))]
+        );
+        assert_eq!(
+            registry.get(&udn("uuid:device-1")).unwrap().boot_id,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_update_is_not_reboot() {
+        let mut registry = Registry::default();
+        let update = Notification {
+            next_boot_id: Some(2),
+            ..notification(
+                NotificationSubtype::Update,
+                "uuid:device-1",
+                "http://10.0.0.1/d.xml",
+                Some(1),
+            )
+        };
+        assert!(registry.notify(&update).is_empty());
+
+        let _ = registry.notify(&notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            Some(1),
+        ));
+        assert_eq!(
+            registry.notify(&update),
+            vec![RegistryEvent::Updated(udn("uuid:device-1"))]
+        );
+        assert_eq!(
+            registry.get(&udn("uuid:device-1")).unwrap().boot_id,
+            Some(2)
+        );
+        assert!(registry
+            .notify(&notification(
+                NotificationSubtype::Alive,
+                "uuid:device-1",
+                "http://10.0.0.1/d.xml",
+                Some(2),
+            ))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_byebye_then_alive() {
+        let mut registry = Registry::default();
+        let alive = notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            None,
+        );
+        let _ = registry.notify(&alive);
+        assert_eq!(
+            registry.notify(&notification(
+                NotificationSubtype::ByeBye,
+                "uuid:device-1",
+                "",
+                None
+            )),
+            vec![RegistryEvent::Removed(udn("uuid:device-1"))]
+        );
+        assert_eq!(
+            registry.get(&udn("uuid:device-1")).unwrap().presence,
+            Presence::ByeBye
+        );
+        assert_eq!(
+            registry.notify(&alive),
+            vec![RegistryEvent::Added(udn("uuid:device-1"))]
+        );
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut registry = Registry::default();
+        let _ = registry.notify(&notification(
+            NotificationSubtype::Alive,
+            "uuid:device-1",
+            "http://10.0.0.1/d.xml",
+            None,
+        ));
+        let now = Instant::now();
+        assert!(registry.expire(now + Duration::from_secs(90)).is_empty());
+        assert_eq!(
+            registry.get(&udn("uuid:device-1")).unwrap().presence,
+            Presence::Expiring
+        );
+        assert_eq!(
+            registry.expire(now + Duration::from_secs(101)),
+            vec![RegistryEvent::Removed(udn("uuid:device-1"))]
+        );
+        assert_eq!(registry.purge().len(), 1);
+        assert_eq!(registry.devices().count(), 0);
+    }
+}
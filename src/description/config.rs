@@ -0,0 +1,296 @@
+/*!
+This module provides support for managing the configuration number of a device, the value of the
+`configId` attribute in descriptions and the `CONFIGID.UPNP.ORG` header in SSDP messages. A device
+must change its configuration number whenever its device or service descriptions change;
+[`config_id`](fn.config_id.html) derives a stable value from the descriptions themselves, and
+[`diff`](fn.diff.html) reports what changed between two versions of a description.
+
+# Example
+
+```rust
+use upnp_rs::description::config::{config_id, diff};
+use upnp_rs::description::device::DeviceRoot;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+let old = DeviceRoot::from_str("<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>1</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Kitchen</friendlyName><manufacturer>Example</manufacturer><modelName>Example</modelName><UDN>uuid:device-1</UDN></device></root>").unwrap();
+let mut new = old.clone();
+new.device.friendly_name = "Dining Room".to_string();
+
+assert_ne!(
+    config_id(&old, &HashMap::new()).unwrap(),
+    config_id(&new, &HashMap::new()).unwrap()
+);
+assert_eq!(diff(&old, &new).len(), 1);
+```
+*/
+
+use crate::common::udn::Udn;
+use crate::common::xml::write::Writable;
+use crate::description::device::{Device, DeviceRoot, Service};
+use crate::description::service::Spcd;
+use crate::description::ServiceId;
+use crate::error::Error;
+use crate::syntax::MAX_CONFIG_ID;
+use quick_xml::Writer;
+use std::collections::HashMap;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A single difference between two versions of a device description.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The specification version or `URLBase` of the root changed.
+    RootChanged,
+    /// A device, identified by its UDN, is only present in the new description.
+    DeviceAdded(Udn),
+    /// A device, identified by its UDN, is only present in the old description.
+    DeviceRemoved(Udn),
+    /// The properties of a device, not including its services or embedded devices, changed.
+    DeviceChanged(Udn),
+    /// A service, identified by its service ID, is only present in the new description.
+    ServiceAdded { device: Udn, service_id: ServiceId },
+    /// A service, identified by its service ID, is only present in the old description.
+    ServiceRemoved { device: Udn, service_id: ServiceId },
+    /// The properties of a service, or its service description, changed.
+    ServiceChanged { device: Udn, service_id: ServiceId },
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Compute a configuration number from a device description and the service descriptions it
+/// refers to, keyed by `SCPDURL`. The value is derived from the serialized form of each
+/// description, ignoring any existing `configId`, and so is stable across runs and only changes
+/// when the descriptions do. The result is always within the range permitted for configuration
+/// numbers, `0..=16777215`.
+///
+pub fn config_id(root: &DeviceRoot, scpds: &HashMap<String, Spcd>) -> Result<u32, Error> {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    let mut root = root.clone();
+    root.config_id = None;
+    hash = fnv1a(hash, &to_bytes(&root)?);
+
+    let mut scpd_urls: Vec<&String> = scpds.keys().collect();
+    scpd_urls.sort();
+    for scpd_url in scpd_urls {
+        let mut scpd = scpds[scpd_url].clone();
+        scpd.config_id = None;
+        hash = fnv1a(hash, scpd_url.as_bytes());
+        hash = fnv1a(hash, &to_bytes(&scpd)?);
+    }
+
+    // fold the 64-bit hash into the 24 bits permitted.
+    Ok(((hash ^ (hash >> 24) ^ (hash >> 48)) & MAX_CONFIG_ID as u64) as u32)
+}
+
+///
+/// Compare two versions of a device description, returning the devices and services that were
+/// added, removed, or changed. Devices are matched by UDN and services by service ID within their
+/// device. Changes are reported for the old description first, in document order, followed by
+/// additions in the new description.
+///
+pub fn diff(old: &DeviceRoot, new: &DeviceRoot) -> Vec<Change> {
+    diff_with(old, &HashMap::new(), new, &HashMap::new())
+}
+
+///
+/// As `diff`, but services are also reported as changed when their service description, looked
+/// up by `SCPDURL` in `old_scpds` and `new_scpds`, changed.
+///
+pub fn diff_with(
+    old: &DeviceRoot,
+    old_scpds: &HashMap<String, Spcd>,
+    new: &DeviceRoot,
+    new_scpds: &HashMap<String, Spcd>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if old.spec_version != new.spec_version || old.url_base != new.url_base {
+        changes.push(Change::RootChanged);
+    }
+
+    let old_devices = old.device.all_devices();
+    let new_devices = new.device.all_devices();
+
+    for old_device in &old_devices {
+        let udn = &old_device.unique_device_name;
+        match new_devices.iter().find(|d| &d.unique_device_name == udn) {
+            None => changes.push(Change::DeviceRemoved(udn.clone())),
+            Some(new_device) => {
+                if device_fingerprint(old_device) != device_fingerprint(new_device) {
+                    changes.push(Change::DeviceChanged(udn.clone()));
+                }
+                for old_service in &old_device.service_list {
+                    let service_id = old_service.service_id.clone();
+                    match new_device
+                        .service_list
+                        .iter()
+                        .find(|s| s.service_id == old_service.service_id)
+                    {
+                        None => changes.push(Change::ServiceRemoved {
+                            device: udn.clone(),
+                            service_id,
+                        }),
+                        Some(new_service) => {
+                            if service_fingerprint(old_service, old_scpds)
+                                != service_fingerprint(new_service, new_scpds)
+                            {
+                                changes.push(Change::ServiceChanged {
+                                    device: udn.clone(),
+                                    service_id,
+                                })
+                            }
+                        }
+                    }
+                }
+                for new_service in &new_device.service_list {
+                    if !old_device
+                        .service_list
+                        .iter()
+                        .any(|s| s.service_id == new_service.service_id)
+                    {
+                        changes.push(Change::ServiceAdded {
+                            device: udn.clone(),
+                            service_id: new_service.service_id.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    for new_device in &new_devices {
+        if !old_devices
+            .iter()
+            .any(|d| d.unique_device_name == new_device.unique_device_name)
+        {
+            changes.push(Change::DeviceAdded(new_device.unique_device_name.clone()));
+        }
+    }
+    changes
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn to_bytes<W: Writable<Vec<u8>>>(value: &W) -> Result<Vec<u8>, Error> {
+    let mut writer = Writer::new(Vec::new());
+    value.write(&mut writer)?;
+    Ok(writer.into_inner())
+}
+
+fn device_fingerprint(device: &Device) -> Option<Vec<u8>> {
+    let mut device = device.clone();
+    device.service_list.clear();
+    device.device_list.clear();
+    to_bytes(&device).ok()
+}
+
+fn service_fingerprint(service: &Service, scpds: &HashMap<String, Spcd>) -> Option<Vec<u8>> {
+    let mut bytes = to_bytes(service).ok()?;
+    if let Some(scpd) = scpds.get(&service.scpd_url) {
+        let mut scpd = scpd.clone();
+        scpd.config_id = None;
+        bytes.extend(to_bytes(&scpd).ok()?);
+    }
+    Some(bytes)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::description::TypeID;
+    use std::str::FromStr;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\" configId=\"7\"><specVersion><major>1</major><minor>1</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:root</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType><serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId><SCPDURL>/cd.xml</SCPDURL><controlURL>/cd/control</controlURL><eventSubURL>/cd/event</eventSubURL></service></serviceList><deviceList><device><deviceType>urn:example-com:device:Widget:1</deviceType><friendlyName>Widget</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:embedded</UDN></device></deviceList></device></root>";
+
+    const SCPD: &str = "<?xml version=\"1.0\"?><scpd xmlns=\"urn:schemas-upnp-org:service-1-0\"><specVersion><major>1</major><minor>1</minor></specVersion><actionList></actionList><serviceStateTable><stateVariable sendEvents=\"no\"><name>A_ARG_TYPE_Count</name><dataType>ui4</dataType></stateVariable></serviceStateTable></scpd>";
+
+    fn scpds() -> HashMap<String, Spcd> {
+        let mut scpds = HashMap::new();
+        let _ = scpds.insert("/cd.xml".to_string(), Spcd::from_str(SCPD).unwrap());
+        scpds
+    }
+
+    #[test]
+    fn test_config_id_is_stable() {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let first = config_id(&root, &scpds()).unwrap();
+        assert!(first <= MAX_CONFIG_ID);
+
+        let mut renumbered = root.clone();
+        renumbered.config_id = Some(first);
+        assert_eq!(config_id(&renumbered, &scpds()).unwrap(), first);
+
+        let mut scpds = scpds();
+        scpds.get_mut("/cd.xml").unwrap().service_state_table[0].name = "Changed".to_string();
+        assert_ne!(config_id(&root, &scpds).unwrap(), first);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        assert!(diff(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.device.device_list[0].friendly_name = "Gadget".to_string();
+        new.device.service_list[0].control_url = "/cd/control2".to_string();
+        let mut added = new.device.service_list[0].clone();
+        added.service_id = ServiceId::new("ConnectionManager".to_string());
+        added.service_type = TypeID::new_service("ConnectionManager".to_string(), 1);
+        new.device.service_list.push(added);
+
+        let root = Udn::from_str("uuid:root").unwrap();
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::ServiceChanged {
+                    device: root.clone(),
+                    service_id: ServiceId::new("ContentDirectory".to_string())
+                },
+                Change::ServiceAdded {
+                    device: root,
+                    service_id: ServiceId::new("ConnectionManager".to_string())
+                },
+                Change::DeviceChanged(Udn::from_str("uuid:embedded").unwrap()),
+            ]
+        );
+
+        let mut new_scpds = scpds();
+        new_scpds
+            .get_mut("/cd.xml")
+            .unwrap()
+            .service_state_table
+            .clear();
+        assert_eq!(diff_with(&old, &scpds(), &old, &new_scpds).len(), 1);
+
+        new.device.device_list.clear();
+        assert_eq!(
+            diff(&old, &new).last(),
+            Some(&Change::DeviceRemoved(
+                Udn::from_str("uuid:embedded").unwrap()
+            ))
+        );
+    }
+}
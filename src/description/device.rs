@@ -6,9 +6,17 @@ What's this all about then?
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+use crate::common::http::{fetch_bytes_with, HttpClient};
+use crate::common::udn::Udn;
+use crate::common::uri::URL;
+use crate::common::xml::read::{read_xml, Element, XmlFragment};
 use crate::common::xml::write::*;
-use crate::description::TypeID;
-use crate::error::{xml_error, Error};
+use crate::description::{config_id_from, spec_version_from, ServiceId, TypeID, Violation};
+use crate::discovery::search::SearchTarget;
+use crate::error::{
+    header_type_mismatch, invalid_field_value, missing_required_field, xml_error, Error,
+};
+use crate::syntax::{HTTP_HEADER_CONTENT_TYPE, MAX_CONFIG_ID, XML_ATTR_CONFIG_ID};
 use crate::syntax::{
     XML_ELEM_DEVICE, XML_ELEM_DEVICE_LIST, XML_ELEM_DEVICE_TYPE, XML_ELEM_FRIENDLY_NAME,
     XML_ELEM_ICON, XML_ELEM_ICON_DEPTH, XML_ELEM_ICON_HEIGHT, XML_ELEM_ICON_LIST,
@@ -17,13 +25,22 @@ use crate::syntax::{
     XML_ELEM_MODEL_URL, XML_ELEM_PRESENTATION_URL, XML_ELEM_ROOT, XML_ELEM_SERIAL_NUMBER,
     XML_ELEM_SERVICE, XML_ELEM_SERVICE_CONTROL_URL, XML_ELEM_SERVICE_EVENT_URL,
     XML_ELEM_SERVICE_ID, XML_ELEM_SERVICE_LIST, XML_ELEM_SERVICE_SCPD_URL, XML_ELEM_SERVICE_TYPE,
-    XML_ELEM_UDN, XML_ELEM_UPC, XML_ELEM_URL_BASE, XML_NS_DEVICE, XML_NS_SERVICE,
+    XML_ELEM_SPEC_VERSION, XML_ELEM_UDN, XML_ELEM_UPC, XML_ELEM_URL_BASE, XML_NS_DEVICE,
+    XML_NS_SERVICE,
 };
 use crate::SpecVersion;
 use quick_xml::Writer;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::io::Write;
+use std::str::FromStr;
+use tracing::error;
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Icon {
     pub mime_type: String,
     pub width: u16,
@@ -33,15 +50,19 @@ pub struct Icon {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Service {
     pub service_type: TypeID,
-    pub service_id: String,    /* URI */
+    pub service_id: ServiceId,
     pub scpd_url: String,      /* URL */
     pub control_url: String,   /* URL */
     pub event_sub_url: String, /* URL */
+    /// Elements not defined by the specification, such as vendor extensions.
+    pub extensions: Vec<XmlFragment>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Device {
     pub device_type: TypeID,
     pub friendly_name: String,
@@ -52,17 +73,23 @@ pub struct Device {
     pub model_number: Option<String>,
     pub model_url: Option<String>, /* URL */
     pub serial_number: Option<String>,
-    pub unique_device_name: String,
+    pub unique_device_name: Udn,
     pub upc: Option<String>,
     pub icon_list: Vec<Icon>,
     pub service_list: Vec<Service>,
     pub device_list: Vec<Device>,
     pub presentation_url: Option<String>, /* URL */
+    /// Elements not defined by the specification, such as `dlna:X_DLNADOC`.
+    pub extensions: Vec<XmlFragment>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DeviceRoot {
     pub spec_version: SpecVersion,
+    /// The configuration number, only written for version 1.1 and later.
+    pub config_id: Option<u32>,
+    /// Only written for versions before 2.0, where it is no longer permitted.
     pub url_base: String, /* URL */
     pub device: Device,
 }
@@ -75,26 +102,422 @@ pub fn to_writer<T: Write>(root: &DeviceRoot, writer: T) -> Result<T, Error> {
     root.write_root(writer)
 }
 
+pub fn to_writer_with<T: Write>(
+    root: &DeviceRoot,
+    writer: T,
+    options: &WriteOptions,
+) -> Result<T, Error> {
+    root.write_root_with(writer, options)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl DeviceRoot {
+    ///
+    /// Return the base URL against which relative URLs in this description are resolved; this is
+    /// the `URLBase` value if present, else the `location` the description was retrieved from.
+    ///
+    pub fn base_url(&self, location: &URL) -> URL {
+        if self.url_base.is_empty() {
+            location.clone()
+        } else {
+            URL::from_str(&self.url_base).unwrap()
+        }
+    }
+
+    ///
+    /// Check this description against the rules of the given specification version, returning
+    /// all the violations found; an empty result means the description may be written as-is.
+    ///
+    pub fn validate(&self, spec_version: SpecVersion) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let path = XML_ELEM_ROOT.to_string();
+
+        if self.spec_version != spec_version {
+            violations.push(Violation::new(
+                format!("{}/{}", path, XML_ELEM_SPEC_VERSION),
+                format!(
+                    "version {} does not match the requested version {}",
+                    self.spec_version, spec_version
+                ),
+            ));
+        }
+
+        let config_path = format!("{}/@{}", path, XML_ATTR_CONFIG_ID);
+        match (spec_version, self.config_id) {
+            (SpecVersion::V10, Some(_)) => violations.push(Violation::new(
+                config_path,
+                "not defined in version 1.0 and will not be written",
+            )),
+            (SpecVersion::V10, None) => {}
+            (_, None) => violations.push(Violation::new(
+                config_path,
+                format!("required in version {}", spec_version),
+            )),
+            (_, Some(config_id)) if config_id > MAX_CONFIG_ID => violations.push(Violation::new(
+                config_path,
+                format!("value {} is in the reserved range", config_id),
+            )),
+            _ => {}
+        }
+
+        if !self.url_base.is_empty() {
+            let url_path = format!("{}/{}", path, XML_ELEM_URL_BASE);
+            if spec_version == SpecVersion::V20 {
+                violations.push(Violation::new(
+                    url_path,
+                    "not permitted in version 2.0 and will not be written",
+                ));
+            } else if URL::from_str(&self.url_base).is_err() {
+                violations.push(Violation::new(url_path, "not a valid URL"));
+            }
+        }
+
+        let mut udns = HashSet::new();
+        self.device.validate(
+            spec_version,
+            &format!("{}/{}", path, XML_ELEM_DEVICE),
+            &mut udns,
+            &mut violations,
+        );
+
+        violations
+    }
+}
+
+impl Device {
+    ///
+    /// Download all the icons for this device, resolving each icon's URL against `base`, which is
+    /// usually the result of `DeviceRoot::base_url`. An error is returned if any icon cannot be
+    /// retrieved, or if the content type returned does not match the icon's declared MIME type.
+    ///
+    #[cfg(feature = "net")]
+    pub fn fetch_icons(&self, base: &URL) -> Result<Vec<(Icon, Vec<u8>)>, Error> {
+        self.fetch_icons_with(base, default_client().as_ref())
+    }
+
+    ///
+    /// Download all the icons for this device, using the provided client.
+    ///
+    pub fn fetch_icons_with(
+        &self,
+        base: &URL,
+        client: &dyn HttpClient,
+    ) -> Result<Vec<(Icon, Vec<u8>)>, Error> {
+        self.icon_list
+            .iter()
+            .map(|icon| Ok((icon.clone(), icon.fetch_with(base, client)?)))
+            .collect()
+    }
+
+    ///
+    /// Select the icon best suited for display at the given size and color depth; this is the
+    /// smallest icon at least as large as requested, or failing that the largest icon. Between
+    /// icons of the same size the closest color depth, preferring deeper, is chosen.
+    ///
+    pub fn best_icon(&self, width: u16, height: u16, depth: u16) -> Option<&Icon> {
+        let large_enough = |icon: &&Icon| icon.width >= width && icon.height >= height;
+        let depth_distance =
+            |icon: &Icon| (icon.depth < depth, (icon.depth as i32 - depth as i32).abs());
+        if self.icon_list.iter().any(|icon| large_enough(&icon)) {
+            self.icon_list
+                .iter()
+                .filter(large_enough)
+                .min_by_key(|icon| (icon.area(), depth_distance(icon)))
+        } else {
+            self.icon_list
+                .iter()
+                .min_by_key(|icon| (std::cmp::Reverse(icon.area()), depth_distance(icon)))
+        }
+    }
+
+    ///
+    /// Resolve this device's presentation URL, if any, against `base`.
+    ///
+    pub fn presentation_url(&self, base: &URL) -> Option<URL> {
+        self.presentation_url
+            .as_ref()
+            .and_then(|url| base.resolve(url).ok())
+    }
+
+    ///
+    /// Return this device and all its embedded devices, recursively, in document order.
+    ///
+    pub fn all_devices(&self) -> Vec<&Device> {
+        let mut devices = vec![self];
+        for embedded in &self.device_list {
+            devices.extend(embedded.all_devices());
+        }
+        devices
+    }
+
+    fn validate(
+        &self,
+        spec_version: SpecVersion,
+        path: &str,
+        udns: &mut HashSet<Udn>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let mut require = |name: &str, value: &str| {
+            if value.trim().is_empty() {
+                violations.push(Violation::new(format!("{}/{}", path, name), "required"));
+            }
+        };
+        require(XML_ELEM_FRIENDLY_NAME, &self.friendly_name);
+        require(XML_ELEM_MANUFACTURER, &self.manufacturer);
+        require(XML_ELEM_MODEL_NAME, &self.model_name);
+
+        if !udns.insert(self.unique_device_name.clone()) {
+            violations.push(Violation::new(
+                format!("{}/{}", path, XML_ELEM_UDN),
+                format!("{} is not unique", self.unique_device_name),
+            ));
+        }
+
+        if let Some(upc) = &self.upc {
+            if upc.len() != 12 || !upc.chars().all(|c| c.is_ascii_digit()) {
+                violations.push(Violation::new(
+                    format!("{}/{}", path, XML_ELEM_UPC),
+                    "must be a 12-digit, all-numeric, code",
+                ));
+            }
+        }
+
+        for (index, icon) in self.icon_list.iter().enumerate() {
+            let icon_path = format!(
+                "{}/{}/{}[{}]",
+                path, XML_ELEM_ICON_LIST, XML_ELEM_ICON, index
+            );
+            if icon.mime_type.trim().is_empty() {
+                violations.push(Violation::new(
+                    format!("{}/{}", icon_path, XML_ELEM_ICON_MIME_TYPE),
+                    "required",
+                ));
+            }
+            if icon.url.trim().is_empty() {
+                violations.push(Violation::new(
+                    format!("{}/{}", icon_path, XML_ELEM_ICON_URL),
+                    "required",
+                ));
+            }
+        }
+
+        if spec_version == SpecVersion::V10 && self.service_list.is_empty() {
+            violations.push(Violation::new(
+                format!("{}/{}", path, XML_ELEM_SERVICE_LIST),
+                "at least one service is required in version 1.0",
+            ));
+        }
+        let mut service_ids = HashSet::new();
+        for (index, service) in self.service_list.iter().enumerate() {
+            let service_path = format!(
+                "{}/{}/{}[{}]",
+                path, XML_ELEM_SERVICE_LIST, XML_ELEM_SERVICE, index
+            );
+            if !service.service_id.is_conformant() {
+                violations.push(Violation::new(
+                    format!("{}/{}", service_path, XML_ELEM_SERVICE_ID),
+                    "must be at most 64 characters, with no colons or whitespace",
+                ));
+            }
+            if !service_ids.insert(&service.service_id) {
+                violations.push(Violation::new(
+                    format!("{}/{}", service_path, XML_ELEM_SERVICE_ID),
+                    "must be unique within the device",
+                ));
+            }
+            for (name, value) in [
+                (XML_ELEM_SERVICE_SCPD_URL, &service.scpd_url),
+                (XML_ELEM_SERVICE_CONTROL_URL, &service.control_url),
+                (XML_ELEM_SERVICE_EVENT_URL, &service.event_sub_url),
+            ] {
+                if value.trim().is_empty() {
+                    violations.push(Violation::new(
+                        format!("{}/{}", service_path, name),
+                        "required",
+                    ));
+                }
+            }
+        }
+
+        for (index, device) in self.device_list.iter().enumerate() {
+            device.validate(
+                spec_version,
+                &format!(
+                    "{}/{}/{}[{}]",
+                    path, XML_ELEM_DEVICE_LIST, XML_ELEM_DEVICE, index
+                ),
+                udns,
+                violations,
+            );
+        }
+    }
+}
+
+impl Icon {
+    ///
+    /// Download this icon, resolving its URL against `base`; the content type returned must
+    /// match the icon's declared MIME type.
+    ///
+    pub fn fetch_with(&self, base: &URL, client: &dyn HttpClient) -> Result<Vec<u8>, Error> {
+        let url = base.resolve(&self.url)?;
+        let (content_type, bytes) = fetch_bytes_with(url.to_string(), client)?;
+        match content_type {
+            Some(content_type) if content_type != self.mime_type.trim().to_lowercase() => {
+                error!(
+                    "fetch_with - icon {} has content type {}, expected {}",
+                    url, content_type, self.mime_type
+                );
+                header_type_mismatch(HTTP_HEADER_CONTENT_TYPE, &self.mime_type, content_type).into()
+            }
+            _ => Ok(bytes),
+        }
+    }
+
+    fn area(&self) -> u32 {
+        self.width as u32 * self.height as u32
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl FromStr for DeviceRoot {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DeviceRoot::try_from(&read_xml(s)?)
+    }
+}
+
+impl TryFrom<&Element> for DeviceRoot {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        if element.local_name() != XML_ELEM_ROOT {
+            return invalid_field_value(XML_ELEM_ROOT, &element.name).into();
+        }
+        Ok(DeviceRoot {
+            spec_version: spec_version_from(element)?,
+            config_id: config_id_from(element)?,
+            url_base: element
+                .child_text(XML_ELEM_URL_BASE)
+                .unwrap_or_default()
+                .to_string(),
+            device: Device::try_from(required_child(element, XML_ELEM_DEVICE)?)?,
+        })
+    }
+}
+
+impl TryFrom<&Element> for Device {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        let optional_text = |name: &str| element.child_text(name).map(String::from);
+        Ok(Device {
+            device_type: TypeID::device_from(SearchTarget::from_str(
+                element.required_child_text(XML_ELEM_DEVICE_TYPE)?,
+            )?)?,
+            friendly_name: element
+                .required_child_text(XML_ELEM_FRIENDLY_NAME)?
+                .to_string(),
+            manufacturer: element
+                .required_child_text(XML_ELEM_MANUFACTURER)?
+                .to_string(),
+            manufacturer_url: optional_text(XML_ELEM_MANUFACTURER_URL),
+            model_description: optional_text(XML_ELEM_MODEL_DESCR),
+            model_name: element
+                .required_child_text(XML_ELEM_MODEL_NAME)?
+                .to_string(),
+            model_number: optional_text(XML_ELEM_MODEL_NUMBER),
+            model_url: optional_text(XML_ELEM_MODEL_URL),
+            serial_number: optional_text(XML_ELEM_SERIAL_NUMBER),
+            unique_device_name: {
+                let udn = element.required_child_text(XML_ELEM_UDN)?;
+                Udn::from_str(udn).map_err(|_| invalid_field_value(XML_ELEM_UDN, udn))?
+            },
+            upc: optional_text(XML_ELEM_UPC),
+            icon_list: list_from(element, XML_ELEM_ICON_LIST, XML_ELEM_ICON)?,
+            service_list: list_from(element, XML_ELEM_SERVICE_LIST, XML_ELEM_SERVICE)?,
+            device_list: list_from(element, XML_ELEM_DEVICE_LIST, XML_ELEM_DEVICE)?,
+            presentation_url: optional_text(XML_ELEM_PRESENTATION_URL),
+            extensions: XmlFragment::unknown_children(element, DEVICE_ELEMENTS),
+        })
+    }
+}
+
+impl TryFrom<&Element> for Icon {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        let number = |name: &str| -> Result<u16, Error> {
+            let value = element.required_child_text(name)?;
+            value
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| invalid_field_value(name, value).into())
+        };
+        Ok(Icon {
+            mime_type: element
+                .required_child_text(XML_ELEM_ICON_MIME_TYPE)?
+                .to_string(),
+            width: number(XML_ELEM_ICON_WIDTH)?,
+            height: number(XML_ELEM_ICON_HEIGHT)?,
+            depth: number(XML_ELEM_ICON_DEPTH)?,
+            url: element.required_child_text(XML_ELEM_ICON_URL)?.to_string(),
+        })
+    }
+}
+
+impl TryFrom<&Element> for Service {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        Ok(Service {
+            service_type: TypeID::service_from(SearchTarget::from_str(
+                element.required_child_text(XML_ELEM_SERVICE_TYPE)?,
+            )?)?,
+            service_id: {
+                let service_id = element.required_child_text(XML_ELEM_SERVICE_ID)?;
+                ServiceId::from_str(service_id)
+                    .map_err(|_| invalid_field_value(XML_ELEM_SERVICE_ID, service_id))?
+            },
+            scpd_url: element
+                .required_child_text(XML_ELEM_SERVICE_SCPD_URL)?
+                .to_string(),
+            control_url: element
+                .required_child_text(XML_ELEM_SERVICE_CONTROL_URL)?
+                .to_string(),
+            event_sub_url: element
+                .required_child_text(XML_ELEM_SERVICE_EVENT_URL)?
+                .to_string(),
+            extensions: XmlFragment::unknown_children(element, SERVICE_ELEMENTS),
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
 impl<T: Write> RootWritable<T> for DeviceRoot {}
 
 impl<T: Write> Writable<T> for DeviceRoot {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let root =
-            start_ns_element(writer, XML_ELEM_ROOT, XML_NS_DEVICE, None).map_err(xml_error)?;
-
-        self.spec_version.write(writer)?;
-
-        text_element(
+        let root = start_description_element(
             writer,
-            XML_ELEM_URL_BASE,
-            self.url_base.to_string().as_bytes(),
+            XML_ELEM_ROOT,
+            XML_NS_DEVICE,
+            self.spec_version,
+            self.config_id,
         )
         .map_err(xml_error)?;
 
+        self.spec_version.write(writer)?;
+
+        if !self.url_base.is_empty() && self.spec_version < SpecVersion::V20 {
+            text_element(writer, XML_ELEM_URL_BASE, self.url_base.as_str()).map_err(xml_error)?;
+        }
+
         self.device.write(writer)?;
 
         root.end(writer).map_err(xml_error)
@@ -105,50 +528,40 @@ impl<T: Write> Writable<T> for Device {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
         let top = start_element(writer, XML_ELEM_DEVICE).map_err(xml_error)?;
 
-        text_element(
-            writer,
-            XML_ELEM_DEVICE_TYPE,
-            self.device_type.to_string().as_bytes(),
-        )
-        .map_err(xml_error)?;
+        display_element(writer, XML_ELEM_DEVICE_TYPE, &self.device_type).map_err(xml_error)?;
 
-        text_element(
-            writer,
-            XML_ELEM_FRIENDLY_NAME,
-            self.friendly_name.as_bytes(),
-        )
-        .map_err(xml_error)?;
+        text_element(writer, XML_ELEM_FRIENDLY_NAME, self.friendly_name.as_str())
+            .map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_MANUFACTURER, self.manufacturer.as_bytes())
+        text_element(writer, XML_ELEM_MANUFACTURER, self.manufacturer.as_str())
             .map_err(xml_error)?;
 
         if let Some(s) = &self.manufacturer_url {
-            text_element(writer, XML_ELEM_MANUFACTURER_URL, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MANUFACTURER_URL, s.as_str()).map_err(xml_error)?;
         }
 
         if let Some(s) = &self.model_description {
-            text_element(writer, XML_ELEM_MODEL_DESCR, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MODEL_DESCR, s.as_str()).map_err(xml_error)?;
         }
 
-        text_element(writer, XML_ELEM_MODEL_NAME, self.model_name.as_bytes()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_MODEL_NAME, self.model_name.as_str()).map_err(xml_error)?;
 
         if let Some(s) = &self.model_number {
-            text_element(writer, XML_ELEM_MODEL_NUMBER, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MODEL_NUMBER, s.as_str()).map_err(xml_error)?;
         }
 
         if let Some(s) = &self.model_url {
-            text_element(writer, XML_ELEM_MODEL_URL, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MODEL_URL, s.as_str()).map_err(xml_error)?;
         }
 
         if let Some(s) = &self.serial_number {
-            text_element(writer, XML_ELEM_SERIAL_NUMBER, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_SERIAL_NUMBER, s.as_str()).map_err(xml_error)?;
         }
 
-        text_element(writer, XML_ELEM_UDN, self.unique_device_name.as_bytes())
-            .map_err(xml_error)?;
+        display_element(writer, XML_ELEM_UDN, &self.unique_device_name).map_err(xml_error)?;
 
         if let Some(s) = &self.upc {
-            text_element(writer, XML_ELEM_UPC, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_UPC, s.as_str()).map_err(xml_error)?;
         }
 
         if !&self.icon_list.is_empty() {
@@ -176,7 +589,11 @@ impl<T: Write> Writable<T> for Device {
         }
 
         if let Some(s) = &self.presentation_url {
-            text_element(writer, XML_ELEM_PRESENTATION_URL, s.as_bytes()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_PRESENTATION_URL, s.as_str()).map_err(xml_error)?;
+        }
+
+        for extension in &self.extensions {
+            extension.write_in(writer, XML_NS_DEVICE)?;
         }
 
         top.end(writer).map_err(xml_error)
@@ -187,27 +604,12 @@ impl<T: Write> Writable<T> for Icon {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
         let element = start_element(writer, XML_ELEM_ICON).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_ICON_MIME_TYPE, self.mime_type.as_bytes())
+        text_element(writer, XML_ELEM_ICON_MIME_TYPE, self.mime_type.as_str())
             .map_err(xml_error)?;
-        text_element(
-            writer,
-            XML_ELEM_ICON_WIDTH,
-            self.width.to_string().as_bytes(),
-        )
-        .map_err(xml_error)?;
-        text_element(
-            writer,
-            XML_ELEM_ICON_HEIGHT,
-            self.height.to_string().as_bytes(),
-        )
-        .map_err(xml_error)?;
-        text_element(
-            writer,
-            XML_ELEM_ICON_DEPTH,
-            self.depth.to_string().as_bytes(),
-        )
-        .map_err(xml_error)?;
-        text_element(writer, XML_ELEM_ICON_URL, self.url.as_bytes()).map_err(xml_error)?;
+        display_element(writer, XML_ELEM_ICON_WIDTH, self.width).map_err(xml_error)?;
+        display_element(writer, XML_ELEM_ICON_HEIGHT, self.height).map_err(xml_error)?;
+        display_element(writer, XML_ELEM_ICON_DEPTH, self.depth).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_ICON_URL, self.url.as_str()).map_err(xml_error)?;
 
         element.end(writer).map_err(xml_error)
     }
@@ -218,40 +620,90 @@ impl<T: Write> Writable<T> for Service {
         let element =
             start_ns_element(writer, XML_ELEM_SERVICE, XML_NS_SERVICE, None).map_err(xml_error)?;
 
-        text_element(
-            writer,
-            XML_ELEM_SERVICE_TYPE,
-            self.service_type.to_string().as_bytes(),
-        )
-        .map_err(xml_error)?;
+        display_element(writer, XML_ELEM_SERVICE_TYPE, &self.service_type).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_SERVICE_ID, self.service_id.as_bytes()).map_err(xml_error)?;
+        display_element(writer, XML_ELEM_SERVICE_ID, &self.service_id).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_SERVICE_SCPD_URL, self.scpd_url.as_bytes())
+        text_element(writer, XML_ELEM_SERVICE_SCPD_URL, self.scpd_url.as_str())
             .map_err(xml_error)?;
 
         text_element(
             writer,
             XML_ELEM_SERVICE_CONTROL_URL,
-            self.control_url.as_bytes(),
+            self.control_url.as_str(),
         )
         .map_err(xml_error)?;
 
         text_element(
             writer,
             XML_ELEM_SERVICE_EVENT_URL,
-            self.event_sub_url.as_bytes(),
+            self.event_sub_url.as_str(),
         )
         .map_err(xml_error)?;
 
+        for extension in &self.extensions {
+            extension.write_in(writer, XML_NS_SERVICE)?;
+        }
+
         element.end(writer).map_err(xml_error)
     }
 }
 
 // ------------------------------------------------------------------------------------------------
-// Private Types
+// Private Values
 // ------------------------------------------------------------------------------------------------
 
+const DEVICE_ELEMENTS: &[&str] = &[
+    XML_ELEM_DEVICE_TYPE,
+    XML_ELEM_FRIENDLY_NAME,
+    XML_ELEM_MANUFACTURER,
+    XML_ELEM_MANUFACTURER_URL,
+    XML_ELEM_MODEL_DESCR,
+    XML_ELEM_MODEL_NAME,
+    XML_ELEM_MODEL_NUMBER,
+    XML_ELEM_MODEL_URL,
+    XML_ELEM_SERIAL_NUMBER,
+    XML_ELEM_UDN,
+    XML_ELEM_UPC,
+    XML_ELEM_ICON_LIST,
+    XML_ELEM_SERVICE_LIST,
+    XML_ELEM_DEVICE_LIST,
+    XML_ELEM_PRESENTATION_URL,
+];
+
+const SERVICE_ELEMENTS: &[&str] = &[
+    XML_ELEM_SERVICE_TYPE,
+    XML_ELEM_SERVICE_ID,
+    XML_ELEM_SERVICE_SCPD_URL,
+    XML_ELEM_SERVICE_CONTROL_URL,
+    XML_ELEM_SERVICE_EVENT_URL,
+];
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn required_child<'a>(element: &'a Element, name: &str) -> Result<&'a Element, Error> {
+    match element.child(name) {
+        Some(child) => Ok(child),
+        None => missing_required_field(name).into(),
+    }
+}
+
+fn list_from<'a, T>(
+    element: &'a Element,
+    list_name: &str,
+    item_name: &'a str,
+) -> Result<Vec<T>, Error>
+where
+    T: TryFrom<&'a Element, Error = Error>,
+{
+    match element.child(list_name) {
+        None => Ok(Default::default()),
+        Some(list) => list.children_named(item_name).map(T::try_from).collect(),
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
@@ -302,9 +754,10 @@ mod tests {
     fn test_xml_serialize() {
         let device = DeviceRoot {
             spec_version: SpecVersion::V10,
+            config_id: None,
             url_base: "http://10.59.104.28:49152/".to_string(),
             device: Device {
-                device_type: TypeID::new_device("Basic".to_string(), "1".to_string()),
+                device_type: TypeID::new_device("Basic".to_string(), 1),
                 friendly_name: "AXIS P3301 - 00408CA45086".to_string(),
                 manufacturer: "AXIS".to_string(),
                 manufacturer_url: Some("http://www.axis.com/".to_string()),
@@ -313,22 +766,26 @@ mod tests {
                 model_number: Some("P3301".to_string()),
                 model_url: Some("http://www.axis.com/".to_string()),
                 serial_number: Some("00408CA45086".to_string()),
-                unique_device_name: "uuid:Upnp-BasicDevice-1_0-00408CA45086".to_string(),
+                unique_device_name: Udn::from_str("uuid:Upnp-BasicDevice-1_0-00408CA45086")
+                    .unwrap(),
                 upc: None,
                 icon_list: vec![],
                 service_list: vec![Service {
                     service_type: TypeID::new_service_with_domain(
                         "axis-com".to_string(),
                         "BasicService".to_string(),
-                        "1".to_string(),
+                        1,
                     ),
-                    service_id: "urn:axis-com:serviceId:BasicServiceId".to_string(),
+                    service_id: ServiceId::from_str("urn:axis-com:serviceId:BasicServiceId")
+                        .unwrap(),
                     scpd_url: "/scpd_basic.xml".to_string(),
                     control_url: "/upnp/control/BasicServiceId".to_string(),
                     event_sub_url: "/upnp/event/BasicServiceId".to_string(),
+                    extensions: vec![],
                 }],
                 device_list: vec![],
                 presentation_url: Some("http://10.59.104.28:80/".to_string()),
+                extensions: vec![],
             },
         };
         println!("\n{:#?}\n", device);
@@ -339,4 +796,172 @@ mod tests {
 
         assert_eq!(xml, EX_DEVICE);
     }
+
+    #[test]
+    fn test_xml_parse() {
+        let device = DeviceRoot::from_str(EX_DEVICE).unwrap();
+        assert_eq!(device.spec_version, SpecVersion::V10);
+        assert_eq!(device.url_base, "http://10.59.104.28:49152/");
+        assert_eq!(device.device.friendly_name, "AXIS P3301 - 00408CA45086");
+        assert_eq!(
+            device.device.device_type.to_string(),
+            "urn:schemas-upnp-org:device:Basic:1"
+        );
+        assert_eq!(device.device.upc, None);
+        assert_eq!(device.device.service_list.len(), 1);
+        assert_eq!(
+            device.device.service_list[0].service_type.to_string(),
+            "urn:axis-com:service:BasicService:1"
+        );
+
+        let written = to_writer(&device, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_DEVICE);
+    }
+
+    #[test]
+    fn test_extensions_round_trip() {
+        const EXTENDED: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\" xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test</UDN><dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC><serviceList><service><serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType><serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId><SCPDURL>/cd.xml</SCPDURL><controlURL>/cd/control</controlURL><eventSubURL>/cd/event</eventSubURL><X_vendorFlag a=\"1\">on</X_vendorFlag></service></serviceList></device></root>";
+        let root = DeviceRoot::from_str(EXTENDED).unwrap();
+        assert_eq!(root.device.extensions.len(), 1);
+        assert_eq!(root.device.extensions[0].element().text, "DMS-1.50");
+        let service = &root.device.service_list[0];
+        assert_eq!(service.extensions.len(), 1);
+        assert_eq!(service.extensions[0].element().local_name(), "X_vendorFlag");
+
+        let written = to_writer(&root, Vec::new()).unwrap();
+        let written = from_utf8(&written).unwrap();
+        assert!(written.contains("<dlna:X_DLNADOC xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\">DMS-1.50</dlna:X_DLNADOC>"));
+        let reread = DeviceRoot::from_str(written).unwrap();
+        let extension = reread.device.extensions[0].element();
+        assert_eq!(extension.name, "dlna:X_DLNADOC");
+        assert_eq!(
+            extension.namespace.as_deref(),
+            Some("urn:schemas-dlna-org:device-1-0")
+        );
+        let extension = reread.device.service_list[0].extensions[0].element();
+        assert_eq!(extension.attribute("a"), Some("1"));
+        assert_eq!(extension.text, "on");
+    }
+
+    #[test]
+    fn test_versioned_serialize() {
+        let mut device = DeviceRoot::from_str(EX_DEVICE).unwrap();
+        device.config_id = Some(42);
+        let written = to_writer(&device, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_DEVICE);
+
+        device.spec_version = SpecVersion::V20;
+        let written = to_writer(&device, Vec::new()).unwrap();
+        let xml = from_utf8(&written).unwrap();
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\" configId=\"42\"><specVersion><major>2</major><minor>0</minor></specVersion><device>"
+        ));
+        assert!(!xml.contains(XML_ELEM_URL_BASE));
+
+        let parsed = DeviceRoot::from_str(xml).unwrap();
+        assert_eq!(parsed.config_id, Some(42));
+        assert_eq!(parsed.url_base, "");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut device = DeviceRoot::from_str(EX_DEVICE).unwrap();
+        assert!(device.validate(SpecVersion::V10).is_empty());
+
+        device.spec_version = SpecVersion::V20;
+        device.device.upc = Some("12345".to_string());
+        device.device.device_list.push(device.device.clone());
+        let violations: Vec<String> = device
+            .validate(SpecVersion::V20)
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "root/@configId: required in version 2.0",
+                "root/URLBase: not permitted in version 2.0 and will not be written",
+                "root/device/UPC: must be a 12-digit, all-numeric, code",
+                "root/device/deviceList/device[0]/UDN: uuid:Upnp-BasicDevice-1_0-00408CA45086 is not unique",
+                "root/device/deviceList/device[0]/UPC: must be a 12-digit, all-numeric, code",
+            ]
+        );
+    }
+
+    fn icon(mime_type: &str, size: u16, depth: u16, url: &str) -> Icon {
+        Icon {
+            mime_type: mime_type.to_string(),
+            width: size,
+            height: size,
+            depth,
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_best_icon() {
+        let mut device = DeviceRoot::from_str(EX_DEVICE).unwrap().device;
+        assert!(device.best_icon(48, 48, 24).is_none());
+
+        device.icon_list = vec![
+            icon("image/png", 120, 24, "/large.png"),
+            icon("image/png", 48, 8, "/medium-8.png"),
+            icon("image/png", 48, 24, "/medium-24.png"),
+            icon("image/png", 32, 24, "/small.png"),
+        ];
+        assert_eq!(device.best_icon(48, 48, 24).unwrap().url, "/medium-24.png");
+        assert_eq!(device.best_icon(40, 40, 32).unwrap().url, "/medium-24.png");
+        assert_eq!(device.best_icon(64, 64, 24).unwrap().url, "/large.png");
+        assert_eq!(device.best_icon(256, 256, 24).unwrap().url, "/large.png");
+        assert_eq!(device.best_icon(16, 16, 24).unwrap().url, "/small.png");
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_fetch_icons() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = URL::from_str(&format!(
+            "http://{}/description.xml",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 4\r\nConnection: close\r\n\r\nPNG!"
+                )
+                .unwrap();
+            }
+        });
+
+        let mut device = DeviceRoot::from_str(EX_DEVICE).unwrap().device;
+        device.icon_list = vec![icon("image/png", 48, 24, "icons/48.png")];
+        let icons = device.fetch_icons(&base).unwrap();
+        assert_eq!(icons.len(), 1);
+        assert_eq!(icons[0].1, b"PNG!");
+
+        device.icon_list = vec![icon("image/jpeg", 48, 24, "/icons/48.jpg")];
+        assert!(device.fetch_icons(&base).is_err());
+        server.join().unwrap();
+
+        assert_eq!(
+            device.presentation_url(&base).unwrap().to_string(),
+            "http://10.59.104.28/"
+        );
+    }
 }
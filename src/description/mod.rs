@@ -1,39 +1,149 @@
 /*!
 This module implements the UPnP device and service descriptions using the UPnP template language.
 */
+use crate::common::http::{fetch_with, HttpClient};
+use crate::common::uri::URL;
+use crate::common::xml::read::Element;
+use crate::description::device::DeviceRoot;
 use crate::discovery::search::SearchTarget;
-use crate::error::{invalid_value_for_type, unsupported_operation, Error};
-use crate::UPNP_DOMAIN;
+use crate::error::{
+    invalid_field_value, invalid_value_for_type, unsupported_operation, Error, MessageFormatError,
+};
+use crate::syntax::{XML_ATTR_CONFIG_ID, XML_ELEM_MAJOR, XML_ELEM_MINOR, XML_ELEM_SPEC_VERSION};
+use crate::{SpecVersion, UPNP_DOMAIN};
+use std::convert::TryFrom;
 use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::scope;
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
+///
+/// The type of a device or service, as the URN `urn:{domain}:{device|service}:{name}:{version}`.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TypeID {
     Device {
         domain: String,
         name: String,
-        version: String,
+        version: u32,
     },
     Service {
         domain: String,
         name: String,
-        version: String,
+        version: u32,
     },
 }
 
+///
+/// The identifier of a service instance within a device, as the URN
+/// `urn:{domain}:serviceId:{id}`. Standard services use the domain `upnp-org`, vendor services use
+/// the vendor's domain name with periods replaced by hyphens.
+///
+/// Parsing only requires the URN structure, as many devices in the wild use identifiers that are
+/// longer, or contain characters, that the UDA does not allow; use
+/// [`ServiceId::is_conformant`] where strict validation is required.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ServiceId {
+    domain: String,
+    id: String,
+}
+
+///
+/// A requirement on the version of a device or service type, used to search for types that
+/// implement any of a range of versions.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VersionReq {
+    /// Only this version.
+    Exactly(u32),
+    /// This version or any later version, the UPnP rule for a type in a search target.
+    AtLeast(u32),
+    /// Any version from the first to the second, inclusive.
+    Between(u32, u32),
+}
+
+///
+/// A single rule violation found when validating a description, or a message, against a version
+/// of the specification.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// The path to the offending element, or attribute, e.g. `root/device/deviceList/device[0]/UDN`;
+    /// for a message this is the name of the offending header.
+    pub path: String,
+    pub message: String,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Fetch and parse the device descriptions at all of the `locations`, with at most
+/// `max_concurrency` requests in flight at any time. The results are returned in the same order
+/// as `locations`, a failure to fetch or parse one description does not affect the others.
+///
+#[cfg(feature = "net")]
+pub fn fetch_all(
+    locations: &[URL],
+    max_concurrency: usize,
+) -> Vec<(URL, Result<DeviceRoot, Error>)> {
+    fetch_all_with(locations, max_concurrency, default_client().as_ref())
+}
+
+///
+/// As `fetch_all`, but using the provided client for all requests.
+///
+pub fn fetch_all_with(
+    locations: &[URL],
+    max_concurrency: usize,
+    client: &dyn HttpClient,
+) -> Vec<(URL, Result<DeviceRoot, Error>)> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<DeviceRoot, Error>>>> =
+        Mutex::new(locations.iter().map(|_| None).collect());
+    scope(|scope| {
+        for _ in 0..max_concurrency.clamp(1, locations.len().max(1)) {
+            let _ = scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                match locations.get(index) {
+                    Some(location) => {
+                        let result = fetch_with(location.to_string(), client);
+                        results.lock().unwrap()[index] = Some(result);
+                    }
+                    None => break,
+                }
+            });
+        }
+    });
+    locations
+        .iter()
+        .cloned()
+        .zip(
+            results
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(Option::unwrap),
+        )
+        .collect()
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
 impl TypeID {
-    pub fn new_device(name: String, version: String) -> Self {
+    pub fn new_device(name: String, version: u32) -> Self {
         TypeID::Device {
             domain: UPNP_DOMAIN.to_string(),
             name,
@@ -41,7 +151,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_device_with_domain(domain: String, name: String, version: String) -> Self {
+    pub fn new_device_with_domain(domain: String, name: String, version: u32) -> Self {
         TypeID::Device {
             domain,
             name,
@@ -49,7 +159,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_service(name: String, version: String) -> Self {
+    pub fn new_service(name: String, version: u32) -> Self {
         TypeID::Service {
             domain: UPNP_DOMAIN.to_string(),
             name,
@@ -57,7 +167,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_service_with_domain(domain: String, name: String, version: String) -> Self {
+    pub fn new_service_with_domain(domain: String, name: String, version: u32) -> Self {
         TypeID::Service {
             domain,
             name,
@@ -93,10 +203,100 @@ impl TypeID {
         }
     }
 
+    ///
+    /// Returns `true` if a device or service implementing this type also satisfies a request for
+    /// the `requested` type; that is, the kind, domain, and name are the same and this version is
+    /// greater than or equal to the requested version. Later versions of a standard type are
+    /// required to be backwards compatible with earlier versions.
+    ///
+    pub fn is_compatible_with(&self, requested: &TypeID) -> bool {
+        match (self, requested) {
+            (
+                TypeID::Device {
+                    domain,
+                    name,
+                    version,
+                },
+                TypeID::Device {
+                    domain: requested_domain,
+                    name: requested_name,
+                    version: requested_version,
+                },
+            )
+            | (
+                TypeID::Service {
+                    domain,
+                    name,
+                    version,
+                },
+                TypeID::Service {
+                    domain: requested_domain,
+                    name: requested_name,
+                    version: requested_version,
+                },
+            ) => {
+                domain == requested_domain && name == requested_name && version >= requested_version
+            }
+            _ => false,
+        }
+    }
+
+    ///
+    /// Returns `true` if this type has the same kind, domain, and name as `other`, regardless of
+    /// version.
+    ///
+    pub fn is_same_type_as(&self, other: &TypeID) -> bool {
+        match (self, other) {
+            (
+                TypeID::Device { domain, name, .. },
+                TypeID::Device {
+                    domain: other_domain,
+                    name: other_name,
+                    ..
+                },
+            )
+            | (
+                TypeID::Service { domain, name, .. },
+                TypeID::Service {
+                    domain: other_domain,
+                    name: other_name,
+                    ..
+                },
+            ) => domain == other_domain && name == other_name,
+            _ => false,
+        }
+    }
+
+    pub fn domain(&self) -> &str {
+        match self {
+            TypeID::Device { domain, .. } | TypeID::Service { domain, .. } => domain,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TypeID::Device { name, .. } | TypeID::Service { name, .. } => name,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            TypeID::Device { version, .. } | TypeID::Service { version, .. } => *version,
+        }
+    }
+
+    pub fn is_device(&self) -> bool {
+        matches!(self, TypeID::Device { .. })
+    }
+
+    pub fn is_service(&self) -> bool {
+        matches!(self, TypeID::Service { .. })
+    }
+
     pub fn default_id(&self) -> String {
         match self {
             TypeID::Device { name, .. } => format!("urn:upnp-org:deviceId:{}", name),
-            TypeID::Service { name, .. } => format!("urn:upnp-org:serviceId:{}", name),
+            TypeID::Service { name, .. } => ServiceId::new(name.clone()).to_string(),
         }
     }
 }
@@ -118,6 +318,281 @@ impl Display for TypeID {
     }
 }
 
+impl FromStr for TypeID {
+    type Err = Error;
+
+    ///
+    /// Parse a complete device or service type URN, for example
+    /// `urn:schemas-upnp-org:service:ContentDirectory:2`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TypeID::try_from(SearchTarget::from_str(s)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TypeID {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<&SearchTarget> for TypeID {
+    type Error = Error;
+
+    ///
+    /// Convert any of the device or service type search targets, in the UPnP or another domain,
+    /// into a type. The versioned targets convert to the type with the minimum version required,
+    /// as sent on the wire. All other targets are rejected.
+    ///
+    fn try_from(search_target: &SearchTarget) -> Result<Self, Self::Error> {
+        let search_target = search_target.unversioned();
+        match search_target {
+            SearchTarget::DeviceType(_) | SearchTarget::DomainDeviceType(_, _) => {
+                TypeID::device_from(search_target)
+            }
+            SearchTarget::ServiceType(_) | SearchTarget::DomainServiceType(_, _) => {
+                TypeID::service_from(search_target)
+            }
+            _ => invalid_value_for_type("TypeID", search_target.to_string()).into(),
+        }
+    }
+}
+
+impl TryFrom<SearchTarget> for TypeID {
+    type Error = Error;
+
+    fn try_from(search_target: SearchTarget) -> Result<Self, Self::Error> {
+        TypeID::try_from(&search_target)
+    }
+}
+
+impl From<TypeID> for SearchTarget {
+    fn from(type_id: TypeID) -> Self {
+        SearchTarget::from(&type_id)
+    }
+}
+
+impl From<&TypeID> for SearchTarget {
+    fn from(type_id: &TypeID) -> Self {
+        match type_id {
+            TypeID::Device {
+                domain,
+                name,
+                version,
+            } => {
+                if domain == UPNP_DOMAIN {
+                    SearchTarget::DeviceType(format!("{}:{}", name, version))
+                } else {
+                    SearchTarget::DomainDeviceType(domain.clone(), format!("{}:{}", name, version))
+                }
+            }
+            TypeID::Service {
+                domain,
+                name,
+                version,
+            } => {
+                if domain == UPNP_DOMAIN {
+                    SearchTarget::ServiceType(format!("{}:{}", name, version))
+                } else {
+                    SearchTarget::DomainServiceType(domain.clone(), format!("{}:{}", name, version))
+                }
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const UPNP_ID_DOMAIN: &str = "upnp-org";
+const SERVICE_ID_KIND: &str = "serviceId";
+const SERVICE_ID_MAX_LENGTH: usize = 64;
+
+impl Display for ServiceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "urn:{}:{}:{}", self.domain, SERVICE_ID_KIND, self.id)
+    }
+}
+
+impl FromStr for ServiceId {
+    type Err = MessageFormatError;
+
+    ///
+    /// Parse a service identifier URN, for example `urn:upnp-org:serviceId:ContentDirectory`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .trim()
+            .strip_prefix("urn:")
+            .and_then(|rest| rest.split_once(':'))
+            .and_then(|(domain, rest)| Some((domain, rest.split_once(':')?)));
+        match parts {
+            Some((domain, (SERVICE_ID_KIND, id))) if !domain.is_empty() && !id.is_empty() => Ok(
+                ServiceId::new_with_domain(domain.to_string(), id.to_string()),
+            ),
+            _ => invalid_value_for_type("ServiceId", s).into(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ServiceId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl ServiceId {
+    ///
+    /// A service identifier in the UPnP Forum domain, `upnp-org`.
+    ///
+    pub fn new(id: String) -> Self {
+        Self::new_with_domain(UPNP_ID_DOMAIN.to_string(), id)
+    }
+
+    pub fn new_with_domain(domain: String, id: String) -> Self {
+        Self { domain, id }
+    }
+
+    ///
+    /// The identifier for the first instance of `service_type` in a device, named for the type.
+    /// The UPnP Forum domain `schemas-upnp-org` is written as `upnp-org`, vendor domains are used
+    /// as-is. For standard types this is the same value as `TypeID::default_id`.
+    ///
+    pub fn for_type(service_type: &TypeID) -> Self {
+        Self::for_type_named(service_type, service_type.name())
+    }
+
+    ///
+    /// As [`ServiceId::for_type`], with the name `id`, used to distinguish further instances of
+    /// the same service type in a device.
+    ///
+    pub fn for_type_named(service_type: &TypeID, id: &str) -> Self {
+        let domain = if service_type.domain() == UPNP_DOMAIN {
+            UPNP_ID_DOMAIN
+        } else {
+            service_type.domain()
+        };
+        Self::new_with_domain(domain.to_string(), id.to_string())
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    ///
+    /// Returns `true` if this identifier is in the UPnP Forum domain.
+    ///
+    pub fn is_standard(&self) -> bool {
+        self.domain == UPNP_ID_DOMAIN
+    }
+
+    ///
+    /// Returns `true` if this identifier meets the UDA requirements; the domain contains no periods,
+    /// and the identifier is at most 64 characters with no colons or whitespace.
+    ///
+    pub fn is_conformant(&self) -> bool {
+        !self.domain.contains('.')
+            && !self.domain.contains(char::is_whitespace)
+            && self.id.chars().count() <= SERVICE_ID_MAX_LENGTH
+            && !self.id.contains(|c: char| c == ':' || c.is_whitespace())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            VersionReq::Exactly(version) => write!(f, "={}", version),
+            VersionReq::AtLeast(version) => write!(f, ">={}", version),
+            VersionReq::Between(minimum, maximum) => write!(f, "{}..={}", minimum, maximum),
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = Error;
+
+    ///
+    /// Parse a requirement of the form `=2`, `>=2`, or `2..=4`; a bare version, `2`, is the same
+    /// as `>=2`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| Error::from(invalid_value_for_type("VersionReq", s)))
+        };
+        if let Some(version) = s.strip_prefix(">=") {
+            Ok(VersionReq::AtLeast(number(version)?))
+        } else if let Some(version) = s.strip_prefix('=') {
+            Ok(VersionReq::Exactly(number(version)?))
+        } else if let Some((minimum, maximum)) = s.split_once("..=") {
+            let (minimum, maximum) = (number(minimum)?, number(maximum)?);
+            if minimum <= maximum {
+                Ok(VersionReq::Between(minimum, maximum))
+            } else {
+                invalid_value_for_type("VersionReq", s).into()
+            }
+        } else {
+            Ok(VersionReq::AtLeast(number(s)?))
+        }
+    }
+}
+
+impl VersionReq {
+    ///
+    /// Returns `true` if `version` satisfies this requirement.
+    ///
+    pub fn matches(&self, version: u32) -> bool {
+        match self {
+            VersionReq::Exactly(required) => version == *required,
+            VersionReq::AtLeast(minimum) => version >= *minimum,
+            VersionReq::Between(minimum, maximum) => (*minimum..=*maximum).contains(&version),
+        }
+    }
+
+    ///
+    /// The lowest version that satisfies this requirement.
+    ///
+    pub fn minimum(&self) -> u32 {
+        match self {
+            VersionReq::Exactly(version)
+            | VersionReq::AtLeast(version)
+            | VersionReq::Between(version, _) => *version,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Violation {
+    pub(crate) fn new<S1: Into<String>, S2: Into<String>>(path: S1, message: S2) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
@@ -126,20 +601,193 @@ impl Display for TypeID {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn split_type_and_version(type_name: String) -> Result<(String, String), Error> {
-    match type_name.find(':') {
-        None => invalid_value_for_type("type_and_version", type_name).into(),
-        Some(sep) => {
-            let (name, ver) = type_name.split_at(sep);
-            Ok((name.to_string(), ver.to_string()))
+fn split_type_and_version(type_name: String) -> Result<(String, u32), Error> {
+    match type_name
+        .rsplit_once(':')
+        .and_then(|(name, version)| Some((name, version.trim().parse::<u32>().ok()?)))
+    {
+        Some((name, version)) if !name.is_empty() => Ok((name.to_string(), version)),
+        _ => invalid_value_for_type("type_and_version", type_name).into(),
+    }
+}
+
+pub(crate) fn spec_version_from(element: &Element) -> Result<SpecVersion, Error> {
+    match element.child(XML_ELEM_SPEC_VERSION) {
+        None => Ok(SpecVersion::V10),
+        Some(spec_version) => {
+            let version = format!(
+                "{}.{}",
+                spec_version.required_child_text(XML_ELEM_MAJOR)?.trim(),
+                spec_version.required_child_text(XML_ELEM_MINOR)?.trim()
+            );
+            SpecVersion::from_str(&version)
+                .map_err(|_| invalid_field_value(XML_ELEM_SPEC_VERSION, version).into())
         }
     }
 }
 
+pub(crate) fn config_id_from(element: &Element) -> Result<Option<u32>, Error> {
+    match element.attribute(XML_ATTR_CONFIG_ID) {
+        None => Ok(None),
+        Some(config_id) => u32::from_str(config_id.trim())
+            .map(Some)
+            .map_err(|_| invalid_field_value(XML_ATTR_CONFIG_ID, config_id).into()),
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
 
+pub mod config;
+
 pub mod device;
 
+pub mod scaffold;
+
 pub mod service;
+
+pub mod well_known;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "net")]
+    use crate::emulation::{from_device_with, Options};
+
+    #[cfg(feature = "net")]
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN></device></root>";
+
+    #[test]
+    fn test_type_compatibility() {
+        let v1 = TypeID::new_device("MediaServer".to_string(), 1);
+        let v2 = TypeID::new_device("MediaServer".to_string(), 2);
+        let other = TypeID::new_service("MediaServer".to_string(), 1);
+        assert!(v2.is_compatible_with(&v1));
+        assert!(!v1.is_compatible_with(&v2));
+        assert!(!other.is_compatible_with(&v1));
+        assert!(v1.is_same_type_as(&v2));
+        assert_eq!(v2.version(), 2);
+    }
+
+    #[test]
+    fn test_type_from_str() {
+        let type_id = TypeID::from_str("urn:schemas-upnp-org:service:ContentDirectory:2").unwrap();
+        assert_eq!(
+            type_id,
+            TypeID::new_service("ContentDirectory".to_string(), 2)
+        );
+        assert_eq!(
+            type_id.to_string(),
+            "urn:schemas-upnp-org:service:ContentDirectory:2"
+        );
+        let type_id = TypeID::from_str("urn:example-com:device:Widget:1").unwrap();
+        assert!(type_id.is_device());
+        assert_eq!(type_id.domain(), "example-com");
+        assert_eq!(type_id.name(), "Widget");
+        assert!(TypeID::from_str("urn:schemas-upnp-org:device:Widget").is_err());
+        assert!(TypeID::from_str("urn:schemas-upnp-org:device:Widget:one").is_err());
+        assert!(TypeID::from_str("upnp:rootdevice").is_err());
+    }
+
+    #[test]
+    fn test_service_id() {
+        let service_id = ServiceId::from_str("urn:upnp-org:serviceId:ContentDirectory").unwrap();
+        assert!(service_id.is_standard());
+        assert!(service_id.is_conformant());
+        assert_eq!(service_id.id(), "ContentDirectory");
+        assert_eq!(
+            service_id,
+            ServiceId::for_type(&TypeID::new_service("ContentDirectory".to_string(), 1))
+        );
+        assert_eq!(
+            service_id.to_string(),
+            TypeID::new_service("ContentDirectory".to_string(), 1).default_id()
+        );
+
+        let vendor = ServiceId::for_type(&TypeID::new_service_with_domain(
+            "example-com".to_string(),
+            "Dimmer".to_string(),
+            1,
+        ));
+        assert_eq!(vendor.to_string(), "urn:example-com:serviceId:Dimmer");
+        assert!(!vendor.is_standard());
+
+        let legacy = ServiceId::from_str(
+            "urn:upnp-org:serviceId:urn:schemas-upnp-org:service:ConnectionManager",
+        )
+        .unwrap();
+        assert!(!legacy.is_conformant());
+        assert!(ServiceId::from_str("urn:upnp-org:service:ContentDirectory").is_err());
+        assert!(ServiceId::from_str("urn:upnp-org:serviceId:").is_err());
+        assert!(ServiceId::from_str("ContentDirectory").is_err());
+    }
+
+    #[test]
+    fn test_search_target_round_trip() {
+        for target in [
+            SearchTarget::DeviceType("MediaServer:3".to_string()),
+            SearchTarget::ServiceType("ContentDirectory:1".to_string()),
+            SearchTarget::DomainDeviceType("example-com".to_string(), "Widget:2".to_string()),
+            SearchTarget::DomainServiceType("example-com".to_string(), "Gadget:1".to_string()),
+        ] {
+            let type_id = TypeID::try_from(&target).unwrap();
+            assert_eq!(SearchTarget::from(&type_id), target);
+        }
+        assert_eq!(
+            TypeID::try_from(SearchTarget::DeviceTypeVersioned {
+                name: "MediaServer".to_string(),
+                version: VersionReq::Between(2, 4),
+            })
+            .unwrap(),
+            TypeID::new_device("MediaServer".to_string(), 2)
+        );
+        assert!(TypeID::try_from(SearchTarget::RootDevice).is_err());
+    }
+
+    #[test]
+    fn test_version_req() {
+        assert_eq!(VersionReq::from_str("2").unwrap(), VersionReq::AtLeast(2));
+        assert_eq!(VersionReq::from_str("=3").unwrap(), VersionReq::Exactly(3));
+        assert_eq!(
+            VersionReq::from_str("2..=4").unwrap(),
+            VersionReq::Between(2, 4)
+        );
+        assert!(VersionReq::from_str("4..=2").is_err());
+        assert!(VersionReq::Between(2, 4).matches(3));
+        assert!(!VersionReq::Between(2, 4).matches(5));
+        assert_eq!(VersionReq::AtLeast(2).to_string(), ">=2");
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_fetch_all_keeps_order_and_failures() {
+        let devices: Vec<_> = (0..3)
+            .map(|_| {
+                from_device_with(
+                    DeviceRoot::from_str(DESCRIPTION).unwrap(),
+                    Default::default(),
+                    Options {
+                        answer_search: false,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut locations: Vec<URL> = devices.iter().map(|device| device.location()).collect();
+        locations.insert(1, URL::from_str("http://127.0.0.1:1/missing.xml").unwrap());
+
+        let results = fetch_all(&locations, 2);
+        assert_eq!(results.len(), 4);
+        for (index, (location, result)) in results.iter().enumerate() {
+            assert_eq!(location, &locations[index]);
+            assert_eq!(result.is_err(), index == 1);
+        }
+        assert!(fetch_all(&[], 4).is_empty());
+    }
+}
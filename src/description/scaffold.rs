@@ -0,0 +1,313 @@
+/*!
+This module provides a scaffold for the description of a new device implementation. Given the
+type of the device, and the type and service description of each service it implements, a
+[`Scaffold`] produces a minimal [`DeviceRoot`] that passes validation, with a consistent service
+identifier and set of URLs for each service, along with the service descriptions keyed by service
+identifier as expected by [`from_device`](../../emulation/fn.from_device.html).
+
+For a service of type `urn:schemas-upnp-org:service:SwitchPower:1` the service identifier is
+`urn:upnp-org:serviceId:SwitchPower`, and the `SCPDURL`, `controlURL`, and `eventSubURL` are
+`/SwitchPower/scpd.xml`, `/SwitchPower/control`, and `/SwitchPower/event` respectively. Where a
+device implements more than one instance of a service type, later instances are numbered, as in
+`SwitchPower2`.
+
+# Example
+
+```rust,no_run
+use upnp_rs::description::scaffold::Scaffold;
+use upnp_rs::description::service::Spcd;
+use upnp_rs::description::well_known::{device_type, service_type};
+use upnp_rs::emulation::from_device;
+
+# fn switch_power() -> Spcd { unimplemented!() }
+let (root, services) = Scaffold::new(device_type("BinaryLight", 1), "Light")
+    .with_service(service_type("SwitchPower", 1), switch_power())
+    .build();
+let device = from_device(root, services).unwrap();
+```
+*/
+
+use crate::common::udn::Udn;
+use crate::description::device::{Device, DeviceRoot, Service};
+use crate::description::service::Spcd;
+use crate::description::{ServiceId, TypeID};
+use crate::SpecVersion;
+use std::collections::HashMap;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Collects the values required to produce the description of a device and its services.
+///
+#[derive(Clone, Debug)]
+pub struct Scaffold {
+    spec_version: SpecVersion,
+    device_type: TypeID,
+    friendly_name: String,
+    manufacturer: String,
+    model_name: String,
+    unique_device_name: Udn,
+    services: Vec<(Service, Spcd)>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The service identifier for the first instance of `service_type`; the UPnP Forum domain
+/// `schemas-upnp-org` is written as `upnp-org`, vendor domains are used as-is.
+///
+pub fn service_id_for(service_type: &TypeID) -> ServiceId {
+    ServiceId::for_type(service_type)
+}
+
+///
+/// A service entry for the first instance of `service_type`, with the default service
+/// identifier and URLs.
+///
+pub fn service_for(service_type: &TypeID) -> Service {
+    service_named(service_type, service_type.name())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Scaffold {
+    ///
+    /// A scaffold for a device of `device_type`, with a new random UDN, no services, and
+    /// describing itself using version 1.1 of the specification.
+    ///
+    pub fn new(device_type: TypeID, friendly_name: &str) -> Self {
+        Self {
+            spec_version: SpecVersion::V11,
+            device_type,
+            friendly_name: friendly_name.to_string(),
+            manufacturer: env!("CARGO_PKG_NAME").to_string(),
+            model_name: friendly_name.to_string(),
+            unique_device_name: Udn::new_v4(),
+            services: Default::default(),
+        }
+    }
+
+    ///
+    /// Set the specification version used by the device and service descriptions.
+    ///
+    pub fn with_spec_version(self, spec_version: SpecVersion) -> Self {
+        Self {
+            spec_version,
+            ..self
+        }
+    }
+
+    ///
+    /// Set the manufacturer, default: the name of this crate.
+    ///
+    pub fn with_manufacturer(self, manufacturer: &str) -> Self {
+        Self {
+            manufacturer: manufacturer.to_string(),
+            ..self
+        }
+    }
+
+    ///
+    /// Set the model name, default: the friendly name.
+    ///
+    pub fn with_model_name(self, model_name: &str) -> Self {
+        Self {
+            model_name: model_name.to_string(),
+            ..self
+        }
+    }
+
+    ///
+    /// Set the UDN, rather than using a new random value; a device should use the same UDN each
+    /// time it starts.
+    ///
+    pub fn with_udn(self, unique_device_name: Udn) -> Self {
+        Self {
+            unique_device_name,
+            ..self
+        }
+    }
+
+    ///
+    /// Add an instance of the service `service_type`, described by `scpd`.
+    ///
+    pub fn with_service(mut self, service_type: TypeID, scpd: Spcd) -> Self {
+        let instance = self
+            .services
+            .iter()
+            .filter(|(service, _)| service.service_type.is_same_type_as(&service_type))
+            .count();
+        let name = if instance == 0 {
+            service_type.name().to_string()
+        } else {
+            format!("{}{}", service_type.name(), instance + 1)
+        };
+        let service = service_named(&service_type, &name);
+        self.services.push((service, scpd));
+        self
+    }
+
+    ///
+    /// The service entries added so far, in the order added.
+    ///
+    pub fn services(&self) -> impl Iterator<Item = &Service> {
+        self.services.iter().map(|(service, _)| service)
+    }
+
+    ///
+    /// The device description.
+    ///
+    pub fn device_root(&self) -> DeviceRoot {
+        DeviceRoot {
+            spec_version: self.spec_version,
+            config_id: self.config_id(),
+            url_base: String::new(),
+            device: Device {
+                device_type: self.device_type.clone(),
+                friendly_name: self.friendly_name.clone(),
+                manufacturer: self.manufacturer.clone(),
+                manufacturer_url: None,
+                model_description: None,
+                model_name: self.model_name.clone(),
+                model_number: None,
+                model_url: None,
+                serial_number: None,
+                unique_device_name: self.unique_device_name.clone(),
+                upc: None,
+                icon_list: Vec::new(),
+                service_list: self.services().cloned().collect(),
+                device_list: Vec::new(),
+                presentation_url: None,
+                extensions: Vec::new(),
+            },
+        }
+    }
+
+    ///
+    /// The service descriptions keyed by service identifier; each uses the specification version
+    /// and configuration number of the device.
+    ///
+    pub fn service_descriptions(&self) -> HashMap<ServiceId, Spcd> {
+        self.services
+            .iter()
+            .map(|(service, scpd)| {
+                (
+                    service.service_id.clone(),
+                    Spcd {
+                        spec_version: self.spec_version,
+                        config_id: self.config_id(),
+                        ..scpd.clone()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    ///
+    /// The device description and the service descriptions, as taken by
+    /// [`from_device`](../../emulation/fn.from_device.html).
+    ///
+    pub fn build(self) -> (DeviceRoot, HashMap<ServiceId, Spcd>) {
+        (self.device_root(), self.service_descriptions())
+    }
+
+    fn config_id(&self) -> Option<u32> {
+        if self.spec_version >= SpecVersion::V11 {
+            Some(1)
+        } else {
+            None
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn service_named(service_type: &TypeID, name: &str) -> Service {
+    Service {
+        service_type: service_type.clone(),
+        service_id: ServiceId::for_type_named(service_type, name),
+        scpd_url: format!("/{}/scpd.xml", name),
+        control_url: format!("/{}/control", name),
+        event_sub_url: format!("/{}/event", name),
+        extensions: Vec::new(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::description::well_known::{device_type, service_type};
+
+    fn spcd() -> Spcd {
+        Spcd {
+            spec_version: SpecVersion::V10,
+            config_id: None,
+            action_list: Vec::new(),
+            service_state_table: Vec::new(),
+            extensions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_scaffold() {
+        let (root, services) = Scaffold::new(device_type("BinaryLight", 1), "Light")
+            .with_service(service_type("SwitchPower", 1), spcd())
+            .with_service(service_type("SwitchPower", 1), spcd())
+            .with_service(
+                TypeID::new_service_with_domain("example-com".to_string(), "Dimmer".to_string(), 1),
+                spcd(),
+            )
+            .build();
+        assert!(root.validate(SpecVersion::V11).is_empty());
+
+        let service_list = &root.device.service_list;
+        assert_eq!(service_list.len(), 3);
+        assert_eq!(
+            service_list[0].service_id.to_string(),
+            "urn:upnp-org:serviceId:SwitchPower"
+        );
+        assert_eq!(service_list[0].scpd_url, "/SwitchPower/scpd.xml");
+        assert_eq!(service_list[0].control_url, "/SwitchPower/control");
+        assert_eq!(service_list[0].event_sub_url, "/SwitchPower/event");
+        assert_eq!(
+            service_list[1].service_id.to_string(),
+            "urn:upnp-org:serviceId:SwitchPower2"
+        );
+        assert_eq!(service_list[1].control_url, "/SwitchPower2/control");
+        assert_eq!(
+            service_list[2].service_id.to_string(),
+            "urn:example-com:serviceId:Dimmer"
+        );
+
+        assert_eq!(services.len(), 3);
+        let scpd = &services[&ServiceId::new("SwitchPower".to_string())];
+        assert_eq!(scpd.spec_version, SpecVersion::V11);
+        assert_eq!(scpd.config_id, root.config_id);
+    }
+
+    #[test]
+    fn test_service_for() {
+        let service = service_for(&service_type("ContentDirectory", 1));
+        assert_eq!(service.service_id, service_id_for(&service.service_type));
+        assert_eq!(
+            service.service_id.to_string(),
+            service.service_type.default_id()
+        );
+    }
+}
@@ -2,30 +2,44 @@
 What's this all about then?
 */
 
+use crate::common::data_types::{boolean_from_str, validate_value};
+use crate::common::xml::read::{read_xml, Element, XmlFragment};
 use crate::common::xml::write::*;
-use crate::error::{xml_error, Error};
+use crate::description::{config_id_from, spec_version_from};
+use crate::error::{
+    invalid_argument, invalid_field_value, unsupported_operation, xml_error, Error,
+};
 use crate::syntax::{
-    XML_ATTR_SEND_EVENTS, XML_ELEM_ACTION, XML_ELEM_ACTION_LIST, XML_ELEM_ALLOWED_LIST,
-    XML_ELEM_ALLOWED_RANGE, XML_ELEM_ALLOWED_VALUE, XML_ELEM_ARGUMENT, XML_ELEM_ARGUMENT_LIST,
-    XML_ELEM_DATA_TYPE, XML_ELEM_DEFAULT_VALUE, XML_ELEM_DIRECTION, XML_ELEM_MAXIMUM,
-    XML_ELEM_MINIMUM, XML_ELEM_NAME, XML_ELEM_REL_STATE_VARIABLE, XML_ELEM_RETVAL, XML_ELEM_SPCD,
-    XML_ELEM_STATE_TABLE, XML_ELEM_STATE_VARIABLE, XML_ELEM_STEP, XML_NS_SERVICE,
+    XML_ATTR_MULTICAST, XML_ATTR_SEND_EVENTS, XML_ELEM_ACTION, XML_ELEM_ACTION_LIST,
+    XML_ELEM_ALLOWED_LIST, XML_ELEM_ALLOWED_RANGE, XML_ELEM_ALLOWED_VALUE, XML_ELEM_ARGUMENT,
+    XML_ELEM_ARGUMENT_LIST, XML_ELEM_DATA_TYPE, XML_ELEM_DEFAULT_VALUE, XML_ELEM_DIRECTION,
+    XML_ELEM_MAXIMUM, XML_ELEM_MAXIMUM_RATE, XML_ELEM_MINIMUM, XML_ELEM_MINIMUM_DELTA,
+    XML_ELEM_NAME, XML_ELEM_REL_STATE_VARIABLE, XML_ELEM_RETVAL, XML_ELEM_SPCD,
+    XML_ELEM_SPEC_VERSION, XML_ELEM_STATE_TABLE, XML_ELEM_STATE_VARIABLE, XML_ELEM_STEP,
+    XML_NS_SERVICE,
 };
 use crate::SpecVersion;
 use quick_xml::Writer;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter};
 use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Direction {
     In,
     Out,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Argument {
     pub name: String,
     pub direction: Direction,
@@ -34,12 +48,14 @@ pub struct Argument {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Action {
     pub name: String,
     pub argument_list: Vec<Argument>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AllowedValue {
     List {
         values: Vec<String>,
@@ -51,20 +67,76 @@ pub enum AllowedValue {
     },
 }
 
+///
+/// The moderation of events for an evented state variable; a variable may limit the rate at which
+/// it is evented, and a numeric variable may require a minimum change in value before it is
+/// evented.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Moderation {
+    /// The minimum interval between events, written in seconds as `maximumRate`.
+    pub maximum_rate: Option<Duration>,
+    /// The minimum change in value, since the last event, before the variable is evented again.
+    pub minimum_delta: Option<f64>,
+}
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StateVariable {
     pub send_events: bool,
+    /// Whether changes are also sent using multicast eventing, from version 1.1.
+    pub multicast: bool,
     pub name: String,
     pub data_type: String,
     pub default_value: Option<String>,
     pub allowed_values: Option<AllowedValue>,
+    pub moderation: Option<Moderation>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Spcd {
     pub spec_version: SpecVersion,
+    /// The configuration number, only written for version 1.1 and later.
+    pub config_id: Option<u32>,
     pub action_list: Vec<Action>,
     pub service_state_table: Vec<StateVariable>,
+    /// Elements not defined by the specification, such as vendor extensions.
+    pub extensions: Vec<XmlFragment>,
+}
+
+///
+/// Writes a service description incrementally, one action or state variable at a time, so that
+/// a large description need not be held in memory. Actions must all be written before the first
+/// state variable, and extensions after the last; the document is incomplete until
+/// [`SpcdWriter::finish`] is called.
+///
+/// ```rust
+/// use upnp_rs::description::service::{Direction, SpcdWriter, StateVariable};
+/// use upnp_rs::SpecVersion;
+///
+/// let mut writer = SpcdWriter::new(Vec::new(), SpecVersion::V11, Some(1)).unwrap();
+/// for index in 0..3 {
+///     writer
+///         .state_variable(&StateVariable {
+///             send_events: false,
+///             multicast: false,
+///             name: format!("A_ARG_TYPE_{}", index),
+///             data_type: "string".to_string(),
+///             default_value: None,
+///             allowed_values: None,
+///             moderation: None,
+///         })
+///         .unwrap();
+/// }
+/// let xml = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert!(xml.ends_with("</serviceStateTable></scpd>"));
+/// ```
+///
+pub struct SpcdWriter<T: Write> {
+    writer: Writer<T>,
+    section: SpcdSection,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -75,22 +147,321 @@ pub fn to_writer<T: Write>(root: &Spcd, writer: T) -> Result<T, Error> {
     root.write_root(writer)
 }
 
+pub fn to_writer_with<T: Write>(
+    root: &Spcd,
+    writer: T,
+    options: &WriteOptions,
+) -> Result<T, Error> {
+    root.write_root_with(writer, options)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl Spcd {
+    ///
+    /// Return the action with the given name, if this service defines one.
+    ///
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.action_list.iter().find(|action| action.name == name)
+    }
+
+    ///
+    /// Return the state variable with the given name, if this service defines one.
+    ///
+    pub fn state_variable(&self, name: &str) -> Option<&StateVariable> {
+        self.service_state_table
+            .iter()
+            .find(|variable| variable.name == name)
+    }
+}
+
+impl Action {
+    ///
+    /// Check the in `arguments` for a call to this action, defined in `spcd`, before the request
+    /// is sent. Every in argument must be present, no out or unknown arguments may be present,
+    /// and each value must match the data type and allowed values of its related state variable.
+    ///
+    pub fn validate_args(
+        &self,
+        spcd: &Spcd,
+        arguments: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        for name in arguments.keys() {
+            match self.argument_list.iter().find(|arg| &arg.name == name) {
+                None => {
+                    return invalid_argument(&self.name, name, "not defined for this action").into()
+                }
+                Some(Argument {
+                    direction: Direction::Out,
+                    ..
+                }) => return invalid_argument(&self.name, name, "is an out argument").into(),
+                _ => {}
+            }
+        }
+        for argument in &self.argument_list {
+            if let Direction::In = argument.direction {
+                match arguments.get(&argument.name) {
+                    None => {
+                        return invalid_argument(&self.name, &argument.name, "is missing").into()
+                    }
+                    Some(value) => match spcd.state_variable(&argument.related_state_variable) {
+                        None => {
+                            return invalid_argument(
+                                &self.name,
+                                &argument.name,
+                                format!(
+                                    "related state variable `{}` is not defined",
+                                    argument.related_state_variable
+                                ),
+                            )
+                            .into()
+                        }
+                        Some(variable) => variable.validate_value(value).map_err(|reason| {
+                            invalid_argument(&self.name, &argument.name, reason)
+                        })?,
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StateVariable {
+    fn validate_value(&self, value: &str) -> Result<(), String> {
+        validate_value(&self.data_type, value).map_err(|e| e.to_string())?;
+        match &self.allowed_values {
+            None => Ok(()),
+            Some(AllowedValue::List { values }) => {
+                if values.iter().any(|allowed| allowed == value) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "value `{}` is not in the allowed value list",
+                        value
+                    ))
+                }
+            }
+            Some(AllowedValue::Range {
+                minimum,
+                maximum,
+                step,
+            }) => {
+                let number = |s: &str| s.trim().parse::<f64>().ok();
+                match (number(value), number(minimum), number(maximum)) {
+                    (Some(value), Some(minimum), Some(maximum)) => {
+                        if value < minimum || value > maximum {
+                            return Err(format!(
+                                "value `{}` is not in the allowed range {}..={}",
+                                value, minimum, maximum
+                            ));
+                        }
+                        match step.as_deref().and_then(number) {
+                            Some(step) if step > 0.0 && ((value - minimum) % step) != 0.0 => {
+                                Err(format!(
+                                    "value `{}` is not a multiple of the step {} from {}",
+                                    value, step, minimum
+                                ))
+                            }
+                            _ => Ok(()),
+                        }
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Spcd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Spcd::try_from(&read_xml(s)?)
+    }
+}
+
+impl TryFrom<&Element> for Spcd {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        if element.local_name() != XML_ELEM_SPCD {
+            return invalid_field_value(XML_ELEM_SPCD, &element.name).into();
+        }
+        Ok(Spcd {
+            spec_version: spec_version_from(element)?,
+            config_id: config_id_from(element)?,
+            action_list: match element.child(XML_ELEM_ACTION_LIST) {
+                None => Default::default(),
+                Some(list) => list
+                    .children_named(XML_ELEM_ACTION)
+                    .map(Action::try_from)
+                    .collect::<Result<Vec<Action>, Error>>()?,
+            },
+            service_state_table: match element.child(XML_ELEM_STATE_TABLE) {
+                None => Default::default(),
+                Some(list) => list
+                    .children_named(XML_ELEM_STATE_VARIABLE)
+                    .map(StateVariable::try_from)
+                    .collect::<Result<Vec<StateVariable>, Error>>()?,
+            },
+            extensions: XmlFragment::unknown_children(
+                element,
+                &[
+                    XML_ELEM_SPEC_VERSION,
+                    XML_ELEM_ACTION_LIST,
+                    XML_ELEM_STATE_TABLE,
+                ],
+            ),
+        })
+    }
+}
+
+impl TryFrom<&Element> for Action {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        Ok(Action {
+            name: element.required_child_text(XML_ELEM_NAME)?.to_string(),
+            argument_list: match element.child(XML_ELEM_ARGUMENT_LIST) {
+                None => Default::default(),
+                Some(list) => list
+                    .children_named(XML_ELEM_ARGUMENT)
+                    .map(Argument::try_from)
+                    .collect::<Result<Vec<Argument>, Error>>()?,
+            },
+        })
+    }
+}
+
+impl TryFrom<&Element> for Argument {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        let direction = element.required_child_text(XML_ELEM_DIRECTION)?;
+        Ok(Argument {
+            name: element.required_child_text(XML_ELEM_NAME)?.to_string(),
+            direction: match direction.trim() {
+                "in" => Direction::In,
+                "out" => Direction::Out,
+                _ => return invalid_field_value(XML_ELEM_DIRECTION, direction).into(),
+            },
+            return_value: element.child(XML_ELEM_RETVAL).is_some(),
+            related_state_variable: element
+                .required_child_text(XML_ELEM_REL_STATE_VARIABLE)?
+                .to_string(),
+        })
+    }
+}
+
+impl TryFrom<&Element> for StateVariable {
+    type Error = Error;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        Ok(StateVariable {
+            send_events: match element.attribute(XML_ATTR_SEND_EVENTS) {
+                None => true,
+                Some(value) => boolean_from_str(value)?,
+            },
+            multicast: match element.attribute(XML_ATTR_MULTICAST) {
+                None => false,
+                Some(value) => boolean_from_str(value)?,
+            },
+            name: element.required_child_text(XML_ELEM_NAME)?.to_string(),
+            data_type: element.required_child_text(XML_ELEM_DATA_TYPE)?.to_string(),
+            default_value: element.child_text(XML_ELEM_DEFAULT_VALUE).map(String::from),
+            allowed_values: if let Some(list) = element.child(XML_ELEM_ALLOWED_LIST) {
+                Some(AllowedValue::List {
+                    values: list
+                        .children_named(XML_ELEM_ALLOWED_VALUE)
+                        .map(|value| value.text.clone())
+                        .collect(),
+                })
+            } else if let Some(range) = element.child(XML_ELEM_ALLOWED_RANGE) {
+                Some(AllowedValue::Range {
+                    minimum: range.required_child_text(XML_ELEM_MINIMUM)?.to_string(),
+                    maximum: range.required_child_text(XML_ELEM_MAXIMUM)?.to_string(),
+                    step: range.child_text(XML_ELEM_STEP).map(String::from),
+                })
+            } else {
+                None
+            },
+            moderation: {
+                let maximum_rate = match element.child_text(XML_ELEM_MAXIMUM_RATE) {
+                    None => None,
+                    Some(value) => Some(
+                        f64::from_str(value.trim())
+                            .ok()
+                            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                            .ok_or_else(|| invalid_field_value(XML_ELEM_MAXIMUM_RATE, value))?,
+                    ),
+                };
+                let minimum_delta = match element.child_text(XML_ELEM_MINIMUM_DELTA) {
+                    None => None,
+                    Some(value) => Some(
+                        f64::from_str(value.trim())
+                            .map_err(|_| invalid_field_value(XML_ELEM_MINIMUM_DELTA, value))?,
+                    ),
+                };
+                if maximum_rate.is_some() || minimum_delta.is_some() {
+                    Some(Moderation {
+                        maximum_rate,
+                        minimum_delta,
+                    })
+                } else {
+                    None
+                }
+            },
+        })
+    }
+}
+
+impl Moderation {
+    ///
+    /// Returns `true` if the change from `previous`, the value last evented, to `value` is
+    /// large enough to be evented. Values that are not numeric are always significant.
+    ///
+    pub fn is_significant(&self, previous: &str, value: &str) -> bool {
+        match (
+            self.minimum_delta,
+            f64::from_str(previous.trim()),
+            f64::from_str(value.trim()),
+        ) {
+            (Some(minimum_delta), Ok(previous), Ok(value)) => {
+                (value - previous).abs() >= minimum_delta
+            }
+            _ => true,
+        }
+    }
+
+    ///
+    /// The time remaining, after `elapsed` since the last event, before the variable may be
+    /// evented again.
+    ///
+    pub fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        match self.maximum_rate {
+            Some(maximum_rate) if elapsed < maximum_rate => Some(maximum_rate - elapsed),
+            _ => None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
 impl<T: Write> Writable<T> for Argument {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
         let argument = start_element(writer, XML_ELEM_ARGUMENT).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_NAME, self.name.as_bytes()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_NAME, self.name.as_str()).map_err(xml_error)?;
 
         text_element(
             writer,
             XML_ELEM_DIRECTION,
             match &self.direction {
-                Direction::In => "in".as_bytes(),
-                Direction::Out => "out".as_bytes(),
+                Direction::In => "in",
+                Direction::Out => "out",
             },
         )
         .map_err(xml_error)?;
@@ -102,7 +473,7 @@ impl<T: Write> Writable<T> for Argument {
         text_element(
             writer,
             XML_ELEM_REL_STATE_VARIABLE,
-            self.related_state_variable.as_bytes(),
+            self.related_state_variable.as_str(),
         )
         .map_err(xml_error)?;
 
@@ -114,7 +485,7 @@ impl<T: Write> Writable<T> for Action {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
         let action = start_element(writer, XML_ELEM_ACTION).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_NAME, self.name.as_bytes()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_NAME, self.name.as_str()).map_err(xml_error)?;
 
         if !&self.argument_list.is_empty() {
             let list = start_element(writer, XML_ELEM_ARGUMENT_LIST).map_err(xml_error)?;
@@ -134,7 +505,7 @@ impl<T: Write> Writable<T> for AllowedValue {
             AllowedValue::List { values } => {
                 let list = start_element(writer, XML_ELEM_ALLOWED_LIST).map_err(xml_error)?;
                 for value in values {
-                    text_element(writer, XML_ELEM_ALLOWED_VALUE, value.as_bytes())
+                    text_element(writer, XML_ELEM_ALLOWED_VALUE, value.as_str())
                         .map_err(xml_error)?;
                 }
                 list.end(writer).map_err(xml_error)
@@ -146,12 +517,12 @@ impl<T: Write> Writable<T> for AllowedValue {
             } => {
                 let range = start_element(writer, XML_ELEM_ALLOWED_RANGE).map_err(xml_error)?;
 
-                text_element(writer, XML_ELEM_MINIMUM, minimum.as_bytes()).map_err(xml_error)?;
+                text_element(writer, XML_ELEM_MINIMUM, minimum.as_str()).map_err(xml_error)?;
 
-                text_element(writer, XML_ELEM_MAXIMUM, maximum.as_bytes()).map_err(xml_error)?;
+                text_element(writer, XML_ELEM_MAXIMUM, maximum.as_str()).map_err(xml_error)?;
 
                 if let Some(step) = step {
-                    text_element(writer, XML_ELEM_STEP, step.as_bytes()).map_err(xml_error)?;
+                    text_element(writer, XML_ELEM_STEP, step.as_str()).map_err(xml_error)?;
                 }
                 range.end(writer).map_err(xml_error)
             }
@@ -161,22 +532,22 @@ impl<T: Write> Writable<T> for AllowedValue {
 
 impl<T: Write> Writable<T> for StateVariable {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let variable = start_element_with(
-            writer,
-            XML_ELEM_STATE_VARIABLE,
-            vec![(
-                XML_ATTR_SEND_EVENTS,
-                if self.send_events { "yes" } else { "no" },
-            )],
-        )
-        .map_err(xml_error)?;
+        let mut attributes = vec![(
+            XML_ATTR_SEND_EVENTS,
+            if self.send_events { "yes" } else { "no" },
+        )];
+        if self.multicast {
+            attributes.push((XML_ATTR_MULTICAST, "yes"));
+        }
+        let variable =
+            start_element_with(writer, XML_ELEM_STATE_VARIABLE, attributes).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_NAME, self.name.as_bytes()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_NAME, self.name.as_str()).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_DATA_TYPE, self.data_type.as_bytes()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_DATA_TYPE, self.data_type.as_str()).map_err(xml_error)?;
 
         if let Some(default_value) = &self.default_value {
-            text_element(writer, XML_ELEM_DEFAULT_VALUE, default_value.as_bytes())
+            text_element(writer, XML_ELEM_DEFAULT_VALUE, default_value.as_str())
                 .map_err(xml_error)?;
         }
 
@@ -184,6 +555,17 @@ impl<T: Write> Writable<T> for StateVariable {
             allowed.write(writer)?;
         }
 
+        if let Some(moderation) = &self.moderation {
+            if let Some(maximum_rate) = &moderation.maximum_rate {
+                display_element(writer, XML_ELEM_MAXIMUM_RATE, maximum_rate.as_secs_f64())
+                    .map_err(xml_error)?;
+            }
+            if let Some(minimum_delta) = &moderation.minimum_delta {
+                display_element(writer, XML_ELEM_MINIMUM_DELTA, minimum_delta)
+                    .map_err(xml_error)?;
+            }
+        }
+
         variable.end(writer).map_err(xml_error)
     }
 }
@@ -192,8 +574,14 @@ impl<T: Write> RootWritable<T> for Spcd {}
 
 impl<T: Write> Writable<T> for Spcd {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let root =
-            start_ns_element(writer, XML_ELEM_SPCD, XML_NS_SERVICE, None).map_err(xml_error)?;
+        let root = start_description_element(
+            writer,
+            XML_ELEM_SPCD,
+            XML_NS_SERVICE,
+            self.spec_version,
+            self.config_id,
+        )
+        .map_err(xml_error)?;
 
         self.spec_version.write(writer)?;
 
@@ -211,14 +599,148 @@ impl<T: Write> Writable<T> for Spcd {
         }
         list.end(writer).map_err(xml_error)?;
 
+        for extension in &self.extensions {
+            extension.write_in(writer, XML_NS_SERVICE)?;
+        }
+
         root.end(writer).map_err(xml_error)
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl<T: Write> Debug for SpcdWriter<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpcdWriter")
+            .field("section", &self.section)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Write> SpcdWriter<T> {
+    ///
+    /// Start a document, writing the XML declaration, the root element, and the specification
+    /// version.
+    ///
+    pub fn new(
+        writer: T,
+        spec_version: SpecVersion,
+        config_id: Option<u32>,
+    ) -> Result<Self, Error> {
+        Self::new_with(writer, spec_version, config_id, &Default::default())
+    }
+
+    ///
+    /// As [`SpcdWriter::new`], laid out according to `options`.
+    ///
+    pub fn new_with(
+        writer: T,
+        spec_version: SpecVersion,
+        config_id: Option<u32>,
+        options: &WriteOptions,
+    ) -> Result<Self, Error> {
+        let mut writer = document_writer(writer, options)?;
+        let _ = start_description_element(
+            &mut writer,
+            XML_ELEM_SPCD,
+            XML_NS_SERVICE,
+            spec_version,
+            config_id,
+        )
+        .map_err(xml_error)?;
+        spec_version.write(&mut writer)?;
+        Ok(Self {
+            writer,
+            section: SpcdSection::Start,
+        })
+    }
+
+    ///
+    /// Append an action; an error is returned if a state variable has already been written.
+    ///
+    pub fn action(&mut self, action: &Action) -> Result<&mut Self, Error> {
+        match self.section {
+            SpcdSection::Start => {
+                let _ = start_element(&mut self.writer, XML_ELEM_ACTION_LIST).map_err(xml_error)?;
+                self.section = SpcdSection::Actions;
+            }
+            SpcdSection::Actions => {}
+            _ => return unsupported_operation("action written after state variables").into(),
+        }
+        action.write(&mut self.writer)?;
+        Ok(self)
+    }
+
+    ///
+    /// Append a state variable; an error is returned if an extension has already been written.
+    ///
+    pub fn state_variable(&mut self, variable: &StateVariable) -> Result<&mut Self, Error> {
+        self.enter_state_table()?;
+        variable.write(&mut self.writer)?;
+        Ok(self)
+    }
+
+    ///
+    /// Append an element not defined by the specification, after all state variables.
+    ///
+    pub fn extension(&mut self, extension: &XmlFragment) -> Result<&mut Self, Error> {
+        self.leave_state_table()?;
+        extension.write_in(&mut self.writer, XML_NS_SERVICE)?;
+        Ok(self)
+    }
+
+    ///
+    /// Close any open lists and the root element, returning the underlying writer. The state
+    /// table is required, so an empty one is written if no state variables were appended.
+    ///
+    pub fn finish(mut self) -> Result<T, Error> {
+        self.leave_state_table()?;
+        end_element(&mut self.writer, XML_ELEM_SPCD).map_err(xml_error)?;
+        Ok(self.writer.into_inner())
+    }
+
+    fn enter_state_table(&mut self) -> Result<(), Error> {
+        match self.section {
+            SpcdSection::Start | SpcdSection::Actions => {
+                if self.section == SpcdSection::Actions {
+                    end_element(&mut self.writer, XML_ELEM_ACTION_LIST).map_err(xml_error)?;
+                }
+                let _ = start_element(&mut self.writer, XML_ELEM_STATE_TABLE).map_err(xml_error)?;
+                self.section = SpcdSection::StateTable;
+                Ok(())
+            }
+            SpcdSection::StateTable => Ok(()),
+            SpcdSection::Extensions => {
+                unsupported_operation("state variable written after extensions").into()
+            }
+        }
+    }
+
+    fn leave_state_table(&mut self) -> Result<(), Error> {
+        if self.section != SpcdSection::Extensions {
+            self.enter_state_table()?;
+            end_element(&mut self.writer, XML_ELEM_STATE_TABLE).map_err(xml_error)?;
+            self.section = SpcdSection::Extensions;
+        }
+        Ok(())
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The part of the document an `SpcdWriter` is currently writing.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpcdSection {
+    Start,
+    Actions,
+    StateTable,
+    Extensions,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
@@ -226,3 +748,169 @@ impl<T: Write> Writable<T> for Spcd {
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::from_utf8;
+
+    const EX_SCPD: &str = "<?xml version=\"1.0\"?><scpd xmlns=\"urn:schemas-upnp-org:service-1-0\"><specVersion><major>1</major><minor>1</minor></specVersion><actionList><action><name>SetTarget</name><argumentList><argument><name>newTargetValue</name><direction>in</direction><relatedStateVariable>Target</relatedStateVariable></argument></argumentList></action><action><name>GetStatus</name><argumentList><argument><name>ResultStatus</name><direction>out</direction><retval></retval><relatedStateVariable>Status</relatedStateVariable></argument></argumentList></action></actionList><serviceStateTable><stateVariable sendEvents=\"no\"><name>Target</name><dataType>boolean</dataType><defaultValue>0</defaultValue></stateVariable><stateVariable sendEvents=\"yes\"><name>Status</name><dataType>ui1</dataType><allowedValueRange><minimum>0</minimum><maximum>100</maximum><step>5</step></allowedValueRange></stateVariable></serviceStateTable></scpd>";
+
+    #[test]
+    fn test_xml_parse_and_serialize() {
+        let spcd = Spcd::from_str(EX_SCPD).unwrap();
+        assert_eq!(spcd.spec_version, SpecVersion::V11);
+        assert_eq!(spcd.action_list.len(), 2);
+        assert!(spcd.action_list[1].argument_list[0].return_value);
+        assert_eq!(spcd.service_state_table.len(), 2);
+        assert!(!spcd.service_state_table[0].send_events);
+        assert!(!spcd.service_state_table[1].multicast);
+
+        let written = to_writer(&spcd, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_SCPD);
+    }
+
+    #[test]
+    fn test_extensions_round_trip() {
+        let xml = EX_SCPD.replace(
+            "</serviceStateTable>",
+            "</serviceStateTable><X_vendorInfo>1</X_vendorInfo>",
+        );
+        let spcd = Spcd::from_str(&xml).unwrap();
+        assert_eq!(spcd.extensions.len(), 1);
+        assert_eq!(spcd.extensions[0].element().name, "X_vendorInfo");
+
+        let written = to_writer(&spcd, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_incremental_writer() {
+        let xml = EX_SCPD.replace(
+            "</serviceStateTable>",
+            "</serviceStateTable><X_vendorInfo>1</X_vendorInfo>",
+        );
+        let spcd = Spcd::from_str(&xml).unwrap();
+
+        let mut writer = SpcdWriter::new(Vec::new(), spcd.spec_version, spcd.config_id).unwrap();
+        for action in &spcd.action_list {
+            writer.action(action).unwrap();
+        }
+        for variable in &spcd.service_state_table {
+            writer.state_variable(variable).unwrap();
+        }
+        writer.extension(&spcd.extensions[0]).unwrap();
+        assert!(writer.action(&spcd.action_list[0]).is_err());
+        assert!(writer.state_variable(&spcd.service_state_table[0]).is_err());
+        let written = writer.finish().unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), xml);
+
+        let empty = SpcdWriter::new(Vec::new(), SpecVersion::V10, None)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(from_utf8(&empty)
+            .unwrap()
+            .ends_with("</specVersion><serviceStateTable></serviceStateTable></scpd>"));
+    }
+
+    #[test]
+    fn test_evented_variable_options() {
+        let xml = EX_SCPD
+            .replace(
+                "<stateVariable sendEvents=\"yes\">",
+                "<stateVariable sendEvents=\"yes\" multicast=\"yes\">",
+            )
+            .replace(
+                "<step>5</step></allowedValueRange>",
+                "<step>5</step></allowedValueRange><maximumRate>0.5</maximumRate><minimumDelta>5</minimumDelta>",
+            );
+        let spcd = Spcd::from_str(&xml).unwrap();
+        assert!(spcd.service_state_table[0].moderation.is_none());
+        assert!(spcd.service_state_table[1].multicast);
+        let moderation = spcd.service_state_table[1].moderation.clone().unwrap();
+        assert_eq!(moderation.maximum_rate, Some(Duration::from_millis(500)));
+        assert_eq!(moderation.minimum_delta, Some(5.0));
+
+        assert!(!moderation.is_significant("10", "14"));
+        assert!(moderation.is_significant("10", "15"));
+        assert!(moderation.is_significant("10", "unknown"));
+        assert_eq!(
+            moderation.remaining(Duration::from_millis(200)),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(moderation.remaining(Duration::from_secs(1)), None);
+
+        let written = to_writer(&spcd, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), xml);
+
+        let xml = xml.replace("0.5", "soon");
+        assert!(Spcd::from_str(&xml).is_err());
+    }
+
+    #[test]
+    fn test_xml_serialize_pretty() {
+        let spcd = Spcd::from_str(EX_SCPD).unwrap();
+        let options = WriteOptions {
+            standalone: Some(true),
+            ..WriteOptions::pretty()
+        };
+        let written = to_writer_with(&spcd, Vec::new(), &options).unwrap();
+        let xml = from_utf8(&written).unwrap();
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\" standalone=\"yes\"?>\n<scpd xmlns=\"urn:schemas-upnp-org:service-1-0\">\n  <specVersion>\n    <major>1</major>\n"
+        ));
+        assert!(xml.contains("\n          <retval></retval>\n"));
+        assert!(xml.ends_with("  </serviceStateTable>\n</scpd>"));
+
+        let parsed = Spcd::from_str(xml).unwrap();
+        let written = to_writer(&parsed, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_SCPD);
+    }
+
+    #[test]
+    fn test_validate_args() {
+        let spcd = Spcd::from_str(EX_SCPD).unwrap();
+        let set_target = spcd.action("SetTarget").unwrap();
+        let arguments = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert!(set_target
+            .validate_args(&spcd, &arguments(&[("newTargetValue", "1")]))
+            .is_ok());
+        for (args, message) in [
+            (
+                arguments(&[]),
+                "The argument `newTargetValue` for action `SetTarget` is invalid: is missing",
+            ),
+            (
+                arguments(&[("newTargetValue", "maybe")]),
+                "The argument `newTargetValue` for action `SetTarget` is invalid: The value provided is not valid for type `boolean` (Value: `maybe`)",
+            ),
+            (
+                arguments(&[("newTargetValue", "1"), ("other", "1")]),
+                "The argument `other` for action `SetTarget` is invalid: not defined for this action",
+            ),
+        ] {
+            match set_target.validate_args(&spcd, &args) {
+                Err(Error::Control(e)) => assert_eq!(e.to_string(), message),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+
+        let get_status = spcd.action("GetStatus").unwrap();
+        assert!(get_status
+            .validate_args(&spcd, &arguments(&[("ResultStatus", "1")]))
+            .is_err());
+
+        let status = spcd.state_variable("Status").unwrap();
+        assert!(status.validate_value("15").is_ok());
+        assert!(status.validate_value("12").is_err());
+        assert!(status.validate_value("105").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+/*!
+This module provides constants and constructors for the device and service types defined by the
+UPnP Forum that are most commonly encountered, in particular those used by the AV (DLNA) and
+Internet Gateway device profiles.
+
+Each constructor takes the version of the type required, the `*_LATEST` constants give the most
+recent version of each type published by the UPnP Forum. All type identifiers may be converted
+into a `SearchTarget`; note that a search for an earlier version of a type is also answered by
+devices implementing later versions.
+
+# Example
+
+```rust
+use upnp_rs::description::well_known::*;
+use upnp_rs::discovery::search::SearchTarget;
+
+let media_server = media_server(MEDIA_SERVER_LATEST);
+assert_eq!(
+    media_server.to_string(),
+    "urn:schemas-upnp-org:device:MediaServer:4"
+);
+
+let search_target: SearchTarget = content_directory(1).into();
+assert_eq!(
+    search_target.to_string(),
+    "urn:schemas-upnp-org:service:ContentDirectory:1"
+);
+```
+*/
+
+use crate::description::TypeID;
+
+// ------------------------------------------------------------------------------------------------
+// Public Values -- Device Types
+// ------------------------------------------------------------------------------------------------
+
+pub const BASIC_DEVICE: &str = "Basic";
+pub const BASIC_DEVICE_LATEST: u32 = 1;
+
+pub const MEDIA_SERVER: &str = "MediaServer";
+pub const MEDIA_SERVER_LATEST: u32 = 4;
+
+pub const MEDIA_RENDERER: &str = "MediaRenderer";
+pub const MEDIA_RENDERER_LATEST: u32 = 3;
+
+pub const INTERNET_GATEWAY_DEVICE: &str = "InternetGatewayDevice";
+pub const INTERNET_GATEWAY_DEVICE_LATEST: u32 = 2;
+
+pub const WAN_DEVICE: &str = "WANDevice";
+pub const WAN_DEVICE_LATEST: u32 = 2;
+
+pub const WAN_CONNECTION_DEVICE: &str = "WANConnectionDevice";
+pub const WAN_CONNECTION_DEVICE_LATEST: u32 = 2;
+
+pub const LAN_DEVICE: &str = "LANDevice";
+pub const LAN_DEVICE_LATEST: u32 = 1;
+
+// ------------------------------------------------------------------------------------------------
+// Public Values -- Service Types
+// ------------------------------------------------------------------------------------------------
+
+pub const CONTENT_DIRECTORY: &str = "ContentDirectory";
+pub const CONTENT_DIRECTORY_LATEST: u32 = 4;
+
+pub const CONNECTION_MANAGER: &str = "ConnectionManager";
+pub const CONNECTION_MANAGER_LATEST: u32 = 3;
+
+pub const AV_TRANSPORT: &str = "AVTransport";
+pub const AV_TRANSPORT_LATEST: u32 = 3;
+
+pub const RENDERING_CONTROL: &str = "RenderingControl";
+pub const RENDERING_CONTROL_LATEST: u32 = 3;
+
+pub const SCHEDULED_RECORDING: &str = "ScheduledRecording";
+pub const SCHEDULED_RECORDING_LATEST: u32 = 2;
+
+pub const WAN_IP_CONNECTION: &str = "WANIPConnection";
+pub const WAN_IP_CONNECTION_LATEST: u32 = 2;
+
+pub const WAN_PPP_CONNECTION: &str = "WANPPPConnection";
+pub const WAN_PPP_CONNECTION_LATEST: u32 = 1;
+
+pub const WAN_COMMON_INTERFACE_CONFIG: &str = "WANCommonInterfaceConfig";
+pub const WAN_COMMON_INTERFACE_CONFIG_LATEST: u32 = 1;
+
+pub const LAYER3_FORWARDING: &str = "Layer3Forwarding";
+pub const LAYER3_FORWARDING_LATEST: u32 = 1;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Construct a device type, in the UPnP domain, with the given name and version.
+///
+pub fn device_type(name: &str, version: u32) -> TypeID {
+    TypeID::new_device(name.to_string(), version)
+}
+
+///
+/// Construct a service type, in the UPnP domain, with the given name and version.
+///
+pub fn service_type(name: &str, version: u32) -> TypeID {
+    TypeID::new_service(name.to_string(), version)
+}
+
+// ------------------------------------------------------------------------------------------------
+
+pub fn basic_device(version: u32) -> TypeID {
+    device_type(BASIC_DEVICE, version)
+}
+
+pub fn media_server(version: u32) -> TypeID {
+    device_type(MEDIA_SERVER, version)
+}
+
+pub fn media_renderer(version: u32) -> TypeID {
+    device_type(MEDIA_RENDERER, version)
+}
+
+pub fn internet_gateway_device(version: u32) -> TypeID {
+    device_type(INTERNET_GATEWAY_DEVICE, version)
+}
+
+pub fn wan_device(version: u32) -> TypeID {
+    device_type(WAN_DEVICE, version)
+}
+
+pub fn wan_connection_device(version: u32) -> TypeID {
+    device_type(WAN_CONNECTION_DEVICE, version)
+}
+
+pub fn lan_device(version: u32) -> TypeID {
+    device_type(LAN_DEVICE, version)
+}
+
+// ------------------------------------------------------------------------------------------------
+
+pub fn content_directory(version: u32) -> TypeID {
+    service_type(CONTENT_DIRECTORY, version)
+}
+
+pub fn connection_manager(version: u32) -> TypeID {
+    service_type(CONNECTION_MANAGER, version)
+}
+
+pub fn av_transport(version: u32) -> TypeID {
+    service_type(AV_TRANSPORT, version)
+}
+
+pub fn rendering_control(version: u32) -> TypeID {
+    service_type(RENDERING_CONTROL, version)
+}
+
+pub fn scheduled_recording(version: u32) -> TypeID {
+    service_type(SCHEDULED_RECORDING, version)
+}
+
+pub fn wan_ip_connection(version: u32) -> TypeID {
+    service_type(WAN_IP_CONNECTION, version)
+}
+
+pub fn wan_ppp_connection(version: u32) -> TypeID {
+    service_type(WAN_PPP_CONNECTION, version)
+}
+
+pub fn wan_common_interface_config(version: u32) -> TypeID {
+    service_type(WAN_COMMON_INTERFACE_CONFIG, version)
+}
+
+pub fn layer3_forwarding(version: u32) -> TypeID {
+    service_type(LAYER3_FORWARDING, version)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::search::SearchTarget;
+
+    #[test]
+    fn test_type_strings() {
+        assert_eq!(
+            internet_gateway_device(2).to_string(),
+            "urn:schemas-upnp-org:device:InternetGatewayDevice:2"
+        );
+        assert_eq!(
+            wan_ip_connection(1).to_string(),
+            "urn:schemas-upnp-org:service:WANIPConnection:1"
+        );
+    }
+
+    #[test]
+    fn test_search_target_conversion() {
+        assert_eq!(
+            SearchTarget::from(media_renderer(1)),
+            SearchTarget::DeviceType("MediaRenderer:1".to_string())
+        );
+        assert_eq!(
+            SearchTarget::from(&av_transport(AV_TRANSPORT_LATEST)),
+            SearchTarget::ServiceType("AVTransport:3".to_string())
+        );
+    }
+}
@@ -0,0 +1,355 @@
+/*!
+This module provides the `SsdpIdentity` of a device, the `BOOTID.UPNP.ORG` and `CONFIGID.UPNP.ORG`
+values sent in all announcements and search responses from UDA 1.1 onwards.
+
+# Specification
+
+The boot instance identifier must only change when the device reboots, or when it sends an
+`ssdp:update` message to announce a change in its network interfaces; it must not change between
+individual announcements. It must also increase each time it changes, and so must be persisted
+across restarts, or be derived from a timestamp. The configuration identifier changes only when
+the device or service descriptions change.
+
+An `IdentityStore` is used to persist the identity, a `FileStore` is provided that keeps the
+values in a small text file.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::identity::{FileStore, SsdpIdentity};
+
+let store = FileStore::new("/var/lib/my-device/ssdp-identity");
+let identity = SsdpIdentity::load_or_create(&store, 1).unwrap();
+println!("BOOTID.UPNP.ORG: {}", identity.boot_id());
+```
+*/
+
+use crate::error::{invalid_field_value, invalid_header_value, Error, MessageFormatError};
+use crate::syntax::{HTTP_HEADER_BOOTID, HTTP_HEADER_CONFIGID, MAX_CONFIG_ID};
+use std::fmt::Debug;
+use std::fs::{read_to_string, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The boot and configuration identifiers of a device.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SsdpIdentity {
+    boot_id: u32,
+    config_id: u32,
+    next_boot_id: Option<u32>,
+}
+
+///
+/// Implemented by types that persist an identity between runs of a device.
+///
+pub trait IdentityStore: Debug {
+    ///
+    /// Return the identity last stored, or `None` if no identity has been stored.
+    ///
+    fn load(&self) -> Result<Option<SsdpIdentity>, Error>;
+
+    ///
+    /// Store the identity, replacing any previously stored.
+    ///
+    fn store(&self, identity: &SsdpIdentity) -> Result<(), Error>;
+}
+
+///
+/// An identity store that keeps the identity in memory, useful for testing.
+///
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    identity: Mutex<Option<SsdpIdentity>>,
+}
+
+///
+/// An identity store that keeps the identity in a text file, with one `header: value` line for
+/// each of the boot and configuration identifiers.
+///
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+///
+/// The largest value permitted for `BOOTID.UPNP.ORG`, a non-negative 31-bit integer.
+///
+pub const MAX_BOOT_ID: u32 = 2_147_483_647;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Parse the value of a `BOOTID.UPNP.ORG`, or `NEXTBOOTID.UPNP.ORG`, header named `name`; the
+/// value must be a non-negative 31-bit integer.
+///
+pub fn parse_boot_id(value: &str, name: &str) -> Result<u32, MessageFormatError> {
+    match value.trim().parse::<u32>() {
+        Ok(boot_id) if boot_id <= MAX_BOOT_ID => Ok(boot_id),
+        _ => invalid_header_value(name, value).into(),
+    }
+}
+
+///
+/// Parse the value of a `CONFIGID.UPNP.ORG` header; the value must be a non-negative integer no
+/// greater than `MAX_CONFIG_ID`.
+///
+pub fn parse_config_id(value: &str) -> Result<u32, MessageFormatError> {
+    match value.trim().parse::<u32>() {
+        Ok(config_id) if config_id <= MAX_CONFIG_ID => Ok(config_id),
+        _ => invalid_header_value(HTTP_HEADER_CONFIGID, value).into(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl SsdpIdentity {
+    ///
+    /// Create a new identity, the boot identifier is derived from the current time so that it
+    /// increases between runs even when the identity is not persisted.
+    ///
+    pub fn new(config_id: u32) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            boot_id: (now % (MAX_BOOT_ID as u64 + 1)) as u32,
+            config_id: config_id.min(MAX_CONFIG_ID),
+            next_boot_id: None,
+        }
+    }
+
+    ///
+    /// Create an identity with specific values, for example when restoring one from storage.
+    ///
+    pub fn with_values(boot_id: u32, config_id: u32) -> Self {
+        Self {
+            boot_id: boot_id.min(MAX_BOOT_ID),
+            config_id: config_id.min(MAX_CONFIG_ID),
+            next_boot_id: None,
+        }
+    }
+
+    ///
+    /// Called once when the device starts; load the identity from `store` and advance its boot
+    /// identifier, or create a new identity if none was stored. The new identity is stored before
+    /// it is returned.
+    ///
+    pub fn load_or_create(store: &dyn IdentityStore, config_id: u32) -> Result<Self, Error> {
+        let identity = match store.load()? {
+            Some(mut previous) => {
+                previous.boot_id = previous.following_boot_id();
+                previous.next_boot_id = None;
+                previous.config_id = config_id.min(MAX_CONFIG_ID);
+                previous
+            }
+            None => Self::new(config_id),
+        };
+        info!(
+            "load_or_create - boot_id: {}, config_id: {}",
+            identity.boot_id, identity.config_id
+        );
+        store.store(&identity)?;
+        Ok(identity)
+    }
+
+    ///
+    /// The value for `BOOTID.UPNP.ORG`.
+    ///
+    pub fn boot_id(&self) -> u32 {
+        self.boot_id
+    }
+
+    ///
+    /// The value for `CONFIGID.UPNP.ORG`.
+    ///
+    pub fn config_id(&self) -> u32 {
+        self.config_id
+    }
+
+    ///
+    /// The value for `NEXTBOOTID.UPNP.ORG`, only present between `begin_update` and
+    /// `complete_update`.
+    ///
+    pub fn next_boot_id(&self) -> Option<u32> {
+        self.next_boot_id
+    }
+
+    ///
+    /// Set a new configuration identifier, this should be called whenever the device or service
+    /// descriptions change.
+    ///
+    pub fn set_config_id(&mut self, config_id: u32) {
+        self.config_id = config_id.min(MAX_CONFIG_ID);
+    }
+
+    ///
+    /// Start announcing a change of boot identifier; returns the value to send in the
+    /// `NEXTBOOTID.UPNP.ORG` header of the `ssdp:update` messages.
+    ///
+    pub fn begin_update(&mut self) -> u32 {
+        let next_boot_id = self
+            .next_boot_id
+            .unwrap_or_else(|| self.following_boot_id());
+        self.next_boot_id = Some(next_boot_id);
+        next_boot_id
+    }
+
+    ///
+    /// Complete the change of boot identifier started by `begin_update`, once all `ssdp:update`
+    /// messages have been sent; the new value is stored in `store`.
+    ///
+    pub fn complete_update(&mut self, store: &dyn IdentityStore) -> Result<(), Error> {
+        if let Some(next_boot_id) = self.next_boot_id.take() {
+            self.boot_id = next_boot_id;
+            store.store(self)?;
+        }
+        Ok(())
+    }
+
+    fn following_boot_id(&self) -> u32 {
+        if self.boot_id >= MAX_BOOT_ID {
+            0
+        } else {
+            self.boot_id + 1
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl IdentityStore for MemoryStore {
+    fn load(&self) -> Result<Option<SsdpIdentity>, Error> {
+        Ok(self.identity.lock().unwrap().clone())
+    }
+
+    fn store(&self, identity: &SsdpIdentity) -> Result<(), Error> {
+        *self.identity.lock().unwrap() = Some(identity.clone());
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl FileStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl IdentityStore for FileStore {
+    fn load(&self) -> Result<Option<SsdpIdentity>, Error> {
+        let content = match read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut boot_id = None;
+        let mut config_id = None;
+        for line in content.lines() {
+            if let Some((name, value)) = line.split_once(':') {
+                let parsed = value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| invalid_field_value(name.trim(), value.trim()))?;
+                match name.trim() {
+                    HTTP_HEADER_BOOTID => boot_id = Some(parsed),
+                    HTTP_HEADER_CONFIGID => config_id = Some(parsed),
+                    _ => {}
+                }
+            }
+        }
+        match (boot_id, config_id) {
+            (Some(boot_id), Some(config_id)) => {
+                Ok(Some(SsdpIdentity::with_values(boot_id, config_id)))
+            }
+            _ => invalid_field_value("identity", content).into(),
+        }
+    }
+
+    fn store(&self, identity: &SsdpIdentity) -> Result<(), Error> {
+        write(
+            &self.path,
+            format!(
+                "{}: {}\n{}: {}\n",
+                HTTP_HEADER_BOOTID, identity.boot_id, HTTP_HEADER_CONFIGID, identity.config_id
+            ),
+        )?;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_boot_id_advances_per_boot() {
+        let store = MemoryStore::default();
+        let first = SsdpIdentity::load_or_create(&store, 1).unwrap();
+        let second = SsdpIdentity::load_or_create(&store, 2).unwrap();
+        assert_eq!(second.boot_id(), first.boot_id() + 1);
+        assert_eq!(second.config_id(), 2);
+        assert_eq!(store.load().unwrap(), Some(second));
+    }
+
+    #[test]
+    fn test_update() {
+        let store = MemoryStore::default();
+        let mut identity = SsdpIdentity::with_values(MAX_BOOT_ID - 1, 1);
+        assert_eq!(identity.begin_update(), MAX_BOOT_ID);
+        assert_eq!(identity.begin_update(), MAX_BOOT_ID);
+        assert_eq!(identity.boot_id(), MAX_BOOT_ID - 1);
+
+        identity.complete_update(&store).unwrap();
+        assert_eq!(identity.boot_id(), MAX_BOOT_ID);
+        assert_eq!(identity.next_boot_id(), None);
+        assert_eq!(identity.begin_update(), 0);
+        assert_eq!(store.load().unwrap().unwrap().boot_id(), MAX_BOOT_ID);
+    }
+
+    #[test]
+    fn test_parse_identifiers() {
+        assert_eq!(parse_boot_id("42", HTTP_HEADER_BOOTID).unwrap(), 42);
+        assert_eq!(
+            parse_boot_id("2147483647", HTTP_HEADER_BOOTID).unwrap(),
+            MAX_BOOT_ID
+        );
+        assert!(parse_boot_id("2147483648", HTTP_HEADER_BOOTID).is_err());
+        assert!(parse_boot_id("-1", HTTP_HEADER_BOOTID).is_err());
+        assert_eq!(parse_config_id("16777215").unwrap(), MAX_CONFIG_ID);
+        assert!(parse_config_id("16777216").is_err());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let path = temp_dir().join(format!("upnp-rs-identity-{}", std::process::id()));
+        let store = FileStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let identity = SsdpIdentity::with_values(42, 7);
+        store.store(&identity).unwrap();
+        assert_eq!(store.load().unwrap(), Some(identity));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,480 @@
+/*!
+This module provides a listener for the `NOTIFY` messages multicast by devices when they become
+available and when they leave the network. Unlike search, which is initiated by the control point,
+these messages arrive unsolicited and so the listener is driven by the caller, which repeatedly
+calls [`NotifyListener::receive`] from its own processing loop.
+
+From UDA 1.1 a device also sends `ssdp:update` messages when its boot identifier changes, for
+example when a network interface is added; these carry both the current `BOOTID.UPNP.ORG` and the
+new `NEXTBOOTID.UPNP.ORG` value, which must differ. The UDA 2.0 `SEARCHPORT.UPNP.ORG` and
+`SECURELOCATION.UPNP.ORG` headers are also captured, so that a control point may track reboots and
+send unicast searches to a device without a separate search.
+
+The listener requires the `net` feature; without it a [`Notification`] may still be parsed from a
+[`Request`] received by other means.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::listen::{NotifyListener, Options};
+
+let listener = NotifyListener::bind(Options::default()).unwrap();
+loop {
+    if let Some(notification) = listener.receive().unwrap() {
+        println!("{:?} {}", notification.subtype, notification.service_name);
+    }
+}
+```
+*/
+
+use crate::common::headers;
+use crate::common::httpu::{Request, DEFAULT_BUFFER_SIZE, DEFAULT_RECV_TIMEOUT};
+use crate::common::interface::IP;
+use crate::common::uri::{URI, URL};
+use crate::description::TypeID;
+use crate::discovery::identity::{parse_boot_id, parse_config_id};
+use crate::discovery::{NotificationType, UniqueServiceName};
+use crate::error::{invalid_field_value, invalid_header_value, MessageFormatError};
+use crate::syntax::{
+    Method, DYNAMIC_PORTS, HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID,
+    HTTP_HEADER_LOCATION, HTTP_HEADER_NEXT_BOOTID, HTTP_HEADER_NT, HTTP_HEADER_NTS,
+    HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SECURE_LOCATION, HTTP_HEADER_USN,
+};
+use regex::Regex;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+#[cfg(feature = "net")]
+use crate::common::httpu::{create_udp_socket, Options as MulticastOptions};
+#[cfg(feature = "net")]
+use crate::common::interface::ip_address_for_interface;
+#[cfg(feature = "net")]
+use crate::error::{multicast_join_failed, Error};
+#[cfg(feature = "net")]
+use crate::observer::{self, MessageKind};
+#[cfg(feature = "net")]
+use crate::syntax::MULTICAST_ADDRESS;
+#[cfg(feature = "net")]
+use std::io::ErrorKind;
+#[cfg(feature = "net")]
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+#[cfg(feature = "net")]
+use tracing::{debug, debug_span, trace};
+
+pub use crate::discovery::NotificationSubtype;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the set of values used to configure the listener.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// A specific network interface to listen on, by name, index, or network; if specified the
+    /// interface's address is used to join the multicast group, else the system chooses. A value
+    /// matching no interface is an error. Default: `None`.
+    pub network_interface: Option<String>,
+    /// Denotes whether the implementation wants to only use IPv4, IPv6, or doesn't care.
+    pub network_version: Option<IP>,
+    /// The time a call to `receive` will wait for a message. Default: `2` seconds.
+    pub recv_timeout: Duration,
+    /// The size of the buffer used to receive messages. Default: `1500`.
+    pub buffer_size: usize,
+}
+
+///
+/// A notification received from a device.
+///
+#[derive(Clone, Debug)]
+pub struct Notification {
+    /// Whether the device is announcing itself, changing its boot identifier, or leaving.
+    pub subtype: NotificationSubtype,
+    /// The notification type, from the `NT` header.
+    pub notification_type: NotificationType,
+    /// The unique service name, from the `USN` header.
+    pub service_name: UniqueServiceName,
+    /// The location of the device description; only present in _alive_ and _update_ messages.
+    pub location: Option<URL>,
+    /// How long the advertisement is valid for; only present in _alive_ messages.
+    pub max_age: Option<Duration>,
+    /// The device boot identifier, sent from UDA 1.1 onwards.
+    pub boot_id: Option<u32>,
+    /// The device configuration identifier, sent from UDA 1.1 onwards.
+    pub config_id: Option<u32>,
+    /// The boot identifier the device will use from now on; only present in _update_ messages.
+    pub next_boot_id: Option<u32>,
+    /// The port on which the device answers unicast searches, from UDA 2.0 onwards; a value
+    /// outside the range `DYNAMIC_PORTS` is ignored. Not present in _byebye_ messages.
+    pub search_port: Option<u16>,
+    /// The `https` location of the device description, from UDA 2.0 onwards; a value that is not
+    /// an `https` URL is ignored. Not present in _byebye_ messages.
+    pub secure_location: Option<URL>,
+    /// The address of the device that sent this notification, where known.
+    pub source: Option<SocketAddr>,
+}
+
+///
+/// A listener bound to the SSDP multicast group.
+///
+#[cfg(feature = "net")]
+#[derive(Debug)]
+pub struct NotifyListener {
+    socket: UdpSocket,
+    buffer_size: usize,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            network_interface: None,
+            network_version: None,
+            recv_timeout: DEFAULT_RECV_TIMEOUT,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<&Request> for Notification {
+    type Error = MessageFormatError;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        lazy_static! {
+            static ref MAX_AGE: Regex = Regex::new(r"(?i)max-age\s*=\s*(\d+)").unwrap();
+        }
+        if request.known_method() != Some(Method::Notify) {
+            return invalid_field_value("method", request.method()).into();
+        }
+        let header = |name: &str| request.header(name).map(str::trim);
+        headers::check_required(
+            request.headers(),
+            &[HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_USN],
+        )?;
+
+        let subtype = NotificationSubtype::from_str(header(HTTP_HEADER_NTS).unwrap())?;
+        let notification_type = header(HTTP_HEADER_NT).unwrap();
+        let service_name = header(HTTP_HEADER_USN).unwrap();
+
+        match subtype {
+            NotificationSubtype::Alive => headers::check_required(
+                request.headers(),
+                &[HTTP_HEADER_LOCATION, HTTP_HEADER_CACHE_CONTROL],
+            )?,
+            NotificationSubtype::Update => headers::check_required(
+                request.headers(),
+                &[
+                    HTTP_HEADER_LOCATION,
+                    HTTP_HEADER_BOOTID,
+                    HTTP_HEADER_NEXT_BOOTID,
+                ],
+            )?,
+            NotificationSubtype::ByeBye => {}
+        }
+
+        let location = match header(HTTP_HEADER_LOCATION) {
+            Some(location) if subtype != NotificationSubtype::ByeBye => Some(
+                URI::from_str(location)
+                    .map_err(|_| invalid_header_value(HTTP_HEADER_LOCATION, location))?,
+            ),
+            _ => None,
+        };
+        let max_age = if subtype == NotificationSubtype::Alive {
+            let max_age = headers::check_regex(
+                header(HTTP_HEADER_CACHE_CONTROL).unwrap(),
+                HTTP_HEADER_CACHE_CONTROL,
+                &MAX_AGE,
+            )?;
+            Some(Duration::from_secs(headers::check_parsed_value::<u64>(
+                &max_age,
+                HTTP_HEADER_CACHE_CONTROL,
+            )?))
+        } else {
+            None
+        };
+
+        let boot_id = match header(HTTP_HEADER_BOOTID) {
+            None => None,
+            Some(value) => Some(parse_boot_id(value, HTTP_HEADER_BOOTID)?),
+        };
+        let next_boot_id = match header(HTTP_HEADER_NEXT_BOOTID) {
+            Some(value) if subtype == NotificationSubtype::Update => {
+                let next_boot_id = parse_boot_id(value, HTTP_HEADER_NEXT_BOOTID)?;
+                if boot_id == Some(next_boot_id) {
+                    return invalid_header_value(HTTP_HEADER_NEXT_BOOTID, value).into();
+                }
+                Some(next_boot_id)
+            }
+            _ => None,
+        };
+        let config_id = header(HTTP_HEADER_CONFIGID).and_then(|value| parse_config_id(value).ok());
+        let search_port = match header(HTTP_HEADER_SEARCH_PORT) {
+            Some(value) if subtype != NotificationSubtype::ByeBye => value
+                .parse::<u16>()
+                .ok()
+                .filter(|port| DYNAMIC_PORTS.contains(port)),
+            _ => None,
+        };
+        let secure_location = match header(HTTP_HEADER_SECURE_LOCATION) {
+            Some(location) if subtype != NotificationSubtype::ByeBye => {
+                match URI::from_str(location) {
+                    Ok(url) if location.to_ascii_lowercase().starts_with("https://") => Some(url),
+                    _ => {
+                        warn!(
+                            "try_from - ignoring {} value {:?}, not an https URL",
+                            HTTP_HEADER_SECURE_LOCATION, location
+                        );
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        Ok(Notification {
+            subtype,
+            notification_type: NotificationType::from_str(notification_type)
+                .map_err(|_| invalid_header_value(HTTP_HEADER_NT, notification_type))?,
+            service_name: UniqueServiceName::from_str(service_name)
+                .map_err(|_| invalid_header_value(HTTP_HEADER_USN, service_name))?,
+            location,
+            max_age,
+            boot_id,
+            config_id,
+            next_boot_id,
+            search_port,
+            secure_location,
+            source: None,
+        })
+    }
+}
+
+impl Notification {
+    ///
+    /// The device or service type in the `NT` header, `None` if the notification is for the root
+    /// device or a device UUID.
+    ///
+    pub fn type_id(&self) -> Option<TypeID> {
+        self.notification_type.type_id()
+    }
+
+    ///
+    /// The address to which unicast search requests should be sent to reach the device that sent
+    /// this notification; this is the notification's source address with the port taken from any
+    /// `SEARCHPORT.UPNP.ORG` header, or the standard SSDP port.
+    ///
+    pub fn search_address(&self) -> Option<SocketAddr> {
+        self.source.map(|source| {
+            SocketAddr::new(source.ip(), self.search_port.unwrap_or(DEFAULT_SEARCH_PORT))
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+impl NotifyListener {
+    ///
+    /// Bind to the SSDP multicast port and join the multicast group on the selected interface.
+    ///
+    pub fn bind(options: Options) -> Result<Self, Error> {
+        let multicast: SocketAddr = MULTICAST_ADDRESS.parse().unwrap();
+        let socket = create_udp_socket(
+            &SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), multicast.port()),
+            &MulticastOptions {
+                reuse_address: true,
+                reuse_port: cfg!(all(
+                    unix,
+                    not(any(target_os = "solaris", target_os = "illumos"))
+                )),
+                ..Default::default()
+            },
+        )?;
+        let interface =
+            match ip_address_for_interface(&options.network_interface, &options.network_version)? {
+                Some(IpAddr::V4(address)) => address,
+                _ => Ipv4Addr::UNSPECIFIED,
+            };
+        if let IpAddr::V4(group) = multicast.ip() {
+            socket
+                .join_multicast_v4(&group, &interface)
+                .map_err(|e| multicast_join_failed(group.to_string(), e))?;
+        }
+        socket.set_read_timeout(Some(options.recv_timeout))?;
+        Ok(NotifyListener {
+            socket,
+            buffer_size: options.buffer_size,
+        })
+    }
+
+    ///
+    /// The local address the listener is bound to.
+    ///
+    pub fn local_address(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    ///
+    /// Wait for the next notification; `None` is returned if the receive timeout expires, or if
+    /// the message received was not a well-formed notification (for example an `M-SEARCH` sent
+    /// by another control point).
+    ///
+    pub fn receive(&self) -> Result<Option<Notification>, Error> {
+        let mut buf = vec![0u8; self.buffer_size];
+        match self.socket.recv_from(&mut buf) {
+            Ok((received, from)) => {
+                trace!("receive - {} bytes from {}", received, from);
+                Ok(parse_notification(&buf[..received], from))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Values
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_SEARCH_PORT: u16 = 1900;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+fn parse_notification(bytes: &[u8], from: SocketAddr) -> Option<Notification> {
+    let _span = debug_span!("listen", peer = %from).entered();
+    let request = match Request::try_from(bytes) {
+        Ok(request) => request,
+        Err(e) => {
+            observer::parse_failed(MessageKind::Notify);
+            warn!(error = %e, "ignoring malformed message");
+            return None;
+        }
+    };
+    if request.known_method() != Some(Method::Notify) {
+        return None;
+    }
+    match Notification::try_from(&request) {
+        Ok(mut notification) => {
+            observer::received(MessageKind::Notify);
+            debug!(
+                usn = %notification.service_name,
+                nt = %notification.notification_type,
+                "received notification"
+            );
+            notification.source = Some(from);
+            Some(notification)
+        }
+        Err(e) => {
+            observer::parse_failed(MessageKind::Notify);
+            warn!(error = %e, "ignoring malformed notification");
+            None
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+
+    const ALIVE: &[u8] = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nSERVER: Linux/5.0 UPnP/2.0 Test/1.0\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 7\r\nCONFIGID.UPNP.ORG: 2\r\n\r\n";
+
+    const BYEBYE: &[u8] = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:byebye\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n";
+
+    #[test]
+    fn test_parse_alive() {
+        let notification = parse_notification(ALIVE, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert_eq!(notification.subtype, NotificationSubtype::Alive);
+        assert_eq!(notification.notification_type, NotificationType::RootDevice);
+        assert_eq!(
+            notification.service_name.device().to_string(),
+            "uuid:device-1"
+        );
+        assert_eq!(notification.max_age, Some(Duration::from_secs(1800)));
+        assert_eq!(notification.boot_id, Some(7));
+        assert_eq!(notification.config_id, Some(2));
+        assert!(notification.location.is_some());
+        assert!(notification.source.is_some());
+    }
+
+    #[test]
+    fn test_parse_byebye() {
+        let notification = parse_notification(BYEBYE, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert_eq!(notification.subtype, NotificationSubtype::ByeBye);
+        assert!(notification.location.is_none());
+        assert!(notification.max_age.is_none());
+        assert!(notification.boot_id.is_none());
+        assert!(notification.search_port.is_none());
+        assert!(notification.secure_location.is_none());
+    }
+
+    #[test]
+    fn test_parse_v20_headers() {
+        let alive = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 7\r\nCONFIGID.UPNP.ORG: 2\r\nSEARCHPORT.UPNP.ORG: 49200\r\nSECURELOCATION.UPNP.ORG: https://10.0.0.1/description.xml\r\n\r\n";
+        let notification = parse_notification(alive, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert_eq!(notification.search_port, Some(49200));
+        assert_eq!(
+            notification.secure_location.as_ref().unwrap().to_string(),
+            "https://10.0.0.1/description.xml"
+        );
+        assert_eq!(
+            notification.search_address(),
+            Some("10.0.0.1:49200".parse().unwrap())
+        );
+
+        let reserved = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nUSN: uuid:device-1::upnp:rootdevice\r\nSEARCHPORT.UPNP.ORG: 80\r\n\r\n";
+        let notification = parse_notification(reserved, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert!(notification.search_port.is_none());
+        assert_eq!(
+            notification.search_address(),
+            Some("10.0.0.1:1900".parse().unwrap())
+        );
+
+        let insecure = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nUSN: uuid:device-1::upnp:rootdevice\r\nSECURELOCATION.UPNP.ORG: http://10.0.0.1/description.xml\r\n\r\n";
+        let notification = parse_notification(insecure, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert!(notification.secure_location.is_none());
+
+        let too_large = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 2147483648\r\n\r\n";
+        assert!(parse_notification(too_large, "10.0.0.1:1900".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let update = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:update\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 7\r\nNEXTBOOTID.UPNP.ORG: 8\r\nCONFIGID.UPNP.ORG: 2\r\n\r\n";
+        let notification = parse_notification(update, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert_eq!(notification.subtype, NotificationSubtype::Update);
+        assert!(notification.location.is_some());
+        assert!(notification.max_age.is_none());
+        assert_eq!(notification.boot_id, Some(7));
+        assert_eq!(notification.next_boot_id, Some(8));
+
+        let missing = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:update\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 7\r\n\r\n";
+        assert!(parse_notification(missing, "10.0.0.1:1900".parse().unwrap()).is_none());
+
+        let unchanged = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:update\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 7\r\nNEXTBOOTID.UPNP.ORG: 7\r\n\r\n";
+        assert!(parse_notification(unchanged, "10.0.0.1:1900".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_ignore_search() {
+        let search = b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n";
+        assert!(parse_notification(search, "10.0.0.1:1900".parse().unwrap()).is_none());
+    }
+}
@@ -61,9 +61,25 @@ section explains the format of version information in discovery messages and spe
 on discovery messages to maintain compatibility with advances in minor versions.
 */
 
-use crate::{SpecVersion, UPNP_STRING};
-use os_version::{detect, OsVersion};
-use std::fmt::{Display, Error, Formatter};
+use crate::common::udn::Udn;
+use crate::common::uri::URL;
+use crate::description::TypeID;
+use crate::discovery::identity::{SsdpIdentity, MAX_BOOT_ID};
+use crate::discovery::search::SearchTarget;
+use crate::error::{
+    invalid_field_value, invalid_header_value, invalid_value_for_type, Error, MessageFormatError,
+};
+use crate::syntax::{Nts, DYNAMIC_PORTS, HTTP_HEADER_CP_UUID, HTTP_HEADER_NTS, MAX_CONFIG_ID};
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::fs::{read_to_string, write};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub use crate::common::user_agent::{ProductVersion, ProductVersions};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -78,136 +94,533 @@ pub struct ControlPoint {
     /// Specifies the friendly name of the control point. The friendly name is vendor specific.
     pub friendly_name: String,
     /// UUID of the control point. When the control point is implemented in a UPnP device it
-    /// is recommended to use the UDN of the co-located UPnP device.
-    pub uuid: Option<String>,
+    /// is recommended to use the UDN of the co-located UPnP device. The 2.0 specification
+    /// requires this value to remain the same across runs, see `ControlPoint::load_or_create`.
+    pub uuid: Option<Uuid>,
     /// A control point can request that a device replies to a TCP port on the control point.
     /// When this header is present it identifies the TCP port on which the device can reply to
-    /// the search.
+    /// the search. When set, a search will listen on this port and merge any responses received
+    /// over TCP with those received over UDP.
     pub port: Option<u16>,
 }
 
 ///
-/// A product name and version, used in constructing `SERVER` and `CACHE-CONTROL` headers. These
-/// arespecified by UPnP vendor. String.
+/// The kind of notification, the value of the `NTS` header; used both when sending and when
+/// receiving `NOTIFY` messages.
 ///
-/// Field value MUST begin with the following "product tokens" (defined by HTTP/1.1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NotificationSubtype {
+    /// `ssdp:alive`, the device or service is available.
+    Alive,
+    /// `ssdp:update`, the device's boot identifier is changing, from UDA 1.1 onwards.
+    Update,
+    /// `ssdp:byebye`, the device or service is leaving the network.
+    ByeBye,
+}
+
+///
+/// `NotificationType` corresponds to the set of values defined by the UDA `NT` header. These
+/// overlap with the search targets, but `ssdp:all` is not a valid notification type and a
+/// notification is always for a specific version of a device or service type.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NotificationType {
+    /// Corresponds to the value `upnp:rootdevice`
+    RootDevice,
+    /// Corresponds to the value `uuid:{device-UUID}`
+    Device(Udn),
+    /// Corresponds to the value `urn:schemas-upnp-org:device:{deviceType:ver}`
+    DeviceType(String),
+    /// Corresponds to the value `urn:schemas-upnp-org:service:{serviceType:ver}`
+    ServiceType(String),
+    /// Corresponds to the value `urn:{domain-name}:device:{deviceType:ver}`
+    DomainDeviceType(String, String),
+    /// Corresponds to the value `urn:{domain-name}:service:{serviceType:ver}`
+    DomainServiceType(String, String),
+}
+
+///
+/// A _Unique Service Name_, the value of the `USN` header. This is composed of the device's UDN
+/// and, except for the device UUID advertisement itself, the notification type being advertised
+/// separated by `::`. For example `uuid:{device-UUID}::upnp:rootdevice`.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UniqueServiceName {
+    device: Udn,
+    notification_type: Option<SearchTarget>,
+}
+
+///
+/// A single advertisement of a device, and the values sent with it; shared by the notifications
+/// sent by [`notify`], the responses built by [`respond`], and an [`notify::Advertiser`].
 ///
 #[derive(Clone, Debug)]
-pub struct ProductVersion {
-    name: String,
-    version: String,
+pub struct SsdpDeviceInfo {
+    /// The advertised type, sent in the `NT` header and matched against a search's `ST` header.
+    pub notification_type: NotificationType,
+    /// The identifier of the advertisement, sent in the `USN` header.
+    pub service_name: UniqueServiceName,
+    /// The location of the device description, sent in the `LOCATION` header.
+    pub location: URL,
+    /// The boot and configuration identifiers, sent from UDA 1.1 onwards.
+    pub identity: SsdpIdentity,
+    /// The port on which the device answers unicast searches, sent from UDA 1.1 onwards; this
+    /// must be in the range `DYNAMIC_PORTS`.
+    pub search_port: Option<u16>,
+    /// The `https` location of the device description, sent in the `SECURELOCATION.UPNP.ORG`
+    /// header from UDA 2.0 onwards.
+    pub secure_location: Option<String>,
 }
 
 ///
-/// The set of three products, and associated version identifiers, present in both `SERVER` and
-/// `CACHE-CONTROL` headers.
+/// Constructs an [`SsdpDeviceInfo`], validating the values provided when built.
 ///
 #[derive(Clone, Debug)]
-pub struct ProductVersions {
-    product: ProductVersion,
-    upnp: ProductVersion,
-    platform: ProductVersion,
+pub struct SsdpDeviceInfoBuilder {
+    device: SsdpDeviceInfo,
+    boot_id: Option<u32>,
+    config_id: Option<u32>,
 }
 
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
-const DEFAULT_PRODUCT_NAME: &str = env!("CARGO_PKG_NAME");
-const DEFAULT_PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+impl ControlPoint {
+    ///
+    /// Create a control point with a newly generated (version 4) UUID.
+    ///
+    pub fn new<S: Into<String>>(friendly_name: S) -> Self {
+        Self {
+            friendly_name: friendly_name.into(),
+            uuid: Some(Uuid::new_v4()),
+            port: None,
+        }
+    }
+
+    ///
+    /// Create a control point with the provided UUID, which may include the `uuid:` prefix; an
+    /// error is returned if the value is not a valid UUID.
+    ///
+    pub fn with_uuid<S: Into<String>>(friendly_name: S, uuid: &str) -> Result<Self, Error> {
+        Ok(Self {
+            friendly_name: friendly_name.into(),
+            uuid: Some(parse_control_point_uuid(uuid)?),
+            port: None,
+        })
+    }
 
-lazy_static! {
-    static ref PLATFORM_NAME: String = platform_name();
-    static ref PLATFORM_VERSION: String = platform_version();
+    ///
+    /// Create a control point using the UUID stored in the file at `path`, or with a newly
+    /// generated UUID which is then stored in `path`, so that the same UUID is used each time the
+    /// control point runs.
+    ///
+    pub fn load_or_create<P, S>(path: P, friendly_name: S) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        let path = path.as_ref();
+        let stored = match read_to_string(path) {
+            Ok(content) => match content
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim() == HTTP_HEADER_CP_UUID)
+            {
+                Some((_, value)) => Some(parse_control_point_uuid(value.trim())?),
+                None => return invalid_field_value("control_point", content).into(),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let control_point = match stored {
+            Some(uuid) => Self {
+                friendly_name: friendly_name.into(),
+                uuid: Some(uuid),
+                port: None,
+            },
+            None => {
+                let control_point = Self::new(friendly_name);
+                write(
+                    path,
+                    format!(
+                        "{}: {}\n",
+                        HTTP_HEADER_CP_UUID,
+                        control_point.uuid.unwrap().hyphenated()
+                    ),
+                )?;
+                control_point
+            }
+        };
+        info!(
+            "load_or_create - friendly_name: {}, uuid: {:?}",
+            control_point.friendly_name, control_point.uuid
+        );
+        Ok(control_point)
+    }
 }
 
-impl Display for ProductVersion {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}/{}", self.name, self.version)
+// ------------------------------------------------------------------------------------------------
+
+impl SsdpDeviceInfo {
+    ///
+    /// Returns a builder for the advertisement of `notification_type`, identified by
+    /// `service_name`, for the device described at `location`.
+    ///
+    pub fn builder(
+        notification_type: NotificationType,
+        service_name: UniqueServiceName,
+        location: URL,
+    ) -> SsdpDeviceInfoBuilder {
+        SsdpDeviceInfoBuilder::new(notification_type, service_name, location)
+    }
+
+    ///
+    /// Validate the values that are not constrained by their type; the search port must be in
+    /// the range `DYNAMIC_PORTS`, and any secure location must be an `https` URL.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(search_port) = self.search_port {
+            if !DYNAMIC_PORTS.contains(&search_port) {
+                error!("validate - search_port must be in {:?}", DYNAMIC_PORTS);
+                return invalid_field_value("search_port", search_port.to_string()).into();
+            }
+        }
+        if let Some(secure_location) = &self.secure_location {
+            if !secure_location.starts_with("https://") || URL::from_str(secure_location).is_err() {
+                error!("validate - secure_location must be an https URL");
+                return invalid_field_value("secure_location", secure_location).into();
+            }
+        }
+        Ok(())
     }
 }
 
-impl ProductVersion {
-    pub fn for_default_product() -> Self {
+// ------------------------------------------------------------------------------------------------
+
+impl SsdpDeviceInfoBuilder {
+    ///
+    /// Create a builder, the identity defaults to `SsdpIdentity::new(0)`.
+    ///
+    pub fn new(
+        notification_type: NotificationType,
+        service_name: UniqueServiceName,
+        location: URL,
+    ) -> Self {
         Self {
-            name: DEFAULT_PRODUCT_NAME.to_string(),
-            version: DEFAULT_PRODUCT_VERSION.to_string(),
+            device: SsdpDeviceInfo {
+                notification_type,
+                service_name,
+                location,
+                identity: SsdpIdentity::new(0),
+                search_port: None,
+                secure_location: None,
+            },
+            boot_id: None,
+            config_id: None,
         }
     }
-    pub fn for_product(name: &str, version: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            version: version.to_string(),
+
+    pub fn identity(&mut self, identity: SsdpIdentity) -> &mut Self {
+        self.device.identity = identity;
+        self
+    }
+
+    ///
+    /// Replace the boot identifier of the identity; this must be no greater than `MAX_BOOT_ID`.
+    ///
+    pub fn boot_id(&mut self, boot_id: u32) -> &mut Self {
+        self.boot_id = Some(boot_id);
+        self
+    }
+
+    ///
+    /// Replace the configuration identifier of the identity; this must be no greater than
+    /// `MAX_CONFIG_ID`.
+    ///
+    pub fn config_id(&mut self, config_id: u32) -> &mut Self {
+        self.config_id = Some(config_id);
+        self
+    }
+
+    pub fn search_port(&mut self, search_port: u16) -> &mut Self {
+        self.device.search_port = Some(search_port);
+        self
+    }
+
+    pub fn secure_location(&mut self, secure_location: &str) -> &mut Self {
+        self.device.secure_location = Some(secure_location.to_string());
+        self
+    }
+
+    ///
+    /// Validate all values and return the completed device information.
+    ///
+    pub fn build(&self) -> Result<SsdpDeviceInfo, Error> {
+        let mut device = self.device.clone();
+        if self.boot_id.is_some() || self.config_id.is_some() {
+            let boot_id = self.boot_id.unwrap_or_else(|| device.identity.boot_id());
+            if boot_id > MAX_BOOT_ID {
+                error!("build - boot_id must be no greater than {}", MAX_BOOT_ID);
+                return invalid_field_value("boot_id", boot_id.to_string()).into();
+            }
+            let config_id = self
+                .config_id
+                .unwrap_or_else(|| device.identity.config_id());
+            if config_id > MAX_CONFIG_ID {
+                error!(
+                    "build - config_id must be no greater than {}",
+                    MAX_CONFIG_ID
+                );
+                return invalid_field_value("config_id", config_id.to_string()).into();
+            }
+            device.identity = SsdpIdentity::with_values(boot_id, config_id);
         }
+        device.validate()?;
+        Ok(device)
     }
+}
 
-    pub fn for_default_upnp() -> Self {
-        Self {
-            name: UPNP_STRING.to_string(),
-            version: SpecVersion::default().to_string(),
+// ------------------------------------------------------------------------------------------------
+
+impl Display for NotificationSubtype {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", Nts::from(*self))
+    }
+}
+
+impl FromStr for NotificationSubtype {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NotificationSubtype::try_from(Nts::from_str(s)?)
+    }
+}
+
+impl From<NotificationSubtype> for Nts {
+    fn from(subtype: NotificationSubtype) -> Self {
+        match subtype {
+            NotificationSubtype::Alive => Nts::Alive,
+            NotificationSubtype::Update => Nts::Update,
+            NotificationSubtype::ByeBye => Nts::ByeBye,
         }
     }
+}
 
-    pub fn for_upnp_version(version: SpecVersion) -> Self {
-        Self {
-            name: UPNP_STRING.to_string(),
-            version: version.to_string(),
+impl TryFrom<Nts> for NotificationSubtype {
+    type Error = MessageFormatError;
+
+    fn try_from(nts: Nts) -> Result<Self, Self::Error> {
+        match nts {
+            Nts::Alive => Ok(NotificationSubtype::Alive),
+            Nts::Update => Ok(NotificationSubtype::Update),
+            Nts::ByeBye => Ok(NotificationSubtype::ByeBye),
+            Nts::PropertyChange => invalid_header_value(HTTP_HEADER_NTS, nts.as_str()).into(),
         }
     }
+}
 
-    pub fn for_platform() -> Self {
-        Self {
-            name: PLATFORM_NAME.clone(),
-            version: PLATFORM_VERSION.clone(),
+// ------------------------------------------------------------------------------------------------
+
+impl Display for NotificationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", SearchTarget::from(self.clone()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for NotificationType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for NotificationType {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NotificationType::try_from(SearchTarget::from_str(s)?)
+    }
+}
+
+impl From<NotificationType> for SearchTarget {
+    fn from(notification_type: NotificationType) -> Self {
+        match notification_type {
+            NotificationType::RootDevice => SearchTarget::RootDevice,
+            NotificationType::Device(udn) => SearchTarget::Device(udn),
+            NotificationType::DeviceType(device) => SearchTarget::DeviceType(device),
+            NotificationType::ServiceType(service) => SearchTarget::ServiceType(service),
+            NotificationType::DomainDeviceType(domain, device) => {
+                SearchTarget::DomainDeviceType(domain, device)
+            }
+            NotificationType::DomainServiceType(domain, service) => {
+                SearchTarget::DomainServiceType(domain, service)
+            }
         }
     }
+}
 
-    pub fn name(&self) -> &String {
-        &self.name
+impl TryFrom<SearchTarget> for NotificationType {
+    type Error = MessageFormatError;
+
+    ///
+    /// Convert a search target to the corresponding notification type; `ssdp:all` is rejected,
+    /// and the versioned type targets are converted to their minimum version.
+    ///
+    fn try_from(search_target: SearchTarget) -> Result<Self, Self::Error> {
+        match search_target.unversioned() {
+            SearchTarget::All => {
+                invalid_value_for_type("NotificationType", search_target.to_string()).into()
+            }
+            SearchTarget::RootDevice => Ok(NotificationType::RootDevice),
+            SearchTarget::Device(udn) => Ok(NotificationType::Device(udn)),
+            SearchTarget::DeviceType(device) => Ok(NotificationType::DeviceType(device)),
+            SearchTarget::ServiceType(service) => Ok(NotificationType::ServiceType(service)),
+            SearchTarget::DomainDeviceType(domain, device) => {
+                Ok(NotificationType::DomainDeviceType(domain, device))
+            }
+            SearchTarget::DomainServiceType(domain, service) => {
+                Ok(NotificationType::DomainServiceType(domain, service))
+            }
+            SearchTarget::DeviceTypeVersioned { .. }
+            | SearchTarget::ServiceTypeVersioned { .. } => {
+                unreachable!()
+            }
+        }
     }
+}
 
-    pub fn version(&self) -> &String {
-        &self.version
+impl From<&TypeID> for NotificationType {
+    fn from(type_id: &TypeID) -> Self {
+        NotificationType::try_from(SearchTarget::from(type_id)).unwrap()
+    }
+}
+
+impl NotificationType {
+    ///
+    /// The device or service type, `None` if this is the root device or a device UUID.
+    ///
+    pub fn type_id(&self) -> Option<TypeID> {
+        TypeID::try_from(SearchTarget::from(self.clone())).ok()
     }
 }
 
 // ------------------------------------------------------------------------------------------------
 
-impl Default for ProductVersions {
-    fn default() -> Self {
-        Self {
-            product: ProductVersion::for_default_product(),
-            upnp: ProductVersion::for_default_upnp(),
-            platform: ProductVersion::for_platform(),
+const USN_SEPARATOR: &str = "::";
+
+impl Display for UniqueServiceName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match &self.notification_type {
+            None => write!(f, "{}", self.device),
+            Some(nt) => write!(f, "{}{}{}", self.device, USN_SEPARATOR, nt),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for UniqueServiceName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for UniqueServiceName {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(USN_SEPARATOR) {
+            None => Ok(UniqueServiceName::new(Udn::from_str(s)?)),
+            Some((device, notification_type)) => UniqueServiceName::with_type(
+                Udn::from_str(device)?,
+                SearchTarget::from_str(notification_type)?,
+            ),
         }
     }
 }
 
-impl Display for ProductVersions {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{} {} {}", self.product, self.upnp, self.platform)
+impl From<Udn> for UniqueServiceName {
+    fn from(device: Udn) -> Self {
+        UniqueServiceName::new(device)
     }
 }
 
-impl ProductVersions {
-    pub fn new(product: ProductVersion, upnp: ProductVersion, platform: ProductVersion) -> Self {
+impl UniqueServiceName {
+    ///
+    /// Construct the USN used to advertise the device UUID itself.
+    ///
+    pub fn new(device: Udn) -> Self {
         Self {
-            product,
-            upnp,
-            platform,
+            device,
+            notification_type: None,
         }
     }
 
-    pub fn product_version(&self) -> &ProductVersion {
-        &self.product
+    ///
+    /// Construct the USN used to advertise a root device.
+    ///
+    pub fn root_device(device: Udn) -> Self {
+        Self {
+            device,
+            notification_type: Some(SearchTarget::RootDevice),
+        }
+    }
+
+    ///
+    /// Construct a USN for the device and notification type. Only the root device, device type,
+    /// and service type targets are valid, `ssdp:all` and `uuid:` targets are rejected.
+    ///
+    pub fn with_type(
+        device: Udn,
+        notification_type: SearchTarget,
+    ) -> Result<Self, MessageFormatError> {
+        match notification_type {
+            SearchTarget::All | SearchTarget::Device(_) => {
+                invalid_value_for_type("UniqueServiceName", notification_type.to_string()).into()
+            }
+            _ => Ok(Self {
+                device,
+                notification_type: Some(notification_type.unversioned()),
+            }),
+        }
+    }
+
+    ///
+    /// The UDN of the device.
+    ///
+    pub fn device(&self) -> &Udn {
+        &self.device
+    }
+
+    ///
+    /// The notification type portion, if present.
+    ///
+    pub fn notification_type(&self) -> Option<&SearchTarget> {
+        self.notification_type.as_ref()
     }
 
-    pub fn upnp_version(&self) -> &ProductVersion {
-        &self.upnp
+    pub fn is_root_device(&self) -> bool {
+        matches!(self.notification_type, Some(SearchTarget::RootDevice))
     }
 
-    pub fn platform_version(&self) -> &ProductVersion {
-        &self.platform
+    pub fn is_device_type(&self) -> bool {
+        matches!(
+            self.notification_type,
+            Some(SearchTarget::DeviceType(_)) | Some(SearchTarget::DomainDeviceType(_, _))
+        )
+    }
+
+    pub fn is_service_type(&self) -> bool {
+        matches!(
+            self.notification_type,
+            Some(SearchTarget::ServiceType(_)) | Some(SearchTarget::DomainServiceType(_, _))
+        )
     }
 }
 
@@ -215,26 +628,13 @@ impl ProductVersions {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn platform_name() -> String {
-    let version = detect().expect("Could not detect platform name/version");
-    match version {
-        OsVersion::Linux(v) => format!("linux/{}", v.distro),
-        OsVersion::MacOS(_) => "macos".to_string(),
-        OsVersion::Windows(_) => "windows".to_string(),
-        OsVersion::OpenBSD(_) => "OpenBSD".to_string(),
-        _ => panic!("Unknown or unsupported platform"),
-    }
-}
-
-fn platform_version() -> String {
-    let version = detect().expect("Could not detect platform name/version");
-    match version {
-        OsVersion::Linux(v) => v.version.expect("No version information for Linux"),
-        OsVersion::MacOS(v) => v.version,
-        OsVersion::Windows(v) => v.version,
-        OsVersion::OpenBSD(v) => v.version,
-        _ => panic!("Unknown or unsupported platform"),
-    }
+///
+/// Parse the value of a `CPUUID.UPNP.ORG` header, or a stored control point UUID; the `uuid:`
+/// prefix is optional.
+///
+pub(crate) fn parse_control_point_uuid(value: &str) -> Result<Uuid, MessageFormatError> {
+    Uuid::parse_str(value.strip_prefix("uuid:").unwrap_or(value))
+        .map_err(|_| invalid_header_value(HTTP_HEADER_CP_UUID, value))
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -244,3 +644,140 @@ fn platform_version() -> String {
 pub mod search;
 
 pub mod notify;
+
+pub mod identity;
+
+pub mod listen;
+
+pub mod probe;
+
+pub mod respond;
+
+pub mod rules;
+
+pub mod validation;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_point_uuid() {
+        let control_point = ControlPoint::new("Test");
+        assert_eq!(control_point.uuid.unwrap().get_version_num(), 4);
+        assert_ne!(ControlPoint::new("Test").uuid, control_point.uuid);
+
+        let control_point =
+            ControlPoint::with_uuid("Test", "uuid:2fac1234-31f8-11b4-a222-08002b34c003").unwrap();
+        assert_eq!(
+            control_point.uuid.unwrap().to_string(),
+            "2fac1234-31f8-11b4-a222-08002b34c003"
+        );
+        assert!(ControlPoint::with_uuid("Test", "uuid:test-cp").is_err());
+    }
+
+    #[test]
+    fn test_control_point_load_or_create() {
+        let path = std::env::temp_dir().join(format!("upnp-rs-cpuuid-{}", std::process::id()));
+        let created = ControlPoint::load_or_create(&path, "First").unwrap();
+        let loaded = ControlPoint::load_or_create(&path, "Second").unwrap();
+        assert_eq!(loaded.friendly_name, "Second");
+        assert_eq!(loaded.uuid, created.uuid);
+
+        std::fs::write(&path, "nothing useful\n").unwrap();
+        assert!(ControlPoint::load_or_create(&path, "Third").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_device_info_builder() {
+        let mut builder = SsdpDeviceInfo::builder(
+            NotificationType::RootDevice,
+            UniqueServiceName::from_str("uuid:device-1::upnp:rootdevice").unwrap(),
+            URL::from_str("http://10.0.0.1/description.xml").unwrap(),
+        );
+        builder.boot_id(7).config_id(3).search_port(49152);
+        let device = builder.build().unwrap();
+        assert_eq!(device.identity.boot_id(), 7);
+        assert_eq!(device.identity.config_id(), 3);
+        assert_eq!(device.search_port, Some(49152));
+
+        assert!(builder.clone().boot_id(MAX_BOOT_ID + 1).build().is_err());
+        assert!(builder
+            .clone()
+            .config_id(MAX_CONFIG_ID + 1)
+            .build()
+            .is_err());
+        assert!(builder.clone().search_port(1901).build().is_err());
+        assert!(builder
+            .clone()
+            .secure_location("http://10.0.0.1/description.xml")
+            .build()
+            .is_err());
+        assert!(builder
+            .secure_location("https://10.0.0.1/description.xml")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_notification_type() {
+        assert!(NotificationType::from_str("ssdp:all").is_err());
+        assert_eq!(
+            NotificationType::from_str("upnp:rootdevice").unwrap(),
+            NotificationType::RootDevice
+        );
+        let service_type =
+            NotificationType::from_str("urn:schemas-upnp-org:service:ContentDirectory:1").unwrap();
+        assert_eq!(
+            service_type,
+            NotificationType::ServiceType("ContentDirectory:1".to_string())
+        );
+        assert_eq!(
+            service_type.to_string(),
+            "urn:schemas-upnp-org:service:ContentDirectory:1"
+        );
+        assert!(service_type.type_id().is_some());
+        assert_eq!(
+            SearchTarget::from(service_type),
+            SearchTarget::ServiceType("ContentDirectory:1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_usn_parse() {
+        let usn = UniqueServiceName::from_str("uuid:device-1").unwrap();
+        assert_eq!(usn.device().to_string(), "uuid:device-1");
+        assert!(usn.notification_type().is_none());
+
+        let usn = UniqueServiceName::from_str("uuid:device-1::upnp:rootdevice").unwrap();
+        assert!(usn.is_root_device());
+        assert_eq!(usn.to_string(), "uuid:device-1::upnp:rootdevice");
+
+        let usn = UniqueServiceName::from_str(
+            "uuid:device-1::urn:schemas-upnp-org:service:ContentDirectory:1",
+        )
+        .unwrap();
+        assert!(usn.is_service_type());
+        assert_eq!(
+            usn.notification_type(),
+            Some(&SearchTarget::ServiceType("ContentDirectory:1".to_string()))
+        );
+
+        let usn =
+            UniqueServiceName::from_str("uuid:device-1::urn:example-com:device:Thing:2").unwrap();
+        assert!(usn.is_device_type());
+    }
+
+    #[test]
+    fn test_usn_invalid() {
+        assert!(UniqueServiceName::from_str("undefined").is_err());
+        assert!(UniqueServiceName::from_str("uuid:device-1::").is_err());
+        assert!(UniqueServiceName::from_str("uuid:device-1::uuid:device-2").is_err());
+        assert!(UniqueServiceName::from_str("uuid:device-1::ssdp:all").is_err());
+    }
+}
@@ -1,38 +1,71 @@
 /*!
 This module provides three functions that provide 1) device available, 2) device updated, and
 3) device leaving notifications over multicast UDP.
+
+A device sends one message for each of its advertisements, [`advertisements_for`] expands a device
+description into the full set required by the specification. An [`Advertiser`] sends the complete
+set, and when shut down, or dropped, revokes every advertisement it sent with a burst of
+`ssdp:byebye` messages; [`device_gone_all`] sends the same burst for a device without one.
+
+# Example
+
+```rust,no_run
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::discovery::identity::SsdpIdentity;
+use upnp_rs::discovery::notify::{advertisements_for, device_available, Options};
+use upnp_rs::common::uri::URL;
+use upnp_rs::SpecVersion;
+use std::str::FromStr;
+
+# fn root() -> DeviceRoot { unimplemented!() }
+let root: DeviceRoot = root();
+let location = URL::from_str("http://10.0.0.1:8080/description.xml").unwrap();
+let identity = SsdpIdentity::new(1);
+for advertisement in advertisements_for(&root, &location) {
+    let device = advertisement.into_device(identity.clone());
+    device_available(&device, Options::default_for(SpecVersion::V11)).unwrap();
+}
+```
 */
-use crate::common::httpu::{multicast_once, Options as MulticastOptions, RequestBuilder};
+use crate::common::httpu::{
+    multicast_once, Options as MulticastOptions, RequestBuilder, Transport,
+};
 use crate::common::interface::IP;
-use crate::common::uri::{URI, URL};
+use crate::common::uri::URL;
 use crate::common::user_agent::user_agent_string;
-use crate::discovery::search::SearchTarget;
-use crate::discovery::ProductVersion;
-use crate::error::{unsupported_version, Error};
-use crate::syntax::{
-    HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID, HTTP_HEADER_HOST,
-    HTTP_HEADER_LOCATION, HTTP_HEADER_NEXT_BOOTID, HTTP_HEADER_NT, HTTP_HEADER_NTS,
-    HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SERVER, HTTP_HEADER_USN, HTTP_METHOD_NOTIFY,
-    MULTICAST_ADDRESS, NTS_ALIVE, NTS_BYE, NTS_UPDATE,
+use crate::description::device::DeviceRoot;
+use crate::discovery::identity::SsdpIdentity;
+use crate::discovery::rules::{HeaderRules, SsdpMessage};
+use crate::discovery::{
+    NotificationSubtype, NotificationType, ProductVersion, SsdpDeviceInfo, UniqueServiceName,
 };
+use crate::error::{invalid_field_value, unsupported_version, Error};
+use crate::syntax::{HeaderName, Method, MULTICAST_ADDRESS};
 use crate::SpecVersion;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+use tracing::{debug, error, info_span, warn};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
 ///
-/// Description of a device sent in _alive_ and _update_ messages.
+/// Description of a device sent in _alive_ and _update_ messages, the same values are used in
+/// search responses.
 ///
-#[derive(Clone, Debug)]
-pub struct Device {
-    pub notification_type: SearchTarget,
-    pub service_name: URI,
+pub type Device = SsdpDeviceInfo;
+
+///
+/// A single advertisement, the `NT` and `USN` pair sent in one notification, and the location of
+/// the description of the device that makes it.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Advertisement {
+    pub notification_type: NotificationType,
+    pub service_name: UniqueServiceName,
     pub location: URL,
-    pub boot_id: u32,
-    pub config_id: u64,
-    pub search_port: Option<u16>,
-    pub secure_location: Option<String>,
 }
 
 ///
@@ -44,25 +77,101 @@ pub struct Options {
     /// The specification that will be used to construct sent messages and to verify responses.
     /// Default: `SpecVersion:V10`.
     pub spec_version: SpecVersion,
-    /// A specific network interface to bind to; if specified the default address for the interface
-    /// will be used, else the address `0.0.0.0:0` will be used. Default: `None`.
+    /// A specific network interface to bind to, by name, index, or network (for example
+    /// `192.168.1.0/24`); if specified the interface's address will be used, else the address
+    /// `0.0.0.0:0` will be used. A value matching no interface is an error. Default: `None`.
     pub network_interface: Option<String>,
     /// Denotes whether the implementation wants to only use IPv4, IPv6, or doesn't care.
     pub network_version: Option<IP>,
     /// The IP packet TTL value.
     pub packet_ttl: u32,
+    /// The TTL, or IPv6 hop limit, of multicast messages. Default: the value recommended by
+    /// `spec_version`, `4` for 1.0 and `2` for later versions.
+    pub multicast_ttl: u32,
     /// The value used to control caching of these notifications by control points.
     pub max_age: u16,
     /// If specified this is to be the `ProduceName/Version` component of the user agent string
-    /// the client will generate as part of sent messages. If not specified a default value based
-    /// on the name and version of this crate will be used. Default: `None`.
+    /// the client will generate as part of sent messages. If not specified the product of the
+    /// value set with `user_agent::set_product_versions` is used, or a default value based on the
+    /// name and version of this crate. Default: `None`.
     pub product_and_version: Option<ProductVersion>,
+    /// If specified, notifications are sent using this transport rather than a newly created UDP
+    /// socket. Required without the `net` feature. Default: `None`.
+    pub transport: Option<Arc<dyn Transport>>,
+    /// The number of times the complete set of `ssdp:byebye` messages is sent on shutdown, as
+    /// UDP delivery is unreliable. Default: `2`.
+    pub byebye_repeat: u8,
+    /// The delay between each `ssdp:byebye` message sent on shutdown. Default: `50ms`.
+    pub byebye_interval: Duration,
+}
+
+///
+/// Constructs a set of [`Options`], validating the values provided when built.
+///
+#[derive(Clone, Debug)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+///
+/// Sends the advertisements for a device, and revokes them when shut down. If any advertisements
+/// have been sent, dropping the advertiser will call [`Advertiser::shutdown`].
+///
+#[derive(Debug)]
+pub struct Advertiser {
+    devices: Vec<SsdpDeviceInfo>,
+    options: Options,
+    announced: bool,
 }
 
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+/**
+Returns the full set of advertisements for the device `root`, its embedded devices, and their
+services, in the order they should be sent.
+
+# Specification
+
+A root device sends three discovery messages, `upnp:rootdevice`, its `uuid:`, and its device type,
+each embedded device sends two, its `uuid:` and its device type, and each device sends one for
+each distinct service type it contains. The `USN` of each message is the device's UDN, combined
+with the `NT` value except for the `uuid:` message.
+
+*/
+pub fn advertisements_for(root: &DeviceRoot, location: &URL) -> Vec<Advertisement> {
+    advertised_pairs(root)
+        .into_iter()
+        .map(|(notification_type, service_name)| Advertisement {
+            notification_type,
+            service_name,
+            location: location.clone(),
+        })
+        .collect()
+}
+
+/**
+Send an `ssdp:byebye` message for every advertisement of the device `root`, see
+[`advertisements_for`], repeating the set `options.byebye_repeat` times with a delay of
+`options.byebye_interval` between each message. All messages are sent even if some fail, the
+first error is returned.
+
+*/
+pub fn device_gone_all(
+    root: &DeviceRoot,
+    identity: &SsdpIdentity,
+    options: Options,
+) -> Result<(), Error> {
+    let pairs = advertised_pairs(root);
+    byebye_burst(
+        pairs
+            .iter()
+            .map(|(notification_type, service_name)| (notification_type, service_name, identity)),
+        &options,
+    )
+}
+
 /**
 Provides an implementation of the `ssdp:alive` notification.
 
@@ -81,51 +190,42 @@ components:
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
 
 */
-pub fn device_available(device: &mut Device, options: Options) -> Result<(), Error> {
-    let next_boot_id = device.boot_id + 1;
-    let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
+pub fn device_available(device: &SsdpDeviceInfo, options: Options) -> Result<(), Error> {
+    let _span = info_span!(
+        "notify",
+        nts = %NotificationSubtype::Alive,
+        nt = %device.notification_type,
+        usn = %device.service_name
+    )
+    .entered();
+    debug!("sending notification");
+    options.validate()?;
+    device.validate()?;
+    let rules = HeaderRules::new(SsdpMessage::Alive, options.spec_version);
+    let mut message_builder = RequestBuilder::new(Method::Notify.as_str());
     message_builder
-        .add_header(HTTP_HEADER_HOST, MULTICAST_ADDRESS)
-        .add_header(
-            HTTP_HEADER_CACHE_CONTROL,
-            &format!("max-age={}", options.max_age),
-        )
-        .add_header(HTTP_HEADER_LOCATION, &device.location.to_string())
-        .add_header(HTTP_HEADER_NT, &device.notification_type.to_string())
-        .add_header(HTTP_HEADER_NTS, NTS_ALIVE)
-        .add_header(
-            HTTP_HEADER_SERVER,
+        .cache_control_max_age(options.max_age.into())
+        .header(HeaderName::Location, &device.location.to_string())
+        .header(HeaderName::Nt, &device.notification_type.to_string())
+        .header(HeaderName::Nts, &NotificationSubtype::Alive.to_string())
+        .header(
+            HeaderName::Server,
             &user_agent_string(options.spec_version, options.product_and_version.clone()),
         )
-        .add_header(HTTP_HEADER_USN, &device.service_name.to_string());
-
-    if options.spec_version >= SpecVersion::V11 {
-        message_builder
-            .add_header(HTTP_HEADER_BOOTID, &device.boot_id.to_string())
-            .add_header(HTTP_HEADER_CONFIGID, &device.config_id.to_string());
-        if let Some(search_port) = &device.search_port {
-            message_builder.add_header(HTTP_HEADER_SEARCH_PORT, &search_port.to_string());
-        }
-    }
-
-    if options.spec_version >= SpecVersion::V20 {
-        if let Some(secure_location) = &device.secure_location {
-            message_builder.add_header(HTTP_HEADER_USN, secure_location);
-        }
-    }
+        .header(HeaderName::Usn, &device.service_name.to_string());
+    add_identity_headers(&mut message_builder, &rules, device);
 
     multicast_once(
-        &message_builder.into(),
+        &message_builder.build()?,
         &MULTICAST_ADDRESS.parse().unwrap(),
         &options.into(),
     )?;
 
-    device.boot_id = next_boot_id;
     Ok(())
 }
 
@@ -159,43 +259,43 @@ interface on which the advertisement is sent.
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications. The `NEXTBOOTID.UPNP.ORG` value is taken from
+  `SsdpIdentity::begin_update`; once all update messages have been sent the caller completes the
+  change with `SsdpIdentity::complete_update`, before sending any announcements.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
 
 */
-pub fn device_update(device: &mut Device, options: Options) -> Result<(), Error> {
-    if options.spec_version == SpecVersion::V10 {
+pub fn device_update(device: &mut SsdpDeviceInfo, options: Options) -> Result<(), Error> {
+    let _span = info_span!(
+        "notify",
+        nts = %NotificationSubtype::Update,
+        nt = %device.notification_type,
+        usn = %device.service_name
+    )
+    .entered();
+    debug!("sending notification");
+    options.validate()?;
+    device.validate()?;
+    let rules = HeaderRules::new(SsdpMessage::Update, options.spec_version);
+    if !rules.is_supported() {
         unsupported_version(options.spec_version).into()
     } else {
-        let next_boot_id = device.boot_id + 1;
-        let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
+        let next_boot_id = device.identity.begin_update();
+        let mut message_builder = RequestBuilder::new(Method::Notify.as_str());
         message_builder
-            .add_header(HTTP_HEADER_HOST, MULTICAST_ADDRESS)
-            .add_header(HTTP_HEADER_LOCATION, &device.location.to_string())
-            .add_header(HTTP_HEADER_NT, &device.notification_type.to_string())
-            .add_header(HTTP_HEADER_NTS, NTS_UPDATE)
-            .add_header(HTTP_HEADER_USN, &device.service_name.to_string())
-            .add_header(HTTP_HEADER_BOOTID, &device.boot_id.to_string())
-            .add_header(HTTP_HEADER_NEXT_BOOTID, &next_boot_id.to_string())
-            .add_header(HTTP_HEADER_CONFIGID, &device.config_id.to_string());
-
-        if let Some(search_port) = &device.search_port {
-            message_builder.add_header(HTTP_HEADER_SEARCH_PORT, &search_port.to_string());
-        }
-
-        if options.spec_version >= SpecVersion::V20 {
-            if let Some(secure_location) = &device.secure_location {
-                message_builder.add_header(HTTP_HEADER_USN, secure_location);
-            }
-        }
+            .header(HeaderName::Location, &device.location.to_string())
+            .header(HeaderName::Nt, &device.notification_type.to_string())
+            .header(HeaderName::Nts, &NotificationSubtype::Update.to_string())
+            .header(HeaderName::Usn, &device.service_name.to_string())
+            .header(HeaderName::NextBootId, &next_boot_id.to_string());
+        add_identity_headers(&mut message_builder, &rules, device);
 
         multicast_once(
-            &message_builder.into(),
+            &message_builder.build()?,
             &MULTICAST_ADDRESS.parse().unwrap(),
             &options.into(),
         )?;
-        device.boot_id = next_boot_id;
         Ok(())
     }
 }
@@ -219,33 +319,18 @@ request must have method `NOTIFY` and `ssdp:byeby`e in the `NTS` header in the f
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
 
 */
-pub fn device_unavailable(device: &mut Device, options: Options) -> Result<(), Error> {
-    let next_boot_id = device.boot_id + 1;
-    let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
-    message_builder
-        .add_header(HTTP_HEADER_HOST, MULTICAST_ADDRESS)
-        .add_header(HTTP_HEADER_NT, &device.notification_type.to_string())
-        .add_header(HTTP_HEADER_NTS, NTS_BYE)
-        .add_header(HTTP_HEADER_USN, &device.service_name.to_string());
-
-    if options.spec_version >= SpecVersion::V11 {
-        message_builder
-            .add_header(HTTP_HEADER_BOOTID, &device.boot_id.to_string())
-            .add_header(HTTP_HEADER_CONFIGID, &device.config_id.to_string());
-    }
-
-    multicast_once(
-        &message_builder.into(),
-        &MULTICAST_ADDRESS.parse().unwrap(),
-        &options.into(),
-    )?;
-    device.boot_id = next_boot_id;
-    Ok(())
+pub fn device_unavailable(device: &SsdpDeviceInfo, options: Options) -> Result<(), Error> {
+    send_byebye(
+        &device.notification_type,
+        &device.service_name,
+        &device.identity,
+        options,
+    )
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -254,6 +339,32 @@ pub fn device_unavailable(device: &mut Device, options: Options) -> Result<(), E
 
 const CACHE_CONTROL_MAX_AGE: u16 = 1800;
 
+///
+/// The smallest `max_age` accepted by `OptionsBuilder::build`; the specification recommends that
+/// advertisements remain valid for at least 30 minutes.
+///
+const MIN_MAX_AGE: u16 = 1800;
+
+const DEFAULT_BYEBYE_REPEAT: u8 = 2;
+
+const DEFAULT_BYEBYE_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Advertisement {
+    ///
+    /// Construct the device description used to send notifications for this advertisement.
+    ///
+    pub fn into_device(self, identity: SsdpIdentity) -> SsdpDeviceInfo {
+        SsdpDeviceInfo {
+            notification_type: self.notification_type,
+            service_name: self.service_name,
+            location: self.location,
+            identity,
+            search_port: None,
+            secure_location: None,
+        }
+    }
+}
+
 impl Options {
     pub fn default_for(spec_version: SpecVersion) -> Self {
         Options {
@@ -266,18 +377,439 @@ impl Options {
             } else {
                 2
             },
+            multicast_ttl: spec_version.default_multicast_ttl(),
             product_and_version: None,
+            transport: None,
+            byebye_repeat: DEFAULT_BYEBYE_REPEAT,
+            byebye_interval: DEFAULT_BYEBYE_INTERVAL,
+        }
+    }
+
+    ///
+    /// Validate all options, ensuring values are within the ranges required by the
+    /// specification. A `max_age` below the recommended minimum is logged, but accepted.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.max_age < MIN_MAX_AGE {
+            warn!(
+                "validate - max_age should be at least {} seconds ({})",
+                MIN_MAX_AGE, self.max_age
+            );
+        }
+        if self.multicast_ttl < 1 || self.multicast_ttl > 255 {
+            error!(
+                "validate - multicast_ttl must be between 1..255 ({})",
+                self.multicast_ttl
+            );
+            return invalid_field_value("multicast_ttl", self.multicast_ttl.to_string()).into();
+        }
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl OptionsBuilder {
+    ///
+    /// Create a builder starting from `Options::default_for(spec_version)`.
+    ///
+    pub fn new(spec_version: SpecVersion) -> Self {
+        Self {
+            options: Options::default_for(spec_version),
+        }
+    }
+
+    pub fn network_interface(&mut self, network_interface: &str) -> &mut Self {
+        self.options.network_interface = Some(network_interface.to_string());
+        self
+    }
+
+    pub fn network_version(&mut self, network_version: IP) -> &mut Self {
+        self.options.network_version = Some(network_version);
+        self
+    }
+
+    pub fn multicast_ttl(&mut self, multicast_ttl: u32) -> &mut Self {
+        self.options.multicast_ttl = multicast_ttl;
+        self
+    }
+
+    pub fn max_age(&mut self, max_age: u16) -> &mut Self {
+        self.options.max_age = max_age;
+        self
+    }
+
+    pub fn product_and_version(&mut self, product_and_version: ProductVersion) -> &mut Self {
+        self.options.product_and_version = Some(product_and_version);
+        self
+    }
+
+    pub fn transport(&mut self, transport: Arc<dyn Transport>) -> &mut Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    pub fn byebye(&mut self, repeat: u8, interval: Duration) -> &mut Self {
+        self.options.byebye_repeat = repeat;
+        self.options.byebye_interval = interval;
+        self
+    }
+
+    ///
+    /// Validate all values and return the completed options. Unlike `Options::validate`, a
+    /// `max_age` below the recommended minimum of 30 minutes is an error.
+    ///
+    pub fn build(&self) -> Result<Options, Error> {
+        if self.options.max_age < MIN_MAX_AGE {
+            error!(
+                "build - max_age should be at least {} seconds ({})",
+                MIN_MAX_AGE, self.options.max_age
+            );
+            return invalid_field_value("max_age", self.options.max_age.to_string()).into();
+        }
+        self.options.validate()?;
+        Ok(self.options.clone())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Drop for Advertiser {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            error!("Advertiser::drop - error sending byebye: {:?}", e);
         }
     }
 }
 
+impl Advertiser {
+    ///
+    /// Create an advertiser for all the advertisements of the device `root`, see
+    /// [`advertisements_for`]. No messages are sent until [`Advertiser::announce`] is called.
+    ///
+    pub fn new(
+        root: &DeviceRoot,
+        location: &URL,
+        identity: SsdpIdentity,
+        options: Options,
+    ) -> Self {
+        Self::with_devices(
+            advertisements_for(root, location)
+                .into_iter()
+                .map(|advertisement| advertisement.into_device(identity.clone()))
+                .collect(),
+            options,
+        )
+    }
+
+    ///
+    /// Create an advertiser for an explicit set of advertisements.
+    ///
+    pub fn with_devices(devices: Vec<SsdpDeviceInfo>, options: Options) -> Self {
+        Self {
+            devices,
+            options,
+            announced: false,
+        }
+    }
+
+    ///
+    /// The advertisements sent by this advertiser.
+    ///
+    pub fn devices(&self) -> &[SsdpDeviceInfo] {
+        &self.devices
+    }
+
+    ///
+    /// Send an `ssdp:alive` message for every advertisement. This should be repeated before the
+    /// `max_age` in the options expires.
+    ///
+    pub fn announce(&mut self) -> Result<(), Error> {
+        self.announced = true;
+        for device in &self.devices {
+            device_available(device, self.options.clone())?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Revoke every advertisement with a burst of `ssdp:byebye` messages, as configured by the
+    /// `byebye_repeat` and `byebye_interval` options. Nothing is sent if the advertisements have
+    /// not been announced, or have already been revoked.
+    ///
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        if !self.announced {
+            return Ok(());
+        }
+        self.announced = false;
+        byebye_burst(
+            self.devices.iter().map(|device| {
+                (
+                    &device.notification_type,
+                    &device.service_name,
+                    &device.identity,
+                )
+            }),
+            &self.options,
+        )
+    }
+}
+
 impl From<Options> for MulticastOptions {
     fn from(options: Options) -> Self {
         MulticastOptions {
             network_interface: options.network_interface,
             network_version: options.network_version,
             packet_ttl: options.packet_ttl,
+            multicast_ttl: options.multicast_ttl,
+            transport: options.transport,
             ..Default::default()
         }
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn advertised_pairs(root: &DeviceRoot) -> Vec<(NotificationType, UniqueServiceName)> {
+    let root_udn = &root.device.unique_device_name;
+    let mut pairs = vec![(
+        NotificationType::RootDevice,
+        UniqueServiceName::root_device(root_udn.clone()),
+    )];
+    for device in root.device.all_devices() {
+        let udn = &device.unique_device_name;
+        pairs.push((
+            NotificationType::Device(udn.clone()),
+            UniqueServiceName::new(udn.clone()),
+        ));
+        let mut types = vec![&device.device_type];
+        for service in &device.service_list {
+            if !types.contains(&&service.service_type) {
+                types.push(&service.service_type);
+            }
+        }
+        for type_id in types {
+            pairs.push((
+                NotificationType::from(type_id),
+                UniqueServiceName::with_type(udn.clone(), type_id.into()).unwrap(),
+            ));
+        }
+    }
+    pairs
+}
+
+fn byebye_burst<'a, I>(advertisements: I, options: &Options) -> Result<(), Error>
+where
+    I: Iterator<
+            Item = (
+                &'a NotificationType,
+                &'a UniqueServiceName,
+                &'a SsdpIdentity,
+            ),
+        > + Clone,
+{
+    let mut result = Ok(());
+    let mut first = true;
+    for _ in 0..options.byebye_repeat {
+        for (notification_type, service_name, identity) in advertisements.clone() {
+            if !first && !options.byebye_interval.is_zero() {
+                sleep(options.byebye_interval);
+            }
+            first = false;
+            let sent = send_byebye(notification_type, service_name, identity, options.clone());
+            if result.is_ok() {
+                result = sent;
+            }
+        }
+    }
+    result
+}
+
+///
+/// Add the identity headers, and any search port or secure location, allowed by `rules`.
+///
+fn add_identity_headers(
+    message_builder: &mut RequestBuilder,
+    rules: &HeaderRules,
+    device: &SsdpDeviceInfo,
+) {
+    if rules.allows(HeaderName::BootId) {
+        message_builder
+            .header(HeaderName::BootId, &device.identity.boot_id().to_string())
+            .header(
+                HeaderName::ConfigId,
+                &device.identity.config_id().to_string(),
+            );
+    }
+    if let Some(search_port) = &device.search_port {
+        if rules.allows(HeaderName::SearchPort) {
+            message_builder.header(HeaderName::SearchPort, &search_port.to_string());
+        }
+    }
+    if let Some(secure_location) = &device.secure_location {
+        if rules.allows(HeaderName::SecureLocation) {
+            message_builder.header(HeaderName::SecureLocation, secure_location);
+        }
+    }
+}
+
+fn send_byebye(
+    notification_type: &NotificationType,
+    service_name: &UniqueServiceName,
+    identity: &SsdpIdentity,
+    options: Options,
+) -> Result<(), Error> {
+    let _span = info_span!(
+        "notify",
+        nts = %NotificationSubtype::ByeBye,
+        nt = %notification_type,
+        usn = %service_name
+    )
+    .entered();
+    debug!("sending notification");
+    let rules = HeaderRules::new(SsdpMessage::ByeBye, options.spec_version);
+    let mut message_builder = RequestBuilder::new(Method::Notify.as_str());
+    message_builder
+        .header(HeaderName::Nt, &notification_type.to_string())
+        .header(HeaderName::Nts, &NotificationSubtype::ByeBye.to_string())
+        .header(HeaderName::Usn, &service_name.to_string());
+
+    if rules.allows(HeaderName::BootId) {
+        message_builder
+            .header(HeaderName::BootId, &identity.boot_id().to_string())
+            .header(HeaderName::ConfigId, &identity.config_id().to_string());
+    }
+
+    multicast_once(
+        &message_builder.build()?,
+        &MULTICAST_ADDRESS.parse().unwrap(),
+        &options.into(),
+    )?;
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:root</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType><serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId><SCPDURL>/cd.xml</SCPDURL><controlURL>/cd/control</controlURL><eventSubURL>/cd/event</eventSubURL></service><service><serviceType>urn:schemas-upnp-org:service:ConnectionManager:1</serviceType><serviceId>urn:upnp-org:serviceId:ConnectionManager</serviceId><SCPDURL>/cm.xml</SCPDURL><controlURL>/cm/control</controlURL><eventSubURL>/cm/event</eventSubURL></service></serviceList><deviceList><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Embedded</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:embedded</UDN></device></deviceList></device></root>";
+
+    #[test]
+    fn test_advertisements_for() {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let location = URL::from_str("http://10.0.0.1/description.xml").unwrap();
+        let advertisements: Vec<(String, String)> = advertisements_for(&root, &location)
+            .into_iter()
+            .map(|advertisement| {
+                (
+                    advertisement.notification_type.to_string(),
+                    advertisement.service_name.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            advertisements,
+            vec![
+                ("upnp:rootdevice", "uuid:root::upnp:rootdevice"),
+                ("uuid:root", "uuid:root"),
+                (
+                    "urn:schemas-upnp-org:device:MediaServer:1",
+                    "uuid:root::urn:schemas-upnp-org:device:MediaServer:1"
+                ),
+                (
+                    "urn:schemas-upnp-org:service:ContentDirectory:1",
+                    "uuid:root::urn:schemas-upnp-org:service:ContentDirectory:1"
+                ),
+                (
+                    "urn:schemas-upnp-org:service:ConnectionManager:1",
+                    "uuid:root::urn:schemas-upnp-org:service:ConnectionManager:1"
+                ),
+                ("uuid:embedded", "uuid:embedded"),
+                (
+                    "urn:schemas-upnp-org:device:Basic:1",
+                    "uuid:embedded::urn:schemas-upnp-org:device:Basic:1"
+                ),
+            ]
+            .into_iter()
+            .map(|(nt, usn)| (nt.to_string(), usn.to_string()))
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_options_builder() {
+        let options = OptionsBuilder::new(SpecVersion::V11)
+            .max_age(3600)
+            .byebye(3, Duration::ZERO)
+            .build()
+            .unwrap();
+        assert_eq!(options.max_age, 3600);
+        assert_eq!(options.byebye_repeat, 3);
+
+        assert!(OptionsBuilder::new(SpecVersion::V11)
+            .max_age(60)
+            .build()
+            .is_err());
+        let mut short = Options::default_for(SpecVersion::V11);
+        short.max_age = 60;
+        assert!(short.validate().is_ok());
+        assert!(OptionsBuilder::new(SpecVersion::V11)
+            .multicast_ttl(0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_advertiser_shutdown_on_drop() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let listener = network.bind("10.0.0.2:1900".parse().unwrap()).unwrap();
+        listener.join(MULTICAST_ADDRESS.parse().unwrap());
+        listener
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let transport: Arc<dyn Transport> =
+            Arc::new(network.bind("10.0.0.1:1900".parse().unwrap()).unwrap());
+
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let location = URL::from_str("http://10.0.0.1/description.xml").unwrap();
+        let options = Options {
+            transport: Some(transport),
+            byebye_repeat: 2,
+            byebye_interval: Duration::from_millis(0),
+            ..Options::default_for(SpecVersion::V11)
+        };
+        let count = |nts: &str| {
+            let mut buf = [0u8; 1500];
+            let mut count = 0;
+            while let Ok((received, _)) = listener.recv_from(&mut buf) {
+                if String::from_utf8_lossy(&buf[..received]).contains(nts) {
+                    count += 1;
+                }
+            }
+            count
+        };
+
+        drop(Advertiser::new(
+            &root,
+            &location,
+            SsdpIdentity::new(1),
+            options.clone(),
+        ));
+        assert_eq!(count("ssdp:byebye"), 0);
+
+        let mut advertiser = Advertiser::new(&root, &location, SsdpIdentity::new(1), options);
+        advertiser.announce().unwrap();
+        assert_eq!(count("ssdp:alive"), 7);
+        drop(advertiser);
+        assert_eq!(count("ssdp:byebye"), 14);
+    }
+}
@@ -0,0 +1,194 @@
+/*!
+This module provides a lightweight check that a device is still reachable. An advertisement, or a
+search response, remains valid for its `max-age` even if the device has since left the network
+without sending a _byebye_ message; probing the description `LOCATION` detects such devices
+before the advertisement expires.
+
+The probe sends an HTTP `HEAD` request for the description, falling back to `GET` for devices that
+do not implement `HEAD`; the device is alive if the description is returned successfully within
+the timeout.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::probe::is_alive;
+use upnp_rs::discovery::search::{search_once, Options};
+use upnp_rs::SpecVersion;
+
+let responses = search_once(Options::default_for(SpecVersion::V10)).unwrap();
+let present: Vec<_> = responses.iter().filter(|response| is_alive(*response)).collect();
+```
+*/
+
+use crate::common::http::{ClientRequest, HttpClient};
+use crate::common::uri::URL;
+use crate::discovery::listen::Notification;
+use crate::discovery::search::Response;
+use crate::syntax::{HTTP_METHOD_GET, HTTP_METHOD_HEAD};
+use std::time::Duration;
+use tracing::{debug, trace};
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Anything that carries the location of a device description, and so may be probed.
+///
+pub trait Probe {
+    ///
+    /// The location of the device description, if known.
+    ///
+    fn probe_location(&self) -> Option<&URL>;
+}
+
+///
+/// The timeout used by [`is_alive`] for the whole request.
+///
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Returns `true` if the device description at the target's location can be retrieved within
+/// [`DEFAULT_PROBE_TIMEOUT`]; a target without a location is never alive.
+///
+#[cfg(feature = "net")]
+pub fn is_alive<P>(target: &P) -> bool
+where
+    P: Probe + ?Sized,
+{
+    is_alive_within(target, DEFAULT_PROBE_TIMEOUT)
+}
+
+///
+/// As [`is_alive`], with the specified timeout.
+///
+#[cfg(feature = "net")]
+pub fn is_alive_within<P>(target: &P, timeout: Duration) -> bool
+where
+    P: Probe + ?Sized,
+{
+    probe(target, default_client().as_ref(), Some(timeout))
+}
+
+///
+/// As [`is_alive`], using the provided client and so its timeout.
+///
+pub fn is_alive_with<P>(target: &P, client: &dyn HttpClient) -> bool
+where
+    P: Probe + ?Sized,
+{
+    probe(target, client, None)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Probe for URL {
+    fn probe_location(&self) -> Option<&URL> {
+        Some(self)
+    }
+}
+
+impl Probe for Response {
+    fn probe_location(&self) -> Option<&URL> {
+        Some(&self.location)
+    }
+}
+
+impl Probe for Notification {
+    fn probe_location(&self) -> Option<&URL> {
+        self.location.as_ref()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const STATUS_METHOD_NOT_ALLOWED: u16 = 405;
+
+const STATUS_NOT_IMPLEMENTED: u16 = 501;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn probe<P>(target: &P, client: &dyn HttpClient, timeout: Option<Duration>) -> bool
+where
+    P: Probe + ?Sized,
+{
+    let location = match target.probe_location() {
+        Some(location) => location.to_string(),
+        None => return false,
+    };
+    trace!("is_alive_with - probing {}", location);
+    let request = |method: &str| {
+        let request = ClientRequest::new(method, &location);
+        match timeout {
+            None => request,
+            Some(timeout) => request.with_timeout(timeout),
+        }
+    };
+    let status = match client.send(&request(HTTP_METHOD_HEAD)) {
+        Ok(response)
+            if response.status == STATUS_METHOD_NOT_ALLOWED
+                || response.status == STATUS_NOT_IMPLEMENTED =>
+        {
+            client.send(&request(HTTP_METHOD_GET))
+        }
+        result => result,
+    };
+    match status {
+        Ok(response) => {
+            debug!("is_alive_with - {} returned {}", location, response.status);
+            response.is_success()
+        }
+        Err(e) => {
+            debug!("is_alive_with - {} failed: {}", location, e);
+            false
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use crate::description::device::DeviceRoot;
+    use crate::emulation::{from_device_with, Options};
+    use std::str::FromStr;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN></device></root>";
+
+    #[test]
+    fn test_is_alive() {
+        let device = from_device_with(
+            DeviceRoot::from_str(DESCRIPTION).unwrap(),
+            Default::default(),
+            Options {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let location = device.location();
+        assert!(is_alive(&location));
+
+        let missing = URL::from_str(&format!("{}.missing", location)).unwrap();
+        assert!(!is_alive(&missing));
+
+        device.stop();
+        assert!(!is_alive(&location));
+    }
+}
@@ -0,0 +1,426 @@
+/*!
+This module provides the device side of search, the construction of the responses a device sends
+to an `M-SEARCH` request. It is independent of any socket handling, so a device that runs its own
+receive loop can still produce correct messages.
+
+A received `M-SEARCH` is validated and parsed into a [`SearchRequest`] using `TryFrom<&Request>`.
+A device answers a search once for each of its advertisements that matches the search target, see
+[`answers`]; each response is built by [`build_response`] and, for a multicast search, sent after
+the random [`SearchRequest::response_delay`].
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::discovery::identity::SsdpIdentity;
+use upnp_rs::discovery::notify::advertisements_for;
+use upnp_rs::discovery::respond::{answers, build_response, DeviceInfo, SearchRequest};
+use upnp_rs::discovery::search::SearchTarget;
+use upnp_rs::SpecVersion;
+use std::str::FromStr;
+
+# fn root() -> DeviceRoot { unimplemented!() }
+let location = URL::from_str("http://10.0.0.1:8080/description.xml").unwrap();
+let devices: Vec<DeviceInfo> = advertisements_for(&root(), &location)
+    .into_iter()
+    .map(|advertisement| DeviceInfo::new(advertisement.into_device(SsdpIdentity::new(1))))
+    .collect();
+
+let request = SearchRequest::new(SearchTarget::RootDevice);
+for device in devices.iter().filter(|device| answers(device, &request)) {
+    let response = build_response(device, &request, SpecVersion::V11);
+    // send response.raw().bytes to the requester
+}
+```
+*/
+
+use crate::common::date;
+use crate::common::headers;
+use crate::common::httpu::{Request, Response};
+use crate::common::user_agent::user_agent_string;
+use crate::discovery::rules::{HeaderRules, SsdpMessage};
+use crate::discovery::search::SearchTarget;
+use crate::discovery::{
+    parse_control_point_uuid, ControlPoint, ProductVersion, SsdpDeviceInfo, UniqueServiceName,
+};
+use crate::error::{invalid_field_value, invalid_header_value, MessageFormatError};
+use crate::syntax::{
+    HeaderName, Method, HTTP_EXTENSION, HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL,
+    HTTP_HEADER_CONFIGID, HTTP_HEADER_CP_FN, HTTP_HEADER_CP_UUID, HTTP_HEADER_DATE,
+    HTTP_HEADER_EXT, HTTP_HEADER_HOST, HTTP_HEADER_LINE_SEP, HTTP_HEADER_LOCATION, HTTP_HEADER_MAN,
+    HTTP_HEADER_MX, HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SECURE_LOCATION, HTTP_HEADER_SERVER,
+    HTTP_HEADER_ST, HTTP_HEADER_TCP_PORT, HTTP_HEADER_USN,
+};
+use crate::SpecVersion;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::trace;
+use uuid::Uuid;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A single advertisement of a device, and the values used in the responses to a search for it.
+///
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    /// The advertisement, and the device's location, identity, and search port.
+    pub device: SsdpDeviceInfo,
+    /// The value used to control caching of responses by control points. Default: `1800`.
+    pub max_age: u16,
+    /// If specified this is to be the `ProduceName/Version` component of the `SERVER` header. If
+    /// not specified the product of the value set with `user_agent::set_product_versions` is used,
+    /// or a default value based on the name and version of this crate. Default: `None`.
+    pub product_and_version: Option<ProductVersion>,
+}
+
+///
+/// An `M-SEARCH` request received by a device.
+///
+#[derive(Clone, Debug)]
+pub struct SearchRequest {
+    /// The search target, from the `ST` header.
+    pub search_target: SearchTarget,
+    /// The maximum time, in seconds, the device may wait before responding, from the `MX`
+    /// header and limited to `1..=5` as required by UDA 1.1; `None` for a unicast search.
+    pub max_wait: Option<u8>,
+    /// The control point details sent from UDA 2.0 onwards.
+    pub control_point: Option<ControlPoint>,
+    /// The address the request was received from, to which responses are sent. This is not set
+    /// when parsing a request, as it is not part of the message.
+    pub source: Option<SocketAddr>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Returns `true` if the advertisement in `device` satisfies the search target of `request`, and
+/// so a response should be sent for it.
+///
+pub fn answers(device: &DeviceInfo, request: &SearchRequest) -> bool {
+    let notification_type = SearchTarget::from(device.device.notification_type.clone());
+    match &request.search_target {
+        SearchTarget::All => true,
+        SearchTarget::RootDevice | SearchTarget::Device(_) => {
+            request.search_target == notification_type
+        }
+        search_target => device
+            .device
+            .notification_type
+            .type_id()
+            .map(|type_id| search_target.accepts(&type_id))
+            .unwrap_or(false),
+    }
+}
+
+///
+/// Build the response to `request` for the advertisement in `device`. The `ST` header echoes the
+/// search target, except that a search for `ssdp:all` is answered with the advertised type; for
+/// a type search the `USN` names the type searched for, which may be an earlier version than that
+/// implemented.
+///
+/// This function does not check that the device answers the request, see [`answers`].
+///
+pub fn build_response(device: &DeviceInfo, request: &SearchRequest, spec: SpecVersion) -> Response {
+    let advertised = &device.device;
+    let (search_target, service_name) = match request.search_target.unversioned() {
+        SearchTarget::All | SearchTarget::RootDevice | SearchTarget::Device(_) => (
+            SearchTarget::from(advertised.notification_type.clone()),
+            advertised.service_name.clone(),
+        ),
+        search_target => (
+            search_target.clone(),
+            UniqueServiceName::with_type(advertised.service_name.device().clone(), search_target)
+                .unwrap_or_else(|_| advertised.service_name.clone()),
+        ),
+    };
+    trace!(
+        "build_response - ST: {}, USN: {}",
+        search_target,
+        service_name
+    );
+
+    let mut lines = vec![
+        "HTTP/1.1 200 OK".to_string(),
+        format!("{}: max-age={}", HTTP_HEADER_CACHE_CONTROL, device.max_age),
+        format!("{}: {}", HTTP_HEADER_DATE, date::now()),
+        format!("{}:", HTTP_HEADER_EXT),
+        format!("{}: {}", HTTP_HEADER_LOCATION, advertised.location),
+        format!(
+            "{}: {}",
+            HTTP_HEADER_SERVER,
+            user_agent_string(spec, device.product_and_version.clone())
+        ),
+        format!("{}: {}", HTTP_HEADER_ST, search_target),
+        format!("{}: {}", HTTP_HEADER_USN, service_name),
+    ];
+    let rules = HeaderRules::new(SsdpMessage::SearchResponse, spec);
+    if rules.allows(HeaderName::BootId) {
+        lines.push(format!(
+            "{}: {}",
+            HTTP_HEADER_BOOTID,
+            advertised.identity.boot_id()
+        ));
+        lines.push(format!(
+            "{}: {}",
+            HTTP_HEADER_CONFIGID,
+            advertised.identity.config_id()
+        ));
+    }
+    if let Some(search_port) = advertised.search_port {
+        if rules.allows(HeaderName::SearchPort) {
+            lines.push(format!("{}: {}", HTTP_HEADER_SEARCH_PORT, search_port));
+        }
+    }
+    if let Some(secure_location) = &advertised.secure_location {
+        if rules.allows(HeaderName::SecureLocation) {
+            lines.push(format!(
+                "{}: {}",
+                HTTP_HEADER_SECURE_LOCATION, secure_location
+            ));
+        }
+    }
+    lines.push(String::new());
+    lines.push(String::new());
+    let message = lines.join(HTTP_HEADER_LINE_SEP);
+    Response::try_from(message.as_bytes()).unwrap()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl From<SsdpDeviceInfo> for DeviceInfo {
+    fn from(device: SsdpDeviceInfo) -> Self {
+        Self::new(device)
+    }
+}
+
+impl DeviceInfo {
+    pub fn new(device: SsdpDeviceInfo) -> Self {
+        Self {
+            device,
+            max_age: CACHE_CONTROL_MAX_AGE,
+            product_and_version: None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<&Request> for SearchRequest {
+    type Error = MessageFormatError;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        if request.known_method() != Some(Method::Search) {
+            return invalid_field_value("method", request.method()).into();
+        }
+        let header = |name: &str| request.header(name).map(str::trim);
+        headers::check_required(request.headers(), &[HTTP_HEADER_MAN, HTTP_HEADER_ST])?;
+
+        let extension = header(HTTP_HEADER_MAN).unwrap();
+        if extension != HTTP_EXTENSION {
+            return invalid_header_value(HTTP_HEADER_MAN, extension).into();
+        }
+
+        let search_target = header(HTTP_HEADER_ST).unwrap();
+        let search_target = SearchTarget::from_str(search_target)
+            .map_err(|_| invalid_header_value(HTTP_HEADER_ST, search_target))?;
+
+        let is_multicast = match header(HTTP_HEADER_HOST) {
+            None => true,
+            Some(host) => SocketAddr::from_str(host)
+                .map(|host| host.ip().is_multicast())
+                .unwrap_or(false),
+        };
+        if is_multicast {
+            headers::check_required(request.headers(), &[HTTP_HEADER_MX])?;
+        }
+        let max_wait = match header(HTTP_HEADER_MX) {
+            None => None,
+            Some(value) => Some(
+                headers::check_parsed_value::<u64>(value, HTTP_HEADER_MX)?
+                    .clamp(MIN_MAX_WAIT as u64, MAX_MAX_WAIT as u64) as u8,
+            ),
+        };
+
+        let control_point = match header(HTTP_HEADER_CP_FN) {
+            None => None,
+            Some(friendly_name) => Some(ControlPoint {
+                friendly_name: friendly_name.to_string(),
+                uuid: match header(HTTP_HEADER_CP_UUID) {
+                    None => None,
+                    Some(value) => Some(parse_control_point_uuid(value)?),
+                },
+                port: match header(HTTP_HEADER_TCP_PORT) {
+                    None => None,
+                    Some(value) => Some(headers::check_parsed_value::<u16>(
+                        value,
+                        HTTP_HEADER_TCP_PORT,
+                    )?),
+                },
+            }),
+        };
+
+        Ok(SearchRequest {
+            search_target,
+            max_wait,
+            control_point,
+            source: None,
+        })
+    }
+}
+
+impl SearchRequest {
+    ///
+    /// A request for `search_target`, with no other values.
+    ///
+    pub fn new(search_target: SearchTarget) -> Self {
+        Self {
+            search_target,
+            max_wait: None,
+            control_point: None,
+            source: None,
+        }
+    }
+
+    ///
+    /// A random delay, between zero and `max_wait` seconds, to wait before responding to this
+    /// request so that responses from many devices are spread over the period; zero for a
+    /// unicast search.
+    ///
+    pub fn response_delay(&self) -> Duration {
+        match self.max_wait {
+            None => Duration::ZERO,
+            Some(max_wait) => {
+                let millis = u64::from(max_wait) * 1000;
+                Duration::from_millis((Uuid::new_v4().as_u128() % u128::from(millis + 1)) as u64)
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const CACHE_CONTROL_MAX_AGE: u16 = 1800;
+
+const MIN_MAX_WAIT: u8 = 1;
+
+const MAX_MAX_WAIT: u8 = 5;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::uri::URL;
+    use crate::description::device::DeviceRoot;
+    use crate::discovery::identity::SsdpIdentity;
+    use crate::discovery::notify::advertisements_for;
+    use std::str::FromStr;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:2</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:Basic:1</serviceType><serviceId>urn:upnp-org:serviceId:Basic</serviceId><SCPDURL>/basic.xml</SCPDURL><controlURL>/control</controlURL><eventSubURL>/event</eventSubURL></service></serviceList></device></root>";
+
+    fn devices() -> Vec<DeviceInfo> {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let location = URL::from_str("http://10.0.0.1/description.xml").unwrap();
+        advertisements_for(&root, &location)
+            .into_iter()
+            .map(|advertisement| {
+                let mut device = advertisement.into_device(SsdpIdentity::with_values(12, 3));
+                device.search_port = Some(1901);
+                DeviceInfo::new(device)
+            })
+            .collect()
+    }
+
+    fn respond(search_target: &str, spec: SpecVersion) -> Vec<Response> {
+        let request = SearchRequest::new(SearchTarget::from_str(search_target).unwrap());
+        devices()
+            .iter()
+            .filter(|device| answers(device, &request))
+            .map(|device| build_response(device, &request, spec))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_response() {
+        let responses = respond("urn:schemas-upnp-org:service:Basic:1", SpecVersion::V11);
+        assert_eq!(responses.len(), 1);
+        let response = &responses[0];
+        assert_eq!(
+            response.header(HTTP_HEADER_ST),
+            Some("urn:schemas-upnp-org:service:Basic:1")
+        );
+        assert_eq!(
+            response.header(HTTP_HEADER_USN),
+            Some("uuid:test-device::urn:schemas-upnp-org:service:Basic:1")
+        );
+        assert_eq!(response.header(HTTP_HEADER_BOOTID), Some("12"));
+        assert_eq!(response.header(HTTP_HEADER_CONFIGID), Some("3"));
+        assert_eq!(response.header(HTTP_HEADER_SEARCH_PORT), Some("1901"));
+        assert_eq!(response.header(HTTP_HEADER_EXT), Some(""));
+        assert!(response.header(HTTP_HEADER_DATE).is_some());
+
+        let responses = respond("upnp:rootdevice", SpecVersion::V10);
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].header(HTTP_HEADER_BOOTID).is_none());
+    }
+
+    fn parse(message: &str) -> Result<SearchRequest, MessageFormatError> {
+        SearchRequest::try_from(&Request::try_from(message.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 30\r\nST: upnp:rootdevice\r\nCPFN.UPNP.ORG: Test\r\nCPUUID.UPNP.ORG: uuid:2fac1234-31f8-11b4-a222-08002b34c003\r\nTCPPORT.UPNP.ORG: 4004\r\n\r\n").unwrap();
+        assert_eq!(request.search_target, SearchTarget::RootDevice);
+        assert_eq!(request.max_wait, Some(5));
+        assert!(request.response_delay() <= Duration::from_secs(5));
+        let control_point = request.control_point.unwrap();
+        assert_eq!(control_point.friendly_name, "Test");
+        assert_eq!(
+            control_point.uuid.map(|uuid| uuid.to_string()).as_deref(),
+            Some("2fac1234-31f8-11b4-a222-08002b34c003")
+        );
+        assert_eq!(control_point.port, Some(4004));
+
+        let request = parse("M-SEARCH * HTTP/1.1\r\nHOST: 10.0.0.1:1900\r\nMAN: \"ssdp:discover\"\r\nST: ssdp:all\r\n\r\n").unwrap();
+        assert_eq!(request.max_wait, None);
+        assert_eq!(request.response_delay(), Duration::ZERO);
+        assert!(request.control_point.is_none());
+
+        assert!(parse("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: ssdp:all\r\n\r\n").is_err());
+        assert!(parse("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: ssdp:discover\r\nMX: 1\r\nST: ssdp:all\r\n\r\n").is_err());
+        assert!(parse("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: nonsense\r\n\r\n").is_err());
+        assert!(parse(
+            "NOTIFY * HTTP/1.1\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(respond("ssdp:all", SpecVersion::V11).len(), 4);
+        assert_eq!(respond("uuid:test-device", SpecVersion::V11).len(), 1);
+        assert_eq!(respond("uuid:other-device", SpecVersion::V11).len(), 0);
+
+        let responses = respond("urn:schemas-upnp-org:device:Basic:1", SpecVersion::V11);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(
+            responses[0].header(HTTP_HEADER_USN),
+            Some("uuid:test-device::urn:schemas-upnp-org:device:Basic:1")
+        );
+    }
+}
@@ -0,0 +1,327 @@
+/*!
+This module provides the table of which headers are required, optional, or forbidden in each SSDP
+message for each version of the specification. The same table is used when building search
+requests, notifications, and search responses, and may be used to validate any captured message
+against a chosen version.
+
+# Specification
+
+UDA 1.1 added the `BOOTID.UPNP.ORG`, `CONFIGID.UPNP.ORG`, and `SEARCHPORT.UPNP.ORG` headers, the
+`ssdp:update` notification, and unicast search; UDA 2.0 added the control point headers in a search
+and `SECURELOCATION.UPNP.ORG`. A header not listed for a message is an extension header, and is
+treated as optional.
+
+# Example
+
+```rust
+use upnp_rs::discovery::rules::{HeaderRules, Requirement, SsdpMessage};
+use upnp_rs::syntax::HeaderName;
+use upnp_rs::SpecVersion;
+
+let rules = HeaderRules::new(SsdpMessage::Alive, SpecVersion::V10);
+assert_eq!(rules.requirement(HeaderName::BootId), Requirement::Forbidden);
+
+let rules = HeaderRules::new(SsdpMessage::Alive, SpecVersion::V11);
+assert_eq!(rules.requirement(HeaderName::BootId), Requirement::Required);
+
+let message = [
+    ("HOST", "239.255.255.250:1900"),
+    ("NT", "upnp:rootdevice"),
+    ("NTS", "ssdp:byebye"),
+    ("USN", "uuid:device-1::upnp:rootdevice"),
+];
+assert!(HeaderRules::new(SsdpMessage::ByeBye, SpecVersion::V10).validate(message).is_ok());
+assert!(HeaderRules::new(SsdpMessage::ByeBye, SpecVersion::V11).validate(message).is_err());
+```
+*/
+
+use crate::error::{invalid_header_value, missing_required_header, unsupported_version, Error};
+use crate::syntax::HeaderName;
+use crate::SpecVersion;
+use std::str::FromStr;
+use tracing::error;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The SSDP messages described by the rules table.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SsdpMessage {
+    /// A multicast `M-SEARCH` request.
+    Search,
+    /// A unicast `M-SEARCH` request, sent directly to a device.
+    UnicastSearch,
+    /// The response sent by a device to either form of search.
+    SearchResponse,
+    /// An `ssdp:alive` notification.
+    Alive,
+    /// An `ssdp:update` notification.
+    Update,
+    /// An `ssdp:byebye` notification.
+    ByeBye,
+}
+
+///
+/// Whether a header must, may, or must not appear in a message.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Requirement {
+    /// The header must be present.
+    Required,
+    /// The header may be present.
+    Optional,
+    /// The header is not defined for the message by this version, and must not be present.
+    Forbidden,
+}
+
+///
+/// The header rules for one message and one specification version.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeaderRules {
+    message: SsdpMessage,
+    version: SpecVersion,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HeaderRules {
+    pub fn new(message: SsdpMessage, version: SpecVersion) -> Self {
+        Self { message, version }
+    }
+
+    pub fn message(&self) -> SsdpMessage {
+        self.message
+    }
+
+    pub fn version(&self) -> SpecVersion {
+        self.version
+    }
+
+    ///
+    /// Returns `true` if the message is defined by this version; `ssdp:update` and unicast search
+    /// were added in UDA 1.1.
+    ///
+    pub fn is_supported(&self) -> bool {
+        self.headers()
+            .any(|(_, requirement)| requirement == Requirement::Required)
+    }
+
+    ///
+    /// The requirement for `header` in this message; headers not in the table are optional.
+    ///
+    pub fn requirement(&self, header: HeaderName) -> Requirement {
+        self.headers()
+            .find(|(name, _)| *name == header)
+            .map(|(_, requirement)| requirement)
+            .unwrap_or(Requirement::Optional)
+    }
+
+    ///
+    /// Returns `true` if `header` may be included in this message.
+    ///
+    pub fn allows(&self, header: HeaderName) -> bool {
+        self.requirement(header) != Requirement::Forbidden
+    }
+
+    ///
+    /// All the headers listed in the table for this message, with their requirement.
+    ///
+    pub fn headers(&self) -> impl Iterator<Item = (HeaderName, Requirement)> + '_ {
+        let index = match self.version {
+            SpecVersion::V10 => 0,
+            SpecVersion::V11 => 1,
+            SpecVersion::V20 => 2,
+        };
+        RULES
+            .iter()
+            .filter(move |(message, _, _)| *message == self.message)
+            .map(move |(_, header, requirements)| (*header, requirements[index]))
+    }
+
+    ///
+    /// The headers that must be present in this message.
+    ///
+    pub fn required(&self) -> impl Iterator<Item = HeaderName> + '_ {
+        self.headers()
+            .filter(|(_, requirement)| *requirement == Requirement::Required)
+            .map(|(header, _)| header)
+    }
+
+    ///
+    /// Validate the headers of a message against the rules; an error is returned if the message
+    /// is not defined by this version, a required header is missing, or a forbidden header is
+    /// present. Header names are compared ignoring case, and unknown headers are ignored.
+    ///
+    pub fn validate<I, K, V>(&self, headers: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        if !self.is_supported() {
+            error!(
+                "validate - {:?} is not defined by version {}",
+                self.message, self.version
+            );
+            return Err(unsupported_version(self.version));
+        }
+        let mut present: Vec<HeaderName> = Vec::new();
+        for (name, value) in headers {
+            if let Ok(header) = HeaderName::from_str(name.as_ref()) {
+                if self.requirement(header) == Requirement::Forbidden {
+                    error!(
+                        "validate - header {} is not allowed in {:?} for version {}",
+                        header, self.message, self.version
+                    );
+                    return invalid_header_value(header.as_str(), value.as_ref()).into();
+                }
+                present.push(header);
+            }
+        }
+        let missing: Vec<&str> = self
+            .required()
+            .filter(|header| !present.contains(header))
+            .map(|header| header.as_str())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            error!("validate - message missing headers '{:?}'", missing);
+            missing_required_header(missing.join(", ")).into()
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Values
+// ------------------------------------------------------------------------------------------------
+
+const R: Requirement = Requirement::Required;
+const O: Requirement = Requirement::Optional;
+const F: Requirement = Requirement::Forbidden;
+
+///
+/// The requirement for each header, in the order UDA 1.0, 1.1, and 2.0.
+///
+const RULES: &[(SsdpMessage, HeaderName, [Requirement; 3])] = &[
+    (SsdpMessage::Search, HeaderName::Host, [R, R, R]),
+    (SsdpMessage::Search, HeaderName::Man, [R, R, R]),
+    (SsdpMessage::Search, HeaderName::Mx, [R, R, R]),
+    (SsdpMessage::Search, HeaderName::St, [R, R, R]),
+    (SsdpMessage::Search, HeaderName::UserAgent, [F, O, O]),
+    (SsdpMessage::Search, HeaderName::CpFriendlyName, [F, F, R]),
+    (SsdpMessage::Search, HeaderName::CpUuid, [F, F, O]),
+    (SsdpMessage::Search, HeaderName::TcpPort, [F, F, O]),
+    (SsdpMessage::UnicastSearch, HeaderName::Host, [F, R, R]),
+    (SsdpMessage::UnicastSearch, HeaderName::Man, [F, R, R]),
+    (SsdpMessage::UnicastSearch, HeaderName::Mx, [F, F, F]),
+    (SsdpMessage::UnicastSearch, HeaderName::St, [F, R, R]),
+    (SsdpMessage::UnicastSearch, HeaderName::UserAgent, [F, O, O]),
+    (
+        SsdpMessage::SearchResponse,
+        HeaderName::CacheControl,
+        [R, R, R],
+    ),
+    (SsdpMessage::SearchResponse, HeaderName::Date, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::Ext, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::Location, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::Server, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::St, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::Usn, [R, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::BootId, [F, R, R]),
+    (SsdpMessage::SearchResponse, HeaderName::ConfigId, [F, R, R]),
+    (
+        SsdpMessage::SearchResponse,
+        HeaderName::SearchPort,
+        [F, O, O],
+    ),
+    (
+        SsdpMessage::SearchResponse,
+        HeaderName::SecureLocation,
+        [F, F, O],
+    ),
+    (SsdpMessage::Alive, HeaderName::Host, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::CacheControl, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::Location, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::Nt, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::Nts, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::Server, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::Usn, [R, R, R]),
+    (SsdpMessage::Alive, HeaderName::BootId, [F, R, R]),
+    (SsdpMessage::Alive, HeaderName::ConfigId, [F, R, R]),
+    (SsdpMessage::Alive, HeaderName::SearchPort, [F, O, O]),
+    (SsdpMessage::Alive, HeaderName::SecureLocation, [F, F, O]),
+    (SsdpMessage::Update, HeaderName::Host, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::Location, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::Nt, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::Nts, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::Usn, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::BootId, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::ConfigId, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::NextBootId, [F, R, R]),
+    (SsdpMessage::Update, HeaderName::SearchPort, [F, O, O]),
+    (SsdpMessage::Update, HeaderName::SecureLocation, [F, F, O]),
+    (SsdpMessage::ByeBye, HeaderName::Host, [R, R, R]),
+    (SsdpMessage::ByeBye, HeaderName::Nt, [R, R, R]),
+    (SsdpMessage::ByeBye, HeaderName::Nts, [R, R, R]),
+    (SsdpMessage::ByeBye, HeaderName::Usn, [R, R, R]),
+    (SsdpMessage::ByeBye, HeaderName::BootId, [F, R, R]),
+    (SsdpMessage::ByeBye, HeaderName::ConfigId, [F, R, R]),
+];
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_added_messages_unsupported_in_v10() {
+        assert!(!HeaderRules::new(SsdpMessage::Update, SpecVersion::V10).is_supported());
+        assert!(!HeaderRules::new(SsdpMessage::UnicastSearch, SpecVersion::V10).is_supported());
+        assert!(HeaderRules::new(SsdpMessage::Update, SpecVersion::V11).is_supported());
+        assert!(HeaderRules::new(SsdpMessage::Search, SpecVersion::V10).is_supported());
+    }
+
+    #[test]
+    fn test_control_point_headers() {
+        let rules = HeaderRules::new(SsdpMessage::Search, SpecVersion::V11);
+        assert!(!rules.allows(HeaderName::CpFriendlyName));
+        assert!(rules.allows(HeaderName::UserAgent));
+        let rules = HeaderRules::new(SsdpMessage::Search, SpecVersion::V20);
+        assert_eq!(
+            rules.requirement(HeaderName::CpFriendlyName),
+            Requirement::Required
+        );
+        assert_eq!(
+            rules.requirement(HeaderName::TcpPort),
+            Requirement::Optional
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let rules = HeaderRules::new(SsdpMessage::Search, SpecVersion::V10);
+        let mut headers = vec![
+            ("host", "239.255.255.250:1900"),
+            ("MAN", "\"ssdp:discover\""),
+            ("MX", "1"),
+            ("ST", "ssdp:all"),
+            ("X-VENDOR", "ignored"),
+        ];
+        assert!(rules.validate(headers.clone()).is_ok());
+        headers.push(("USER-AGENT", "Linux/5.0 UPnP/1.1 Test/1.0"));
+        assert!(rules.validate(headers.clone()).is_err());
+        let _ = headers.remove(2);
+        let rules = HeaderRules::new(SsdpMessage::Search, SpecVersion::V11);
+        assert!(rules.validate(headers).is_err());
+    }
+}
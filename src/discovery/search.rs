@@ -1,42 +1,60 @@
 /*!
 This module provides three functions that provide 1) multicast search, 2) unicast search, and 3)
 multicast search with caching. The caching version of search will merge the set of new responses
-with any (non-expired) previously cached responses.
+with any (non-expired) previously cached responses. A [`Searcher`] keeps its socket between
+searches, and a [`SearchSession`] repeats the search in the background, pushing newly discovered
+responses to a channel.
 
 # Specification
 
 TBD
 
 */
+use crate::common::date;
 use crate::common::headers;
+use crate::common::http::HttpClient;
 use crate::common::httpu::{
-    multicast, Options as MulticastOptions, RequestBuilder, Response as MulticastResponse,
+    default_transport, multicast_until, CancellationToken, Options as MulticastOptions, Oversized,
+    RawResponse, RequestBuilder, Response as MulticastResponse, Transport, DEFAULT_BUFFER_SIZE,
+    DEFAULT_SEND_INTERVAL,
 };
 use crate::common::interface::IP;
+use crate::common::udn::Udn;
 use crate::common::uri::{URI, URL};
 use crate::common::user_agent::user_agent_string;
-use crate::discovery::{ControlPoint, ProductVersion, ProductVersions};
+use crate::description::device::DeviceRoot;
+use crate::description::{TypeID, VersionReq};
+use crate::discovery::identity::{parse_boot_id, parse_config_id};
+use crate::discovery::probe;
+use crate::discovery::rules::{HeaderRules, Requirement, SsdpMessage};
+use crate::discovery::{ControlPoint, ProductVersion, ProductVersions, UniqueServiceName};
 use crate::error::{
     invalid_field_value, invalid_header_value, invalid_value_for_type, missing_required_field,
-    unsupported_operation, unsupported_version, Error, MessageFormatError,
+    unsupported_version, Error, MessageFormatError,
 };
+use crate::observer::{self, MessageKind};
 use crate::syntax::{
-    HTTP_EXTENSION, HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID,
-    HTTP_HEADER_CP_FN, HTTP_HEADER_CP_UUID, HTTP_HEADER_DATE, HTTP_HEADER_EXT, HTTP_HEADER_HOST,
-    HTTP_HEADER_LOCATION, HTTP_HEADER_MAN, HTTP_HEADER_MX, HTTP_HEADER_SEARCH_PORT,
-    HTTP_HEADER_SERVER, HTTP_HEADER_ST, HTTP_HEADER_TCP_PORT, HTTP_HEADER_USER_AGENT,
-    HTTP_HEADER_USN, HTTP_METHOD_SEARCH, MULTICAST_ADDRESS,
+    HeaderName, Method, HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID,
+    HTTP_HEADER_DATE, HTTP_HEADER_EXT, HTTP_HEADER_LOCATION, HTTP_HEADER_NLS,
+    HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SERVER, HTTP_HEADER_ST, HTTP_HEADER_USN,
+    HTTP_HEADER_X_AV_PHYSICAL_UNIT_INFO, HTTP_HEADER_X_AV_SERVER_INFO, MULTICAST_ADDRESS,
 };
 use crate::SpecVersion;
 use regex::Regex;
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt::{Display, Error as FmtError, Formatter};
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
 use std::time::{Duration, SystemTime};
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, info_span, trace, warn};
+
+#[cfg(feature = "net")]
+use crate::common::httpu::{create_tcp_listener, tcp_receive_using};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -48,14 +66,14 @@ use tracing::{error, info, trace};
 /// This type does not separate out the version of a device or service type, it does ensure
 /// that the ':' separator character is present in the combined value.
 ///
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SearchTarget {
     /// Corresponds to the value `ssdp:all`
     All,
     /// Corresponds to the value `upnp:rootdevice`
     RootDevice,
     /// Corresponds to the value `uuid:{device-UUID}`
-    Device(String),
+    Device(Udn),
     /// Corresponds to the value `urn:schemas-upnp-org:device:{deviceType:ver}`
     DeviceType(String),
     /// Corresponds to the value `urn:schemas-upnp-org:service:{serviceType:ver}`
@@ -64,6 +82,41 @@ pub enum SearchTarget {
     DomainDeviceType(String, String),
     /// Corresponds to the value `urn:{domain-name}:service:{serviceType:ver}`
     DomainServiceType(String, String),
+    /// A search for any version of the device type `name` satisfying `version`. This is sent as
+    /// `urn:schemas-upnp-org:device:{name}:{ver}`, where `ver` is the minimum version required,
+    /// and is never the result of parsing an `ST` header.
+    DeviceTypeVersioned { name: String, version: VersionReq },
+    /// A search for any version of the service type `name` satisfying `version`. This is sent as
+    /// `urn:schemas-upnp-org:service:{name}:{ver}`, where `ver` is the minimum version required,
+    /// and is never the result of parsing an `ST` header.
+    ServiceTypeVersioned { name: String, version: VersionReq },
+}
+
+///
+/// Determines how search responses that do not conform to the specification are handled.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Reject any response with a missing or malformed header, useful for conformance testing.
+    #[default]
+    Strict,
+    /// Accept responses from devices with minor errors; only the `LOCATION`, `ST`, and `USN`
+    /// headers are required, other missing values take defaults, and malformed values are
+    /// retained in `other_headers`.
+    Lenient,
+}
+
+///
+/// A single match between a search target and a device, or one of its embedded devices or
+/// services. Each match corresponds to one search response (or notification) a device would send.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedTarget {
+    /// The value to return in the `ST` (or `NT`) header; for type matches this is the type and
+    /// version that was searched for, not necessarily the version implemented.
+    pub search_target: SearchTarget,
+    /// The value to return in the `USN` header.
+    pub service_name: UniqueServiceName,
 }
 
 ///
@@ -92,31 +145,66 @@ pub struct Options {
     pub spec_version: SpecVersion,
     /// The scope of the search to perform. Default: `SearchTarget::RootDevices`.
     pub search_target: SearchTarget,
-    /// A specific network interface to bind to; if specified the default address for the interface
-    /// will be used, else the address `0.0.0.0:0` will be used. Default: `None`.
+    /// A specific network interface to bind to, by name, index, or network (for example
+    /// `192.168.1.0/24`); if specified the interface's address will be used, else the address
+    /// `0.0.0.0:0` will be used. A value matching no interface is an error. Default: `None`.
     pub network_interface: Option<String>,
     /// Denotes whether the implementation wants to only use IPv4, IPv6, or doesn't care.
     pub network_version: Option<IP>,
     /// The IP packet TTL value.
     pub packet_ttl: u32,
-    /// The maximum wait time for devices to use in responding. This will also be used as the read
-    /// timeout on the underlying socket. This value **must** be between `0` and `120`;
-    /// default: `2`.
-    pub max_wait_time: u8,
+    /// The TTL, or IPv6 hop limit, of multicast messages. Default: the value recommended by
+    /// `spec_version`, `4` for 1.0 and `2` for later versions.
+    pub multicast_ttl: u32,
+    /// The maximum wait time for devices to use in responding. This is sent in the `MX` header
+    /// in whole seconds, rounded up, and also determines the receive window on the underlying
+    /// socket, which may be shorter than a second. This value **must** be greater than zero and
+    /// no more than `120` seconds; default: `2` seconds.
+    pub max_wait_time: Duration,
+    /// If specified the search ends once this time has elapsed from sending the request, even if
+    /// devices may still respond within `max_wait_time`. Default: `None`.
+    pub deadline: Option<Duration>,
     /// If specified this is to be the `ProduceName/Version` component of the user agent string
-    /// the client will generate as part of sent messages. If not specified a default value based
-    /// on the name and version of this crate will be used. Default: `None`.
+    /// the client will generate as part of sent messages. If not specified the product of the
+    /// value set with `user_agent::set_product_versions` is used, or a default value based on the
+    /// name and version of this crate. Default: `None`.
     pub product_and_version: Option<ProductVersion>,
     /// If specified this will be used to add certain control point values in the sent messages.
     /// This value is **only** used by the 2.0 specification where it is required, otherwise it
     /// will be ignores. Default: `None`.
     pub control_point: Option<ControlPoint>,
+    /// The size of the buffer used to receive each response; some devices send responses larger
+    /// than a single Ethernet MTU. Default: `DEFAULT_BUFFER_SIZE`.
+    pub buffer_size: usize,
+    /// How responses larger than `buffer_size` are handled. Default: `Oversized::GrowBuffer`.
+    pub oversized: Oversized,
+    /// Allow the search socket to share its local address with other sockets (`SO_REUSEADDR`).
+    /// Default: `false`.
+    pub reuse_address: bool,
+    /// Allow the search socket to share its local port with other sockets (`SO_REUSEPORT`), this
+    /// is not supported on all platforms. Default: `false`.
+    pub reuse_port: bool,
+    /// Bind the search socket to the device named by `network_interface` (`SO_BINDTODEVICE`), this
+    /// is only supported on Linux. Default: `false`.
+    pub bind_to_device: bool,
+    /// The number of times the search request is sent, the specification recommends sending more
+    /// than once to compensate for the unreliability of UDP. Responses to repeated requests are
+    /// removed from the results. Default: `1`.
+    pub send_count: u8,
+    /// The delay between repeated sends of the search request. Default: `100` milliseconds.
+    pub send_interval: Duration,
+    /// How responses that do not conform to the specification are handled. Default:
+    /// `Strictness::Strict`.
+    pub strictness: Strictness,
+    /// If specified, requests are sent and responses received using this transport rather than a
+    /// newly created UDP socket; responses over TCP are not collected. Required without the `net`
+    /// feature. Default: `None`.
+    pub transport: Option<Arc<dyn Transport>>,
 }
 
 #[derive(Clone, Debug)]
 struct CachedResponse {
     response: Response,
-    #[allow(dead_code)]
     expiration: SystemTime,
 }
 
@@ -137,26 +225,121 @@ pub struct ResponseCache {
 /// A Single device response.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Response {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_seconds"))]
     pub max_age: Duration,
-    pub date: String,
+    /// The time the response was generated, from the `DATE` header; in lenient mode a missing
+    /// or malformed header is replaced by the time the response was parsed.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_date"))]
+    pub date: SystemTime,
     pub versions: ProductVersions,
     pub search_target: SearchTarget,
-    pub service_name: URI,
+    pub service_name: UniqueServiceName,
     pub location: URL,
-    pub boot_id: u64,
-    pub config_id: Option<u64>,
+    pub boot_id: u32,
+    pub config_id: Option<u32>,
     pub search_port: Option<u16>,
+    /// Any headers not parsed into the fields above, keyed by upper case name; see
+    /// [`Response::header`] and [`Response::headers_matching`].
     pub other_headers: HashMap<String, String>,
+    /// The address of the device that sent this response, where known.
+    pub source: Option<SocketAddr>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    raw: RawResponse,
+}
+
+///
+/// The outcome of a multicast search, separating the responses that could be parsed from those
+/// that could not, so that one malformed device does not hide all the others.
+///
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    /// The responses parsed successfully, with duplicates removed.
+    pub ok: Vec<Response>,
+    /// The address each unparseable response was received from, where known, and the reason it
+    /// was rejected.
+    pub failed: Vec<(Option<SocketAddr>, Error)>,
+}
+
+///
+/// A single device, identified by its UDN, and all the responses received for it. A root device
+/// will commonly send one response for each of `upnp:rootdevice`, its UUID, its device type, and
+/// each of its service types.
+///
+#[derive(Clone, Debug)]
+pub struct DeviceResponses {
+    /// The unique device name, the `uuid:` prefixed portion of the `USN` header.
+    pub unique_device_name: Udn,
+    /// The location of the device description, taken from the first response received.
+    pub location: URL,
+    /// The distinct set of search targets advertised by the device, in the order received.
+    pub search_targets: Vec<SearchTarget>,
+    /// All the responses received for this device.
+    pub responses: Vec<Response>,
+}
+
+///
+/// Post-processing operations on a set of search responses, implemented for slices so that the
+/// result of `search_once` can be used directly.
+///
+pub trait Responses {
+    ///
+    /// Remove any responses with a duplicate `USN` value, the first response received is retained.
+    ///
+    fn dedup_by_usn(&self) -> Vec<Response>;
+
+    ///
+    /// Fold the set of responses into one entry per device, keyed by the UDN portion of the `USN`
+    /// header. Devices are returned in the order in which they first responded.
+    ///
+    fn group_by_device(&self) -> Vec<DeviceResponses>;
+}
+
+///
+/// Performs repeated multicast searches using the same socket, so that the multicast group is
+/// joined once rather than for each search; useful for applications that poll periodically.
+///
+#[derive(Debug)]
+pub struct Searcher {
+    options: Options,
+    transport: Arc<dyn Transport>,
+    owns_transport: bool,
+}
+
+///
+/// A search that runs in the background, repeating the multicast search on an interval and
+/// sending each newly discovered response to a channel; see [`SearchSession::start`].
+///
+#[derive(Debug)]
+pub struct SearchSession;
+
+///
+/// A running [`SearchSession`], the session is stopped when this value is dropped.
+///
+#[derive(Debug)]
+pub struct SearchHandle {
+    cancel: CancellationToken,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
 }
 
+// ------------------------------------------------------------------------------------------------
+// Public Values
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The delay between the end of one search and the start of the next in a [`SearchSession`].
+///
+pub const DEFAULT_SEARCH_INTERVAL: Duration = Duration::from_secs(10);
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
 ///
 /// Perform a multicast search but store the results in a cache that allows a client to keep
-/// the results around and use the `update` method to refresh the cache from the network.
+/// the results around and use the `refresh` method to remove those that are no longer valid.
 ///
 /// The search function can be configured using the [`Options`](struct.Options.html) struct,
 /// although the defaults are reasonable for most clients.
@@ -168,12 +351,24 @@ pub struct Response {
 /// # Parameters
 ///
 /// * `options` - protocol options such as the specification version to use and any network
-/// configuration values.
+///   configuration values.
 ///
 pub fn search(options: Options) -> Result<ResponseCache, Error> {
     info!("search - options: {:?}", options);
-    options.validate()?;
-    unsupported_operation("search").into()
+    let responses = search_once(options.clone())?;
+    let now = SystemTime::now();
+    Ok(ResponseCache {
+        minimum_refresh: options.max_wait_time,
+        options,
+        last_updated: now,
+        responses: responses
+            .into_iter()
+            .map(|response| CachedResponse {
+                expiration: now + response.max_age,
+                response,
+            })
+            .collect(),
+    })
 }
 
 ///
@@ -181,7 +376,9 @@ pub fn search(options: Options) -> Result<ResponseCache, Error> {
 /// in a cache.
 ///
 /// The search function can be configured using the [`Options`](struct.Options.html) struct,
-/// although the defaults are reasonable for most clients.
+/// although the defaults are reasonable for most clients. Responses that cannot be parsed, as
+/// determined by `options.strictness`, are logged and skipped; use [`search_results`] to
+/// retrieve them.
 ///
 /// # Specification
 ///
@@ -190,56 +387,40 @@ pub fn search(options: Options) -> Result<ResponseCache, Error> {
 /// # Parameters
 ///
 /// * `options` - protocol options such as the specification version to use and any network
-/// configuration values.
+///   configuration values.
 ///
 ///
 pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
-    info!("search_once - options: {:?}", options);
-    options.validate()?;
-    let mut message_builder = RequestBuilder::new(HTTP_METHOD_SEARCH);
-    // All headers from the original 1.0 specification.
-    message_builder
-        .add_header(HTTP_HEADER_HOST, MULTICAST_ADDRESS)
-        .add_header(HTTP_HEADER_MAN, HTTP_EXTENSION)
-        .add_header(HTTP_HEADER_MX, &format!("{}", options.max_wait_time))
-        .add_header(HTTP_HEADER_ST, &options.search_target.to_string());
-    // Headers added by 1.1 specification
-    if options.spec_version >= SpecVersion::V11 {
-        message_builder.add_header(
-            HTTP_HEADER_USER_AGENT,
-            &user_agent_string(options.spec_version, options.product_and_version.clone()),
-        );
-    }
-    // Headers added by 2.0 specification
-    if options.spec_version >= SpecVersion::V20 {
-        match &options.control_point {
-            Some(cp) => {
-                message_builder.add_header(HTTP_HEADER_CP_FN, &cp.friendly_name);
-                if let Some(uuid) = &cp.uuid {
-                    message_builder.add_header(HTTP_HEADER_CP_UUID, uuid);
-                }
-                if let Some(port) = cp.port {
-                    message_builder.add_header(HTTP_HEADER_TCP_PORT, &port.to_string());
-                }
-            }
-            None => {
-                error!("search_once - missing control point, required for UPnP/2.0");
-                return missing_required_field("control_point").into();
-            }
-        }
-    }
-    trace!("search_once - {:?}", &message_builder);
-    let raw_responses = multicast(
-        &message_builder.into(),
-        &MULTICAST_ADDRESS.parse().unwrap(),
-        &options.into(),
-    )?;
+    search_until(options, &CancellationToken::new())
+}
 
-    let mut responses: Vec<Response> = Vec::new();
-    for raw_response in raw_responses {
-        responses.push(raw_response.try_into()?);
-    }
-    Ok(responses)
+///
+/// Perform a multicast search as `search_once` does, but return the responses that could not be
+/// parsed alongside those that could. An error is only returned if the search itself failed.
+///
+/// # Parameters
+///
+/// * `options` - protocol options such as the specification version to use and any network
+///   configuration values.
+///
+pub fn search_results(options: Options) -> Result<SearchResults, Error> {
+    let receive_tcp = options.transport.is_none();
+    search_with(options, &CancellationToken::new(), receive_tcp)
+}
+
+///
+/// Perform a multicast search as `search_once` does, but allow the search to be ended early from
+/// another thread by cancelling `cancel`; in which case the responses received so far are returned.
+///
+/// # Parameters
+///
+/// * `options` - protocol options such as the specification version to use and any network
+///   configuration values.
+/// * `cancel` - a token that may be used to end the search before `max_wait_time` has elapsed.
+///
+pub fn search_until(options: Options, cancel: &CancellationToken) -> Result<Vec<Response>, Error> {
+    let receive_tcp = options.transport.is_none();
+    search_with(options, cancel, receive_tcp).map(|results| results.ok)
 }
 
 ///
@@ -247,7 +428,9 @@ pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
 /// in a cache.
 ///
 /// The search function can be configured using the [`Options`](struct.Options.html) struct,
-/// although the defaults are reasonable for most clients.
+/// although the defaults are reasonable for most clients. Responses that cannot be parsed, as
+/// determined by `options.strictness`, are logged and skipped; use [`search_results_to_device`]
+/// to retrieve them.
 ///
 /// # Specification
 ///
@@ -256,7 +439,7 @@ pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
 /// # Parameters
 ///
 /// * `options` - protocol options such as the specification version to use and any network
-/// configuration values.
+///   configuration values.
 /// * `device_address` - the address of the device to query.
 ///
 ///
@@ -264,34 +447,120 @@ pub fn search_once_to_device(
     options: Options,
     device_address: SocketAddr,
 ) -> Result<Vec<Response>, Error> {
+    search_results_to_device(options, device_address).map(|results| results.ok)
+}
+
+///
+/// Perform a unicast search as `search_once_to_device` does, but return the responses that could
+/// not be parsed alongside those that could. An error is only returned if the search itself
+/// failed.
+///
+/// # Parameters
+///
+/// * `options` - protocol options such as the specification version to use and any network
+///   configuration values.
+/// * `device_address` - the address of the device to query.
+///
+pub fn search_results_to_device(
+    options: Options,
+    device_address: SocketAddr,
+) -> Result<SearchResults, Error> {
+    let _span = info_span!(
+        "search",
+        st = %options.search_target,
+        version = %options.spec_version,
+        peer = %device_address
+    )
+    .entered();
     info!(
         "search_once_to_device - options: {:?}, device_address: {:?}",
         options, device_address
     );
     options.validate()?;
-    if options.spec_version >= SpecVersion::V11 {
-        let mut message_builder = RequestBuilder::new(HTTP_METHOD_SEARCH);
+    let rules = HeaderRules::new(SsdpMessage::UnicastSearch, options.spec_version);
+    if rules.is_supported() {
+        let mut message_builder = RequestBuilder::new(Method::Search.as_str());
         message_builder
-            .add_header(HTTP_HEADER_HOST, MULTICAST_ADDRESS)
-            .add_header(HTTP_HEADER_MAN, HTTP_EXTENSION)
-            .add_header(HTTP_HEADER_ST, &options.search_target.to_string())
-            .add_header(
-                HTTP_HEADER_USER_AGENT,
+            .man_discover()
+            .st(&options.search_target)
+            .header(
+                HeaderName::UserAgent,
                 &user_agent_string(options.spec_version, options.product_and_version.clone()),
             );
 
-        let raw_responses = multicast(&message_builder.into(), &device_address, &options.into())?;
+        let strictness = options.strictness;
+        let received = multicast_until(
+            &message_builder.build()?,
+            &device_address,
+            &options.into(),
+            &CancellationToken::default(),
+        )?;
 
+        // Ignore responses from other devices to any multicast search in progress on the same
+        // port.
+        let from_other = |source: &Option<SocketAddr>| {
+            source
+                .map(|source| source.ip() != device_address.ip())
+                .unwrap_or_default()
+        };
         let mut responses: Vec<Response> = Vec::new();
-        for raw_response in raw_responses {
-            responses.push(raw_response.try_into()?);
+        let mut failed = Vec::new();
+        for (source, e) in received.failed {
+            if !from_other(&source) {
+                observer::parse_failed(MessageKind::SearchResponse);
+                failed.push((source, e));
+            }
+        }
+        for raw_response in received.responses {
+            let source = raw_response.raw().source;
+            if from_other(&source) {
+                trace!(
+                    "search_results_to_device - ignoring response from {:?}",
+                    source
+                );
+                continue;
+            }
+            match parse_response(raw_response, strictness) {
+                Ok(response) => responses.push(response),
+                Err(e) => failed.push((source, e)),
+            }
         }
-        Ok(responses)
+        Ok(SearchResults {
+            ok: responses,
+            failed,
+        })
     } else {
         unsupported_version(options.spec_version).into()
     }
 }
 
+///
+/// Perform a unicast search of the device that sent `response`, as `search_once_to_device` does,
+/// at the port advertised in its `SEARCHPORT.UPNP.ORG` header, or the standard SSDP port if none
+/// was advertised. It is an error if the address of the device is not known.
+///
+/// # Parameters
+///
+/// * `options` - protocol options such as the specification version to use and any network
+///   configuration values.
+/// * `response` - a response, or notification, previously received from the device.
+///
+pub fn search_once_to_responder(
+    options: Options,
+    response: &Response,
+) -> Result<Vec<Response>, Error> {
+    match response.search_address() {
+        Some(device_address) => search_once_to_device(options, device_address),
+        None => {
+            error!(
+                "search_once_to_responder - address of {} not known",
+                response.service_name
+            );
+            missing_required_field("source").into()
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -302,9 +571,9 @@ impl Display for SearchTarget {
             f,
             "{}",
             match self {
-                SearchTarget::All => "ssdp::all".to_string(),
+                SearchTarget::All => "ssdp:all".to_string(),
                 SearchTarget::RootDevice => "upnp:rootdevice".to_string(),
-                SearchTarget::Device(device) => format!("uuid:{}", device),
+                SearchTarget::Device(device) => device.to_string(),
                 SearchTarget::DeviceType(device) =>
                     format!("urn:schemas-upnp-org:device:{}", device),
                 SearchTarget::ServiceType(service) =>
@@ -313,11 +582,23 @@ impl Display for SearchTarget {
                     format!("urn:{}:device:{}", domain, device),
                 SearchTarget::DomainServiceType(domain, service) =>
                     format!("urn:{}:service:{}", domain, service),
+                SearchTarget::DeviceTypeVersioned { .. }
+                | SearchTarget::ServiceTypeVersioned { .. } => self.unversioned().to_string(),
             }
         )
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for SearchTarget {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for SearchTarget {
     type Err = MessageFormatError;
 
@@ -326,18 +607,18 @@ impl FromStr for SearchTarget {
             static ref DOMAIN_URN: Regex =
                 Regex::new(r"^urn:([^:]+):(device|service):(.+)$").unwrap();
         }
-        if s == "ssdp::all" {
+        if s == "ssdp:all" {
             Ok(SearchTarget::All)
         } else if s == "upnp:rootdevice" {
             Ok(SearchTarget::RootDevice)
-        } else if let Some(device) = s.strip_prefix("uuid:") {
-            Ok(SearchTarget::Device(device.to_string()))
+        } else if s.starts_with("uuid:") {
+            Ok(SearchTarget::Device(Udn::from_str(s)?))
         } else if let Some(device_type) = s.strip_prefix("urn:schemas-upnp-org:device:") {
             Ok(SearchTarget::DeviceType(device_type.to_string()))
         } else if let Some(service_type) = s.strip_prefix("urn:schemas-upnp-org:service:") {
             Ok(SearchTarget::ServiceType(service_type.to_string()))
-        } else if let Some(domain) = s.strip_prefix("urn:") {
-            match DOMAIN_URN.captures(domain) {
+        } else if s.starts_with("urn:") {
+            match DOMAIN_URN.captures(s) {
                 Some(captures) => {
                     if captures.get(2).unwrap().as_str() == "device" {
                         Ok(SearchTarget::DomainDeviceType(
@@ -363,6 +644,153 @@ impl FromStr for SearchTarget {
     }
 }
 
+#[allow(non_upper_case_globals)]
+impl SearchTarget {
+    ///
+    /// Deprecated name of [`SearchTarget::RootDevice`].
+    ///
+    #[deprecated(note = "use SearchTarget::RootDevice")]
+    pub const RootDevices: SearchTarget = SearchTarget::RootDevice;
+
+    ///
+    /// Return the target sent on the wire for this target; for the versioned type targets this is
+    /// the type with the minimum version required, all other targets are returned unchanged.
+    ///
+    pub fn unversioned(&self) -> SearchTarget {
+        match self {
+            SearchTarget::DeviceTypeVersioned { name, version } => {
+                SearchTarget::DeviceType(format!("{}:{}", name, version.minimum()))
+            }
+            SearchTarget::ServiceTypeVersioned { name, version } => {
+                SearchTarget::ServiceType(format!("{}:{}", name, version.minimum()))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    ///
+    /// Returns `true` if a device, or service, implementing `type_id` satisfies this search
+    /// target. Only the type targets accept any type, for the versioned targets the version of
+    /// `type_id` must meet the version requirement.
+    ///
+    pub fn accepts(&self, type_id: &TypeID) -> bool {
+        match self {
+            SearchTarget::DeviceType(_)
+            | SearchTarget::DomainDeviceType(_, _)
+            | SearchTarget::ServiceType(_)
+            | SearchTarget::DomainServiceType(_, _) => TypeID::try_from(self)
+                .map(|requested| type_id.is_compatible_with(&requested))
+                .unwrap_or(false),
+            SearchTarget::DeviceTypeVersioned { name, version } => {
+                type_id.is_same_type_as(&TypeID::new_device(name.clone(), 0))
+                    && version.matches(type_id.version())
+            }
+            SearchTarget::ServiceTypeVersioned { name, version } => {
+                type_id.is_same_type_as(&TypeID::new_service(name.clone(), 0))
+                    && version.matches(type_id.version())
+            }
+            _ => false,
+        }
+    }
+
+    ///
+    /// Return the set of matches for this search target against the device `root`, its embedded
+    /// devices, and all their services, following the UDA matching rules:
+    ///
+    /// * `ssdp:all` expands to the root device, each device UUID, each device type, and each
+    ///   (distinct per device) service type.
+    /// * `upnp:rootdevice` matches the root device only.
+    /// * `uuid:` matches the root or embedded device with the same UDN.
+    /// * device and service types match where the domain and name are the same and the version
+    ///   implemented is greater than or equal to the version searched for. The UPnP domain may be
+    ///   given explicitly or implicitly.
+    /// * versioned device and service types match where the version implemented meets the version
+    ///   requirement, the match is reported for the unversioned target.
+    ///
+    pub fn matches(&self, root: &DeviceRoot) -> Vec<MatchedTarget> {
+        let devices = root.device.all_devices();
+        let root_udn = &root.device.unique_device_name;
+        match self {
+            SearchTarget::All => {
+                let mut matched = vec![MatchedTarget::new(
+                    SearchTarget::RootDevice,
+                    UniqueServiceName::root_device(root_udn.clone()),
+                )];
+                for device in devices {
+                    let udn = &device.unique_device_name;
+                    matched.push(MatchedTarget::new(
+                        SearchTarget::Device(udn.clone()),
+                        UniqueServiceName::new(udn.clone()),
+                    ));
+                    matched.push(MatchedTarget::for_type(udn, &device.device_type));
+                    for service in &device.service_list {
+                        let service_match = MatchedTarget::for_type(udn, &service.service_type);
+                        if !matched.contains(&service_match) {
+                            matched.push(service_match);
+                        }
+                    }
+                }
+                matched
+            }
+            SearchTarget::RootDevice => vec![MatchedTarget::new(
+                SearchTarget::RootDevice,
+                UniqueServiceName::root_device(root_udn.clone()),
+            )],
+            SearchTarget::Device(udn) => devices
+                .iter()
+                .filter(|device| &device.unique_device_name == udn)
+                .map(|_| MatchedTarget::new(self.clone(), UniqueServiceName::new(udn.clone())))
+                .collect(),
+            SearchTarget::DeviceType(_)
+            | SearchTarget::DomainDeviceType(_, _)
+            | SearchTarget::DeviceTypeVersioned { .. } => devices
+                .iter()
+                .filter(|device| self.accepts(&device.device_type))
+                .map(|device| self.matched_for(&device.unique_device_name))
+                .collect(),
+            SearchTarget::ServiceType(_)
+            | SearchTarget::DomainServiceType(_, _)
+            | SearchTarget::ServiceTypeVersioned { .. } => devices
+                .iter()
+                .filter(|device| {
+                    device
+                        .service_list
+                        .iter()
+                        .any(|service| self.accepts(&service.service_type))
+                })
+                .map(|device| self.matched_for(&device.unique_device_name))
+                .collect(),
+        }
+    }
+
+    fn matched_for(&self, udn: &Udn) -> MatchedTarget {
+        let search_target = self.unversioned();
+        MatchedTarget::new(
+            search_target.clone(),
+            UniqueServiceName::with_type(udn.clone(), search_target).unwrap(),
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MatchedTarget {
+    fn new(search_target: SearchTarget, service_name: UniqueServiceName) -> Self {
+        Self {
+            search_target,
+            service_name,
+        }
+    }
+
+    fn for_type(udn: &Udn, type_id: &TypeID) -> Self {
+        let search_target = SearchTarget::from(type_id);
+        Self {
+            service_name: UniqueServiceName::with_type(udn.clone(), search_target.clone()).unwrap(),
+            search_target,
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl Options {
@@ -380,9 +808,20 @@ impl Options {
             } else {
                 2
             },
-            max_wait_time: 2,
+            multicast_ttl: spec_version.default_multicast_ttl(),
+            max_wait_time: Duration::from_secs(2),
+            deadline: None,
             product_and_version: None,
             control_point: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            oversized: Oversized::GrowBuffer,
+            reuse_address: false,
+            reuse_port: false,
+            bind_to_device: false,
+            send_count: 1,
+            send_interval: DEFAULT_SEND_INTERVAL,
+            strictness: Strictness::Strict,
+            transport: None,
         }
     }
 
@@ -402,25 +841,38 @@ impl Options {
         lazy_static! {
             static ref UA_VERSION: Regex = Regex::new(r"^[\d\.]+$").unwrap();
         }
-        if self.max_wait_time < 1 || self.max_wait_time > 120 {
+        if self.max_wait_time.is_zero() || self.max_wait_time > MAX_WAIT_TIME {
             error!(
-                "validate - max_wait_time must be between 1..120 ({})",
+                "validate - max_wait_time must be between 0..120 seconds ({:?})",
                 self.max_wait_time
             );
-            return invalid_field_value("max_wait_time", &self.max_wait_time.to_string()).into();
+            return invalid_field_value("max_wait_time", format!("{:?}", self.max_wait_time))
+                .into();
         }
-        if self.spec_version >= SpecVersion::V11 {
+        if self.multicast_ttl < 1 || self.multicast_ttl > 255 {
+            error!(
+                "validate - multicast_ttl must be between 1..255 ({})",
+                self.multicast_ttl
+            );
+            return invalid_field_value("multicast_ttl", self.multicast_ttl.to_string()).into();
+        }
+        if self.send_count < 1 {
+            error!("validate - send_count must be at least 1");
+            return invalid_field_value("send_count", self.send_count.to_string()).into();
+        }
+        let rules = HeaderRules::new(SsdpMessage::Search, self.spec_version);
+        if rules.allows(HeaderName::UserAgent) {
             if let Some(user_agent) = &self.product_and_version {
-                if user_agent.name.contains('/') || !UA_VERSION.is_match(&user_agent.version) {
+                if user_agent.name().contains('/') || !UA_VERSION.is_match(user_agent.version()) {
                     error!(
                         "validate - user_agent needs to match 'ProductName/Version' ({:?})",
                         user_agent
                     );
-                    return invalid_field_value("UserAgent", &user_agent.to_string()).into();
+                    return invalid_field_value("UserAgent", user_agent.to_string()).into();
                 }
             }
         }
-        if self.spec_version >= SpecVersion::V20 {
+        if rules.requirement(HeaderName::CpFriendlyName) == Requirement::Required {
             if self.control_point.is_none() {
                 error!("validate - control_point required");
                 return missing_required_field("ControlPoint").into();
@@ -434,6 +886,15 @@ impl Options {
         }
         Ok(())
     }
+
+    ///
+    /// The value of the `MX` header, `max_wait_time` in whole seconds rounded up.
+    ///
+    fn max_wait_seconds(&self) -> u8 {
+        let seconds =
+            self.max_wait_time.as_secs() + u64::from(self.max_wait_time.subsec_nanos() > 0);
+        seconds.clamp(1, MAX_WAIT_TIME.as_secs()) as u8
+    }
 }
 
 impl From<Options> for MulticastOptions {
@@ -442,13 +903,49 @@ impl From<Options> for MulticastOptions {
             network_interface: options.network_interface,
             network_version: options.network_version,
             packet_ttl: options.packet_ttl,
-            recv_timeout: options.max_wait_time as u64,
+            multicast_ttl: options.multicast_ttl,
+            recv_timeout: options.max_wait_time
+                + RESPONSE_LATENCY_MARGIN.min(options.max_wait_time / 2),
+            deadline: options.deadline,
+            buffer_size: options.buffer_size,
+            oversized: options.oversized,
+            reuse_address: options.reuse_address,
+            reuse_port: options.reuse_port,
+            bind_to_device: options.bind_to_device,
+            send_count: options.send_count,
+            send_interval: options.send_interval,
+            transport: options.transport,
             ..Default::default()
         }
     }
 }
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Added to the `MX` value to form the receive window, allowing for network latency in responses
+/// sent at the very end of the device's random delay; at most half the wait time, so that short
+/// waits remain short.
+///
+const RESPONSE_LATENCY_MARGIN: Duration = Duration::from_secs(1);
+
+///
+/// The largest `MX` value allowed by the specification.
+///
+const MAX_WAIT_TIME: Duration = Duration::from_secs(120);
+
+///
+/// The port devices listen on for search requests, unless they specify another with the
+/// `SEARCHPORT.UPNP.ORG` header.
+///
+const DEFAULT_SEARCH_PORT: u16 = 1900;
+
+const REQUIRED_HEADERS_LENIENT: [&str; 3] = [HTTP_HEADER_LOCATION, HTTP_HEADER_ST, HTTP_HEADER_USN];
+
+///
+/// The `max-age` used in lenient mode when a response has no valid `CACHE-CONTROL` header.
+///
+const DEFAULT_MAX_AGE: u64 = 1800;
+
 const REQUIRED_HEADERS_V10: [&str; 7] = [
     HTTP_HEADER_CACHE_CONTROL,
     HTTP_HEADER_DATE,
@@ -463,91 +960,207 @@ impl TryFrom<MulticastResponse> for Response {
     type Error = Error;
 
     fn try_from(response: MulticastResponse) -> Result<Self, Self::Error> {
+        Response::parse(response, Strictness::Strict)
+    }
+}
+
+impl Response {
+    ///
+    /// The device or service type in the `ST` header, `None` if the response is for the root
+    /// device or a device UUID.
+    ///
+    pub fn type_id(&self) -> Option<TypeID> {
+        TypeID::try_from(&self.search_target).ok()
+    }
+
+    ///
+    /// The message this response was parsed from, and the address of the device that sent it.
+    ///
+    pub fn raw(&self) -> &RawResponse {
+        &self.raw
+    }
+
+    ///
+    /// The address to which unicast search requests should be sent to reach the device that sent
+    /// this response; this is the response's source address with the port taken from any
+    /// `SEARCHPORT.UPNP.ORG` header, or the standard SSDP port.
+    ///
+    pub fn search_address(&self) -> Option<SocketAddr> {
+        self.source.map(|source| {
+            SocketAddr::new(source.ip(), self.search_port.unwrap_or(DEFAULT_SEARCH_PORT))
+        })
+    }
+
+    ///
+    /// The value of the header `name`, in any case, from `other_headers` parsed as a `T`; `None` if
+    /// the header is not present, and an error if its value cannot be parsed.
+    ///
+    pub fn header<T: FromStr>(&self, name: &str) -> Result<Option<T>, Error> {
+        match self.other_headers.get(&name.to_ascii_uppercase()) {
+            None => Ok(None),
+            Some(value) => Ok(Some(headers::check_parsed_value(value.trim(), name)?)),
+        }
+    }
+
+    ///
+    /// All headers in `other_headers` whose names start with `prefix`, in any case, ordered by
+    /// name; for example all `X-RINCON-` headers sent by a vendor.
+    ///
+    pub fn headers_matching(&self, prefix: &str) -> Vec<(&str, &str)> {
+        let prefix = prefix.to_ascii_uppercase();
+        let mut matching: Vec<(&str, &str)> = self
+            .other_headers
+            .iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        matching.sort_unstable();
+        matching
+    }
+
+    ///
+    /// The network location identifier from the `01-NLS` header, sent by many devices in place of,
+    /// or as well as, `BOOTID.UPNP.ORG`.
+    ///
+    pub fn nls(&self) -> Option<&str> {
+        self.other_headers
+            .get(HTTP_HEADER_NLS)
+            .map(|value| value.trim())
+    }
+
+    ///
+    /// The parameters of the `X-AV-Server-Info` header sent by Sony AV devices, such as the model
+    /// name `mn`.
+    ///
+    pub fn av_server_info(&self) -> Option<Vec<(String, String)>> {
+        self.other_headers
+            .get(HTTP_HEADER_X_AV_SERVER_INFO)
+            .map(|value| headers::parse_parameters(value))
+    }
+
+    ///
+    /// The parameters of the `X-AV-Physical-Unit-Info` header sent by Sony AV devices, such as the
+    /// physical unit name `pa`.
+    ///
+    pub fn av_physical_unit_info(&self) -> Option<Vec<(String, String)>> {
+        self.other_headers
+            .get(HTTP_HEADER_X_AV_PHYSICAL_UNIT_INFO)
+            .map(|value| headers::parse_parameters(value))
+    }
+
+    ///
+    /// Parse a search response from the raw message; `strictness` determines whether headers
+    /// that are missing or malformed cause the whole response to be rejected. In lenient mode
+    /// only the `LOCATION`, `ST`, and `USN` headers are required, other missing values take
+    /// their defaults, and any malformed value is retained in `other_headers`.
+    ///
+    pub fn parse(response: MulticastResponse, strictness: Strictness) -> Result<Self, Error> {
         lazy_static! {
-            static ref UA_ALL: Regex =
-                Regex::new(r"^([^/]+)/([\d\.]+),?[ ]+([^/]+)/([\d\.]+),?[ ]+([^/]+)/([\d\.]+)$")
-                    .unwrap();
+            static ref MAX_AGE: Regex = Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap();
+            static ref MAX_AGE_ANY_CASE: Regex =
+                Regex::new(r#"(?i)max-age\s*=\s*"?(\d+)"#).unwrap();
         }
-        headers::check_required(&response.headers, &REQUIRED_HEADERS_V10)?;
-        headers::check_empty(
-            response.headers.get(HTTP_HEADER_EXT).unwrap(),
-            HTTP_HEADER_EXT,
-        )?;
+        let lenient = strictness == Strictness::Lenient;
+        let header = |name: &str| {
+            response
+                .header(name)
+                .map(|value| if lenient { value.trim() } else { value })
+        };
+        let mut other_headers: HashMap<String, String> = response
+            .headers()
+            .map(|(k, v)| (k.to_ascii_uppercase(), v))
+            .filter(|(k, _)| !REQUIRED_HEADERS_V10.contains(&k.as_str()))
+            .map(|(k, v)| (k, if lenient { v.trim() } else { v }.to_string()))
+            .collect();
+        let mut set_aside = |name: &str| {
+            warn!("parse - ignoring malformed header {}", name);
+            if let Some(value) = header(name) {
+                let _ = other_headers.insert(name.to_string(), value.to_string());
+            }
+        };
 
-        let server = response.headers.get(HTTP_HEADER_SERVER).unwrap();
-        let versions = match UA_ALL.captures(server) {
-            Some(captures) => ProductVersions {
-                product: ProductVersion {
-                    name: captures.get(5).unwrap().as_str().to_string(),
-                    version: captures.get(6).unwrap().as_str().to_string(),
-                },
-                upnp: ProductVersion {
-                    name: captures.get(3).unwrap().as_str().to_string(),
-                    version: captures.get(4).unwrap().as_str().to_string(),
-                },
-                platform: ProductVersion {
-                    name: captures.get(1).unwrap().as_str().to_string(),
-                    version: captures.get(2).unwrap().as_str().to_string(),
-                },
-            },
-            None => {
+        if lenient {
+            headers::check_required_by(|name| header(name).is_some(), &REQUIRED_HEADERS_LENIENT)?;
+        } else {
+            let required: Vec<&str> =
+                HeaderRules::new(SsdpMessage::SearchResponse, SpecVersion::V10)
+                    .required()
+                    .map(|header| header.as_str())
+                    .collect();
+            headers::check_required_by(|name| header(name).is_some(), &required)?;
+            headers::check_empty(header(HTTP_HEADER_EXT).unwrap(), HTTP_HEADER_EXT)?;
+        }
+
+        let server = header(HTTP_HEADER_SERVER).unwrap_or_default();
+        let versions = match ProductVersions::from_str(server) {
+            Ok(versions) => versions,
+            Err(_) if lenient => {
+                set_aside(HTTP_HEADER_SERVER);
+                ProductVersions::parse_lenient(server)
+            }
+            Err(_) => {
                 error!("invalid value for server header '{}", server);
                 return invalid_field_value(HTTP_HEADER_SERVER, server).into();
             }
         };
 
-        let max_age = headers::check_parsed_value::<u64>(
-            &headers::check_regex(
-                response.headers.get(HTTP_HEADER_CACHE_CONTROL).unwrap(),
+        let cache_control = header(HTTP_HEADER_CACHE_CONTROL).unwrap_or_default();
+        let max_age = if lenient {
+            match MAX_AGE_ANY_CASE
+                .captures(cache_control)
+                .and_then(|captures| captures.get(1).unwrap().as_str().parse::<u64>().ok())
+            {
+                Some(max_age) => max_age,
+                None => {
+                    set_aside(HTTP_HEADER_CACHE_CONTROL);
+                    DEFAULT_MAX_AGE
+                }
+            }
+        } else {
+            headers::check_parsed_value::<u64>(
+                &headers::check_regex(cache_control, HTTP_HEADER_CACHE_CONTROL, &MAX_AGE)?,
                 HTTP_HEADER_CACHE_CONTROL,
-                &Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap(),
-            )?,
-            HTTP_HEADER_CACHE_CONTROL,
-        )?;
+            )?
+        };
 
-        let date = headers::check_not_empty(
-            response.headers.get(HTTP_HEADER_DATE),
-            "Thu, 01 Jan 1970 00:00:00 GMT",
-        );
+        let date = match header(HTTP_HEADER_DATE).map(date::parse) {
+            Some(Ok(date)) => date,
+            Some(Err(_)) if lenient => {
+                set_aside(HTTP_HEADER_DATE);
+                SystemTime::now()
+            }
+            Some(Err(e)) => return Err(e.into()),
+            None => SystemTime::now(),
+        };
 
-        let location = headers::check_not_empty(
-            response.headers.get(HTTP_HEADER_LOCATION),
-            "http://www.example.org",
-        );
+        let location =
+            headers::check_not_empty(header(HTTP_HEADER_LOCATION), "http://www.example.org");
 
-        let service_name =
-            headers::check_not_empty(response.headers.get(HTTP_HEADER_USN), "undefined");
+        let service_name = headers::check_not_empty(header(HTTP_HEADER_USN), "undefined");
 
-        let search_target =
-            headers::check_not_empty(response.headers.get(HTTP_HEADER_ST), "undefined");
+        let search_target = headers::check_not_empty(header(HTTP_HEADER_ST), "undefined");
 
-        let mut boot_id = 0u64;
-        let mut config_id: Option<u64> = None;
+        let mut boot_id = 0u32;
+        let mut config_id: Option<u32> = None;
         let mut search_port: Option<u16> = None;
-        if versions.upnp.version == SpecVersion::V20.to_string() {
-            boot_id = headers::check_parsed_value::<u64>(
-                response
-                    .headers
-                    .get(HTTP_HEADER_BOOTID)
-                    .unwrap_or(&"0".to_string()),
-                HTTP_HEADER_BOOTID,
-            )?;
-            if let Some(s) = response.headers.get(HTTP_HEADER_CONFIGID) {
-                config_id = s.parse::<u64>().ok();
+        if versions.upnp_version().version() == &SpecVersion::V20.to_string() {
+            let value = header(HTTP_HEADER_BOOTID).unwrap_or("0");
+            boot_id = match parse_boot_id(value, HTTP_HEADER_BOOTID) {
+                Ok(boot_id) => boot_id,
+                Err(_) if lenient => {
+                    set_aside(HTTP_HEADER_BOOTID);
+                    0
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(s) = header(HTTP_HEADER_CONFIGID) {
+                config_id = parse_config_id(s).ok();
             }
-            if let Some(s) = response.headers.get(HTTP_HEADER_SEARCH_PORT) {
+            if let Some(s) = header(HTTP_HEADER_SEARCH_PORT) {
                 search_port = s.parse::<u16>().ok();
             }
         }
 
-        let remaining_headers: HashMap<String, String> = response
-            .headers
-            .clone()
-            .iter()
-            .filter(|(k, _)| !REQUIRED_HEADERS_V10.contains(&k.as_str()))
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-
         Ok(Response {
             max_age: Duration::from_secs(max_age),
             date,
@@ -556,20 +1169,93 @@ impl TryFrom<MulticastResponse> for Response {
                 .map_err(|_| invalid_header_value(HTTP_HEADER_LOCATION, &location))?,
             search_target: SearchTarget::from_str(&search_target)
                 .map_err(|_| invalid_field_value("SearchTarget", search_target))?,
-            service_name: URI::from_str(&service_name)
-                .map_err(|_| invalid_field_value("URI", service_name))?,
+            service_name: UniqueServiceName::from_str(&service_name)
+                .map_err(|_| invalid_header_value(HTTP_HEADER_USN, service_name))?,
             boot_id,
             config_id,
             search_port,
-            other_headers: remaining_headers,
+            other_headers,
+            source: response.raw().source,
+            raw: response.raw().clone(),
         })
     }
 }
 
 // ------------------------------------------------------------------------------------------------
 
+impl Responses for [Response] {
+    fn dedup_by_usn(&self) -> Vec<Response> {
+        let mut seen: HashSet<String> = Default::default();
+        self.iter()
+            .filter(|response| seen.insert(response.service_name.to_string()))
+            .cloned()
+            .collect()
+    }
+
+    fn group_by_device(&self) -> Vec<DeviceResponses> {
+        let mut devices: Vec<DeviceResponses> = Default::default();
+        for response in self {
+            let unique_device_name = response.service_name.device().clone();
+            match devices
+                .iter_mut()
+                .find(|device| device.unique_device_name == unique_device_name)
+            {
+                Some(device) => {
+                    if !device.search_targets.contains(&response.search_target) {
+                        device.search_targets.push(response.search_target.clone());
+                    }
+                    device.responses.push(response.clone());
+                }
+                None => devices.push(DeviceResponses {
+                    unique_device_name,
+                    location: response.location.clone(),
+                    search_targets: vec![response.search_target.clone()],
+                    responses: vec![response.clone()],
+                }),
+            }
+        }
+        devices
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
 impl ResponseCache {
+    ///
+    /// Remove responses whose advertisement has expired, and those for devices that no longer
+    /// answer a [`probe`] of their description location; each distinct
+    /// location is probed once.
+    ///
+    #[cfg(feature = "net")]
     pub fn refresh(&mut self) -> Self {
+        self.refresh_by(probe::is_alive::<Response>)
+    }
+
+    ///
+    /// As [`ResponseCache::refresh`], probing each location using the provided client.
+    ///
+    pub fn refresh_with(&mut self, client: &dyn HttpClient) -> Self {
+        self.refresh_by(|response| probe::is_alive_with(response, client))
+    }
+
+    fn refresh_by<F>(&mut self, is_alive: F) -> Self
+    where
+        F: Fn(&Response) -> bool,
+    {
+        let now = SystemTime::now();
+        self.responses.retain(|cached| cached.expiration > now);
+        let mut alive: HashMap<String, bool> = Default::default();
+        self.responses.retain(|cached| {
+            *alive
+                .entry(cached.response.location.to_string())
+                .or_insert_with(|| is_alive(&cached.response))
+        });
+        debug!(
+            "refresh - {} responses remain for {} locations",
+            self.responses.len(),
+            alive.len()
+        );
+        self.last_updated = now;
         self.to_owned()
     }
 
@@ -582,10 +1268,869 @@ impl ResponseCache {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl Searcher {
+    ///
+    /// Create a searcher, and its socket, using `options`; if `options` provides a transport it
+    /// is used instead.
+    ///
+    pub fn new(options: Options) -> Result<Self, Error> {
+        info!("Searcher::new - options: {:?}", options);
+        options.validate()?;
+        let (transport, owns_transport) = match &options.transport {
+            Some(transport) => (transport.clone(), false),
+            None => {
+                let multicast_options: MulticastOptions = options.clone().into();
+                (
+                    Arc::from(default_transport(
+                        &MULTICAST_ADDRESS.parse().unwrap(),
+                        &multicast_options,
+                    )?),
+                    true,
+                )
+            }
+        };
+        Ok(Self {
+            options,
+            transport,
+            owns_transport,
+        })
+    }
+
+    ///
+    /// The options used for each search.
+    ///
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    ///
+    /// Perform a multicast search, as `search_once` does, using this searcher's socket.
+    ///
+    pub fn search(&self) -> Result<Vec<Response>, Error> {
+        self.search_until(&CancellationToken::new())
+    }
+
+    ///
+    /// Perform a multicast search, as `search_until` does, using this searcher's socket.
+    ///
+    pub fn search_until(&self, cancel: &CancellationToken) -> Result<Vec<Response>, Error> {
+        self.search_results(cancel).map(|results| results.ok)
+    }
+
+    ///
+    /// Perform a multicast search, as `search_results` does, using this searcher's socket.
+    ///
+    pub fn search_results(&self, cancel: &CancellationToken) -> Result<SearchResults, Error> {
+        let mut options = self.options.clone();
+        options.transport = Some(self.transport.clone());
+        search_with(options, cancel, self.owns_transport)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl SearchSession {
+    ///
+    /// Start a background search, repeated every `DEFAULT_SEARCH_INTERVAL`, returning a handle
+    /// that stops the search when dropped and the receiving end of a channel of responses.
+    ///
+    /// A response is sent the first time its `USN` is seen, and again only if it is seen after
+    /// its previous advertisement has expired. The session also stops if the receiver is dropped.
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - protocol options such as the specification version to use and any network
+    ///   configuration values.
+    ///
+    pub fn start(options: Options) -> Result<(SearchHandle, Receiver<Response>), Error> {
+        Self::start_with_interval(options, DEFAULT_SEARCH_INTERVAL)
+    }
+
+    ///
+    /// As [`SearchSession::start`], waiting `interval` between the end of one search and the
+    /// start of the next.
+    ///
+    pub fn start_with_interval(
+        options: Options,
+        interval: Duration,
+    ) -> Result<(SearchHandle, Receiver<Response>), Error> {
+        info!(
+            "SearchSession::start - options: {:?}, interval: {:?}",
+            options, interval
+        );
+        let searcher = Searcher::new(options)?;
+        let cancel = CancellationToken::new();
+        let (stop, stopped) = channel();
+        let (sender, receiver) = channel();
+        let thread = {
+            let cancel = cancel.clone();
+            spawn(move || run_session(&searcher, interval, &cancel, &stopped, &sender))
+        };
+        Ok((
+            SearchHandle {
+                cancel,
+                stop: Some(stop),
+                thread: Some(thread),
+            },
+            receiver,
+        ))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl SearchHandle {
+    ///
+    /// Stop the session, ending any search in progress, and wait for it to finish.
+    ///
+    pub fn stop(self) {}
+
+    fn stop_thread(&mut self) {
+        self.cancel.cancel();
+        let _ = self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn search_with(
+    options: Options,
+    cancel: &CancellationToken,
+    #[cfg_attr(not(feature = "net"), allow(unused_variables))] receive_tcp: bool,
+) -> Result<SearchResults, Error> {
+    let _span = info_span!(
+        "search",
+        st = %options.search_target,
+        version = %options.spec_version
+    )
+    .entered();
+    info!("search_with - options: {:?}", options);
+    options.validate()?;
+    let rules = HeaderRules::new(SsdpMessage::Search, options.spec_version);
+    let mut message_builder = RequestBuilder::new(Method::Search.as_str());
+    message_builder
+        .man_discover()
+        .mx(options.max_wait_seconds())
+        .st(&options.search_target);
+    if rules.allows(HeaderName::UserAgent) {
+        message_builder.header(
+            HeaderName::UserAgent,
+            &user_agent_string(options.spec_version, options.product_and_version.clone()),
+        );
+    }
+    if rules.allows(HeaderName::CpFriendlyName) {
+        match &options.control_point {
+            Some(cp) => {
+                message_builder.header(HeaderName::CpFriendlyName, &cp.friendly_name);
+                if let Some(uuid) = &cp.uuid {
+                    message_builder.header(HeaderName::CpUuid, &uuid.hyphenated().to_string());
+                }
+                if let Some(port) = cp.port {
+                    message_builder.header(HeaderName::TcpPort, &port.to_string());
+                }
+            }
+            None if rules.requirement(HeaderName::CpFriendlyName) == Requirement::Required => {
+                error!("search_until - missing control point, required for UPnP/2.0");
+                return missing_required_field("control_point").into();
+            }
+            None => {}
+        }
+    }
+    trace!("search_until - {:?}", &message_builder);
+    #[cfg(feature = "net")]
+    let reply_port = match &options.control_point {
+        Some(cp) if rules.allows(HeaderName::TcpPort) => cp.port,
+        _ => None,
+    };
+    let strictness = options.strictness;
+    let search_target = options.search_target.clone();
+    let multicast_options: MulticastOptions = options.into();
+    // TCP replies are only received on a socket of our own, not over a provided transport.
+    #[cfg(feature = "net")]
+    let tcp_listener = match reply_port {
+        Some(port) if receive_tcp => Some(create_tcp_listener(port, &multicast_options)?),
+        _ => None,
+    };
+
+    #[allow(unused_mut)]
+    let mut received = multicast_until(
+        &message_builder.build()?,
+        &MULTICAST_ADDRESS.parse().unwrap(),
+        &multicast_options,
+        cancel,
+    )?;
+
+    #[cfg(feature = "net")]
+    if let Some(tcp_listener) = tcp_listener {
+        received.append(tcp_receive_using(&tcp_listener, &multicast_options)?);
+    }
+
+    let mut responses: Vec<Response> = Vec::new();
+    let mut failed = received.failed;
+    for _ in &failed {
+        observer::parse_failed(MessageKind::SearchResponse);
+    }
+    for raw_response in received.responses {
+        let source = raw_response.raw().source;
+        match parse_response(raw_response, strictness) {
+            Ok(response) if reports_accepted_type(&search_target, &response) => {
+                responses.push(response)
+            }
+            Ok(_) => {}
+            Err(e) => failed.push((source, e)),
+        }
+    }
+    // Devices respond to each copy of a repeated request, and may respond over both UDP and TCP.
+    Ok(SearchResults {
+        ok: responses.dedup_by_usn(),
+        failed,
+    })
+}
+
+fn run_session(
+    searcher: &Searcher,
+    interval: Duration,
+    cancel: &CancellationToken,
+    stopped: &Receiver<()>,
+    sender: &Sender<Response>,
+) {
+    let mut seen: Vec<CachedResponse> = Default::default();
+    while !cancel.is_cancelled() {
+        match searcher.search_until(cancel) {
+            Ok(responses) => {
+                let now = SystemTime::now();
+                seen.retain(|cached| cached.expiration > now);
+                for response in responses {
+                    if seen
+                        .iter()
+                        .any(|cached| cached.response.service_name == response.service_name)
+                    {
+                        continue;
+                    }
+                    seen.push(CachedResponse {
+                        expiration: now + response.max_age,
+                        response: response.clone(),
+                    });
+                    if sender.send(response).is_err() {
+                        debug!("run_session - receiver dropped, stopping");
+                        return;
+                    }
+                }
+            }
+            Err(e) => warn!("run_session - search failed: {:?}", e),
+        }
+        // The sender is only dropped, never used, so any result other than a timeout means stop.
+        if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+            break;
+        }
+    }
+    debug!("run_session - stopped");
+}
+
+fn parse_response(
+    raw_response: MulticastResponse,
+    strictness: Strictness,
+) -> Result<Response, Error> {
+    observer::received(MessageKind::SearchResponse);
+    let peer = raw_response.raw().source;
+    match Response::parse(raw_response, strictness) {
+        Ok(response) => {
+            debug!(
+                usn = %response.service_name,
+                st = %response.search_target,
+                peer = ?peer,
+                "received search response"
+            );
+            Ok(response)
+        }
+        Err(e) => {
+            observer::parse_failed(MessageKind::SearchResponse);
+            warn!(peer = ?peer, error = %e, "could not parse search response");
+            Err(e)
+        }
+    }
+}
+
+///
+/// For the versioned type targets, exclude responses that report a type version that does not
+/// meet the requirement. Devices usually respond with the type searched for, rather than the
+/// type they implement, so the version implemented is not always known.
+///
+fn reports_accepted_type(search_target: &SearchTarget, response: &Response) -> bool {
+    match search_target {
+        SearchTarget::DeviceTypeVersioned { .. } | SearchTarget::ServiceTypeVersioned { .. } => {
+            response
+                .type_id()
+                .map(|reported| search_target.accepts(&reported))
+                .unwrap_or(true)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_seconds<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_u64(duration.as_secs())
+}
+
+#[cfg(feature = "serde")]
+fn serialize_date<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&date::format(*time))
+}
+
 //fn callback_wrapper(inner: &CallbackFn) -> bool {
 //    false
 //}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(service_name: &str, search_target: SearchTarget) -> Response {
+        Response {
+            max_age: Duration::from_secs(1800),
+            date: SystemTime::UNIX_EPOCH,
+            versions: ProductVersions::default(),
+            search_target,
+            service_name: UniqueServiceName::from_str(service_name).unwrap(),
+            location: URI::from_str("http://10.0.0.1/description.xml").unwrap(),
+            boot_id: 0,
+            config_id: None,
+            search_port: None,
+            other_headers: Default::default(),
+            source: None,
+            raw: Default::default(),
+        }
+    }
+
+    fn responses() -> Vec<Response> {
+        vec![
+            response("uuid:device-1::upnp:rootdevice", SearchTarget::RootDevice),
+            response(
+                "uuid:device-1",
+                SearchTarget::Device(Udn::from_str("uuid:device-1").unwrap()),
+            ),
+            response("uuid:device-2::upnp:rootdevice", SearchTarget::RootDevice),
+            response(
+                "uuid:device-1::urn:schemas-upnp-org:service:ContentDirectory:1",
+                SearchTarget::ServiceType("ContentDirectory:1".to_string()),
+            ),
+            response("uuid:device-1::upnp:rootdevice", SearchTarget::RootDevice),
+        ]
+    }
+
+    const MALFORMED_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\ncache-control: MAX-AGE = 900\r\nLOCATION:   http://10.0.0.1/description.xml  \r\nSERVER: Some Router\r\nST: upnp:rootdevice\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n";
+
+    #[test]
+    fn test_search_using_loopback() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        device.join(MULTICAST_ADDRESS.parse().unwrap());
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            let (received, from) = device.recv_from(&mut buf).unwrap();
+            assert!(buf[..received].starts_with(Method::Search.as_str().as_bytes()));
+            let _ = device
+                .send_to(
+                    b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.1/description.xml\r\nSERVER: Linux/5.0 UPnP/1.0 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n",
+                    &from,
+                )
+                .unwrap();
+        });
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(500);
+        options.transport = Some(Arc::new(client));
+        let responses = search_once(options).unwrap();
+        responder.join().unwrap();
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].source, Some("10.0.0.1:1900".parse().unwrap()));
+        assert_eq!(
+            responses[0].date,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(784111777)
+        );
+    }
+
+    #[test]
+    fn test_search_results_keeps_malformed_separate() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let broken = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        broken.join(MULTICAST_ADDRESS.parse().unwrap());
+        let device = network.bind("10.0.0.3:1900".parse().unwrap()).unwrap();
+        device.join(MULTICAST_ADDRESS.parse().unwrap());
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            let (_, from) = broken.recv_from(&mut buf).unwrap();
+            let _ = broken.send_to(MALFORMED_RESPONSE, &from).unwrap();
+            let (_, from) = device.recv_from(&mut buf).unwrap();
+            let _ = device
+                .send_to(
+                    b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.3/description.xml\r\nSERVER: Linux/5.0 UPnP/1.0 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-3::upnp:rootdevice\r\n\r\n",
+                    &from,
+                )
+                .unwrap();
+        });
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(500);
+        options.transport = Some(Arc::new(client));
+        let results = search_results(options).unwrap();
+        responder.join().unwrap();
+
+        assert_eq!(results.ok.len(), 1);
+        assert_eq!(results.ok[0].source, Some("10.0.0.3:1900".parse().unwrap()));
+        assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.failed[0].0, Some("10.0.0.1:1900".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_search_results_keeps_bad_status_separate() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let broken = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        broken.join(MULTICAST_ADDRESS.parse().unwrap());
+        let device = network.bind("10.0.0.3:1900".parse().unwrap()).unwrap();
+        device.join(MULTICAST_ADDRESS.parse().unwrap());
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            let (_, from) = broken.recv_from(&mut buf).unwrap();
+            let _ = broken
+                .send_to(b"HTTP/1.1 503 Service Unavailable\r\n\r\n", &from)
+                .unwrap();
+            let (_, from) = device.recv_from(&mut buf).unwrap();
+            let _ = device
+                .send_to(
+                    b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.3/description.xml\r\nSERVER: Linux/5.0 UPnP/1.0 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-3::upnp:rootdevice\r\n\r\n",
+                    &from,
+                )
+                .unwrap();
+        });
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(500);
+        options.transport = Some(Arc::new(client));
+        let results = search_results(options).unwrap();
+        responder.join().unwrap();
+
+        assert_eq!(results.ok.len(), 1);
+        assert_eq!(results.ok[0].source, Some("10.0.0.3:1900".parse().unwrap()));
+        assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.failed[0].0, Some("10.0.0.1:1900".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_search_to_device_strictness() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        device
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let client = Arc::new(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap());
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            for _ in 0..2 {
+                let (_, from) = device.recv_from(&mut buf).unwrap();
+                let _ = device.send_to(MALFORMED_RESPONSE, &from).unwrap();
+            }
+        });
+
+        let mut options = Options::default_for(SpecVersion::V11);
+        options.max_wait_time = Duration::from_millis(300);
+        options.transport = Some(client.clone());
+        let results =
+            search_results_to_device(options.clone(), "10.0.0.1:1900".parse().unwrap()).unwrap();
+        assert!(results.ok.is_empty());
+        assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.failed[0].0, Some("10.0.0.1:1900".parse().unwrap()));
+
+        options.strictness = Strictness::Lenient;
+        let responses = search_once_to_device(options, "10.0.0.1:1900".parse().unwrap()).unwrap();
+        responder.join().unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(
+            responses[0].location.to_string(),
+            "http://10.0.0.1/description.xml"
+        );
+    }
+
+    #[test]
+    fn test_search_session_sends_new_responses_once() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        device.join(MULTICAST_ADDRESS.parse().unwrap());
+        device
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            let mut searches = 0;
+            while let Ok((_, from)) = device.recv_from(&mut buf) {
+                searches += 1;
+                let _ = device.send_to(
+                    b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.1/description.xml\r\nSERVER: Linux/5.0 UPnP/1.0 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n",
+                    &from,
+                );
+            }
+            searches
+        });
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(100);
+        options.transport = Some(Arc::new(client));
+        let (handle, receiver) =
+            SearchSession::start_with_interval(options, Duration::from_millis(50)).unwrap();
+
+        let first = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            first.service_name.to_string(),
+            "uuid:device-1::upnp:rootdevice"
+        );
+        assert!(receiver.recv_timeout(Duration::from_millis(400)).is_err());
+
+        handle.stop();
+        assert!(receiver.recv().is_err());
+        assert!(responder.join().unwrap() > 1);
+    }
+
+    #[test]
+    fn test_searcher_reuses_transport() {
+        use crate::common::httpu::{LoopbackNetwork, Transport};
+
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.1:1900".parse().unwrap()).unwrap();
+        device.join(MULTICAST_ADDRESS.parse().unwrap());
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            for _ in 0..2 {
+                let (_, from) = device.recv_from(&mut buf).unwrap();
+                let _ = device
+                    .send_to(
+                        b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.1/description.xml\r\nSERVER: Linux/5.0 UPnP/1.0 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n",
+                        &from,
+                    )
+                    .unwrap();
+            }
+        });
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(200);
+        options.transport = Some(Arc::new(client));
+        let searcher = Searcher::new(options).unwrap();
+        assert_eq!(searcher.search().unwrap().len(), 1);
+        assert_eq!(searcher.search().unwrap().len(), 1);
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_search_deadline() {
+        use crate::common::httpu::LoopbackNetwork;
+
+        let network = LoopbackNetwork::new();
+        let client = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.max_wait_time = Duration::from_millis(1500);
+        options.deadline = Some(Duration::from_millis(100));
+        options.transport = Some(Arc::new(client));
+        assert_eq!(options.max_wait_seconds(), 2);
+
+        let started = std::time::Instant::now();
+        let responses = search_once(options).unwrap();
+        assert!(responses.is_empty());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let raw = MulticastResponse::try_from(MALFORMED_RESPONSE).unwrap();
+        assert!(Response::parse(raw.clone(), Strictness::Strict).is_err());
+
+        let mut response = Response::parse(raw, Strictness::Lenient).unwrap();
+        assert_eq!(response.raw().bytes, MALFORMED_RESPONSE);
+        assert_eq!(response.search_address(), None);
+        response.source = Some("10.0.0.1:49152".parse().unwrap());
+        assert_eq!(
+            response.search_address(),
+            Some("10.0.0.1:1900".parse().unwrap())
+        );
+        assert_eq!(response.max_age, Duration::from_secs(900));
+        assert_eq!(
+            response.location.to_string(),
+            "http://10.0.0.1/description.xml"
+        );
+        assert_eq!(response.search_target, SearchTarget::RootDevice);
+        assert_eq!(
+            response.other_headers.get(HTTP_HEADER_SERVER).unwrap(),
+            "Some Router"
+        );
+        assert_eq!(response.versions.platform_version().name(), "Some");
+        assert!(response.versions.upnp_version().name().is_empty());
+    }
+
+    #[test]
+    fn test_other_headers() {
+        let mut response = response("uuid:device-1::upnp:rootdevice", SearchTarget::RootDevice);
+        for (name, value) in [
+            ("01-NLS", "1f8e6c2a4b"),
+            (
+                "X-AV-SERVER-INFO",
+                "av=5.0; cn=\"Sony Corporation\"; mn=\"BRAVIA; KDL\"; mv=1.7",
+            ),
+            ("X-RINCON-HOUSEHOLD", "Sonos_abc"),
+            ("X-RINCON-BOOTSEQ", "42"),
+        ] {
+            let _ = response
+                .other_headers
+                .insert(name.to_string(), value.to_string());
+        }
+
+        assert_eq!(
+            response.header::<u32>("x-rincon-bootseq").unwrap(),
+            Some(42)
+        );
+        assert_eq!(response.header::<u32>("X-MISSING").unwrap(), None);
+        assert!(response.header::<u32>("X-RINCON-HOUSEHOLD").is_err());
+        assert_eq!(
+            response.headers_matching("x-rincon-"),
+            vec![
+                ("X-RINCON-BOOTSEQ", "42"),
+                ("X-RINCON-HOUSEHOLD", "Sonos_abc")
+            ]
+        );
+        assert_eq!(response.nls(), Some("1f8e6c2a4b"));
+        assert_eq!(
+            response.av_server_info().unwrap(),
+            vec![
+                ("av".to_string(), "5.0".to_string()),
+                ("cn".to_string(), "Sony Corporation".to_string()),
+                ("mn".to_string(), "BRAVIA; KDL".to_string()),
+                ("mv".to_string(), "1.7".to_string()),
+            ]
+        );
+        assert!(response.av_physical_unit_info().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_refresh_removes_expired_and_unreachable() {
+        use crate::description::device::DeviceRoot;
+        use crate::emulation::{from_device_with, Options as EmulatorOptions};
+
+        let device = from_device_with(
+            DeviceRoot::from_str("<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:device-1</UDN></device></root>").unwrap(),
+            Default::default(),
+            EmulatorOptions {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let now = SystemTime::now();
+        let cached = |service_name: &str, location: &str, expiration: SystemTime| {
+            let mut response = response(service_name, SearchTarget::RootDevice);
+            response.location = URI::from_str(location).unwrap();
+            CachedResponse {
+                response,
+                expiration,
+            }
+        };
+        let mut cache = ResponseCache {
+            options: Options::default_for(SpecVersion::V10),
+            minimum_refresh: Duration::from_secs(2),
+            last_updated: now,
+            responses: vec![
+                cached(
+                    "uuid:device-1::upnp:rootdevice",
+                    &device.location().to_string(),
+                    now + Duration::from_secs(1800),
+                ),
+                cached(
+                    "uuid:device-2::upnp:rootdevice",
+                    &device.location().to_string(),
+                    now - Duration::from_secs(1),
+                ),
+                cached(
+                    "uuid:device-3::upnp:rootdevice",
+                    "http://127.0.0.1:1/description.xml",
+                    now + Duration::from_secs(1800),
+                ),
+            ],
+        };
+
+        let refreshed = cache.refresh();
+        let remaining: Vec<String> = refreshed
+            .responses()
+            .iter()
+            .map(|response| response.service_name.to_string())
+            .collect();
+        assert_eq!(remaining, vec!["uuid:device-1::upnp:rootdevice"]);
+    }
+
+    #[test]
+    fn test_dedup_by_usn() {
+        let deduped = responses().dedup_by_usn();
+        assert_eq!(deduped.len(), 4);
+    }
+
+    #[test]
+    fn test_group_by_device() {
+        let devices = responses().group_by_device();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].unique_device_name.to_string(), "uuid:device-1");
+        assert_eq!(devices[0].responses.len(), 4);
+        assert_eq!(devices[0].search_targets.len(), 3);
+        assert_eq!(devices[1].unique_device_name.to_string(), "uuid:device-2");
+        assert_eq!(devices[1].search_targets, vec![SearchTarget::RootDevice]);
+    }
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:MediaServer:2</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:root</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:ContentDirectory:2</serviceType><serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId><SCPDURL>/cd.xml</SCPDURL><controlURL>/cd/control</controlURL><eventSubURL>/cd/event</eventSubURL></service><service><serviceType>urn:schemas-upnp-org:service:ContentDirectory:2</serviceType><serviceId>urn:upnp-org:serviceId:ContentDirectory2</serviceId><SCPDURL>/cd2.xml</SCPDURL><controlURL>/cd2/control</controlURL><eventSubURL>/cd2/event</eventSubURL></service></serviceList><deviceList><device><deviceType>urn:example-com:device:Widget:1</deviceType><friendlyName>Widget</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:embedded</UDN></device></deviceList></device></root>";
+
+    #[test]
+    fn test_matches_all() {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let matched = SearchTarget::All.matches(&root);
+        assert_eq!(
+            matched
+                .iter()
+                .map(|m| m.service_name.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "uuid:root::upnp:rootdevice",
+                "uuid:root",
+                "uuid:root::urn:schemas-upnp-org:device:MediaServer:2",
+                "uuid:root::urn:schemas-upnp-org:service:ContentDirectory:2",
+                "uuid:embedded",
+                "uuid:embedded::urn:example-com:device:Widget:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matches_specific() {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        assert_eq!(SearchTarget::RootDevice.matches(&root).len(), 1);
+        assert_eq!(
+            SearchTarget::Device(Udn::from_str("uuid:embedded").unwrap())
+                .matches(&root)
+                .len(),
+            1
+        );
+        assert!(SearchTarget::Device(Udn::from_str("uuid:other").unwrap())
+            .matches(&root)
+            .is_empty());
+
+        let matched = SearchTarget::DeviceType("MediaServer:1".to_string()).matches(&root);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(
+            matched[0].service_name.to_string(),
+            "uuid:root::urn:schemas-upnp-org:device:MediaServer:1"
+        );
+        assert!(SearchTarget::DeviceType("MediaServer:3".to_string())
+            .matches(&root)
+            .is_empty());
+        assert_eq!(
+            SearchTarget::DomainServiceType(
+                "schemas-upnp-org".to_string(),
+                "ContentDirectory:1".to_string()
+            )
+            .matches(&root)
+            .len(),
+            1
+        );
+        assert_eq!(
+            SearchTarget::DomainDeviceType("example-com".to_string(), "Widget:1".to_string())
+                .matches(&root)[0]
+                .service_name
+                .device()
+                .to_string(),
+            "uuid:embedded"
+        );
+    }
+
+    #[test]
+    fn test_matches_versioned() {
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let target = SearchTarget::DeviceTypeVersioned {
+            name: "MediaServer".to_string(),
+            version: VersionReq::AtLeast(2),
+        };
+        assert_eq!(
+            target.to_string(),
+            "urn:schemas-upnp-org:device:MediaServer:2"
+        );
+        let matched = target.matches(&root);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(
+            matched[0].search_target,
+            SearchTarget::DeviceType("MediaServer:2".to_string())
+        );
+        assert!(SearchTarget::DeviceTypeVersioned {
+            name: "MediaServer".to_string(),
+            version: VersionReq::Exactly(1),
+        }
+        .matches(&root)
+        .is_empty());
+        assert_eq!(
+            SearchTarget::ServiceTypeVersioned {
+                name: "ContentDirectory".to_string(),
+                version: VersionReq::Between(1, 3),
+            }
+            .matches(&root)
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_search_target_all() {
+        assert_eq!(SearchTarget::All.to_string(), "ssdp:all");
+        assert_eq!(
+            SearchTarget::from_str("ssdp:all").unwrap(),
+            SearchTarget::All
+        );
+    }
+}
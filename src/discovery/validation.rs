@@ -0,0 +1,350 @@
+/*!
+This module provides conformance checks for SSDP messages, for device vendors testing their
+firmware against the specification. Each function takes a message, either as received bytes or
+already parsed, and the specification version the message should conform to, and returns all the
+[`Violation`]s found rather than stopping at the first.
+
+The headers required, optional, or forbidden in each message are taken from the
+[`rules`](crate::discovery::rules) table, the values of known headers are then checked in turn.
+
+# Example
+
+```rust
+use upnp_rs::discovery::validation::validate_bytes;
+use upnp_rs::SpecVersion;
+
+let message = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:byebye\r\nUSN: uuid:device-1::upnp:rootdevice\r\n\r\n";
+assert!(validate_bytes(message, SpecVersion::V10).is_empty());
+
+let violations = validate_bytes(message, SpecVersion::V11);
+for violation in &violations {
+    println!("{}", violation);
+}
+assert_eq!(violations.len(), 2);
+```
+*/
+
+use crate::common::date;
+use crate::common::httpu::{Request, Response};
+use crate::common::uri::URI;
+use crate::common::user_agent::ProductVersions;
+use crate::description::Violation;
+use crate::discovery::identity::MAX_BOOT_ID;
+use crate::discovery::rules::{HeaderRules, Requirement, SsdpMessage};
+use crate::discovery::search::SearchTarget;
+use crate::discovery::{NotificationSubtype, NotificationType, UniqueServiceName};
+use crate::syntax::{HeaderName, Method, Nts, DYNAMIC_PORTS, HTTP_EXTENSION, MAX_CONFIG_ID};
+use crate::SpecVersion;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Validate an `M-SEARCH` request; a request with a multicast `HOST` is checked as a multicast
+/// search, any other as a unicast search.
+///
+pub fn validate_search_request(request: &Request, spec_version: SpecVersion) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check_request_line(request, Method::Search, &mut violations);
+    let is_multicast = match request.header(HeaderName::Host).map(str::trim) {
+        None => true,
+        Some(host) => match SocketAddr::from_str(host) {
+            Ok(address) if address.ip().is_multicast() => {
+                if address.port() != SSDP_PORT {
+                    violations.push(Violation::new(
+                        HeaderName::Host.as_str(),
+                        format!("multicast searches must be sent to port {}", SSDP_PORT),
+                    ));
+                }
+                true
+            }
+            Ok(_) => false,
+            Err(_) => {
+                violations.push(Violation::new(
+                    HeaderName::Host.as_str(),
+                    "not a valid address and port",
+                ));
+                true
+            }
+        },
+    };
+    let rules = HeaderRules::new(
+        if is_multicast {
+            SsdpMessage::Search
+        } else {
+            SsdpMessage::UnicastSearch
+        },
+        spec_version,
+    );
+    check_headers(&rules, request.headers(), &mut violations);
+    violations
+}
+
+///
+/// Validate the response from a device to a search.
+///
+pub fn validate_search_response(response: &Response, spec_version: SpecVersion) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if response.status_code() != 200 {
+        violations.push(Violation::new(
+            "status",
+            format!("must be 200, not {}", response.status_code()),
+        ));
+    }
+    let rules = HeaderRules::new(SsdpMessage::SearchResponse, spec_version);
+    check_headers(&rules, response.headers(), &mut violations);
+    violations
+}
+
+///
+/// Validate a `NOTIFY` request; the `NTS` header determines which of the `ssdp:alive`,
+/// `ssdp:update`, or `ssdp:byebye` rules apply.
+///
+pub fn validate_notify(request: &Request, spec_version: SpecVersion) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check_request_line(request, Method::Notify, &mut violations);
+    let subtype = match request.header(HeaderName::Nts).map(str::trim) {
+        None => {
+            violations.push(Violation::new(HeaderName::Nts.as_str(), "required"));
+            return violations;
+        }
+        Some(nts) => match Nts::from_str(nts).and_then(NotificationSubtype::try_from) {
+            Ok(subtype) => subtype,
+            Err(_) => {
+                violations.push(Violation::new(
+                    HeaderName::Nts.as_str(),
+                    format!("not a notification subtype: '{}'", nts),
+                ));
+                return violations;
+            }
+        },
+    };
+    let rules = HeaderRules::new(
+        match subtype {
+            NotificationSubtype::Alive => SsdpMessage::Alive,
+            NotificationSubtype::Update => SsdpMessage::Update,
+            NotificationSubtype::ByeBye => SsdpMessage::ByeBye,
+        },
+        spec_version,
+    );
+    check_headers(&rules, request.headers(), &mut violations);
+    if !rules.is_supported() {
+        return violations;
+    }
+    if let (Some(boot_id), Some(next_boot_id)) = (
+        parsed_header::<u32, _>(request.header(HeaderName::BootId)),
+        parsed_header::<u32, _>(request.header(HeaderName::NextBootId)),
+    ) {
+        if next_boot_id <= boot_id {
+            violations.push(Violation::new(
+                HeaderName::NextBootId.as_str(),
+                format!("must be greater than the current boot id {}", boot_id),
+            ));
+        }
+    }
+    violations
+}
+
+///
+/// Validate a message as received; the start line determines whether it is validated as a
+/// search request, a search response, or a notification.
+///
+pub fn validate_bytes(message: &[u8], spec_version: SpecVersion) -> Vec<Violation> {
+    if message.starts_with(b"HTTP/") {
+        match Response::try_from(message) {
+            Ok(response) => validate_search_response(&response, spec_version),
+            Err(e) => vec![Violation::new("message", e.to_string())],
+        }
+    } else {
+        match Request::try_from(message) {
+            Ok(request) => match request.known_method() {
+                Some(Method::Search) => validate_search_request(&request, spec_version),
+                Some(Method::Notify) => validate_notify(&request, spec_version),
+                _ => vec![Violation::new(
+                    "method",
+                    format!("not an SSDP method: '{}'", request.method()),
+                )],
+            },
+            Err(e) => vec![Violation::new("message", e.to_string())],
+        }
+    }
+}
+
+///
+/// Validate the TTL, or IPv6 hop limit, of a received multicast packet; the specification
+/// requires that this be configurable, but a value of zero is never delivered and any value is
+/// limited to a single byte.
+///
+pub fn validate_multicast_ttl(ttl: u32, spec_version: SpecVersion) -> Vec<Violation> {
+    let recommended = if spec_version == SpecVersion::V10 {
+        4
+    } else {
+        2
+    };
+    if ttl == 0 || ttl > 255 {
+        vec![Violation::new(
+            "ttl",
+            format!(
+                "must be between 1 and 255, and should default to {}, not {}",
+                recommended, ttl
+            ),
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Values
+// ------------------------------------------------------------------------------------------------
+
+const SSDP_PORT: u16 = 1900;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn check_request_line(request: &Request, method: Method, violations: &mut Vec<Violation>) {
+    if request.known_method() != Some(method) {
+        violations.push(Violation::new(
+            "method",
+            format!("must be {}, not '{}'", method, request.method()),
+        ));
+    }
+    if let Some(resource) = request.resource() {
+        violations.push(Violation::new(
+            "resource",
+            format!("must be '*', not '{}'", resource),
+        ));
+    }
+}
+
+fn check_headers<I, K, V>(rules: &HeaderRules, headers: I, violations: &mut Vec<Violation>)
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    if !rules.is_supported() {
+        violations.push(Violation::new(
+            "message",
+            format!(
+                "{:?} is not defined by version {}",
+                rules.message(),
+                rules.version()
+            ),
+        ));
+        return;
+    }
+    let mut present: Vec<HeaderName> = Vec::new();
+    for (name, value) in headers {
+        if let Ok(header) = HeaderName::from_str(name.as_ref()) {
+            if rules.requirement(header) == Requirement::Forbidden {
+                violations.push(Violation::new(
+                    header.as_str(),
+                    format!("not allowed in version {}", rules.version()),
+                ));
+            } else if let Some(message) = check_value(header, value.as_ref().trim()) {
+                violations.push(Violation::new(header.as_str(), message));
+            }
+            present.push(header);
+        }
+    }
+    for header in rules.required().filter(|header| !present.contains(header)) {
+        violations.push(Violation::new(header.as_str(), "required"));
+    }
+}
+
+///
+/// Check the value of a header, returning a description of any problem.
+///
+fn check_value(header: HeaderName, value: &str) -> Option<String> {
+    lazy_static! {
+        static ref MAX_AGE: Regex = Regex::new(r"^max-age\s*=\s*\d+$").unwrap();
+    }
+    let valid = match header {
+        HeaderName::CacheControl => MAX_AGE.is_match(value),
+        HeaderName::Date => date::parse(value).is_ok(),
+        HeaderName::Ext => value.is_empty(),
+        HeaderName::Location | HeaderName::SecureLocation => URI::from_str(value).is_ok(),
+        HeaderName::Man => value == HTTP_EXTENSION,
+        HeaderName::Mx => match value.parse::<u8>() {
+            Ok(seconds) => (1..=120).contains(&seconds),
+            Err(_) => false,
+        },
+        HeaderName::Nt => NotificationType::from_str(value).is_ok(),
+        HeaderName::St => SearchTarget::from_str(value).is_ok(),
+        HeaderName::Usn => UniqueServiceName::from_str(value).is_ok(),
+        HeaderName::Server | HeaderName::UserAgent => ProductVersions::from_str(value).is_ok(),
+        HeaderName::BootId | HeaderName::NextBootId => {
+            matches!(value.parse::<u32>(), Ok(id) if id <= MAX_BOOT_ID)
+        }
+        HeaderName::ConfigId => matches!(value.parse::<u32>(), Ok(id) if id <= MAX_CONFIG_ID),
+        HeaderName::SearchPort | HeaderName::TcpPort => {
+            matches!(value.parse::<u16>(), Ok(port) if DYNAMIC_PORTS.contains(&port))
+        }
+        HeaderName::CpFriendlyName => !value.is_empty(),
+        _ => true,
+    };
+    if valid {
+        None
+    } else {
+        Some(format!("invalid value '{}'", value))
+    }
+}
+
+fn parsed_header<T: FromStr, S: AsRef<str>>(value: Option<S>) -> Option<T> {
+    value.and_then(|value| value.as_ref().trim().parse::<T>().ok())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH: &[u8] = b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n";
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sun, 06 Nov 1994 08:49:37 GMT\r\nEXT:\r\nLOCATION: http://10.0.0.1/description.xml\r\nSERVER: Linux/5.0 UPnP/1.1 Test/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 1\r\nCONFIGID.UPNP.ORG: 1\r\nSEARCHPORT.UPNP.ORG: 1900\r\n\r\n";
+
+    #[test]
+    fn test_search_request() {
+        assert!(validate_bytes(SEARCH, SpecVersion::V10).is_empty());
+        let violations = validate_bytes(SEARCH, SpecVersion::V20);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "CPFN.UPNP.ORG");
+    }
+
+    #[test]
+    fn test_search_response() {
+        let violations = validate_bytes(RESPONSE, SpecVersion::V11);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "SEARCHPORT.UPNP.ORG");
+
+        let violations = validate_bytes(RESPONSE, SpecVersion::V10);
+        assert_eq!(violations.len(), 3);
+    }
+
+    #[test]
+    fn test_update_boot_ids() {
+        let message = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nLOCATION: http://10.0.0.1/description.xml\r\nNT: upnp:rootdevice\r\nNTS: ssdp:update\r\nUSN: uuid:device-1::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 2\r\nCONFIGID.UPNP.ORG: 1\r\nNEXTBOOTID.UPNP.ORG: 2\r\n\r\n";
+        let violations = validate_bytes(message, SpecVersion::V11);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "NEXTBOOTID.UPNP.ORG");
+        assert_eq!(validate_bytes(message, SpecVersion::V10).len(), 1);
+    }
+
+    #[test]
+    fn test_multicast_ttl() {
+        assert!(validate_multicast_ttl(2, SpecVersion::V11).is_empty());
+        assert_eq!(validate_multicast_ttl(0, SpecVersion::V11).len(), 1);
+    }
+}
@@ -0,0 +1,1666 @@
+/*!
+This module allows a previously captured [`DeviceSnapshot`](../snapshot/struct.DeviceSnapshot.html),
+or a [`DeviceRoot`] constructed in code, to be hosted as an emulated device. The emulated device
+serves the description and service descriptions over HTTP, optionally answers SSDP `M-SEARCH`
+requests and periodically advertises itself, and will answer SOAP actions using either canned
+responses or handler functions. GENA subscriptions are accepted, renewed, and cancelled, for any
+event path, and subscribers are sent the values of the evented state variables of the service.
+This allows the reproduction of interoperability issues reported by users without access to the
+physical device, and allows control point code to be tested against a known-good device on
+`localhost`.
+
+Any absolute URLs in the captured description that refer to the original device are rewritten to
+refer to the emulated device.
+
+# Example
+
+```rust,no_run
+use upnp_rs::emulation::from_snapshot;
+use upnp_rs::snapshot::DeviceSnapshot;
+
+let snapshot = DeviceSnapshot::load("device.snapshot").unwrap();
+let device = from_snapshot(snapshot).unwrap();
+println!("emulated device description at {}", device.location());
+```
+
+A device may also be described in code, with action handlers that update the evented state of
+the service; see the `upnp-device` example for a complete switch.
+
+```rust,no_run
+use upnp_rs::control::UpnpError;
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::emulation::{from_device, EmulatedDevice};
+
+fn host(root: DeviceRoot) -> EmulatedDevice {
+    let device = from_device(root, Default::default()).unwrap();
+    device.add_action_handler(
+        "/control",
+        "urn:schemas-upnp-org:service:SwitchPower:1#SetTarget",
+        |events, arguments| match arguments.first() {
+            Some((_, value)) => {
+                events.set("/event", "Status", value);
+                Ok(Vec::new())
+            }
+            None => Err(UpnpError::InvalidArgs),
+        },
+    );
+    device
+}
+```
+
+Rather than handling each action separately, a type implementing [`ServiceHandler`] may answer
+all the actions of a service, see [`EmulatedDevice::add_service_handler`].
+
+Changes to state variables marked `multicast="yes"` in the service description are also sent
+using [multicast eventing](../eventing/multicast/index.html).
+*/
+
+use crate::common::http::{
+    default_client, read_request, write_response, ClientRequest, HttpClient, HttpRequest,
+    DEFAULT_KEEP_ALIVE_TIMEOUT,
+};
+use crate::common::httpu::{create_udp_socket, Options as MulticastOptions, Request};
+use crate::common::soap::{Arguments, Body, Envelope};
+use crate::common::uri::URL;
+use crate::common::user_agent::user_agent_string;
+use crate::control::UpnpError;
+use crate::description::device::{to_writer as description_to_writer, DeviceRoot};
+use crate::description::service::{to_writer as scpd_to_writer, Moderation, Spcd, StateVariable};
+use crate::description::ServiceId;
+use crate::discovery::identity::SsdpIdentity;
+use crate::discovery::notify::{advertisements_for, Advertiser, Options as NotifyOptions};
+use crate::discovery::respond::{answers, build_response, DeviceInfo, SearchRequest};
+use crate::discovery::{SsdpDeviceInfo, UniqueServiceName};
+use crate::error::{
+    invalid_field_value, invalid_value_for_type, missing_required_field, multicast_join_failed,
+    Error,
+};
+use crate::eventing::client::timeout_to_string;
+use crate::eventing::multicast::{EventLevel, MulticastPublisher};
+use crate::eventing::PropertySet;
+use crate::snapshot::{DeviceSnapshot, ServiceSnapshot};
+use crate::syntax::{
+    Method, DYNAMIC_PORTS, GENA_HEADER_CALLBACK, GENA_HEADER_SEQ, GENA_HEADER_SID,
+    GENA_HEADER_TIMEOUT, GENA_METHOD_SUBSCRIBE, GENA_METHOD_UNSUBSCRIBE, GENA_NTS_PROPERTY_CHANGE,
+    GENA_NT_EVENT, HTTP_HEADER_CONTENT_TYPE, HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_SERVER,
+    HTTP_METHOD_NOTIFY, MULTICAST_ADDRESS, SOAP_HTTP_HEADER_ACTION,
+};
+use crate::SpecVersion;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Error as FmtError, Formatter};
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tracing::{debug, error, info, trace};
+use url::Url;
+use uuid::Uuid;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the set of values used to host an emulated device.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The specification version used to construct SSDP responses. Default: `SpecVersion:V10`.
+    pub spec_version: SpecVersion,
+    /// The local address the HTTP server will bind to, this is also the address used in the
+    /// rewritten description URLs. Default: `127.0.0.1`.
+    pub address: IpAddr,
+    /// The port the HTTP server will bind to, `0` will select any available port. Default: `0`.
+    pub http_port: u16,
+    /// If `true` the emulated device will join the SSDP multicast group and answer `M-SEARCH`
+    /// requests. Default: `true`.
+    pub answer_search: bool,
+    /// If specified, unicast `M-SEARCH` requests are also answered on this UDP port, which is
+    /// advertised in the `SEARCHPORT.UPNP.ORG` header of notifications and search responses
+    /// (UDA 1.1 and later). This is used where the device cannot receive unicast messages on the
+    /// shared SSDP port; it must be in the range `49152..=65535`, or `0` to use the first
+    /// available port in that range. Default: `None`.
+    pub search_port: Option<u16>,
+    /// The value used to control caching of responses by control points. Default: `1800`.
+    pub max_age: u16,
+    /// The duration granted to event subscriptions, regardless of the duration requested.
+    /// Default: `1800` seconds.
+    pub subscription_timeout: Duration,
+    /// If specified, `ssdp:alive` notifications are multicast at this interval, and
+    /// `ssdp:byebye` notifications are sent when the device is stopped. Default: `None`.
+    pub advertise_interval: Option<Duration>,
+    /// If specified, every subscriber is sent the current value of all evented state variables
+    /// at this interval, in addition to the events sent when a value changes. Default: `None`.
+    pub event_interval: Option<Duration>,
+}
+
+///
+/// A function that answers a SOAP action, given the in arguments it returns the out arguments
+/// or the UPnP error to return as a fault. The [`EventSource`] allows the handler to change the
+/// value of evented state variables.
+///
+pub type ActionHandler =
+    Arc<dyn Fn(&EventSource, &Arguments) -> Result<Arguments, UpnpError> + Send + Sync>;
+
+///
+/// The implementation of a service hosted by an emulated device. All actions posted to the
+/// service's control URL are dispatched to [`handle`](ServiceHandler::handle), which is given the
+/// action name, without the service type, and the in arguments.
+///
+/// Any function or closure with the signature of `handle` is a handler, so a simple service may
+/// be implemented without defining a type.
+///
+pub trait ServiceHandler: Send + Sync {
+    ///
+    /// Answer the action `action`, given the in arguments return the out arguments or the UPnP
+    /// error to return as a fault.
+    ///
+    fn handle(&self, action: &str, arguments: Arguments) -> Result<Arguments, UpnpError>;
+
+    ///
+    /// The initial values of the service's evented state variables; variables not included
+    /// take their default value from the service description.
+    ///
+    fn state_variables(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    ///
+    /// Called once when the handler is added to a device, `events` may be retained to change the
+    /// value of evented state variables at any time.
+    ///
+    fn attached(&self, _events: ServiceEvents) {}
+}
+
+///
+/// The evented state of a single service, as given to a [`ServiceHandler`].
+///
+#[derive(Clone, Debug)]
+pub struct ServiceEvents {
+    source: EventSource,
+    event_path: String,
+}
+
+///
+/// The evented state of an emulated device; setting the value of a state variable sends an event
+/// to all subscribers to the corresponding event path.
+///
+#[derive(Clone, Debug)]
+pub struct EventSource {
+    state: Arc<EmulatorState>,
+}
+
+///
+/// A running emulated device, the device is stopped when this value is dropped.
+///
+#[derive(Debug)]
+pub struct EmulatedDevice {
+    state: Arc<EmulatorState>,
+    local_address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Host the device captured in `snapshot` using the default options.
+///
+pub fn from_snapshot(snapshot: DeviceSnapshot) -> Result<EmulatedDevice, Error> {
+    from_snapshot_with(snapshot, Options::default())
+}
+
+///
+/// Host the device described by `root` using the default options. The service descriptions are
+/// provided in `services`, keyed by service identifier.
+///
+pub fn from_device(
+    root: DeviceRoot,
+    services: HashMap<ServiceId, Spcd>,
+) -> Result<EmulatedDevice, Error> {
+    from_device_with(root, services, Options::default())
+}
+
+///
+/// Host the device described by `root` using the provided options. The service descriptions are
+/// provided in `services`, keyed by service identifier; services without a description will
+/// return `404 Not Found` for their `SCPDURL`.
+///
+pub fn from_device_with(
+    root: DeviceRoot,
+    services: HashMap<ServiceId, Spcd>,
+    options: Options,
+) -> Result<EmulatedDevice, Error> {
+    let origin = if root.url_base.is_empty() {
+        PLACEHOLDER_ORIGIN.to_string()
+    } else {
+        origin_of(&root.url_base)?
+    };
+    let location = url_of(&format!("{}/description.xml", origin))?;
+    let base = root.base_url(&location);
+
+    let mut service_snapshots = Vec::new();
+    for service in root
+        .device
+        .all_devices()
+        .iter()
+        .flat_map(|d| &d.service_list)
+    {
+        if let Some(scpd) = services.get(&service.service_id) {
+            service_snapshots.push(ServiceSnapshot {
+                service_id: service.service_id.clone(),
+                scpd_url: base.resolve(&service.scpd_url)?,
+                xml: String::from_utf8(scpd_to_writer(scpd, Vec::new())?).unwrap(),
+                state: None,
+            });
+        }
+    }
+
+    from_snapshot_with(
+        DeviceSnapshot {
+            location,
+            captured: UNIX_EPOCH,
+            description: String::from_utf8(description_to_writer(&root, Vec::new())?).unwrap(),
+            services: service_snapshots,
+        },
+        options,
+    )
+}
+
+///
+/// Host the device captured in `snapshot` using the provided options.
+///
+pub fn from_snapshot_with(
+    snapshot: DeviceSnapshot,
+    options: Options,
+) -> Result<EmulatedDevice, Error> {
+    info!(
+        "from_snapshot_with - location: {}, options: {:?}",
+        snapshot.location, options
+    );
+    let listener = TcpListener::bind(SocketAddr::new(options.address, options.http_port))?;
+    listener.set_nonblocking(true)?;
+    let local_address = listener.local_addr()?;
+    let search_socket = match options.search_port {
+        Some(port) => Some(create_search_port_socket(options.address, port)?),
+        None => None,
+    };
+
+    let original = origin_of(&snapshot.location.to_string())?;
+    let emulated = format!("http://{}", local_address);
+    let rewrite = |s: &str| s.replace(&original, &emulated);
+
+    let mut documents: HashMap<String, String> = Default::default();
+    let description_path = path_of(&snapshot.location.to_string())?;
+    let description = rewrite(&snapshot.description);
+    let root = DeviceRoot::from_str(&description)?;
+    let _ = documents.insert(description_path.clone(), description);
+    for service in &snapshot.services {
+        let _ = documents.insert(
+            path_of(&service.scpd_url.to_string())?,
+            rewrite(&service.xml),
+        );
+    }
+
+    let location = format!("{}{}", emulated, description_path);
+    let evented = evented_services(&root, &url_of(&location)?, &snapshot.services)?;
+    let identity = SsdpIdentity::new(root.config_id.unwrap_or_default());
+    let publisher = if evented.multicast.is_empty() {
+        None
+    } else {
+        Some(MulticastPublisher::new(&Default::default())?.with_boot_id(identity.boot_id()))
+    };
+
+    let state = Arc::new(EmulatorState {
+        location,
+        server: user_agent_string(options.spec_version, None),
+        spec_version: options.spec_version,
+        identity,
+        max_age: options.max_age,
+        search_port: match &search_socket {
+            Some(socket) => Some(socket.local_addr()?.port()),
+            None => None,
+        },
+        root,
+        documents,
+        canned_responses: Default::default(),
+        action_handlers: Default::default(),
+        service_handlers: Default::default(),
+        subscription_timeout: options.subscription_timeout,
+        subscriptions: Default::default(),
+        properties: Mutex::new(evented.properties),
+        moderation: evented.moderation,
+        moderated: Default::default(),
+        multicast: evented.multicast,
+        publisher,
+        client: default_client(),
+    });
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let mut threads = Vec::new();
+    {
+        let state = state.clone();
+        let stop = stop.clone();
+        threads.push(spawn(move || http_loop(listener, state, stop)));
+    }
+    if options.answer_search {
+        let socket = create_search_socket(options.address)?;
+        let state = state.clone();
+        let stop = stop.clone();
+        threads.push(spawn(move || search_loop(socket, state, stop)));
+    }
+    if let Some(socket) = search_socket {
+        let state = state.clone();
+        let stop = stop.clone();
+        threads.push(spawn(move || search_loop(socket, state, stop)));
+    }
+    if let Some(interval) = options.advertise_interval {
+        let state = state.clone();
+        let stop = stop.clone();
+        threads.push(spawn(move || advertise_loop(interval, state, stop)));
+    }
+    if let Some(interval) = options.event_interval {
+        let state = state.clone();
+        let stop = stop.clone();
+        threads.push(spawn(move || {
+            every(interval, &stop, || {
+                let sids: Vec<String> = state
+                    .subscriptions
+                    .lock()
+                    .unwrap()
+                    .keys()
+                    .cloned()
+                    .collect();
+                for sid in sids {
+                    send_initial_event(&state, &sid);
+                }
+            })
+        }));
+    }
+
+    Ok(EmulatedDevice {
+        state,
+        local_address,
+        stop,
+        threads,
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            spec_version: SpecVersion::V10,
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            http_port: 0,
+            answer_search: true,
+            search_port: None,
+            max_age: 1800,
+            subscription_timeout: Duration::from_secs(1800),
+            advertise_interval: None,
+            event_interval: None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl EventSource {
+    ///
+    /// Return the current value of the state variable `name` evented at `event_path`.
+    ///
+    pub fn get(&self, event_path: &str, name: &str) -> Option<String> {
+        self.state
+            .properties
+            .lock()
+            .unwrap()
+            .get(event_path)
+            .and_then(|properties| properties.iter().find(|(existing, _)| existing == name))
+            .map(|(_, value)| value.clone())
+    }
+
+    ///
+    /// Set the value of the state variable `name` evented at `event_path`, sending an event to
+    /// all subscribers of that path if the value has changed. If the service description
+    /// moderates the variable, the event is delayed until the maximum rate allows, and is not
+    /// sent for a change smaller than the minimum delta.
+    ///
+    pub fn set(&self, event_path: &str, name: &str, value: &str) {
+        let previous = {
+            let mut properties = self.state.properties.lock().unwrap();
+            let properties = properties.entry(event_path.to_string()).or_default();
+            match properties.iter_mut().find(|(existing, _)| existing == name) {
+                Some((_, existing)) if existing == value => return,
+                Some((_, existing)) => Some(std::mem::replace(existing, value.to_string())),
+                None => {
+                    properties.push((name.to_string(), value.to_string()));
+                    None
+                }
+            }
+        };
+        let key = (event_path.to_string(), name.to_string());
+        match self.state.moderation.get(&key) {
+            Some(moderation) => {
+                let _ = self
+                    .state
+                    .moderated
+                    .lock()
+                    .unwrap()
+                    .entry(key)
+                    .or_insert_with(|| Moderated {
+                        value: previous.unwrap_or_default(),
+                        evented_at: None,
+                        pending: false,
+                    });
+                send_moderated_event(&self.state, event_path, name, moderation);
+            }
+            None => send_event(
+                &self.state,
+                event_path,
+                None,
+                &PropertySet::new().with_property(name, value),
+            ),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<F> ServiceHandler for F
+where
+    F: Fn(&str, Arguments) -> Result<Arguments, UpnpError> + Send + Sync,
+{
+    fn handle(&self, action: &str, arguments: Arguments) -> Result<Arguments, UpnpError> {
+        self(action, arguments)
+    }
+}
+
+impl ServiceEvents {
+    ///
+    /// Return the current value of the evented state variable `name`.
+    ///
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.source.get(&self.event_path, name)
+    }
+
+    ///
+    /// Set the value of the evented state variable `name`, sending an event to all subscribers
+    /// if the value has changed.
+    ///
+    pub fn set(&self, name: &str, value: &str) {
+        self.source.set(&self.event_path, name, value)
+    }
+
+    ///
+    /// The path of the service's event subscription URL.
+    ///
+    pub fn event_path(&self) -> &str {
+        &self.event_path
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl EmulatedDevice {
+    ///
+    /// The location of the emulated device's description.
+    ///
+    pub fn location(&self) -> URL {
+        URL::from_str(&self.state.location).unwrap()
+    }
+
+    ///
+    /// The local address of the emulated device's HTTP server.
+    ///
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    ///
+    /// The port on which unicast search requests are answered, if one was requested in the
+    /// options.
+    ///
+    pub fn search_port(&self) -> Option<u16> {
+        self.state.search_port
+    }
+
+    ///
+    /// Provide a canned response for the SOAP action `action` (of the form `serviceType#name`)
+    /// when posted to the control URL path `control_path`. The `response` is the complete SOAP
+    /// envelope to return. Actions without a canned response return an _Invalid Action_ fault.
+    ///
+    pub fn add_canned_response(&self, control_path: &str, action: &str, response: &str) {
+        let _ = self.state.canned_responses.lock().unwrap().insert(
+            (control_path.to_string(), action.to_string()),
+            response.to_string(),
+        );
+    }
+
+    ///
+    /// Answer the SOAP action `action` (of the form `serviceType#name`) when posted to the
+    /// control URL path `control_path` by calling `handler`. A canned response for the same
+    /// action takes precedence over a handler.
+    ///
+    pub fn add_action_handler<F>(&self, control_path: &str, action: &str, handler: F)
+    where
+        F: Fn(&EventSource, &Arguments) -> Result<Arguments, UpnpError> + Send + Sync + 'static,
+    {
+        let _ = self.state.action_handlers.lock().unwrap().insert(
+            (control_path.to_string(), action.to_string()),
+            Arc::new(handler),
+        );
+    }
+
+    ///
+    /// Answer all actions posted to the control URL of the service `service_id` by calling
+    /// `handler`. Canned responses and action handlers for specific actions take precedence over
+    /// a service handler. It is an error if the device has no such service.
+    ///
+    pub fn add_service_handler<H>(&self, service_id: &ServiceId, handler: H) -> Result<(), Error>
+    where
+        H: ServiceHandler + 'static,
+    {
+        let service = match self
+            .state
+            .root
+            .device
+            .all_devices()
+            .into_iter()
+            .flat_map(|device| &device.service_list)
+            .find(|service| &service.service_id == service_id)
+        {
+            Some(service) => service.clone(),
+            None => return missing_required_field(service_id.to_string()).into(),
+        };
+        let base = self.state.root.base_url(&self.location());
+        let control_path = path_of(&base.resolve(&service.control_url)?.to_string())?;
+        let events = ServiceEvents {
+            source: self.event_source(),
+            event_path: path_of(&base.resolve(&service.event_sub_url)?.to_string())?,
+        };
+        for (name, value) in handler.state_variables() {
+            events.set(&name, &value);
+        }
+        handler.attached(events);
+        let _ = self
+            .state
+            .service_handlers
+            .lock()
+            .unwrap()
+            .insert(control_path, Arc::new(handler));
+        Ok(())
+    }
+
+    ///
+    /// The evented state of this device, used to change the value of evented state variables.
+    ///
+    pub fn event_source(&self) -> EventSource {
+        EventSource {
+            state: self.state.clone(),
+        }
+    }
+
+    ///
+    /// Return the identifiers of all current event subscriptions.
+    ///
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.state
+            .subscriptions
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    ///
+    /// Forget the event subscription `sid`, as a device does when a subscription expires; any
+    /// further renewal will fail. Returns `true` if the subscription existed.
+    ///
+    pub fn cancel_subscription(&self, sid: &str) -> bool {
+        self.state
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(sid)
+            .is_some()
+    }
+
+    ///
+    /// Stop the emulated device, waiting for all its threads to complete.
+    ///
+    pub fn stop(mut self) {
+        self.stop_threads();
+    }
+
+    fn stop_threads(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for EmulatedDevice {
+    fn drop(&mut self) {
+        self.stop_threads();
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+struct EmulatorState {
+    location: String,
+    server: String,
+    spec_version: SpecVersion,
+    identity: SsdpIdentity,
+    max_age: u16,
+    search_port: Option<u16>,
+    root: DeviceRoot,
+    documents: HashMap<String, String>,
+    canned_responses: Mutex<HashMap<(String, String), String>>,
+    action_handlers: Mutex<HashMap<(String, String), ActionHandler>>,
+    service_handlers: Mutex<HashMap<String, Arc<dyn ServiceHandler>>>,
+    subscription_timeout: Duration,
+    subscriptions: Mutex<HashMap<String, Subscriber>>,
+    properties: Mutex<PropertyTable>,
+    moderation: ModerationTable,
+    moderated: Mutex<HashMap<(String, String), Moderated>>,
+    multicast: HashMap<String, MulticastService>,
+    publisher: Option<MulticastPublisher>,
+    client: Arc<dyn HttpClient>,
+}
+
+#[derive(Debug, Default)]
+struct EventedServices {
+    properties: PropertyTable,
+    moderation: ModerationTable,
+    multicast: HashMap<String, MulticastService>,
+}
+
+#[derive(Debug)]
+struct MulticastService {
+    service_name: UniqueServiceName,
+    service_id: String,
+    variables: Vec<String>,
+}
+
+type PropertyTable = HashMap<String, Vec<(String, String)>>;
+
+type ModerationTable = HashMap<(String, String), Moderation>;
+
+#[derive(Debug)]
+struct Moderated {
+    value: String,
+    evented_at: Option<Instant>,
+    pending: bool,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    event_path: String,
+    callbacks: Vec<String>,
+    seq: u32,
+}
+
+impl Debug for EmulatorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        f.debug_struct("EmulatorState")
+            .field("location", &self.location)
+            .field("spec_version", &self.spec_version)
+            .field("identity", &self.identity)
+            .field("search_port", &self.search_port)
+            .field("subscriptions", &self.subscriptions)
+            .field("properties", &self.properties)
+            .finish_non_exhaustive()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const PLACEHOLDER_ORIGIN: &str = "http://emulated.invalid";
+
+fn every<F: FnMut()>(interval: Duration, stop: &AtomicBool, mut f: F) {
+    let mut next = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        if Instant::now() >= next {
+            f();
+            next = Instant::now() + interval;
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+fn origin_of(url: &str) -> Result<String, Error> {
+    match Url::parse(url) {
+        Ok(url) => Ok(url.origin().ascii_serialization()),
+        Err(_) => invalid_value_for_type("URL", url).into(),
+    }
+}
+
+fn url_of(url: &str) -> Result<URL, Error> {
+    URL::from_str(url).or_else(|_| invalid_value_for_type("URL", url).into())
+}
+
+fn path_of(url: &str) -> Result<String, Error> {
+    match Url::parse(url) {
+        Ok(url) => Ok(url.path().to_string()),
+        Err(_) => invalid_value_for_type("URL", url).into(),
+    }
+}
+
+fn http_loop(listener: TcpListener, state: Arc<EmulatorState>, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, from)) => {
+                trace!("http_loop - accepted connection from {:?}", from);
+                if let Err(e) = handle_connection(stream, &state) {
+                    error!("http_loop - error handling request: {:?}", e);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => sleep(POLL_INTERVAL),
+            Err(e) => {
+                error!("http_loop - accept returned error: {:?}", e);
+                break;
+            }
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: &Arc<EmulatorState>) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_request(&mut reader)? {
+        let mut response_headers: Vec<(&str, String)> = vec![
+            (
+                HTTP_HEADER_CONTENT_TYPE,
+                "text/xml; charset=\"utf-8\"".to_string(),
+            ),
+            (HTTP_HEADER_SERVER, state.server.clone()),
+        ];
+        let mut new_subscription: Option<String> = None;
+        let (status, content) = handle_http(
+            &request,
+            state,
+            &mut response_headers,
+            &mut new_subscription,
+        );
+        trace!(
+            "handle_connection - {} {} => {}",
+            request.method,
+            request.path,
+            status
+        );
+        let keep_alive = request.keep_alive();
+        write_response(
+            reader.get_mut(),
+            status,
+            &response_headers,
+            content.as_bytes(),
+            keep_alive,
+        )?;
+
+        if let Some(sid) = new_subscription {
+            send_initial_event(state, &sid);
+        }
+        if !keep_alive {
+            break;
+        }
+        reader
+            .get_ref()
+            .set_read_timeout(Some(DEFAULT_KEEP_ALIVE_TIMEOUT))?;
+    }
+    Ok(())
+}
+
+fn handle_http(
+    request: &HttpRequest,
+    state: &Arc<EmulatorState>,
+    response_headers: &mut Vec<(&str, String)>,
+    new_subscription: &mut Option<String>,
+) -> (&'static str, String) {
+    let headers = &request.headers;
+    let body = &request.body;
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", path) => match state.documents.get(path) {
+            Some(document) => ("200 OK", document.clone()),
+            None => ("404 Not Found", String::new()),
+        },
+        ("POST", path) => {
+            let action = headers
+                .get(SOAP_HTTP_HEADER_ACTION)
+                .map(|a| a.trim_matches('"').to_string())
+                .unwrap_or_default();
+            let key = (path.to_string(), action);
+            let canned = state.canned_responses.lock().unwrap().get(&key).cloned();
+            let handler = state.action_handlers.lock().unwrap().get(&key).cloned();
+            let service_handler = state.service_handlers.lock().unwrap().get(path).cloned();
+            match (canned, handler, service_handler) {
+                (Some(response), _, _) => ("200 OK", response),
+                (None, Some(handler), _) => {
+                    let (status, response) = invoke_handler(state, &handler, body);
+                    (status, response.to_string())
+                }
+                (None, None, Some(service_handler)) => {
+                    let handler: ActionHandler = Arc::new(move |_, arguments: &Arguments| {
+                        service_handler.handle(action_name(&key.1), arguments.clone())
+                    });
+                    let (status, response) = invoke_handler(state, &handler, body);
+                    (status, response.to_string())
+                }
+                (None, None, None) => (
+                    "500 Internal Server Error",
+                    Envelope::new_fault(401, "Invalid Action").to_string(),
+                ),
+            }
+        }
+        (GENA_METHOD_SUBSCRIBE, path) => {
+            let mut subscriptions = state.subscriptions.lock().unwrap();
+            let sid = match (
+                headers.get(GENA_HEADER_SID),
+                headers.get(GENA_HEADER_CALLBACK),
+                headers.get(HTTP_HEADER_NT),
+            ) {
+                (Some(sid), None, None) if subscriptions.contains_key(sid) => Some(sid.clone()),
+                (None, Some(callbacks), Some(nt)) if nt == GENA_NT_EVENT => {
+                    let sid = format!("uuid:{}", Uuid::new_v4());
+                    let _ = subscriptions.insert(
+                        sid.clone(),
+                        Subscriber {
+                            event_path: path.to_string(),
+                            callbacks: callbacks
+                                .split(['<', '>'])
+                                .filter(|callback| !callback.trim().is_empty())
+                                .map(String::from)
+                                .collect(),
+                            seq: 0,
+                        },
+                    );
+                    *new_subscription = Some(sid.clone());
+                    Some(sid)
+                }
+                _ => None,
+            };
+            match sid {
+                Some(sid) => {
+                    response_headers.push((GENA_HEADER_SID, sid));
+                    response_headers.push((
+                        GENA_HEADER_TIMEOUT,
+                        timeout_to_string(Some(state.subscription_timeout)),
+                    ));
+                    ("200 OK", String::new())
+                }
+                None => ("412 Precondition Failed", String::new()),
+            }
+        }
+        (GENA_METHOD_UNSUBSCRIBE, _) => match headers
+            .get(GENA_HEADER_SID)
+            .and_then(|sid| state.subscriptions.lock().unwrap().remove(sid))
+        {
+            Some(_) => ("200 OK", String::new()),
+            None => ("412 Precondition Failed", String::new()),
+        },
+        _ => ("405 Method Not Allowed", String::new()),
+    }
+}
+
+fn action_name(action: &str) -> &str {
+    match action.rsplit_once('#') {
+        Some((_, name)) => name,
+        None => action,
+    }
+}
+
+fn invoke_handler(
+    state: &Arc<EmulatorState>,
+    handler: &ActionHandler,
+    body: &[u8],
+) -> (&'static str, Envelope) {
+    let request = std::str::from_utf8(body)
+        .ok()
+        .and_then(|body| Envelope::from_str(body).ok());
+    match request.map(Envelope::into_body) {
+        Some(Body::Action { action, arguments }) => {
+            let events = EventSource {
+                state: state.clone(),
+            };
+            match handler(&events, &arguments) {
+                Ok(arguments) => ("200 OK", Envelope::new_response(action, arguments)),
+                Err(error) => (
+                    "500 Internal Server Error",
+                    Envelope::new_fault(error.code(), error.description()),
+                ),
+            }
+        }
+        _ => (
+            "500 Internal Server Error",
+            Envelope::new_fault(
+                UpnpError::InvalidArgs.code(),
+                UpnpError::InvalidArgs.description(),
+            ),
+        ),
+    }
+}
+
+fn evented_services(
+    root: &DeviceRoot,
+    location: &URL,
+    services: &[ServiceSnapshot],
+) -> Result<EventedServices, Error> {
+    let base = root.base_url(location);
+    let mut evented = EventedServices::default();
+    for device in root.device.all_devices() {
+        for service in &device.service_list {
+            let scpd = services
+                .iter()
+                .find(|snapshot| snapshot.service_id == service.service_id)
+                .and_then(|snapshot| Spcd::from_str(&snapshot.xml).ok());
+            let scpd = match scpd {
+                Some(scpd) => scpd,
+                None => continue,
+            };
+            let event_path = path_of(&base.resolve(&service.event_sub_url)?.to_string())?;
+            let variables: Vec<&StateVariable> = scpd
+                .service_state_table
+                .iter()
+                .filter(|variable| variable.send_events)
+                .collect();
+            for variable in &variables {
+                if let Some(moderation) = &variable.moderation {
+                    let _ = evented.moderation.insert(
+                        (event_path.clone(), variable.name.clone()),
+                        moderation.clone(),
+                    );
+                }
+            }
+            let multicast: Vec<String> = variables
+                .iter()
+                .filter(|variable| variable.multicast)
+                .map(|variable| variable.name.clone())
+                .collect();
+            if !multicast.is_empty() {
+                let _ = evented.multicast.insert(
+                    event_path.clone(),
+                    MulticastService {
+                        service_name: UniqueServiceName::with_type(
+                            device.unique_device_name.clone(),
+                            (&service.service_type).into(),
+                        )?,
+                        service_id: service.service_id.to_string(),
+                        variables: multicast,
+                    },
+                );
+            }
+            let _ = evented.properties.insert(
+                event_path,
+                variables
+                    .iter()
+                    .map(|variable| {
+                        (
+                            variable.name.clone(),
+                            variable.default_value.clone().unwrap_or_default(),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+    }
+    Ok(evented)
+}
+
+fn send_initial_event(state: &EmulatorState, sid: &str) {
+    let event_path = match state.subscriptions.lock().unwrap().get(sid) {
+        Some(subscriber) => subscriber.event_path.clone(),
+        None => return,
+    };
+    let mut property_set = PropertySet::new();
+    if let Some(properties) = state.properties.lock().unwrap().get(&event_path) {
+        for (name, value) in properties {
+            property_set.add_property(name, value);
+        }
+    }
+    send_event(state, &event_path, Some(sid), &property_set);
+}
+
+fn send_moderated_event(
+    state: &Arc<EmulatorState>,
+    event_path: &str,
+    name: &str,
+    moderation: &Moderation,
+) {
+    let value = match state
+        .properties
+        .lock()
+        .unwrap()
+        .get(event_path)
+        .and_then(|properties| properties.iter().find(|(existing, _)| existing == name))
+    {
+        Some((_, value)) => value.clone(),
+        None => return,
+    };
+    {
+        let mut moderated = state.moderated.lock().unwrap();
+        let last = match moderated.get_mut(&(event_path.to_string(), name.to_string())) {
+            Some(last) => last,
+            None => return,
+        };
+        if last.pending || !moderation.is_significant(&last.value, &value) {
+            return;
+        }
+        if let Some(remaining) = last
+            .evented_at
+            .and_then(|evented_at| moderation.remaining(evented_at.elapsed()))
+        {
+            // the delayed event sends the value current at the time it is sent.
+            last.pending = true;
+            let state = state.clone();
+            let event_path = event_path.to_string();
+            let name = name.to_string();
+            let moderation = moderation.clone();
+            let _ = spawn(move || {
+                sleep(remaining);
+                if let Some(last) = state
+                    .moderated
+                    .lock()
+                    .unwrap()
+                    .get_mut(&(event_path.clone(), name.clone()))
+                {
+                    last.pending = false;
+                }
+                send_moderated_event(&state, &event_path, &name, &moderation);
+            });
+            return;
+        }
+        last.value = value.clone();
+        last.evented_at = Some(Instant::now());
+    }
+    trace!("send_moderated_event - {} {} = {}", event_path, name, value);
+    send_event(
+        state,
+        event_path,
+        None,
+        &PropertySet::new().with_property(name, &value),
+    );
+}
+
+fn send_event(
+    state: &EmulatorState,
+    event_path: &str,
+    only: Option<&str>,
+    properties: &PropertySet,
+) {
+    let deliveries: Vec<(String, u32, Vec<String>)> = state
+        .subscriptions
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter(|(sid, subscriber)| {
+            subscriber.event_path == event_path && (only.is_none() || only == Some(sid.as_str()))
+        })
+        .map(|(sid, subscriber)| {
+            let seq = subscriber.seq;
+            subscriber.seq = subscriber.seq.checked_add(1).unwrap_or(1);
+            (sid.clone(), seq, subscriber.callbacks.clone())
+        })
+        .collect();
+    if only.is_none() {
+        send_multicast_event(state, event_path, properties);
+    }
+    let body = properties.to_string();
+    for (sid, seq, callbacks) in deliveries {
+        // The first callback URL that accepts the event is the only one used.
+        let delivered = callbacks.iter().any(|callback| {
+            state
+                .client
+                .send(
+                    &ClientRequest::new(HTTP_METHOD_NOTIFY, callback.as_str())
+                        .with_header(HTTP_HEADER_NT, GENA_NT_EVENT)
+                        .with_header(HTTP_HEADER_NTS, GENA_NTS_PROPERTY_CHANGE)
+                        .with_header(GENA_HEADER_SID, &sid)
+                        .with_header(GENA_HEADER_SEQ, &seq.to_string())
+                        .with_header(HTTP_HEADER_CONTENT_TYPE, "text/xml; charset=\"utf-8\"")
+                        .with_body(body.clone().into_bytes())
+                        .with_timeout(NOTIFY_TIMEOUT),
+                )
+                .map(|response| response.is_success())
+                .unwrap_or(false)
+        });
+        trace!("send_event - {} seq {} delivered: {}", sid, seq, delivered);
+    }
+}
+
+fn send_multicast_event(state: &EmulatorState, event_path: &str, properties: &PropertySet) {
+    if let (Some(publisher), Some(service)) = (&state.publisher, state.multicast.get(event_path)) {
+        let mut multicast = PropertySet::new();
+        for (name, value) in properties.properties() {
+            if service.variables.contains(name) {
+                multicast.add_property(name, value);
+            }
+        }
+        if !multicast.is_empty() {
+            if let Err(e) = publisher.publish(
+                &service.service_name,
+                &service.service_id,
+                EventLevel::General,
+                &multicast,
+            ) {
+                error!("send_multicast_event - failed to send: {}", e);
+            }
+        }
+    }
+}
+
+fn advertise_loop(interval: Duration, state: Arc<EmulatorState>, stop: Arc<AtomicBool>) {
+    let location = URL::from_str(&state.location).unwrap();
+    let options = NotifyOptions {
+        max_age: state.max_age,
+        ..NotifyOptions::default_for(state.spec_version)
+    };
+    let mut advertiser = Advertiser::with_devices(advertised_devices(&state, &location), options);
+    every(interval, &stop, || {
+        if let Err(e) = advertiser.announce() {
+            error!("advertise_loop - error sending alive: {:?}", e);
+        }
+    });
+    if let Err(e) = advertiser.shutdown() {
+        error!("advertise_loop - error sending byebye: {:?}", e);
+    }
+}
+
+fn create_search_socket(address: IpAddr) -> Result<UdpSocket, Error> {
+    let multicast: SocketAddr = MULTICAST_ADDRESS.parse().unwrap();
+    let socket = create_udp_socket(
+        &SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), multicast.port()),
+        &MulticastOptions {
+            reuse_address: true,
+            reuse_port: cfg!(all(
+                unix,
+                not(any(target_os = "solaris", target_os = "illumos"))
+            )),
+            ..Default::default()
+        },
+    )?;
+    if let (IpAddr::V4(group), IpAddr::V4(interface)) = (multicast.ip(), address) {
+        socket
+            .join_multicast_v4(&group, &interface)
+            .map_err(|e| multicast_join_failed(group.to_string(), e))?;
+    }
+    socket.set_read_timeout(Some(POLL_INTERVAL * 5))?;
+    Ok(socket)
+}
+
+///
+/// Bind the unicast search port; for port `0` the first available port in the dynamic range is
+/// used, as the operating system's choice of port need not be in that range.
+///
+fn create_search_port_socket(address: IpAddr, port: u16) -> Result<UdpSocket, Error> {
+    let socket = if port == 0 {
+        let mut ports = DYNAMIC_PORTS;
+        loop {
+            match ports.next() {
+                Some(port) => match UdpSocket::bind(SocketAddr::new(address, port)) {
+                    Ok(socket) => break socket,
+                    Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+                    Err(e) => return Err(e.into()),
+                },
+                None => {
+                    error!("create_search_port_socket - no available port");
+                    return Err(std::io::Error::from(std::io::ErrorKind::AddrInUse).into());
+                }
+            }
+        }
+    } else if DYNAMIC_PORTS.contains(&port) {
+        UdpSocket::bind(SocketAddr::new(address, port))?
+    } else {
+        error!("create_search_port_socket - port {} not in range", port);
+        return invalid_field_value("search_port", port.to_string()).into();
+    };
+    socket.set_read_timeout(Some(POLL_INTERVAL * 5))?;
+    Ok(socket)
+}
+
+fn search_loop(socket: UdpSocket, state: Arc<EmulatorState>, stop: Arc<AtomicBool>) {
+    let devices = search_devices(&state);
+    let mut buf = [0u8; 1500];
+    while !stop.load(Ordering::SeqCst) {
+        match socket.recv_from(&mut buf) {
+            Ok((received, from)) => {
+                if let Some(mut request) = search_request_of(&buf[..received]) {
+                    trace!(
+                        "search_loop - M-SEARCH for {} from {}",
+                        request.search_target,
+                        from
+                    );
+                    request.source = Some(from);
+                    for device in devices.iter().filter(|device| answers(device, &request)) {
+                        let response = build_response(device, &request, state.spec_version);
+                        if let Err(e) = socket.send_to(&response.raw().bytes, from) {
+                            error!("search_loop - error sending response: {:?}", e);
+                        }
+                    }
+                }
+            }
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                error!("search_loop - socket read returned error: {:?}", e);
+                break;
+            }
+        }
+    }
+}
+
+fn search_request_of(message: &[u8]) -> Option<SearchRequest> {
+    let request = Request::try_from(message).ok()?;
+    if request.known_method() != Some(Method::Search) {
+        return None;
+    }
+    SearchRequest::try_from(&request)
+        .inspect_err(|e| debug!("search_request_of - ignoring invalid search: {}", e))
+        .ok()
+}
+
+fn search_devices(state: &EmulatorState) -> Vec<DeviceInfo> {
+    let location = URL::from_str(&state.location).unwrap();
+    advertised_devices(state, &location)
+        .into_iter()
+        .map(|device| DeviceInfo {
+            max_age: state.max_age,
+            ..DeviceInfo::new(device)
+        })
+        .collect()
+}
+
+fn advertised_devices(state: &EmulatorState, location: &URL) -> Vec<SsdpDeviceInfo> {
+    advertisements_for(&state.root, location)
+        .into_iter()
+        .map(|advertisement| SsdpDeviceInfo {
+            search_port: state.search_port,
+            ..advertisement.into_device(state.identity.clone())
+        })
+        .collect()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::http::fetch;
+    use crate::description::service::Spcd;
+    use crate::snapshot::ServiceSnapshot;
+    use std::time::UNIX_EPOCH;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><URLBase>http://10.0.0.1:49152/</URLBase><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:Basic:1</serviceType><serviceId>urn:upnp-org:serviceId:Basic</serviceId><SCPDURL>/basic.xml</SCPDURL><controlURL>/control</controlURL><eventSubURL>/event</eventSubURL></service></serviceList></device></root>";
+
+    const SCPD: &str = "<?xml version=\"1.0\"?><scpd xmlns=\"urn:schemas-upnp-org:service-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><serviceStateTable></serviceStateTable></scpd>";
+
+    fn snapshot() -> DeviceSnapshot {
+        DeviceSnapshot {
+            location: URL::from_str("http://10.0.0.1:49152/description.xml").unwrap(),
+            captured: UNIX_EPOCH,
+            description: DESCRIPTION.to_string(),
+            services: vec![ServiceSnapshot {
+                service_id: ServiceId::new("Basic".to_string()),
+                scpd_url: URL::from_str("http://10.0.0.1:49152/basic.xml").unwrap(),
+                xml: SCPD.to_string(),
+                state: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_search_response() {
+        let request = search_request_of(
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: urn:schemas-upnp-org:service:Basic:1\r\n\r\n",
+        )
+        .unwrap();
+        let state = EmulatorState {
+            location: "http://127.0.0.1:8080/description.xml".to_string(),
+            server: "Test/1.0 UPnP/1.1 Test/1.0".to_string(),
+            spec_version: SpecVersion::V11,
+            identity: SsdpIdentity::with_values(12, 3),
+            max_age: 1800,
+            search_port: Some(49200),
+            root: DeviceRoot::from_str(DESCRIPTION).unwrap(),
+            documents: Default::default(),
+            canned_responses: Default::default(),
+            action_handlers: Default::default(),
+            service_handlers: Default::default(),
+            subscription_timeout: Duration::from_secs(1800),
+            subscriptions: Default::default(),
+            properties: Default::default(),
+            moderation: Default::default(),
+            moderated: Default::default(),
+            multicast: Default::default(),
+            publisher: None,
+            client: default_client(),
+        };
+        let responses: Vec<String> = search_devices(&state)
+            .iter()
+            .filter(|device| answers(device, &request))
+            .map(|device| {
+                String::from_utf8(
+                    build_response(device, &request, state.spec_version)
+                        .raw()
+                        .bytes
+                        .clone(),
+                )
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(responses.len(), 1);
+        let response = &responses[0];
+        assert!(response.contains("\r\nST: urn:schemas-upnp-org:service:Basic:1\r\n"));
+        assert!(response
+            .contains("\r\nUSN: uuid:test-device::urn:schemas-upnp-org:service:Basic:1\r\n"));
+        assert!(response.contains("\r\nBOOTID.UPNP.ORG: 12\r\nCONFIGID.UPNP.ORG: 3\r\n"));
+        assert!(response.contains("\r\nSEARCHPORT.UPNP.ORG: 49200\r\n"));
+        assert!(response.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_answer_on_search_port() {
+        let device = from_snapshot_with(
+            snapshot(),
+            Options {
+                spec_version: SpecVersion::V11,
+                answer_search: false,
+                search_port: Some(0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let search_port = device.search_port().unwrap();
+        assert!(DYNAMIC_PORTS.contains(&search_port));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let _ = client
+            .send_to(
+                format!("M-SEARCH * HTTP/1.1\r\nHOST: 127.0.0.1:{}\r\nMAN: \"ssdp:discover\"\r\nST: upnp:rootdevice\r\nUSER-AGENT: Linux/5.0 UPnP/1.1 Test/1.0\r\n\r\n", search_port).as_bytes(),
+                ("127.0.0.1", search_port),
+            )
+            .unwrap();
+        let mut buf = [0u8; 1500];
+        let (received, _) = client.recv_from(&mut buf).unwrap();
+        let response = String::from_utf8_lossy(&buf[..received]);
+        assert!(response.contains("\r\nST: upnp:rootdevice\r\n"));
+        assert!(response.contains(&format!("\r\nSEARCHPORT.UPNP.ORG: {}\r\n", search_port)));
+
+        assert!(from_snapshot_with(
+            snapshot(),
+            Options {
+                answer_search: false,
+                search_port: Some(1901),
+                ..Default::default()
+            },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_serve_description() {
+        let device = from_snapshot_with(
+            snapshot(),
+            Options {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let root: DeviceRoot = fetch(device.location().to_string()).unwrap();
+        assert_eq!(root.url_base, format!("http://{}/", device.local_address()));
+
+        let scpd_url = URL::from_str(&root.url_base)
+            .unwrap()
+            .resolve(&root.device.service_list[0].scpd_url)
+            .unwrap();
+        let _: Spcd = fetch(scpd_url.to_string()).unwrap();
+
+        device.stop();
+    }
+
+    #[test]
+    fn test_device_actions_and_events() {
+        use crate::control::ServiceClient;
+        use crate::description::TypeID;
+        use crate::eventing::client::subscribe;
+        use crate::eventing::listener::{EventListener, Options as ListenerOptions};
+
+        let root = DeviceRoot::from_str(DESCRIPTION).unwrap();
+        let mut scpd = Spcd::from_str(SCPD).unwrap();
+        scpd.service_state_table
+            .push(crate::description::service::StateVariable {
+                send_events: true,
+                multicast: false,
+                name: "Status".to_string(),
+                data_type: "boolean".to_string(),
+                default_value: Some("0".to_string()),
+                allowed_values: None,
+                moderation: None,
+            });
+        let mut services = HashMap::new();
+        let _ = services.insert(ServiceId::new("Basic".to_string()), scpd);
+        let device = from_device_with(
+            root,
+            services,
+            Options {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        device.add_action_handler(
+            "/control",
+            "urn:schemas-upnp-org:service:Basic:1#SetStatus",
+            |events, arguments| match arguments.first() {
+                Some((_, value)) => {
+                    events.set("/event", "Status", value);
+                    Ok(vec![("Result".to_string(), "OK".to_string())])
+                }
+                None => Err(UpnpError::InvalidArgs),
+            },
+        );
+
+        let listener = EventListener::bind(ListenerOptions {
+            address: "127.0.0.1:0".parse().unwrap(),
+            buffer_size: 8,
+            ..Default::default()
+        })
+        .unwrap();
+        let events = listener.events_for("basic");
+        let base = URL::from_str(&format!("http://{}", device.local_address())).unwrap();
+        let _ = subscribe(
+            &base.resolve("/event").unwrap(),
+            &[listener.callback_url("basic")],
+            None,
+        )
+        .unwrap();
+        let initial = events.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(initial.seq, 0);
+        assert_eq!(initial.properties.get("Status"), Some("0"));
+
+        let client = ServiceClient::new(
+            TypeID::from_str("urn:schemas-upnp-org:service:Basic:1").unwrap(),
+            base.resolve("/control").unwrap(),
+        );
+        let result = client.invoke("SetStatus", &[("NewStatus", "1")]).unwrap();
+        assert_eq!(result, vec![("Result".to_string(), "OK".to_string())]);
+        let changed = events.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(changed.seq, 1);
+        assert_eq!(changed.properties.get("Status"), Some("1"));
+        assert_eq!(
+            device.event_source().get("/event", "Status"),
+            Some("1".to_string())
+        );
+
+        assert!(client.invoke("GetStatus", &[]).is_err());
+
+        device.stop();
+    }
+
+    #[test]
+    fn test_moderated_events() {
+        use crate::description::service::StateVariable;
+        use crate::eventing::client::subscribe;
+        use crate::eventing::listener::{EventListener, Options as ListenerOptions};
+
+        let mut scpd = Spcd::from_str(SCPD).unwrap();
+        scpd.service_state_table.push(StateVariable {
+            send_events: true,
+            multicast: false,
+            name: "Level".to_string(),
+            data_type: "ui1".to_string(),
+            default_value: Some("0".to_string()),
+            allowed_values: None,
+            moderation: Some(Moderation {
+                maximum_rate: Some(Duration::from_secs(1)),
+                minimum_delta: Some(5.0),
+            }),
+        });
+        let mut services = HashMap::new();
+        let _ = services.insert(ServiceId::new("Basic".to_string()), scpd);
+        let device = from_device_with(
+            DeviceRoot::from_str(DESCRIPTION).unwrap(),
+            services,
+            Options {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let listener = EventListener::bind(ListenerOptions {
+            address: "127.0.0.1:0".parse().unwrap(),
+            buffer_size: 8,
+            ..Default::default()
+        })
+        .unwrap();
+        let events = listener.events_for("basic");
+        let base = URL::from_str(&format!("http://{}", device.local_address())).unwrap();
+        let _ = subscribe(
+            &base.resolve("/event").unwrap(),
+            &[listener.callback_url("basic")],
+            None,
+        )
+        .unwrap();
+        let initial = events.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(initial.properties.get("Level"), Some("0"));
+
+        let source = device.event_source();
+        // smaller than the minimum delta.
+        source.set("/event", "Level", "2");
+        // the first event is not delayed.
+        source.set("/event", "Level", "10");
+        let changed = events.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(changed.seq, 1);
+        assert_eq!(changed.properties.get("Level"), Some("10"));
+
+        // within the maximum rate, only the latest value is sent.
+        source.set("/event", "Level", "20");
+        source.set("/event", "Level", "30");
+        let changed = events.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(changed.seq, 2);
+        assert_eq!(changed.properties.get("Level"), Some("30"));
+        assert!(events.next_timeout(Duration::from_millis(500)).is_none());
+
+        device.stop();
+    }
+
+    #[test]
+    fn test_service_handler() {
+        use crate::control::ServiceClient;
+        use crate::description::TypeID;
+
+        #[derive(Default)]
+        struct Switch {
+            events: Mutex<Option<ServiceEvents>>,
+        }
+
+        impl ServiceHandler for Switch {
+            fn handle(&self, action: &str, arguments: Arguments) -> Result<Arguments, UpnpError> {
+                let events = self.events.lock().unwrap();
+                let events = events.as_ref().unwrap();
+                match (action, arguments.first()) {
+                    ("SetStatus", Some((_, value))) => {
+                        events.set("Status", value);
+                        Ok(Vec::new())
+                    }
+                    ("GetStatus", _) => Ok(vec![(
+                        "ResultStatus".to_string(),
+                        events.get("Status").unwrap_or_default(),
+                    )]),
+                    _ => Err(UpnpError::InvalidAction),
+                }
+            }
+
+            fn state_variables(&self) -> Vec<(String, String)> {
+                vec![("Status".to_string(), "1".to_string())]
+            }
+
+            fn attached(&self, events: ServiceEvents) {
+                *self.events.lock().unwrap() = Some(events);
+            }
+        }
+
+        let device = from_device_with(
+            DeviceRoot::from_str(DESCRIPTION).unwrap(),
+            Default::default(),
+            Options {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(device
+            .add_service_handler(&ServiceId::new("Missing".to_string()), Switch::default())
+            .is_err());
+        device
+            .add_service_handler(&ServiceId::new("Basic".to_string()), Switch::default())
+            .unwrap();
+
+        let base = URL::from_str(&format!("http://{}", device.local_address())).unwrap();
+        let client = ServiceClient::new(
+            TypeID::from_str("urn:schemas-upnp-org:service:Basic:1").unwrap(),
+            base.resolve("/control").unwrap(),
+        );
+        let status = |client: &ServiceClient| client.invoke("GetStatus", &[]).unwrap();
+        assert_eq!(
+            status(&client),
+            vec![("ResultStatus".to_string(), "1".to_string())]
+        );
+        let _ = client.invoke("SetStatus", &[("NewStatus", "0")]).unwrap();
+        assert_eq!(
+            status(&client),
+            vec![("ResultStatus".to_string(), "0".to_string())]
+        );
+        assert!(client.invoke("Toggle", &[]).is_err());
+
+        device.stop();
+    }
+}
@@ -1,16 +1,35 @@
 /*!
-One-line description.
-More detailed description, with
+This module provides the single error type used across the crate, [`Error`](enum.Error.html), along with the
+detailed [`MessageFormatError`] describing problems with the content of messages and documents.
+
+The variants of `Error` are categorized by the area of the stack in which the error arose so that
+callers can match on the category and react accordingly; the underlying cause, where there is one,
+is available through [`std::error::Error::source`].
+
+Socket errors are further classified by their [`std::io::ErrorKind`] into the [`NetworkError`]
+variants `Timeout`, `PermissionDenied`, `AddressInUse`, and `NetworkUnreachable`, so that a caller
+can give an actionable message; any other socket error is reported as `Transport`.
+
 # Example
- */
+
+```rust
+use upnp_rs::error::{invalid_header_value, Error};
+use std::error::Error as _;
+
+let error: Error = invalid_header_value("CACHE-CONTROL", "forever").into();
+assert!(matches!(error, Error::Protocol(_)));
+assert!(error.source().is_some());
+```
+*/
 
 use quick_xml::Error as XMLError;
-use reqwest::Error as HTTPError;
+use std::error::Error as StdError;
 use std::fmt::Display;
-use std::io::Error as IOError;
+use std::io::{Error as IOError, ErrorKind as IOErrorKind};
 use std::str::Utf8Error;
 use thiserror::Error;
 
+use crate::control::UpnpError;
 use crate::SpecVersion;
 
 // ------------------------------------------------------------------------------------------------
@@ -22,17 +41,25 @@ use crate::SpecVersion;
 ///
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error(transparent)]
-    NetworkTransport(#[from] IOError),
+    /// An error in the underlying network transport, or in HTTP messaging.
+    #[error("A network operation failed: {0}")]
+    Network(#[source] NetworkError),
 
-    #[error(transparent)]
-    Messaging(#[from] HTTPError),
+    /// An SSDP or HTTP protocol message was malformed or incomplete.
+    #[error("A protocol message was invalid: {0}")]
+    Protocol(#[source] MessageFormatError),
 
-    #[error(transparent)]
-    MessageFormat(#[from] MessageFormatError),
+    /// A device or service description document was malformed or incomplete.
+    #[error("A device or service description was invalid: {0}")]
+    Description(#[source] MessageFormatError),
 
-    #[error("An operation you attempted returned an error status `{status}` (Operation: `{0}`)")]
-    OperationFailed { operation: String, status: String },
+    /// An error reported by, or in communicating with, a service's control endpoint.
+    #[error("A control operation failed: {0}")]
+    Control(#[source] ControlError),
+
+    /// An error reported by, or in communicating with, a service's eventing endpoint.
+    #[error("An eventing operation failed: {0}")]
+    Eventing(#[source] EventingError),
 
     #[error("The version supplied is valid, but not supported (Version: `{version}`)")]
     UnsupportedVersion { version: SpecVersion },
@@ -41,6 +68,82 @@ pub enum Error {
     UnsupportedOperation { operation: String },
 }
 
+///
+/// Errors within the `Network` category.
+///
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error("An error occurred in the network transport: {0}")]
+    Transport(#[source] IOError),
+
+    #[error("A network operation timed out: {0}")]
+    Timeout(#[source] IOError),
+
+    #[error("Permission denied by the operating system; binding to the SSDP port 1900 may require elevated privileges (such as CAP_NET_BIND_SERVICE), or use a SEARCHPORT: {0}")]
+    PermissionDenied(#[source] IOError),
+
+    #[error(
+        "The local address is already in use; enable address reuse, or choose another port: {0}"
+    )]
+    AddressInUse(#[source] IOError),
+
+    #[error("The network, or host, is unreachable; check the selected network interface: {0}")]
+    NetworkUnreachable(#[source] IOError),
+
+    #[error("Could not join the multicast group `{group}`; check the selected network interface supports multicast: {source}")]
+    MulticastJoinFailed {
+        group: String,
+        #[source]
+        source: IOError,
+    },
+
+    #[error("An error occurred in HTTP messaging: {0}")]
+    Messaging(#[source] Box<dyn StdError + Send + Sync>),
+
+    #[error(
+        "An operation you attempted returned an error status `{status}` (Operation: `{operation}`)"
+    )]
+    OperationFailed { operation: String, status: String },
+
+    #[error("No network interface matched `{selector}`")]
+    UnknownInterface { selector: String },
+
+    #[error("The network interface `{interface}` has no address of the requested version")]
+    NoInterfaceAddress { interface: String },
+}
+
+///
+/// Errors within the `Control` category.
+///
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error("The action `{action}` failed with UPnP error {code} ({description})")]
+    ActionFailed {
+        action: String,
+        code: u16,
+        description: String,
+    },
+
+    #[error("The argument `{argument}` for action `{action}` is invalid: {reason}")]
+    InvalidArgument {
+        action: String,
+        argument: String,
+        reason: String,
+    },
+}
+
+///
+/// Errors within the `Eventing` category.
+///
+#[derive(Debug, Error)]
+pub enum EventingError {
+    #[error("The subscription request to `{url}` failed with status `{status}`")]
+    SubscriptionFailed { url: String, status: String },
+
+    #[error("The subscription `{sid}` is not known, or has expired")]
+    UnknownSubscription { sid: String },
+}
+
 #[derive(Clone, Copy, Debug, Error)]
 pub enum ValueSource {
     Socket,
@@ -79,6 +182,9 @@ pub enum MessageFormatError {
 
     #[error("The value provided is not valid for type `{for_type}` (Value: `{value}`)")]
     InvalidValueForType { for_type: String, value: String },
+
+    #[error("A message larger than the receive buffer (`{buffer_size}` bytes) was truncated")]
+    MessageTruncated { buffer_size: usize },
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -205,8 +311,104 @@ where
     }
 }
 
+pub fn message_truncated(buffer_size: usize) -> MessageFormatError {
+    MessageFormatError::MessageTruncated { buffer_size }
+}
+
 pub fn xml_error(e: XMLError) -> Error {
-    Error::MessageFormat(MessageFormatError::XmlFormat(e))
+    Error::Description(MessageFormatError::XmlFormat(e))
+}
+
+pub fn operation_failed<S1, S2>(operation: S1, status: S2) -> Error
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    Error::Network(NetworkError::OperationFailed {
+        operation: operation.into(),
+        status: status.into(),
+    })
+}
+
+pub fn action_failed<S1, S2>(action: S1, code: u16, description: S2) -> Error
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    Error::Control(ControlError::ActionFailed {
+        action: action.into(),
+        code,
+        description: description.into(),
+    })
+}
+
+///
+/// An error reported by an [`HttpClient`](../common/http/trait.HttpClient.html) implementation.
+///
+pub fn messaging_error<E>(e: E) -> Error
+where
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    Error::Network(NetworkError::Messaging(e.into()))
+}
+
+pub fn multicast_join_failed<S>(group: S, source: IOError) -> Error
+where
+    S: Into<String>,
+{
+    Error::Network(NetworkError::MulticastJoinFailed {
+        group: group.into(),
+        source,
+    })
+}
+
+pub fn unknown_interface<S>(selector: S) -> Error
+where
+    S: Into<String>,
+{
+    Error::Network(NetworkError::UnknownInterface {
+        selector: selector.into(),
+    })
+}
+
+pub fn no_interface_address<S>(interface: S) -> Error
+where
+    S: Into<String>,
+{
+    Error::Network(NetworkError::NoInterfaceAddress {
+        interface: interface.into(),
+    })
+}
+
+pub fn invalid_argument<S1, S2, S3>(action: S1, argument: S2, reason: S3) -> Error
+where
+    S1: Into<String>,
+    S2: Into<String>,
+    S3: Into<String>,
+{
+    Error::Control(ControlError::InvalidArgument {
+        action: action.into(),
+        argument: argument.into(),
+        reason: reason.into(),
+    })
+}
+
+pub fn subscription_failed<S1, S2>(url: S1, status: S2) -> Error
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    Error::Eventing(EventingError::SubscriptionFailed {
+        url: url.into(),
+        status: status.into(),
+    })
+}
+
+pub fn unknown_subscription<S>(sid: S) -> Error
+where
+    S: Into<String>,
+{
+    Error::Eventing(EventingError::UnknownSubscription { sid: sid.into() })
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -229,6 +431,120 @@ impl Display for ValueSource {
 
 // ------------------------------------------------------------------------------------------------
 
+impl Error {
+    ///
+    /// Returns `true` if this error arose in the network transport or HTTP messaging.
+    ///
+    pub fn is_network(&self) -> bool {
+        matches!(self, Error::Network(_))
+    }
+
+    ///
+    /// Returns `true` if this error is a network operation that timed out.
+    ///
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::Network(NetworkError::Timeout(_)))
+    }
+
+    ///
+    /// Returns `true` if this error arose from a malformed message or document.
+    ///
+    pub fn is_format(&self) -> bool {
+        matches!(self, Error::Protocol(_) | Error::Description(_))
+    }
+
+    ///
+    /// Returns `true` if a publisher rejected a subscription request as it does not recognize the
+    /// subscription, in which case the subscriber should subscribe again.
+    ///
+    pub fn is_unknown_subscription(&self) -> bool {
+        matches!(
+            self,
+            Error::Eventing(EventingError::UnknownSubscription { .. })
+        )
+    }
+
+    ///
+    /// Returns the UPnP error reported by a device, if this error is the result of a failed action.
+    ///
+    pub fn upnp_error(&self) -> Option<UpnpError> {
+        match self {
+            Error::Control(ControlError::ActionFailed { code, .. }) => {
+                Some(UpnpError::from_code(*code))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<IOError> for Error {
+    fn from(e: IOError) -> Self {
+        Error::Network(e.into())
+    }
+}
+
+///
+/// Socket errors are classified by kind, see the module documentation.
+///
+impl From<IOError> for NetworkError {
+    fn from(e: IOError) -> Self {
+        match e.kind() {
+            IOErrorKind::TimedOut | IOErrorKind::WouldBlock => NetworkError::Timeout(e),
+            IOErrorKind::PermissionDenied => NetworkError::PermissionDenied(e),
+            IOErrorKind::AddrInUse => NetworkError::AddressInUse(e),
+            IOErrorKind::NetworkUnreachable
+            | IOErrorKind::HostUnreachable
+            | IOErrorKind::AddrNotAvailable => NetworkError::NetworkUnreachable(e),
+            _ => NetworkError::Transport(e),
+        }
+    }
+}
+
+impl From<NetworkError> for Error {
+    fn from(e: NetworkError) -> Self {
+        Error::Network(e)
+    }
+}
+
+///
+/// Message format errors are categorized by their source; XML errors and invalid document fields
+/// are description errors, all others (headers, socket values, encoding) are protocol errors.
+///
+impl From<MessageFormatError> for Error {
+    fn from(e: MessageFormatError) -> Self {
+        match &e {
+            MessageFormatError::XmlFormat(_)
+            | MessageFormatError::MissingRequiredValue {
+                source: ValueSource::Field,
+                ..
+            }
+            | MessageFormatError::ValueTypeMismatch {
+                source: ValueSource::Field,
+                ..
+            }
+            | MessageFormatError::InvalidValue {
+                source: ValueSource::Field,
+                ..
+            } => Error::Description(e),
+            _ => Error::Protocol(e),
+        }
+    }
+}
+
+impl From<ControlError> for Error {
+    fn from(e: ControlError) -> Self {
+        Error::Control(e)
+    }
+}
+
+impl From<EventingError> for Error {
+    fn from(e: EventingError) -> Self {
+        Error::Eventing(e)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
 impl<T> From<MessageFormatError> for Result<T, MessageFormatError> {
     fn from(e: MessageFormatError) -> Self {
         Err(e)
@@ -237,7 +553,7 @@ impl<T> From<MessageFormatError> for Result<T, MessageFormatError> {
 
 impl<T> From<MessageFormatError> for Result<T, Error> {
     fn from(e: MessageFormatError) -> Self {
-        Err(Error::MessageFormat(e))
+        Err(e.into())
     }
 }
 
@@ -250,3 +566,68 @@ impl<T> From<Error> for Result<T, Error> {
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_categories() {
+        let error: Error = missing_required_field("UDN").into();
+        assert!(matches!(error, Error::Description(_)));
+        assert!(error.is_format());
+
+        let error: Error = missing_required_header("USN").into();
+        assert!(matches!(error, Error::Protocol(_)));
+
+        let error: Error = IOError::from(ErrorKind::TimedOut).into();
+        assert!(error.is_network());
+        assert!(error.is_timeout());
+
+        let error: Error = IOError::from(ErrorKind::PermissionDenied).into();
+        assert!(matches!(
+            error,
+            Error::Network(NetworkError::PermissionDenied(_))
+        ));
+
+        let error: Error = IOError::from(ErrorKind::AddrInUse).into();
+        assert!(matches!(
+            error,
+            Error::Network(NetworkError::AddressInUse(_))
+        ));
+
+        let error = action_failed("GetStatus", 401, "Invalid Action");
+        assert!(matches!(
+            error,
+            Error::Control(ControlError::ActionFailed { code: 401, .. })
+        ));
+    }
+
+    #[test]
+    fn test_source_chaining() {
+        let error: Error = IOError::other("broken").into();
+        let network = error.source().unwrap();
+        let transport = network.source().unwrap();
+        assert_eq!(transport.to_string(), "broken");
+    }
+
+    #[test]
+    fn test_display_includes_cause() {
+        let error: Error = IOError::other("broken").into();
+        assert_eq!(
+            error.to_string(),
+            "A network operation failed: An error occurred in the network transport: broken"
+        );
+
+        let error = unknown_subscription("uuid:1");
+        assert_eq!(
+            error.to_string(),
+            "An eventing operation failed: The subscription `uuid:1` is not known, or has expired"
+        );
+    }
+}
@@ -0,0 +1,520 @@
+/*!
+This module provides the control point side of GENA subscriptions; subscribing to a service's
+event URL, renewing the subscription before it expires, and cancelling it. A [`SubscriptionGuard`]
+owns a subscription and cancels it when dropped.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::eventing::client::{renew, subscribe, unsubscribe};
+use std::str::FromStr;
+use std::time::Duration;
+
+let event_url = URL::from_str("http://10.0.0.2:1400/MediaRenderer/AVTransport/Event").unwrap();
+let callback = URL::from_str("http://10.0.0.1:8080/events").unwrap();
+let subscription = subscribe(&event_url, &[callback], Some(Duration::from_secs(1800))).unwrap();
+let subscription = renew(&subscription, Some(Duration::from_secs(1800))).unwrap();
+unsubscribe(&subscription).unwrap();
+```
+
+Using a guard, the subscription is cancelled at the end of the scope. If a renewal is rejected as
+the publisher no longer recognizes the subscription, the caller should subscribe again.
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::eventing::client::SubscriptionGuard;
+use std::str::FromStr;
+use std::time::Duration;
+
+let event_url = URL::from_str("http://10.0.0.2:1400/MediaRenderer/AVTransport/Event").unwrap();
+let callback = URL::from_str("http://10.0.0.1:8080/events").unwrap();
+let mut guard =
+    SubscriptionGuard::subscribe(&event_url, &[callback], Some(Duration::from_secs(1800))).unwrap();
+match guard.renew(Some(Duration::from_secs(1800))) {
+    Ok(granted) => println!("renewed for {:?}", granted),
+    Err(e) if e.is_unknown_subscription() => println!("subscription lost, subscribe again"),
+    Err(e) => println!("renewal failed: {}", e),
+}
+```
+*/
+
+use crate::common::http::{ClientRequest, ClientResponse, HttpClient};
+use crate::common::uri::URL;
+use crate::common::user_agent::user_agent_string;
+use crate::error::{
+    invalid_header_value, missing_required_header, subscription_failed, unknown_subscription, Error,
+};
+use crate::observer::{self, MessageKind};
+use crate::syntax::{
+    GENA_HEADER_CALLBACK, GENA_HEADER_SID, GENA_HEADER_TIMEOUT, GENA_METHOD_SUBSCRIBE,
+    GENA_METHOD_UNSUBSCRIBE, GENA_NT_EVENT, GENA_TIMEOUT_INFINITE, GENA_TIMEOUT_PREFIX,
+    HTTP_HEADER_NT, HTTP_HEADER_USER_AGENT,
+};
+use crate::SpecVersion;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, info_span, warn};
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An accepted subscription; the subscription identifier (SID) and the duration granted by the
+/// publisher, `None` denotes an infinite subscription (only permitted in UDA 1.0).
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscription {
+    sid: String,
+    event_url: URL,
+    timeout: Option<Duration>,
+}
+
+///
+/// Owns a subscription, and the client used to manage it, cancelling the subscription when
+/// dropped. Cancellation on drop is best-effort, the request is limited to
+/// `DROP_UNSUBSCRIBE_TIMEOUT` and any failure is only logged.
+///
+#[derive(Debug)]
+pub struct SubscriptionGuard {
+    subscription: Subscription,
+    client: Arc<dyn HttpClient>,
+    active: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Values
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The time allowed for the `UNSUBSCRIBE` request sent when a `SubscriptionGuard` is dropped.
+///
+pub const DROP_UNSUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Subscribe to the events published at `event_url`, events will be sent to the first of the
+/// `callbacks` URLs that the publisher can reach. The publisher may grant a different timeout from
+/// the one requested.
+///
+#[cfg(feature = "net")]
+pub fn subscribe(
+    event_url: &URL,
+    callbacks: &[URL],
+    timeout: Option<Duration>,
+) -> Result<Subscription, Error> {
+    subscribe_with(event_url, callbacks, timeout, default_client().as_ref())
+}
+
+pub fn subscribe_with(
+    event_url: &URL,
+    callbacks: &[URL],
+    timeout: Option<Duration>,
+    client: &dyn HttpClient,
+) -> Result<Subscription, Error> {
+    let _span = info_span!("subscribe", url = %event_url).entered();
+    info!("subscribing");
+    let callbacks: String = callbacks
+        .iter()
+        .map(|callback| format!("<{}>", callback))
+        .collect();
+    let response = send(
+        client,
+        ClientRequest::new(GENA_METHOD_SUBSCRIBE, &event_url.to_string())
+            .with_header(GENA_HEADER_CALLBACK, &callbacks)
+            .with_header(HTTP_HEADER_NT, GENA_NT_EVENT)
+            .with_header(GENA_HEADER_TIMEOUT, &timeout_to_string(timeout)),
+    )?;
+    if !response.is_success() {
+        error!(
+            "subscribe_with - subscription to {} returned status {}",
+            event_url, response.status
+        );
+        return subscription_failed(event_url.to_string(), response.status_text()).into();
+    }
+    subscription_from(event_url, &response)
+}
+
+///
+/// Renew the subscription, returning the subscription with the newly granted timeout. If the
+/// publisher no longer recognizes the subscription an `EventingError::UnknownSubscription` error
+/// is returned and the caller should subscribe again.
+///
+#[cfg(feature = "net")]
+pub fn renew(
+    subscription: &Subscription,
+    timeout: Option<Duration>,
+) -> Result<Subscription, Error> {
+    renew_with(subscription, timeout, default_client().as_ref())
+}
+
+pub fn renew_with(
+    subscription: &Subscription,
+    timeout: Option<Duration>,
+    client: &dyn HttpClient,
+) -> Result<Subscription, Error> {
+    let _span = info_span!(
+        "subscribe",
+        url = %subscription.event_url,
+        sid = %subscription.sid
+    )
+    .entered();
+    info!("renewing");
+    let response = send(
+        client,
+        ClientRequest::new(GENA_METHOD_SUBSCRIBE, &subscription.event_url.to_string())
+            .with_header(GENA_HEADER_SID, &subscription.sid)
+            .with_header(GENA_HEADER_TIMEOUT, &timeout_to_string(timeout)),
+    )?;
+    check_status(subscription, &response)?;
+    subscription_from(&subscription.event_url, &response)
+}
+
+///
+/// Cancel the subscription.
+///
+#[cfg(feature = "net")]
+pub fn unsubscribe(subscription: &Subscription) -> Result<(), Error> {
+    unsubscribe_with(subscription, default_client().as_ref())
+}
+
+pub fn unsubscribe_with(subscription: &Subscription, client: &dyn HttpClient) -> Result<(), Error> {
+    cancel(subscription, client, None)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Subscription {
+    pub fn sid(&self) -> &str {
+        &self.sid
+    }
+
+    pub fn event_url(&self) -> &URL {
+        &self.event_url
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        if self.active {
+            if let Err(e) = cancel(
+                &self.subscription,
+                self.client.as_ref(),
+                Some(DROP_UNSUBSCRIBE_TIMEOUT),
+            ) {
+                warn!(
+                    "drop - could not cancel subscription {}: {}",
+                    self.subscription.sid, e
+                );
+            }
+        }
+    }
+}
+
+impl SubscriptionGuard {
+    ///
+    /// Subscribe, as `subscribe` does, returning a guard that cancels the subscription when
+    /// dropped.
+    ///
+    #[cfg(feature = "net")]
+    pub fn subscribe(
+        event_url: &URL,
+        callbacks: &[URL],
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error> {
+        Self::subscribe_with(event_url, callbacks, timeout, default_client())
+    }
+
+    ///
+    /// As [`SubscriptionGuard::subscribe`], sending all requests using `client`.
+    ///
+    pub fn subscribe_with(
+        event_url: &URL,
+        callbacks: &[URL],
+        timeout: Option<Duration>,
+        client: Arc<dyn HttpClient>,
+    ) -> Result<Self, Error> {
+        let subscription = subscribe_with(event_url, callbacks, timeout, client.as_ref())?;
+        Ok(Self::new(subscription, client))
+    }
+
+    ///
+    /// Take ownership of an existing subscription, which will be managed using `client`.
+    ///
+    pub fn new(subscription: Subscription, client: Arc<dyn HttpClient>) -> Self {
+        Self {
+            subscription,
+            client,
+            active: true,
+        }
+    }
+
+    pub fn subscription(&self) -> &Subscription {
+        &self.subscription
+    }
+
+    ///
+    /// Renew the subscription, returning the newly granted timeout. If the publisher no longer
+    /// recognizes the subscription an error for which `Error::is_unknown_subscription` is `true`
+    /// is returned, and the subscription will not be cancelled on drop.
+    ///
+    pub fn renew(&mut self, timeout: Option<Duration>) -> Result<Option<Duration>, Error> {
+        match renew_with(&self.subscription, timeout, self.client.as_ref()) {
+            Ok(renewed) => {
+                self.subscription = renewed;
+                Ok(self.subscription.timeout)
+            }
+            Err(e) => {
+                if e.is_unknown_subscription() {
+                    self.active = false;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    ///
+    /// Cancel the subscription now, returning any error rather than only logging it.
+    ///
+    pub fn unsubscribe(mut self) -> Result<(), Error> {
+        self.active = false;
+        unsubscribe_with(&self.subscription, self.client.as_ref())
+    }
+
+    ///
+    /// Release the subscription from the guard, it will no longer be cancelled on drop.
+    ///
+    pub fn release(mut self) -> Subscription {
+        self.active = false;
+        self.subscription.clone()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const STATUS_PRECONDITION_FAILED: u16 = 412;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn cancel(
+    subscription: &Subscription,
+    client: &dyn HttpClient,
+    request_timeout: Option<Duration>,
+) -> Result<(), Error> {
+    let _span = info_span!(
+        "subscribe",
+        url = %subscription.event_url,
+        sid = %subscription.sid
+    )
+    .entered();
+    info!("cancelling");
+    let request = ClientRequest::new(GENA_METHOD_UNSUBSCRIBE, &subscription.event_url.to_string())
+        .with_header(GENA_HEADER_SID, &subscription.sid);
+    let request = match request_timeout {
+        None => request,
+        Some(request_timeout) => request.with_timeout(request_timeout),
+    };
+    let response = send(client, request)?;
+    check_status(subscription, &response)
+}
+
+fn send(client: &dyn HttpClient, request: ClientRequest) -> Result<ClientResponse, Error> {
+    let request = request.with_header(
+        HTTP_HEADER_USER_AGENT,
+        &user_agent_string(SpecVersion::default(), None),
+    );
+    observer::sent(MessageKind::Eventing);
+    let response = client.send(&request)?;
+    observer::received(MessageKind::Eventing);
+    info!(status = response.status, "received");
+    Ok(response)
+}
+
+fn check_status(subscription: &Subscription, response: &ClientResponse) -> Result<(), Error> {
+    match response.status {
+        _ if response.is_success() => Ok(()),
+        STATUS_PRECONDITION_FAILED => {
+            error!(
+                "check_status - subscription {} is not known to {}",
+                subscription.sid, subscription.event_url
+            );
+            unknown_subscription(&subscription.sid).into()
+        }
+        status => {
+            error!(
+                "check_status - request for {} returned status {}",
+                subscription.sid, status
+            );
+            subscription_failed(subscription.event_url.to_string(), response.status_text()).into()
+        }
+    }
+}
+
+fn subscription_from(event_url: &URL, response: &ClientResponse) -> Result<Subscription, Error> {
+    let header = |name: &str| -> Result<&str, Error> {
+        match response.header(name) {
+            Some(value) => Ok(value.trim()),
+            None => missing_required_header(name).into(),
+        }
+    };
+    Ok(Subscription {
+        sid: header(GENA_HEADER_SID)?.to_string(),
+        event_url: event_url.clone(),
+        timeout: timeout_from_str(header(GENA_HEADER_TIMEOUT)?)?,
+    })
+}
+
+pub(crate) fn timeout_to_string(timeout: Option<Duration>) -> String {
+    match timeout {
+        None => GENA_TIMEOUT_INFINITE.to_string(),
+        Some(timeout) => format!("{}{}", GENA_TIMEOUT_PREFIX, timeout.as_secs()),
+    }
+}
+
+pub(crate) fn timeout_from_str(s: &str) -> Result<Option<Duration>, Error> {
+    if s.eq_ignore_ascii_case(GENA_TIMEOUT_INFINITE) {
+        return Ok(None);
+    }
+    // some publishers send the infinite value without the prefix.
+    if s.eq_ignore_ascii_case(&GENA_TIMEOUT_INFINITE[GENA_TIMEOUT_PREFIX.len()..]) {
+        warn!("timeout_from_str - accepting '{}' without prefix", s);
+        return Ok(None);
+    }
+    match s.get(..GENA_TIMEOUT_PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(GENA_TIMEOUT_PREFIX) => {
+            match s[GENA_TIMEOUT_PREFIX.len()..].trim().parse::<u64>() {
+                Ok(seconds) => Ok(Some(Duration::from_secs(seconds))),
+                Err(_) => invalid_header_value(GENA_HEADER_TIMEOUT, s).into(),
+            }
+        }
+        _ => invalid_header_value(GENA_HEADER_TIMEOUT, s).into(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_timeout_values() {
+        assert_eq!(
+            timeout_from_str("Second-1800").unwrap(),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(
+            timeout_from_str("second-300").unwrap(),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(timeout_from_str("Second-infinite").unwrap(), None);
+        assert_eq!(timeout_from_str("SECOND-INFINITE").unwrap(), None);
+        assert_eq!(timeout_from_str("infinite").unwrap(), None);
+        assert!(timeout_from_str("Second-forever").is_err());
+        assert!(timeout_from_str("Minute-5").is_err());
+        assert!(timeout_from_str("Second-").is_err());
+
+        assert_eq!(
+            timeout_to_string(Some(Duration::from_secs(1800))),
+            "Second-1800"
+        );
+        assert_eq!(timeout_to_string(None), "Second-infinite");
+        assert_eq!(timeout_from_str(&timeout_to_string(None)).unwrap(), None);
+    }
+
+    #[derive(Debug, Default)]
+    struct InfinitePublisher {
+        requested: std::sync::Mutex<Option<String>>,
+    }
+
+    impl HttpClient for InfinitePublisher {
+        fn send(&self, request: &ClientRequest) -> Result<ClientResponse, Error> {
+            *self.requested.lock().unwrap() =
+                request.header(GENA_HEADER_TIMEOUT).map(str::to_string);
+            Ok(ClientResponse::new(200, "OK")
+                .with_header(GENA_HEADER_SID, "uuid:subscription-1")
+                .with_header(GENA_HEADER_TIMEOUT, "Second-infinite"))
+        }
+    }
+
+    #[test]
+    fn test_infinite_subscription() {
+        let publisher = InfinitePublisher::default();
+        let event_url = URL::from_str("http://10.0.0.1/event").unwrap();
+        let callback = URL::from_str("http://10.0.0.2/callback").unwrap();
+
+        let subscription = subscribe_with(&event_url, &[callback], None, &publisher).unwrap();
+        assert_eq!(
+            publisher.requested.lock().unwrap().as_deref(),
+            Some("Second-infinite")
+        );
+        assert_eq!(subscription.timeout(), None);
+
+        let renewed =
+            renew_with(&subscription, Some(Duration::from_secs(300)), &publisher).unwrap();
+        assert_eq!(
+            publisher.requested.lock().unwrap().as_deref(),
+            Some("Second-300")
+        );
+        assert_eq!(renewed.timeout(), None);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_guard_unsubscribes_on_drop() {
+        use crate::emulation::{from_snapshot_with, Options as EmulatorOptions};
+        use crate::snapshot::DeviceSnapshot;
+        use std::time::UNIX_EPOCH;
+
+        let device = from_snapshot_with(
+            DeviceSnapshot {
+                location: URL::from_str("http://10.0.0.1:49152/description.xml").unwrap(),
+                captured: UNIX_EPOCH,
+                description: "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN></device></root>".to_string(),
+                services: Vec::new(),
+            },
+            EmulatorOptions {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let event_url = URL::from_str(&format!("http://{}/event", device.local_address())).unwrap();
+        let callback = URL::from_str("http://127.0.0.1:9/callback").unwrap();
+        let timeout = Some(Duration::from_secs(300));
+
+        let mut guard =
+            SubscriptionGuard::subscribe(&event_url, std::slice::from_ref(&callback), timeout)
+                .unwrap();
+        assert_eq!(device.subscriptions().len(), 1);
+        assert!(guard.renew(timeout).unwrap().is_some());
+        drop(guard);
+        assert!(device.subscriptions().is_empty());
+
+        let mut guard = SubscriptionGuard::subscribe(&event_url, &[callback], timeout).unwrap();
+        assert!(device.cancel_subscription(guard.subscription().sid()));
+        assert!(guard.renew(timeout).unwrap_err().is_unknown_subscription());
+    }
+}
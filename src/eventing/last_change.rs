@@ -0,0 +1,284 @@
+/*!
+This module provides the aggregation of state changes into the `LastChange` state variable used by
+the AV services, in particular `AVTransport` and `RenderingControl`. Rather than eventing each
+variable separately these services collect the changes to all variables, for all virtual
+instances, into a single XML document that is evented at most once per moderation period.
+
+```xml
+<Event xmlns="urn:schemas-upnp-org:metadata-1-0/AVT/">
+  <InstanceID val="0">
+    <TransportState val="PLAYING"/>
+    <CurrentTrack val="2"/>
+  </InstanceID>
+</Event>
+```
+
+# Example
+
+```rust
+use upnp_rs::eventing::last_change::{Aggregator, RCS_EVENT_NAMESPACE};
+
+let mut aggregator = Aggregator::new(RCS_EVENT_NAMESPACE);
+aggregator.set_with(0, "Volume", "24", &[("channel", "Master")]);
+aggregator.set(0, "Mute", "0");
+if let Some(last_change) = aggregator.poll() {
+    // publish `last_change` as the value of the `LastChange` variable
+    assert!(last_change.contains("<Volume channel=\"Master\" val=\"24\"/>"));
+}
+assert!(!aggregator.is_pending());
+```
+*/
+
+use crate::syntax::XML_ATTR_NAMESPACE;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::Writer;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Collects changes to state variables, by virtual instance, and renders them as a `LastChange`
+/// document no more often than the moderation period. A later change to a variable, with the
+/// same attributes, replaces any earlier change not yet taken.
+///
+#[derive(Clone, Debug)]
+pub struct Aggregator {
+    namespace: String,
+    moderation_period: Duration,
+    pending: BTreeMap<u32, Vec<Change>>,
+    last_taken: Option<Instant>,
+}
+
+///
+/// The name of the state variable carrying the aggregated changes.
+///
+pub const LAST_CHANGE: &str = "LastChange";
+
+///
+/// The namespace of the `LastChange` document of the `AVTransport` service.
+///
+pub const AVT_EVENT_NAMESPACE: &str = "urn:schemas-upnp-org:metadata-1-0/AVT/";
+
+///
+/// The namespace of the `LastChange` document of the `RenderingControl` service.
+///
+pub const RCS_EVENT_NAMESPACE: &str = "urn:schemas-upnp-org:metadata-1-0/RCS/";
+
+///
+/// The moderation period required by the AV service specifications, `LastChange` is evented at
+/// most five times a second.
+///
+pub const DEFAULT_MODERATION_PERIOD: Duration = Duration::from_millis(200);
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Aggregator {
+    ///
+    /// An aggregator for the `LastChange` document in `namespace`, using the default moderation
+    /// period.
+    ///
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            moderation_period: DEFAULT_MODERATION_PERIOD,
+            pending: Default::default(),
+            last_taken: None,
+        }
+    }
+
+    pub fn with_moderation_period(self, moderation_period: Duration) -> Self {
+        Self {
+            moderation_period,
+            ..self
+        }
+    }
+
+    ///
+    /// Record the change of variable `name`, of virtual instance `instance_id`, to `value`.
+    ///
+    pub fn set(&mut self, instance_id: u32, name: &str, value: &str) {
+        self.set_with(instance_id, name, value, &[])
+    }
+
+    ///
+    /// Record the change of variable `name` as for [`set`](Aggregator::set), where the variable
+    /// is qualified by additional attributes such as the `channel` of `Volume`.
+    ///
+    pub fn set_with(
+        &mut self,
+        instance_id: u32,
+        name: &str,
+        value: &str,
+        attributes: &[(&str, &str)],
+    ) {
+        let attributes: Vec<(String, String)> = attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let changes = self.pending.entry(instance_id).or_default();
+        match changes
+            .iter_mut()
+            .find(|change| change.name == name && change.attributes == attributes)
+        {
+            Some(change) => change.value = value.to_string(),
+            None => changes.push(Change {
+                name: name.to_string(),
+                value: value.to_string(),
+                attributes,
+            }),
+        }
+    }
+
+    ///
+    /// Returns `true` if there are changes not yet taken.
+    ///
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    ///
+    /// The time at which the pending changes may be taken, `None` if there are none.
+    ///
+    pub fn due_at(&self) -> Option<Instant> {
+        if self.is_pending() {
+            Some(match self.last_taken {
+                None => Instant::now(),
+                Some(last_taken) => last_taken + self.moderation_period,
+            })
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// If there are pending changes and the moderation period has elapsed since changes were
+    /// last taken, return the `LastChange` document and clear the pending changes.
+    ///
+    pub fn poll(&mut self) -> Option<String> {
+        match self.due_at() {
+            Some(due_at) if due_at <= Instant::now() => self.flush(),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Return the `LastChange` document for any pending changes, regardless of the moderation
+    /// period, and clear the pending changes.
+    ///
+    pub fn flush(&mut self) -> Option<String> {
+        if self.is_pending() {
+            let document = self.render();
+            self.pending.clear();
+            self.last_taken = Some(Instant::now());
+            Some(document)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// The `LastChange` document for the pending changes, without clearing them.
+    ///
+    pub fn render(&self) -> String {
+        let mut writer = Writer::new(Vec::new());
+        // writing to a vector cannot fail.
+        let _ = writer.write_event(Event::Start(
+            BytesStart::new(ELEM_EVENT)
+                .with_attributes([(XML_ATTR_NAMESPACE, self.namespace.as_str())]),
+        ));
+        for (instance_id, changes) in &self.pending {
+            let instance_id = instance_id.to_string();
+            let _ = writer.write_event(Event::Start(
+                BytesStart::new(ELEM_INSTANCE_ID)
+                    .with_attributes([(ATTR_VALUE, instance_id.as_str())]),
+            ));
+            for change in changes {
+                let _ = writer.write_event(Event::Empty(
+                    BytesStart::new(change.name.as_str())
+                        .with_attributes(
+                            change
+                                .attributes
+                                .iter()
+                                .map(|(name, value)| (name.as_str(), value.as_str())),
+                        )
+                        .with_attributes([(ATTR_VALUE, change.value.as_str())]),
+                ));
+            }
+            let _ = writer.write_event(Event::End(BytesEnd::new(ELEM_INSTANCE_ID)));
+        }
+        let _ = writer.write_event(Event::End(BytesEnd::new(ELEM_EVENT)));
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+struct Change {
+    name: String,
+    value: String,
+    attributes: Vec<(String, String)>,
+}
+
+const ELEM_EVENT: &str = "Event";
+
+const ELEM_INSTANCE_ID: &str = "InstanceID";
+
+const ATTR_VALUE: &str = "val";
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::xml::read::read_xml;
+
+    #[test]
+    fn test_render() {
+        let mut aggregator = Aggregator::new(AVT_EVENT_NAMESPACE);
+        aggregator.set(1, "TransportState", "STOPPED");
+        aggregator.set(0, "TransportState", "STOPPED");
+        aggregator.set(0, "CurrentTrackURI", "http://10.0.0.1/a?b=1&c=2");
+        aggregator.set(0, "TransportState", "PLAYING");
+        assert_eq!(
+            aggregator.render(),
+            "<Event xmlns=\"urn:schemas-upnp-org:metadata-1-0/AVT/\"><InstanceID val=\"0\"><TransportState val=\"PLAYING\"/><CurrentTrackURI val=\"http://10.0.0.1/a?b=1&amp;c=2\"/></InstanceID><InstanceID val=\"1\"><TransportState val=\"STOPPED\"/></InstanceID></Event>"
+        );
+
+        let mut aggregator = Aggregator::new(RCS_EVENT_NAMESPACE);
+        aggregator.set_with(0, "Volume", "24", &[("channel", "Master")]);
+        aggregator.set_with(0, "Volume", "10", &[("channel", "LF")]);
+        aggregator.set_with(0, "Volume", "30", &[("channel", "Master")]);
+        let document = read_xml(&aggregator.render()).unwrap();
+        let volumes = document.elements_by_tag_name("Volume");
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0].attribute("channel"), Some("Master"));
+        assert_eq!(volumes[0].attribute("val"), Some("30"));
+    }
+
+    #[test]
+    fn test_moderation() {
+        let mut aggregator =
+            Aggregator::new(AVT_EVENT_NAMESPACE).with_moderation_period(Duration::from_secs(60));
+        assert!(aggregator.poll().is_none());
+        aggregator.set(0, "TransportState", "PLAYING");
+        assert!(aggregator.poll().is_some());
+        assert!(!aggregator.is_pending());
+
+        aggregator.set(0, "TransportState", "STOPPED");
+        assert!(aggregator.poll().is_none());
+        assert!(aggregator.due_at().unwrap() > Instant::now());
+        assert!(aggregator.is_pending());
+        assert!(aggregator.flush().unwrap().contains("STOPPED"));
+        assert!(aggregator.due_at().is_none());
+    }
+}
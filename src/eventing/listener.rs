@@ -0,0 +1,563 @@
+/*!
+This module provides the control point's event listener, a small HTTP server that accepts the
+`NOTIFY` requests sent by publishers to subscription callback URLs. Received events are delivered
+as iterators rather than callbacks, so that applications can consume them from their own
+processing loops.
+
+Each service is given its own callback path, and therefore its own stream of events, created with
+[`EventListener::events_for`]. Each stream has a bounded buffer; when a stream's buffer is full
+requests for that stream are not acknowledged until the application consumes an event, which in
+turn causes publishers to wait. Each connection is handled on its own thread, so a full stream does
+not delay events for other services; the number of connections handled at once is bounded, and
+further connections are answered with `503 Service Unavailable` until one completes.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::eventing::client::subscribe;
+use upnp_rs::eventing::listener::{EventListener, Options};
+use std::str::FromStr;
+
+let listener = EventListener::bind(Options {
+    address: "10.0.0.1:8080".parse().unwrap(),
+    ..Default::default()
+})
+.unwrap();
+let events = listener.events_for("av-transport");
+
+let event_url = URL::from_str("http://10.0.0.2:1400/MediaRenderer/AVTransport/Event").unwrap();
+let _ = subscribe(&event_url, &[listener.callback_url("av-transport")], None).unwrap();
+
+for event in events.take(10) {
+    println!("{} #{}: {}", event.sid, event.seq, event.properties);
+}
+```
+*/
+
+use crate::common::http::{read_request, write_response, HttpRequest, DEFAULT_KEEP_ALIVE_TIMEOUT};
+use crate::common::uri::URL;
+use crate::error::Error;
+use crate::eventing::PropertySet;
+use crate::syntax::{
+    GENA_HEADER_SEQ, GENA_HEADER_SID, GENA_NTS_PROPERTY_CHANGE, GENA_NT_EVENT, HTTP_HEADER_NT,
+    HTTP_HEADER_NTS, HTTP_METHOD_NOTIFY,
+};
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
+use tracing::{error, info, trace, warn};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the set of values used to configure the listener.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The local address the listener will bind to, this should be an address reachable by
+    /// publishers as it is used to construct callback URLs. Default: `0.0.0.0:0`.
+    pub address: SocketAddr,
+    /// The number of events buffered for each stream before the listener waits for the
+    /// application to consume them. Default: `64`.
+    pub buffer_size: usize,
+    /// The number of connections handled at once, further connections are rejected until one
+    /// completes. Default: `16`.
+    pub max_connections: usize,
+}
+
+///
+/// An event received from a publisher.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The subscription identifier the event was sent for.
+    pub sid: String,
+    /// The event key, `0` for the initial event sent after subscribing.
+    pub seq: u32,
+    /// The callback path, without the leading `/`, the event was sent to.
+    pub service: String,
+    /// The evented state variables and their new values.
+    pub properties: PropertySet,
+}
+
+///
+/// A bounded stream of events for a single service, or for all services without a stream of their
+/// own. Iteration blocks until an event is received, and ends when the listener is stopped.
+///
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: Receiver<Event>,
+}
+
+///
+/// A running event listener, the listener is stopped when this value is dropped.
+///
+#[derive(Debug)]
+pub struct EventListener {
+    state: Arc<ListenerState>,
+    local_address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            address: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            buffer_size: 64,
+            max_connections: 16,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Iterator for EventStream {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl EventStream {
+    ///
+    /// Return the next event if one has already been received, without blocking.
+    ///
+    pub fn try_next(&self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+
+    ///
+    /// Wait up to `timeout` for the next event, returning `None` if no event was received or the
+    /// listener has been stopped.
+    ///
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Event> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl EventListener {
+    ///
+    /// Bind the listener to the address in `options` and start accepting event messages.
+    ///
+    pub fn bind(options: Options) -> Result<Self, Error> {
+        let listener = TcpListener::bind(options.address)?;
+        listener.set_nonblocking(true)?;
+        let local_address = listener.local_addr()?;
+        info!("bind - listening for events on {}", local_address);
+
+        let state = Arc::new(ListenerState {
+            buffer_size: options.buffer_size.max(1),
+            max_connections: options.max_connections.max(1),
+            routes: Default::default(),
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let state = state.clone();
+            let stop = stop.clone();
+            spawn(move || listen_loop(listener, state, stop))
+        };
+        Ok(Self {
+            state,
+            local_address,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    ///
+    /// The address the listener is bound to.
+    ///
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    ///
+    /// The callback URL to provide when subscribing to `service`, events sent to this URL are
+    /// delivered to the stream returned by `events_for(service)`.
+    ///
+    pub fn callback_url(&self, service: &str) -> URL {
+        URL::from_str(&format!(
+            "http://{}/{}",
+            self.local_address,
+            service.trim_start_matches('/')
+        ))
+        .unwrap()
+    }
+
+    ///
+    /// Return a stream of the events sent to the callback URL for `service`, replacing any stream
+    /// previously returned for the same service.
+    ///
+    pub fn events_for(&self, service: &str) -> EventStream {
+        self.add_route(service.trim_start_matches('/'))
+    }
+
+    ///
+    /// Return a stream of the events for all services that do not have a stream of their own,
+    /// replacing any stream previously returned by this method. Events sent to services without a
+    /// stream are rejected if this method has not been called.
+    ///
+    pub fn events(&self) -> EventStream {
+        self.add_route(ANY_SERVICE)
+    }
+
+    ///
+    /// Stop the listener, ending all event streams.
+    ///
+    pub fn stop(mut self) {
+        self.stop_thread();
+    }
+
+    fn add_route(&self, service: &str) -> EventStream {
+        let (sender, receiver) = sync_channel(self.state.buffer_size);
+        let _ = self
+            .state
+            .routes
+            .lock()
+            .unwrap()
+            .insert(service.to_string(), sender);
+        EventStream { receiver }
+    }
+
+    fn stop_thread(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.state.routes.lock().unwrap().clear();
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct ListenerState {
+    buffer_size: usize,
+    max_connections: usize,
+    routes: Mutex<HashMap<String, SyncSender<Event>>>,
+}
+
+const ANY_SERVICE: &str = "*";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const REJECT_READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn listen_loop(listener: TcpListener, state: Arc<ListenerState>, stop: Arc<AtomicBool>) {
+    let mut handlers: Vec<JoinHandle<()>> = Vec::new();
+    while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, from)) => {
+                trace!("listen_loop - accepted connection from {:?}", from);
+                handlers.retain(|handler| !handler.is_finished());
+                if handlers.len() >= state.max_connections {
+                    warn!(
+                        "listen_loop - rejecting connection from {:?}, {} connections in progress",
+                        from,
+                        handlers.len()
+                    );
+                    if let Err(e) = reject_connection(stream) {
+                        error!("listen_loop - error rejecting request: {:?}", e);
+                    }
+                    continue;
+                }
+                let state = state.clone();
+                let stop = stop.clone();
+                handlers.push(spawn(move || {
+                    if let Err(e) = handle_connection(stream, &state, &stop) {
+                        error!("listen_loop - error handling request: {:?}", e);
+                    }
+                }));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => sleep(POLL_INTERVAL),
+            Err(e) => {
+                error!("listen_loop - accept returned error: {:?}", e);
+                break;
+            }
+        }
+    }
+    for handler in handlers {
+        let _ = handler.join();
+    }
+}
+
+fn reject_connection(stream: TcpStream) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    // the request is read, briefly, so that closing the connection does not reset it before
+    // the client has read the response.
+    stream.set_read_timeout(Some(REJECT_READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let _ = read_request(&mut reader);
+    write_response(reader.get_mut(), "503 Service Unavailable", &[], b"", false)
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    state: &ListenerState,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_request(&mut reader)? {
+        let status = handle_notify(&request, state, stop);
+        trace!(
+            "handle_connection - {} {} => {}",
+            request.method,
+            request.path,
+            status
+        );
+        let keep_alive = request.keep_alive() && !stop.load(Ordering::SeqCst);
+        write_response(reader.get_mut(), status, &[], b"", keep_alive)?;
+        if !keep_alive {
+            break;
+        }
+        reader
+            .get_ref()
+            .set_read_timeout(Some(DEFAULT_KEEP_ALIVE_TIMEOUT))?;
+    }
+    Ok(())
+}
+
+fn handle_notify(request: &HttpRequest, state: &ListenerState, stop: &AtomicBool) -> &'static str {
+    if request.method != HTTP_METHOD_NOTIFY {
+        return "405 Method Not Allowed";
+    }
+    match (
+        request.header(HTTP_HEADER_NT),
+        request.header(HTTP_HEADER_NTS),
+        request.header(GENA_HEADER_SID),
+    ) {
+        (None, _, _) | (_, None, _) => "400 Bad Request",
+        (Some(nt), Some(nts), Some(sid))
+            if nt == GENA_NT_EVENT && nts == GENA_NTS_PROPERTY_CHANGE =>
+        {
+            let properties = PropertySet::from_str(&String::from_utf8_lossy(&request.body));
+            let seq = request
+                .header(GENA_HEADER_SEQ)
+                .map(|seq| seq.parse::<u32>());
+            match (properties, seq) {
+                (Ok(properties), Some(Ok(seq))) => {
+                    let event = Event {
+                        sid: sid.to_string(),
+                        seq,
+                        service: request.path.trim_start_matches('/').to_string(),
+                        properties,
+                    };
+                    if deliver(state, event, stop) {
+                        "200 OK"
+                    } else {
+                        "412 Precondition Failed"
+                    }
+                }
+                _ => "400 Bad Request",
+            }
+        }
+        _ => "412 Precondition Failed",
+    }
+}
+
+fn deliver(state: &ListenerState, event: Event, stop: &AtomicBool) -> bool {
+    let mut event = event;
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let sender = {
+            let routes = state.routes.lock().unwrap();
+            match routes
+                .get(&event.service)
+                .or_else(|| routes.get(ANY_SERVICE))
+            {
+                Some(sender) => sender.clone(),
+                None => {
+                    warn!("deliver - no stream for service {:?}", event.service);
+                    return false;
+                }
+            }
+        };
+        match sender.try_send(event) {
+            Ok(()) => return true,
+            Err(TrySendError::Full(returned)) => {
+                trace!("deliver - stream for {:?} is full", returned.service);
+                event = returned;
+                sleep(POLL_INTERVAL);
+            }
+            Err(TrySendError::Disconnected(returned)) => {
+                info!("deliver - stream for {:?} was dropped", returned.service);
+                let mut routes = state.routes.lock().unwrap();
+                if routes.get(&returned.service).is_some() {
+                    let _ = routes.remove(&returned.service);
+                } else {
+                    let _ = routes.remove(ANY_SERVICE);
+                }
+                event = returned;
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::http::{default_client, ClientRequest};
+
+    fn notify(url: &URL, sid: &str, seq: u32, properties: &PropertySet) -> u16 {
+        default_client()
+            .send(
+                &ClientRequest::new(HTTP_METHOD_NOTIFY, &url.to_string())
+                    .with_header(HTTP_HEADER_NT, GENA_NT_EVENT)
+                    .with_header(HTTP_HEADER_NTS, GENA_NTS_PROPERTY_CHANGE)
+                    .with_header(GENA_HEADER_SID, sid)
+                    .with_header(GENA_HEADER_SEQ, &seq.to_string())
+                    .with_body(properties.to_string().into_bytes()),
+            )
+            .unwrap()
+            .status
+    }
+
+    fn listener(buffer_size: usize) -> EventListener {
+        EventListener::bind(Options {
+            address: "127.0.0.1:0".parse().unwrap(),
+            buffer_size,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_per_service_streams() {
+        let listener = listener(8);
+        let volume = PropertySet::new().with_property("Volume", "10");
+        let state = PropertySet::new().with_property("TransportState", "PLAYING");
+
+        let rendering = listener.events_for("rendering");
+        let callback = listener.callback_url("rendering");
+        assert_eq!(notify(&callback, "uuid:1", 0, &volume), 200);
+
+        let transport_url = listener.callback_url("transport");
+        assert_eq!(notify(&transport_url, "uuid:2", 0, &state), 412);
+        let others = listener.events();
+        assert_eq!(notify(&transport_url, "uuid:2", 1, &state), 200);
+
+        let event = rendering.next_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(event.sid, "uuid:1");
+        assert_eq!(event.service, "rendering");
+        assert_eq!(event.properties.get("Volume"), Some("10"));
+        assert!(rendering.try_next().is_none());
+
+        let event = others.next_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(event.seq, 1);
+        assert_eq!(event.service, "transport");
+
+        listener.stop();
+        assert_eq!(others.count(), 0);
+    }
+
+    #[test]
+    fn test_bounded_buffer() {
+        let listener = listener(1);
+        let events = listener.events();
+        let callback = listener.callback_url("any");
+        let properties = PropertySet::new().with_property("Mute", "1");
+
+        assert_eq!(notify(&callback, "uuid:1", 0, &properties), 200);
+        let blocked = {
+            let callback = callback.clone();
+            let properties = properties.clone();
+            spawn(move || notify(&callback, "uuid:1", 1, &properties))
+        };
+        sleep(Duration::from_millis(200));
+        assert!(!blocked.is_finished());
+
+        assert_eq!(events.next_timeout(Duration::from_secs(1)).unwrap().seq, 0);
+        assert_eq!(blocked.join().unwrap(), 200);
+        assert_eq!(events.next_timeout(Duration::from_secs(1)).unwrap().seq, 1);
+    }
+
+    #[test]
+    fn test_full_stream_does_not_block_others() {
+        let listener = listener(1);
+        let full = listener.events_for("full");
+        let other = listener.events_for("other");
+        let full_url = listener.callback_url("full");
+        let properties = PropertySet::new().with_property("Mute", "1");
+
+        assert_eq!(notify(&full_url, "uuid:1", 0, &properties), 200);
+        let blocked = {
+            let properties = properties.clone();
+            spawn(move || notify(&full_url, "uuid:1", 1, &properties))
+        };
+        sleep(Duration::from_millis(200));
+        assert!(!blocked.is_finished());
+
+        let other_url = listener.callback_url("other");
+        assert_eq!(notify(&other_url, "uuid:2", 0, &properties), 200);
+        assert_eq!(
+            other.next_timeout(Duration::from_secs(1)).unwrap().sid,
+            "uuid:2"
+        );
+        assert!(!blocked.is_finished());
+
+        assert_eq!(full.next_timeout(Duration::from_secs(1)).unwrap().seq, 0);
+        assert_eq!(blocked.join().unwrap(), 200);
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let listener = EventListener::bind(Options {
+            address: "127.0.0.1:0".parse().unwrap(),
+            max_connections: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let events = listener.events();
+        let callback = listener.callback_url("any");
+        let properties = PropertySet::new().with_property("Mute", "1");
+
+        let idle = TcpStream::connect(listener.local_address()).unwrap();
+        sleep(Duration::from_millis(200));
+        assert_eq!(notify(&callback, "uuid:1", 0, &properties), 503);
+
+        drop(idle);
+        sleep(Duration::from_millis(200));
+        assert_eq!(notify(&callback, "uuid:1", 0, &properties), 200);
+        assert_eq!(events.next_timeout(Duration::from_secs(1)).unwrap().seq, 0);
+    }
+}
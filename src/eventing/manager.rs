@@ -0,0 +1,500 @@
+/*!
+This module provides a `SubscriptionManager` that owns a set of GENA subscriptions and keeps them
+alive; each subscription is renewed when a configurable fraction of its granted timeout has
+elapsed, failed renewals are retried with an increasing delay, and subscriptions that cannot be
+renewed are reported as lost. All remaining subscriptions are cancelled when the manager is shut
+down, or dropped.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::eventing::manager::{Options, SubscriptionEvent, SubscriptionManager};
+use std::str::FromStr;
+
+let manager = SubscriptionManager::new(Options::default()).unwrap();
+let event_url = URL::from_str("http://10.0.0.2:1400/MediaRenderer/AVTransport/Event").unwrap();
+let callback = URL::from_str("http://10.0.0.1:8080/events").unwrap();
+let subscription = manager.subscribe(&event_url, &[callback]).unwrap();
+
+for event in manager.events() {
+    if let SubscriptionEvent::SubscriptionLost { subscription, reason } = event {
+        println!("lost subscription {}: {}", subscription.sid(), reason);
+    }
+}
+```
+*/
+
+use crate::common::http::HttpClient;
+use crate::common::uri::URL;
+use crate::error::{invalid_field_value, unknown_subscription, Error, EventingError};
+use crate::eventing::client::{renew_with, subscribe_with, unsubscribe_with, Subscription};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{spawn, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the set of values used to manage subscriptions.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The subscription duration requested from the publisher, `None` requests an infinite
+    /// subscription. Default: `1800` seconds.
+    pub timeout: Option<Duration>,
+    /// The fraction of the granted timeout after which a subscription is renewed, this must be
+    /// greater than `0.0` and less than `1.0`. Default: `0.5`.
+    pub renew_fraction: f64,
+    /// The delay before the first retry of a failed renewal, this is doubled on each subsequent
+    /// failure. Default: `5` seconds.
+    pub retry_delay: Duration,
+    /// The maximum delay between retries of a failed renewal. Default: `60` seconds.
+    pub max_retry_delay: Duration,
+}
+
+///
+/// Events reported by the manager as subscriptions are renewed or lost.
+///
+#[derive(Clone, Debug)]
+pub enum SubscriptionEvent {
+    /// The subscription was renewed, with the newly granted timeout.
+    Renewed(Subscription),
+    /// The subscription could not be renewed before it expired, or the publisher no longer
+    /// recognizes it, and it has been removed from the manager.
+    SubscriptionLost {
+        subscription: Subscription,
+        reason: String,
+    },
+}
+
+///
+/// Owns a set of subscriptions, renewing each in a background thread.
+///
+#[derive(Debug)]
+pub struct SubscriptionManager {
+    shared: Arc<Shared>,
+    events: Receiver<SubscriptionEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            timeout: Some(Duration::from_secs(1800)),
+            renew_fraction: 0.5,
+            retry_delay: Duration::from_secs(5),
+            max_retry_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Options {
+    ///
+    /// Check that the values are within the permitted ranges.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(self.renew_fraction > 0.0 && self.renew_fraction < 1.0) {
+            error!(
+                "validate - renew_fraction must be between 0.0..1.0 ({})",
+                self.renew_fraction
+            );
+            return invalid_field_value("renew_fraction", self.renew_fraction.to_string()).into();
+        }
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl SubscriptionManager {
+    #[cfg(feature = "net")]
+    pub fn new(options: Options) -> Result<Self, Error> {
+        Self::with_client(options, default_client())
+    }
+
+    ///
+    /// Create a manager that uses `client` for all requests; it is an error if `options` are not
+    /// valid.
+    ///
+    pub fn with_client(options: Options, client: Arc<dyn HttpClient>) -> Result<Self, Error> {
+        options.validate()?;
+        let (sender, events) = channel();
+        let shared = Arc::new(Shared {
+            options,
+            client,
+            state: Mutex::new(State {
+                entries: Vec::new(),
+                shutdown: false,
+            }),
+            wakeup: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            spawn(move || renewal_loop(shared, sender))
+        };
+        Ok(Self {
+            shared,
+            events,
+            thread: Some(thread),
+        })
+    }
+
+    ///
+    /// Subscribe to the events published at `event_url` and manage the new subscription.
+    ///
+    pub fn subscribe(&self, event_url: &URL, callbacks: &[URL]) -> Result<Subscription, Error> {
+        let subscription = subscribe_with(
+            event_url,
+            callbacks,
+            self.shared.options.timeout,
+            self.shared.client.as_ref(),
+        )?;
+        let entry = Entry::new(subscription.clone(), &self.shared.options);
+        self.shared.state.lock().unwrap().entries.push(entry);
+        self.shared.wakeup.notify_all();
+        Ok(subscription)
+    }
+
+    ///
+    /// Cancel the subscription `sid` and stop managing it.
+    ///
+    pub fn unsubscribe(&self, sid: &str) -> Result<(), Error> {
+        let subscription = {
+            let mut state = self.shared.state.lock().unwrap();
+            match state
+                .entries
+                .iter()
+                .position(|e| e.subscription.sid() == sid)
+            {
+                Some(index) => state.entries.remove(index).subscription,
+                None => return unknown_subscription(sid).into(),
+            }
+        };
+        unsubscribe_with(&subscription, self.shared.client.as_ref())
+    }
+
+    ///
+    /// The current state of all managed subscriptions.
+    ///
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|entry| entry.subscription.clone())
+            .collect()
+    }
+
+    ///
+    /// The receiver for renewal and loss events; iterating over this blocks until the manager is
+    /// shut down.
+    ///
+    pub fn events(&self) -> &Receiver<SubscriptionEvent> {
+        &self.events
+    }
+
+    ///
+    /// Stop renewing subscriptions and cancel all those remaining.
+    ///
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.shared.state.lock().unwrap().shutdown = true;
+            self.shared.wakeup.notify_all();
+            let _ = thread.join();
+            let entries: Vec<Entry> = self
+                .shared
+                .state
+                .lock()
+                .unwrap()
+                .entries
+                .drain(..)
+                .collect();
+            for entry in entries {
+                if let Err(e) = unsubscribe_with(&entry.subscription, self.shared.client.as_ref()) {
+                    warn!(
+                        "stop - could not cancel subscription {}: {}",
+                        entry.subscription.sid(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SubscriptionManager {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Entry {
+    fn new(subscription: Subscription, options: &Options) -> Self {
+        let now = Instant::now();
+        Self {
+            next_renewal: subscription.timeout().map(|timeout| {
+                now + Duration::from_secs_f64(timeout.as_secs_f64() * options.renew_fraction)
+            }),
+            expires: subscription.timeout().map(|timeout| now + timeout),
+            retry_delay: None,
+            subscription,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct Shared {
+    options: Options,
+    client: Arc<dyn HttpClient>,
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    entries: Vec<Entry>,
+    shutdown: bool,
+}
+
+#[derive(Debug)]
+struct Entry {
+    subscription: Subscription,
+    next_renewal: Option<Instant>,
+    expires: Option<Instant>,
+    retry_delay: Option<Duration>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn renewal_loop(shared: Arc<Shared>, sender: Sender<SubscriptionEvent>) {
+    info!("renewal_loop - starting");
+    let mut state = shared.state.lock().unwrap();
+    while !state.shutdown {
+        let now = Instant::now();
+        let due: Vec<Subscription> = state
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry.next_renewal, Some(next) if next <= now))
+            .map(|entry| entry.subscription.clone())
+            .collect();
+
+        if due.is_empty() {
+            let next = state.entries.iter().filter_map(|e| e.next_renewal).min();
+            state = match next {
+                None => shared.wakeup.wait(state).unwrap(),
+                Some(next) => {
+                    shared
+                        .wakeup
+                        .wait_timeout(state, next.saturating_duration_since(now))
+                        .unwrap()
+                        .0
+                }
+            };
+            continue;
+        }
+
+        drop(state);
+        let results: Vec<(Subscription, Result<Subscription, Error>)> = due
+            .into_iter()
+            .map(|subscription| {
+                let result = renew_with(
+                    &subscription,
+                    shared.options.timeout,
+                    shared.client.as_ref(),
+                );
+                (subscription, result)
+            })
+            .collect();
+        state = shared.state.lock().unwrap();
+
+        for (subscription, result) in results {
+            let index = match state
+                .entries
+                .iter()
+                .position(|e| e.subscription.sid() == subscription.sid())
+            {
+                Some(index) => index,
+                None => continue, // unsubscribed while renewing.
+            };
+            match result {
+                Ok(renewed) => {
+                    info!("renewal_loop - renewed {}", renewed.sid());
+                    state.entries[index] = Entry::new(renewed.clone(), &shared.options);
+                    let _ = sender.send(SubscriptionEvent::Renewed(renewed));
+                }
+                Err(e) => {
+                    let now = Instant::now();
+                    let entry = &mut state.entries[index];
+                    let delay = entry
+                        .retry_delay
+                        .map(|delay| (delay * 2).min(shared.options.max_retry_delay))
+                        .unwrap_or(shared.options.retry_delay);
+                    let expired = matches!(entry.expires, Some(expires) if now + delay >= expires);
+                    if expired
+                        || matches!(
+                            e,
+                            Error::Eventing(EventingError::UnknownSubscription { .. })
+                        )
+                    {
+                        error!(
+                            "renewal_loop - lost subscription {}: {}",
+                            subscription.sid(),
+                            e
+                        );
+                        let entry = state.entries.remove(index);
+                        let _ = sender.send(SubscriptionEvent::SubscriptionLost {
+                            subscription: entry.subscription,
+                            reason: e.to_string(),
+                        });
+                    } else {
+                        warn!(
+                            "renewal_loop - renewal of {} failed, retrying in {:?}: {}",
+                            subscription.sid(),
+                            delay,
+                            e
+                        );
+                        entry.retry_delay = Some(delay);
+                        entry.next_renewal = Some(now + delay);
+                    }
+                }
+            }
+        }
+    }
+    info!("renewal_loop - stopping");
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use crate::emulation::{from_snapshot_with, EmulatedDevice, Options as EmulatorOptions};
+    use crate::snapshot::DeviceSnapshot;
+    use std::str::FromStr;
+    use std::time::UNIX_EPOCH;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN></device></root>";
+
+    fn emulated_device() -> EmulatedDevice {
+        from_snapshot_with(
+            DeviceSnapshot {
+                location: URL::from_str("http://10.0.0.1:49152/description.xml").unwrap(),
+                captured: UNIX_EPOCH,
+                description: DESCRIPTION.to_string(),
+                services: Vec::new(),
+            },
+            EmulatorOptions {
+                answer_search: false,
+                subscription_timeout: Duration::from_secs(1),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_invalid_renew_fraction() {
+        for renew_fraction in [-0.5, 0.0, 1.0, 1.5, f64::NAN] {
+            assert!(SubscriptionManager::new(Options {
+                renew_fraction,
+                ..Default::default()
+            })
+            .is_err());
+        }
+        assert!(Options {
+            renew_fraction: 0.9,
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_renew_and_lose() {
+        let device = emulated_device();
+        let event_url = URL::from_str(&format!("http://{}/event", device.local_address())).unwrap();
+        let callback = URL::from_str("http://127.0.0.1:9/callback").unwrap();
+
+        let manager = SubscriptionManager::new(Options {
+            retry_delay: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .unwrap();
+        let subscription = manager.subscribe(&event_url, &[callback]).unwrap();
+        assert_eq!(subscription.timeout(), Some(Duration::from_secs(1)));
+        assert_eq!(device.subscriptions(), vec![subscription.sid().to_string()]);
+
+        match manager
+            .events()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+        {
+            SubscriptionEvent::Renewed(renewed) => assert_eq!(renewed.sid(), subscription.sid()),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        assert!(device.cancel_subscription(subscription.sid()));
+        match manager
+            .events()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+        {
+            SubscriptionEvent::SubscriptionLost {
+                subscription: lost, ..
+            } => {
+                assert_eq!(lost.sid(), subscription.sid())
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(manager.subscriptions().is_empty());
+    }
+
+    #[test]
+    fn test_shutdown_unsubscribes() {
+        let device = emulated_device();
+        let event_url = URL::from_str(&format!("http://{}/event", device.local_address())).unwrap();
+        let callback = URL::from_str("http://127.0.0.1:9/callback").unwrap();
+
+        let manager = SubscriptionManager::new(Default::default()).unwrap();
+        let first = manager
+            .subscribe(&event_url, std::slice::from_ref(&callback))
+            .unwrap();
+        let _ = manager.subscribe(&event_url, &[callback]).unwrap();
+        assert_eq!(device.subscriptions().len(), 2);
+
+        manager.unsubscribe(first.sid()).unwrap();
+        assert!(manager.unsubscribe(first.sid()).is_err());
+        assert_eq!(device.subscriptions().len(), 1);
+
+        manager.shutdown();
+        assert!(device.subscriptions().is_empty());
+    }
+}
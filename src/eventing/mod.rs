@@ -1,28 +1,263 @@
 /*!
 This module implements the UPnP device and service eventing capabilities via the _General Event
 Notification Architecture_ (GENA) protocol.
+
+An event message is sent by a service, to each subscriber's callback URL, as a `NOTIFY` request
+with a body containing the names and new values of the evented state variables.
+
+```http
+NOTIFY delivery path HTTP/1.1
+HOST: delivery host:delivery port
+CONTENT-TYPE: text/xml; charset="utf-8"
+NT: upnp:event
+NTS: upnp:propchange
+SID: uuid:subscription-UUID
+SEQ: event key
+
+<?xml version="1.0"?>
+<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">
+  <e:property>
+    <variableName>new value</variableName>
+  </e:property>
+  other variable names and values (if any) go here
+</e:propertyset>
+```
+
+# Example
+
+```rust
+use upnp_rs::eventing::PropertySet;
+use std::str::FromStr;
+
+let properties = PropertySet::new()
+    .with_property("Volume", "10")
+    .with_property("Mute", "0");
+let parsed = PropertySet::from_str(&properties.to_string()).unwrap();
+assert_eq!(parsed.get("Volume"), Some("10"));
+```
 */
 
+use crate::common::xml::read::{read_xml, Element as ReadElement};
+use crate::common::xml::write::{start_element_with, RootWritable, Writable};
+use crate::error::{invalid_field_value, xml_error, Error};
+use crate::syntax::{
+    GENA_ELEM_PROPERTY, GENA_ELEM_PROPERTY_SET, GENA_NS_EVENT, GENA_PREFIX_EVENT,
+    XML_ATTR_NAMESPACE,
+};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The body of an event message, a list of state variable names and their new values in the order
+/// they appear in the message. Values are kept as strings, so that the XML content of variables
+/// such as `LastChange` is left for the caller to parse.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PropertySet {
+    properties: Vec<(String, String)>,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Write the property set, as a complete XML document, to `writer`.
+///
+pub fn to_writer<T: Write>(property_set: &PropertySet, writer: T) -> Result<T, Error> {
+    property_set.write_root(writer)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl PropertySet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_property(mut self, name: &str, value: &str) -> Self {
+        self.add_property(name, value);
+        self
+    }
+
+    pub fn add_property(&mut self, name: &str, value: &str) {
+        self.properties.push((name.to_string(), value.to_string()));
+    }
+
+    ///
+    /// Return the value of the named variable; if a variable appears more than once the last
+    /// value is returned.
+    ///
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn properties(&self) -> &[(String, String)] {
+        &self.properties
+    }
+
+    pub fn into_properties(self) -> Vec<(String, String)> {
+        self.properties
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+}
+
+impl FromStr for PropertySet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let root = read_xml(s)?;
+        if root.local_name() != local_name(GENA_ELEM_PROPERTY_SET) {
+            return invalid_field_value(GENA_ELEM_PROPERTY_SET, root.name).into();
+        }
+        let mut properties = Vec::new();
+        for property in root.children_named(local_name(GENA_ELEM_PROPERTY)) {
+            for variable in &property.children {
+                properties.push((variable.local_name().to_string(), variable_value(variable)));
+            }
+        }
+        Ok(Self { properties })
+    }
+}
+
+impl<T: Write> RootWritable<T> for PropertySet {}
+
+impl<T: Write> Writable<T> for PropertySet {
+    fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
+        let xmlns = format!("{}:{}", XML_ATTR_NAMESPACE, GENA_PREFIX_EVENT);
+        let property_set = start_element_with(
+            writer,
+            GENA_ELEM_PROPERTY_SET,
+            vec![(xmlns.as_str(), GENA_NS_EVENT)],
+        )
+        .map_err(xml_error)?;
+        for (name, value) in &self.properties {
+            let property =
+                start_element_with(writer, GENA_ELEM_PROPERTY, Vec::new()).map_err(xml_error)?;
+            writer
+                .write_event(Event::Start(BytesStart::new(name.as_str())))
+                .map_err(xml_error)?;
+            writer
+                .write_event(Event::Text(BytesText::new(value)))
+                .map_err(xml_error)?;
+            writer
+                .write_event(Event::End(BytesEnd::new(name.as_str())))
+                .map_err(xml_error)?;
+            property.end(writer).map_err(xml_error)?;
+        }
+        property_set.end(writer).map_err(xml_error)
+    }
+}
+
+impl Display for PropertySet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        let written = self.write_root(Vec::new()).map_err(|_| FmtError)?;
+        write!(f, "{}", String::from_utf8(written).map_err(|_| FmtError)?)
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
-// Private Types
+// Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn local_name(name: &str) -> &str {
+    name.split_once(':').map(|(_, local)| local).unwrap_or(name)
+}
+
+///
+/// Most services escape XML content in a variable's value, some embed it directly; in the latter
+/// case the embedded elements are written back out so the value is the same either way.
+///
+fn variable_value(variable: &ReadElement) -> String {
+    if variable.children.is_empty() {
+        variable.text.clone()
+    } else {
+        variable
+            .children
+            .iter()
+            .map(|child| child.to_string())
+            .collect()
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
-// Private Functions
+// Modules
 // ------------------------------------------------------------------------------------------------
 
+pub mod client;
+
+pub mod last_change;
+
+#[cfg(feature = "net")]
+pub mod listener;
+
+pub mod manager;
+
+pub mod multicast;
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const LAST_CHANGE: &str = "<Event xmlns=\"urn:schemas-upnp-org:metadata-1-0/RCS/\"><InstanceID val=\"0\"><Volume channel=\"Master\" val=\"24\"></Volume></InstanceID></Event>";
+
+    #[test]
+    fn test_round_trip() {
+        let properties = PropertySet::new()
+            .with_property("LastChange", LAST_CHANGE)
+            .with_property("PresetNameList", "FactoryDefaults");
+        let xml = properties.to_string();
+        assert_eq!(
+            xml,
+            format!(
+                "<?xml version=\"1.0\"?><e:propertyset xmlns:e=\"urn:schemas-upnp-org:event-1-0\"><e:property><LastChange>{}</LastChange></e:property><e:property><PresetNameList>FactoryDefaults</PresetNameList></e:property></e:propertyset>",
+                LAST_CHANGE
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+                    .replace('"', "&quot;")
+            )
+        );
+        assert_eq!(PropertySet::from_str(&xml).unwrap(), properties);
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        const BODY: &str = "<?xml version=\"1.0\"?><e:propertyset xmlns:e=\"urn:schemas-upnp-org:event-1-0\"><e:property><Status>1</Status><Target>0</Target></e:property><e:property><LastChange><Event xmlns=\"urn:schemas-upnp-org:metadata-1-0/RCS/\"><InstanceID val=\"0\"><Volume channel=\"Master\" val=\"24\"/></InstanceID></Event></LastChange></e:property><e:property><Status>0</Status></e:property></e:propertyset>";
+        let properties = PropertySet::from_str(BODY).unwrap();
+        assert_eq!(properties.len(), 4);
+        assert_eq!(
+            properties.properties()[0],
+            ("Status".to_string(), "1".to_string())
+        );
+        assert_eq!(properties.get("Status"), Some("0"));
+        assert_eq!(properties.get("Target"), Some("0"));
+        assert_eq!(properties.get("LastChange"), Some(LAST_CHANGE));
+
+        assert!(PropertySet::from_str("<propertylist/>").is_err());
+    }
+}
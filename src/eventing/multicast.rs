@@ -0,0 +1,556 @@
+/*!
+This module implements multicast eventing, added in UDA 1.1. Rather than sending an event to each
+subscriber, a service multicasts changes to those state variables marked `multicast="yes"` in its
+service description to a well-known group; any control point may listen without subscribing.
+
+```http
+NOTIFY * HTTP/1.0
+HOST: 239.255.255.246:7900
+CONTENT-TYPE: text/xml; charset="utf-8"
+USN: uuid:device-UUID::urn:domain-name:service:serviceType:v
+SVCID: serviceID
+NT: upnp:event
+NTS: upnp:propchange
+SEQ: event key
+LVL: event importance
+BOOTID.UPNP.ORG: boot identifier
+CONTENT-LENGTH: bytes in body
+
+<?xml version="1.0"?>
+<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">
+  <e:property>
+    <variableName>new value</variableName>
+  </e:property>
+</e:propertyset>
+```
+
+The event key is maintained by the publisher for each service identifier, starting at `0`.
+
+Creating a publisher or listener with its own socket requires the `net` feature; either may be
+created over any other [`Transport`] with `using`.
+
+# Example
+
+```rust,no_run
+use upnp_rs::eventing::multicast::{MulticastListener, Options};
+
+let listener = MulticastListener::bind(Options::default()).unwrap();
+loop {
+    if let Some(event) = listener.receive().unwrap() {
+        println!("{} #{}: {}", event.service_id, event.seq, event.properties);
+    }
+}
+```
+*/
+
+use crate::common::headers;
+use crate::common::httpu::{
+    split_at_body, Request, Transport, DEFAULT_BUFFER_SIZE, DEFAULT_MULTICAST_TTL,
+    DEFAULT_RECV_TIMEOUT,
+};
+use crate::common::interface::IP;
+use crate::discovery::identity::parse_boot_id;
+use crate::discovery::UniqueServiceName;
+use crate::error::{invalid_field_value, invalid_header_value, Error};
+use crate::eventing::PropertySet;
+use crate::observer::{self, MessageKind};
+use crate::syntax::{
+    Method, GENA_HEADER_LVL, GENA_HEADER_SEQ, GENA_HEADER_SVCID, GENA_NTS_PROPERTY_CHANGE,
+    GENA_NT_EVENT, HTTP_HEADER_BOOTID, HTTP_HEADER_CONTENT_LENGTH, HTTP_HEADER_CONTENT_TYPE,
+    HTTP_HEADER_HOST, HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_USN, HTTP_METHOD_NOTIFY,
+    MULTICAST_EVENT_ADDRESS,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::str::{from_utf8, FromStr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, debug_span, trace, warn};
+
+#[cfg(feature = "net")]
+use crate::common::httpu::{create_udp_socket, Options as MulticastOptions};
+#[cfg(feature = "net")]
+use crate::common::interface::ip_address_for_interface;
+#[cfg(feature = "net")]
+use crate::error::multicast_join_failed;
+#[cfg(feature = "net")]
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the set of values used to configure a publisher or listener.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// A specific network interface to use, by name, index, or network; if specified the
+    /// interface's address is used to send, or to join the multicast group, else the system
+    /// chooses. A value matching no interface is an error. Default: `None`.
+    pub network_interface: Option<String>,
+    /// Denotes whether the implementation wants to only use IPv4, IPv6, or doesn't care.
+    pub network_version: Option<IP>,
+    /// The TTL of multicast messages sent by a publisher. Default: `2`.
+    pub multicast_ttl: u32,
+    /// The time a call to `receive` will wait for a message. Default: `2` seconds.
+    pub recv_timeout: Duration,
+    /// The size of the buffer used to receive messages. Default: `1500`.
+    pub buffer_size: usize,
+}
+
+///
+/// The importance of a multicast event, from the `LVL` header.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum EventLevel {
+    Emergency,
+    Fault,
+    Warning,
+    Info,
+    Debug,
+    /// The level for events with no particular importance, the default.
+    #[default]
+    General,
+    /// A vendor-defined level, in the form `domain:/level`.
+    Other(String),
+}
+
+///
+/// A multicast event, as sent by a publisher or received by a listener.
+///
+#[derive(Clone, Debug)]
+pub struct MulticastEvent {
+    /// The service that sent the event, from the `USN` header.
+    pub service_name: UniqueServiceName,
+    /// The service identifier, from the `SVCID` header.
+    pub service_id: String,
+    /// The event key, from the `SEQ` header.
+    pub seq: u32,
+    /// The importance of the event, from the `LVL` header.
+    pub level: EventLevel,
+    /// The device boot identifier.
+    pub boot_id: Option<u32>,
+    /// The changed state variables and their new values.
+    pub properties: PropertySet,
+    /// The address of the device that sent this event, where known.
+    pub source: Option<SocketAddr>,
+}
+
+///
+/// Sends multicast events, maintaining the event key for each service identifier.
+///
+#[derive(Debug)]
+pub struct MulticastPublisher {
+    transport: Arc<dyn Transport>,
+    boot_id: Option<u32>,
+    sequences: Mutex<HashMap<String, u32>>,
+}
+
+///
+/// A listener bound to the multicast eventing group.
+///
+#[derive(Debug)]
+pub struct MulticastListener {
+    transport: Arc<dyn Transport>,
+    buffer_size: usize,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            network_interface: None,
+            network_version: None,
+            multicast_ttl: DEFAULT_MULTICAST_TTL,
+            recv_timeout: DEFAULT_RECV_TIMEOUT,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for EventLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                EventLevel::Emergency => "upnp:/emergency",
+                EventLevel::Fault => "upnp:/fault",
+                EventLevel::Warning => "upnp:/warning",
+                EventLevel::Info => "upnp:/info",
+                EventLevel::Debug => "upnp:/debug",
+                EventLevel::General => "upnp:/general",
+                EventLevel::Other(level) => level,
+            }
+        )
+    }
+}
+
+impl FromStr for EventLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "upnp:/emergency" => Ok(EventLevel::Emergency),
+            "upnp:/fault" => Ok(EventLevel::Fault),
+            "upnp:/warning" => Ok(EventLevel::Warning),
+            "upnp:/info" => Ok(EventLevel::Info),
+            "upnp:/debug" => Ok(EventLevel::Debug),
+            "upnp:/general" => Ok(EventLevel::General),
+            other if other.contains(":/") && !other.starts_with("upnp:") => {
+                Ok(EventLevel::Other(other.to_string()))
+            }
+            other => invalid_header_value(GENA_HEADER_LVL, other).into(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<&[u8]> for MulticastEvent {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let request = Request::try_from(bytes)?;
+        if request.known_method() != Some(Method::Notify) {
+            return invalid_field_value("method", request.method()).into();
+        }
+        let header = |name: &str| request.header(name).map(str::trim);
+        headers::check_required(
+            request.headers(),
+            &[
+                HTTP_HEADER_NT,
+                HTTP_HEADER_NTS,
+                HTTP_HEADER_USN,
+                GENA_HEADER_SVCID,
+                GENA_HEADER_SEQ,
+                GENA_HEADER_LVL,
+            ],
+        )?;
+        if header(HTTP_HEADER_NT) != Some(GENA_NT_EVENT) {
+            return invalid_header_value(HTTP_HEADER_NT, header(HTTP_HEADER_NT).unwrap()).into();
+        }
+        if header(HTTP_HEADER_NTS) != Some(GENA_NTS_PROPERTY_CHANGE) {
+            return invalid_header_value(HTTP_HEADER_NTS, header(HTTP_HEADER_NTS).unwrap()).into();
+        }
+        let service_name = header(HTTP_HEADER_USN).unwrap();
+
+        let (_, mut body) = split_at_body(bytes);
+        if let Some(length) = header(HTTP_HEADER_CONTENT_LENGTH) {
+            let length = headers::check_parsed_value::<usize>(length, HTTP_HEADER_CONTENT_LENGTH)?;
+            body = &body[..length.min(body.len())];
+        }
+        let body = from_utf8(body).map_err(|_| invalid_field_value("body", "not UTF-8"))?;
+
+        Ok(MulticastEvent {
+            service_name: UniqueServiceName::from_str(service_name)
+                .map_err(|_| invalid_header_value(HTTP_HEADER_USN, service_name))?,
+            service_id: header(GENA_HEADER_SVCID).unwrap().to_string(),
+            seq: headers::check_parsed_value::<u32>(
+                header(GENA_HEADER_SEQ).unwrap(),
+                GENA_HEADER_SEQ,
+            )?,
+            level: EventLevel::from_str(header(GENA_HEADER_LVL).unwrap())?,
+            boot_id: match header(HTTP_HEADER_BOOTID) {
+                None => None,
+                Some(value) => Some(parse_boot_id(value, HTTP_HEADER_BOOTID)?),
+            },
+            properties: PropertySet::from_str(body)?,
+            source: None,
+        })
+    }
+}
+
+impl MulticastEvent {
+    ///
+    /// The complete message for this event, as sent to the multicast group.
+    ///
+    pub fn to_message(&self) -> String {
+        let body = self.properties.to_string();
+        let mut message = format!(
+            "{} * HTTP/1.0\r\n{}: {}\r\n{}: text/xml; charset=\"utf-8\"\r\n{}: {}\r\n{}: {}\r\n{}: {}\r\n{}: {}\r\n{}: {}\r\n{}: {}\r\n",
+            HTTP_METHOD_NOTIFY,
+            HTTP_HEADER_HOST,
+            MULTICAST_EVENT_ADDRESS,
+            HTTP_HEADER_CONTENT_TYPE,
+            HTTP_HEADER_USN,
+            self.service_name,
+            GENA_HEADER_SVCID,
+            self.service_id,
+            HTTP_HEADER_NT,
+            GENA_NT_EVENT,
+            HTTP_HEADER_NTS,
+            GENA_NTS_PROPERTY_CHANGE,
+            GENA_HEADER_SEQ,
+            self.seq,
+            GENA_HEADER_LVL,
+            self.level,
+        );
+        if let Some(boot_id) = self.boot_id {
+            message.push_str(&format!("{}: {}\r\n", HTTP_HEADER_BOOTID, boot_id));
+        }
+        message.push_str(&format!(
+            "{}: {}\r\n\r\n{}",
+            HTTP_HEADER_CONTENT_LENGTH,
+            body.len(),
+            body
+        ));
+        message
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MulticastPublisher {
+    ///
+    /// Create a publisher that sends from a new UDP socket, on the interface selected by
+    /// `options`.
+    ///
+    #[cfg(feature = "net")]
+    pub fn new(options: &Options) -> Result<Self, Error> {
+        let socket = create_udp_socket(
+            &SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            &Default::default(),
+        )?;
+        if let Some(IpAddr::V4(address)) =
+            ip_address_for_interface(&options.network_interface, &options.network_version)?
+        {
+            socket2::SockRef::from(&socket).set_multicast_if_v4(&address)?;
+        }
+        socket.set_multicast_ttl_v4(options.multicast_ttl)?;
+        socket.set_multicast_loop_v4(true)?;
+        Ok(Self::using(Arc::new(socket)))
+    }
+
+    ///
+    /// Create a publisher that sends using `transport`.
+    ///
+    pub fn using(transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            boot_id: None,
+            sequences: Default::default(),
+        }
+    }
+
+    ///
+    /// Set the boot identifier sent with each event.
+    ///
+    pub fn with_boot_id(self, boot_id: u32) -> Self {
+        Self {
+            boot_id: Some(boot_id),
+            ..self
+        }
+    }
+
+    ///
+    /// Send the changed `properties` of the service `service_id`, identified on the network by
+    /// `service_name`, returning the event key used.
+    ///
+    pub fn publish(
+        &self,
+        service_name: &UniqueServiceName,
+        service_id: &str,
+        level: EventLevel,
+        properties: &PropertySet,
+    ) -> Result<u32, Error> {
+        let seq = {
+            let mut sequences = self.sequences.lock().unwrap();
+            let next = sequences.entry(service_id.to_string()).or_insert(0);
+            let seq = *next;
+            *next = next.checked_add(1).unwrap_or(1);
+            seq
+        };
+        let event = MulticastEvent {
+            service_name: service_name.clone(),
+            service_id: service_id.to_string(),
+            seq,
+            level,
+            boot_id: self.boot_id,
+            properties: properties.clone(),
+            source: None,
+        };
+        trace!("publish - {} seq {}", service_id, seq);
+        let _ = self.transport.send_to(
+            event.to_message().as_bytes(),
+            &MULTICAST_EVENT_ADDRESS.parse().unwrap(),
+        )?;
+        observer::sent(MessageKind::Eventing);
+        Ok(seq)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MulticastListener {
+    ///
+    /// Bind to the multicast eventing port and join the multicast group on the selected
+    /// interface.
+    ///
+    #[cfg(feature = "net")]
+    pub fn bind(options: Options) -> Result<Self, Error> {
+        let multicast: SocketAddr = MULTICAST_EVENT_ADDRESS.parse().unwrap();
+        let socket: UdpSocket = create_udp_socket(
+            &SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), multicast.port()),
+            &MulticastOptions {
+                reuse_address: true,
+                reuse_port: cfg!(all(
+                    unix,
+                    not(any(target_os = "solaris", target_os = "illumos"))
+                )),
+                ..Default::default()
+            },
+        )?;
+        let interface =
+            match ip_address_for_interface(&options.network_interface, &options.network_version)? {
+                Some(IpAddr::V4(address)) => address,
+                _ => Ipv4Addr::UNSPECIFIED,
+            };
+        if let IpAddr::V4(group) = multicast.ip() {
+            socket
+                .join_multicast_v4(&group, &interface)
+                .map_err(|e| multicast_join_failed(group.to_string(), e))?;
+        }
+        socket.set_read_timeout(Some(options.recv_timeout))?;
+        Ok(Self::using(Arc::new(socket), options.buffer_size))
+    }
+
+    ///
+    /// Create a listener that receives using `transport`, which must already have joined the
+    /// multicast eventing group.
+    ///
+    pub fn using(transport: Arc<dyn Transport>, buffer_size: usize) -> Self {
+        Self {
+            transport,
+            buffer_size,
+        }
+    }
+
+    ///
+    /// Wait for the next event; `None` is returned if the receive timeout expires, or if the
+    /// message received was not a well-formed multicast event.
+    ///
+    pub fn receive(&self) -> Result<Option<MulticastEvent>, Error> {
+        let mut buf = vec![0u8; self.buffer_size];
+        match self.transport.recv_from(&mut buf) {
+            Ok((received, from)) => {
+                trace!("receive - {} bytes from {}", received, from);
+                Ok(parse_event(&buf[..received], from))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parse_event(bytes: &[u8], from: SocketAddr) -> Option<MulticastEvent> {
+    let _span = debug_span!("multicast_event", peer = %from).entered();
+    match MulticastEvent::try_from(bytes) {
+        Ok(mut event) => {
+            observer::received(MessageKind::Eventing);
+            debug!(
+                usn = %event.service_name,
+                seq = event.seq,
+                "received multicast event"
+            );
+            event.source = Some(from);
+            Some(event)
+        }
+        Err(e) => {
+            observer::parse_failed(MessageKind::Eventing);
+            warn!(error = %e, "ignoring malformed multicast event");
+            None
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::LoopbackNetwork;
+
+    const USN: &str = "uuid:device-1::urn:schemas-upnp-org:service:SwitchPower:1";
+
+    #[test]
+    fn test_event_level() {
+        for level in &[
+            EventLevel::Emergency,
+            EventLevel::Fault,
+            EventLevel::Warning,
+            EventLevel::Info,
+            EventLevel::Debug,
+            EventLevel::General,
+            EventLevel::Other("example-com:/trace".to_string()),
+        ] {
+            assert_eq!(&EventLevel::from_str(&level.to_string()).unwrap(), level);
+        }
+        assert!(EventLevel::from_str("upnp:/unknown").is_err());
+        assert!(EventLevel::from_str("general").is_err());
+    }
+
+    #[test]
+    fn test_publish_and_receive() {
+        let network = LoopbackNetwork::new();
+        let device = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+        let control_point = network.bind("10.0.0.1:7900".parse().unwrap()).unwrap();
+        control_point.join(MULTICAST_EVENT_ADDRESS.parse().unwrap());
+        control_point
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+
+        let publisher = MulticastPublisher::using(Arc::new(device)).with_boot_id(3);
+        let listener = MulticastListener::using(Arc::new(control_point), DEFAULT_BUFFER_SIZE);
+        let service_name = UniqueServiceName::from_str(USN).unwrap();
+        let properties = PropertySet::new().with_property("Status", "1");
+        for expected in 0..2 {
+            let seq = publisher
+                .publish(
+                    &service_name,
+                    "urn:upnp-org:serviceId:SwitchPower",
+                    EventLevel::Info,
+                    &properties,
+                )
+                .unwrap();
+            assert_eq!(seq, expected);
+        }
+
+        let event = listener.receive().unwrap().unwrap();
+        assert_eq!(event.service_name.to_string(), USN);
+        assert_eq!(event.service_id, "urn:upnp-org:serviceId:SwitchPower");
+        assert_eq!(event.seq, 0);
+        assert_eq!(event.level, EventLevel::Info);
+        assert_eq!(event.boot_id, Some(3));
+        assert_eq!(event.properties.get("Status"), Some("1"));
+        assert_eq!(event.source, Some("10.0.0.2:5000".parse().unwrap()));
+        assert_eq!(listener.receive().unwrap().unwrap().seq, 1);
+        assert!(listener.receive().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let missing_level = format!("NOTIFY * HTTP/1.0\r\nHOST: 239.255.255.246:7900\r\nUSN: {}\r\nSVCID: urn:upnp-org:serviceId:SwitchPower\r\nNT: upnp:event\r\nNTS: upnp:propchange\r\nSEQ: 0\r\n\r\n", USN);
+        assert!(MulticastEvent::try_from(missing_level.as_bytes()).is_err());
+
+        let search = b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n";
+        assert!(parse_event(search, "10.0.0.1:1900".parse().unwrap()).is_none());
+    }
+}
@@ -16,7 +16,11 @@
 * For more information see the specifications and other documents at [Open Connectivity
 * Foundation](https://openconnectivity.org/developer/specifications/upnp-resources/upnp/).
 *
-* The main interface is the [`ssdp`](ssdp/index.html) module.
+* The main interface is the [`discovery`](discovery/index.html) module, with shared protocol support
+* (HTTPU, SOAP, XML) in the [`common`](common/index.html) module. The [`prelude`](prelude/index.html)
+* module re-exports the types most commonly used by a control point. The previous `ssdp`, `httpu`,
+* and `soap` module paths remain, as deprecated aliases of `discovery`, `common::httpu`, and
+* `common::soap`, for one release.
 *
 * # Example
 *
@@ -43,6 +47,14 @@
 * }
 * ```
 *
+* # Features
+*
+* The `net` feature, enabled by default, provides everything that needs the operating system's
+* network stack: creating sockets, selecting network interfaces, the default HTTP client, and the
+* event listener and device emulator servers. Without it the protocol core remains; message
+* building and parsing, the description model, and the functions that take a caller-provided
+* [`Transport`](common::httpu::Transport) or [`HttpClient`](common::http::HttpClient).
+*
 * # Documentation
 *
 * Where possible any documentation for fields, functions, and values will be taken directly from the
@@ -76,10 +88,11 @@ use std::str::FromStr;
 /// This allows the client to constrain the messaging to only the capabilities described by a
 /// specific version.
 ///
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SpecVersion {
     /// Denotes messages conforming to UPnP version
     /// [1.0](http://www.upnp.org/specs/arch/UPnP-arch-DeviceArchitecture-v1.0.pdf)
+    #[default]
     V10,
     /// Denotes messages conforming to UPnP version
     /// [1.1](http://www.upnp.org/specs/arch/UPnP-arch-DeviceArchitecture-v1.1.pdf)
@@ -89,6 +102,21 @@ pub enum SpecVersion {
     V20,
 }
 
+///
+/// Describes how this crate was built; the crate version, the set of enabled features, and the
+/// default specification version. This is useful when triaging interoperability reports from users
+/// running with different feature sets.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The version of this crate, as taken from the Cargo manifest.
+    pub version: &'static str,
+    /// The names of all the optional crate features enabled in this build.
+    pub features: Vec<&'static str>,
+    /// The specification version used when none is provided by the client.
+    pub default_spec_version: SpecVersion,
+}
+
 ///
 /// The protocol short form identifier used in constructing numerous values.
 ///
@@ -100,15 +128,40 @@ pub const UPNP_STRING: &str = "UPnP";
 pub const UPNP_DOMAIN: &str = "schemas-upnp-org";
 
 // ------------------------------------------------------------------------------------------------
-// Implementations
+// Public Functions
 // ------------------------------------------------------------------------------------------------
 
-impl Default for SpecVersion {
-    fn default() -> Self {
-        SpecVersion::V10
+///
+/// Return details of the current build of this crate.
+///
+pub fn build_info() -> BuildInfo {
+    let mut features = Vec::new();
+    if cfg!(feature = "net") {
+        features.push("net");
+    }
+    if cfg!(feature = "reqwest") {
+        features.push("reqwest");
+    }
+    if cfg!(feature = "ureq") {
+        features.push("ureq");
+    }
+    if cfg!(feature = "command_line") {
+        features.push("command_line");
+    }
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features,
+        default_spec_version: SpecVersion::default(),
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
 impl Display for SpecVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         write!(
@@ -129,6 +182,16 @@ impl Display for SpecVersion {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for SpecVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for SpecVersion {
     type Err = error::MessageFormatError;
 
@@ -142,6 +205,37 @@ impl FromStr for SpecVersion {
     }
 }
 
+impl SpecVersion {
+    ///
+    /// The TTL, or IPv6 hop limit, the specification recommends for multicast messages; `4` for
+    /// 1.0 and `2` for later versions.
+    ///
+    pub fn default_multicast_ttl(&self) -> u32 {
+        match self {
+            SpecVersion::V10 => 4,
+            _ => 2,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        writeln!(f, "{} {}", env!("CARGO_PKG_NAME"), self.version)?;
+        writeln!(
+            f,
+            "features: {}",
+            if self.features.is_empty() {
+                "none".to_string()
+            } else {
+                self.features.join(", ")
+            }
+        )?;
+        write!(f, "default UPnP version: {}", self.default_spec_version)
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
@@ -152,10 +246,108 @@ pub mod error;
 
 pub mod discovery;
 
+///
+/// Deprecated alias of the [`discovery`] module. The compiler reports the deprecation where a
+/// path ends at this module, or at its `search`, `notify`, or `listen` modules, as in
+/// `use upnp_rs::ssdp::search::*`; other paths through it are not reported.
+///
+#[deprecated(note = "use discovery")]
+pub mod ssdp {
+    pub use crate::discovery::*;
+
+    #[deprecated(note = "use discovery::search")]
+    pub mod search {
+        pub use crate::discovery::search::*;
+    }
+
+    #[deprecated(note = "use discovery::notify")]
+    pub mod notify {
+        pub use crate::discovery::notify::*;
+    }
+
+    #[deprecated(note = "use discovery::listen")]
+    pub mod listen {
+        pub use crate::discovery::listen::*;
+    }
+}
+
+///
+/// Deprecated alias of the [`common::httpu`] module.
+///
+#[deprecated(note = "use common::httpu")]
+pub mod httpu {
+    pub use crate::common::httpu::*;
+}
+
+///
+/// Deprecated alias of the [`common::soap`] module.
+///
+#[deprecated(note = "use common::soap")]
+pub mod soap {
+    pub use crate::common::soap::*;
+}
+
 pub mod description;
 
 pub mod control;
 
 pub mod eventing;
 
+pub mod control_point;
+
 pub mod syntax;
+
+pub mod snapshot;
+
+#[cfg(feature = "net")]
+pub mod emulation;
+
+pub mod profiles;
+
+pub mod observer;
+
+pub mod prelude;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_features() {
+        let info = build_info();
+        for (feature, enabled) in [
+            ("net", cfg!(feature = "net")),
+            ("reqwest", cfg!(feature = "reqwest")),
+            ("ureq", cfg!(feature = "ureq")),
+            ("command_line", cfg!(feature = "command_line")),
+            ("serde", cfg!(feature = "serde")),
+        ] {
+            assert_eq!(info.features.contains(&feature), enabled, "{}", feature);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_aliases() {
+        let target: discovery::search::SearchTarget = ssdp::search::SearchTarget::RootDevices;
+        assert_eq!(target, discovery::search::SearchTarget::RootDevice);
+        assert_eq!(
+            httpu::DEFAULT_BUFFER_SIZE,
+            common::httpu::DEFAULT_BUFFER_SIZE
+        );
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "net",
+        feature = "reqwest",
+        not(any(feature = "ureq", feature = "command_line", feature = "serde"))
+    ))]
+    fn test_build_info_default_features() {
+        assert_eq!(build_info().features, vec!["net", "reqwest"]);
+    }
+}
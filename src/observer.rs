@@ -0,0 +1,236 @@
+/*!
+This module provides a hook for monitoring the protocol messages sent and received by this crate,
+for example to export message counts as metrics. A single [`ProtocolObserver`] may be installed
+for the process with [`set_observer`]; all its methods have empty default implementations so an
+observer need only implement those it is interested in.
+
+The [`MessageCounters`] observer keeps a running count of each event for each kind of message,
+suitable for periodic collection by a metrics exporter such as Prometheus.
+
+Detailed diagnostics are also available through the `tracing` spans `search`, `notify`, `listen`,
+`fetch`, `invoke`, and `subscribe`, which carry fields such as the search target (`st`), unique
+service name (`usn`), and peer address (`peer`).
+
+# Example
+
+```rust
+use upnp_rs::observer::{set_observer, MessageCounters, MessageKind};
+use std::sync::Arc;
+
+let counters = Arc::new(MessageCounters::default());
+set_observer(counters.clone());
+
+// ... search, listen, etc.
+
+println!("search responses received: {}", counters.received_count(MessageKind::SearchResponse));
+```
+*/
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The kinds of message reported to an observer.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// An SSDP `M-SEARCH` request.
+    Search,
+    /// A response to an SSDP search.
+    SearchResponse,
+    /// An SSDP `NOTIFY` advertisement.
+    Notify,
+    /// A device or service description retrieved over HTTP.
+    Description,
+    /// A SOAP control request, or its response.
+    Control,
+    /// A GENA subscription request, or its response.
+    Eventing,
+}
+
+///
+/// Receives a callback for each protocol message sent or received, and for each received message
+/// that could not be parsed. Callbacks are made on the thread performing the operation and so
+/// should return quickly.
+///
+pub trait ProtocolObserver: Send + Sync {
+    fn message_sent(&self, _kind: MessageKind) {}
+
+    fn message_received(&self, _kind: MessageKind) {}
+
+    fn parse_failed(&self, _kind: MessageKind) {}
+}
+
+///
+/// An observer that counts the messages of each kind sent, received, and that failed to parse.
+///
+#[derive(Debug, Default)]
+pub struct MessageCounters {
+    sent: [AtomicU64; MESSAGE_KINDS],
+    received: [AtomicU64; MESSAGE_KINDS],
+    parse_failed: [AtomicU64; MESSAGE_KINDS],
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Install `observer` for the process, replacing any previously installed.
+///
+pub fn set_observer(observer: Arc<dyn ProtocolObserver>) {
+    *OBSERVER.write().unwrap() = Some(observer);
+}
+
+///
+/// Remove any installed observer.
+///
+pub fn clear_observer() {
+    *OBSERVER.write().unwrap() = None;
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for MessageKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}",
+            match self {
+                MessageKind::Search => "search",
+                MessageKind::SearchResponse => "search_response",
+                MessageKind::Notify => "notify",
+                MessageKind::Description => "description",
+                MessageKind::Control => "control",
+                MessageKind::Eventing => "eventing",
+            }
+        )
+    }
+}
+
+impl MessageKind {
+    ///
+    /// All message kinds, in a fixed order.
+    ///
+    pub const ALL: [MessageKind; MESSAGE_KINDS] = [
+        MessageKind::Search,
+        MessageKind::SearchResponse,
+        MessageKind::Notify,
+        MessageKind::Description,
+        MessageKind::Control,
+        MessageKind::Eventing,
+    ];
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ProtocolObserver for MessageCounters {
+    fn message_sent(&self, kind: MessageKind) {
+        let _ = self.sent[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn message_received(&self, kind: MessageKind) {
+        let _ = self.received[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn parse_failed(&self, kind: MessageKind) {
+        let _ = self.parse_failed[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl MessageCounters {
+    pub fn sent_count(&self, kind: MessageKind) -> u64 {
+        self.sent[kind as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn received_count(&self, kind: MessageKind) -> u64 {
+        self.received[kind as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn parse_failed_count(&self, kind: MessageKind) -> u64 {
+        self.parse_failed[kind as usize].load(Ordering::Relaxed)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const MESSAGE_KINDS: usize = 6;
+
+lazy_static! {
+    static ref OBSERVER: RwLock<Option<Arc<dyn ProtocolObserver>>> = RwLock::new(None);
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) fn sent(kind: MessageKind) {
+    notify(|observer| observer.message_sent(kind));
+}
+
+pub(crate) fn received(kind: MessageKind) {
+    notify(|observer| observer.message_received(kind));
+}
+
+pub(crate) fn parse_failed(kind: MessageKind) {
+    notify(|observer| observer.parse_failed(kind));
+}
+
+fn notify<F>(f: F)
+where
+    F: FnOnce(&dyn ProtocolObserver),
+{
+    if let Some(observer) = OBSERVER.read().unwrap().as_ref() {
+        f(observer.as_ref());
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let counters = MessageCounters::default();
+        counters.message_sent(MessageKind::Search);
+        counters.message_sent(MessageKind::Search);
+        counters.message_received(MessageKind::SearchResponse);
+        counters.parse_failed(MessageKind::SearchResponse);
+        assert_eq!(counters.sent_count(MessageKind::Search), 2);
+        assert_eq!(counters.sent_count(MessageKind::Notify), 0);
+        assert_eq!(counters.received_count(MessageKind::SearchResponse), 1);
+        assert_eq!(counters.parse_failed_count(MessageKind::SearchResponse), 1);
+        assert_eq!(
+            MessageKind::ALL[MessageKind::Eventing as usize],
+            MessageKind::Eventing
+        );
+    }
+
+    #[test]
+    fn test_installed_observer() {
+        let counters = Arc::new(MessageCounters::default());
+        set_observer(counters.clone());
+        sent(MessageKind::Control);
+        received(MessageKind::Control);
+        clear_observer();
+        // Other tests may send messages concurrently, but none are observed once cleared.
+        let sent_count = counters.sent_count(MessageKind::Control);
+        assert!(sent_count >= 1);
+        assert!(counters.received_count(MessageKind::Control) >= 1);
+        sent(MessageKind::Control);
+        assert_eq!(counters.sent_count(MessageKind::Control), sent_count);
+    }
+}
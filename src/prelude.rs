@@ -0,0 +1,35 @@
+/*!
+This module re-exports the types and functions most commonly used by a control point, so that a
+single glob import is sufficient for typical use. The items are re-exported from their defining
+modules, and these paths will remain stable when the internal module layout changes.
+
+Where the defining modules use the same name for different types, such as the `Options` of search
+and of the notify listener, the re-export is renamed for the module it belongs to.
+
+# Example
+
+```rust,no_run
+use upnp_rs::prelude::*;
+
+let mut options = SearchOptions::default_for(SpecVersion::V11);
+options.search_target = SearchTarget::RootDevice;
+
+let responses: Result<Vec<SearchResponse>, Error> = search_once(options);
+for response in responses.unwrap() {
+    println!("{} at {}", response.service_name, response.location);
+}
+```
+*/
+
+pub use crate::description::device::DeviceRoot;
+pub use crate::description::{ServiceId, TypeID};
+pub use crate::discovery::listen::{Notification, NotificationSubtype};
+pub use crate::discovery::search::{
+    search_once, Options as SearchOptions, Response as SearchResponse, SearchTarget,
+};
+pub use crate::discovery::{NotificationType, UniqueServiceName};
+pub use crate::error::Error;
+pub use crate::SpecVersion;
+
+#[cfg(feature = "net")]
+pub use crate::discovery::listen::{NotifyListener, Options as ListenOptions};
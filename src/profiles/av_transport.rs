@@ -0,0 +1,433 @@
+/*!
+This module provides a client for the `AVTransport` service of a `MediaRenderer` device, with
+typed wrappers for the common transport actions and parsing of the duration and metadata values
+returned by `GetPositionInfo`.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::profiles::av_transport::{from_location, SeekTarget};
+use std::str::FromStr;
+use std::time::Duration;
+
+let location = URL::from_str("http://10.0.0.2:1400/xml/device_description.xml").unwrap();
+let transport = from_location(location).unwrap();
+transport
+    .set_av_transport_uri("http://10.0.0.1:8200/MediaItems/22.mp3", None)
+    .unwrap();
+transport.play().unwrap();
+transport.seek(SeekTarget::RelativeTime(Duration::from_secs(30))).unwrap();
+println!("{:?}", transport.get_position_info().unwrap().relative_time);
+```
+*/
+
+use crate::common::http::HttpClient;
+use crate::common::soap::Arguments;
+use crate::common::uri::URL;
+use crate::control::{argument, parsed_argument, ServiceClient};
+use crate::description::device::DeviceRoot;
+use crate::description::well_known::av_transport;
+use crate::error::{invalid_field_value, Error};
+use crate::profiles::content_directory::DidlLite;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "net")]
+use crate::common::http::{default_client, fetch};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The target of a `Seek` action, this determines both the `Unit` and `Target` arguments.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekTarget {
+    /// Seek to the start of the given track number (`TRACK_NR`).
+    Track(u32),
+    /// Seek to a time from the start of the media (`ABS_TIME`).
+    AbsoluteTime(Duration),
+    /// Seek to a time from the start of the current track (`REL_TIME`).
+    RelativeTime(Duration),
+    /// Seek to a counter position from the start of the media (`ABS_COUNT`).
+    AbsoluteCount(i32),
+    /// Seek to a counter position from the start of the current track (`REL_COUNT`).
+    RelativeCount(i32),
+}
+
+///
+/// The result of the `GetPositionInfo` action. Values the renderer reports as `NOT_IMPLEMENTED`,
+/// or leaves empty, are `None`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionInfo {
+    pub track: u32,
+    pub track_duration: Option<Duration>,
+    pub track_metadata: Option<DidlLite>,
+    pub track_uri: Option<String>,
+    pub relative_time: Option<Duration>,
+    pub absolute_time: Option<Duration>,
+    pub relative_count: Option<i32>,
+    pub absolute_count: Option<i32>,
+}
+
+///
+/// A client for the `AVTransport` service of a single media renderer.
+///
+#[derive(Clone, Debug)]
+pub struct AvTransport {
+    location: URL,
+    service: ServiceClient,
+    instance_id: u32,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Retrieve the device description at `location` and return a client for its `AVTransport`
+/// service.
+///
+#[cfg(feature = "net")]
+pub fn from_location(location: URL) -> Result<AvTransport, Error> {
+    let root: DeviceRoot = fetch(location.to_string())?;
+    from_description(&root, location)
+}
+
+///
+/// Return a client for the `AVTransport` service in the provided description, retrieved from
+/// `location`.
+///
+#[cfg(feature = "net")]
+pub fn from_description(root: &DeviceRoot, location: URL) -> Result<AvTransport, Error> {
+    from_description_with(root, location, default_client())
+}
+
+///
+/// As [`from_description`], returning a client that sends requests using `client`.
+///
+pub fn from_description_with(
+    root: &DeviceRoot,
+    location: URL,
+    client: Arc<dyn HttpClient>,
+) -> Result<AvTransport, Error> {
+    let service = ServiceClient::for_service_with(root, &location, &av_transport(1), client)?;
+    Ok(AvTransport {
+        location,
+        service,
+        instance_id: 0,
+    })
+}
+
+///
+/// Parse a duration in the AVTransport `H+:MM:SS[.F+]` (or `H+:MM:SS[.F0/F1]`) form, returning
+/// `None` for the value `NOT_IMPLEMENTED` or an empty string.
+///
+pub fn parse_duration(s: &str) -> Result<Option<Duration>, Error> {
+    let s = s.trim();
+    if s.is_empty() || s == NOT_IMPLEMENTED {
+        return Ok(None);
+    }
+    let invalid = || invalid_field_value("duration", s);
+    let (sign, unsigned) = match s.strip_prefix(['+', '-']) {
+        Some(rest) => (&s[..1], rest),
+        None => ("+", s),
+    };
+    if sign == "-" {
+        return invalid().into();
+    }
+    let parts: Vec<&str> = unsigned.split(':').collect();
+    if parts.len() != 3 {
+        return invalid().into();
+    }
+    let hours = u64::from_str(parts[0]).map_err(|_| invalid())?;
+    let minutes = u64::from_str(parts[1]).map_err(|_| invalid())?;
+    let (seconds, fraction) = match parts[2].split_once('.') {
+        None => (parts[2], None),
+        Some((seconds, fraction)) => (seconds, Some(fraction)),
+    };
+    let seconds = u64::from_str(seconds).map_err(|_| invalid())?;
+    if minutes > 59 || seconds > 59 {
+        return invalid().into();
+    }
+    let nanos = match fraction {
+        None => 0,
+        Some(fraction) => match fraction.split_once('/') {
+            Some((numerator, denominator)) => {
+                let numerator = u64::from_str(numerator).map_err(|_| invalid())?;
+                let denominator = u64::from_str(denominator).map_err(|_| invalid())?;
+                if denominator == 0 || numerator >= denominator {
+                    return invalid().into();
+                }
+                numerator * 1_000_000_000 / denominator
+            }
+            None => {
+                let digits = &fraction[..fraction.len().min(9)];
+                let value = u64::from_str(digits).map_err(|_| invalid())?;
+                value * 10u64.pow(9 - digits.len() as u32)
+            }
+        },
+    };
+    Ok(Some(
+        Duration::from_secs(hours * 3600 + minutes * 60 + seconds) + Duration::from_nanos(nanos),
+    ))
+}
+
+///
+/// Format a duration in the AVTransport `H+:MM:SS[.F+]` form, with milliseconds included only if
+/// non-zero.
+///
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let millis = duration.subsec_millis();
+    let formatted = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    );
+    if millis == 0 {
+        formatted
+    } else {
+        format!("{}.{:03}", formatted, millis)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+const NOT_IMPLEMENTED: &str = "NOT_IMPLEMENTED";
+const COUNT_NOT_IMPLEMENTED: i32 = i32::MAX;
+const NORMAL_SPEED: &str = "1";
+
+const ARG_INSTANCE_ID: &str = "InstanceID";
+const ARG_CURRENT_URI: &str = "CurrentURI";
+const ARG_CURRENT_URI_METADATA: &str = "CurrentURIMetaData";
+const ARG_SPEED: &str = "Speed";
+const ARG_UNIT: &str = "Unit";
+const ARG_TARGET: &str = "Target";
+const ARG_TRACK: &str = "Track";
+const ARG_TRACK_DURATION: &str = "TrackDuration";
+const ARG_TRACK_METADATA: &str = "TrackMetaData";
+const ARG_TRACK_URI: &str = "TrackURI";
+const ARG_REL_TIME: &str = "RelTime";
+const ARG_ABS_TIME: &str = "AbsTime";
+const ARG_REL_COUNT: &str = "RelCount";
+const ARG_ABS_COUNT: &str = "AbsCount";
+
+impl SeekTarget {
+    fn unit(&self) -> &'static str {
+        match self {
+            SeekTarget::Track(_) => "TRACK_NR",
+            SeekTarget::AbsoluteTime(_) => "ABS_TIME",
+            SeekTarget::RelativeTime(_) => "REL_TIME",
+            SeekTarget::AbsoluteCount(_) => "ABS_COUNT",
+            SeekTarget::RelativeCount(_) => "REL_COUNT",
+        }
+    }
+
+    fn target(&self) -> String {
+        match self {
+            SeekTarget::Track(track) => track.to_string(),
+            SeekTarget::AbsoluteTime(time) | SeekTarget::RelativeTime(time) => {
+                format_duration(*time)
+            }
+            SeekTarget::AbsoluteCount(count) | SeekTarget::RelativeCount(count) => {
+                count.to_string()
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl PositionInfo {
+    fn try_from_arguments(arguments: &Arguments) -> Result<Self, Error> {
+        let optional_count = |name: &str| -> Result<Option<i32>, Error> {
+            let count: i32 = parsed_argument(arguments, name)?;
+            Ok(if count == COUNT_NOT_IMPLEMENTED {
+                None
+            } else {
+                Some(count)
+            })
+        };
+        let track_metadata = argument(arguments, ARG_TRACK_METADATA)?.trim();
+        let track_uri = argument(arguments, ARG_TRACK_URI)?.trim();
+        Ok(Self {
+            track: parsed_argument(arguments, ARG_TRACK)?,
+            track_duration: parse_duration(argument(arguments, ARG_TRACK_DURATION)?)?,
+            track_metadata: if track_metadata.is_empty() || track_metadata == NOT_IMPLEMENTED {
+                None
+            } else {
+                Some(DidlLite::from_str(track_metadata)?)
+            },
+            track_uri: if track_uri.is_empty() {
+                None
+            } else {
+                Some(track_uri.to_string())
+            },
+            relative_time: parse_duration(argument(arguments, ARG_REL_TIME)?)?,
+            absolute_time: parse_duration(argument(arguments, ARG_ABS_TIME)?)?,
+            relative_count: optional_count(ARG_REL_COUNT)?,
+            absolute_count: optional_count(ARG_ABS_COUNT)?,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl AvTransport {
+    ///
+    /// The location of the media renderer's device description.
+    ///
+    pub fn location(&self) -> &URL {
+        &self.location
+    }
+
+    ///
+    /// The client for the `AVTransport` service.
+    ///
+    pub fn service(&self) -> &ServiceClient {
+        &self.service
+    }
+
+    ///
+    /// Use a virtual transport instance other than the default, `0`. Instance identifiers are
+    /// returned by `ConnectionManager::PrepareForConnection`.
+    ///
+    pub fn with_instance_id(self, instance_id: u32) -> Self {
+        Self {
+            instance_id,
+            ..self
+        }
+    }
+
+    ///
+    /// Set the URI of the media to play, with optional DIDL-Lite metadata describing it.
+    ///
+    pub fn set_av_transport_uri(&self, uri: &str, metadata: Option<&str>) -> Result<(), Error> {
+        self.invoke(
+            "SetAVTransportURI",
+            &[
+                (ARG_CURRENT_URI, uri),
+                (ARG_CURRENT_URI_METADATA, metadata.unwrap_or_default()),
+            ],
+        )
+        .map(|_| ())
+    }
+
+    ///
+    /// Start playback at normal speed.
+    ///
+    pub fn play(&self) -> Result<(), Error> {
+        self.play_at_speed(NORMAL_SPEED)
+    }
+
+    ///
+    /// Start playback at the given speed, one of the renderer's `TransportPlaySpeed` values.
+    ///
+    pub fn play_at_speed(&self, speed: &str) -> Result<(), Error> {
+        self.invoke("Play", &[(ARG_SPEED, speed)]).map(|_| ())
+    }
+
+    pub fn pause(&self) -> Result<(), Error> {
+        self.invoke("Pause", &[]).map(|_| ())
+    }
+
+    pub fn stop(&self) -> Result<(), Error> {
+        self.invoke("Stop", &[]).map(|_| ())
+    }
+
+    pub fn seek(&self, target: SeekTarget) -> Result<(), Error> {
+        self.invoke(
+            "Seek",
+            &[(ARG_UNIT, target.unit()), (ARG_TARGET, &target.target())],
+        )
+        .map(|_| ())
+    }
+
+    pub fn get_position_info(&self) -> Result<PositionInfo, Error> {
+        PositionInfo::try_from_arguments(&self.invoke("GetPositionInfo", &[])?)
+    }
+
+    fn invoke(&self, action: &str, arguments: &[(&str, &str)]) -> Result<Arguments, Error> {
+        let instance_id = self.instance_id.to_string();
+        let mut all_arguments = vec![(ARG_INSTANCE_ID, instance_id.as_str())];
+        all_arguments.extend_from_slice(arguments);
+        self.service.invoke(action, &all_arguments)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            parse_duration("0:03:42").unwrap(),
+            Some(Duration::from_secs(222))
+        );
+        assert_eq!(
+            parse_duration("1:00:00.500").unwrap(),
+            Some(Duration::from_millis(3_600_500))
+        );
+        assert_eq!(
+            parse_duration("0:00:01.1/4").unwrap(),
+            Some(Duration::from_millis(1_250))
+        );
+        assert_eq!(parse_duration("NOT_IMPLEMENTED").unwrap(), None);
+        assert_eq!(parse_duration("").unwrap(), None);
+        assert!(parse_duration("0:61:00").is_err());
+        assert!(parse_duration("-0:00:01").is_err());
+        assert!(parse_duration("12:00").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(222)), "0:03:42");
+        assert_eq!(
+            format_duration(Duration::from_millis(36_000_250)),
+            "10:00:00.250"
+        );
+        assert_eq!(
+            SeekTarget::RelativeTime(Duration::from_secs(90)).target(),
+            "0:01:30"
+        );
+    }
+
+    #[test]
+    fn test_position_info() {
+        let arguments: Arguments = vec![
+            ("Track", "1"),
+            ("TrackDuration", "0:03:42.000"),
+            ("TrackMetaData", "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\"><item id=\"1\" parentID=\"0\" restricted=\"1\"><dc:title xmlns:dc=\"http://purl.org/dc/elements/1.1/\">Song</dc:title></item></DIDL-Lite>"),
+            ("TrackURI", "http://10.0.0.1/song.mp3"),
+            ("RelTime", "0:00:10"),
+            ("AbsTime", "NOT_IMPLEMENTED"),
+            ("RelCount", "2147483647"),
+            ("AbsCount", "12"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let info = PositionInfo::try_from_arguments(&arguments).unwrap();
+        assert_eq!(info.track, 1);
+        assert_eq!(info.track_duration, Some(Duration::from_secs(222)));
+        assert_eq!(info.track_metadata.unwrap().items[0].object.title, "Song");
+        assert_eq!(info.track_uri.as_deref(), Some("http://10.0.0.1/song.mp3"));
+        assert_eq!(info.relative_time, Some(Duration::from_secs(10)));
+        assert_eq!(info.absolute_time, None);
+        assert_eq!(info.relative_count, None);
+        assert_eq!(info.absolute_count, Some(12));
+    }
+}
@@ -0,0 +1,472 @@
+/*!
+This module provides a client for the `ContentDirectory` service of a `MediaServer` device,
+supporting the `Browse` and `Search` actions and the parsing of the DIDL-Lite documents they
+return into typed containers and items.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::profiles::content_directory::{from_location, ROOT_OBJECT_ID};
+use std::str::FromStr;
+
+let location = URL::from_str("http://10.0.0.1:8200/rootDesc.xml").unwrap();
+let content_directory = from_location(location).unwrap();
+let result = content_directory.browse_children(ROOT_OBJECT_ID).unwrap();
+for container in &result.result.containers {
+    println!("{} ({})", container.object.title, container.object.id);
+}
+```
+*/
+
+use crate::common::data_types::boolean_from_str;
+use crate::common::http::HttpClient;
+use crate::common::soap::Arguments;
+use crate::common::uri::URL;
+use crate::common::xml::read::{read_xml, Element};
+use crate::control::{argument, parsed_argument, ServiceClient};
+use crate::description::device::DeviceRoot;
+use crate::description::well_known::content_directory;
+use crate::error::{invalid_field_value, Error};
+use crate::profiles::dlna::ProtocolInfo;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[cfg(feature = "net")]
+use crate::common::http::{default_client, fetch};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Determines whether `Browse` returns the metadata of the object itself, or of its children.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrowseFlag {
+    Metadata,
+    DirectChildren,
+}
+
+///
+/// Optional values common to the `Browse` and `Search` actions.
+///
+#[derive(Clone, Debug)]
+pub struct BrowseOptions {
+    /// A comma-separated list of properties to return, `*` returns all. Default: `*`.
+    pub filter: String,
+    /// The index of the first result to return. Default: `0`.
+    pub starting_index: u32,
+    /// The maximum number of results to return, `0` requests all. Default: `0`.
+    pub requested_count: u32,
+    /// A comma-separated list of `+` or `-` prefixed property names. Default: empty.
+    pub sort_criteria: String,
+}
+
+///
+/// A resource (`res` element) of an object, typically the URI of the media itself.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resource {
+    pub uri: String,
+    /// The `protocolInfo` value, `<protocol>:<network>:<contentFormat>:<additionalInfo>`.
+    pub protocol_info: String,
+    pub size: Option<u64>,
+    pub duration: Option<String>,
+    pub resolution: Option<String>,
+    pub bitrate: Option<u32>,
+}
+
+///
+/// The properties common to both containers and items.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Object {
+    pub id: String,
+    pub parent_id: String,
+    pub restricted: bool,
+    /// The `dc:title` property.
+    pub title: String,
+    /// The `upnp:class` property, for example `object.item.audioItem.musicTrack`.
+    pub class: String,
+    /// The `dc:creator` property, if present.
+    pub creator: Option<String>,
+    pub resources: Vec<Resource>,
+    /// All other properties, as qualified name and text value, in document order.
+    pub properties: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Container {
+    pub object: Object,
+    pub child_count: Option<u32>,
+    pub searchable: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Item {
+    pub object: Object,
+    /// The identifier of the item this item refers to, if it is a reference.
+    pub ref_id: Option<String>,
+}
+
+///
+/// A parsed DIDL-Lite document.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DidlLite {
+    pub containers: Vec<Container>,
+    pub items: Vec<Item>,
+}
+
+///
+/// The result of a `Browse` or `Search` action.
+///
+#[derive(Clone, Debug)]
+pub struct BrowseResult {
+    pub result: DidlLite,
+    pub number_returned: u32,
+    pub total_matches: u32,
+    pub update_id: u32,
+}
+
+///
+/// A client for the `ContentDirectory` service of a single media server.
+///
+#[derive(Clone, Debug)]
+pub struct ContentDirectory {
+    location: URL,
+    service: ServiceClient,
+}
+
+///
+/// The identifier of the root container of every content directory.
+///
+pub const ROOT_OBJECT_ID: &str = "0";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Retrieve the device description at `location` and return a client for its `ContentDirectory`
+/// service.
+///
+#[cfg(feature = "net")]
+pub fn from_location(location: URL) -> Result<ContentDirectory, Error> {
+    let root: DeviceRoot = fetch(location.to_string())?;
+    from_description(&root, location)
+}
+
+///
+/// Return a client for the `ContentDirectory` service in the provided description, retrieved
+/// from `location`.
+///
+#[cfg(feature = "net")]
+pub fn from_description(root: &DeviceRoot, location: URL) -> Result<ContentDirectory, Error> {
+    from_description_with(root, location, default_client())
+}
+
+///
+/// As [`from_description`], returning a client that sends requests using `client`.
+///
+pub fn from_description_with(
+    root: &DeviceRoot,
+    location: URL,
+    client: Arc<dyn HttpClient>,
+) -> Result<ContentDirectory, Error> {
+    let service = ServiceClient::for_service_with(root, &location, &content_directory(1), client)?;
+    Ok(ContentDirectory { location, service })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for BrowseFlag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                BrowseFlag::Metadata => "BrowseMetadata",
+                BrowseFlag::DirectChildren => "BrowseDirectChildren",
+            }
+        )
+    }
+}
+
+impl Default for BrowseOptions {
+    fn default() -> Self {
+        Self {
+            filter: "*".to_string(),
+            starting_index: 0,
+            requested_count: 0,
+            sort_criteria: String::new(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Resource {
+    ///
+    /// Parse the `protocolInfo` value of this resource, see [`ProtocolInfo`].
+    ///
+    pub fn parsed_protocol_info(&self) -> Result<ProtocolInfo, Error> {
+        ProtocolInfo::from_str(&self.protocol_info)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const DIDL_ELEM_ROOT: &str = "DIDL-Lite";
+const DIDL_ELEM_CONTAINER: &str = "container";
+const DIDL_ELEM_ITEM: &str = "item";
+const DIDL_ELEM_RES: &str = "res";
+const DIDL_PROP_TITLE: &str = "title";
+const DIDL_PROP_CLASS: &str = "class";
+const DIDL_PROP_CREATOR: &str = "creator";
+
+impl FromStr for DidlLite {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let root = read_xml(s)?;
+        if root.local_name() != DIDL_ELEM_ROOT {
+            return invalid_field_value(DIDL_ELEM_ROOT, root.name).into();
+        }
+        let mut didl = DidlLite::default();
+        for child in &root.children {
+            match child.local_name() {
+                DIDL_ELEM_CONTAINER => didl.containers.push(Container {
+                    object: object_from(child)?,
+                    child_count: optional_attribute(child, "childCount")?,
+                    searchable: optional_boolean(child, "searchable")?,
+                }),
+                DIDL_ELEM_ITEM => didl.items.push(Item {
+                    object: object_from(child)?,
+                    ref_id: child.attribute("refID").map(str::to_string),
+                }),
+                _ => {}
+            }
+        }
+        Ok(didl)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const ARG_OBJECT_ID: &str = "ObjectID";
+const ARG_CONTAINER_ID: &str = "ContainerID";
+const ARG_BROWSE_FLAG: &str = "BrowseFlag";
+const ARG_SEARCH_CRITERIA: &str = "SearchCriteria";
+const ARG_FILTER: &str = "Filter";
+const ARG_STARTING_INDEX: &str = "StartingIndex";
+const ARG_REQUESTED_COUNT: &str = "RequestedCount";
+const ARG_SORT_CRITERIA: &str = "SortCriteria";
+const ARG_RESULT: &str = "Result";
+const ARG_NUMBER_RETURNED: &str = "NumberReturned";
+const ARG_TOTAL_MATCHES: &str = "TotalMatches";
+const ARG_UPDATE_ID: &str = "UpdateID";
+
+impl ContentDirectory {
+    ///
+    /// The location of the media server's device description.
+    ///
+    pub fn location(&self) -> &URL {
+        &self.location
+    }
+
+    ///
+    /// The client for the `ContentDirectory` service.
+    ///
+    pub fn service(&self) -> &ServiceClient {
+        &self.service
+    }
+
+    pub fn browse(
+        &self,
+        object_id: &str,
+        browse_flag: BrowseFlag,
+        options: &BrowseOptions,
+    ) -> Result<BrowseResult, Error> {
+        let results = self.service.invoke(
+            "Browse",
+            &[
+                (ARG_OBJECT_ID, object_id),
+                (ARG_BROWSE_FLAG, &browse_flag.to_string()),
+                (ARG_FILTER, &options.filter),
+                (ARG_STARTING_INDEX, &options.starting_index.to_string()),
+                (ARG_REQUESTED_COUNT, &options.requested_count.to_string()),
+                (ARG_SORT_CRITERIA, &options.sort_criteria),
+            ],
+        )?;
+        BrowseResult::try_from_arguments(&results)
+    }
+
+    ///
+    /// Browse all the direct children of `object_id` with the default options.
+    ///
+    pub fn browse_children(&self, object_id: &str) -> Result<BrowseResult, Error> {
+        self.browse(
+            object_id,
+            BrowseFlag::DirectChildren,
+            &BrowseOptions::default(),
+        )
+    }
+
+    pub fn search(
+        &self,
+        container_id: &str,
+        search_criteria: &str,
+        options: &BrowseOptions,
+    ) -> Result<BrowseResult, Error> {
+        let results = self.service.invoke(
+            "Search",
+            &[
+                (ARG_CONTAINER_ID, container_id),
+                (ARG_SEARCH_CRITERIA, search_criteria),
+                (ARG_FILTER, &options.filter),
+                (ARG_STARTING_INDEX, &options.starting_index.to_string()),
+                (ARG_REQUESTED_COUNT, &options.requested_count.to_string()),
+                (ARG_SORT_CRITERIA, &options.sort_criteria),
+            ],
+        )?;
+        BrowseResult::try_from_arguments(&results)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl BrowseResult {
+    fn try_from_arguments(arguments: &Arguments) -> Result<Self, Error> {
+        Ok(Self {
+            result: DidlLite::from_str(argument(arguments, ARG_RESULT)?)?,
+            number_returned: parsed_argument(arguments, ARG_NUMBER_RETURNED)?,
+            total_matches: parsed_argument(arguments, ARG_TOTAL_MATCHES)?,
+            update_id: parsed_argument(arguments, ARG_UPDATE_ID)?,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn object_from(element: &Element) -> Result<Object, Error> {
+    let mut object = Object {
+        id: element.attribute("id").unwrap_or_default().to_string(),
+        parent_id: element
+            .attribute("parentID")
+            .unwrap_or_default()
+            .to_string(),
+        restricted: optional_boolean(element, "restricted")?,
+        ..Default::default()
+    };
+    for child in &element.children {
+        match child.local_name() {
+            DIDL_PROP_TITLE => object.title = child.text.clone(),
+            DIDL_PROP_CLASS => object.class = child.text.clone(),
+            DIDL_PROP_CREATOR => object.creator = Some(child.text.clone()),
+            DIDL_ELEM_RES => object.resources.push(Resource {
+                uri: child.text.trim().to_string(),
+                protocol_info: child
+                    .attribute("protocolInfo")
+                    .unwrap_or_default()
+                    .to_string(),
+                size: optional_attribute(child, "size")?,
+                duration: child.attribute("duration").map(str::to_string),
+                resolution: child.attribute("resolution").map(str::to_string),
+                bitrate: optional_attribute(child, "bitrate")?,
+            }),
+            _ => object
+                .properties
+                .push((child.name.clone(), child.text.clone())),
+        }
+    }
+    Ok(object)
+}
+
+fn optional_attribute<T: FromStr>(element: &Element, name: &str) -> Result<Option<T>, Error> {
+    match element.attribute(name) {
+        None => Ok(None),
+        Some(value) => T::from_str(value.trim())
+            .map(Some)
+            .map_err(|_| invalid_field_value(name, value).into()),
+    }
+}
+
+fn optional_boolean(element: &Element, name: &str) -> Result<bool, Error> {
+    match element.attribute(name) {
+        None => Ok(false),
+        Some(value) => Ok(boolean_from_str(value)?),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIDL: &str = r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">
+<container id="64" parentID="0" restricted="1" searchable="1" childCount="3"><dc:title>Music</dc:title><upnp:class>object.container.storageFolder</upnp:class></container>
+<item id="64$0" parentID="64" restricted="1"><dc:title>Track &amp; Field</dc:title><dc:creator>Artist</dc:creator><upnp:class>object.item.audioItem.musicTrack</upnp:class><upnp:album>Album</upnp:album><res size="3558000" duration="0:03:42.000" bitrate="16000" protocolInfo="http-get:*:audio/mpeg:DLNA.ORG_PN=MP3">http://10.0.0.1:8200/MediaItems/22.mp3</res></item>
+</DIDL-Lite>"#;
+
+    #[test]
+    fn test_didl_parse() {
+        let didl = DidlLite::from_str(DIDL).unwrap();
+        assert_eq!(didl.containers.len(), 1);
+        let container = &didl.containers[0];
+        assert_eq!(container.object.title, "Music");
+        assert_eq!(container.child_count, Some(3));
+        assert!(container.searchable);
+
+        assert_eq!(didl.items.len(), 1);
+        let item = &didl.items[0].object;
+        assert_eq!(item.id, "64$0");
+        assert_eq!(item.title, "Track & Field");
+        assert_eq!(item.class, "object.item.audioItem.musicTrack");
+        assert_eq!(item.creator.as_deref(), Some("Artist"));
+        assert_eq!(
+            item.properties,
+            vec![("upnp:album".to_string(), "Album".to_string())]
+        );
+        assert_eq!(
+            item.resources,
+            vec![Resource {
+                uri: "http://10.0.0.1:8200/MediaItems/22.mp3".to_string(),
+                protocol_info: "http-get:*:audio/mpeg:DLNA.ORG_PN=MP3".to_string(),
+                size: Some(3558000),
+                duration: Some("0:03:42.000".to_string()),
+                resolution: None,
+                bitrate: Some(16000),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_browse_result() {
+        let result = BrowseResult::try_from_arguments(&vec![
+            (ARG_RESULT.to_string(), DIDL.to_string()),
+            (ARG_NUMBER_RETURNED.to_string(), "2".to_string()),
+            (ARG_TOTAL_MATCHES.to_string(), "2".to_string()),
+            (ARG_UPDATE_ID.to_string(), "7".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(result.number_returned, 2);
+        assert_eq!(result.update_id, 7);
+        assert_eq!(result.result.items.len(), 1);
+
+        assert!(BrowseResult::try_from_arguments(&vec![(
+            ARG_RESULT.to_string(),
+            "<root/>".to_string()
+        )])
+        .is_err());
+    }
+}
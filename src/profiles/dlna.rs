@@ -0,0 +1,417 @@
+/*!
+This module provides typed values for the DLNA extensions found in nearly all media devices; the
+`dlna:X_DLNADOC` element of a device description that identifies the DLNA device classes a device
+implements, and the `protocolInfo` strings that describe each resource in a `ContentDirectory`
+result, or the formats supported by a `ConnectionManager`.
+
+# Example
+
+```rust
+use upnp_rs::profiles::dlna::{DlnaFlags, ProtocolInfo};
+use std::str::FromStr;
+
+let info = ProtocolInfo::from_str(
+    "http-get:*:audio/mpeg:DLNA.ORG_PN=MP3;DLNA.ORG_OP=01;DLNA.ORG_FLAGS=01700000000000000000000000000000",
+)
+.unwrap();
+assert_eq!(info.content_format, "audio/mpeg");
+assert_eq!(info.additional_info.profile_name(), Some("MP3"));
+assert!(info.additional_info.operations().unwrap().byte_seek);
+assert!(info.additional_info.flags().unwrap().contains(DlnaFlags::STREAMING_TRANSFER));
+```
+*/
+
+use crate::description::device::Device;
+use crate::error::{invalid_field_value, Error};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A DLNA device class and guidelines version, as in the `dlna:X_DLNADOC` value `DMS-1.50`.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DlnaDoc {
+    /// The device class, for example `DMS` (media server) or `DMR` (media renderer); a mobile
+    /// device class is prefixed with `M-`.
+    pub device_class: String,
+    /// The guidelines version, for example `1.50`.
+    pub version: String,
+}
+
+///
+/// A `protocolInfo` value, `<protocol>:<network>:<contentFormat>:<additionalInfo>`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolInfo {
+    /// The transport protocol, for example `http-get` or `rtsp-rtp-udp`.
+    pub protocol: String,
+    /// The network, usually `*`.
+    pub network: String,
+    /// The MIME type of the content, for example `audio/mpeg`.
+    pub content_format: String,
+    pub additional_info: AdditionalInfo,
+}
+
+///
+/// The fourth field of a `protocolInfo` value; `*`, or a list of `;` separated `name=value`
+/// parameters, which for DLNA content are the `DLNA.ORG_` parameters.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdditionalInfo {
+    /// The parameters, as name and value, in the order they appeared.
+    pub parameters: Vec<(String, String)>,
+}
+
+///
+/// The seek operations supported by the server for a resource, from the `DLNA.ORG_OP` parameter.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Operations {
+    /// Seek using the `TimeSeekRange.dlna.org` header.
+    pub time_seek: bool,
+    /// Seek using the HTTP `Range` header.
+    pub byte_seek: bool,
+}
+
+///
+/// The primary flags from the `DLNA.ORG_FLAGS` parameter.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DlnaFlags(u32);
+
+///
+/// The namespace of the `X_DLNADOC` and `X_DLNACAP` elements.
+///
+pub const DLNA_DEVICE_NAMESPACE: &str = "urn:schemas-dlna-org:device-1-0";
+
+///
+/// The local name of the device description element that lists DLNA device classes.
+///
+pub const DLNA_ELEM_X_DLNADOC: &str = "X_DLNADOC";
+
+pub const DLNA_PARAM_PROFILE_NAME: &str = "DLNA.ORG_PN";
+pub const DLNA_PARAM_OPERATIONS: &str = "DLNA.ORG_OP";
+pub const DLNA_PARAM_PLAY_SPEED: &str = "DLNA.ORG_PS";
+pub const DLNA_PARAM_CONVERSION: &str = "DLNA.ORG_CI";
+pub const DLNA_PARAM_FLAGS: &str = "DLNA.ORG_FLAGS";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the DLNA device classes declared by `X_DLNADOC` extension elements of `device`; a
+/// value that cannot be parsed is ignored.
+///
+pub fn dlna_docs(device: &Device) -> Vec<DlnaDoc> {
+    device
+        .extensions
+        .iter()
+        .map(|extension| extension.element())
+        .filter(|element| {
+            element.local_name() == DLNA_ELEM_X_DLNADOC
+                && element.namespace.as_deref() == Some(DLNA_DEVICE_NAMESPACE)
+        })
+        .flat_map(|element| element.text.split(','))
+        .filter_map(|value| DlnaDoc::from_str(value).ok())
+        .collect()
+}
+
+///
+/// Parse a comma-separated list of `protocolInfo` values, as returned by the `GetProtocolInfo`
+/// action of a `ConnectionManager` service.
+///
+pub fn parse_protocol_info_list(s: &str) -> Result<Vec<ProtocolInfo>, Error> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(ProtocolInfo::from_str)
+        .collect()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for DlnaDoc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}-{}", self.device_class, self.version)
+    }
+}
+
+impl FromStr for DlnaDoc {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().rsplit_once('-') {
+            Some((device_class, version))
+                if !device_class.is_empty()
+                    && !version.is_empty()
+                    && version.chars().all(|c| c.is_ascii_digit() || c == '.') =>
+            {
+                Ok(Self {
+                    device_class: device_class.to_string(),
+                    version: version.to_string(),
+                })
+            }
+            _ => invalid_field_value(DLNA_ELEM_X_DLNADOC, s).into(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for ProtocolInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.protocol, self.network, self.content_format, self.additional_info
+        )
+    }
+}
+
+impl FromStr for ProtocolInfo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.trim().splitn(4, ':').collect();
+        match fields[..] {
+            [protocol, network, content_format, additional_info] if !protocol.is_empty() => {
+                Ok(Self {
+                    protocol: protocol.to_string(),
+                    network: network.to_string(),
+                    content_format: content_format.to_string(),
+                    additional_info: AdditionalInfo::from_str(additional_info)?,
+                })
+            }
+            _ => invalid_field_value("protocolInfo", s).into(),
+        }
+    }
+}
+
+impl ProtocolInfo {
+    ///
+    /// Returns `true` if a resource described by `self` may be sent to a renderer that accepts
+    /// `sink`; the protocol and content format must match, `*` matches any value, and where both
+    /// name a DLNA profile the profiles must match.
+    ///
+    pub fn matches(&self, sink: &ProtocolInfo) -> bool {
+        let field = |a: &str, b: &str| a == "*" || b == "*" || a.eq_ignore_ascii_case(b);
+        field(&self.protocol, &sink.protocol)
+            && field(&self.network, &sink.network)
+            && field(&self.content_format, &sink.content_format)
+            && match (
+                self.additional_info.profile_name(),
+                sink.additional_info.profile_name(),
+            ) {
+                (Some(source), Some(sink)) => source == sink,
+                _ => true,
+            }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for AdditionalInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if self.parameters.is_empty() {
+            write!(f, "*")
+        } else {
+            write!(
+                f,
+                "{}",
+                self.parameters
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<String>>()
+                    .join(";")
+            )
+        }
+    }
+}
+
+impl FromStr for AdditionalInfo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" || s.is_empty() {
+            return Ok(Self::default());
+        }
+        s.split(';')
+            .filter(|parameter| !parameter.is_empty())
+            .map(|parameter| match parameter.split_once('=') {
+                Some((name, value)) if !name.is_empty() => {
+                    Ok((name.to_string(), value.to_string()))
+                }
+                _ => invalid_field_value("additionalInfo", parameter).into(),
+            })
+            .collect::<Result<Vec<(String, String)>, Error>>()
+            .map(|parameters| Self { parameters })
+    }
+}
+
+impl AdditionalInfo {
+    ///
+    /// Return the value of the named parameter, if present.
+    ///
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    ///
+    /// The DLNA media format profile, for example `MP3` or `AVC_MP4_MP_SD_AAC_MULT5`.
+    ///
+    pub fn profile_name(&self) -> Option<&str> {
+        self.parameter(DLNA_PARAM_PROFILE_NAME)
+    }
+
+    ///
+    /// The seek operations supported, if the `DLNA.ORG_OP` parameter is present and valid.
+    ///
+    pub fn operations(&self) -> Option<Operations> {
+        let value = self.parameter(DLNA_PARAM_OPERATIONS)?;
+        let mut digits = value.chars();
+        match (digits.next(), digits.next(), digits.next()) {
+            (Some(time_seek @ ('0' | '1')), Some(byte_seek @ ('0' | '1')), None) => {
+                Some(Operations {
+                    time_seek: time_seek == '1',
+                    byte_seek: byte_seek == '1',
+                })
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns `Some(true)` if the content is converted (transcoded) from another format, from
+    /// the `DLNA.ORG_CI` parameter.
+    ///
+    pub fn is_converted(&self) -> Option<bool> {
+        match self.parameter(DLNA_PARAM_CONVERSION)? {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The primary flags, if the `DLNA.ORG_FLAGS` parameter is present and valid; the parameter
+    /// is 32 hexadecimal digits of which only the first 8 are defined.
+    ///
+    pub fn flags(&self) -> Option<DlnaFlags> {
+        let value = self.parameter(DLNA_PARAM_FLAGS)?;
+        if value.len() != 32 {
+            return None;
+        }
+        u32::from_str_radix(value.get(..8)?, 16).ok().map(DlnaFlags)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl DlnaFlags {
+    pub const SENDER_PACED: DlnaFlags = DlnaFlags(1 << 31);
+    pub const TIME_BASED_SEEK: DlnaFlags = DlnaFlags(1 << 30);
+    pub const BYTE_BASED_SEEK: DlnaFlags = DlnaFlags(1 << 29);
+    pub const PLAY_CONTAINER: DlnaFlags = DlnaFlags(1 << 28);
+    pub const S0_INCREASING: DlnaFlags = DlnaFlags(1 << 27);
+    pub const SN_INCREASING: DlnaFlags = DlnaFlags(1 << 26);
+    pub const RTSP_PAUSE: DlnaFlags = DlnaFlags(1 << 25);
+    pub const STREAMING_TRANSFER: DlnaFlags = DlnaFlags(1 << 24);
+    pub const INTERACTIVE_TRANSFER: DlnaFlags = DlnaFlags(1 << 23);
+    pub const BACKGROUND_TRANSFER: DlnaFlags = DlnaFlags(1 << 22);
+    pub const CONNECTION_STALLING: DlnaFlags = DlnaFlags(1 << 21);
+    pub const DLNA_V15: DlnaFlags = DlnaFlags(1 << 20);
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    ///
+    /// Returns `true` if all the flags in `other` are set.
+    ///
+    pub fn contains(&self, other: DlnaFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Display for DlnaFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{:08X}{:024}", self.0, 0)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::description::device::DeviceRoot;
+
+    #[test]
+    fn test_protocol_info_round_trip() {
+        for value in [
+            "http-get:*:audio/mpeg:*",
+            "http-get:*:video/mp4:DLNA.ORG_PN=AVC_MP4_MP_SD_AAC_MULT5;DLNA.ORG_OP=11;DLNA.ORG_CI=1",
+            "rtsp-rtp-udp:*:video/x-ms-wmv:*",
+        ] {
+            assert_eq!(ProtocolInfo::from_str(value).unwrap().to_string(), value);
+        }
+        assert!(ProtocolInfo::from_str("http-get:*:audio/mpeg").is_err());
+        assert!(ProtocolInfo::from_str("http-get:*:audio/mpeg:DLNA.ORG_PN").is_err());
+    }
+
+    #[test]
+    fn test_additional_info() {
+        let info = AdditionalInfo::from_str(
+            "DLNA.ORG_PN=MP3;DLNA.ORG_OP=10;DLNA.ORG_CI=0;DLNA.ORG_FLAGS=81500000000000000000000000000000",
+        )
+        .unwrap();
+        assert_eq!(
+            info.operations(),
+            Some(Operations {
+                time_seek: true,
+                byte_seek: false
+            })
+        );
+        assert_eq!(info.is_converted(), Some(false));
+        let flags = info.flags().unwrap();
+        assert!(flags.contains(DlnaFlags::SENDER_PACED));
+        assert!(flags.contains(DlnaFlags::DLNA_V15));
+        assert!(!flags.contains(DlnaFlags::TIME_BASED_SEEK));
+        assert_eq!(flags.to_string(), "81500000000000000000000000000000");
+    }
+
+    #[test]
+    fn test_matches() {
+        let source = ProtocolInfo::from_str("http-get:*:audio/mpeg:DLNA.ORG_PN=MP3").unwrap();
+        let sinks = parse_protocol_info_list(
+            "http-get:*:audio/L16:*, http-get:*:audio/mpeg:DLNA.ORG_PN=MP3X",
+        )
+        .unwrap();
+        assert!(!source.matches(&sinks[0]));
+        assert!(!source.matches(&sinks[1]));
+        assert!(source.matches(&ProtocolInfo::from_str("http-get:*:*:*").unwrap()));
+    }
+
+    #[test]
+    fn test_dlna_docs() {
+        let root = DeviceRoot::from_str("<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\" xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test</UDN><dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC><dlna:X_DLNADOC>M-DMS-1.50</dlna:X_DLNADOC><dlna:X_DLNACAP>av-upload</dlna:X_DLNACAP></device></root>").unwrap();
+        let docs = dlna_docs(&root.device);
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].device_class, "DMS");
+        assert_eq!(docs[1].device_class, "M-DMS");
+        assert_eq!(docs[1].to_string(), "M-DMS-1.50");
+    }
+}
@@ -0,0 +1,410 @@
+/*!
+This module provides a client for the _Internet Gateway Device_ (IGD) profile, specifically the
+port mapping actions of the `WANIPConnection` and `WANPPPConnection` services commonly used for
+NAT traversal.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::search::Options;
+use upnp_rs::profiles::igd::{discover, PortMapping, Protocol};
+use upnp_rs::SpecVersion;
+
+let gateways = discover(Options::default_for(SpecVersion::V10)).unwrap();
+if let Some(gateway) = gateways.first() {
+    println!("external address: {}", gateway.get_external_ip_address().unwrap());
+    gateway
+        .add_port_mapping(&PortMapping::new(
+            8080,
+            Protocol::Tcp,
+            8080,
+            "192.168.1.10",
+            "example mapping",
+        ))
+        .unwrap();
+}
+```
+*/
+
+use crate::common::data_types::{boolean_from_str, boolean_to_string};
+use crate::common::http::HttpClient;
+use crate::common::uri::URL;
+use crate::control::{argument, parsed_argument, ServiceClient, UpnpError};
+use crate::description::device::DeviceRoot;
+use crate::description::well_known::{wan_ip_connection, wan_ppp_connection};
+use crate::error::{invalid_field_value, Error};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[cfg(feature = "net")]
+use crate::common::http::{default_client, fetch};
+#[cfg(feature = "net")]
+use crate::discovery::search::{search_once, Options, SearchTarget};
+#[cfg(feature = "net")]
+use std::collections::HashSet;
+#[cfg(feature = "net")]
+use tracing::{error, info};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The transport protocol of a port mapping.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+///
+/// A single port mapping entry, as added by `AddPortMapping` or returned by
+/// `GetGenericPortMappingEntry`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortMapping {
+    /// The remote host the mapping applies to, `None` denotes any host.
+    pub remote_host: Option<String>,
+    pub external_port: u16,
+    pub protocol: Protocol,
+    pub internal_port: u16,
+    /// The address of the internal client the traffic is forwarded to.
+    pub internal_client: String,
+    pub enabled: bool,
+    pub description: String,
+    /// The lease duration in seconds, `0` denotes a static mapping.
+    pub lease_duration: u32,
+}
+
+///
+/// A client for the connection service of a single Internet Gateway Device.
+///
+#[derive(Clone, Debug)]
+pub struct Gateway {
+    location: URL,
+    service: ServiceClient,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Search for gateways that provide either a `WANIPConnection` or `WANPPPConnection` service and
+/// return a client for each. The search target in `options` is replaced; devices whose
+/// description cannot be retrieved are logged and skipped.
+///
+#[cfg(feature = "net")]
+pub fn discover(options: Options) -> Result<Vec<Gateway>, Error> {
+    let mut locations: Vec<URL> = Default::default();
+    let mut seen: HashSet<String> = Default::default();
+    for service_type in &[wan_ip_connection(1), wan_ppp_connection(1)] {
+        let mut options = options.clone();
+        options.search_target = SearchTarget::from(service_type);
+        for response in search_once(options)? {
+            if seen.insert(response.location.to_string()) {
+                locations.push(response.location);
+            }
+        }
+    }
+    info!("discover - found {} candidate gateways", locations.len());
+    Ok(locations
+        .into_iter()
+        .filter_map(|location| match from_location(location.clone()) {
+            Ok(gateway) => Some(gateway),
+            Err(e) => {
+                error!("discover - ignoring gateway at {}: {:?}", location, e);
+                None
+            }
+        })
+        .collect())
+}
+
+///
+/// Retrieve the device description at `location` and return a client for its `WANIPConnection`
+/// service or, if not present, its `WANPPPConnection` service.
+///
+#[cfg(feature = "net")]
+pub fn from_location(location: URL) -> Result<Gateway, Error> {
+    let root: DeviceRoot = fetch(location.to_string())?;
+    from_description(&root, location)
+}
+
+///
+/// Return a client for the `WANIPConnection` or `WANPPPConnection` service in the provided
+/// description, retrieved from `location`.
+///
+#[cfg(feature = "net")]
+pub fn from_description(root: &DeviceRoot, location: URL) -> Result<Gateway, Error> {
+    from_description_with(root, location, default_client())
+}
+
+///
+/// As [`from_description`], returning a client that sends requests using `client`.
+///
+pub fn from_description_with(
+    root: &DeviceRoot,
+    location: URL,
+    client: Arc<dyn HttpClient>,
+) -> Result<Gateway, Error> {
+    let service =
+        ServiceClient::for_service_with(root, &location, &wan_ip_connection(1), client.clone())
+            .or_else(|_| {
+                ServiceClient::for_service_with(root, &location, &wan_ppp_connection(1), client)
+            })?;
+    Ok(Gateway { location, service })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Protocol::Tcp => "TCP",
+                Protocol::Udp => "UDP",
+            }
+        )
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "TCP" => Ok(Protocol::Tcp),
+            "UDP" => Ok(Protocol::Udp),
+            _ => invalid_field_value(ARG_PROTOCOL, s).into(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl PortMapping {
+    ///
+    /// Construct an enabled, permanent, mapping from any remote host.
+    ///
+    pub fn new(
+        external_port: u16,
+        protocol: Protocol,
+        internal_port: u16,
+        internal_client: &str,
+        description: &str,
+    ) -> Self {
+        Self {
+            remote_host: None,
+            external_port,
+            protocol,
+            internal_port,
+            internal_client: internal_client.to_string(),
+            enabled: true,
+            description: description.to_string(),
+            lease_duration: 0,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const ARG_REMOTE_HOST: &str = "NewRemoteHost";
+const ARG_EXTERNAL_PORT: &str = "NewExternalPort";
+const ARG_PROTOCOL: &str = "NewProtocol";
+const ARG_INTERNAL_PORT: &str = "NewInternalPort";
+const ARG_INTERNAL_CLIENT: &str = "NewInternalClient";
+const ARG_ENABLED: &str = "NewEnabled";
+const ARG_DESCRIPTION: &str = "NewPortMappingDescription";
+const ARG_LEASE_DURATION: &str = "NewLeaseDuration";
+const ARG_INDEX: &str = "NewPortMappingIndex";
+const ARG_EXTERNAL_IP_ADDRESS: &str = "NewExternalIPAddress";
+
+const ERROR_ARRAY_INDEX_INVALID: UpnpError = UpnpError::ServiceSpecific(713);
+
+impl Gateway {
+    ///
+    /// The location of the gateway's device description.
+    ///
+    pub fn location(&self) -> &URL {
+        &self.location
+    }
+
+    ///
+    /// The client for the connection service in use.
+    ///
+    pub fn service(&self) -> &ServiceClient {
+        &self.service
+    }
+
+    pub fn get_external_ip_address(&self) -> Result<IpAddr, Error> {
+        let results = self.service.invoke("GetExternalIPAddress", &[])?;
+        let address = argument(&results, ARG_EXTERNAL_IP_ADDRESS)?;
+        IpAddr::from_str(address.trim())
+            .map_err(|_| invalid_field_value(ARG_EXTERNAL_IP_ADDRESS, address).into())
+    }
+
+    pub fn add_port_mapping(&self, mapping: &PortMapping) -> Result<(), Error> {
+        let _ = self.service.invoke(
+            "AddPortMapping",
+            &[
+                (
+                    ARG_REMOTE_HOST,
+                    mapping.remote_host.as_deref().unwrap_or_default(),
+                ),
+                (ARG_EXTERNAL_PORT, &mapping.external_port.to_string()),
+                (ARG_PROTOCOL, &mapping.protocol.to_string()),
+                (ARG_INTERNAL_PORT, &mapping.internal_port.to_string()),
+                (ARG_INTERNAL_CLIENT, &mapping.internal_client),
+                (ARG_ENABLED, &boolean_to_string(mapping.enabled)),
+                (ARG_DESCRIPTION, &mapping.description),
+                (ARG_LEASE_DURATION, &mapping.lease_duration.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_port_mapping(
+        &self,
+        remote_host: Option<&str>,
+        external_port: u16,
+        protocol: Protocol,
+    ) -> Result<(), Error> {
+        let _ = self.service.invoke(
+            "DeletePortMapping",
+            &[
+                (ARG_REMOTE_HOST, remote_host.unwrap_or_default()),
+                (ARG_EXTERNAL_PORT, &external_port.to_string()),
+                (ARG_PROTOCOL, &protocol.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_generic_port_mapping_entry(&self, index: u16) -> Result<PortMapping, Error> {
+        let results = self.service.invoke(
+            "GetGenericPortMappingEntry",
+            &[(ARG_INDEX, &index.to_string())],
+        )?;
+        let remote_host = argument(&results, ARG_REMOTE_HOST)?.trim();
+        Ok(PortMapping {
+            remote_host: if remote_host.is_empty() {
+                None
+            } else {
+                Some(remote_host.to_string())
+            },
+            external_port: parsed_argument(&results, ARG_EXTERNAL_PORT)?,
+            protocol: Protocol::from_str(argument(&results, ARG_PROTOCOL)?)?,
+            internal_port: parsed_argument(&results, ARG_INTERNAL_PORT)?,
+            internal_client: argument(&results, ARG_INTERNAL_CLIENT)?.to_string(),
+            enabled: boolean_from_str(argument(&results, ARG_ENABLED)?)?,
+            description: argument(&results, ARG_DESCRIPTION)?.to_string(),
+            lease_duration: parsed_argument(&results, ARG_LEASE_DURATION)?,
+        })
+    }
+
+    ///
+    /// Return all port mappings, by calling `GetGenericPortMappingEntry` with increasing indices
+    /// until the gateway reports that the index is invalid.
+    ///
+    pub fn port_mappings(&self) -> Result<Vec<PortMapping>, Error> {
+        let mut mappings = Vec::new();
+        for index in 0..=u16::MAX {
+            match self.get_generic_port_mapping_entry(index) {
+                Ok(mapping) => mappings.push(mapping),
+                Err(e) if e.upnp_error() == Some(ERROR_ARRAY_INDEX_INVALID) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(mappings)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use crate::common::soap::{Action, Envelope};
+    use crate::emulation::{from_snapshot_with, Options as EmulatorOptions};
+    use crate::snapshot::DeviceSnapshot;
+    use std::time::UNIX_EPOCH;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType><friendlyName>Gateway</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:gateway</UDN><deviceList><device><deviceType>urn:schemas-upnp-org:device:WANDevice:1</deviceType><friendlyName>WAN</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:wan</UDN><deviceList><device><deviceType>urn:schemas-upnp-org:device:WANConnectionDevice:1</deviceType><friendlyName>WAN Connection</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:wan-connection</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><serviceId>urn:upnp-org:serviceId:WANIPConn1</serviceId><SCPDURL>/ipc.xml</SCPDURL><controlURL>/ipc/control</controlURL><eventSubURL>/ipc/event</eventSubURL></service></serviceList></device></deviceList></device></deviceList></device></root>";
+
+    fn response(action: &str, arguments: &[(&str, &str)]) -> String {
+        Envelope::new_response(
+            Action::new(wan_ip_connection(1), action.to_string()),
+            arguments
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+        .to_string()
+    }
+
+    #[test]
+    fn test_gateway_actions() {
+        let device = from_snapshot_with(
+            DeviceSnapshot {
+                location: URL::from_str("http://10.0.0.1:5000/rootDesc.xml").unwrap(),
+                captured: UNIX_EPOCH,
+                description: DESCRIPTION.to_string(),
+                services: Vec::new(),
+            },
+            EmulatorOptions {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let action = |name: &str| format!("{}#{}", wan_ip_connection(1), name);
+        device.add_canned_response(
+            "/ipc/control",
+            &action("GetExternalIPAddress"),
+            &response(
+                "GetExternalIPAddress",
+                &[(ARG_EXTERNAL_IP_ADDRESS, "203.0.113.7")],
+            ),
+        );
+        device.add_canned_response(
+            "/ipc/control",
+            &action("GetGenericPortMappingEntry"),
+            &response(
+                "GetGenericPortMappingEntry",
+                &[
+                    (ARG_REMOTE_HOST, ""),
+                    (ARG_EXTERNAL_PORT, "8080"),
+                    (ARG_PROTOCOL, "TCP"),
+                    (ARG_INTERNAL_PORT, "80"),
+                    (ARG_INTERNAL_CLIENT, "192.168.1.10"),
+                    (ARG_ENABLED, "1"),
+                    (ARG_DESCRIPTION, "web"),
+                    (ARG_LEASE_DURATION, "0"),
+                ],
+            ),
+        );
+
+        let gateway = from_location(device.location()).unwrap();
+        assert_eq!(
+            gateway.get_external_ip_address().unwrap().to_string(),
+            "203.0.113.7"
+        );
+        assert_eq!(
+            gateway.get_generic_port_mapping_entry(0).unwrap(),
+            PortMapping::new(8080, Protocol::Tcp, 80, "192.168.1.10", "web")
+        );
+        match gateway.delete_port_mapping(None, 8080, Protocol::Tcp) {
+            Err(e) => assert_eq!(e.upnp_error(), Some(UpnpError::InvalidAction)),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+/*!
+This module provides strongly-typed clients for commonly used device and service profiles defined
+by the UPnP Forum. Each client wraps the generic [`control`](../control/index.html) client, taking
+care of discovery, description, and the conversion of action arguments to and from Rust types.
+*/
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+pub mod av_transport;
+
+pub mod content_directory;
+
+pub mod dlna;
+
+pub mod igd;
@@ -0,0 +1,570 @@
+/*!
+This module provides the ability to capture the complete description of a device, the device
+description document and the service description (SCPD) of every service it, and any embedded
+device, provides. A snapshot retains the original documents exactly as they were received so that
+it can be saved to a single archive file for offline analysis or attached to bug reports.
+
+By default only the documents are captured. Setting `state_listener` in the [`Options`] passed to
+[`capture_with_options`] also captures the current values of evented state variables; each service
+with evented variables is subscribed to, the initial event is taken as its state, and the
+subscription is then cancelled. This requires the `net` feature, and the listener address must be
+reachable by the device.
+
+# Example
+
+```rust,no_run
+use upnp_rs::snapshot::{capture, DeviceSnapshot};
+use std::str::FromStr;
+use upnp_rs::common::uri::URL;
+
+let snapshot = capture(URL::from_str("http://10.0.0.1:49152/description.xml").unwrap()).unwrap();
+snapshot.save("device.snapshot").unwrap();
+
+let reloaded = DeviceSnapshot::load("device.snapshot").unwrap();
+println!("{:#?}", reloaded.device().unwrap());
+```
+*/
+
+use crate::common::http::{fetch_string_with, HttpClient};
+use crate::common::uri::URL;
+use crate::description::device::{DeviceRoot, Service};
+use crate::description::service::Spcd;
+use crate::description::ServiceId;
+use crate::error::{invalid_value_for_type, Error};
+use crate::eventing::PropertySet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+#[cfg(feature = "net")]
+use crate::common::http::default_client;
+#[cfg(feature = "net")]
+use crate::eventing::client::{subscribe_with, unsubscribe_with};
+#[cfg(feature = "net")]
+use crate::eventing::listener::{EventListener, Options as ListenerOptions};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The captured service description for a single service.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceSnapshot {
+    /// The `serviceId` of the service within the device description.
+    pub service_id: ServiceId,
+    /// The absolute URL the SCPD was retrieved from.
+    pub scpd_url: URL,
+    /// The SCPD document, exactly as received.
+    pub xml: String,
+    /// The values of the service's evented state variables, as sent in the initial event, or
+    /// `None` if state was not captured.
+    pub state: Option<PropertySet>,
+}
+
+///
+/// The captured state of a device, its description and all its service descriptions.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    /// The location the device description was retrieved from.
+    pub location: URL,
+    /// The time at which the snapshot was captured.
+    pub captured: SystemTime,
+    /// The device description document, exactly as received.
+    pub description: String,
+    /// The service descriptions for all services of the root and any embedded devices.
+    pub services: Vec<ServiceSnapshot>,
+}
+
+///
+/// This type encapsulates the set of values used to configure a capture.
+///
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// If set, the local address of an event listener used to capture the values of evented
+    /// state variables; this must be reachable by the device. Default: `None`.
+    pub state_listener: Option<SocketAddr>,
+    /// The time to wait for the initial event from each service. Default: `5` seconds.
+    pub state_timeout: Duration,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Capture a snapshot of the device whose description is at `location`.
+///
+#[cfg(feature = "net")]
+pub fn capture(location: URL) -> Result<DeviceSnapshot, Error> {
+    capture_with(location, default_client().as_ref())
+}
+
+///
+/// Capture a snapshot of the device whose description is at `location`, using the provided
+/// HTTP client.
+///
+pub fn capture_with(location: URL, client: &dyn HttpClient) -> Result<DeviceSnapshot, Error> {
+    capture_with_options(location, client, &Default::default())
+}
+
+///
+/// Capture a snapshot of the device whose description is at `location`, using the provided
+/// HTTP client and options.
+///
+pub fn capture_with_options(
+    location: URL,
+    client: &dyn HttpClient,
+    options: &Options,
+) -> Result<DeviceSnapshot, Error> {
+    info!("capture_with - location: {}", location);
+    let description = fetch_string_with(location.to_string(), client)?;
+    let root = DeviceRoot::from_str(&description)?;
+
+    let base = root.base_url(&location);
+
+    let mut services = Vec::new();
+    for device in root.device.all_devices() {
+        for service in &device.service_list {
+            let scpd_url = base.resolve(&service.scpd_url)?;
+            info!(
+                "capture_with - fetching SCPD for {} from {}",
+                service.service_id, scpd_url
+            );
+            let xml = fetch_string_with(scpd_url.to_string(), client)?;
+            let state = match options.state_listener {
+                Some(address) => capture_state(service, &base, &xml, address, options, client),
+                None => None,
+            };
+            services.push(ServiceSnapshot {
+                service_id: service.service_id.clone(),
+                scpd_url,
+                xml,
+                state,
+            });
+        }
+    }
+
+    Ok(DeviceSnapshot {
+        location,
+        captured: SystemTime::now(),
+        description,
+        services,
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+const ARCHIVE_MAGIC: &str = "UPNP-SNAPSHOT/1";
+const ARCHIVE_LOCATION: &str = "LOCATION: ";
+const ARCHIVE_CAPTURED: &str = "CAPTURED: ";
+const ARCHIVE_DESCRIPTION: &str = "DESCRIPTION: ";
+const ARCHIVE_SERVICE: &str = "SERVICE: ";
+const ARCHIVE_STATE: &str = "STATE: ";
+
+impl DeviceSnapshot {
+    ///
+    /// Parse the captured device description.
+    ///
+    pub fn device(&self) -> Result<DeviceRoot, Error> {
+        DeviceRoot::from_str(&self.description)
+    }
+
+    ///
+    /// Return the captured service description for the given `serviceId`, if present.
+    ///
+    pub fn service(&self, service_id: &ServiceId) -> Option<&ServiceSnapshot> {
+        self.services.iter().find(|s| &s.service_id == service_id)
+    }
+
+    ///
+    /// Save this snapshot as an archive file at `path`.
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    ///
+    /// Load a snapshot from the archive file at `path`.
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    ///
+    /// Write this snapshot in archive form. The archive is a simple text format, a set of header
+    /// lines followed by each document prefixed by a line containing its length in bytes. The
+    /// capture time is written as seconds since the Unix epoch with a nanosecond fraction.
+    ///
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "{}", ARCHIVE_MAGIC)?;
+        writeln!(writer, "{}{}", ARCHIVE_LOCATION, self.location)?;
+        let captured = self.captured.duration_since(UNIX_EPOCH).unwrap_or_default();
+        writeln!(
+            writer,
+            "{}{}.{:09}",
+            ARCHIVE_CAPTURED,
+            captured.as_secs(),
+            captured.subsec_nanos()
+        )?;
+        writeln!(writer, "{}{}", ARCHIVE_DESCRIPTION, self.description.len())?;
+        writeln!(writer, "{}", self.description)?;
+        for service in &self.services {
+            writeln!(
+                writer,
+                "{}{} {} {}",
+                ARCHIVE_SERVICE,
+                service.xml.len(),
+                service.service_id,
+                service.scpd_url
+            )?;
+            writeln!(writer, "{}", service.xml)?;
+            if let Some(state) = &service.state {
+                let state = state.to_string();
+                writeln!(writer, "{}{}", ARCHIVE_STATE, state.len())?;
+                writeln!(writer, "{}", state)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Read a snapshot from its archive form, as written by `write_to`.
+    ///
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut archive = String::new();
+        let _ = reader.read_to_string(&mut archive)?;
+        let mut remaining = archive.as_str();
+
+        if next_line(&mut remaining)? != ARCHIVE_MAGIC {
+            error!("read_from - not a snapshot archive");
+            return invalid_value_for_type("DeviceSnapshot", ARCHIVE_MAGIC).into();
+        }
+        let location = URL::from_str(header_value(&mut remaining, ARCHIVE_LOCATION)?).unwrap();
+        let captured =
+            UNIX_EPOCH + parse_captured(header_value(&mut remaining, ARCHIVE_CAPTURED)?)?;
+        let length = parse_number(header_value(&mut remaining, ARCHIVE_DESCRIPTION)?)?;
+        let description = next_document(&mut remaining, length)?;
+
+        let mut services = Vec::new();
+        while !remaining.is_empty() {
+            let values: Vec<&str> = header_value(&mut remaining, ARCHIVE_SERVICE)?
+                .splitn(3, ' ')
+                .collect();
+            if values.len() != 3 {
+                return invalid_value_for_type("DeviceSnapshot", values.join(" ")).into();
+            }
+            let length = parse_number(values[0])?;
+            let xml = next_document(&mut remaining, length)?;
+            let state = if remaining.starts_with(ARCHIVE_STATE) {
+                let length = parse_number(header_value(&mut remaining, ARCHIVE_STATE)?)?;
+                let state = next_document(&mut remaining, length)?;
+                Some(PropertySet::from_str(&state)?)
+            } else {
+                None
+            };
+            services.push(ServiceSnapshot {
+                service_id: ServiceId::from_str(values[1])
+                    .map_err(|_| invalid_value_for_type("DeviceSnapshot", values[1]))?,
+                scpd_url: URL::from_str(values[2]).unwrap(),
+                xml,
+                state,
+            });
+        }
+
+        Ok(DeviceSnapshot {
+            location,
+            captured,
+            description,
+            services,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ServiceSnapshot {
+    ///
+    /// Parse the captured service description.
+    ///
+    pub fn spcd(&self) -> Result<Spcd, Error> {
+        Spcd::from_str(&self.xml)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            state_listener: None,
+            state_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Values
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+const STATE_CALLBACK_PATH: &str = "snapshot";
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "net")]
+fn capture_state(
+    service: &Service,
+    base: &URL,
+    xml: &str,
+    address: SocketAddr,
+    options: &Options,
+    client: &dyn HttpClient,
+) -> Option<PropertySet> {
+    let evented = Spcd::from_str(xml)
+        .map(|spcd| spcd.service_state_table.iter().any(|v| v.send_events))
+        .unwrap_or_default();
+    if !evented || service.event_sub_url.is_empty() {
+        return None;
+    }
+    match subscribe_for_state(service, base, address, options, client) {
+        Ok(None) => {
+            warn!(
+                "capture_state - no initial event from {}",
+                service.service_id
+            );
+            None
+        }
+        Ok(state) => state,
+        Err(e) => {
+            warn!(
+                "capture_state - could not capture state of {}: {}",
+                service.service_id, e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "net"))]
+fn capture_state(
+    service: &Service,
+    _: &URL,
+    _: &str,
+    _: SocketAddr,
+    _: &Options,
+    _: &dyn HttpClient,
+) -> Option<PropertySet> {
+    warn!(
+        "capture_state - capturing the state of {} requires the net feature",
+        service.service_id
+    );
+    None
+}
+
+#[cfg(feature = "net")]
+fn subscribe_for_state(
+    service: &Service,
+    base: &URL,
+    address: SocketAddr,
+    options: &Options,
+    client: &dyn HttpClient,
+) -> Result<Option<PropertySet>, Error> {
+    let listener = EventListener::bind(ListenerOptions {
+        address,
+        buffer_size: 1,
+        ..Default::default()
+    })?;
+    let events = listener.events_for(STATE_CALLBACK_PATH);
+    let subscription = subscribe_with(
+        &base.resolve(&service.event_sub_url)?,
+        &[listener.callback_url(STATE_CALLBACK_PATH)],
+        None,
+        client,
+    )?;
+    let event = events.next_timeout(options.state_timeout);
+    if let Err(e) = unsubscribe_with(&subscription, client) {
+        warn!(
+            "subscribe_for_state - could not unsubscribe from {}: {}",
+            service.service_id, e
+        );
+    }
+    Ok(event.map(|event| event.properties))
+}
+
+fn next_line<'a>(remaining: &mut &'a str) -> Result<&'a str, Error> {
+    match remaining.split_once('\n') {
+        Some((line, rest)) => {
+            *remaining = rest;
+            Ok(line)
+        }
+        None => invalid_value_for_type("DeviceSnapshot", *remaining).into(),
+    }
+}
+
+fn header_value<'a>(remaining: &mut &'a str, name: &str) -> Result<&'a str, Error> {
+    let line = next_line(remaining)?;
+    match line.strip_prefix(name) {
+        Some(value) => Ok(value),
+        None => {
+            error!("header_value - expecting '{}', found '{}'", name, line);
+            invalid_value_for_type("DeviceSnapshot", line).into()
+        }
+    }
+}
+
+fn next_document(remaining: &mut &str, length: usize) -> Result<String, Error> {
+    if remaining.len() > length && remaining.is_char_boundary(length) {
+        let (document, rest) = remaining.split_at(length);
+        if let Some(rest) = rest.strip_prefix('\n') {
+            *remaining = rest;
+            return Ok(document.to_string());
+        }
+    }
+    invalid_value_for_type("DeviceSnapshot", length.to_string()).into()
+}
+
+fn parse_captured(value: &str) -> Result<Duration, Error> {
+    let (seconds, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return invalid_value_for_type("DeviceSnapshot", value).into();
+    }
+    let nanos = format!("{:0<9}", fraction);
+    Ok(Duration::new(
+        parse_number(seconds)? as u64,
+        parse_number(&nanos)? as u32,
+    ))
+}
+
+fn parse_number(value: &str) -> Result<usize, Error> {
+    match value.parse::<usize>() {
+        Ok(v) => Ok(v),
+        Err(_) => invalid_value_for_type("DeviceSnapshot", value).into(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let snapshot = DeviceSnapshot {
+            location: URL::from_str("http://10.0.0.1:49152/description.xml").unwrap(),
+            captured: UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789),
+            description: "<root>\nmulti-line\n</root>".to_string(),
+            services: vec![
+                ServiceSnapshot {
+                    service_id: ServiceId::new("ContentDirectory".to_string()),
+                    scpd_url: URL::from_str("http://10.0.0.1:49152/cd.xml").unwrap(),
+                    xml: "<scpd></scpd>".to_string(),
+                    state: Some(PropertySet::new().with_property("SystemUpdateID", "7")),
+                },
+                ServiceSnapshot {
+                    service_id: ServiceId::new("ConnectionManager".to_string()),
+                    scpd_url: URL::from_str("http://10.0.0.1:49152/cm.xml").unwrap(),
+                    xml: "<scpd></scpd>".to_string(),
+                    state: None,
+                },
+            ],
+        };
+        let mut archive = Vec::new();
+        snapshot.write_to(&mut archive).unwrap();
+
+        let read = DeviceSnapshot::read_from(archive.as_slice()).unwrap();
+        assert_eq!(read, snapshot);
+        assert!(read
+            .service(&ServiceId::new("ContentDirectory".to_string()))
+            .is_some());
+    }
+
+    #[test]
+    fn test_archive_whole_seconds() {
+        let read = DeviceSnapshot::read_from(
+            "UPNP-SNAPSHOT/1\nLOCATION: http://10.0.0.1/d.xml\nCAPTURED: 1600000000\nDESCRIPTION: 7\n<root/>\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            read.captured,
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+        );
+        assert_eq!(parse_captured("1.5").unwrap(), Duration::from_millis(1_500));
+        assert!(parse_captured("1.1234567891").is_err());
+        assert!(parse_captured("1.-5").is_err());
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_capture_state() {
+        use crate::common::http::default_client;
+        use crate::description::service::StateVariable;
+        use crate::emulation::{from_device_with, Options as EmulatorOptions};
+        use std::collections::HashMap;
+
+        const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><URLBase>http://10.0.0.1:49152/</URLBase><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType><friendlyName>Test</friendlyName><manufacturer>Test</manufacturer><modelName>Test</modelName><UDN>uuid:test-device</UDN><serviceList><service><serviceType>urn:schemas-upnp-org:service:Basic:1</serviceType><serviceId>urn:upnp-org:serviceId:Basic</serviceId><SCPDURL>/basic.xml</SCPDURL><controlURL>/control</controlURL><eventSubURL>/event</eventSubURL></service></serviceList></device></root>";
+        const SCPD: &str = "<?xml version=\"1.0\"?><scpd xmlns=\"urn:schemas-upnp-org:service-1-0\"><specVersion><major>1</major><minor>0</minor></specVersion><serviceStateTable></serviceStateTable></scpd>";
+
+        let mut scpd = Spcd::from_str(SCPD).unwrap();
+        scpd.service_state_table.push(StateVariable {
+            send_events: true,
+            multicast: false,
+            name: "Status".to_string(),
+            data_type: "boolean".to_string(),
+            default_value: Some("1".to_string()),
+            allowed_values: None,
+            moderation: None,
+        });
+        let mut services = HashMap::new();
+        let _ = services.insert(ServiceId::new("Basic".to_string()), scpd);
+        let device = from_device_with(
+            DeviceRoot::from_str(DESCRIPTION).unwrap(),
+            services,
+            EmulatorOptions {
+                answer_search: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let client = default_client();
+
+        let snapshot = capture_with(device.location(), client.as_ref()).unwrap();
+        assert_eq!(snapshot.services[0].state, None);
+
+        let snapshot = capture_with_options(
+            device.location(),
+            client.as_ref(),
+            &Options {
+                state_listener: Some("127.0.0.1:0".parse().unwrap()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let state = snapshot.services[0].state.as_ref().unwrap();
+        assert_eq!(state.get("Status"), Some("1"));
+    }
+
+    #[test]
+    fn test_archive_invalid() {
+        assert!(DeviceSnapshot::read_from("SOMETHING ELSE\n".as_bytes()).is_err());
+        assert!(DeviceSnapshot::read_from(
+            "UPNP-SNAPSHOT/1\nLOCATION: x\nCAPTURED: 0\nDESCRIPTION: 100\n<root/>\n".as_bytes()
+        )
+        .is_err());
+    }
+}
@@ -1,12 +1,135 @@
 /*!
-One-line description.
+Header names, method names, and other literal values used in SSDP, GENA, and SOAP messages.
 
-More detailed description, with
+The values are provided both as string constants and, for header names, `NTS` values, and
+methods, as the typed enums [`HeaderName`], [`Nts`], and [`Method`] which can be matched
+exhaustively.
 
 # Example
 
+```rust
+use upnp_rs::syntax::{HeaderName, Method, HTTP_HEADER_CACHE_CONTROL};
+use std::str::FromStr;
+
+assert_eq!(HeaderName::CacheControl.as_str(), HTTP_HEADER_CACHE_CONTROL);
+assert_eq!(HeaderName::from_str("cache-control").unwrap(), HeaderName::CacheControl);
+assert_eq!(Method::from_str("M-SEARCH").unwrap(), Method::Search);
+```
  */
 
+use crate::error::{invalid_field_value, invalid_header_value, MessageFormatError};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The name of a header used by SSDP, GENA, or SOAP messages; see the `HTTP_HEADER_`,
+/// `GENA_HEADER_`, and `SOAP_HTTP_HEADER_` constants for the meaning of each. Names are parsed
+/// case-insensitively, and displayed in upper case.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HeaderName {
+    /// `BOOTID.UPNP.ORG`
+    BootId,
+    /// `CACHE-CONTROL`
+    CacheControl,
+    /// `CONFIGID.UPNP.ORG`
+    ConfigId,
+    /// `CONTENT-LENGTH`
+    ContentLength,
+    /// `CONTENT-TYPE`
+    ContentType,
+    /// `CPFN.UPNP.ORG`
+    CpFriendlyName,
+    /// `CPUUID.UPNP.ORG`
+    CpUuid,
+    /// `DATE`
+    Date,
+    /// `EXT`
+    Ext,
+    /// `HOST`
+    Host,
+    /// `LOCATION`
+    Location,
+    /// `MAN`
+    Man,
+    /// `MX`
+    Mx,
+    /// `NEXTBOOTID.UPNP.ORG`
+    NextBootId,
+    /// `NT`
+    Nt,
+    /// `NTS`
+    Nts,
+    /// `SEARCHPORT.UPNP.ORG`
+    SearchPort,
+    /// `SECURELOCATION.UPNP.ORG`
+    SecureLocation,
+    /// `SERVER`
+    Server,
+    /// `ST`
+    St,
+    /// `TCPPORT.UPNP.ORG`
+    TcpPort,
+    /// `USER-AGENT`
+    UserAgent,
+    /// `USN`
+    Usn,
+    /// `SOAPACTION`
+    SoapAction,
+    /// `CALLBACK`
+    Callback,
+    /// `LVL`
+    Lvl,
+    /// `SEQ`
+    Seq,
+    /// `SID`
+    Sid,
+    /// `SVCID`
+    SvcId,
+    /// `TIMEOUT`
+    Timeout,
+}
+
+///
+/// The value of the `NTS` header, the notification sub type of a `NOTIFY` message.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Nts {
+    /// `ssdp:alive`
+    Alive,
+    /// `ssdp:byebye`
+    ByeBye,
+    /// `ssdp:update`
+    Update,
+    /// `upnp:propchange`
+    PropertyChange,
+}
+
+///
+/// An HTTP method used by SSDP, description, control, or GENA messages. Methods are case-sensitive.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// `NOTIFY`
+    Notify,
+    /// `M-SEARCH`
+    Search,
+    /// `GET`
+    Get,
+    /// `HEAD`
+    Head,
+    /// `POST`
+    Post,
+    /// `SUBSCRIBE`
+    Subscribe,
+    /// `UNSUBSCRIBE`
+    Unsubscribe,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Values -- Network
 // ------------------------------------------------------------------------------------------------
@@ -18,6 +141,12 @@ default SSDP port number of `1900`.
 */
 pub const MULTICAST_ADDRESS: &str = "239.255.255.250:1900";
 
+/**
+Multicast channel and port reserved for multicast eventing by _Internet Assigned Numbers Authority_
+(IANA), from UDA 1.1. Must be `239.255.255.246:7900`.
+*/
+pub const MULTICAST_EVENT_ADDRESS: &str = "239.255.255.246:7900";
+
 // ------------------------------------------------------------------------------------------------
 // Public Values -- HTTP (core)
 // ------------------------------------------------------------------------------------------------
@@ -57,6 +186,21 @@ From § 1.2 Discovery: Search
 */
 pub const HTTP_METHOD_SEARCH: &str = "M-SEARCH";
 
+/**
+Used to retrieve descriptions, and icons, and to probe a device's presence.
+*/
+pub const HTTP_METHOD_GET: &str = "GET";
+
+/**
+Used to probe a device's presence without retrieving its description.
+*/
+pub const HTTP_METHOD_HEAD: &str = "HEAD";
+
+/**
+From § 3.2 Control: Action
+*/
+pub const HTTP_METHOD_POST: &str = "POST";
+
 // ------------------------------------------------------------------------------------------------
 
 /**
@@ -91,6 +235,16 @@ when received.
 */
 pub const HTTP_HEADER_CACHE_CONTROL: &str = "CACHE-CONTROL";
 
+/**
+The media type of the message body, used when validating retrieved resources such as icons.
+*/
+pub const HTTP_HEADER_CONTENT_TYPE: &str = "CONTENT-TYPE";
+
+/**
+The length of the message body in bytes, required for messages with a body sent over UDP.
+*/
+pub const HTTP_HEADER_CONTENT_LENGTH: &str = "CONTENT-LENGTH";
+
 /**
 The CONFIGID.UPNP.ORG field value MUST be a non-negative, 31-bit integer, ASCII encoded, decimal,
 without leading zeros (leading zeroes, if present, MUST be ignored by the recipient) that MUST
@@ -254,6 +408,28 @@ double colon) MUST match the value of the UDN element in the device description.
 */
 pub const HTTP_HEADER_USN: &str = "USN";
 
+/**
+Not specified by the UDA. Sent by Microsoft, and many Linux, devices along with an `OPT` header
+declaring the `01` namespace. Field value is an identifier for the network location (NLS) that
+changes whenever the device restarts, used in a similar way to `BOOTID.UPNP.ORG`.
+*/
+pub const HTTP_HEADER_NLS: &str = "01-NLS";
+
+/**
+Not specified by the UDA. Sent by Sony AV devices. Field value is a list of `name=value` pairs
+separated by semicolons, for example `av=5.0; cn="Sony Corporation"; mn="BRAVIA KDL-40EX720"; mv="1.7"`,
+identifying the AV protocol version (`av`), company (`cn`), model name (`mn`), and model version
+(`mv`).
+*/
+pub const HTTP_HEADER_X_AV_SERVER_INFO: &str = "X-AV-SERVER-INFO";
+
+/**
+Not specified by the UDA. Sent by Sony AV devices. Field value is a list of `name=value` pairs
+separated by semicolons, for example `pa="BRAVIA KDL-40EX720"`, identifying the physical unit
+(`pa`) of which the device is part.
+*/
+pub const HTTP_HEADER_X_AV_PHYSICAL_UNIT_INFO: &str = "X-AV-PHYSICAL-UNIT-INFO";
+
 pub const NTS_ALIVE: &str = "ssdp:alive";
 
 pub const NTS_BYE: &str = "ssdp:byebye";
@@ -264,9 +440,11 @@ pub const NTS_UPDATE: &str = "ssdp:update";
 // Public Values -- XML (core)
 // ------------------------------------------------------------------------------------------------
 
-pub const XML_DECL_VERSION: &[u8] = b"1.0";
+pub const XML_DECL_VERSION: &str = "1.0";
 
 pub const XML_ATTR_NAMESPACE: &str = "xmlns";
+pub const XML_ATTR_ID: &str = "xml:id";
+pub const XML_ATTR_UNQUALIFIED_ID: &str = "id";
 
 // ------------------------------------------------------------------------------------------------
 // Public Values -- XML
@@ -275,66 +453,373 @@ pub const XML_ATTR_NAMESPACE: &str = "xmlns";
 pub const XML_NS_DEVICE: &str = "urn:schemas-upnp-org:device-1-0";
 pub const XML_NS_SERVICE: &str = "urn:schemas-upnp-org:service-1-0";
 
+pub const XML_ATTR_CONFIG_ID: &str = "configId";
 pub const XML_ATTR_SEND_EVENTS: &str = "sendEvents";
-
-pub const XML_ELEM_ACTION: &[u8] = b"action";
-pub const XML_ELEM_ACTION_LIST: &[u8] = b"actionList";
-pub const XML_ELEM_ARGUMENT: &[u8] = b"argument";
-pub const XML_ELEM_ARGUMENT_LIST: &[u8] = b"argumentList";
-pub const XML_ELEM_ALLOWED_LIST: &[u8] = b"allowedValueList";
-pub const XML_ELEM_ALLOWED_RANGE: &[u8] = b"allowedValueRange";
-pub const XML_ELEM_ALLOWED_VALUE: &[u8] = b"allowedValue";
-pub const XML_ELEM_DATA_TYPE: &[u8] = b"dataType";
-pub const XML_ELEM_DEFAULT_VALUE: &[u8] = b"defaultValue";
-pub const XML_ELEM_DEVICE: &[u8] = b"device";
-pub const XML_ELEM_DEVICE_LIST: &[u8] = b"deviceList";
-pub const XML_ELEM_DEVICE_TYPE: &[u8] = b"deviceType";
-pub const XML_ELEM_DIRECTION: &[u8] = b"direction";
-pub const XML_ELEM_FRIENDLY_NAME: &[u8] = b"friendlyName";
-pub const XML_ELEM_ICON: &[u8] = b"icon";
-pub const XML_ELEM_ICON_DEPTH: &[u8] = b"depth";
-pub const XML_ELEM_ICON_HEIGHT: &[u8] = b"height";
-pub const XML_ELEM_ICON_LIST: &[u8] = b"iconList";
-pub const XML_ELEM_ICON_MIME_TYPE: &[u8] = b"mimetype";
-pub const XML_ELEM_ICON_URL: &[u8] = b"url";
-pub const XML_ELEM_ICON_WIDTH: &[u8] = b"width";
-pub const XML_ELEM_MANUFACTURER: &[u8] = b"manufacturer";
-pub const XML_ELEM_MANUFACTURER_URL: &[u8] = b"manufacturerURL";
-pub const XML_ELEM_MAJOR: &[u8] = b"major";
-pub const XML_ELEM_MAXIMUM: &[u8] = b"maximum";
-pub const XML_ELEM_MINIMUM: &[u8] = b"minimum";
-pub const XML_ELEM_MINOR: &[u8] = b"minor";
-pub const XML_ELEM_MODEL_DESCR: &[u8] = b"modelDescription";
-pub const XML_ELEM_MODEL_NAME: &[u8] = b"modelName";
-pub const XML_ELEM_MODEL_NUMBER: &[u8] = b"modelNumber";
-pub const XML_ELEM_MODEL_URL: &[u8] = b"modelURL";
-pub const XML_ELEM_NAME: &[u8] = b"name";
-pub const XML_ELEM_PRESENTATION_URL: &[u8] = b"presentationURL";
-pub const XML_ELEM_RETVAL: &[u8] = b"retval";
-pub const XML_ELEM_ROOT: &[u8] = b"root";
-pub const XML_ELEM_SERIAL_NUMBER: &[u8] = b"serialNumber";
-pub const XML_ELEM_SERVICE: &[u8] = b"service";
-pub const XML_ELEM_SERVICE_CONTROL_URL: &[u8] = b"controlURL";
-pub const XML_ELEM_SERVICE_EVENT_URL: &[u8] = b"eventSubURL";
-pub const XML_ELEM_SERVICE_ID: &[u8] = b"serviceId";
-pub const XML_ELEM_SERVICE_LIST: &[u8] = b"serviceList";
-pub const XML_ELEM_SERVICE_SCPD_URL: &[u8] = b"SCPDURL";
-pub const XML_ELEM_SERVICE_TYPE: &[u8] = b"serviceType";
-pub const XML_ELEM_REL_STATE_VARIABLE: &[u8] = b"relatedStateVariable";
-pub const XML_ELEM_SPCD: &[u8] = b"spcd";
-pub const XML_ELEM_SPEC_VERSION: &[u8] = b"specVersion";
-pub const XML_ELEM_STATE_TABLE: &[u8] = b"serviceStateTable";
-pub const XML_ELEM_STATE_VARIABLE: &[u8] = b"stateVariable";
-pub const XML_ELEM_STEP: &[u8] = b"step";
-pub const XML_ELEM_UDN: &[u8] = b"UDN";
-pub const XML_ELEM_UPC: &[u8] = b"UPC";
-pub const XML_ELEM_URL_BASE: &[u8] = b"URLBase";
+pub const XML_ATTR_MULTICAST: &str = "multicast";
+
+pub const XML_ELEM_ACTION: &str = "action";
+pub const XML_ELEM_ACTION_LIST: &str = "actionList";
+pub const XML_ELEM_ARGUMENT: &str = "argument";
+pub const XML_ELEM_ARGUMENT_LIST: &str = "argumentList";
+pub const XML_ELEM_ALLOWED_LIST: &str = "allowedValueList";
+pub const XML_ELEM_ALLOWED_RANGE: &str = "allowedValueRange";
+pub const XML_ELEM_ALLOWED_VALUE: &str = "allowedValue";
+pub const XML_ELEM_DATA_TYPE: &str = "dataType";
+pub const XML_ELEM_DEFAULT_VALUE: &str = "defaultValue";
+pub const XML_ELEM_DEVICE: &str = "device";
+pub const XML_ELEM_DEVICE_LIST: &str = "deviceList";
+pub const XML_ELEM_DEVICE_TYPE: &str = "deviceType";
+pub const XML_ELEM_DIRECTION: &str = "direction";
+pub const XML_ELEM_FRIENDLY_NAME: &str = "friendlyName";
+pub const XML_ELEM_ICON: &str = "icon";
+pub const XML_ELEM_ICON_DEPTH: &str = "depth";
+pub const XML_ELEM_ICON_HEIGHT: &str = "height";
+pub const XML_ELEM_ICON_LIST: &str = "iconList";
+pub const XML_ELEM_ICON_MIME_TYPE: &str = "mimetype";
+pub const XML_ELEM_ICON_URL: &str = "url";
+pub const XML_ELEM_ICON_WIDTH: &str = "width";
+pub const XML_ELEM_MANUFACTURER: &str = "manufacturer";
+pub const XML_ELEM_MANUFACTURER_URL: &str = "manufacturerURL";
+pub const XML_ELEM_MAJOR: &str = "major";
+pub const XML_ELEM_MAXIMUM: &str = "maximum";
+pub const XML_ELEM_MAXIMUM_RATE: &str = "maximumRate";
+pub const XML_ELEM_MINIMUM: &str = "minimum";
+pub const XML_ELEM_MINIMUM_DELTA: &str = "minimumDelta";
+pub const XML_ELEM_MINOR: &str = "minor";
+pub const XML_ELEM_MODEL_DESCR: &str = "modelDescription";
+pub const XML_ELEM_MODEL_NAME: &str = "modelName";
+pub const XML_ELEM_MODEL_NUMBER: &str = "modelNumber";
+pub const XML_ELEM_MODEL_URL: &str = "modelURL";
+pub const XML_ELEM_NAME: &str = "name";
+pub const XML_ELEM_PRESENTATION_URL: &str = "presentationURL";
+pub const XML_ELEM_RETVAL: &str = "retval";
+pub const XML_ELEM_ROOT: &str = "root";
+pub const XML_ELEM_SERIAL_NUMBER: &str = "serialNumber";
+pub const XML_ELEM_SERVICE: &str = "service";
+pub const XML_ELEM_SERVICE_CONTROL_URL: &str = "controlURL";
+pub const XML_ELEM_SERVICE_EVENT_URL: &str = "eventSubURL";
+pub const XML_ELEM_SERVICE_ID: &str = "serviceId";
+pub const XML_ELEM_SERVICE_LIST: &str = "serviceList";
+pub const XML_ELEM_SERVICE_SCPD_URL: &str = "SCPDURL";
+pub const XML_ELEM_SERVICE_TYPE: &str = "serviceType";
+pub const XML_ELEM_REL_STATE_VARIABLE: &str = "relatedStateVariable";
+pub const XML_ELEM_SPCD: &str = "scpd";
+pub const XML_ELEM_SPEC_VERSION: &str = "specVersion";
+pub const XML_ELEM_STATE_TABLE: &str = "serviceStateTable";
+pub const XML_ELEM_STATE_VARIABLE: &str = "stateVariable";
+pub const XML_ELEM_STEP: &str = "step";
+pub const XML_ELEM_UDN: &str = "UDN";
+pub const XML_ELEM_UPC: &str = "UPC";
+pub const XML_ELEM_URL_BASE: &str = "URLBase";
+
+///
+/// The largest value permitted for the `configId` attribute, and the `CONFIGID.UPNP.ORG` header;
+/// values from 2^24 to 2^31-1 are reserved for future use.
+///
+pub const MAX_CONFIG_ID: u32 = 16_777_215;
+
+///
+/// The range from which `SEARCHPORT.UPNP.ORG` and `TCPPORT.UPNP.ORG` values must be chosen.
+///
+pub const DYNAMIC_PORTS: std::ops::RangeInclusive<u16> = 49152..=65535;
 
 // ------------------------------------------------------------------------------------------------
 // Public Values -- SOAP
 // ------------------------------------------------------------------------------------------------
 
-pub const SOAP_NS_ENVELOPE: &str = "http://schemas.xmlsoap.org/common.soap/envelope/";
-pub const SOAP_NS_ENCODING: &str = "http://schemas.xmlsoap.org/common.soap/encoding/";
+pub const SOAP_NS_ENVELOPE: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+pub const SOAP_NS_ENCODING: &str = "http://schemas.xmlsoap.org/soap/encoding/";
+pub const SOAP_NS_CONTROL: &str = "urn:schemas-upnp-org:control-1-0";
+
+pub const SOAP_HTTP_HEADER_ACTION: &str = "SOAPACTION";
+pub const SOAP_HTTP_CONTENT_TYPE: &str = "text/xml; charset=\"utf-8\"";
+
+pub const SOAP_PREFIX_ENVELOPE: &str = "s";
+pub const SOAP_PREFIX_ACTION: &str = "u";
+
+pub const SOAP_ATTR_ENCODING_STYLE: &str = "s:encodingStyle";
+
+pub const SOAP_ELEM_ENVELOPE: &str = "s:Envelope";
+pub const SOAP_ELEM_BODY: &str = "s:Body";
+pub const SOAP_ELEM_FAULT: &str = "s:Fault";
+pub const SOAP_ELEM_FAULT_CODE: &str = "faultcode";
+pub const SOAP_ELEM_FAULT_STRING: &str = "faultstring";
+pub const SOAP_ELEM_DETAIL: &str = "detail";
+pub const SOAP_ELEM_UPNP_ERROR: &str = "UPnPError";
+pub const SOAP_ELEM_ERROR_CODE: &str = "errorCode";
+pub const SOAP_ELEM_ERROR_DESCRIPTION: &str = "errorDescription";
+
+pub const SOAP_FAULT_CODE_CLIENT: &str = "s:Client";
+pub const SOAP_FAULT_STRING_UPNP: &str = "UPnPError";
+pub const SOAP_ACTION_RESPONSE_SUFFIX: &str = "Response";
+
+// ------------------------------------------------------------------------------------------------
+// Public Values -- GENA
+// ------------------------------------------------------------------------------------------------
+
+pub const GENA_METHOD_SUBSCRIBE: &str = "SUBSCRIBE";
+pub const GENA_METHOD_UNSUBSCRIBE: &str = "UNSUBSCRIBE";
+
+pub const GENA_HEADER_CALLBACK: &str = "CALLBACK";
+pub const GENA_HEADER_SEQ: &str = "SEQ";
+pub const GENA_HEADER_SID: &str = "SID";
+pub const GENA_HEADER_SVCID: &str = "SVCID";
+pub const GENA_HEADER_LVL: &str = "LVL";
+pub const GENA_HEADER_TIMEOUT: &str = "TIMEOUT";
+
+pub const GENA_NT_EVENT: &str = "upnp:event";
+pub const GENA_NTS_PROPERTY_CHANGE: &str = "upnp:propchange";
+
+pub const GENA_TIMEOUT_PREFIX: &str = "Second-";
+pub const GENA_TIMEOUT_INFINITE: &str = "Second-infinite";
+
+pub const GENA_NS_EVENT: &str = "urn:schemas-upnp-org:event-1-0";
+
+pub const GENA_PREFIX_EVENT: &str = "e";
+
+pub const GENA_ELEM_PROPERTY_SET: &str = "e:propertyset";
+pub const GENA_ELEM_PROPERTY: &str = "e:property";
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HeaderName {
+    ///
+    /// All the values of this type.
+    ///
+    pub const ALL: &'static [HeaderName] = &[
+        HeaderName::BootId,
+        HeaderName::CacheControl,
+        HeaderName::ConfigId,
+        HeaderName::ContentLength,
+        HeaderName::ContentType,
+        HeaderName::CpFriendlyName,
+        HeaderName::CpUuid,
+        HeaderName::Date,
+        HeaderName::Ext,
+        HeaderName::Host,
+        HeaderName::Location,
+        HeaderName::Man,
+        HeaderName::Mx,
+        HeaderName::NextBootId,
+        HeaderName::Nt,
+        HeaderName::Nts,
+        HeaderName::SearchPort,
+        HeaderName::SecureLocation,
+        HeaderName::Server,
+        HeaderName::St,
+        HeaderName::TcpPort,
+        HeaderName::UserAgent,
+        HeaderName::Usn,
+        HeaderName::SoapAction,
+        HeaderName::Callback,
+        HeaderName::Lvl,
+        HeaderName::Seq,
+        HeaderName::Sid,
+        HeaderName::SvcId,
+        HeaderName::Timeout,
+    ];
+
+    ///
+    /// The value as it appears in a message.
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HeaderName::BootId => HTTP_HEADER_BOOTID,
+            HeaderName::CacheControl => HTTP_HEADER_CACHE_CONTROL,
+            HeaderName::ConfigId => HTTP_HEADER_CONFIGID,
+            HeaderName::ContentLength => HTTP_HEADER_CONTENT_LENGTH,
+            HeaderName::ContentType => HTTP_HEADER_CONTENT_TYPE,
+            HeaderName::CpFriendlyName => HTTP_HEADER_CP_FN,
+            HeaderName::CpUuid => HTTP_HEADER_CP_UUID,
+            HeaderName::Date => HTTP_HEADER_DATE,
+            HeaderName::Ext => HTTP_HEADER_EXT,
+            HeaderName::Host => HTTP_HEADER_HOST,
+            HeaderName::Location => HTTP_HEADER_LOCATION,
+            HeaderName::Man => HTTP_HEADER_MAN,
+            HeaderName::Mx => HTTP_HEADER_MX,
+            HeaderName::NextBootId => HTTP_HEADER_NEXT_BOOTID,
+            HeaderName::Nt => HTTP_HEADER_NT,
+            HeaderName::Nts => HTTP_HEADER_NTS,
+            HeaderName::SearchPort => HTTP_HEADER_SEARCH_PORT,
+            HeaderName::SecureLocation => HTTP_HEADER_SECURE_LOCATION,
+            HeaderName::Server => HTTP_HEADER_SERVER,
+            HeaderName::St => HTTP_HEADER_ST,
+            HeaderName::TcpPort => HTTP_HEADER_TCP_PORT,
+            HeaderName::UserAgent => HTTP_HEADER_USER_AGENT,
+            HeaderName::Usn => HTTP_HEADER_USN,
+            HeaderName::SoapAction => SOAP_HTTP_HEADER_ACTION,
+            HeaderName::Callback => GENA_HEADER_CALLBACK,
+            HeaderName::Lvl => GENA_HEADER_LVL,
+            HeaderName::Seq => GENA_HEADER_SEQ,
+            HeaderName::Sid => GENA_HEADER_SID,
+            HeaderName::SvcId => GENA_HEADER_SVCID,
+            HeaderName::Timeout => GENA_HEADER_TIMEOUT,
+        }
+    }
+}
+
+impl Display for HeaderName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl AsRef<str> for HeaderName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl FromStr for HeaderName {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HeaderName::ALL
+            .iter()
+            .find(|value| s.eq_ignore_ascii_case(value.as_str()))
+            .copied()
+            .ok_or_else(|| invalid_header_value("header", s))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Nts {
+    ///
+    /// All the values of this type.
+    ///
+    pub const ALL: &'static [Nts] = &[Nts::Alive, Nts::ByeBye, Nts::Update, Nts::PropertyChange];
+
+    ///
+    /// The value as it appears in a message.
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Nts::Alive => NTS_ALIVE,
+            Nts::ByeBye => NTS_BYE,
+            Nts::Update => NTS_UPDATE,
+            Nts::PropertyChange => GENA_NTS_PROPERTY_CHANGE,
+        }
+    }
+}
+
+impl Display for Nts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl AsRef<str> for Nts {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl FromStr for Nts {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Nts::ALL
+            .iter()
+            .find(|value| value.as_str() == s)
+            .copied()
+            .ok_or_else(|| invalid_header_value(HTTP_HEADER_NTS, s))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Method {
+    ///
+    /// All the values of this type.
+    ///
+    pub const ALL: &'static [Method] = &[
+        Method::Notify,
+        Method::Search,
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Subscribe,
+        Method::Unsubscribe,
+    ];
+
+    ///
+    /// The value as it appears in a message.
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Notify => HTTP_METHOD_NOTIFY,
+            Method::Search => HTTP_METHOD_SEARCH,
+            Method::Get => HTTP_METHOD_GET,
+            Method::Head => HTTP_METHOD_HEAD,
+            Method::Post => HTTP_METHOD_POST,
+            Method::Subscribe => GENA_METHOD_SUBSCRIBE,
+            Method::Unsubscribe => GENA_METHOD_UNSUBSCRIBE,
+        }
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl AsRef<str> for Method {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl FromStr for Method {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Method::ALL
+            .iter()
+            .find(|value| value.as_str() == s)
+            .copied()
+            .ok_or_else(|| invalid_field_value("method", s))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
 
-pub const SOAP_HTTP_HEADER_ACTION: &[u8] = b"SOAPACTION";
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_all() {
+        for name in HeaderName::ALL {
+            assert_eq!(&HeaderName::from_str(name.as_str()).unwrap(), name);
+        }
+        for nts in Nts::ALL {
+            assert_eq!(&Nts::from_str(nts.as_ref()).unwrap(), nts);
+        }
+        for method in Method::ALL {
+            assert_eq!(&Method::from_str(method.as_ref()).unwrap(), method);
+        }
+    }
+
+    #[test]
+    fn test_header_names_ignore_case() {
+        assert_eq!(HeaderName::from_str("usn").unwrap(), HeaderName::Usn);
+        assert_eq!(
+            HeaderName::from_str("BootId.UPnP.Org").unwrap(),
+            HeaderName::BootId
+        );
+        assert!(HeaderName::from_str("X-UNKNOWN").is_err());
+    }
+
+    #[test]
+    fn test_methods_match_case() {
+        assert_eq!(Method::from_str("NOTIFY").unwrap(), Method::Notify);
+        assert!(Method::from_str("notify").is_err());
+        assert!(Nts::from_str("ssdp:unknown").is_err());
+    }
+}